@@ -0,0 +1,228 @@
+#![allow(clippy::module_name_repetitions)]
+
+pub mod session;
+pub mod types;
+
+use anyhow::{format_err, Error};
+use reqwest::{header::COOKIE, Client, RequestBuilder, Response, StatusCode, Url};
+use serde::Deserialize;
+use serde_json::Value;
+use stack_string::{format_sstr, StackString};
+use std::{fmt, path::Path};
+
+pub use session::{device_login, DeviceAuthorization, RemoteSession};
+pub use types::{PriceHistoryEntry, SpotForecast, SpotRequest};
+
+/// Header carrying the csrf token of the session on mutating requests
+pub const CSRF_HEADER: &str = "x-csrf-token";
+/// Header making a mutating request safe to retry
+pub const IDEMPOTENCY_HEADER: &str = "idempotency-key";
+
+/// Typed client for the aws_app_http api, authenticated with the session of
+/// the device login flow
+#[derive(Clone)]
+pub struct AwsAppClient {
+    client: Client,
+    base_url: Url,
+    session: RemoteSession,
+}
+
+impl fmt::Debug for AwsAppClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AwsAppClient({})", self.base_url)
+    }
+}
+
+impl AwsAppClient {
+    /// # Errors
+    /// Returns error if `base_url` is invalid
+    pub fn new(base_url: &str, session: RemoteSession) -> Result<Self, Error> {
+        Ok(Self {
+            client: Client::new(),
+            base_url: base_url.parse()?,
+            session,
+        })
+    }
+
+    /// Client using the session saved by `device_login` at `session_path`
+    /// # Errors
+    /// Returns error if `base_url` is invalid or there is no saved session
+    pub async fn from_session_file(base_url: &str, session_path: &Path) -> Result<Self, Error> {
+        let session = RemoteSession::read(session_path).await?;
+        Self::new(base_url, session)
+    }
+
+    fn cookie(&self) -> StackString {
+        format_sstr!(
+            "jwt={}; session-id={}",
+            self.session.jwt,
+            self.session.session_id
+        )
+    }
+
+    fn check_status(response: Response) -> Result<Response, Error> {
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Err(format_err!(
+                "Session expired, run `aws-app-rust login` again"
+            ));
+        }
+        response.error_for_status().map_err(Into::into)
+    }
+
+    fn get(&self, path: &str) -> Result<RequestBuilder, Error> {
+        let url = self.base_url.join(path)?;
+        Ok(self.client.get(url).header(COOKIE, self.cookie().as_str()))
+    }
+
+    /// Request carrying the csrf token every mutating request needs
+    async fn mutating(&self, builder: RequestBuilder) -> Result<RequestBuilder, Error> {
+        let csrf_token = self.csrf_token().await?;
+        Ok(builder
+            .header(COOKIE, self.cookie().as_str())
+            .header(CSRF_HEADER, csrf_token.as_str()))
+    }
+
+    /// Token that has to accompany every mutating request in this session
+    /// # Errors
+    /// Returns error if api call fails
+    pub async fn csrf_token(&self) -> Result<StackString, Error> {
+        #[derive(Deserialize)]
+        struct CsrfToken {
+            csrf_token: StackString,
+        }
+
+        let response = self.get("aws/api/csrf_token")?.send().await?;
+        let token: CsrfToken = Self::check_status(response)?.json().await?;
+        Ok(token.csrf_token)
+    }
+
+    /// Returns `{"resource": ..., "items": [...]}`, `resource` is one of the
+    /// names accepted by `aws-app-rust list -r`
+    /// # Errors
+    /// Returns error if api call fails
+    pub async fn list(&self, resource: &str) -> Result<Value, Error> {
+        let response = self
+            .get("aws/api/list")?
+            .query(&[("resource", resource)])
+            .send()
+            .await?;
+        Self::check_status(response)?
+            .json()
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Request spot instances, the progress of the launch is shown on
+    /// `/aws/launch_status`. With an `idempotency_key` a retried request
+    /// isn't submitted twice.
+    /// # Errors
+    /// Returns error if api call fails or the server rejects the request
+    pub async fn request_spot(
+        &self,
+        request: &SpotRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<(), Error> {
+        let url = self.base_url.join("aws/request_spot")?;
+        let mut builder = self.mutating(self.client.post(url).json(request)).await?;
+        if let Some(key) = idempotency_key {
+            builder = builder.header(IDEMPOTENCY_HEADER, key);
+        }
+        Self::check_status(builder.send().await?)?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if api call fails
+    pub async fn cancel_spot(&self, spot_id: &str) -> Result<(), Error> {
+        let url = self.base_url.join("aws/cancel_spot")?;
+        let builder = self.client.delete(url).query(&[("spot_id", spot_id)]);
+        let response = self.mutating(builder).await?.send().await?;
+        Self::check_status(response)?;
+        Ok(())
+    }
+
+    /// `instance` is an instance id or name tag
+    /// # Errors
+    /// Returns error if api call fails
+    pub async fn terminate(&self, instance: &str) -> Result<(), Error> {
+        let url = self.base_url.join("aws/terminate")?;
+        let builder = self.client.delete(url).query(&[("instance", instance)]);
+        let response = self.mutating(builder).await?.send().await?;
+        Self::check_status(response)?;
+        Ok(())
+    }
+
+    /// Forecasts of the instance types starting with `search`, all recorded
+    /// types if `None`
+    /// # Errors
+    /// Returns error if api call fails
+    pub async fn spot_forecast(&self, search: Option<&str>) -> Result<Vec<SpotForecast>, Error> {
+        let mut builder = self.get("aws/api/spot_forecast")?;
+        if let Some(search) = search {
+            builder = builder.query(&[("search", search)]);
+        }
+        Self::check_status(builder.send().await?)?
+            .json()
+            .await
+            .map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if api call fails
+    pub async fn price_history(
+        &self,
+        instance_type: &str,
+    ) -> Result<Vec<PriceHistoryEntry>, Error> {
+        let response = self
+            .get("aws/api/price_history")?
+            .query(&[("instance_type", instance_type)])
+            .send()
+            .await?;
+        Self::check_status(response)?
+            .json()
+            .await
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use serde_json::json;
+
+    use crate::{types::PriceHistoryEntry, SpotRequest};
+
+    #[test]
+    fn test_spot_request_json() -> Result<(), Error> {
+        let mut request = SpotRequest::new("ami-0001", "m5.large", "0.05", "worker");
+        request.count = Some(2);
+        assert_eq!(
+            serde_json::to_value(&request)?,
+            json!({
+                "ami": "ami-0001",
+                "instance_type": "m5.large",
+                "security_group": "",
+                "script": "",
+                "key_name": "",
+                "price": "0.05",
+                "name": "worker",
+                "count": 2,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_price_history_entry() -> Result<(), Error> {
+        let entry: PriceHistoryEntry = serde_json::from_value(json!({
+            "instance_type": "m5.large",
+            "price_type": "spot",
+            "price": 0.031,
+            "price_timestamp": "2024-06-01T00:00:00Z",
+            "recorded_at": "2024-06-01T00:05:00Z",
+        }))?;
+        assert_eq!(entry.price_type, "spot");
+        assert_eq!(entry.recorded_at.minute(), 5);
+        Ok(())
+    }
+}
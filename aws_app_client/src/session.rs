@@ -0,0 +1,145 @@
+use anyhow::{format_err, Error};
+use log::debug;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::{fmt, os::unix::fs::PermissionsExt, path::Path, time::Duration};
+use tokio::{fs, time::sleep};
+use uuid::Uuid;
+
+/// Response to `POST {auth_url}/api/auth/device`
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: StackString,
+    pub user_code: StackString,
+    pub verification_uri: StackString,
+    pub expires_in: u64,
+    #[serde(default = "default_poll_interval")]
+    pub interval: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    5
+}
+
+/// Response to `POST {auth_url}/api/auth/device/token`, `error` is one of
+/// the RFC 8628 codes while the user has not yet approved the request
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum DeviceTokenResponse {
+    Session(RemoteSession),
+    Error { error: StackString },
+}
+
+/// Credentials obtained from the auth service, sent to aws_app_http as the
+/// same `jwt` and `session-id` cookies a browser would use
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteSession {
+    pub jwt: StackString,
+    pub session_id: Uuid,
+}
+
+impl fmt::Debug for RemoteSession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RemoteSession(session_id={})", self.session_id)
+    }
+}
+
+impl RemoteSession {
+    /// # Errors
+    /// Returns error if session file is missing or invalid
+    pub async fn read(path: &Path) -> Result<Self, Error> {
+        if !path.exists() {
+            return Err(format_err!("Not logged in, run `aws-app-rust login` first"));
+        }
+        let data = fs::read(path).await?;
+        serde_json::from_slice(&data).map_err(Into::into)
+    }
+
+    /// Write session to `path`, readable only by the current user
+    /// # Errors
+    /// Returns error if write fails
+    pub async fn write(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(path, serde_json::to_vec(self)?).await?;
+        fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await?;
+        Ok(())
+    }
+}
+
+/// Run the OAuth2 device authorization flow against the auth service at
+/// `auth_url` and save the session to `session_path`, `prompt` is called
+/// with the url and code the user must enter
+/// # Errors
+/// Returns error if `auth_url` is invalid, the request is denied or expires
+pub async fn device_login(
+    auth_url: &str,
+    session_path: &Path,
+    prompt: impl Fn(&DeviceAuthorization),
+) -> Result<RemoteSession, Error> {
+    let auth_url: Url = auth_url.parse()?;
+    let client = Client::new();
+
+    let authorization: DeviceAuthorization = client
+        .post(auth_url.join("api/auth/device")?)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    prompt(&authorization);
+
+    let token_url = auth_url.join("api/auth/device/token")?;
+    let mut interval = authorization.interval;
+    let mut remaining = authorization.expires_in;
+    while remaining > 0 {
+        sleep(Duration::from_secs(interval)).await;
+        remaining = remaining.saturating_sub(interval);
+        let response: DeviceTokenResponse = client
+            .post(token_url.clone())
+            .json(&serde_json::json!({"device_code": authorization.device_code}))
+            .send()
+            .await?
+            .json()
+            .await?;
+        match response {
+            DeviceTokenResponse::Session(session) => {
+                session.write(session_path).await?;
+                return Ok(session);
+            }
+            DeviceTokenResponse::Error { error } => match error.as_str() {
+                "authorization_pending" => debug!("waiting for approval"),
+                "slow_down" => interval += 5,
+                _ => return Err(format_err!("Device login failed: {error}")),
+            },
+        }
+    }
+    Err(format_err!("Device code expired"))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::session::{DeviceTokenResponse, RemoteSession};
+
+    #[test]
+    fn test_device_token_response() -> Result<(), Error> {
+        let pending: DeviceTokenResponse =
+            serde_json::from_str(r#"{"error": "authorization_pending"}"#)?;
+        assert!(matches!(
+            pending,
+            DeviceTokenResponse::Error { error } if error == "authorization_pending"
+        ));
+        let session: DeviceTokenResponse = serde_json::from_str(
+            r#"{"jwt": "abc", "session_id": "8b1a9953-c461-4b4e-8f11-1e3c8a1a8a3b"}"#,
+        )?;
+        assert!(matches!(
+            session,
+            DeviceTokenResponse::Session(RemoteSession { jwt, .. }) if jwt == "abc"
+        ));
+        Ok(())
+    }
+}
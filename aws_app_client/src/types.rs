@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use time::OffsetDateTime;
+
+/// Body of `POST /aws/request_spot`, optional fields left unset take the
+/// server's defaults
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SpotRequest {
+    pub ami: StackString,
+    pub instance_type: StackString,
+    pub security_group: StackString,
+    /// Script filename on the server, run by cloud-init
+    pub script: StackString,
+    pub key_name: StackString,
+    /// Maximum spot price (USD/hr)
+    pub price: StackString,
+    /// Name tag of the instances
+    pub name: StackString,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_instance_types: Option<Vec<StackString>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub availability_zones: Option<Vec<StackString>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_total_price: Option<StackString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_recover: Option<bool>,
+    /// `default` or `dedicated`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenancy: Option<StackString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ebs_optimized: Option<bool>,
+    /// Root volume size (GB)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_volume_size: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_volume_type: Option<StackString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_volume_iops: Option<i32>,
+    /// Data volumes as `DEVICE:SIZE[:TYPE][:keep]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_volumes: Option<Vec<StackString>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance_profile: Option<StackString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_imdsv2: Option<bool>,
+    /// Route53 host name to point at the instance once it is running
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_name: Option<StackString>,
+}
+
+impl SpotRequest {
+    /// Request with the fields the server requires, the rest unset
+    #[must_use]
+    pub fn new(
+        ami: impl Into<StackString>,
+        instance_type: impl Into<StackString>,
+        price: impl Into<StackString>,
+        name: impl Into<StackString>,
+    ) -> Self {
+        Self {
+            ami: ami.into(),
+            instance_type: instance_type.into(),
+            price: price.into(),
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Entry of `GET /aws/api/spot_forecast`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SpotForecast {
+    pub instance_type: StackString,
+    pub samples: usize,
+    /// Latest spot price (USD/hr)
+    pub current: f64,
+    pub mean: f64,
+    /// Mean over the last day (USD/hr)
+    pub rolling_mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub max: f64,
+    /// Standard deviation relative to the mean
+    pub volatility: f64,
+    pub ondemand_price: Option<f64>,
+    /// `low`, `medium`, `high` or `unknown`
+    pub risk: StackString,
+    /// Suggested maximum spot price (USD/hr)
+    pub suggested_bid: f64,
+}
+
+/// Entry of `GET /aws/api/price_history`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PriceHistoryEntry {
+    pub instance_type: StackString,
+    /// `ondemand`, `reserved` or `spot`
+    pub price_type: StackString,
+    /// Price (USD/hr)
+    pub price: f64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub price_timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub recorded_at: OffsetDateTime,
+}
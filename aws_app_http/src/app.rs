@@ -1,44 +1,145 @@
 use anyhow::Error;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Server,
+};
+use log::{debug, error, info, warn};
+use parking_lot::RwLock;
 use rweb::{
-    filters::BoxedFilter,
+    filters::{log::custom, query::query, BoxedFilter},
     http::header::CONTENT_TYPE,
     openapi::{self, Info},
-    Filter, Reply,
+    Filter, Rejection, Reply,
 };
+use serde::Deserialize;
 use stack_string::format_sstr;
-use std::{net::SocketAddr, sync::Arc, time::Duration};
-use tokio::{task::spawn, time::interval};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+use time::OffsetDateTime;
+use tokio::{
+    select,
+    signal::{
+        ctrl_c,
+        unix::{signal, SignalKind},
+    },
+    task::spawn,
+    time::{interval, sleep},
+};
 
 use aws_app_lib::{
-    aws_app_interface::AwsAppInterface, config::Config, novnc_instance::NoVncInstance,
+    aws_app_interface::{AwsAppInterface, INSTANCE_LIST},
+    config::Config,
+    cron_schedule::CronSchedule,
+    ddns::update_ddns_records,
+    novnc_instance::NoVncInstance,
     pgpool::PgPool,
+    resource_type::ResourceType,
+    ses_client::SesInstance,
+    storage::{open_storage, Storage},
+    telemetry::init_tracing,
+    webhook::{self, ResourceStates},
 };
 
 use super::{
-    errors::error_response,
-    logged_user::{fill_from_db, get_secrets},
+    csrf::csrf_filter,
+    errors::{error_response, ServiceError},
+    events::{event_stream_path, inbound_email_notify_path, sns_events_path},
+    file_transfer::{
+        attachment_download_path, create_key_pair_path, download_path, export_path, graph_path,
+        s3_upload_path, upload_path, zone_export_path,
+    },
+    graphql::graphql_path,
+    logged_user::{fill_from_db, get_secrets, LoggedUser},
+    metrics::{get_metrics, record_background_task, record_request},
+    rate_limit::rate_limit_filter,
+    request_id::traced_request,
     routes::{
-        add_user_to_group, build_spot_request, cancel_spot, cleanup_ecr_images, command,
-        create_access_key, create_image, create_snapshot, create_user, crontab_logs,
-        delete_access_key, delete_ecr_image, delete_image, delete_script, delete_snapshot,
-        delete_user, delete_volume, edit_script, get_instances, get_prices, inbound_email_delete,
-        inbound_email_detail, instance_status, list, modify_volume, novnc_launcher, novnc_shutdown,
-        novnc_status, remove_user_from_group, replace_script, request_spot, sync_frontpage,
-        sync_inboud_email, systemd_action, systemd_logs, systemd_restart_all, tag_item, terminate,
-        update, update_dns_name, user,
+        add_user_to_group, auto_recover, backup_assign, batch_delete_snapshot, batch_delete_volume,
+        batch_tag, batch_terminate, bucket_summary, build_spot_request, cancel_replacement,
+        cancel_spot, cleanup_ecr_images, command, copy_snapshot, costs_by_tag, create_access_key,
+        create_dr_policy, create_health_check, create_image, create_price_alert,
+        create_routing_record, create_snapshot, create_user, create_webhook, crontab_logs,
+        crontab_preview, dashboard, decommission, decommission_plan, delete_access_key,
+        delete_dr_policy, delete_ecr_image, delete_email_rule, delete_health_check, delete_image,
+        delete_key_pair, delete_orphaned_attachments, delete_price_alert, delete_script,
+        delete_snapshot, delete_user, delete_volume, delete_webhook, docker_action, docker_logs,
+        docker_pull, dr_policies, ecr_history, ecs_redeploy, edit_crontab, edit_script,
+        email_rules, get_csrf_token, get_instances, get_prices, health, host, iam_report,
+        import_key_pair, inbound_email_delete, inbound_email_detail, inbound_email_reply,
+        inbound_email_spam_feedback, install_crontab, instance_list, instance_self,
+        instance_status, inventory_changes, lambda_invoke, launch_analytics, launch_status, list,
+        modify_volume, novnc_launcher, novnc_shutdown, novnc_status, preferences, price_alerts,
+        price_history, quotas, reencrypt_volume, release_address, remove_user_from_group,
+        replace_script, replacements, request_spot, reset_host_key, save_email_rule,
+        save_preferences, secrets, ses_activate_rule_set, ses_create_receipt_rule,
+        ses_delete_receipt_rule, ses_identities, ses_verify_identity, set_theme, spot_forecast,
+        sqs_delete, sqs_peek, sqs_purge, start_replacement, switch_account, sync_frontpage,
+        sync_inboud_email, systemd_action, systemd_dependencies, systemd_logs, systemd_restart_all,
+        systemd_restart_dependents, systemd_restart_preview, tag_item, tag_search, tasks,
+        terminate, terraform_drift, test_email_rules, ubuntu_images, update, update_dns_name,
+        update_price_alert, user, vend_credentials, waste_report, webhooks,
     },
+    task_supervisor::TaskSupervisor,
+    theme::load_templates,
 };
 
+/// Query for the json listing used by the cli in `--remote` mode
+#[derive(Deserialize)]
+struct ApiListRequest {
+    resource: ResourceType,
+}
+
 #[derive(Clone)]
 pub struct AppState {
-    pub aws: AwsAppInterface,
+    aws: Arc<RwLock<AwsAppInterface>>,
     pub novnc: NoVncInstance,
+    pub tasks: TaskSupervisor,
+}
+
+impl AppState {
+    #[must_use]
+    pub fn new(aws: AwsAppInterface) -> Self {
+        Self {
+            aws: Arc::new(RwLock::new(aws)),
+            novnc: NoVncInstance::new(),
+            tasks: TaskSupervisor::new(),
+        }
+    }
+
+    /// Interface for the currently selected account
+    #[must_use]
+    pub fn aws(&self) -> AwsAppInterface {
+        self.aws.read().clone()
+    }
+
+    /// Switch every subsequent request to the named account profile
+    /// # Errors
+    /// Returns error if the profile does not exist
+    pub async fn set_account(&self, name: Option<&str>) -> Result<(), Error> {
+        let mut aws = self.aws();
+        aws.set_account(name).await?;
+        *self.aws.write() = aws;
+        Ok(())
+    }
+
+    /// Switch every subsequent request to `region` of the current account,
+    /// listings of the previous region are dropped
+    /// # Errors
+    /// Returns error if a client fails to switch region
+    pub async fn set_region(&self, region: &str) -> Result<(), Error> {
+        let mut aws = self.aws();
+        aws.set_region(region).await?;
+        aws.cache.invalidate_all();
+        *INSTANCE_LIST.write().await = Arc::new(Vec::new());
+        *self.aws.write() = aws;
+        Ok(())
+    }
 }
 
 /// # Errors
 /// Returns error if config fails, `get_secrets` fails, or app fails to run
 pub async fn start_app() -> Result<(), Error> {
-    let config = Config::init_config()?;
+    let config = Config::init_config_with_metadata().await?;
+    let _tracing = init_tracing(&config, "aws-app-http")?;
     get_secrets(&config.secret_path, &config.jwt_secret_path).await?;
     run_app(&config).await
 }
@@ -47,6 +148,10 @@ fn get_aws_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
     let frontpage_path = sync_frontpage(app.clone()).boxed();
     let list_path = list(app.clone()).boxed();
     let terminate_path = terminate(app.clone()).boxed();
+    let batch_terminate_path = batch_terminate(app.clone()).boxed();
+    let batch_delete_volume_path = batch_delete_volume(app.clone()).boxed();
+    let batch_delete_snapshot_path = batch_delete_snapshot(app.clone()).boxed();
+    let batch_tag_path = batch_tag(app.clone()).boxed();
     let create_image_path = create_image(app.clone()).boxed();
     let delete_image_path = delete_image(app.clone()).boxed();
     let delete_volume_path = delete_volume(app.clone()).boxed();
@@ -55,6 +160,29 @@ fn get_aws_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
     let create_snapshot_path = create_snapshot(app.clone()).boxed();
     let tag_item_path = tag_item(app.clone()).boxed();
     let delete_ecr_image_path = delete_ecr_image(app.clone()).boxed();
+    let docker_action_path = docker_action(app.clone()).boxed();
+    let docker_logs_path = docker_logs(app.clone()).boxed();
+    let docker_pull_path = docker_pull(app.clone()).boxed();
+    let ecs_redeploy_path = ecs_redeploy(app.clone()).boxed();
+    let price_alerts_path = price_alerts(app.clone()).boxed();
+    let create_price_alert_path = create_price_alert(app.clone()).boxed();
+    let update_price_alert_path = update_price_alert(app.clone()).boxed();
+    let delete_price_alert_path = delete_price_alert(app.clone()).boxed();
+    let copy_snapshot_path = copy_snapshot(app.clone()).boxed();
+    let ecr_history_path = ecr_history(app.clone()).boxed();
+    let reencrypt_volume_path = reencrypt_volume(app.clone()).boxed();
+    let dr_policies_path = dr_policies(app.clone()).boxed();
+    let create_dr_policy_path = create_dr_policy(app.clone()).boxed();
+    let delete_dr_policy_path = delete_dr_policy(app.clone()).boxed();
+    let terraform_drift_path = terraform_drift(app.clone()).boxed();
+    let inventory_changes_path = inventory_changes(app.clone()).boxed();
+    let quotas_path = quotas(app.clone()).boxed();
+    let replacements_path = replacements(app.clone()).boxed();
+    let start_replacement_path = start_replacement(app.clone()).boxed();
+    let cancel_replacement_path = cancel_replacement(app.clone()).boxed();
+    let tag_search_path = tag_search(app.clone()).boxed();
+    let decommission_plan_path = decommission_plan(app.clone()).boxed();
+    let decommission_path = decommission(app.clone()).boxed();
     let cleanup_ecr_images_path = cleanup_ecr_images(app.clone()).boxed();
     let edit_script_path = edit_script(app.clone()).boxed();
     let replace_script_path = replace_script(app.clone()).boxed();
@@ -65,15 +193,25 @@ fn get_aws_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
     let remove_user_from_group_path = remove_user_from_group(app.clone()).boxed();
     let create_access_key_path = create_access_key(app.clone()).boxed();
     let delete_access_key_path = delete_access_key(app.clone()).boxed();
+    let vend_credentials_path = vend_credentials(app.clone()).boxed();
     let build_spot_request_path = build_spot_request(app.clone()).boxed();
     let request_spot_path = request_spot(app.clone()).boxed();
     let cancel_spot_path = cancel_spot(app.clone()).boxed();
+    let auto_recover_path = auto_recover(app.clone()).boxed();
     let get_prices_path = get_prices(app.clone()).boxed();
+    let price_history_path = price_history(app.clone()).boxed();
+    let spot_forecast_path = spot_forecast(app.clone()).boxed();
     let update_path = update(app.clone()).boxed();
     let instance_status_path = instance_status(app.clone()).boxed();
     let command_path = command(app.clone()).boxed();
     let get_instances_path = get_instances(app.clone()).boxed();
+    let ubuntu_images_path = ubuntu_images(app.clone()).boxed();
+    let preferences_path = preferences(app.clone()).boxed();
+    let save_preferences_path = save_preferences(app.clone()).boxed();
     let user_path = user().boxed();
+    let csrf_token_path = get_csrf_token().boxed();
+    let reset_host_key_path = reset_host_key(app.clone()).boxed();
+    let health_path = health(app.clone()).boxed();
     let novnc_launcher_path = novnc_launcher(app.clone()).boxed();
     let novnc_status_path = novnc_status(app.clone()).boxed();
     let novnc_shutdown_path = novnc_shutdown(app.clone()).boxed();
@@ -81,10 +219,57 @@ fn get_aws_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
     let systemd_action_path = systemd_action(app.clone()).boxed();
     let systemd_logs_path = systemd_logs(app.clone()).boxed();
     let systemd_restart_all_path = systemd_restart_all(app.clone()).boxed();
+    let systemd_dependencies_path = systemd_dependencies(app.clone()).boxed();
+    let systemd_restart_preview_path = systemd_restart_preview(app.clone()).boxed();
+    let systemd_restart_dependents_path = systemd_restart_dependents(app.clone()).boxed();
     let crontab_logs_path = crontab_logs(app.clone()).boxed();
     let inbound_email_detail_path = inbound_email_detail(app.clone()).boxed();
+    let delete_orphaned_attachments_path = delete_orphaned_attachments(app.clone()).boxed();
     let inbound_email_delete_path = inbound_email_delete(app.clone()).boxed();
+    let inbound_email_spam_feedback_path = inbound_email_spam_feedback(app.clone()).boxed();
+    let inbound_email_reply_path = inbound_email_reply(app.clone()).boxed();
     let sync_inboud_email_path = sync_inboud_email(app.clone()).boxed();
+    let sqs_peek_path = sqs_peek(app.clone()).boxed();
+    let ses_identities_path = ses_identities(app.clone()).boxed();
+    let ses_verify_identity_path = ses_verify_identity(app.clone()).boxed();
+    let ses_create_receipt_rule_path = ses_create_receipt_rule(app.clone()).boxed();
+    let ses_delete_receipt_rule_path = ses_delete_receipt_rule(app.clone()).boxed();
+    let ses_activate_rule_set_path = ses_activate_rule_set(app.clone()).boxed();
+    let email_rules_path = email_rules(app.clone()).boxed();
+    let instance_self_path = instance_self().boxed();
+    let create_health_check_path = create_health_check(app.clone()).boxed();
+    let delete_health_check_path = delete_health_check(app.clone()).boxed();
+    let create_routing_record_path = create_routing_record(app.clone()).boxed();
+    let save_email_rule_path = save_email_rule(app.clone()).boxed();
+    let delete_email_rule_path = delete_email_rule(app.clone()).boxed();
+    let test_email_rules_path = test_email_rules(app.clone()).boxed();
+    let sqs_purge_path = sqs_purge(app.clone()).boxed();
+    let sqs_delete_path = sqs_delete(app.clone()).boxed();
+    let backup_assign_path = backup_assign(app.clone()).boxed();
+    let lambda_invoke_path = lambda_invoke(app.clone()).boxed();
+    let launch_analytics_path = launch_analytics(app.clone()).boxed();
+    let launch_status_path = launch_status(app.clone()).boxed();
+    let costs_by_tag_path = costs_by_tag(app.clone()).boxed();
+    let waste_report_path = waste_report(app.clone()).boxed();
+    let release_address_path = release_address(app.clone()).boxed();
+    let import_key_pair_path = import_key_pair(app.clone()).boxed();
+    let delete_key_pair_path = delete_key_pair(app.clone()).boxed();
+    let instance_list_path = instance_list(app.clone()).boxed();
+    let iam_report_path = iam_report(app.clone()).boxed();
+    let bucket_summary_path = bucket_summary(app.clone()).boxed();
+    let dashboard_path = dashboard(app.clone()).boxed();
+    let host_path = host(app.clone()).boxed();
+    let edit_crontab_path = edit_crontab().boxed();
+    let crontab_preview_path = crontab_preview().boxed();
+    let install_crontab_path = install_crontab(app.clone()).boxed();
+    let tasks_path = tasks(app.clone()).boxed();
+    let switch_account_path = switch_account(app.clone()).boxed();
+    let theme_path = set_theme(app.clone()).boxed();
+    let secrets_path = secrets(app.clone()).boxed();
+    let webhooks_path = webhooks(app.clone())
+        .or(create_webhook(app.clone()))
+        .or(delete_webhook(app.clone()))
+        .boxed();
 
     let novnc_scope = novnc_launcher_path
         .or(novnc_status_path)
@@ -94,6 +279,10 @@ fn get_aws_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
     frontpage_path
         .or(list_path)
         .or(terminate_path)
+        .or(batch_terminate_path)
+        .or(batch_delete_volume_path)
+        .or(batch_delete_snapshot_path)
+        .or(batch_tag_path)
         .or(create_image_path)
         .or(delete_image_path)
         .or(delete_volume_path)
@@ -112,44 +301,357 @@ fn get_aws_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
         .or(remove_user_from_group_path)
         .or(create_access_key_path)
         .or(delete_access_key_path)
+        .or(vend_credentials_path)
         .or(build_spot_request_path)
         .or(request_spot_path)
         .or(cancel_spot_path)
+        .or(auto_recover_path)
         .or(get_prices_path)
+        .or(price_history_path)
+        .or(spot_forecast_path)
         .or(update_path)
         .or(instance_status_path)
         .or(command_path)
         .or(get_instances_path)
+        .or(ubuntu_images_path)
+        .or(preferences_path)
+        .or(save_preferences_path)
         .or(user_path)
+        .or(csrf_token_path)
+        .or(reset_host_key_path)
+        .or(health_path)
         .or(novnc_scope)
         .or(update_dns_name_path)
         .or(systemd_action_path)
         .or(systemd_logs_path)
         .or(systemd_restart_all_path)
+        .or(systemd_dependencies_path)
+        .or(systemd_restart_preview_path)
+        .or(systemd_restart_dependents_path)
         .or(crontab_logs_path)
         .or(inbound_email_detail_path)
+        .or(delete_orphaned_attachments_path)
         .or(inbound_email_delete_path)
+        .or(inbound_email_spam_feedback_path)
+        .or(inbound_email_reply_path)
         .or(sync_inboud_email_path)
+        .or(sqs_peek_path)
+        .or(ses_identities_path)
+        .or(ses_verify_identity_path)
+        .or(ses_create_receipt_rule_path)
+        .or(ses_delete_receipt_rule_path)
+        .or(ses_activate_rule_set_path)
+        .or(email_rules_path)
+        .or(instance_self_path)
+        .or(create_health_check_path)
+        .or(delete_health_check_path)
+        .or(create_routing_record_path)
+        .or(save_email_rule_path)
+        .or(delete_email_rule_path)
+        .or(test_email_rules_path)
+        .or(sqs_purge_path)
+        .or(sqs_delete_path)
+        .or(backup_assign_path)
+        .or(lambda_invoke_path)
+        .or(launch_analytics_path)
+        .or(launch_status_path)
+        .or(costs_by_tag_path)
+        .or(waste_report_path)
+        .or(release_address_path)
+        .or(import_key_pair_path)
+        .or(delete_key_pair_path)
+        .or(instance_list_path)
+        .or(iam_report_path)
+        .or(bucket_summary_path)
+        .or(dashboard_path)
+        .or(tasks_path)
+        .or(switch_account_path)
+        .or(theme_path)
+        .or(webhooks_path)
+        .or(secrets_path)
+        .or(host_path)
+        .or(edit_crontab_path)
+        .or(crontab_preview_path)
+        .or(install_crontab_path)
+        .or(docker_action_path)
+        .or(docker_logs_path)
+        .or(docker_pull_path)
+        .or(ecs_redeploy_path)
+        .or(price_alerts_path)
+        .or(create_price_alert_path)
+        .or(update_price_alert_path)
+        .or(delete_price_alert_path)
+        .or(decommission_plan_path)
+        .or(decommission_path)
+        .or(copy_snapshot_path)
+        .or(ecr_history_path)
+        .or(reencrypt_volume_path)
+        .or(dr_policies_path)
+        .or(create_dr_policy_path)
+        .or(delete_dr_policy_path)
+        .or(terraform_drift_path)
+        .or(inventory_changes_path)
+        .or(quotas_path)
+        .or(replacements_path)
+        .or(start_replacement_path)
+        .or(cancel_replacement_path)
+        .or(tag_search_path)
         .boxed()
 }
 
 async fn run_app(config: &Config) -> Result<(), Error> {
-    async fn update_db(pool: PgPool) {
+    async fn update_db(storage: Arc<dyn Storage>) {
         let mut i = interval(Duration::from_secs(60));
         loop {
-            fill_from_db(&pool).await.unwrap_or(());
+            let result = fill_from_db(storage.as_ref()).await;
+            record_background_task("fill_from_db", result.is_ok());
+            i.tick().await;
+        }
+    }
+
+    async fn update_ddns(aws: AwsAppInterface, ses: SesInstance) {
+        let mut i = interval(Duration::from_secs(aws.config.ddns_interval.max(60)));
+        loop {
+            i.tick().await;
+            let result =
+                update_ddns_records(&aws.config, &aws.route53, &ses, aws.storage.as_ref()).await;
+            if let Err(e) = &result {
+                error!("ddns update failed: {e}");
+            }
+            record_background_task("update_ddns", result.is_ok());
+        }
+    }
+
+    async fn recover_spot_instances(aws: AwsAppInterface, ses: SesInstance) {
+        let mut i = interval(Duration::from_secs(
+            aws.config.spot_recovery_interval.max(60),
+        ));
+        loop {
+            i.tick().await;
+            let result = aws.recover_spot_instances(&ses).await;
+            match &result {
+                Ok(recovered) if *recovered > 0 => info!("recovered {recovered} spot instances"),
+                Ok(_) => {}
+                Err(e) => error!("spot recovery failed: {e}"),
+            }
+            record_background_task("recover_spot_instances", result.is_ok());
+        }
+    }
+
+    async fn check_certificate_expiry(aws: AwsAppInterface, ses: SesInstance, interval_secs: u64) {
+        let mut i = interval(Duration::from_secs(interval_secs.max(60)));
+        loop {
+            i.tick().await;
+            let result = aws.check_certificate_expiry(&ses).await;
+            match &result {
+                Ok(expiring) if *expiring > 0 => warn!("{expiring} acm certificates expiring"),
+                Ok(_) => {}
+                Err(e) => error!("certificate expiry check failed: {e}"),
+            }
+            record_background_task("check_certificate_expiry", result.is_ok());
+        }
+    }
+
+    async fn check_price_alerts(aws: AwsAppInterface, ses: SesInstance) {
+        let mut i = interval(Duration::from_secs(aws.config.price_alert_interval.max(60)));
+        loop {
+            i.tick().await;
+            let result = aws.check_price_alerts(&ses).await;
+            match &result {
+                Ok(triggered) if *triggered > 0 => info!("{triggered} price alerts triggered"),
+                Ok(_) => {}
+                Err(e) => error!("price alert check failed: {e}"),
+            }
+            record_background_task("check_price_alerts", result.is_ok());
+        }
+    }
+
+    async fn enforce_dr_policies(aws: AwsAppInterface) {
+        let mut i = interval(Duration::from_secs(aws.config.dr_interval.max(60)));
+        loop {
+            i.tick().await;
+            let result = aws.enforce_dr_policies().await;
+            match &result {
+                Ok(started) if *started > 0 => info!("{started} dr snapshots or copies started"),
+                Ok(_) => {}
+                Err(e) => error!("dr policy enforcement failed: {e}"),
+            }
+            record_background_task("enforce_dr_policies", result.is_ok());
+        }
+    }
+
+    async fn check_instance_setup(aws: AwsAppInterface, ses: SesInstance) {
+        let mut i = interval(Duration::from_secs(aws.config.setup_check_interval.max(30)));
+        loop {
+            i.tick().await;
+            let result = aws.check_instance_setup(&ses).await;
+            match &result {
+                Ok(finished) if *finished > 0 => info!("setup finished on {finished} instances"),
+                Ok(_) => {}
+                Err(e) => error!("instance setup check failed: {e}"),
+            }
+            record_background_task("check_instance_setup", result.is_ok());
+        }
+    }
+
+    async fn poll_resource_events(aws: AwsAppInterface) {
+        let mut i = interval(Duration::from_secs(
+            aws.config.webhook_poll_interval.max(30),
+        ));
+        let mut states = ResourceStates::default();
+        loop {
+            i.tick().await;
+            let result = aws.poll_resource_events(&mut states).await;
+            match &result {
+                Ok(events) if *events > 0 => info!("found {events} resource events"),
+                Ok(_) => {}
+                Err(e) => error!("resource event poll failed: {e}"),
+            }
+            record_background_task("poll_resource_events", result.is_ok());
+        }
+    }
+
+    async fn record_inventory_snapshots(aws: AwsAppInterface) {
+        let mut i = interval(Duration::from_secs(
+            aws.config.inventory_snapshot_interval.max(60),
+        ));
+        loop {
+            i.tick().await;
+            let result = aws.record_inventory_snapshot().await;
+            match &result {
+                Ok(resources) => debug!("recorded inventory of {resources} resources"),
+                Err(e) => error!("inventory snapshot failed: {e}"),
+            }
+            record_background_task("record_inventory_snapshots", result.is_ok());
+        }
+    }
+
+    async fn advance_replacements(aws: AwsAppInterface, ses: SesInstance) {
+        let mut i = interval(Duration::from_secs(aws.config.setup_check_interval.max(30)));
+        loop {
+            i.tick().await;
+            let result = aws.advance_replacements(&ses).await;
+            match &result {
+                Ok(advanced) if *advanced > 0 => info!("advanced {advanced} instance replacements"),
+                Ok(_) => {}
+                Err(e) => error!("instance replacement failed: {e}"),
+            }
+            record_background_task("advance_replacements", result.is_ok());
+        }
+    }
+
+    async fn deliver_webhooks(aws: AwsAppInterface) {
+        let mut i = interval(Duration::from_secs(30));
+        let timeout = Duration::from_secs(aws.config.webhook_timeout);
+        loop {
             i.tick().await;
+            let result = webhook::deliver_webhooks(
+                aws.storage.as_ref(),
+                aws.config.webhook_max_attempts,
+                timeout,
+            )
+            .await;
+            if let Err(e) = &result {
+                error!("webhook delivery failed: {e}");
+            }
+            record_background_task("deliver_webhooks", result.is_ok());
         }
     }
 
+    async fn scheduled_update(aws: AwsAppInterface, schedule: CronSchedule) {
+        loop {
+            let now = OffsetDateTime::now_utc();
+            let next = match schedule.next_after(now) {
+                Some(next) => next,
+                None => {
+                    error!("update schedule {schedule} never fires");
+                    return;
+                }
+            };
+            sleep((next - now).unsigned_abs()).await;
+            let result = aws.scheduled_update().await;
+            if let Err(e) = &result {
+                error!("scheduled update failed: {e}");
+            }
+            record_background_task("scheduled_update", result.is_ok());
+        }
+    }
+
+    async fn scheduled_db_backup(aws: AwsAppInterface, schedule: CronSchedule) {
+        loop {
+            let now = OffsetDateTime::now_utc();
+            let next = match schedule.next_after(now) {
+                Some(next) => next,
+                None => {
+                    error!("db backup schedule {schedule} never fires");
+                    return;
+                }
+            };
+            sleep((next - now).unsigned_abs()).await;
+            let result = aws.scheduled_db_backup().await;
+            if let Err(e) = &result {
+                error!("scheduled db backup failed: {e}");
+            }
+            record_background_task("scheduled_db_backup", result.is_ok());
+        }
+    }
+
+    let update_schedule: Option<CronSchedule> = config
+        .update_schedule
+        .as_ref()
+        .map(|s| s.parse())
+        .transpose()?;
+    let db_backup_schedule: Option<CronSchedule> = config
+        .db_backup_schedule
+        .as_ref()
+        .map(|s| s.parse())
+        .transpose()?;
+    load_templates(config)?;
     let pool = PgPool::new(&config.database_url)?;
+    let storage = open_storage(&config, &pool)?;
     let sdk_config = aws_config::load_from_env().await;
-    let app = AppState {
-        aws: AwsAppInterface::new(config.clone(), &sdk_config, pool),
-        novnc: NoVncInstance::new(),
-    };
+    let app = AppState::new(AwsAppInterface::new(
+        config.clone(),
+        &sdk_config,
+        pool,
+        storage,
+    ));
 
-    let update_handle = spawn(update_db(app.aws.pool.clone()));
+    let update_handle = spawn(update_db(app.aws().storage.clone()));
+    let ddns_handle = if config.ddns_records.is_empty() {
+        None
+    } else {
+        let ses = SesInstance::new(&sdk_config);
+        Some(spawn(update_ddns(app.aws(), ses)))
+    };
+    let schedule_handle =
+        update_schedule.map(|schedule| spawn(scheduled_update(app.aws(), schedule)));
+    let db_backup_handle =
+        db_backup_schedule.map(|schedule| spawn(scheduled_db_backup(app.aws(), schedule)));
+    let recovery_handle = spawn(recover_spot_instances(
+        app.aws(),
+        SesInstance::new(&sdk_config),
+    ));
+    let price_alert_handle = spawn(check_price_alerts(app.aws(), SesInstance::new(&sdk_config)));
+    let dr_handle = spawn(enforce_dr_policies(app.aws()));
+    let setup_check_handle = spawn(check_instance_setup(
+        app.aws(),
+        SesInstance::new(&sdk_config),
+    ));
+    let certificate_handle = config.certificate_alert_interval.map(|interval_secs| {
+        spawn(check_certificate_expiry(
+            app.aws(),
+            SesInstance::new(&sdk_config),
+            interval_secs,
+        ))
+    });
+    let resource_events_handle = spawn(poll_resource_events(app.aws()));
+    let webhook_handle = spawn(deliver_webhooks(app.aws()));
+    let inventory_handle = spawn(record_inventory_snapshots(app.aws()));
+    let replacement_handle = spawn(advance_replacements(
+        app.aws(),
+        SesInstance::new(&sdk_config),
+    ));
 
     let (spec, aws_path) = openapi::spec()
         .info(Info {
@@ -175,13 +677,113 @@ async fn run_app(config: &Config) -> Result<(), Error> {
             rweb::reply::with_header(reply, CONTENT_TYPE, "text/yaml")
         });
 
-    let routes = aws_path
-        .or(spec_json_path)
-        .or(spec_yaml_path)
-        .recover(error_response);
+    let metrics_path = rweb::path!("aws" / "metrics")
+        .and(rweb::path::end())
+        .and_then({
+            let app = app.clone();
+            move || {
+                let aws = app.aws();
+                async move {
+                    let host = aws
+                        .sysinfo
+                        .get_host_metrics(&aws.config.host_ignored_file_systems);
+                    let body = get_metrics(&host).await.map_err(Into::<Rejection>::into)?;
+                    Ok::<_, Rejection>(rweb::reply::with_header(
+                        body,
+                        CONTENT_TYPE,
+                        "text/plain; version=0.0.4",
+                    ))
+                }
+            }
+        });
+
+    let api_list_path = rweb::path!("aws" / "api" / "list")
+        .and(rweb::path::end())
+        .and(LoggedUser::filter())
+        .and(query::<ApiListRequest>())
+        .and_then({
+            let app = app.clone();
+            move |_: LoggedUser, request: ApiListRequest| {
+                let aws = app.aws();
+                async move {
+                    let listing = aws
+                        .get_resource_json(request.resource)
+                        .await
+                        .map_err(ServiceError::from)?;
+                    Ok::<_, Rejection>(rweb::reply::json(&listing))
+                }
+            }
+        });
+
+    let routes = csrf_filter()
+        .and(rate_limit_filter(
+            config.rate_limit_burst,
+            config.rate_limit_per_minute,
+        ))
+        .and(
+            aws_path
+                .or(spec_json_path)
+                .or(spec_yaml_path)
+                .or(metrics_path)
+                .or(api_list_path)
+                .or(graphql_path(&app))
+                .or(sns_events_path(&app))
+                .or(inbound_email_notify_path(&app))
+                .or(event_stream_path())
+                .or(upload_path(&app))
+                .or(download_path(&app))
+                .or(attachment_download_path(&app))
+                .or(export_path(&app))
+                .or(create_key_pair_path(&app))
+                .or(s3_upload_path(&app))
+                .or(zone_export_path(&app))
+                .or(graph_path(&app)),
+        )
+        .recover(error_response)
+        .with(custom(record_request));
     let addr: SocketAddr = format_sstr!("{}:{}", config.host, config.port).parse()?;
-    rweb::serve(routes).bind(addr).await;
-    update_handle.await.map_err(Into::into)
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let shutdown = async move {
+        select! {
+            _ = sigterm.recv() => {},
+            _ = ctrl_c() => {},
+        }
+    };
+    let service = rweb::service(routes);
+    let make_service = make_service_fn(move |_| {
+        let service = service.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| traced_request(service.clone(), req))) }
+    });
+    Server::try_bind(&addr)?
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await?;
+
+    update_handle.abort();
+    recovery_handle.abort();
+    price_alert_handle.abort();
+    dr_handle.abort();
+    setup_check_handle.abort();
+    resource_events_handle.abort();
+    webhook_handle.abort();
+    inventory_handle.abort();
+    replacement_handle.abort();
+    if let Some(ddns_handle) = ddns_handle {
+        ddns_handle.abort();
+    }
+    if let Some(schedule_handle) = schedule_handle {
+        schedule_handle.abort();
+    }
+    if let Some(db_backup_handle) = db_backup_handle {
+        db_backup_handle.abort();
+    }
+    if let Some(certificate_handle) = certificate_handle {
+        certificate_handle.abort();
+    }
+    app.tasks
+        .drain(Duration::from_secs(config.shutdown_timeout))
+        .await;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -264,6 +866,7 @@ mod tests {
             .await?;
         assert!(result.len() > 0);
         assert!(result.contains("Instance Id"));
+        assert!(result.contains("csrf-token"));
 
         for (rtype, substr) in &[
             (ResourceType::Instances, "Instance Id"),
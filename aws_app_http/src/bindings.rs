@@ -0,0 +1,180 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use stack_string::{format_sstr, StackString};
+use std::collections::BTreeMap;
+
+/// Where the response of a binding is rendered
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// Replaces `main_article` and clears `sub_article`
+    Main,
+    /// Replaces `sub_article`
+    Sub,
+    /// Only the status button is updated
+    Status,
+}
+
+/// Frontend binding of a route: elements carrying `data-action="{name}"`
+/// call it with their `data-params` as the query string
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    #[serde(skip)]
+    pub name: &'static str,
+    pub method: &'static str,
+    pub path: &'static str,
+    pub target: Target,
+    /// Resource listing reloaded after the request succeeds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<&'static str>,
+    /// Prompt shown before the request, followed by the param values
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm: Option<&'static str>,
+    /// Send an `Idempotency-Key` header derived from the params
+    pub idempotent: bool,
+}
+
+impl Binding {
+    const fn new(name: &'static str, method: &'static str, path: &'static str) -> Self {
+        Self {
+            name,
+            method,
+            path,
+            target: Target::Status,
+            refresh: None,
+            confirm: None,
+            idempotent: false,
+        }
+    }
+
+    const fn target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    const fn refresh(mut self, resource: &'static str) -> Self {
+        self.refresh = Some(resource);
+        self
+    }
+
+    const fn confirm(mut self, prompt: &'static str) -> Self {
+        self.confirm = Some(prompt);
+        self
+    }
+
+    const fn idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+}
+
+/// Routes reachable from `data-action` elements, the js route table is
+/// generated from this list
+pub const BINDINGS: &[Binding] = &[
+    Binding::new("list", "GET", "/aws/list").target(Target::Main),
+    Binding::new("instance_status", "GET", "/aws/instance_status").target(Target::Sub),
+    Binding::new("dashboard", "GET", "/aws/dashboard").target(Target::Main),
+    Binding::new("instance_self", "GET", "/aws/self").target(Target::Main),
+    Binding::new("ses_identities", "GET", "/aws/ses/identities").target(Target::Main),
+    Binding::new("email_rules", "GET", "/aws/email_rules").target(Target::Main),
+    Binding::new("launch_analytics", "GET", "/aws/launch_analytics").target(Target::Sub),
+    Binding::new("costs_by_tag", "GET", "/aws/costs_by_tag").target(Target::Sub),
+    Binding::new("waste_report", "GET", "/aws/waste").target(Target::Sub),
+    Binding::new("iam_report", "GET", "/aws/iam_report").target(Target::Sub),
+    Binding::new("bucket_summary", "GET", "/aws/buckets").target(Target::Sub),
+    Binding::new("tasks", "GET", "/aws/tasks").target(Target::Sub),
+    Binding::new("webhooks", "GET", "/aws/webhooks").target(Target::Main),
+    Binding::new("price_alerts", "GET", "/aws/price_alerts").target(Target::Main),
+    Binding::new("preferences", "GET", "/aws/preferences").target(Target::Main),
+    Binding::new("dr", "GET", "/aws/dr").target(Target::Main),
+    Binding::new("terraform_drift", "GET", "/aws/terraform_drift").target(Target::Main),
+    Binding::new("inventory_changes", "GET", "/aws/changes").target(Target::Main),
+    Binding::new("replacements", "GET", "/aws/replacements").target(Target::Main),
+    Binding::new("quotas", "GET", "/aws/quotas").target(Target::Main),
+    Binding::new("tag_search", "GET", "/aws/search").target(Target::Main),
+    Binding::new("secrets", "GET", "/aws/secrets").target(Target::Main),
+    Binding::new("host", "GET", "/aws/host").target(Target::Main),
+    Binding::new("crontab_edit", "GET", "/aws/crontab/edit").target(Target::Sub),
+    Binding::new("docker_logs", "GET", "/aws/docker/logs").target(Target::Sub),
+    Binding::new("ecr_history", "GET", "/aws/ecr/history").target(Target::Sub),
+    Binding::new("decommission_plan", "GET", "/aws/decommission").target(Target::Sub),
+    Binding::new("create_image", "POST", "/aws/create_image")
+        .refresh("ami")
+        .idempotent(),
+    Binding::new("terminate", "DELETE", "/aws/terminate")
+        .refresh("instances")
+        .idempotent(),
+    Binding::new("delete_image", "DELETE", "/aws/delete_image").refresh("ami"),
+    Binding::new("delete_volume", "DELETE", "/aws/delete_volume").refresh("volume"),
+    Binding::new("delete_snapshot", "DELETE", "/aws/delete_snapshot").refresh("snapshot"),
+    Binding::new("delete_ecr_image", "DELETE", "/aws/delete_ecr_image").refresh("ecr"),
+    Binding::new("cleanup_ecr_images", "DELETE", "/aws/cleanup_ecr_images").refresh("ecr"),
+    Binding::new("delete_key_pair", "DELETE", "/aws/delete_key_pair")
+        .refresh("key")
+        .confirm("Delete key pair"),
+    Binding::new("reencrypt_volume", "POST", "/aws/reencrypt_volume").confirm("Re-encrypt volume"),
+    Binding::new("cancel_spot", "DELETE", "/aws/cancel_spot").refresh("spot"),
+    Binding::new("docker_action", "POST", "/aws/docker/action").refresh("docker"),
+    Binding::new("docker_pull", "POST", "/aws/docker/pull").confirm("Pull image"),
+    Binding::new("ecs_redeploy", "POST", "/aws/ecs/redeploy")
+        .refresh("ecs")
+        .confirm("Force new deployment of"),
+];
+
+static BINDINGS_SCRIPT: Lazy<StackString> = Lazy::new(|| {
+    let table: BTreeMap<_, _> = BINDINGS.iter().map(|b| (b.name, b)).collect();
+    let table = serde_json::to_string(&table).unwrap_or_else(|_| "{}".into());
+    format_sstr!("const routeBindings = {table};")
+});
+
+#[must_use]
+pub fn get_binding(name: &str) -> Option<&'static Binding> {
+    BINDINGS.iter().find(|b| b.name == name)
+}
+
+/// Declares `routeBindings`, inlined ahead of `scripts.js`
+#[must_use]
+pub fn bindings_script() -> &'static str {
+    BINDINGS_SCRIPT.as_str()
+}
+
+/// Json object for the `data-params` attribute
+#[must_use]
+pub fn action_params(params: &[(&str, &str)]) -> StackString {
+    let params: Map<String, Value> = params
+        .iter()
+        .map(|(k, v)| ((*k).to_string(), Value::String((*v).to_string())))
+        .collect();
+    Value::Object(params).to_string().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::bindings::{action_params, bindings_script, get_binding, BINDINGS};
+
+    #[test]
+    fn test_bindings_match_routes() {
+        let routes = include_str!("routes.rs");
+        let mut names = HashSet::new();
+        for binding in BINDINGS {
+            assert!(names.insert(binding.name), "duplicate {}", binding.name);
+            let route = format!("#[{}(\"{}\")]", binding.method.to_lowercase(), binding.path);
+            assert!(routes.contains(&route), "no route {route}");
+            assert!(bindings_script().contains(&format!("\"{}\":", binding.name)));
+        }
+        assert!(get_binding("terminate").is_some());
+        assert!(get_binding("missing").is_none());
+    }
+
+    #[test]
+    fn test_action_params() {
+        assert_eq!(
+            action_params(&[("inst_id", "i-1234"), ("name", "it's")]),
+            r#"{"inst_id":"i-1234","name":"it's"}"#
+        );
+        assert_eq!(action_params(&[]), "{}");
+    }
+}
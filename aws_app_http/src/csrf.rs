@@ -0,0 +1,124 @@
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rweb::{
+    filters::{cookie, header},
+    http::Method,
+    Filter, Rejection,
+};
+use stack_string::StackString;
+use std::collections::HashMap;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::errors::ServiceError as Error;
+
+/// Header the frontend echoes the token from the `csrf-token` meta tag in
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// How long a token is kept after its session last used it
+const CSRF_TOKEN_TTL: Duration = Duration::hours(12);
+
+/// Token minted for each session id with when it was last used, a session
+/// that was never served a page (or predates a restart, or was idle longer
+/// than `CSRF_TOKEN_TTL`) has to reload before it can make changes
+static CSRF_TOKENS: Lazy<Mutex<HashMap<Uuid, (OffsetDateTime, StackString)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Token for `session`, minted on first use
+#[must_use]
+pub fn csrf_token(session: Uuid) -> StackString {
+    let now = OffsetDateTime::now_utc();
+    let mut tokens = CSRF_TOKENS.lock();
+    tokens.retain(|_, (last_used, _)| now - *last_used < CSRF_TOKEN_TTL);
+    let (last_used, token) = tokens
+        .entry(session)
+        .or_insert_with(|| (now, Uuid::new_v4().simple().to_string().into()));
+    *last_used = now;
+    token.clone()
+}
+
+pub(crate) fn is_mutating(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    )
+}
+
+/// # Errors
+/// Returns `Error::Forbidden` if `token` is missing or was not minted for
+/// `session`
+pub fn verify_csrf_token(session: Uuid, token: Option<&str>) -> Result<(), Error> {
+    let now = OffsetDateTime::now_utc();
+    let expected = CSRF_TOKENS
+        .lock()
+        .get_mut(&session)
+        .filter(|(last_used, _)| now - *last_used < CSRF_TOKEN_TTL)
+        .map(|(last_used, token)| {
+            *last_used = now;
+            token.clone()
+        });
+    match (expected, token) {
+        (Some(expected), Some(token)) if expected == token => Ok(()),
+        (_, None) => Err(Error::Forbidden("missing csrf token".into())),
+        _ => Err(Error::Forbidden(
+            "invalid csrf token, reload the page".into(),
+        )),
+    }
+}
+
+/// Rejects POST, PUT, PATCH and DELETE requests carrying a session cookie
+/// without the matching `x-csrf-token` header. Requests without a session are
+/// passed through to be rejected by `LoggedUser::filter`.
+#[must_use]
+pub fn csrf_filter() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    rweb::method()
+        .and(cookie::optional("session-id"))
+        .and(header::optional::<String>(CSRF_HEADER))
+        .and_then(
+            |method: Method, session: Option<String>, token: Option<String>| async move {
+                if !is_mutating(&method) {
+                    return Ok(());
+                }
+                let session: Option<Uuid> = session.and_then(|s| s.parse().ok());
+                if let Some(session) = session {
+                    verify_csrf_token(session, token.as_deref()).map_err(rweb::reject::custom)?;
+                }
+                Ok::<_, Rejection>(())
+            },
+        )
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+
+    use crate::csrf::{csrf_token, verify_csrf_token, CSRF_TOKENS, CSRF_TOKEN_TTL};
+
+    #[test]
+    fn test_verify_csrf_token() {
+        let session = Uuid::new_v4();
+        assert!(verify_csrf_token(session, Some("anything")).is_err());
+
+        let token = csrf_token(session);
+        assert_eq!(csrf_token(session), token);
+        assert!(verify_csrf_token(session, Some(token.as_str())).is_ok());
+        assert!(verify_csrf_token(session, None).is_err());
+        assert!(verify_csrf_token(Uuid::new_v4(), Some(token.as_str())).is_err());
+    }
+
+    #[test]
+    fn test_csrf_token_expires() {
+        let idle = Uuid::new_v4();
+        let token = csrf_token(idle);
+        CSRF_TOKENS.lock().insert(
+            idle,
+            (OffsetDateTime::now_utc() - CSRF_TOKEN_TTL, token.clone()),
+        );
+        assert!(verify_csrf_token(idle, Some(token.as_str())).is_err());
+
+        csrf_token(Uuid::new_v4());
+        assert!(!CSRF_TOKENS.lock().contains_key(&idle));
+    }
+}
@@ -2,46 +2,136 @@ use dioxus::prelude::{
     component, dioxus_elements, rsx, Element, GlobalSignal, IntoDynNode, Props, Readable,
     VirtualDom,
 };
-use futures::{future::try_join_all, stream::FuturesUnordered, try_join, TryStreamExt};
+use futures::{
+    join,
+    stream::{self, StreamExt},
+    try_join, TryStreamExt,
+};
+use log::error;
 use stack_string::{format_sstr, StackString};
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     net::Ipv4Addr,
     sync::Arc,
 };
-use time::{macros::format_description, OffsetDateTime};
+use time::{macros::format_description, Duration, OffsetDateTime};
 use time_tz::OffsetDateTimeExt;
+use uuid::Uuid;
 
 use aws_app_lib::{
-    aws_app_interface::{AwsAppInterface, AwsInstancePrice, INSTANCE_LIST},
+    account_profile::AccountProfile,
+    acm_instance::CertificateInfo,
+    ami_catalog::lineage,
+    aws_app_interface::{is_protected, AwsAppInterface, AwsInstancePrice, INSTANCE_LIST},
+    backup_instance::{BackupPlanInfo, ProtectedResourceInfo, RecoveryPointInfo},
+    bucket_summary::BucketSummary,
     config::Config,
+    cost_attribution::{summarize_costs, ResourceCost, TagCost},
+    credential_report::CredentialReportEntry,
     date_time_wrapper::DateTimeWrapper,
+    decommission::DecommissionPlan,
+    docker_instance::ContainerInfo,
+    dr_policy::{DrCompliance, DrPolicy},
     ec2_instance::{
         AmiInfo, Ec2InstanceInfo, ReservedInstanceInfo, SnapshotInfo, SpotInstanceRequestInfo,
-        VolumeInfo,
+        VolumeInfo, UBUNTU_ARCHES, UBUNTU_RELEASES,
     },
+    ecr_history::EcrImageEvent,
     ecr_instance::ImageInfo,
+    ecs_instance::{EcsClusterInfo, EcsServiceInfo},
+    email_thread::{group_threads, ThreadEntry},
     iam_instance::{AccessKeyMetadata, IamGroup, IamUser},
-    models::{InboundEmailDB, InstanceFamily, InstanceList},
-    resource_type::ResourceType,
-    route53_instance::DnsRecord,
-    sysinfo_instance::ProcessInfo,
-    systemd_instance::RunStatus,
+    instance_filter::{InstanceFilter, INSTANCE_SORT_KEYS},
+    instance_metadata::InstanceMetadata,
+    inventory::{ChangeKind, InventoryDiff},
+    lambda_instance::{LambdaFunctionInfo, LambdaInvokeResult},
+    launch_progress::{LaunchProgress, PhaseState},
+    models::{
+        AmiCatalogEntry, EmailAttachment, EmailForwardRule, InboundEmailDB, InstanceFamily,
+        LaunchAnalytics, LaunchCount, SentEmail, UpdateStatus,
+    },
+    price_alert::PriceAlert,
+    price_forecast::{InterruptionRisk, SpotForecast},
+    quota_instance::{QuotaUsage, QUOTA_WARNING_THRESHOLD},
+    replacement::{InstanceReplacement, ReplacementState},
+    resource_type::{ResourceType, ALL_RESOURCES},
+    route53_instance::{DnsRecord, HealthCheckInfo},
+    secrets_instance::SecretSummary,
+    ses_admin::{ReceiptRuleSetInfo, SesIdentity},
+    sqs_instance::QueueInfo,
+    storage::{AmiCatalogRepo, InboundEmailRepo, SentEmailRepo},
+    sysinfo_instance::{DiskAlert, HostMetrics, ProcessInfo},
+    systemd_instance::{
+        ProbeStatus, RestartResult, RunStatus, SocketStatus, TimerStatus, UnitDependencies,
+    },
+    tag_search::TaggedResource,
+    terraform_drift::{DriftReport, DriftStatus},
+    user_preferences::{UserPreferences, MAX_ROWS_PER_PAGE},
+    waste::WasteItem,
+    webhook::{Webhook, WebhookDelivery, WebhookEvent, DELIVERY_PENDING},
 };
 
 use crate::{
+    bindings::{action_params, bindings_script, get_binding},
     errors::ServiceError as Error,
     requests::{get_ami_tags, get_volumes, print_tags},
+    table_preferences::{table_columns, HiddenColumns},
+    task_supervisor::TaskInfo,
+    theme::{scripts, stylesheet, Theme},
 };
 
+/// Upper bound on simultaneous aws api calls issued while rendering a page
+const MAX_CONCURRENT_FETCHES: usize = 4;
+
 /// # Errors
 /// Returns error if db query fails
-pub async fn get_index(app: &AwsAppInterface) -> Result<StackString, Error> {
-    app.fill_instance_list().await?;
-    let instances = INSTANCE_LIST.read().await.clone();
+pub async fn get_index(
+    app: &AwsAppInterface,
+    theme: Theme,
+    csrf_token: StackString,
+    preferences: &UserPreferences,
+) -> Result<StackString, Error> {
+    let (body, banner) = join!(
+        get_cached_frontpage(ResourceType::Instances, app, false, preferences),
+        app.get_identity_banner()
+    );
+    let body = body?;
+    let accounts = AccountProfile::all(&app.config)?
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    let account = app.account.clone();
+    let (identity, identity_warning) = match banner {
+        Ok(banner) => {
+            let warning = banner.expiry_warning(
+                OffsetDateTime::now_utc(),
+                Duration::minutes(app.config.credential_expiry_warning_minutes),
+            );
+            (
+                format_sstr!(
+                    "{} {} {}",
+                    banner.identity.account,
+                    banner.identity.arn,
+                    banner.region
+                ),
+                warning,
+            )
+        }
+        Err(e) => ("unknown identity".into(), Some(format_sstr!("{e}"))),
+    };
     let body = {
-        let mut app =
-            VirtualDom::new_with_props(IndexListElement, IndexListElementProps { instances });
+        let mut app = VirtualDom::new_with_props(
+            IndexListElement,
+            IndexListElementProps {
+                body,
+                accounts,
+                account,
+                identity,
+                identity_warning,
+                theme,
+                csrf_token,
+            },
+        );
         app.rebuild_in_place();
         let mut renderer = dioxus_ssr::Renderer::default();
         let mut buffer = String::new();
@@ -53,31 +143,216 @@ pub async fn get_index(app: &AwsAppInterface) -> Result<StackString, Error> {
     Ok(body.into())
 }
 
+/// Render a resource listing from `aws.cache` unless `refresh` is set or the
+/// cached copy is older than the configured ttl
+/// # Errors
+/// Returns error if db query fails
+pub async fn get_cached_frontpage(
+    resource_type: ResourceType,
+    aws: &AwsAppInterface,
+    refresh: bool,
+    preferences: &UserPreferences,
+) -> Result<StackString, Error> {
+    if resource_type == ResourceType::All {
+        return get_dashboard(aws, refresh, preferences).await;
+    }
+    let (status, body) = fetch_cached_frontpage(resource_type, aws, refresh, preferences).await?;
+    let body = table_preferences_body(resource_type.to_str(), &body, preferences)?;
+    cache_status_body(resource_type, status, body)
+}
+
+/// Returns the cache status and rendered body for a single resource type,
+/// the cache holds the default rendering so tables with hidden columns or a
+/// preferred sort are rendered live
+async fn fetch_cached_frontpage(
+    resource_type: ResourceType,
+    aws: &AwsAppInterface,
+    refresh: bool,
+    preferences: &UserPreferences,
+) -> Result<(StackString, StackString), Error> {
+    let hidden = HiddenColumns::new(
+        resource_type,
+        &preferences.hidden_columns(resource_type.to_str()),
+    );
+    if resource_type == ResourceType::Instances {
+        if let Some(filter) = preferences.instance_filter() {
+            let body = get_instances_frontpage(aws, filter, hidden).await?;
+            return Ok(("live".into(), body.into()));
+        }
+    }
+    if !hidden.is_empty() {
+        let body = get_frontpage(resource_type, aws, hidden).await?;
+        return Ok(("live".into(), body));
+    }
+    if !refresh {
+        if let Some(entry) = aws.cache.get(resource_type) {
+            let status = format_sstr!("cached {}s ago", entry.age().whole_seconds());
+            return Ok((status, entry.value));
+        }
+    }
+    let body = get_frontpage(resource_type, aws, hidden).await?;
+    aws.cache.insert(resource_type, body.clone());
+    Ok(("live".into(), body))
+}
+
+/// Render every resource table on one page, fetching all resource types
+/// concurrently
+/// # Errors
+/// Returns error if any aws api call or db query fails
+pub async fn get_dashboard(
+    aws: &AwsAppInterface,
+    refresh: bool,
+    preferences: &UserPreferences,
+) -> Result<StackString, Error> {
+    let resources = ALL_RESOURCES
+        .iter()
+        .filter(|r| **r != ResourceType::InboundEmail);
+    let sections: Vec<DashboardSection> = stream::iter(resources.map(|r| async move {
+        let (status, body) = fetch_cached_frontpage(*r, aws, refresh, preferences).await?;
+        let body = table_preferences_body(r.to_str(), &body, preferences)?;
+        Ok::<_, Error>(DashboardSection {
+            resource: r.to_str().into(),
+            status,
+            body,
+        })
+    }))
+    .buffered(MAX_CONCURRENT_FETCHES)
+    .try_collect()
+    .await?;
+    let mut app = VirtualDom::new_with_props(DashboardElement, DashboardElementProps { sections });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer.into())
+}
+
+#[derive(Clone, PartialEq)]
+struct DashboardSection {
+    resource: StackString,
+    status: StackString,
+    body: StackString,
+}
+
+#[component]
+fn DashboardElement(sections: Vec<DashboardSection>) -> Element {
+    rsx! {
+        div {
+            class: "cache-status",
+            {action_button("dashboard", "Refresh All", &[("refresh", "true")])},
+        },
+        {sections.iter().enumerate().map(|(idx, section)| {
+            let resource = &section.resource;
+            let status = &section.status;
+            let body = &section.body;
+            rsx! {
+                div {
+                    key: "dashboard-key-{idx}",
+                    id: "dashboard-{resource}",
+                    h3 {"{resource}"},
+                    div {
+                        class: "cache-status",
+                        "{status} ",
+                        {action_button("list", "Refresh", &[("resource", resource.as_str()), ("refresh", "true")])},
+                    },
+                    div {dangerous_inner_html: "{body}"},
+                }
+            }
+        })}
+    }
+}
+
+/// Wrap a rendered resource table with the page size the user chose
+fn table_preferences_body(
+    resource: &str,
+    body: &str,
+    preferences: &UserPreferences,
+) -> Result<StackString, Error> {
+    let mut app = VirtualDom::new_with_props(
+        TablePreferencesElement,
+        TablePreferencesElementProps {
+            resource: resource.into(),
+            rows_per_page: preferences.rows_per_page,
+            body: body.into(),
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer.into())
+}
+
+#[component]
+fn TablePreferencesElement(
+    resource: StackString,
+    rows_per_page: Option<i32>,
+    body: StackString,
+) -> Element {
+    let rows = rows_per_page.map_or_else(StackString::new, |r| format_sstr!("{r}"));
+    rsx! {
+        div {
+            id: "table-{resource}",
+            class: "table-preferences",
+            "data-rows-per-page": "{rows}",
+            dangerous_inner_html: "{body}",
+        }
+    }
+}
+
+fn cache_status_body(
+    resource_type: ResourceType,
+    status: StackString,
+    body: StackString,
+) -> Result<StackString, Error> {
+    let mut app = VirtualDom::new_with_props(
+        CacheStatusElement,
+        CacheStatusElementProps {
+            resource: resource_type.into(),
+            status,
+            body,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer.into())
+}
+
+#[component]
+fn CacheStatusElement(resource: StackString, status: StackString, body: StackString) -> Element {
+    rsx! {
+        div {
+            class: "cache-status",
+            "{status} ",
+            {action_button("list", "Refresh", &[("resource", resource.as_str()), ("refresh", "true")])},
+        },
+        div {dangerous_inner_html: "{body}"},
+    }
+}
+
+/// Render the table of `resource_type` without the `hidden` columns
 /// # Errors
 /// Returns error if db query fails
 pub async fn get_frontpage(
     resource_type: ResourceType,
     aws: &AwsAppInterface,
+    hidden: HiddenColumns,
 ) -> Result<StackString, Error> {
     let body = match resource_type {
         ResourceType::Instances | ResourceType::All => {
-            aws.fill_instance_list().await?;
-            let instances = INSTANCE_LIST.read().await.clone();
-            let mut app =
-                VirtualDom::new_with_props(ListInstanceBody, ListInstanceBodyProps { instances });
-            app.rebuild_in_place();
-            let mut renderer = dioxus_ssr::Renderer::default();
-            let mut buffer = String::new();
-            renderer.render_to(&mut buffer, &app)?;
-            buffer
+            get_instances_frontpage(aws, InstanceFilter::default(), hidden).await?
         }
         ResourceType::Reserved => {
             let reserved: Vec<_> = aws.ec2.get_reserved_instances().await?.collect();
             if reserved.is_empty() {
                 return Ok(StackString::new());
             }
-            let mut app =
-                VirtualDom::new_with_props(ReservedElement, ReservedElementProps { reserved });
+            let mut app = VirtualDom::new_with_props(
+                ReservedElement,
+                ReservedElementProps { reserved, hidden },
+            );
             app.rebuild_in_place();
             let mut renderer = dioxus_ssr::Renderer::default();
             let mut buffer = String::new();
@@ -89,7 +364,8 @@ pub async fn get_frontpage(
             if requests.is_empty() {
                 return Ok(StackString::new());
             }
-            let mut app = VirtualDom::new_with_props(SpotElement, SpotElementProps { requests });
+            let mut app =
+                VirtualDom::new_with_props(SpotElement, SpotElementProps { requests, hidden });
             app.rebuild_in_place();
             let mut renderer = dioxus_ssr::Renderer::default();
             let mut buffer = String::new();
@@ -98,7 +374,21 @@ pub async fn get_frontpage(
         }
         ResourceType::Ami => {
             let ami_tags = Box::pin(get_ami_tags(aws)).await?;
-            let mut app = VirtualDom::new_with_props(AmiElement, AmiElementProps { ami_tags });
+            let catalog = aws
+                .storage
+                .get_ami_catalog()
+                .await?
+                .into_iter()
+                .map(|entry| (entry.ami_id.clone(), entry))
+                .collect();
+            let mut app = VirtualDom::new_with_props(
+                AmiElement,
+                AmiElementProps {
+                    ami_tags,
+                    catalog,
+                    hidden,
+                },
+            );
             app.rebuild_in_place();
             let mut renderer = dioxus_ssr::Renderer::default();
             let mut buffer = String::new();
@@ -107,7 +397,7 @@ pub async fn get_frontpage(
         }
         ResourceType::Key => {
             let keys: Vec<_> = aws.ec2.get_all_key_pairs().await?.collect();
-            let mut app = VirtualDom::new_with_props(KeyElement, KeyElementProps { keys });
+            let mut app = VirtualDom::new_with_props(KeyElement, KeyElementProps { keys, hidden });
             app.rebuild_in_place();
             let mut renderer = dioxus_ssr::Renderer::default();
             let mut buffer = String::new();
@@ -116,7 +406,15 @@ pub async fn get_frontpage(
         }
         ResourceType::Volume => {
             let volumes: Vec<_> = aws.ec2.get_all_volumes().await?.collect();
-            let mut app = VirtualDom::new_with_props(VolumeElement, VolumeElementProps { volumes });
+            let protected = aws.get_protected_resources().await?;
+            let mut app = VirtualDom::new_with_props(
+                VolumeElement,
+                VolumeElementProps {
+                    volumes,
+                    protected,
+                    hidden,
+                },
+            );
             app.rebuild_in_place();
             let mut renderer = dioxus_ssr::Renderer::default();
             let mut buffer = String::new();
@@ -133,8 +431,10 @@ pub async fn get_frontpage(
                 let y = y.tags.get("Name").map_or("", StackString::as_str);
                 x.cmp(y)
             });
-            let mut app =
-                VirtualDom::new_with_props(SnapshotElement, SnapshotElementProps { snapshots });
+            let mut app = VirtualDom::new_with_props(
+                SnapshotElement,
+                SnapshotElementProps { snapshots, hidden },
+            );
             app.rebuild_in_place();
             let mut renderer = dioxus_ssr::Renderer::default();
             let mut buffer = String::new();
@@ -150,12 +450,19 @@ pub async fn get_frontpage(
                     let images: Vec<_> = aws.ecr.get_all_images(repo).await?.collect();
                     Ok(images)
                 });
-            let results: Result<Vec<Vec<ImageInfo>>, Error> = try_join_all(futures).await;
+            let results: Result<Vec<Vec<ImageInfo>>, Error> = stream::iter(futures)
+                .buffer_unordered(MAX_CONCURRENT_FETCHES)
+                .try_collect()
+                .await;
             let images: Vec<ImageInfo> = results?.into_iter().flatten().collect();
+            if let Err(e) = aws.record_ecr_images(&images).await {
+                error!("failed to record ecr image history: {e}");
+            }
             if images.is_empty() {
                 return Ok(StackString::new());
             }
-            let mut app = VirtualDom::new_with_props(EcrElement, EcrElementProps { images });
+            let mut app =
+                VirtualDom::new_with_props(EcrElement, EcrElementProps { images, hidden });
             app.rebuild_in_place();
             let mut renderer = dioxus_ssr::Renderer::default();
             let mut buffer = String::new();
@@ -177,32 +484,27 @@ pub async fn get_frontpage(
         ResourceType::User => {
             let user_name: Option<&str> = None;
             let (current_user, users) =
-                try_join!(aws.iam.get_user(user_name), aws.iam.list_users())?;
-            let users: Vec<_> = users.collect();
-            let futures: FuturesUnordered<_> = users
-                .iter()
-                .map(|u| async move {
-                    aws.iam
-                        .list_groups_for_user(u.user_name.as_str())
-                        .await
-                        .map(|g| {
-                            let groups: Vec<_> = g.collect();
-                            (u.user_name.clone(), groups)
-                        })
-                })
-                .collect();
-            let group_map: HashMap<StackString, _> = futures.try_collect().await?;
-
-            let futures: FuturesUnordered<_> = users
-                .iter()
-                .map(|u| async move {
-                    aws.iam
-                        .list_access_keys(u.user_name.as_str())
-                        .await
-                        .map(|metadata| (u.user_name.clone(), metadata))
-                })
-                .collect();
-            let key_map: HashMap<StackString, _> = futures.try_collect().await?;
+                try_join!(aws.iam.get_user(user_name), aws.list_users_with_mfa())?;
+            let group_futures = stream::iter(users.iter().map(|u| async move {
+                aws.iam
+                    .list_groups_for_user(u.user_name.as_str())
+                    .await
+                    .map(|g| {
+                        let groups: Vec<_> = g.collect();
+                        (u.user_name.clone(), groups)
+                    })
+            }))
+            .buffer_unordered(MAX_CONCURRENT_FETCHES)
+            .try_collect::<HashMap<StackString, _>>();
+            let key_futures = stream::iter(users.iter().map(|u| async move {
+                aws.iam
+                    .list_access_keys(u.user_name.as_str())
+                    .await
+                    .map(|metadata| (u.user_name.clone(), metadata))
+            }))
+            .buffer_unordered(MAX_CONCURRENT_FETCHES)
+            .try_collect::<HashMap<StackString, _>>();
+            let (group_map, key_map) = try_join!(group_futures, key_futures)?;
             let mut app = VirtualDom::new_with_props(
                 UsersElement,
                 UsersElementProps {
@@ -210,6 +512,7 @@ pub async fn get_frontpage(
                     current_user,
                     group_map,
                     key_map,
+                    hidden,
                 },
             );
             app.rebuild_in_place();
@@ -227,7 +530,11 @@ pub async fn get_frontpage(
                     .await
                     .map(|g| g.map(|group| (u.clone(), group)).collect::<Vec<_>>())
             });
-            let results: Result<Vec<_>, Error> = try_join_all(futures).await.map_err(Into::into);
+            let results: Result<Vec<_>, Error> = stream::iter(futures)
+                .buffer_unordered(MAX_CONCURRENT_FETCHES)
+                .try_collect()
+                .await
+                .map_err(Into::into);
             let user_map: HashMap<StackString, HashSet<StackString>> = results?
                 .into_iter()
                 .flatten()
@@ -242,6 +549,7 @@ pub async fn get_frontpage(
                     groups,
                     user_map,
                     users,
+                    hidden,
                 },
             );
             app.rebuild_in_place();
@@ -256,11 +564,16 @@ pub async fn get_frontpage(
                 .list_users()
                 .await?
                 .map(|user| async move { aws.iam.list_access_keys(user.user_name).await });
-            let results: Result<Vec<Vec<_>>, Error> =
-                try_join_all(futures).await.map_err(Into::into);
+            let results: Result<Vec<Vec<_>>, Error> = stream::iter(futures)
+                .buffer_unordered(MAX_CONCURRENT_FETCHES)
+                .try_collect()
+                .await
+                .map_err(Into::into);
             let keys: Vec<AccessKeyMetadata> = results?.into_iter().flatten().collect();
-            let mut app =
-                VirtualDom::new_with_props(AccessKeyElement, AccessKeyElementProps { keys });
+            let mut app = VirtualDom::new_with_props(
+                AccessKeyElement,
+                AccessKeyElementProps { keys, hidden },
+            );
             app.rebuild_in_place();
             let mut renderer = dioxus_ssr::Renderer::default();
             let mut buffer = String::new();
@@ -268,13 +581,18 @@ pub async fn get_frontpage(
             buffer
         }
         ResourceType::Route53 => {
-            let current_ip = aws.route53.get_ip_address().await?;
-            let records = aws.route53.list_all_dns_records().await?;
+            let (current_ip, records, health_checks) = try_join!(
+                aws.route53.get_ip_address(),
+                aws.route53.list_all_dns_records(),
+                aws.route53.list_health_checks(),
+            )?;
             let mut app = VirtualDom::new_with_props(
                 DnsRecordElement,
                 DnsRecordElementProps {
                     records,
                     current_ip,
+                    health_checks,
+                    hidden,
                 },
             );
             app.rebuild_in_place();
@@ -292,14 +610,23 @@ pub async fn get_frontpage(
                     h.entry(proc.name.clone()).or_default().push(proc);
                     h
                 });
-            let services = aws.systemd.list_running_services().await?;
+            let (services, timers, sockets) = try_join!(
+                aws.systemd.list_running_services(),
+                aws.systemd.list_timers(),
+                aws.systemd.list_sockets(),
+            )?;
             let config = aws.config.clone();
+            let probes = aws.systemd.probe_services(&config, &services).await;
             let mut app = VirtualDom::new_with_props(
                 SystemdElement,
                 SystemdElementProps {
                     processes,
                     services,
+                    probes,
+                    timers,
+                    sockets,
                     config,
+                    hidden,
                 },
             );
             app.rebuild_in_place();
@@ -309,13 +636,114 @@ pub async fn get_frontpage(
             buffer
         }
         ResourceType::InboundEmail => {
-            let emails = InboundEmailDB::get_all(&aws.pool, None, None)
-                .await?
-                .try_collect::<Vec<_>>()
-                .await?;
+            let emails = aws.storage.get_emails(None, None).await?;
+            let sent = aws.storage.get_sent_emails().await?;
             let mut app = VirtualDom::new_with_props(
                 InboundEmailElement,
-                InboundEmailElementProps { emails },
+                InboundEmailElementProps { emails, sent },
+            );
+            app.rebuild_in_place();
+            let mut renderer = dioxus_ssr::Renderer::default();
+            let mut buffer = String::new();
+            renderer.render_to(&mut buffer, &app)?;
+            buffer
+        }
+        ResourceType::Sqs => {
+            let queues = aws.sqs.list_queue_info().await?;
+            if queues.is_empty() {
+                return Ok(StackString::new());
+            }
+            let mut app =
+                VirtualDom::new_with_props(SqsElement, SqsElementProps { queues, hidden });
+            app.rebuild_in_place();
+            let mut renderer = dioxus_ssr::Renderer::default();
+            let mut buffer = String::new();
+            renderer.render_to(&mut buffer, &app)?;
+            buffer
+        }
+        ResourceType::Lambda => {
+            let functions: Vec<_> = aws.lambda.list_functions().await?.collect();
+            if functions.is_empty() {
+                return Ok(StackString::new());
+            }
+            let mut app =
+                VirtualDom::new_with_props(LambdaElement, LambdaElementProps { functions, hidden });
+            app.rebuild_in_place();
+            let mut renderer = dioxus_ssr::Renderer::default();
+            let mut buffer = String::new();
+            renderer.render_to(&mut buffer, &app)?;
+            buffer
+        }
+        ResourceType::Acm => {
+            let certificates = aws.acm.list_certificates().await?;
+            if certificates.is_empty() {
+                return Ok(StackString::new());
+            }
+            let mut app = VirtualDom::new_with_props(
+                AcmElement,
+                AcmElementProps {
+                    certificates,
+                    expiry_days: aws.config.certificate_expiry_days,
+                    hidden,
+                },
+            );
+            app.rebuild_in_place();
+            let mut renderer = dioxus_ssr::Renderer::default();
+            let mut buffer = String::new();
+            renderer.render_to(&mut buffer, &app)?;
+            buffer
+        }
+        ResourceType::Docker => {
+            let containers = aws.docker.list_containers().await?;
+            if containers.is_empty() {
+                return Ok(StackString::new());
+            }
+            let mut app = VirtualDom::new_with_props(
+                DockerElement,
+                DockerElementProps { containers, hidden },
+            );
+            app.rebuild_in_place();
+            let mut renderer = dioxus_ssr::Renderer::default();
+            let mut buffer = String::new();
+            renderer.render_to(&mut buffer, &app)?;
+            buffer
+        }
+        ResourceType::Ecs => {
+            let (clusters, services) =
+                try_join!(aws.ecs.list_clusters(), aws.ecs.list_all_services())?;
+            if clusters.is_empty() {
+                return Ok(StackString::new());
+            }
+            let mut app = VirtualDom::new_with_props(
+                EcsElement,
+                EcsElementProps {
+                    clusters,
+                    services,
+                    hidden,
+                },
+            );
+            app.rebuild_in_place();
+            let mut renderer = dioxus_ssr::Renderer::default();
+            let mut buffer = String::new();
+            renderer.render_to(&mut buffer, &app)?;
+            buffer
+        }
+        ResourceType::Backup => {
+            let (plans, resources, recovery_points) = try_join!(
+                aws.backup.list_backup_plans(),
+                aws.backup.list_protected_resources(),
+                aws.backup.list_recovery_points(),
+            )?;
+            let plans: Vec<_> = plans.collect();
+            let resources: Vec<_> = resources.collect();
+            let mut app = VirtualDom::new_with_props(
+                BackupElement,
+                BackupElementProps {
+                    plans,
+                    resources,
+                    recovery_points,
+                    hidden,
+                },
             );
             app.rebuild_in_place();
             let mut renderer = dioxus_ssr::Renderer::default();
@@ -327,110 +755,372 @@ pub async fn get_frontpage(
     Ok(body.into())
 }
 
-fn index_element(children: Element) -> Element {
+fn index_element(
+    children: Element,
+    accounts: &[StackString],
+    account: Option<&StackString>,
+    identity: &str,
+    identity_warning: Option<&StackString>,
+    theme: Theme,
+    csrf_token: &str,
+) -> Element {
+    let account_selector = if accounts.is_empty() {
+        None
+    } else {
+        Some(rsx! {
+            select {
+                id: "account_selector",
+                "onchange": "switchAccount(this.value);",
+                option {value: "", selected: account.is_none(), "default"},
+                {accounts.iter().enumerate().map(|(idx, name)| {
+                    let selected = account == Some(name);
+                    rsx! {
+                        option {key: "account-key-{idx}", value: "{name}", selected: selected, "{name}"}
+                    }
+                })}
+            }
+        })
+    };
+    let body_class = theme.body_class();
+    let theme_label = match theme {
+        Theme::Light => "Dark",
+        Theme::Dark => "Light",
+    };
     rsx! {
         head {
+            meta {name: "csrf-token", content: "{csrf_token}"},
+            meta {name: "viewport", content: "width=device-width, initial-scale=1"},
             style {
-                {include_str!("../../templates/style.css")}
+                {stylesheet()}
             },
         },
         body {
-            input {"type": "button", name: "list_inst", value: "Instances", "onclick": "listResource('instances')"},
-            input {"type": "button", name: "list_ami", value: "AMIs", "onclick": "listResource('ami');"},
-            input {"type": "button", name: "list_vol", value: "Volumes", "onclick": "listResource('volume');"},
-            input {"type": "button", name: "list_snap", value: "Snapshots", "onclick": "listResource('snapshot');"},
-            input {"type": "button", name: "list_ecr", value: "EcrImages", "onclick": "listResource('ecr');"},
-            input {"type": "button", name: "list_key", value: "Keys", "onclick": "listResource('key');"},
-            input {"type": "button", name: "list_reserved", value: "ReservedInstances", "onclick": "listResource('reserved');"},
-            input {"type": "button", name: "list_requests", value: "SpotRequests", "onclick": "listResource('spot');"},
-            input {"type": "button", name: "list_scripts", value: "Scripts", "onclick": "listResource('script');"},
+            class: "{body_class}",
+            div {
+                class: "identity-banner",
+                "{identity}",
+                {identity_warning.map(|warning| rsx! {
+                    span {class: "credential-warning", " {warning}"}
+                })}
+            },
+            {action_button("list", "Instances", &[("resource", "instances")])},
+            {action_button("list", "AMIs", &[("resource", "ami")])},
+            {action_button("list", "Volumes", &[("resource", "volume")])},
+            {action_button("list", "Snapshots", &[("resource", "snapshot")])},
+            {action_button("list", "EcrImages", &[("resource", "ecr")])},
+            {action_button("list", "Keys", &[("resource", "key")])},
+            {action_button("list", "ReservedInstances", &[("resource", "reserved")])},
+            {action_button("list", "SpotRequests", &[("resource", "spot")])},
+            {action_button("list", "Scripts", &[("resource", "script")])},
             br {
-            input {"type": "button", name: "list_users", value: "Users", "onclick": "listResource('user');"},
-            input {"type": "button", name: "list_groups", value: "Groups", "onclick": "listResource('group');"},
-            input {"type": "button", name: "list_access_keys", value: "AccessKey", "onclick": "listResource('access-key');"},
-            input {"type": "button", name: "list_route53", value: "DnsRecords", "onclick": "listResource('route53');"},
-            input {"type": "button", name: "list_systemd", value: "SystemD", "onclick": "listResource('systemd');"},
+            {action_button("list", "Users", &[("resource", "user")])},
+            {action_button("list", "Groups", &[("resource", "group")])},
+            {action_button("list", "AccessKey", &[("resource", "access-key")])},
+            {action_button("list", "DnsRecords", &[("resource", "route53")])},
+            {action_button("list", "SystemD", &[("resource", "systemd")])},
             input {"type": "button", name: "list_price", value: "Price", "onclick": "listAllPrices()"},
             input {"type": "button", name: "novnc", value: "NoVNC", "onclick": "noVncTab('/aws/novnc/status', 'GET')"},
-            input {"type": "button", name: "email", value: "InboundEmail", "onclick": "listResource('inbound-email');"},
+            {action_button("list", "InboundEmail", &[("resource", "inbound-email")])},
+            {action_button("ses_identities", "SES", &[])},
+            {action_button("email_rules", "EmailRules", &[])},
+            {action_button("instance_self", "Host", &[])},
+            {action_button("list", "SqsQueues", &[("resource", "sqs")])},
+            {action_button("list", "Backup", &[("resource", "backup")])},
+            {action_button("list", "Lambda", &[("resource", "lambda")])},
+            {action_button("list", "Certificates", &[("resource", "acm")])},
+            {action_button("list", "Docker", &[("resource", "docker")])},
+            {action_button("list", "ECS", &[("resource", "ecs")])},
+            {action_button("dashboard", "Dashboard", &[])},
+            input {"type": "button", name: "launch_status", value: "Launches", "onclick": "launchStatus()"},
+            {action_button("launch_analytics", "Analytics", &[])},
+            {action_button("costs_by_tag", "Costs", &[("tag", "Name")])},
+            {action_button("waste_report", "Waste", &[])},
+            {action_button("iam_report", "IAM Report", &[])},
+            {action_button("bucket_summary", "Buckets", &[])},
+            {action_button("tasks", "Tasks", &[])},
+            {action_button("webhooks", "Webhooks", &[])},
+            {action_button("price_alerts", "PriceAlerts", &[])},
+            {action_button("dr", "DR", &[])},
+            {action_button("terraform_drift", "Drift", &[])},
+            {action_button("inventory_changes", "Changes", &[])},
+            {action_button("replacements", "Replace", &[])},
+            {action_button("quotas", "Quotas", &[])},
+            {action_button("tag_search", "TagSearch", &[])},
+            input {"type": "button", name: "graph", value: "Graph", "onclick": "resourceGraph()"},
+            {action_button("secrets", "Secrets", &[])},
+            {action_button("host", "Host", &[])},
             input {"type": "button", name: "update", value: "Update", "onclick": "updateMetadata()"},
+            input {"type": "button", id: "theme_toggle", name: "theme", value: "{theme_label}", "onclick": "toggleTheme();"},
+            input {"type": "button", class: "column-toggle", name: "columns", value: "Columns", "onclick": "toggleColumns();"},
+            {action_button("preferences", "Preferences", &[])},
+            {account_selector},
             button {name: "garminconnectoutput", id: "garminconnectoutput", dangerous_inner_html: "&nbsp"},
             },
         },
         article {id: "main_article", {children}},
         article {id: "sub_article", dangerous_inner_html: "&nbsp"},
-        script {"language": "Javascript", "type": "text/javascript", dangerous_inner_html: include_str!("../../templates/scripts.js")},
+        script {"language": "Javascript", "type": "text/javascript", dangerous_inner_html: bindings_script()},
+        script {"language": "Javascript", "type": "text/javascript", dangerous_inner_html: scripts()},
     }
 }
 
 #[component]
-fn IndexListElement(instances: Arc<Vec<Ec2InstanceInfo>>) -> Element {
+fn IndexListElement(
+    body: StackString,
+    accounts: Vec<StackString>,
+    account: Option<StackString>,
+    identity: StackString,
+    identity_warning: Option<StackString>,
+    theme: Theme,
+    csrf_token: StackString,
+) -> Element {
     rsx! {
         {index_element(
-            list_instance_element(&instances)
+            rsx! {div {dangerous_inner_html: "{body}"}},
+            &accounts,
+            account.as_ref(),
+            &identity,
+            identity_warning.as_ref(),
+            theme,
+            &csrf_token,
         )}
     }
 }
 
 #[component]
-fn ListInstanceBody(instances: Arc<Vec<Ec2InstanceInfo>>) -> Element {
-    list_instance_element(&instances)
+fn ListInstanceBody(
+    instances: Arc<Vec<Ec2InstanceInfo>>,
+    protected: HashSet<StackString>,
+    filter: InstanceFilter,
+    hidden: HiddenColumns,
+) -> Element {
+    list_instance_element(&instances, &protected, &filter, &hidden)
 }
 
-fn list_instance_element(instances: &[Ec2InstanceInfo]) -> Element {
-    let local_tz = DateTimeWrapper::local_tz();
-    let empty: StackString = "".into();
+/// The instance list ordered by the sort of `filter`
+async fn get_instances_frontpage(
+    aws: &AwsAppInterface,
+    filter: InstanceFilter,
+    hidden: HiddenColumns,
+) -> Result<String, Error> {
+    aws.fill_instance_list().await?;
+    let mut instances = INSTANCE_LIST.read().await.clone();
+    filter.sort(Arc::make_mut(&mut instances));
+    let protected = aws.get_protected_resources().await?;
+    let mut app = VirtualDom::new_with_props(
+        ListInstanceBody,
+        ListInstanceBodyProps {
+            instances,
+            protected,
+            filter,
+            hidden,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn instance_list_body(
+    instances: Vec<Ec2InstanceInfo>,
+    protected: HashSet<StackString>,
+    filter: InstanceFilter,
+    hidden: HiddenColumns,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        ListInstanceBody,
+        ListInstanceBodyProps {
+            instances: Arc::new(instances),
+            protected,
+            filter,
+            hidden,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+/// Inputs above the instance table, `filterInstances` reloads the table from
+/// `/aws/instance_list` with their values
+fn instance_filter_element(filter: &InstanceFilter) -> Element {
+    let state = filter.state.as_deref().unwrap_or("");
+    let name = filter.name.as_deref().unwrap_or("");
+    let instance_type = filter.instance_type.as_deref().unwrap_or("");
+    let availability_zone = filter.availability_zone.as_deref().unwrap_or("");
+    let states = [
+        "pending",
+        "running",
+        "stopping",
+        "stopped",
+        "shutting-down",
+        "terminated",
+    ];
     rsx! {
-        table {
-            "border": "1",
-            class: "dataframe",
-            thead {
-                tr {
-                    th {"Instance Id"},
-                    th {"Public Hostname"},
-                    th {"State"},
-                    th {"Name"},
-                    th {"Instance Type"},
-                    th {"Created At"},
-                    th {"Availability Zone"},
-                }
+        div {
+            class: "instance-filter",
+            "State ",
+            select {
+                id: "instance_filter_state",
+                option {value: "", selected: state.is_empty(), "any"},
+                {states.iter().enumerate().map(|(idx, s)| {
+                    rsx! {
+                        option {key: "filter-state-{idx}", value: "{s}", selected: state == *s, "{s}"}
+                    }
+                })},
             },
-            tbody {
-                {instances.iter().enumerate().map(|(idx, inst)| {
-                    let inst_id = &inst.id;
-                    let status_button = if &inst.state == "running" {
-                        Some(rsx! {
-                            input {
-                                "type": "button",
-                                name: "status",
-                                value: "Status",
-                                "onclick": "getStatus('{inst_id}')",
-                            }
-                        })
-                    } else {None};
-                    let name = inst.tags.get("Name").unwrap_or(&empty);
-                    let name_button = if &inst.state == "running" && name != "ddbolineinthecloud" {
-                        rsx! {
-                            input {
-                                "type": "button",
-                                name: "CreateImage {name}",
-                                value: "{name}",
-                                "onclick": "createImage('{inst_id}', '{name}')",
-                            }
+            " Name ",
+            input {"type": "text", id: "instance_filter_name", value: "{name}", size: "12"},
+            " Type ",
+            input {"type": "text", id: "instance_filter_type", value: "{instance_type}", size: "10"},
+            " AZ ",
+            input {"type": "text", id: "instance_filter_az", value: "{availability_zone}", size: "10"},
+            " Sort ",
+            select {
+                id: "instance_filter_sort",
+                {INSTANCE_SORT_KEYS.iter().enumerate().map(|(idx, key)| {
+                    let key = key.to_str();
+                    rsx! {
+                        option {
+                            key: "filter-sort-{idx}",
+                            value: "{key}",
+                            selected: filter.sort.to_str() == key,
+                            "{key}"
                         }
-                    } else {
-                        rsx! {"{name}"}
-                    };
-                    let terminate_button = if &inst.state == "running" && name != "ddbolineinthecloud" {
-                        Some(rsx! {
-                            input {
-                                "type": "button",
-                                name: "Terminate",
-                                value: "Terminate",
-                                "onclick": "terminateInstance('{inst_id}')",
-                            }
-                        })
-                    } else {None};
+                    }
+                })},
+            },
+            input {"type": "checkbox", id: "instance_filter_desc", checked: filter.descending},
+            "desc ",
+            input {
+                "type": "button",
+                name: "instance_filter",
+                value: "Filter",
+                "onclick": "filterInstances();",
+            },
+        }
+    }
+}
+
+/// Buttons acting on the rows of a table selected with `batch_checkbox`,
+/// `resource` is the argument `listResource` takes for the table
+fn batch_actions_element(resource: &str, actions: &[(&str, &str)]) -> Element {
+    rsx! {
+        div {
+            class: "batch-actions",
+            "Selected: ",
+            {actions.iter().enumerate().map(|(idx, (action, label))| {
+                rsx! {
+                    input {
+                        key: "batch-{resource}-{idx}",
+                        "type": "button",
+                        name: "batch_{action}",
+                        value: "{label}",
+                        "onclick": "batchAction('{resource}', '{action}');",
+                    }
+                }
+            })},
+            input {"type": "text", id: "batch_tag_{resource}", placeholder: "Name tag"},
+            input {
+                "type": "button",
+                name: "batch_tag",
+                value: "Tag",
+                "onclick": "batchAction('{resource}', 'tag');",
+            },
+        }
+    }
+}
+
+/// Button calling the route bound to `action` in `bindings::BINDINGS`
+fn action_button(action: &str, label: &str, params: &[(&str, &str)]) -> Element {
+    debug_assert!(get_binding(action).is_some(), "no binding for {action}");
+    let params = action_params(params);
+    rsx! {
+        input {
+            "type": "button",
+            name: "{action}",
+            value: "{label}",
+            "data-action": "{action}",
+            "data-params": "{params}",
+        }
+    }
+}
+
+/// Link downloading the table from `/aws/export/{resource}.csv`
+fn export_link(resource: &str, search: Option<&str>) -> Element {
+    let href = match search {
+        Some(search) => format_sstr!("/aws/export/{resource}.csv?search={search}"),
+        None => format_sstr!("/aws/export/{resource}.csv"),
+    };
+    rsx! {
+        a {class: "export-csv", href: "{href}", "Export CSV"}
+    }
+}
+
+fn batch_select_all(resource: &str) -> Element {
+    rsx! {
+        input {"type": "checkbox", "onclick": "batchToggle('{resource}', this.checked);"}
+    }
+}
+
+fn batch_checkbox(resource: &str, id: &str) -> Element {
+    rsx! {
+        input {"type": "checkbox", class: "batch-{resource}", value: "{id}"}
+    }
+}
+
+fn list_instance_element(
+    instances: &[Ec2InstanceInfo],
+    protected: &HashSet<StackString>,
+    filter: &InstanceFilter,
+    hidden: &HiddenColumns,
+) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
+    let empty: StackString = "".into();
+    rsx! {
+        {instance_filter_element(filter)},
+        {batch_actions_element("instances", &[("terminate", "Terminate")])},
+        {export_link("instances", None)},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {{batch_select_all("instances")}},
+                    if hidden.show("Instance Id") { th {"Instance Id"} },
+                    if hidden.show("Public Hostname") { th {class: "optional-col", "Public Hostname"} },
+                    if hidden.show("State") { th {"State"} },
+                    if hidden.show("Name") { th {"Name"} },
+                    if hidden.show("Instance Type") { th {"Instance Type"} },
+                    if hidden.show("Created At") { th {class: "optional-col", "Created At"} },
+                    if hidden.show("Availability Zone") { th {class: "optional-col", "Availability Zone"} },
+                }
+            },
+            tbody {
+                {instances.iter().enumerate().map(|(idx, inst)| {
+                    let inst_id = &inst.id;
+                    let status_button = if &inst.state == "running" {
+                        Some(action_button("instance_status", "Status", &[("instance", inst_id.as_str())]))
+                    } else {None};
+                    let name = inst.tags.get("Name").unwrap_or(&empty);
+                    let is_protected = is_protected(protected, inst_id, Some(name));
+                    let name_button = if &inst.state == "running" && !is_protected {
+                        action_button("create_image", name, &[("inst_id", inst_id.as_str()), ("name", name.as_str())])
+                    } else {
+                        rsx! {"{name}"}
+                    };
+                    let terminate_button = if &inst.state == "running" && !is_protected {
+                        Some(action_button("terminate", "Terminate", &[("instance", inst_id.as_str())]))
+                    } else {None};
+                    let decommission_button = if matches!(inst.state.as_str(), "running" | "stopped") && !is_protected {
+                        Some(action_button("decommission_plan", "Decommission", &[("instance", inst_id.as_str())]))
+                    } else {None};
                     let dn = &inst.dns_name;
                     let st = &inst.state;
                     let it = &inst.instance_type;
@@ -440,15 +1130,17 @@ fn list_instance_element(instances: &[Ec2InstanceInfo]) -> Element {
                         tr {
                             key: "instance-list-key-{idx}",
                             style: "text-align: center;",
-                            td {"{inst_id}"},
-                            td {"{dn}"},
-                            td {"{st}"},
-                            td {{name_button}},
-                            td {"{it}"},
-                            td {"{lt}"},
-                            td {"{az}"},
+                            td {{batch_checkbox("instances", inst_id.as_str())}},
+                            if hidden.show("Instance Id") { td {"{inst_id}"} },
+                            if hidden.show("Public Hostname") { td {class: "optional-col", "{dn}"} },
+                            if hidden.show("State") { td {"{st}"} },
+                            if hidden.show("Name") { td {{name_button}} },
+                            if hidden.show("Instance Type") { td {"{it}"} },
+                            if hidden.show("Created At") { td {class: "optional-col", "{lt}"} },
+                            if hidden.show("Availability Zone") { td {class: "optional-col", "{az}"} },
                             td {{status_button}},
                             td {{terminate_button}},
+                            td {{decommission_button}},
                         }
                     }
                 })}
@@ -458,18 +1150,18 @@ fn list_instance_element(instances: &[Ec2InstanceInfo]) -> Element {
 }
 
 #[component]
-fn ReservedElement(reserved: Vec<ReservedInstanceInfo>) -> Element {
+fn ReservedElement(reserved: Vec<ReservedInstanceInfo>, hidden: HiddenColumns) -> Element {
     rsx! {
         table {
             "border": "1",
             class: "dataframe",
             thead {
                 tr {
-                    th {"Reserved Instance Id"},
-                    th {"Price"},
-                    th {"Instance Type"},
-                    th {"State"},
-                    th {"Availability Zone"},
+                    if hidden.show("Reserved Instance Id") { th {"Reserved Instance Id"} },
+                    if hidden.show("Price") { th {"Price"} },
+                    if hidden.show("Instance Type") { th {"Instance Type"} },
+                    if hidden.show("State") { th {"State"} },
+                    if hidden.show("Availability Zone") { th {"Availability Zone"} },
                 }
             },
             tbody {
@@ -485,11 +1177,11 @@ fn ReservedElement(reserved: Vec<ReservedInstanceInfo>) -> Element {
                         tr {
                             key: "reserved-key-{idx}",
                             "style": "text-align: center;",
-                            td {"{id}"},
-                            td {"{price}"},
-                            td {"{instance_type}"},
-                            td {"{state}"},
-                            td {"{ad}"},
+                            if hidden.show("Reserved Instance Id") { td {"{id}"} },
+                            if hidden.show("Price") { td {"{price}"} },
+                            if hidden.show("Instance Type") { td {"{instance_type}"} },
+                            if hidden.show("State") { td {"{state}"} },
+                            if hidden.show("Availability Zone") { td {"{ad}"} },
                         }
                     }
                 })}
@@ -499,19 +1191,19 @@ fn ReservedElement(reserved: Vec<ReservedInstanceInfo>) -> Element {
 }
 
 #[component]
-fn SpotElement(requests: Vec<SpotInstanceRequestInfo>) -> Element {
+fn SpotElement(requests: Vec<SpotInstanceRequestInfo>, hidden: HiddenColumns) -> Element {
     rsx! {
         table {
             "border": "1",
             class: "dataframe",
             thead {
                 tr {
-                    th {"Spot Request Id"},
-                    th {"Price"},
-                    th {"AMI"},
-                    th {"Instance Type"},
-                    th {"Spot Type"},
-                    th {"Status"},
+                    if hidden.show("Spot Request Id") { th {"Spot Request Id"} },
+                    if hidden.show("Price") { th {"Price"} },
+                    if hidden.show("AMI") { th {"AMI"} },
+                    if hidden.show("Instance Type") { th {"Instance Type"} },
+                    if hidden.show("Spot Type") { th {"Spot Type"} },
+                    if hidden.show("Status") { th {"Status"} },
                 }
             }
             tbody {
@@ -523,26 +1215,21 @@ fn SpotElement(requests: Vec<SpotInstanceRequestInfo>) -> Element {
                     let st = &req.spot_type;
                     let s = &req.status;
                     let pf = match req.status.as_str() {
-                        "pending" | "pending-fulfillment" | "capacity-not-available" => Some(rsx! {
-                            input {
-                                "type": "button",
-                                name: "cancel",
-                                value: "Cancel",
-                                "onclick": "cancelSpotRequest('{id}')",
-                            }
-                        }),
+                        "pending" | "pending-fulfillment" | "capacity-not-available" => {
+                            Some(action_button("cancel_spot", "Cancel", &[("spot_id", id.as_str())]))
+                        }
                         _ => None,
                     };
                     rsx! {
                         tr {
                             key: "requests-key-{idx}",
                             style: "text-align: center;",
-                            td {"{id}"},
-                            td {"${pr}"},
-                            td {"{im}"},
-                            td {"{it}"},
-                            td {"{st}"},
-                            td {"{s}"},
+                            if hidden.show("Spot Request Id") { td {"{id}"} },
+                            if hidden.show("Price") { td {"${pr}"} },
+                            if hidden.show("AMI") { td {"{im}"} },
+                            if hidden.show("Instance Type") { td {"{it}"} },
+                            if hidden.show("Spot Type") { td {"{st}"} },
+                            if hidden.show("Status") { td {"{s}"} },
                             td {{pf}},
                         }
                     }
@@ -553,7 +1240,11 @@ fn SpotElement(requests: Vec<SpotInstanceRequestInfo>) -> Element {
 }
 
 #[component]
-fn AmiElement(ami_tags: Vec<AmiInfo>) -> Element {
+fn AmiElement(
+    ami_tags: Vec<AmiInfo>,
+    catalog: HashMap<StackString, AmiCatalogEntry>,
+    hidden: HiddenColumns,
+) -> Element {
     rsx! {
         table {
             "border": "1",
@@ -562,10 +1253,15 @@ fn AmiElement(ami_tags: Vec<AmiInfo>) -> Element {
                 tr {
                     th {},
                     th {},
-                    th {"AMI"},
-                    th {"Name"},
-                    th {"State"},
-                    th {"Snapshot ID"},
+                    th {},
+                    if hidden.show("AMI") { th {"AMI"} },
+                    if hidden.show("Name") { th {"Name"} },
+                    if hidden.show("State") { th {"State"} },
+                    if hidden.show("Snapshot ID") { th {"Snapshot ID"} },
+                    if hidden.show("Base Release") { th {"Base Release"} },
+                    if hidden.show("Build Script") { th {"Build Script"} },
+                    if hidden.show("Build Date") { th {"Build Date"} },
+                    if hidden.show("Lineage") { th {"Lineage"} },
                 },
             },
             tbody {
@@ -574,18 +1270,42 @@ fn AmiElement(ami_tags: Vec<AmiInfo>) -> Element {
                     let nm = &ami.name;
                     let st = &ami.state;
                     let sn = ami.snapshot_ids.join(" ");
+                    let entry = catalog.get(id);
+                    let base = entry.and_then(|e| e.base_release.as_deref()).unwrap_or("");
+                    let script = entry.and_then(|e| e.build_script.as_deref()).unwrap_or("");
+                    let built = entry.map_or_else(StackString::new, |e| {
+                        format_sstr!("{}", e.build_date.date())
+                    });
+                    let rebuild = entry.and_then(|e| {
+                        let parent = e.parent_ami.as_ref()?;
+                        let script = e.build_script.as_ref()?;
+                        Some(rsx! {
+                            input {
+                                "type": "button",
+                                name: "Rebuild",
+                                value: "Rebuild",
+                                title: "Request {parent} with {script}",
+                                "onclick": "buildSpotRequest('{parent}', null, '{script}')",
+                            }
+                        })
+                    });
+                    let ancestors = lineage(&catalog, id);
+                    let root = ancestors
+                        .last()
+                        .copied()
+                        .or(entry)
+                        .and_then(|e| e.parent_ami.as_ref());
+                    let lineage: Vec<StackString> = ancestors
+                        .iter()
+                        .map(|e| e.name.clone())
+                        .chain(root.cloned())
+                        .collect();
+                    let lineage = lineage.join(" < ");
                     rsx! {
                         tr {
                             key: "ami-tags-key-{idx}",
                             style: "text-align: center;",
-                            td {
-                                input {
-                                    "type": "button",
-                                    name: "DeleteImage",
-                                    value: "DeleteImage",
-                                    "onclick": "deleteImage('{id}')",
-                                }
-                            },
+                            td {{action_button("delete_image", "DeleteImage", &[("ami", id.as_str())])}},
                             td {
                                 input {
                                     "type": "button",
@@ -594,10 +1314,15 @@ fn AmiElement(ami_tags: Vec<AmiInfo>) -> Element {
                                     "onclick": "buildSpotRequest('{id}', null, null)",
                                 }
                             },
-                            td {"{id}"},
-                            td {"{nm}"},
-                            td {"{st}"},
-                            td {"{sn}"},
+                            td {{rebuild}},
+                            if hidden.show("AMI") { td {"{id}"} },
+                            if hidden.show("Name") { td {"{nm}"} },
+                            if hidden.show("State") { td {"{st}"} },
+                            if hidden.show("Snapshot ID") { td {"{sn}"} },
+                            if hidden.show("Base Release") { td {"{base}"} },
+                            if hidden.show("Build Script") { td {"{script}"} },
+                            if hidden.show("Build Date") { td {"{built}"} },
+                            if hidden.show("Lineage") { td {"{lineage}"} },
                         }
                     }
                 })}
@@ -607,15 +1332,40 @@ fn AmiElement(ami_tags: Vec<AmiInfo>) -> Element {
 }
 
 #[component]
-fn KeyElement(keys: Vec<(StackString, StackString)>) -> Element {
+fn KeyElement(keys: Vec<(StackString, StackString)>, hidden: HiddenColumns) -> Element {
     rsx! {
+        div {
+            class: "key-pair-form",
+            "Key Name ",
+            input {"type": "text", id: "key_pair_name", size: "16"},
+            input {
+                "type": "button",
+                name: "create_key_pair",
+                value: "Create",
+                "onclick": "createKeyPair(document.getElementById('key_pair_name').value);",
+            },
+            br {},
+            textarea {
+                id: "key_pair_public_key",
+                rows: "3",
+                cols: "80",
+                placeholder: "ssh-ed25519 AAAA... user@host",
+            },
+            input {
+                "type": "button",
+                name: "import_key_pair",
+                value: "Import",
+                "onclick": "importKeyPair(document.getElementById('key_pair_name').value, document.getElementById('key_pair_public_key').value);",
+            },
+        },
         table {
             "border": "1",
             class: "dataframe",
             thead {
                 tr {
-                    th {"Key Name"}
-                    th {"Key Fingerprint"},
+                    if hidden.show("Key Name") { th {"Key Name"} },
+                    if hidden.show("Key Fingerprint") { th {"Key Fingerprint"} },
+                    th {},
                 }
            },
            tbody {
@@ -624,8 +1374,9 @@ fn KeyElement(keys: Vec<(StackString, StackString)>) -> Element {
                     tr {
                         key: "key-{idx}",
                         style: "text-align: center;",
-                        td {"{key}"},
-                        td {"{fingerprint}"},
+                        if hidden.show("Key Name") { td {"{key}"} },
+                        if hidden.show("Key Fingerprint") { td {"{fingerprint}"} },
+                        td {{action_button("delete_key_pair", "Delete", &[("key_name", key.as_str())])}},
                     }
                 }
             })}
@@ -635,21 +1386,29 @@ fn KeyElement(keys: Vec<(StackString, StackString)>) -> Element {
 }
 
 #[component]
-fn VolumeElement(volumes: Vec<VolumeInfo>) -> Element {
+fn VolumeElement(
+    volumes: Vec<VolumeInfo>,
+    protected: HashSet<StackString>,
+    hidden: HiddenColumns,
+) -> Element {
     let local_tz = DateTimeWrapper::local_tz();
     rsx! {
+        {batch_actions_element("volume", &[("delete_volume", "Delete")])},
+        {export_link("volumes", None)},
         table {
             "border": "1",
             class: "dataframe",
             thead {
                 tr {
+                    th {{batch_select_all("volume")}},
                     th {},
-                    th {"Volume ID"},
-                    th {"Availability Zone"},
+                    if hidden.show("Volume ID") { th {"Volume ID"} },
+                    if hidden.show("Availability Zone") { th {"Availability Zone"} },
                     th {"Size"},
-                    th {"IOPS"},
-                    th {"State"},
-                    th {"Tags"},
+                    if hidden.show("IOPS") { th {"IOPS"} },
+                    if hidden.show("State") { th {"State"} },
+                    if hidden.show("Encrypted") { th {"Encrypted"} },
+                    if hidden.show("Tags") { th {"Tags"} },
                 }
             }
             tbody {
@@ -667,17 +1426,20 @@ fn VolumeElement(volumes: Vec<VolumeInfo>) -> Element {
                     let az = &vol.availability_zone;
                     let io = vol.iops;
                     let st = &vol.state;
-                    let bt = if vol.tags.get("Name").map(StackString::as_str) == Some("ddbolineinthecloud") {
+                    let encrypted = if vol.encrypted {
+                        rsx! {"yes"}
+                    } else {
+                        rsx! {
+                            "no ",
+                            {action_button("reencrypt_volume", "Encrypt", &[("volid", id.as_str())])}
+                        }
+                    };
+                    let name = vol.tags.get("Name");
+                    let is_protected = is_protected(&protected, id, name);
+                    let bt = if is_protected {
                         None
                     } else {
-                        Some(rsx! {
-                            input {
-                                "type": "button",
-                                name: "DeleteVolume",
-                                value: "DeleteVolume",
-                                "onclick": "deleteVolume('{id}')",
-                            }
-                        })
+                        Some(action_button("delete_volume", "DeleteVolume", &[("volid", id.as_str())]))
                     };
                     let tg = if vol.tags.is_empty() {
                         rsx! {
@@ -694,11 +1456,12 @@ fn VolumeElement(volumes: Vec<VolumeInfo>) -> Element {
                             "{tags}"
                         }
                     };
-                    let sp = if let Some("ddbolineinthecloud") = vol.tags.get("Name").map(StackString::as_str) {
+                    let sp = if is_protected {
                         let ymd = format_description!("[year][month][day]");
                         let local = OffsetDateTime::now_utc().to_timezone(local_tz);
                         let local = local.date().format(ymd).unwrap_or_else(|_| String::new());
-                        let dt = format_sstr!("dileptoninthecloud_backup_{local}");
+                        let prefix = name.unwrap_or(id);
+                        let dt = format_sstr!("{prefix}_backup_{local}");
                         Some(rsx! {
                             input {
                                 "type": "button", name: "CreateSnapshot", value: "CreateSnapshot",
@@ -717,18 +1480,20 @@ fn VolumeElement(volumes: Vec<VolumeInfo>) -> Element {
                         tr {
                             key: "volumes-key-{idx}",
                             style: "text-align: center;",
+                            td {{batch_checkbox("volume", id.as_str())}},
                             td {{bt}},
-                            td {"{id}"},
-                            td {"{az}"},
+                            if hidden.show("Volume ID") { td {"{id}"} },
+                            if hidden.show("Availability Zone") { td {"{az}"} },
                             td {
                                 select {
                                     id: "{id}_vol_size",
                                     {vs},
                                 }
                             },
-                            td {"{io}"},
-                            td {"{st}"},
-                            td {{tg}},
+                            if hidden.show("IOPS") { td {"{io}"} },
+                            if hidden.show("State") { td {"{st}"} },
+                            if hidden.show("Encrypted") { td {{encrypted}} },
+                            if hidden.show("Tags") { td {{tg}} },
                             td {{sp}},
                         }
                     }
@@ -741,19 +1506,24 @@ fn VolumeElement(volumes: Vec<VolumeInfo>) -> Element {
 }
 
 #[component]
-fn SnapshotElement(snapshots: Vec<SnapshotInfo>) -> Element {
+fn SnapshotElement(snapshots: Vec<SnapshotInfo>, hidden: HiddenColumns) -> Element {
     rsx! {
+        {batch_actions_element("snapshot", &[("delete_snapshot", "Delete")])},
+        {export_link("snapshots", None)},
         table {
             "border": "1",
             class: "dataframe",
             thead {
                 tr {
+                    th {{batch_select_all("snapshot")}},
                     th {},
-                    th {"Snapshot ID"},
-                    th {"Size"},
-                    th {"State"},
-                    th {"Progress"},
-                    th {"Tags"},
+                    if hidden.show("Snapshot ID") { th {"Snapshot ID"} },
+                    if hidden.show("Size") { th {"Size"} },
+                    if hidden.show("State") { th {"State"} },
+                    if hidden.show("Progress") { th {"Progress"} },
+                    if hidden.show("Encrypted") { th {"Encrypted"} },
+                    if hidden.show("Tags") { th {"Tags"} },
+                    if hidden.show("Copy To Region") { th {"Copy To Region"} },
                 }
             },
             tbody {
@@ -762,6 +1532,7 @@ fn SnapshotElement(snapshots: Vec<SnapshotInfo>) -> Element {
                     let vs = snap.volume_size;
                     let st = &snap.state;
                     let pr = &snap.progress;
+                    let encrypted = if snap.encrypted {"yes"} else {"no"};
                     let tg = if snap.tags.is_empty() {
                         rsx! {
                             input {
@@ -779,16 +1550,24 @@ fn SnapshotElement(snapshots: Vec<SnapshotInfo>) -> Element {
                         tr {
                             key: "snapshot-key-{idx}",
                             style: "text-align: center;",
-                            td {
-                                input {
-                                    "type": "button", name: "DeleteSnapshot", value: "DeleteSnapshot", "onclick": "deleteSnapshot('{id}')",
+                            td {{batch_checkbox("snapshot", id.as_str())}},
+                            td {{action_button("delete_snapshot", "DeleteSnapshot", &[("snapid", id.as_str())])}},
+                            if hidden.show("Snapshot ID") { td {"{id}"} },
+                            if hidden.show("Size") { td {"{vs} GB"} },
+                            if hidden.show("State") { td {"{st}"} },
+                            if hidden.show("Progress") { td {"{pr}"} },
+                            if hidden.show("Encrypted") { td {"{encrypted}"} },
+                            if hidden.show("Tags") { td {{tg}} },
+                            if hidden.show("Copy To Region") {
+                                td {
+                                    input {
+                                        "type": "text", name: "copy_snapshot", id: "{id}_copy_region", size: "12",
+                                    }
+                                    input {
+                                        "type": "button", name: "copy_snapshot", value: "Copy", "onclick": "copySnapshot('{id}');",
+                                    }
                                 }
                             },
-                            td {"{id}"}
-                            td {"{vs} GB"}
-                            td {"{st}"}
-                            td {"{pr}"}
-                            td {{tg}},
                         }
                     }
                 }
@@ -800,21 +1579,22 @@ fn SnapshotElement(snapshots: Vec<SnapshotInfo>) -> Element {
 }
 
 #[component]
-fn EcrElement(images: Vec<ImageInfo>) -> Element {
+fn EcrElement(images: Vec<ImageInfo>, hidden: HiddenColumns) -> Element {
     rsx! {
+        {export_link("ecr", None)},
         table {
             "border": "1",
             class: "dataframe",
             thead {
                 tr {
-                    th {
-                        input {"type": "button", name: "CleanupEcr", value: "CleanupEcr", "onclick": "cleanupEcrImages()"}
-                    },
-                    th {"ECR Repo"},
-                    th {"Tag"},
-                    th {"Digest"},
-                    th {"Pushed At"},
-                    th {"Image Size"},
+                    th {{action_button("cleanup_ecr_images", "CleanupEcr", &[])}},
+                    if hidden.show("ECR Repo") { th {"ECR Repo"} },
+                    if hidden.show("Tag") { th {"Tag"} },
+                    if hidden.show("Digest") { th {"Digest"} },
+                    if hidden.show("Pushed At") { th {"Pushed At"} },
+                    if hidden.show("Image Size") { th {"Image Size"} },
+                    th {},
+                    th {},
                 }
             },
             tbody {
@@ -828,19 +1608,90 @@ fn EcrElement(images: Vec<ImageInfo>) -> Element {
                         tr {
                             key: "images-key-{idx}",
                             style: "text-align: center;",
-                            td {
-                                input {
-                                    "type": "button",
-                                    name: "DeleteEcrImage",
-                                    value: "DeleteEcrImage",
-                                    "onclick": "deleteEcrImage('{repo}', '{digest}')",
-                                }
-                            },
-                            td {"{repo}"},
-                            td {"{tag}"},
-                            td {"{digest}"},
-                            td {"{pushed_at}"},
+                            td {{action_button(
+                                "delete_ecr_image",
+                                "DeleteEcrImage",
+                                &[("reponame", repo.as_str()), ("imageid", digest.as_str())],
+                            )}},
+                            if hidden.show("ECR Repo") { td {"{repo}"} },
+                            if hidden.show("Tag") { td {"{tag}"} },
+                            if hidden.show("Digest") { td {"{digest}"} },
+                            if hidden.show("Pushed At") { td {"{pushed_at}"} },
+                            if hidden.show("Image Size") { td {"{image_size}"} },
+                            td {{action_button(
+                                "docker_pull",
+                                "Pull",
+                                &[("reponame", repo.as_str()), ("tag", tag)],
+                            )}},
+                            td {{action_button("ecr_history", "History", &[("repo", repo.as_str())])}},
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn ecr_history_body(repo: StackString, events: Vec<EcrImageEvent>) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(EcrHistoryElement, EcrHistoryElementProps { repo, events });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+/// Tags which moved to a new digest are shown in red
+#[component]
+fn EcrHistoryElement(repo: StackString, events: Vec<EcrImageEvent>) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
+    rsx! {
+        h3 {"Image history of {repo}"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Recorded At"},
+                    th {"Event"},
+                    th {"Digest"},
+                    th {"Tags"},
+                    th {"Image Size"},
+                    th {"Size Change"},
+                    th {"Moved Tags"},
+                }
+            },
+            tbody {
+                {events.iter().enumerate().map(|(idx, event)| {
+                    let recorded_at = event.recorded_at.to_timezone(local_tz);
+                    let kind = if event.pushed {"push"} else {"retag"};
+                    let full_digest = &event.digest;
+                    let digest = short_digest(full_digest);
+                    let tags = event.tags.join(" ");
+                    let image_size = format_sstr!("{:0.2} MB", event.image_size);
+                    let size_delta = event
+                        .size_delta
+                        .map_or_else(StackString::new, |d| format_sstr!("{d:+0.2} MB"));
+                    let moved_tags: Vec<_> = event
+                        .moved_tags
+                        .iter()
+                        .map(|(tag, from)| format_sstr!("{tag} was {}", short_digest(from)))
+                        .collect();
+                    let moved_tags = moved_tags.join(", ");
+                    rsx! {
+                        tr {
+                            key: "ecr-history-key-{idx}",
+                            style: "text-align: center;",
+                            td {"{recorded_at}"},
+                            td {"{kind}"},
+                            td {title: "{full_digest}", "{digest}"},
+                            td {"{tags}"},
                             td {"{image_size}"},
+                            td {"{size_delta}"},
+                            td {class: "credential-warning", "{moved_tags}"},
                         }
                     }
                 })}
@@ -849,6 +1700,11 @@ fn EcrElement(images: Vec<ImageInfo>) -> Element {
     }
 }
 
+/// `sha256:` and the first 12 hex digits
+fn short_digest(digest: &str) -> &str {
+    digest.get(..19).unwrap_or(digest)
+}
+
 #[component]
 fn ScriptElement(scripts: Vec<StackString>) -> Element {
     rsx! {
@@ -890,6 +1746,7 @@ fn UsersElement(
     current_user: Option<IamUser>,
     group_map: HashMap<StackString, Vec<IamGroup>>,
     key_map: HashMap<StackString, Vec<AccessKeyMetadata>>,
+    hidden: HiddenColumns,
 ) -> Element {
     let empty_vec: Vec<AccessKeyMetadata> = Vec::new();
     rsx! {
@@ -898,10 +1755,11 @@ fn UsersElement(
             class: "dataframe",
             thead {
                 tr {
-                    th {"User ID"},
-                    th {"Create Date"},
-                    th {"User Name"},
-                    th {"Arn"},
+                    if hidden.show("User ID") { th {"User ID"} },
+                    if hidden.show("Create Date") { th {"Create Date"} },
+                    if hidden.show("User Name") { th {"User Name"} },
+                    if hidden.show("Arn") { th {"Arn"} },
+                    if hidden.show("MFA") { th {"MFA"} },
                     th {},
                     th {"Groups"},
                     th {},
@@ -966,14 +1824,26 @@ fn UsersElement(
                     let id = &u.user_id;
                     let cd = u.create_date;
                     let ar = &u.arn;
+                    let mfa = if u.mfa_devices.is_empty() {
+                        rsx! {span {style: "color: red;", "none"}}
+                    } else {
+                        let devices: Vec<_> = u
+                            .mfa_devices
+                            .iter()
+                            .map(|d| if d.is_virtual {"virtual"} else {"hardware"})
+                            .collect();
+                        let devices = devices.join(", ");
+                        rsx! {"{devices}"}
+                    };
                     rsx! {
                         tr {
                             key: "user-key-{idx}",
                             style: "text-align: left;",
-                            td {"{id}"},
-                            td {"{cd}"},
-                            td {"{user_name}"},
-                            td {"{ar}"},
+                            if hidden.show("User ID") { td {"{id}"} },
+                            if hidden.show("Create Date") { td {"{cd}"} },
+                            if hidden.show("User Name") { td {"{user_name}"} },
+                            if hidden.show("Arn") { td {"{ar}"} },
+                            if hidden.show("MFA") { td {{mfa}} },
                             td {{delete_button}},
                             td {{group_select}},
                             td {{group_remove_button}},
@@ -991,6 +1861,7 @@ fn GroupsElement(
     groups: Vec<IamGroup>,
     user_map: HashMap<StackString, HashSet<StackString>>,
     users: HashSet<StackString>,
+    hidden: HiddenColumns,
 ) -> Element {
     let empty_set = HashSet::new();
     rsx! {
@@ -999,10 +1870,10 @@ fn GroupsElement(
             class: "dataframe",
             thead {
                 tr {
-                    th {"Group ID"},
-                    th {"Create Date"},
-                    th {"Group Name"},
-                    th {"Arn"},
+                    if hidden.show("Group ID") { th {"Group ID"} },
+                    if hidden.show("Create Date") { th {"Create Date"} },
+                    if hidden.show("Group Name") { th {"Group Name"} },
+                    if hidden.show("Arn") { th {"Arn"} },
                 }
             }
             {groups.iter().enumerate().map(|(idx, g)| {
@@ -1047,10 +1918,10 @@ fn GroupsElement(
                     tr {
                         key: "group-key-{idx}",
                         style: "text-align: left;",
-                        td {"{id}"},
-                        td {"{cd}"},
-                        td {"{gn}"},
-                        td {"{ar}"},
+                        if hidden.show("Group ID") { td {"{id}"} },
+                        if hidden.show("Create Date") { td {"{cd}"} },
+                        if hidden.show("Group Name") { td {"{gn}"} },
+                        if hidden.show("Arn") { td {"{ar}"} },
                         td {{user_select}},
                         td {{user_add_button}},
                     }
@@ -1063,17 +1934,17 @@ fn GroupsElement(
 }
 
 #[component]
-fn AccessKeyElement(keys: Vec<AccessKeyMetadata>) -> Element {
+fn AccessKeyElement(keys: Vec<AccessKeyMetadata>, hidden: HiddenColumns) -> Element {
     rsx! {
         table {
             "border": "1",
             class: "dataframe",
             thead {
                 tr {
-                    th {"Key ID"},
-                    th {"User Name"},
-                    th {"Create Date"},
-                    th {"Status"},
+                    if hidden.show("Key ID") { th {"Key ID"} },
+                    if hidden.show("User Name") { th {"User Name"} },
+                    if hidden.show("Create Date") { th {"Create Date"} },
+                    if hidden.show("Status") { th {"Status"} },
                 }
             },
             tbody {
@@ -1086,10 +1957,10 @@ fn AccessKeyElement(keys: Vec<AccessKeyMetadata>) -> Element {
                         tr {
                             key: "key-{idx}",
                             style: "text-align: left;",
-                            td {"{access_key_id}"},
-                            td {"{user_name}"},
-                            td {"{cd}"},
-                            td {"{st}"},
+                            if hidden.show("Key ID") { td {"{access_key_id}"} },
+                            if hidden.show("User Name") { td {"{user_name}"} },
+                            if hidden.show("Create Date") { td {"{cd}"} },
+                            if hidden.show("Status") { td {"{st}"} },
                             td {
                                 input {
                                     "type": "button",
@@ -1107,33 +1978,166 @@ fn AccessKeyElement(keys: Vec<AccessKeyMetadata>) -> Element {
 }
 
 #[component]
-fn DnsRecordElement(records: Vec<(String, DnsRecord)>, current_ip: Ipv4Addr) -> Element {
+fn DnsRecordElement(
+    records: Vec<(String, DnsRecord)>,
+    current_ip: Ipv4Addr,
+    health_checks: Vec<HealthCheckInfo>,
+    hidden: HiddenColumns,
+) -> Element {
+    let status: HashMap<&str, &str> = health_checks
+        .iter()
+        .map(|check| (check.id.as_str(), check.status()))
+        .collect();
+    let zones: BTreeSet<&str> = records
+        .iter()
+        .map(|(zone, _)| zone.trim_start_matches("/hostedzone/"))
+        .collect();
     rsx! {
+        {zones.into_iter().enumerate().map(|(idx, zone)| {
+            rsx! {
+                a {
+                    key: "zone-export-key-{idx}",
+                    href: "/aws/route53/{zone}/export",
+                    "Export {zone}"
+                },
+                " ",
+            }
+        })},
         table {
             "border": "1",
             class: "dataframe",
             thead {
                 tr {
-                    th {"Zone ID"},
-                    th {"DNS Name"},
-                    th {"IP Address"},
+                    if hidden.show("Zone ID") { th {"Zone ID"} },
+                    if hidden.show("DNS Name") { th {"DNS Name"} },
+                    if hidden.show("Type") { th {"Type"} },
+                    if hidden.show("TTL") { th {"TTL"} },
+                    if hidden.show("Values") { th {"Values"} },
+                    if hidden.show("Routing") { th {"Routing"} },
+                    if hidden.show("Health Check") { th {"Health Check"} },
                 }
             },
             tbody {
-                {records.iter().enumerate().map(|(idx, (zone, DnsRecord {dnsname, ip}))| {
+                {records.iter().enumerate().map(|(idx, (zone, record))| {
+                    let DnsRecord {dnsname, ip, record_type, ..} = record;
+                    let ttl = record.ttl.map_or_else(|| "alias".into(), |t| t.to_string());
+                    let values = if record.values.is_empty() {
+                        format!("alias {ip}")
+                    } else {
+                        record.values.join("\n")
+                    };
+                    let routing = match (&record.routing, &record.set_identifier) {
+                        (Some(routing), Some(set_id)) => format!("{routing} ({set_id})"),
+                        (Some(routing), None) => routing.to_string(),
+                        _ => String::new(),
+                    };
+                    let health = record.health_check_id.as_ref().map_or_else(String::new, |id| {
+                        let status = status.get(id.as_str()).unwrap_or(&"missing");
+                        format!("{status} ({id})")
+                    });
                     rsx! {
                         tr {
                             key: "record-key-{idx}",
                             style: "text-align; left;",
-                            td {"{zone}"},
-                            td {"{dnsname}"},
-                            td {"{ip}"},
+                            if hidden.show("Zone ID") { td {"{zone}"} },
+                            if hidden.show("DNS Name") { td {"{dnsname}"} },
+                            if hidden.show("Type") { td {"{record_type}"} },
+                            if hidden.show("TTL") { td {"{ttl}"} },
+                            if hidden.show("Values") { td {pre {"{values}"}} },
+                            if hidden.show("Routing") { td {"{routing}"} },
+                            if hidden.show("Health Check") { td {"{health}"} },
+                            td {
+                                if record.is_a_record() {
+                                    input {
+                                        "type": "button",
+                                        name: "Update",
+                                        value: "{current_ip}",
+                                        "onclick": "updateDnsName('{zone}', '{dnsname}.', '{ip}', '{current_ip}');",
+                                    }
+                                }
+                            },
+                        }
+                    }
+                })}
+            }
+        }
+        h3 {"Failover / Weighted Record"},
+        form {
+            action: "javascript:createRoutingRecord()",
+            input {"type": "text", name: "routing_zone", id: "routing_zone", placeholder: "zone id"},
+            input {"type": "text", name: "routing_name", id: "routing_name", placeholder: "dns name"},
+            input {"type": "text", name: "routing_ip", id: "routing_ip", placeholder: "{current_ip}"},
+            input {"type": "text", name: "routing_set_id", id: "routing_set_id", placeholder: "set identifier"},
+            select {
+                id: "routing_policy",
+                option {value: "primary", "failover primary"},
+                option {value: "secondary", "failover secondary"},
+                option {value: "weighted", "weighted"},
+            },
+            input {"type": "text", name: "routing_weight", id: "routing_weight", placeholder: "weight"},
+            input {"type": "text", name: "routing_health_check", id: "routing_health_check", placeholder: "health check id"},
+            input {
+                "type": "button",
+                name: "create_routing_record",
+                value: "Create",
+                "onclick": "createRoutingRecord();",
+            }
+        }
+        h3 {"Health Checks"},
+        form {
+            action: "javascript:createHealthCheck()",
+            select {
+                id: "health_check_type",
+                option {value: "HTTPS", "HTTPS"},
+                option {value: "HTTP", "HTTP"},
+                option {value: "TCP", "TCP"},
+            },
+            input {"type": "text", name: "health_check_host", id: "health_check_host", placeholder: "ip or domain"},
+            input {"type": "text", name: "health_check_port", id: "health_check_port", placeholder: "port"},
+            input {"type": "text", name: "health_check_path", id: "health_check_path", placeholder: "/health"},
+            input {
+                "type": "button",
+                name: "create_health_check",
+                value: "Create",
+                "onclick": "createHealthCheck();",
+            }
+        }
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"ID"},
+                    th {"Type"},
+                    th {"Target"},
+                    th {"Status"},
+                    th {},
+                }
+            },
+            tbody {
+                {health_checks.iter().enumerate().map(|(idx, check)| {
+                    let id = &check.id;
+                    let check_type = &check.check_type;
+                    let target = check.target();
+                    let status = match check.healthy_checkers {
+                        Some((healthy, total)) => {
+                            format!("{} ({healthy}/{total} checkers)", check.status())
+                        }
+                        None => check.status().to_string(),
+                    };
+                    rsx! {
+                        tr {
+                            key: "health-check-key-{idx}",
+                            td {"{id}"},
+                            td {"{check_type}"},
+                            td {"{target}"},
+                            td {"{status}"},
                             td {
                                 input {
                                     "type": "button",
-                                    name: "Update",
-                                    value: "{current_ip}",
-                                    "onclick": "updateDnsName('{zone}', '{dnsname}.', '{ip}', '{current_ip}');",
+                                    name: "delete_health_check",
+                                    value: "Delete",
+                                    "onclick": "deleteHealthCheck('{id}');",
                                 }
                             },
                         }
@@ -1145,227 +2149,388 @@ fn DnsRecordElement(records: Vec<(String, DnsRecord)>, current_ip: Ipv4Addr) ->
 }
 
 #[component]
-fn SystemdElement(
-    processes: HashMap<StackString, Vec<ProcessInfo>>,
-    services: BTreeMap<StackString, RunStatus>,
-    config: Config,
-) -> Element {
+fn SqsElement(queues: Vec<QueueInfo>, hidden: HiddenColumns) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
     rsx! {
         table {
             "border": "1",
             class: "dataframe",
             thead {
                 tr {
-                    th {"Name"},
-                    th {"Status"},
-                    th {
-                        input {
-                            "type": "button",
-                            name: "RestartAll",
-                            value: "RestartAll",
-                            "onclick": "systemdRestartAll();",
-                        }
-                    }
-                    th {
-                        input {
-                            "type": "button",
-                            name: "Crontab",
-                            value: "Crontab",
-                            "onclick": "crontabLogs('user');"
-                        },
-                        br {},
-                        input {
-                            "type": "button",
-                            name: "CrontabRoot",
-                            value: "CrontabRoot",
-                            "onclick": "crontabLogs('root');",
-                        },
-                    }
-                    th {"Memory"},
+                    th {},
+                    th {},
+                    th {},
+                    if hidden.show("Queue Name") { th {"Queue Name"} },
+                    if hidden.show("Messages") { th {"Messages"} },
+                    if hidden.show("In Flight") { th {"In Flight"} },
+                    if hidden.show("Delayed") { th {"Delayed"} },
+                    if hidden.show("Visibility Timeout") { th {"Visibility Timeout"} },
+                    if hidden.show("Created") { th {"Created"} },
                 }
             },
             tbody {
-                {config.systemd_services.iter().enumerate().map(|(idx, service)| {
-                    let proc_key = if service.len() > 15 {
-                        &service[..15]
-                    } else {
-                        service
-                    };
-                    let run_status = services.get(service).unwrap_or(&RunStatus::NotRunning);
-                    let proc_info = processes.get(proc_key);
-                    let action_button = match run_status {
-                        RunStatus::Running => {
-                            rsx! {
-                                input {
-                                    "type": "button",
-                                    name: "SystemdRestart",
-                                    value: "Restart",
-                                    "onclick": "systemdAction('restart', '{service}');",
-                                },
+                {queues.iter().enumerate().map(|(idx, queue)| {
+                    let url = &queue.queue_url;
+                    let name = &queue.queue_name;
+                    let messages = queue.approximate_messages;
+                    let in_flight = queue.approximate_messages_in_flight;
+                    let delayed = queue.approximate_messages_delayed;
+                    let visibility_timeout = queue.visibility_timeout;
+                    let has_redrive = queue.redrive_policy.is_some();
+                    let created = queue.created_timestamp.map_or_else(StackString::new, |t| {
+                        format_sstr!("{}", t.to_timezone(local_tz))
+                    });
+                    rsx! {
+                        tr {
+                            key: "queue-key-{idx}",
+                            style: "text-align: center;",
+                            td {
                                 input {
                                     "type": "button",
-                                    name: "SystemdStop",
-                                    value: "Stop",
-                                    "onclick": "systemdAction('stop', '{service}');",
-                                },
-                            }
-                        },
-                        RunStatus::NotRunning => {
-                            rsx! {
+                                    name: "PeekSqs",
+                                    value: "Peek",
+                                    "onclick": "peekSqsQueue('{url}', {has_redrive})",
+                                }
+                            },
+                            td {
                                 input {
                                     "type": "button",
-                                    name: "SystemdStart",
-                                    value: "Start",
-                                    "onclick": "systemdAction('start', '{service}');",
+                                    name: "PurgeSqs",
+                                    value: "Purge",
+                                    "onclick": "purgeSqsQueue('{url}')",
                                 }
-                            }
-                        }
-                    };
-                    let memory_info = proc_info.as_ref().map(|proc_info| {
-                        let memory: u64 = proc_info.iter().map(|p| p.memory).sum();
-                        let virtual_memory: u64 = proc_info.iter().map(|p| p.virtual_memory).sum();
-                        let memory = if virtual_memory > memory {memory} else {virtual_memory};
-                        let memory = memory as f32 / (1 << 20) as f32;
-                        rsx! {"{memory:0.1} MiB"}
-                    });
-                    rsx! {
-                        tr {
-                            key: "systemd-key-{idx}",
-                            style: "text-align; left;",
-                            td {"{service}"},
-                            td {"{run_status}"},
-                            td {{action_button}},
+                            },
                             td {
                                 input {
                                     "type": "button",
-                                    name: "SystemdLogs",
-                                    value: "Logs",
-                                    "onclick": "systemdLogs('{service}');",
+                                    name: "DeleteSqs",
+                                    value: "Delete",
+                                    "onclick": "deleteSqsQueue('{url}')",
                                 }
                             },
-                            td {{memory_info}},
+                            if hidden.show("Queue Name") { td {"{name}"} },
+                            if hidden.show("Messages") { td {"{messages}"} },
+                            if hidden.show("In Flight") { td {"{in_flight}"} },
+                            if hidden.show("Delayed") { td {"{delayed}"} },
+                            if hidden.show("Visibility Timeout") { td {"{visibility_timeout}"} },
+                            if hidden.show("Created") { td {"{created}"} },
                         }
                     }
-                }
-            )
-        }
+                })}
             }
         }
     }
 }
 
-/// # Errors
-/// Returns error if formatting fails
-pub fn instance_family_body(inst_fam: Vec<InstanceFamily>) -> Result<String, Error> {
-    let mut app = VirtualDom::new_with_props(
-        InstanceFamilyElement,
-        InstanceFamilyElementProps { inst_fam },
-    );
-    app.rebuild_in_place();
-    let mut renderer = dioxus_ssr::Renderer::default();
-    let mut buffer = String::new();
-    renderer.render_to(&mut buffer, &app)?;
-    Ok(buffer)
+#[component]
+fn LambdaElement(functions: Vec<LambdaFunctionInfo>, hidden: HiddenColumns) -> Element {
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    if hidden.show("Function") { th {"Function"} },
+                    if hidden.show("Runtime") { th {"Runtime"} },
+                    if hidden.show("Memory") { th {"Memory"} },
+                    if hidden.show("Timeout") { th {"Timeout"} },
+                    if hidden.show("Code Size") { th {"Code Size"} },
+                    if hidden.show("Last Modified") { th {"Last Modified"} },
+                    if hidden.show("Logs") { th {"Logs"} },
+                    if hidden.show("Invoke") { th {"Invoke"} },
+                }
+            },
+            tbody {
+                {functions.iter().enumerate().map(|(idx, function)| {
+                    let name = &function.function_name;
+                    let runtime = &function.runtime;
+                    let memory = function.memory_size;
+                    let timeout = function.timeout;
+                    let code_size = function.code_size as f64 / 1e6;
+                    let last_modified = &function.last_modified;
+                    let log_url = &function.log_url;
+                    rsx! {
+                        tr {
+                            key: "lambda-key-{idx}",
+                            style: "text-align: center;",
+                            if hidden.show("Function") { td {"{name}"} },
+                            if hidden.show("Runtime") { td {"{runtime}"} },
+                            if hidden.show("Memory") { td {"{memory} MB"} },
+                            if hidden.show("Timeout") { td {"{timeout} s"} },
+                            if hidden.show("Code Size") { td {"{code_size:0.2} MB"} },
+                            if hidden.show("Last Modified") { td {"{last_modified}"} },
+                            if hidden.show("Logs") {
+                                td {
+                                    a {
+                                        href: "{log_url}",
+                                        target: "_blank",
+                                        "CloudWatch",
+                                    }
+                                }
+                            },
+                            if hidden.show("Invoke") {
+                                td {
+                                    textarea {
+                                        name: "lambda_payload",
+                                        id: "lambda_payload_{name}",
+                                        rows: "2",
+                                        cols: "40",
+                                        "{{}}",
+                                    },
+                                    input {
+                                        "type": "button",
+                                        name: "InvokeLambda",
+                                        value: "Invoke",
+                                        "onclick": "invokeLambda('{name}')",
+                                    },
+                                }
+                            },
+                        }
+                    }
+                })}
+            }
+        }
+    }
 }
 
+/// Certificates expiring within `expiry_days` are counted above the table
+/// and their expiry shown in red
 #[component]
-fn InstanceFamilyElement(inst_fam: Vec<InstanceFamily>) -> Element {
+fn AcmElement(
+    certificates: Vec<CertificateInfo>,
+    expiry_days: i64,
+    hidden: HiddenColumns,
+) -> Element {
+    let now = OffsetDateTime::now_utc();
+    let local_tz = DateTimeWrapper::local_tz();
+    let expiring = certificates
+        .iter()
+        .filter(|c| c.expires_within(expiry_days, now))
+        .count();
+    let warning = (expiring > 0)
+        .then(|| format_sstr!("{expiring} certificates expire within {expiry_days} days"));
     rsx! {
-        br {
-            form {
-                action: "javascript:listPrices()",
-                select {
-                    id: "inst_fam",
-                    "onchange": "listPrices();",
-                    {inst_fam.iter().enumerate().map(|(idx, fam)| {
-                        let n = &fam.family_name;
-                        let t = &fam.family_type;
-                        rsx! {
-                            option {
-                                key: "inst-fam-key-{idx}",
-                                value: "{n}.",
-                                "{n} : {t}",
-                            }
-                        }
-                    })}
+        {warning.map(|warning| rsx! {
+            div {span {class: "credential-warning", "{warning}"}}
+        })},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    if hidden.show("Domain") { th {"Domain"} },
+                    if hidden.show("Alternative Names") { th {"Alternative Names"} },
+                    if hidden.show("Status") { th {"Status"} },
+                    if hidden.show("Type") { th {"Type"} },
+                    if hidden.show("In Use") { th {"In Use"} },
+                    if hidden.show("Expires") { th {"Expires"} },
+                    if hidden.show("Days Left") { th {"Days Left"} },
                 }
+            },
+            tbody {
+                {certificates.iter().enumerate().map(|(idx, certificate)| {
+                    let domain = &certificate.domain_name;
+                    let alternative_names = certificate.alternative_names.join(" ");
+                    let status = &certificate.status;
+                    let certificate_type = &certificate.certificate_type;
+                    let in_use = if certificate.in_use {"yes"} else {"no"};
+                    let expires = certificate.not_after.map_or_else(StackString::new, |t| {
+                        format_sstr!("{}", t.to_timezone(local_tz))
+                    });
+                    let days_left = certificate
+                        .days_until_expiry(now)
+                        .map_or_else(StackString::new, |d| format_sstr!("{d}"));
+                    let class = if certificate.expires_within(expiry_days, now) {
+                        "credential-warning"
+                    } else {
+                        ""
+                    };
+                    rsx! {
+                        tr {
+                            key: "acm-key-{idx}",
+                            style: "text-align: center;",
+                            if hidden.show("Domain") { td {"{domain}"} },
+                            if hidden.show("Alternative Names") { td {"{alternative_names}"} },
+                            if hidden.show("Status") { td {"{status}"} },
+                            if hidden.show("Type") { td {"{certificate_type}"} },
+                            if hidden.show("In Use") { td {"{in_use}"} },
+                            if hidden.show("Expires") { td {"{expires}"} },
+                            if hidden.show("Days Left") { td {class: "{class}", "{days_left}"} },
+                        }
+                    }
+                })}
             }
         }
     }
 }
 
-/// # Errors
-/// Returns error if formatting fails
-pub fn prices_body(prices: Vec<AwsInstancePrice>) -> Result<String, Error> {
-    let mut app = VirtualDom::new_with_props(PriceElement, PriceElementProps { prices });
-    app.rebuild_in_place();
-    let mut renderer = dioxus_ssr::Renderer::default();
-    let mut buffer = String::new();
-    renderer.render_to(&mut buffer, &app)?;
-    Ok(buffer)
+#[component]
+fn DockerElement(containers: Vec<ContainerInfo>, hidden: HiddenColumns) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {},
+                    if hidden.show("Name") { th {"Name"} },
+                    if hidden.show("Image") { th {"Image"} },
+                    if hidden.show("State") { th {"State"} },
+                    if hidden.show("Status") { th {"Status"} },
+                    if hidden.show("Ports") { th {"Ports"} },
+                    if hidden.show("Created") { th {"Created"} },
+                    th {},
+                }
+            },
+            tbody {
+                {containers.iter().enumerate().map(|(idx, container)| {
+                    let name = &container.name;
+                    let image = &container.image;
+                    let state = &container.state;
+                    let status = &container.status;
+                    let ports = container.ports.join(" ");
+                    let created = container.created.map_or_else(StackString::new, |t| {
+                        format_sstr!("{}", t.to_timezone(local_tz))
+                    });
+                    let actions: &[(&str, &str)] = if container.is_running() {
+                        &[("stop", "Stop"), ("restart", "Restart")]
+                    } else {
+                        &[("start", "Start")]
+                    };
+                    rsx! {
+                        tr {
+                            key: "docker-key-{idx}",
+                            style: "text-align: center;",
+                            td {
+                                {actions.iter().map(|&(action, label)| {
+                                    action_button(
+                                        "docker_action",
+                                        label,
+                                        &[("action", action), ("container", name.as_str())],
+                                    )
+                                })}
+                            },
+                            if hidden.show("Name") { td {"{name}"} },
+                            if hidden.show("Image") { td {"{image}"} },
+                            if hidden.show("State") { td {"{state}"} },
+                            if hidden.show("Status") { td {"{status}"} },
+                            if hidden.show("Ports") { td {"{ports}"} },
+                            if hidden.show("Created") { td {"{created}"} },
+                            td {{action_button("docker_logs", "Logs", &[("container", name.as_str())])}},
+                        }
+                    }
+                })}
+            }
+        }
+    }
 }
 
+/// Services running fewer tasks than desired have their counts shown in red
 #[component]
-fn PriceElement(prices: Vec<AwsInstancePrice>) -> Element {
+fn EcsElement(
+    clusters: Vec<EcsClusterInfo>,
+    services: Vec<EcsServiceInfo>,
+    hidden: HiddenColumns,
+) -> Element {
     rsx! {
         table {
             "border": "1",
             class: "dataframe",
             thead {
                 tr {
-                    th {"Instance Type"},
-                    th {"Ondemand Price"},
-                    th {"Spot Price"},
-                    th {"Reserved Price"},
-                    th {"N CPU"},
-                    th {"Memory GiB"},
-                    th {"Instance Family"},
+                    if hidden.show("Cluster") { th {"Cluster"} },
+                    if hidden.show("Status") { th {"Status"} },
+                    if hidden.show("Services") { th {"Services"} },
+                    if hidden.show("Running Tasks") { th {"Running Tasks"} },
+                    if hidden.show("Pending Tasks") { th {"Pending Tasks"} },
+                    if hidden.show("Container Instances") { th {"Container Instances"} },
                 }
             },
             tbody {
-                {prices.iter().enumerate().map(|(idx, price)| {
-                    let instance_type = &price.instance_type;
-                    let ncpu = price.ncpu;
-                    let memory = price.memory;
-                    let instance_family = &price.instance_family;
+                {clusters.iter().enumerate().map(|(idx, cluster)| {
+                    let name = &cluster.cluster_name;
+                    let status = &cluster.status;
+                    let active_services = cluster.active_services;
+                    let running_tasks = cluster.running_tasks;
+                    let pending_tasks = cluster.pending_tasks;
+                    let container_instances = cluster.container_instances;
                     rsx! {
                         tr {
-                            key: "price-key-{idx}",
+                            key: "ecs-cluster-key-{idx}",
+                            style: "text-align: center;",
+                            if hidden.show("Cluster") { td {"{name}"} },
+                            if hidden.show("Status") { td {"{status}"} },
+                            if hidden.show("Services") { td {"{active_services}"} },
+                            if hidden.show("Running Tasks") { td {"{running_tasks}"} },
+                            if hidden.show("Pending Tasks") { td {"{pending_tasks}"} },
+                            if hidden.show("Container Instances") { td {"{container_instances}"} },
+                        }
+                    }
+                })}
+            }
+        },
+        br {},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {},
+                    th {"Cluster"},
+                    th {"Service"},
+                    th {"Status"},
+                    th {"Launch Type"},
+                    th {"Task Definition"},
+                    th {"Running/Desired"},
+                    th {"Pending"},
+                    th {"Rollout"},
+                    th {"Logs"},
+                }
+            },
+            tbody {
+                {services.iter().enumerate().map(|(idx, service)| {
+                    let cluster = &service.cluster_name;
+                    let name = &service.service_name;
+                    let status = &service.status;
+                    let launch_type = &service.launch_type;
+                    let task_definition = &service.task_definition;
+                    let running = service.running_count;
+                    let desired = service.desired_count;
+                    let pending = service.pending_count;
+                    let rollout = &service.rollout_state;
+                    let class = if service.is_degraded() {"credential-warning"} else {""};
+                    rsx! {
+                        tr {
+                            key: "ecs-service-key-{idx}",
                             style: "text-align: center;",
+                            td {{action_button(
+                                "ecs_redeploy",
+                                "Redeploy",
+                                &[("cluster", cluster.as_str()), ("service", name.as_str())],
+                            )}},
+                            td {"{cluster}"},
+                            td {"{name}"},
+                            td {"{status}"},
+                            td {"{launch_type}"},
+                            td {"{task_definition}"},
+                            td {class: "{class}", "{running}/{desired}"},
+                            td {"{pending}"},
+                            td {"{rollout}"},
                             td {
-                                {price.data_url.as_ref().map_or_else(
-                                    || {rsx! {"{instance_type}"}},
-                                    |data_url| {
-                                        rsx! {
-                                            a {
-                                                href: "{data_url}",
-                                                target: "_blank",
-                                                "{instance_type}",
-                                            }
+                                {service.log_links.iter().enumerate().map(|(i, link)| {
+                                    let url = &link.url;
+                                    let container = &link.container;
+                                    rsx! {
+                                        a {
+                                            key: "ecs-log-key-{idx}-{i}",
+                                            href: "{url}",
+                                            target: "_blank",
+                                            "{container} "
                                         }
                                     }
-                                )}
-                            },
-                            td {
-                                {price.ondemand_price.map(|p| rsx! {"${p:0.4}/hr"})}
-                            },
-                            td {
-                                {price.spot_price.map(|p| rsx! {"${p:0.4}/hr"})}
-                            },
-                            td {
-                                {price.reserved_price.map(|p| rsx! {"${p:0.4}/hr"})}
+                                })}
                             },
-                            td {"{ncpu}"},
-                            td {"{memory}"},
-                            td {"{instance_family}"},
-                            td {
-                                input {
-                                    "type": "button",
-                                    name: "Request",
-                                    value: "Request",
-                                    "onclick": "buildSpotRequest(null, '{instance_type}', null)",
-                                }
-                            }
                         }
                     }
                 })}
@@ -1376,9 +2541,9 @@ fn PriceElement(prices: Vec<AwsInstancePrice>) -> Element {
 
 /// # Errors
 /// Returns error if formatting fails
-pub fn edit_script_body(fname: StackString, text: StackString) -> Result<String, Error> {
+pub fn lambda_invoke_body(result: LambdaInvokeResult) -> Result<String, Error> {
     let mut app =
-        VirtualDom::new_with_props(EditScriptElement, EditScriptElementProps { fname, text });
+        VirtualDom::new_with_props(LambdaInvokeElement, LambdaInvokeElementProps { result });
     app.rebuild_in_place();
     let mut renderer = dioxus_ssr::Renderer::default();
     let mut buffer = String::new();
@@ -1387,63 +2552,2929 @@ pub fn edit_script_body(fname: StackString, text: StackString) -> Result<String,
 }
 
 #[component]
-fn EditScriptElement(fname: StackString, text: StackString) -> Element {
-    let rows = text.split('\n').count() + 5;
+fn LambdaInvokeElement(result: LambdaInvokeResult) -> Element {
+    let status_code = result.status_code;
+    let function_error = result
+        .function_error
+        .as_ref()
+        .map_or("none", StackString::as_str);
+    let response = &result.response;
+    let log_tail = &result.log_tail;
     rsx! {
-        br {
-            textarea {
-                name: "message",
-                id: "script_editor_form",
-                rows: "{rows}",
-                cols: "100",
-                form: "script_edit_form",
-                "{text}",
-            }
-        }
-        form {
-            id: "script_edit_form",
-            input {
-                "type": "button",
-                name: "update",
-                value: "Update",
-                "onclick": "submitFormData('{fname}')",
-            },
-            input {
-                "type": "button",
-                name: "cancel",
-                value: "Cancel",
-                "onclick": "listResource('script')",
-            }
-            input {
-                "type": "button",
-                name: "request",
-                value: "Request",
-                "onclick": "updateScriptAndBuildSpotRequest('{fname}')",
-            }
-        }
-    }
-}
+        div {
+            "Status {status_code}, Function Error: {function_error}",
+        },
+        textarea {
+            readonly: "readonly",
+            name: "lambda_response",
+            id: "lambda_response",
+            rows: "10",
+            cols: "100",
+            "{response}",
+        },
+        textarea {
+            readonly: "readonly",
+            name: "lambda_logs",
+            id: "lambda_logs",
+            rows: "20",
+            cols: "100",
+            "{log_tail}",
+        },
+    }
+}
+
+fn map_date(date: Option<DateTimeWrapper>) -> StackString {
+    let local_tz = DateTimeWrapper::local_tz();
+    date.map_or_else(
+        || "never".into(),
+        |d| format_sstr!("{}", d.to_timezone(local_tz)),
+    )
+}
+
+#[component]
+fn BackupElement(
+    plans: Vec<BackupPlanInfo>,
+    resources: Vec<ProtectedResourceInfo>,
+    recovery_points: Vec<RecoveryPointInfo>,
+    hidden: HiddenColumns,
+) -> Element {
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    if hidden.show("Backup Plan") { th {"Backup Plan"} },
+                    if hidden.show("Plan ID") { th {"Plan ID"} },
+                    if hidden.show("Last Execution") { th {"Last Execution"} },
+                    if hidden.show("Assign Volumes") { th {"Assign Volumes"} },
+                }
+            },
+            tbody {
+                {plans.iter().enumerate().map(|(idx, plan)| {
+                    let plan_id = &plan.plan_id;
+                    let plan_name = &plan.plan_name;
+                    let last_execution = map_date(plan.last_execution_date);
+                    rsx! {
+                        tr {
+                            key: "backup-plan-key-{idx}",
+                            style: "text-align: center;",
+                            if hidden.show("Backup Plan") { td {"{plan_name}"} },
+                            if hidden.show("Plan ID") { td {"{plan_id}"} },
+                            if hidden.show("Last Execution") { td {"{last_execution}"} },
+                            if hidden.show("Assign Volumes") {
+                                td {
+                                    input {
+                                        "type": "text",
+                                        name: "backup_volumes",
+                                        id: "backup_volumes_{plan_id}",
+                                    },
+                                    input {
+                                        "type": "button",
+                                        name: "AssignBackup",
+                                        value: "Assign",
+                                        "onclick": "assignBackupVolumes('{plan_id}')",
+                                    },
+                                }
+                            },
+                        }
+                    }
+                })}
+            }
+        },
+        br {},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Protected Resource"},
+                    th {"Resource Type"},
+                    th {"Last Backup"},
+                }
+            },
+            tbody {
+                {resources.iter().enumerate().map(|(idx, resource)| {
+                    let arn = &resource.resource_arn;
+                    let resource_type = &resource.resource_type;
+                    let last_backup = map_date(resource.last_backup_time);
+                    rsx! {
+                        tr {
+                            key: "backup-resource-key-{idx}",
+                            style: "text-align: center;",
+                            td {"{arn}"},
+                            td {"{resource_type}"},
+                            td {"{last_backup}"},
+                        }
+                    }
+                })}
+            }
+        },
+        br {},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Vault"},
+                    th {"Resource"},
+                    th {"Status"},
+                    th {"Created"},
+                    th {"Size"},
+                }
+            },
+            tbody {
+                {recovery_points.iter().take(50).enumerate().map(|(idx, point)| {
+                    let vault = &point.vault_name;
+                    let arn = &point.resource_arn;
+                    let status = &point.status;
+                    let created = map_date(point.creation_date);
+                    let size = point.backup_size;
+                    rsx! {
+                        tr {
+                            key: "recovery-point-key-{idx}",
+                            style: "text-align: center;",
+                            td {"{vault}"},
+                            td {"{arn}"},
+                            td {"{status}"},
+                            td {"{created}"},
+                            td {"{size:0.2} MB"},
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+
+#[component]
+fn SystemdElement(
+    processes: HashMap<StackString, Vec<ProcessInfo>>,
+    services: BTreeMap<StackString, RunStatus>,
+    probes: BTreeMap<StackString, ProbeStatus>,
+    timers: Vec<TimerStatus>,
+    sockets: Vec<SocketStatus>,
+    config: Config,
+    hidden: HiddenColumns,
+) -> Element {
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    if hidden.show("Name") { th {"Name"} },
+                    if hidden.show("Status") { th {"Status"} },
+                    if hidden.show("Health") { th {"Health"} },
+                    th {
+                        input {
+                            "type": "button",
+                            name: "RestartAll",
+                            value: "RestartAll",
+                            "onclick": "systemdRestartAll();",
+                        },
+                        br {},
+                        input {
+                            "type": "button",
+                            name: "Dependencies",
+                            value: "Dependencies",
+                            "onclick": "systemdDependencies();",
+                        },
+                    }
+                    th {
+                        input {
+                            "type": "button",
+                            name: "Crontab",
+                            value: "Crontab",
+                            "onclick": "crontabLogs('user');"
+                        },
+                        br {},
+                        input {
+                            "type": "button",
+                            name: "CrontabRoot",
+                            value: "CrontabRoot",
+                            "onclick": "crontabLogs('root');",
+                        },
+                        br {},
+                        {action_button("crontab_edit", "EditCrontab", &[])},
+                    }
+                    if hidden.show("Memory") { th {"Memory"} },
+                }
+            },
+            tbody {
+                {config.systemd_services.iter().enumerate().map(|(idx, service)| {
+                    let proc_key = if service.len() > 15 {
+                        &service[..15]
+                    } else {
+                        service
+                    };
+                    let run_status = services.get(service).unwrap_or(&RunStatus::NotRunning);
+                    let proc_info = processes.get(proc_key);
+                    let action_button = match run_status {
+                        RunStatus::Running => {
+                            rsx! {
+                                input {
+                                    "type": "button",
+                                    name: "SystemdRestart",
+                                    value: "Restart",
+                                    "onclick": "systemdRestartPreview('{service}');",
+                                },
+                                input {
+                                    "type": "button",
+                                    name: "SystemdStop",
+                                    value: "Stop",
+                                    "onclick": "systemdAction('stop', '{service}');",
+                                },
+                            }
+                        },
+                        RunStatus::NotRunning => {
+                            rsx! {
+                                input {
+                                    "type": "button",
+                                    name: "SystemdStart",
+                                    value: "Start",
+                                    "onclick": "systemdAction('start', '{service}');",
+                                }
+                            }
+                        }
+                    };
+                    let memory_info = proc_info.as_ref().map(|proc_info| {
+                        let memory: u64 = proc_info.iter().map(|p| p.memory).sum();
+                        let virtual_memory: u64 = proc_info.iter().map(|p| p.virtual_memory).sum();
+                        let memory = if virtual_memory > memory {memory} else {virtual_memory};
+                        let memory = memory as f32 / (1 << 20) as f32;
+                        rsx! {"{memory:0.1} MiB"}
+                    });
+                    let health = probes.get(service).map(|status| {
+                        let probe = &status.probe;
+                        if status.is_healthy() {
+                            rsx! {td {title: "{probe}", "{status}"}}
+                        } else {
+                            rsx! {td {class: "credential-warning", title: "{probe}", "{status}"}}
+                        }
+                    });
+                    rsx! {
+                        tr {
+                            key: "systemd-key-{idx}",
+                            style: "text-align; left;",
+                            if hidden.show("Name") { td {"{service}"} },
+                            if hidden.show("Status") { td {"{run_status}"} },
+                            if hidden.show("Health") {
+                                {health.unwrap_or_else(|| rsx! {td {}})}
+                            },
+                            td {{action_button}},
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "SystemdLogs",
+                                    value: "Logs",
+                                    "onclick": "systemdLogs('{service}');",
+                                }
+                            },
+                            if hidden.show("Memory") { td {{memory_info}} },
+                        }
+                    }
+                }
+            )
+        }
+            }
+        },
+        if !timers.is_empty() {
+            br {},
+            table {
+                "border": "1",
+                class: "dataframe",
+                thead {
+                    tr {
+                        th {"Timer"},
+                        th {"Status"},
+                        th {"Unit File"},
+                        th {"Activates"},
+                        th {"Next Trigger"},
+                        th {"Last Trigger"},
+                        th {},
+                    }
+                },
+                tbody {
+                    {timers.iter().enumerate().map(|(idx, timer)| {
+                        let name = &timer.name;
+                        let status = format_sstr!("{} {}", timer.active_state, timer.sub_state);
+                        let unit_file_state = &timer.unit_file_state;
+                        let unit = timer.unit.as_ref().map_or("", StackString::as_str);
+                        let next_trigger = map_date(timer.next_trigger.map(Into::into));
+                        let last_trigger = map_date(timer.last_trigger.map(Into::into));
+                        let class = if timer.is_enabled() && !timer.is_scheduled() {
+                            "credential-warning"
+                        } else {
+                            ""
+                        };
+                        rsx! {
+                            tr {
+                                key: "systemd-timer-key-{idx}",
+                                style: "text-align: left;",
+                                td {"{name}"},
+                                td {"{status}"},
+                                td {"{unit_file_state}"},
+                                td {"{unit}"},
+                                td {class: "{class}", "{next_trigger}"},
+                                td {"{last_trigger}"},
+                                td {{unit_file_button(name, timer.is_enabled())}},
+                            }
+                        }
+                    })}
+                }
+            }
+        },
+        if !sockets.is_empty() {
+            br {},
+            table {
+                "border": "1",
+                class: "dataframe",
+                thead {
+                    tr {
+                        th {"Socket"},
+                        th {"Status"},
+                        th {"Unit File"},
+                        th {"Listen"},
+                        th {"Activates"},
+                        th {"Connections"},
+                        th {"Accepted"},
+                        th {},
+                    }
+                },
+                tbody {
+                    {sockets.iter().enumerate().map(|(idx, socket)| {
+                        let name = &socket.name;
+                        let status = format_sstr!("{} {}", socket.active_state, socket.sub_state);
+                        let unit_file_state = &socket.unit_file_state;
+                        let listen = socket.listen.join(" ");
+                        let triggers = socket.triggers.join(" ");
+                        let connections = socket.connections.unwrap_or(0);
+                        let accepted = socket.accepted.unwrap_or(0);
+                        rsx! {
+                            tr {
+                                key: "systemd-socket-key-{idx}",
+                                style: "text-align: left;",
+                                td {"{name}"},
+                                td {"{status}"},
+                                td {"{unit_file_state}"},
+                                td {"{listen}"},
+                                td {"{triggers}"},
+                                td {"{connections}"},
+                                td {"{accepted}"},
+                                td {{unit_file_button(name, socket.is_enabled())}},
+                            }
+                        }
+                    })}
+                }
+            }
+        },
+    }
+}
+
+fn unit_file_button(unit: &str, enabled: bool) -> Element {
+    let (name, value, action) = if enabled {
+        ("SystemdDisable", "Disable", "disable")
+    } else {
+        ("SystemdEnable", "Enable", "enable")
+    };
+    rsx! {
+        input {
+            "type": "button",
+            name: "{name}",
+            value: "{value}",
+            "onclick": "systemdAction('{action}', '{unit}');",
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn systemd_dependencies_body(
+    graph: BTreeMap<StackString, UnitDependencies>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        SystemdDependenciesElement,
+        SystemdDependenciesElementProps { graph },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn SystemdDependenciesElement(graph: BTreeMap<StackString, UnitDependencies>) -> Element {
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Service"},
+                    th {"Requires"},
+                    th {"Starts After"},
+                    th {"Required By"},
+                }
+            },
+            tbody {
+                {graph.iter().enumerate().map(|(idx, (service, deps))| {
+                    let filter_deps = |units: &BTreeSet<StackString>| {
+                        let units: Vec<_> = units
+                            .iter()
+                            .filter(|u| graph.contains_key(*u))
+                            .map(StackString::as_str)
+                            .collect();
+                        units.join(", ")
+                    };
+                    let requires = filter_deps(&deps.requires);
+                    let after = filter_deps(&deps.after);
+                    let required_by = filter_deps(&deps.required_by);
+                    rsx! {
+                        tr {
+                            key: "systemd-deps-key-{idx}",
+                            style: "text-align: left;",
+                            td {"{service}"},
+                            td {"{requires}"},
+                            td {"{after}"},
+                            td {"{required_by}"},
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn systemd_restart_preview_body(
+    service: StackString,
+    affected: Vec<StackString>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        SystemdRestartPreviewElement,
+        SystemdRestartPreviewElementProps { service, affected },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn SystemdRestartPreviewElement(service: StackString, affected: Vec<StackString>) -> Element {
+    let dependents = affected.len().saturating_sub(1);
+    rsx! {
+        div {
+            "Restarting {service} affects {dependents} dependent services, restart order:",
+            ol {
+                {affected.iter().enumerate().map(|(idx, s)| {
+                    rsx! {
+                        li {key: "restart-order-key-{idx}", "{s}"}
+                    }
+                })}
+            },
+            input {
+                "type": "button",
+                name: "SystemdRestartOnly",
+                value: "Restart {service} Only",
+                "onclick": "systemdAction('restart', '{service}');",
+            },
+            input {
+                "type": "button",
+                name: "SystemdRestartDependents",
+                value: "Restart With Dependents",
+                "onclick": "systemdRestartDependents('{service}');",
+            },
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn systemd_restart_body(
+    results: Vec<RestartResult>,
+    note: Option<StackString>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        SystemdRestartElement,
+        SystemdRestartElementProps { results, note },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn SystemdRestartElement(results: Vec<RestartResult>, note: Option<StackString>) -> Element {
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Service"},
+                    th {"Outcome"},
+                    th {"Seconds"},
+                },
+            },
+            tbody {
+                {results.iter().enumerate().map(|(idx, result)| {
+                    let service = &result.service;
+                    let outcome = &result.outcome;
+                    let elapsed = result
+                        .elapsed_seconds
+                        .map_or_else(StackString::new, |e| format_sstr!("{e:0.1}"));
+                    let style = if outcome.is_healthy() { "" } else { "color: red;" };
+                    rsx! {
+                        tr {
+                            key: "restart-result-key-{idx}",
+                            style: "text-align: center; {style}",
+                            td {"{service}"},
+                            td {"{outcome}"},
+                            td {"{elapsed}"},
+                        }
+                    }
+                })}
+            }
+        },
+        {note.map(|note| rsx! {
+            div {"{note}"}
+        })},
+        {action_button("list", "Refresh", &[("resource", "systemd"), ("refresh", "true")])},
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn decommission_plan_body(plan: DecommissionPlan) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(DecommissionElement, DecommissionElementProps { plan });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn DecommissionElement(plan: DecommissionPlan) -> Element {
+    let instance_id = &plan.instance_id;
+    let title = match &plan.name {
+        Some(name) => format_sstr!("{instance_id} ({name})"),
+        None => instance_id.clone(),
+    };
+    let snapshot = plan.root_volume.as_ref().map(|root_volume| {
+        rsx! {
+            li {
+                input {"type": "checkbox", id: "decommission_final_snapshot", checked: true},
+                "Snapshot root volume {root_volume} before terminating",
+            }
+        }
+    });
+    let spot_requests = if plan.spot_requests.is_empty() {
+        None
+    } else {
+        Some(rsx! {
+            li {
+                input {"type": "checkbox", id: "decommission_cancel_spot_requests", checked: true},
+                "Cancel spot requests",
+                ul {
+                    {plan.spot_requests.iter().enumerate().map(|(idx, s)| {
+                        rsx! {li {key: "decommission-spot-key-{idx}", "{s}"}}
+                    })}
+                }
+            }
+        })
+    };
+    let dns_records = if plan.dns_records.is_empty() {
+        None
+    } else {
+        Some(rsx! {
+            li {
+                input {"type": "checkbox", id: "decommission_delete_dns_records", checked: true},
+                "Delete dns records",
+                ul {
+                    {plan.dns_records.iter().enumerate().map(|(idx, (_, r))| {
+                        let values = r.values.join(" ");
+                        let record_type = &r.record_type;
+                        let dnsname = &r.dnsname;
+                        rsx! {li {key: "decommission-dns-key-{idx}", "{record_type} {dnsname} -> {values}"}}
+                    })}
+                }
+            }
+        })
+    };
+    let volumes = if plan.volumes.is_empty() {
+        None
+    } else {
+        Some(rsx! {
+            li {
+                input {"type": "checkbox", id: "decommission_delete_volumes"},
+                "Delete volumes once detached",
+                ul {
+                    {plan.volumes.iter().enumerate().map(|(idx, v)| {
+                        rsx! {li {key: "decommission-volume-key-{idx}", "{v}"}}
+                    })}
+                }
+            }
+        })
+    };
+    rsx! {
+        div {
+            "Decommission {title}:",
+            ul {
+                {snapshot},
+                {spot_requests},
+                li {"Terminate {instance_id}"},
+                {dns_records},
+                {volumes},
+            },
+            input {
+                "type": "button",
+                name: "decommission",
+                value: "Decommission {instance_id}",
+                "onclick": "decommissionInstance('{instance_id}');",
+            },
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn instance_family_body(
+    inst_fam: Vec<InstanceFamily>,
+    update_status: Vec<UpdateStatus>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        InstanceFamilyElement,
+        InstanceFamilyElementProps {
+            inst_fam,
+            update_status,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn InstanceFamilyElement(
+    inst_fam: Vec<InstanceFamily>,
+    update_status: Vec<UpdateStatus>,
+) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
+    rsx! {
+        {update_status.iter().enumerate().map(|(idx, status)| {
+            let task = &status.task;
+            let last_success = map_date(status.last_success.map(Into::into));
+            let failure = status.last_error.as_ref().map(|e| {
+                let last_attempt = status.last_attempt.to_timezone(local_tz);
+                format_sstr!(", last attempt {last_attempt} failed: {e}")
+            }).unwrap_or_default();
+            rsx! {
+                div {
+                    key: "update-status-key-{idx}",
+                    class: "update-status",
+                    "{task} updated {last_success}{failure}",
+                }
+            }
+        })},
+        br {
+            form {
+                action: "javascript:listPrices()",
+                select {
+                    id: "inst_fam",
+                    "onchange": "listPrices();",
+                    {inst_fam.iter().enumerate().map(|(idx, fam)| {
+                        let n = &fam.family_name;
+                        let t = &fam.family_type;
+                        rsx! {
+                            option {
+                                key: "inst-fam-key-{idx}",
+                                value: "{n}.",
+                                "{n} : {t}",
+                            }
+                        }
+                    })}
+                }
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn prices_body(
+    prices: Vec<AwsInstancePrice>,
+    forecasts: BTreeMap<StackString, SpotForecast>,
+    search: StackString,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        PriceElement,
+        PriceElementProps {
+            prices,
+            forecasts,
+            search,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn PriceElement(
+    prices: Vec<AwsInstancePrice>,
+    forecasts: BTreeMap<StackString, SpotForecast>,
+    search: StackString,
+) -> Element {
+    rsx! {
+        {export_link("prices", Some(&search))},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Instance Type"},
+                    th {"Ondemand Price"},
+                    th {"7d Trend"},
+                    th {"Spot Price"},
+                    th {"Interruption Risk"},
+                    th {"Suggested Bid"},
+                    th {"Reserved Price"},
+                    th {"N CPU"},
+                    th {"Memory GiB"},
+                    th {"GPUs"},
+                    th {"Network"},
+                    th {"Instance Family"},
+                }
+            },
+            tbody {
+                {prices.iter().enumerate().map(|(idx, price)| {
+                    let instance_type = &price.instance_type;
+                    let ncpu = price.ncpu;
+                    let memory = price.memory;
+                    let gpus = match (price.gpu_count, &price.gpu_type) {
+                        (0, _) => StackString::new(),
+                        (n, Some(t)) => format_sstr!("{n} x {t}"),
+                        (n, None) => format_sstr!("{n}"),
+                    };
+                    let network = price
+                        .network_performance
+                        .as_ref()
+                        .map_or("", StackString::as_str);
+                    let instance_family = &price.instance_family;
+                    let trend = price.ondemand_trend_label();
+                    let forecast = forecasts.get(instance_type);
+                    let risk = forecast.map_or(InterruptionRisk::Unknown, |f| f.risk);
+                    let risk_class = if risk == InterruptionRisk::High {
+                        "credential-warning"
+                    } else {
+                        ""
+                    };
+                    rsx! {
+                        tr {
+                            key: "price-key-{idx}",
+                            style: "text-align: center;",
+                            td {
+                                {price.data_url.as_ref().map_or_else(
+                                    || {rsx! {"{instance_type}"}},
+                                    |data_url| {
+                                        rsx! {
+                                            a {
+                                                href: "{data_url}",
+                                                target: "_blank",
+                                                "{instance_type}",
+                                            }
+                                        }
+                                    }
+                                )}
+                            },
+                            td {
+                                {price.ondemand_price.map(|p| rsx! {"${p:0.4}/hr"})}
+                            },
+                            td {
+                                a {
+                                    href: "/aws/api/price_history?instance_type={instance_type}",
+                                    target: "_blank",
+                                    "{trend}",
+                                }
+                            },
+                            td {
+                                {price.spot_price.map(|p| rsx! {"${p:0.4}/hr"})}
+                            },
+                            td {
+                                class: "{risk_class}",
+                                a {
+                                    href: "/aws/api/spot_forecast?search={instance_type}",
+                                    target: "_blank",
+                                    {risk.to_str()},
+                                }
+                            },
+                            td {
+                                {forecast.map(|f| {
+                                    let bid = f.suggested_bid;
+                                    rsx! {"${bid:0.4}/hr"}
+                                })}
+                            },
+                            td {
+                                {price.reserved_price.map(|p| rsx! {"${p:0.4}/hr"})}
+                            },
+                            td {"{ncpu}"},
+                            td {"{memory}"},
+                            td {"{gpus}"},
+                            td {"{network}"},
+                            td {"{instance_family}"},
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "Request",
+                                    value: "Request",
+                                    "onclick": "buildSpotRequest(null, '{instance_type}', null)",
+                                }
+                            }
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn edit_script_body(fname: StackString, text: StackString) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(EditScriptElement, EditScriptElementProps { fname, text });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn EditScriptElement(fname: StackString, text: StackString) -> Element {
+    let rows = text.split('\n').count() + 5;
+    rsx! {
+        br {
+            textarea {
+                name: "message",
+                id: "script_editor_form",
+                rows: "{rows}",
+                cols: "100",
+                form: "script_edit_form",
+                "{text}",
+            }
+        }
+        form {
+            id: "script_edit_form",
+            input {
+                "type": "button",
+                name: "update",
+                value: "Update",
+                "onclick": "submitFormData('{fname}')",
+            },
+            {action_button("list", "Cancel", &[("resource", "script")])},
+            input {
+                "type": "button",
+                name: "request",
+                value: "Request",
+                "onclick": "updateScriptAndBuildSpotRequest('{fname}')",
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn edit_crontab_body(text: StackString) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(EditCrontabElement, EditCrontabElementProps { text });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn EditCrontabElement(text: StackString) -> Element {
+    let rows = text.split('\n').count() + 5;
+    rsx! {
+        br {
+            textarea {
+                name: "message",
+                id: "crontab_editor_form",
+                rows: "{rows}",
+                cols: "100",
+                form: "crontab_edit_form",
+                "{text}",
+            }
+        }
+        form {
+            id: "crontab_edit_form",
+            input {
+                "type": "button",
+                name: "preview",
+                value: "Preview",
+                "onclick": "crontabPreview()",
+            },
+            input {
+                "type": "button",
+                name: "install",
+                value: "Install",
+                "onclick": "crontabInstall()",
+            },
+            {action_button("list", "Cancel", &[("resource", "systemd")])},
+        }
+        div {id: "crontab_diff"}
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn crontab_diff_body(diff: StackString) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(CrontabDiffElement, CrontabDiffElementProps { diff });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn CrontabDiffElement(diff: StackString) -> Element {
+    if diff.is_empty() {
+        return rsx! {"No changes"};
+    }
+    rsx! {
+        pre {"{diff}"}
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn build_spot_request_body(
+    amis: Vec<AmiInfo>,
+    inst_fams: Vec<InstanceFamily>,
+    instances: Vec<AwsInstancePrice>,
+    files: Vec<StackString>,
+    keys: Vec<(StackString, StackString)>,
+    instance_profiles: Vec<StackString>,
+    config: Config,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        BuildSpotRequestElement,
+        BuildSpotRequestElementProps {
+            amis,
+            inst_fams,
+            instances,
+            files,
+            keys,
+            instance_profiles,
+            config,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn BuildSpotRequestElement(
+    amis: Vec<AmiInfo>,
+    inst_fams: Vec<InstanceFamily>,
+    instances: Vec<AwsInstancePrice>,
+    files: Vec<StackString>,
+    keys: Vec<(StackString, StackString)>,
+    instance_profiles: Vec<StackString>,
+    config: Config,
+) -> Element {
+    let sec = config.spot_security_group.as_ref().unwrap_or_else(|| {
+        config
+            .default_security_group
+            .as_ref()
+            .expect("NO DEFAULT_SECURITY_GROUP")
+    });
+    let price = config.max_spot_price;
+    let mut ubuntu_releases: Vec<&str> = UBUNTU_RELEASES.to_vec();
+    if !ubuntu_releases.contains(&config.ubuntu_release.as_str()) {
+        ubuntu_releases.push(config.ubuntu_release.as_str());
+    }
+    rsx! {
+        form {
+            action: "javascript:createScript()",
+            table {
+                tbody {
+                    tr {
+                        td {"Ami:"},
+                        td {
+                            select {
+                                id: "ami",
+                                {amis.iter().enumerate().map(|(idx, ami)| {
+                                    let id = &ami.id;
+                                    let name = &ami.name;
+                                    rsx! {
+                                        option {
+                                            key: "ami-key-{idx}",
+                                            value: "{id}",
+                                            "{name}"
+                                        }
+                                    }
+                                })}
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Ubuntu image:"},
+                        td {
+                            select {
+                                id: "ubuntu_release",
+                                "onchange": "ubuntuImages()",
+                                {ubuntu_releases.iter().enumerate().map(|(idx, release)| {
+                                    rsx! {
+                                        option {
+                                            key: "ubuntu-release-key-{idx}",
+                                            value: "{release}",
+                                            "{release}",
+                                        }
+                                    }
+                                })}
+                            },
+                            select {
+                                id: "ubuntu_arch",
+                                "onchange": "ubuntuImages()",
+                                {UBUNTU_ARCHES.iter().enumerate().map(|(idx, arch)| {
+                                    rsx! {
+                                        option {
+                                            key: "ubuntu-arch-key-{idx}",
+                                            value: "{arch}",
+                                            "{arch}",
+                                        }
+                                    }
+                                })}
+                            },
+                            input {
+                                "type": "checkbox",
+                                id: "ubuntu_minimal",
+                                "onchange": "ubuntuImages()",
+                            },
+                            "minimal",
+                        }
+                    },
+                    tr {
+                        td {"Instance family"},
+                        td {
+                            select {
+                                id: "inst_fam",
+                                "onchange": "instanceOptions()",
+                                {inst_fams.iter().enumerate().map(|(idx, fam)| {
+                                    let n = &fam.family_name;
+                                    rsx! {
+                                        option {
+                                            key: "inst-fam-key-{idx}",
+                                            value: "{n}",
+                                            "{n}",
+                                        }
+                                    }
+                                })}
+                            }
+                        },
+                    },
+                    tr {
+                        td {"Instance type"},
+                        td {
+                            select {
+                                id: "instance_type",
+                                {instances.iter().enumerate().map(|(idx, i)| {
+                                    let label = instance_price_label(i);
+                                    let i = &i.instance_type;
+                                    rsx! {
+                                        option {
+                                            key: "instance-type-key-{idx}",
+                                            value: "{i}",
+                                            "{label}",
+                                        }
+                                    }
+                                })}
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Security group"},
+                        td {
+                            input {
+                                "type": "text",
+                                name: "security_group",
+                                id: "security_group",
+                                value: "{sec}",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Script"},
+                        td {
+                            select {
+                                id: "script",
+                                {files.iter().enumerate().map(|(idx, f)| {
+                                    rsx! {
+                                        option {
+                                            key: "script-key-{idx}",
+                                            value: "{f}",
+                                            "{f}",
+                                        }
+                                    }
+                                })}
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Key"},
+                        td {
+                            select {
+                                id: "key",
+                                {keys.iter().enumerate().map(|(idx, (k, _))| {
+                                    rsx! {
+                                        option {
+                                            key: "key-{idx}",
+                                            value: "{k}",
+                                            "{k}",
+                                        }
+                                    }
+                                })}
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Price"},
+                        td {
+                            input {
+                                "type": "text",
+                                name: "price",
+                                id: "price",
+                                value: "{price}",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Count"},
+                        td {
+                            input {
+                                "type": "number",
+                                name: "count",
+                                id: "count",
+                                min: "1",
+                                value: "1",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Extra instance types"},
+                        td {
+                            select {
+                                id: "extra_instance_types",
+                                multiple: true,
+                                {instances.iter().enumerate().map(|(idx, i)| {
+                                    let label = instance_price_label(i);
+                                    let i = &i.instance_type;
+                                    rsx! {
+                                        option {
+                                            key: "extra-instance-type-key-{idx}",
+                                            value: "{i}",
+                                            "{label}",
+                                        }
+                                    }
+                                })}
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Availability zones"},
+                        td {
+                            input {
+                                "type": "text",
+                                name: "availability_zones",
+                                id: "availability_zones",
+                                placeholder: "us-east-1a,us-east-1b",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Max total price"},
+                        td {
+                            input {
+                                "type": "text",
+                                name: "max_total_price",
+                                id: "max_total_price",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Tenancy"},
+                        td {
+                            select {
+                                id: "tenancy",
+                                option { value: "default", "default" },
+                                option { value: "dedicated", "dedicated" },
+                            }
+                        }
+                    },
+                    tr {
+                        td {"EBS optimized"},
+                        td {
+                            input {
+                                "type": "checkbox",
+                                name: "ebs_optimized",
+                                id: "ebs_optimized",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Root volume"},
+                        td {
+                            input {
+                                "type": "number",
+                                name: "root_volume_size",
+                                id: "root_volume_size",
+                                min: "1",
+                                placeholder: "size GB",
+                            },
+                            select {
+                                id: "root_volume_type",
+                                option { value: "", "ami default" },
+                                option { value: "gp3", "gp3" },
+                                option { value: "gp2", "gp2" },
+                                option { value: "io1", "io1" },
+                                option { value: "io2", "io2" },
+                            },
+                            input {
+                                "type": "number",
+                                name: "root_volume_iops",
+                                id: "root_volume_iops",
+                                min: "100",
+                                placeholder: "iops",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Data volumes"},
+                        td {
+                            input {
+                                "type": "text",
+                                name: "data_volumes",
+                                id: "data_volumes",
+                                placeholder: "/dev/sdf:100:gp3,/dev/sdg:500:st1:keep",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Instance profile"},
+                        td {
+                            select {
+                                id: "instance_profile",
+                                option { value: "", "none" },
+                                {instance_profiles.iter().enumerate().map(|(idx, p)| {
+                                    rsx! {
+                                        option {
+                                            key: "instance-profile-key-{idx}",
+                                            value: "{p}",
+                                            "{p}",
+                                        }
+                                    }
+                                })}
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Require IMDSv2"},
+                        td {
+                            input {
+                                "type": "checkbox",
+                                name: "require_imdsv2",
+                                id: "require_imdsv2",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"DNS name"},
+                        td {
+                            input {
+                                "type": "text",
+                                name: "dns_name",
+                                id: "dns_name",
+                                placeholder: "name.example.com",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Auto recover"},
+                        td {
+                            input {
+                                "type": "checkbox",
+                                name: "auto_recover",
+                                id: "auto_recover",
+                            }
+                        }
+                    },
+                    tr {
+                        td {"Name"},
+                        td {
+                            input {
+                                "type": "text",
+                                name: "name",
+                                id: "name",
+                            }
+                        }
+                    },
+                    tr {
+                        td {
+                            input {
+                                "type": "button",
+                                name: "create_request",
+                                value: "Request",
+                                "onclick": "requestSpotInstance();",
+                            }
+                        }
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn textarea_body(entries: Vec<StackString>, id: StackString) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(TextAreaElement, TextAreaElementProps { entries, id });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn TextAreaElement(entries: Vec<StackString>, id: StackString) -> Element {
+    let rows = entries.len() + 5;
+    let text = entries.join("\n");
+    rsx! {
+        textarea {
+            autofocus: "true",
+            readonly: "readonly",
+            name: "message",
+            id: "{id}",
+            rows: "{rows}",
+            cols: "100",
+            "{text}",
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn instance_status_body(
+    entries: Vec<StackString>,
+    instance: StackString,
+    host_key_error: Option<StackString>,
+) -> Result<String, Error> {
+    let mut app: VirtualDom = VirtualDom::new_with_props(
+        InstanceStatusElement,
+        InstanceStatusElementProps {
+            entries,
+            instance,
+            host_key_error,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn InstanceStatusElement(
+    entries: Vec<StackString>,
+    instance: StackString,
+    host_key_error: Option<StackString>,
+) -> Element {
+    let rows = entries.len() + 5;
+    let text = entries.join("\n");
+    rsx! {
+        {host_key_error.map(|e| rsx! {
+            div {
+                "{e}",
+                input {
+                    "type": "button",
+                    name: "reset_host_key",
+                    value: "Reset Host Key",
+                    "onclick": "resetHostKey('{instance}');",
+                }
+            }
+        })}
+        form {
+            action: "javascript:runCommand('{instance}')",
+            input {
+                "type": "text",
+                name: "command_text",
+                id: "command_text",
+            },
+            input {
+                "type": "button",
+                name: "run_command",
+                value: "Run",
+                "onclick": "runCommand('{instance}');",
+            }
+        }
+        form {
+            action: "javascript:uploadFile('{instance}')",
+            input {
+                "type": "file",
+                name: "upload_file",
+                id: "upload_file",
+            },
+            input {
+                "type": "text",
+                name: "upload_path",
+                id: "upload_path",
+                placeholder: "remote path or directory/",
+            },
+            input {
+                "type": "button",
+                name: "upload",
+                value: "Upload",
+                "onclick": "uploadFile('{instance}');",
+            }
+        }
+        form {
+            action: "javascript:downloadFile('{instance}')",
+            input {
+                "type": "text",
+                name: "download_path",
+                id: "download_path",
+                placeholder: "/var/log/cloud-init-output.log",
+            },
+            input {
+                "type": "button",
+                name: "download",
+                value: "Download",
+                "onclick": "downloadFile('{instance}');",
+            }
+        }
+        textarea {
+            autofocus: "true",
+            readonly: "readonly",
+            name: "message",
+            id: "diary_editor_form",
+            rows: "{rows}",
+            cols: "100",
+            "{text}",
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn textarea_fixed_size_body(body: StackString, id: StackString) -> Result<String, Error> {
+    let mut app: VirtualDom = VirtualDom::new_with_props(
+        TextareaFixedSizeElement,
+        TextareaFixedSizeElementProps { body, id },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn TextareaFixedSizeElement(body: StackString, id: StackString) -> Element {
+    rsx! {
+        textarea {
+            autofocus: "true",
+            readonly: "readonly",
+            name: "message",
+            id: "{id}",
+            rows: "50",
+            cols: "100",
+            "{body}",
+        }
+
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn instance_types_body(instances: Vec<AwsInstancePrice>) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        InstanceTypesElement,
+        InstanceTypesElementProps { instances },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn InstanceTypesElement(instances: Vec<AwsInstancePrice>) -> Element {
+    rsx! {
+        {instances.iter().enumerate().map(|(idx, i)| {
+            let label = instance_price_label(i);
+            let i = &i.instance_type;
+            rsx! {
+                option {
+                    key: "instance-type-key-{idx}",
+                    value: "{i}",
+                    "{label}",
+                }
+            }
+        })}
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn ubuntu_images_body(images: Vec<AmiInfo>) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(UbuntuImagesElement, UbuntuImagesElementProps { images });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn UbuntuImagesElement(images: Vec<AmiInfo>) -> Element {
+    rsx! {
+        {images.iter().enumerate().map(|(idx, ami)| {
+            let id = &ami.id;
+            let name = ami.name.rsplit('/').next().unwrap_or(&ami.name);
+            let published = ami
+                .creation_date
+                .as_ref()
+                .and_then(|d| d.get(..10))
+                .unwrap_or("unknown");
+            rsx! {
+                option {
+                    key: "ubuntu-image-key-{idx}",
+                    value: "{id}",
+                    "{name} (published {published})",
+                }
+            }
+        })}
+    }
+}
+
+/// Option text for the spot request builder, e.g.
+/// `t3.small | spot $0.0062/hr | ond $0.0208/hr | 2 cpu 2 GiB`
+fn instance_price_label(price: &AwsInstancePrice) -> StackString {
+    let fmt_price = |p: Option<f64>| match p {
+        Some(p) => format_sstr!("${p:0.4}/hr"),
+        None => "n/a".into(),
+    };
+    let mut label = format_sstr!(
+        "{} | spot {} | ond {} | {} cpu {} GiB",
+        price.instance_type,
+        fmt_price(price.spot_price),
+        fmt_price(price.ondemand_price),
+        price.ncpu,
+        price.memory,
+    );
+    if price.gpu_count > 0 {
+        let gpu_type = price.gpu_type.as_ref().map_or("GPU", StackString::as_str);
+        label.push_str(&format_sstr!(" | {} x {gpu_type}", price.gpu_count));
+    }
+    label
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn novnc_start_body() -> Result<String, Error> {
+    let mut app = VirtualDom::new(NovncStartElement);
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn novnc_status_body(
+    number: usize,
+    domain: StackString,
+    pids: Vec<usize>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        NovncStatusElement,
+        NovncStatusElementProps {
+            number,
+            domain,
+            pids,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn NovncStatusElement(number: usize, domain: StackString, pids: Vec<usize>) -> Element {
+    rsx! {
+        br {
+            "{number} processes currently running {pids:?}"
+        },
+        a {
+            href: "https://{domain}:8787/vnc.html",
+            target: "_blank",
+            "Connect to NoVNC",
+        }
+        input {
+            "type": "button",
+            name: "novnc",
+            value: "Stop NoVNC",
+            "onclick": "noVncTab('/aws/novnc/stop', 'POST')",
+        }
+    }
+}
+
+#[component]
+fn NovncStartElement() -> Element {
+    rsx! {
+        input {
+            "type": "button",
+            name: "novnc",
+            value: "Start NoVNC",
+            "onclick": "noVncTab('/aws/novnc/start', 'POST')",
+        }
+    }
+}
+
+#[component]
+fn InboundEmailElement(emails: Vec<InboundEmailDB>, sent: Vec<SentEmail>) -> Element {
+    // latest email of each thread first, the rest of the conversation hidden
+    // until the thread is expanded
+    let threads = group_threads(emails, sent);
+    let rows: Vec<_> = threads
+        .iter()
+        .flat_map(|thread| {
+            let count = thread.len();
+            let thread_id = thread.thread_id;
+            thread
+                .latest()
+                .map(ThreadEntry::Received)
+                .into_iter()
+                .chain(thread.earlier())
+                .enumerate()
+                .map(move |(position, entry)| (entry, thread_id, count, position > 0))
+        })
+        .collect();
+    rsx! {
+        select {
+            id: "email_filter",
+            "onchange": "filterEmails(this.value);",
+            option {value: "ham", selected: true, "Ham"},
+            option {value: "spam", "Spam"},
+            option {value: "all", "All"},
+        }
+        {export_link("inbound-email", None)},
+        table {
+            "border": "1",
+            class: "dataframe",
+            id: "inbound_email_table",
+            thead {
+                th {"Date"},
+                th {"From"},
+                th {"To"},
+                th {"Subject"},
+                th {"Spam Score"},
+                th {
+                    input {
+                        "type": "button",
+                        name: "sync",
+                        value: "Sync",
+                        "onclick": "syncEmail()",
+                    }
+                    input {
+                        "type": "button",
+                        name: "cleanup_attachments",
+                        value: "Delete Orphaned Attachments",
+                        "onclick": "deleteOrphanedAttachments()",
+                    }
+                }
+            },
+            tbody {
+                {rows.iter().enumerate().map(|(idx, (entry, thread_id, count, is_earlier))| {
+                    let email = match entry {
+                        ThreadEntry::Received(email) => email,
+                        ThreadEntry::Sent(sent) => {
+                            let from = &sent.from_address;
+                            let to = &sent.to_address;
+                            let subject = &sent.subject;
+                            let date = &sent.sent_at;
+                            let body = &sent.body;
+                            return rsx! {
+                                tr {
+                                    key: "email-key-{idx}",
+                                    "data-spam": "false",
+                                    "data-thread": "{thread_id}",
+                                    "data-collapsed": "true",
+                                    style: "display: none;",
+                                    td {"{date}"},
+                                    td {"{from}"},
+                                    td {"{to}"},
+                                    td {
+                                        style: "padding-left: 2em;",
+                                        title: "{body}",
+                                        "{subject} (sent)"
+                                    },
+                                    td {},
+                                    td {},
+                                }
+                            };
+                        }
+                    };
+                    let id = &email.id;
+                    let from = &email.from_address;
+                    let to = &email.to_address;
+                    let subject = &email.subject;
+                    let date = &email.date;
+                    let score = format_sstr!("{:0.1}", email.spam_score);
+                    let verdicts = email.verdicts.as_ref().map_or("", StackString::as_str);
+                    let is_spam = email.is_spam();
+                    let style = if is_spam || *is_earlier {"display: none;"} else {""};
+                    let subject_style = if *is_earlier {"padding-left: 2em;"} else {""};
+                    let (feedback, feedback_label) = if is_spam {
+                        ("false", "Not Spam")
+                    } else {
+                        ("true", "Mark Spam")
+                    };
+                    rsx! {
+                        tr {
+                            key: "email-key-{idx}",
+                            "data-spam": "{is_spam}",
+                            "data-thread": "{thread_id}",
+                            "data-collapsed": "{is_earlier}",
+                            style: "{style}",
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "date",
+                                    value: "{date}",
+                                    "onclick": "emailDetail('{id}')",
+                                }
+                            }
+                            td {
+                                "{from}"
+                            }
+                            td {
+                                "{to}"
+                            }
+                            td {
+                                style: "{subject_style}",
+                                "{subject}"
+                                if *count > 1 && !*is_earlier {
+                                    input {
+                                        "type": "button",
+                                        name: "thread",
+                                        value: "{count} in thread",
+                                        "onclick": "toggleThread('{thread_id}')",
+                                    }
+                                }
+                            }
+                            td {
+                                title: "{verdicts}",
+                                "{score}"
+                            }
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "delete",
+                                    value: "Delete",
+                                    "onclick": "deleteEmail('{id}')",
+                                }
+                                input {
+                                    "type": "button",
+                                    name: "spam_feedback",
+                                    value: "{feedback_label}",
+                                    "onclick": "emailSpamFeedback('{id}', {feedback})",
+                                }
+                            }
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn inbound_email_body(
+    id: Uuid,
+    text: StackString,
+    html: StackString,
+    raw: StackString,
+    attachments: Vec<EmailAttachment>,
+    sent: Vec<SentEmail>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        InboundEmailDetailElement,
+        InboundEmailDetailElementProps {
+            id,
+            text,
+            html,
+            raw,
+            attachments,
+            sent,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn InboundEmailDetailElement(
+    id: Uuid,
+    text: StackString,
+    html: StackString,
+    raw: StackString,
+    attachments: Vec<EmailAttachment>,
+    sent: Vec<SentEmail>,
+) -> Element {
+    let rows = text.split('\n').count() + 5;
+    let raw_rows = raw.split('\n').count() + 5;
+    rsx! {
+        if !sent.is_empty() {
+            table {
+                "border": "1",
+                class: "dataframe",
+                thead {
+                    th {"Replied"},
+                    th {"To"},
+                    th {"Reply"},
+                },
+                tbody {
+                    {sent.iter().enumerate().map(|(idx, reply)| {
+                        let sent_at = &reply.sent_at;
+                        let to = &reply.to_address;
+                        let body = &reply.body;
+                        rsx! {
+                            tr {
+                                key: "sent-key-{idx}",
+                                td {"{sent_at}"},
+                                td {"{to}"},
+                                td {
+                                    pre {"{body}"}
+                                },
+                            }
+                        }
+                    })}
+                }
+            }
+        }
+        br {
+            textarea {
+                name: "reply-body",
+                id: "reply-body-form",
+                rows: "8",
+                cols: "100",
+                placeholder: "Reply, the original text is quoted below it",
+            }
+            input {
+                "type": "button",
+                name: "reply",
+                value: "Send Reply",
+                "onclick": "replyEmail('{id}')",
+            }
+        }
+        if !attachments.is_empty() {
+            table {
+                "border": "1",
+                class: "dataframe",
+                thead {
+                    th {"Attachment"},
+                    th {"Content Type"},
+                    th {"Size"},
+                },
+                tbody {
+                    {attachments.iter().enumerate().map(|(idx, attachment)| {
+                        let id = &attachment.id;
+                        let filename = &attachment.filename;
+                        let content_type = attachment
+                            .content_type
+                            .as_ref()
+                            .map_or("", StackString::as_str);
+                        let size = format_sstr!("{:0.1} kB", attachment.size as f64 / 1e3);
+                        rsx! {
+                            tr {
+                                key: "attachment-key-{idx}",
+                                td {
+                                    a {
+                                        href: "/aws/inbound-email/attachment/{id}",
+                                        "{filename}",
+                                    }
+                                }
+                                td {"{content_type}"},
+                                td {"{size}"},
+                            }
+                        }
+                    })}
+                }
+            }
+        }
+        br {
+            textarea {
+                name: "text-content",
+                id: "text-content-form",
+                rows: "{rows}",
+                cols: "100",
+                "{text}",
+            }
+        }
+        br {
+            div {
+                dangerous_inner_html: "{html}",
+            }
+        }
+        br {
+            textarea {
+                name: "raw-content",
+                id: "raw-content-form",
+                rows: "{raw_rows}",
+                cols: "100",
+                "{raw}",
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn ses_identities_body(
+    identities: Vec<SesIdentity>,
+    rule_sets: Vec<ReceiptRuleSetInfo>,
+    inbound_email_bucket: Option<StackString>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        SesIdentitiesElement,
+        SesIdentitiesElementProps {
+            identities,
+            rule_sets,
+            inbound_email_bucket,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn SesIdentitiesElement(
+    identities: Vec<SesIdentity>,
+    rule_sets: Vec<ReceiptRuleSetInfo>,
+    inbound_email_bucket: Option<StackString>,
+) -> Element {
+    let bucket = inbound_email_bucket.unwrap_or_else(|| "not configured".into());
+    rsx! {
+        h3 {"Identities"},
+        form {
+            action: "javascript:sesVerifyIdentity()",
+            input {
+                "type": "text",
+                name: "ses_identity",
+                id: "ses_identity",
+                placeholder: "example.com",
+            },
+            input {
+                "type": "button",
+                name: "verify",
+                value: "Verify",
+                "onclick": "sesVerifyIdentity();",
+            }
+        }
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Identity"},
+                th {"Type"},
+                th {"Verification"},
+                th {"Sending"},
+                th {"DKIM"},
+                th {"DKIM Records"},
+            },
+            tbody {
+                {identities.iter().enumerate().map(|(idx, identity)| {
+                    let name = &identity.name;
+                    let identity_type = &identity.identity_type;
+                    let verification = &identity.verification_status;
+                    let sending = if identity.sending_enabled {"enabled"} else {"disabled"};
+                    let dkim = if identity.dkim_signing_enabled {
+                        identity.dkim_status.clone()
+                    } else {
+                        format_sstr!("{} (signing disabled)", identity.dkim_status)
+                    };
+                    let records = identity
+                        .dkim_records()
+                        .into_iter()
+                        .map(|(name, value)| format_sstr!("CNAME {name} {value}"))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    rsx! {
+                        tr {
+                            key: "identity-key-{idx}",
+                            td {"{name}"},
+                            td {"{identity_type}"},
+                            td {"{verification}"},
+                            td {"{sending}"},
+                            td {"{dkim}"},
+                            td {pre {"{records}"}},
+                        }
+                    }
+                })}
+            }
+        }
+        h3 {"Receipt Rules (inbound email bucket {bucket})"},
+        form {
+            action: "javascript:sesCreateReceiptRule()",
+            input {
+                "type": "text",
+                name: "ses_rule_set",
+                id: "ses_rule_set",
+                placeholder: "rule set",
+            },
+            input {
+                "type": "text",
+                name: "ses_rule_name",
+                id: "ses_rule_name",
+                placeholder: "rule name",
+            },
+            input {
+                "type": "text",
+                name: "ses_recipients",
+                id: "ses_recipients",
+                placeholder: "recipients, comma separated",
+            },
+            input {
+                "type": "button",
+                name: "create_rule",
+                value: "Add Rule",
+                "onclick": "sesCreateReceiptRule();",
+            }
+        }
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Rule Set"},
+                th {"Rule"},
+                th {"Enabled"},
+                th {"Recipients"},
+                th {"S3 Destination"},
+                th {},
+                th {},
+            },
+            tbody {
+                {rule_sets.iter().enumerate().map(|(set_idx, rule_set)| {
+                    let set_name = &rule_set.name;
+                    let active = rule_set.active;
+                    if rule_set.rules.is_empty() {
+                        return rsx! {
+                            tr {
+                                key: "rule-set-key-{set_idx}",
+                                td {"{set_name}"},
+                                td {}, td {}, td {}, td {}, td {},
+                                td {
+                                    if active {
+                                        "active"
+                                    } else {
+                                        input {
+                                            "type": "button",
+                                            name: "activate",
+                                            value: "Activate",
+                                            "onclick": "sesActivateRuleSet('{set_name}')",
+                                        }
+                                    }
+                                },
+                            }
+                        };
+                    }
+                    rsx! {
+                        {rule_set.rules.iter().enumerate().map(|(idx, rule)| {
+                            let rule_name = &rule.name;
+                            let enabled = rule.enabled;
+                            let recipients = if rule.recipients.is_empty() {
+                                "all verified domains".into()
+                            } else {
+                                rule.recipients.join(", ")
+                            };
+                            let destination = rule.s3_bucket.as_ref().map_or_else(
+                                String::new,
+                                |b| format!("s3://{b}/{}", rule.s3_prefix.as_deref().unwrap_or("")),
+                            );
+                            rsx! {
+                                tr {
+                                    key: "rule-key-{set_idx}-{idx}",
+                                    td {"{set_name}"},
+                                    td {"{rule_name}"},
+                                    td {"{enabled}"},
+                                    td {"{recipients}"},
+                                    td {"{destination}"},
+                                    td {
+                                        input {
+                                            "type": "button",
+                                            name: "delete_rule",
+                                            value: "Delete",
+                                            "onclick": "sesDeleteReceiptRule('{set_name}', '{rule_name}')",
+                                        }
+                                    },
+                                    td {
+                                        if idx == 0 && active {
+                                            "active"
+                                        } else if idx == 0 {
+                                            input {
+                                                "type": "button",
+                                                name: "activate",
+                                                value: "Activate",
+                                                "onclick": "sesActivateRuleSet('{set_name}')",
+                                            }
+                                        }
+                                    },
+                                }
+                            }
+                        })}
+                    }
+                })}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn instance_metadata_body(metadata: Option<InstanceMetadata>) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        InstanceMetadataElement,
+        InstanceMetadataElementProps { metadata },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn InstanceMetadataElement(metadata: Option<InstanceMetadata>) -> Element {
+    let metadata = match metadata {
+        Some(metadata) => metadata,
+        None => return rsx! {"Not running on EC2 (instance metadata unavailable)"},
+    };
+    let interruption = metadata.interruption_status();
+    let rows = [
+        ("Instance ID", Some(&metadata.instance_id)),
+        ("Instance Type", Some(&metadata.instance_type)),
+        ("Lifecycle", metadata.lifecycle.as_ref()),
+        ("Availability Zone", Some(&metadata.availability_zone)),
+        ("Region", Some(&metadata.region)),
+        ("Account", Some(&metadata.account_id)),
+        ("AMI", Some(&metadata.image_id)),
+        ("Private IP", metadata.private_ip.as_ref()),
+        ("Public IP", metadata.public_ip.as_ref()),
+        ("IAM Role", metadata.iam_role.as_ref()),
+        ("Instance Profile", metadata.instance_profile_arn.as_ref()),
+        ("Spot Interruption", Some(&interruption)),
+    ];
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            tbody {
+                {rows.iter().enumerate().map(|(idx, (name, value))| {
+                    let value = value.map_or("", StackString::as_str);
+                    rsx! {
+                        tr {
+                            key: "metadata-key-{idx}",
+                            td {"{name}"},
+                            td {"{value}"},
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn email_rules_body(rules: Vec<EmailForwardRule>) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(EmailRulesElement, EmailRulesElementProps { rules });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn EmailRulesElement(rules: Vec<EmailForwardRule>) -> Element {
+    rsx! {
+        h3 {"Forwarding Rules"},
+        form {
+            action: "javascript:saveEmailRule()",
+            input {
+                "type": "text",
+                name: "rule_name",
+                id: "rule_name",
+                placeholder: "name",
+            },
+            input {
+                "type": "text",
+                name: "rule_recipient",
+                id: "rule_recipient",
+                placeholder: "recipient regex",
+            },
+            input {
+                "type": "text",
+                name: "rule_subject",
+                id: "rule_subject",
+                placeholder: "subject regex",
+            },
+            input {
+                "type": "text",
+                name: "rule_forward_to",
+                id: "rule_forward_to",
+                placeholder: "forward to",
+            },
+            input {
+                "type": "button",
+                name: "save_rule",
+                value: "Add Rule",
+                "onclick": "saveEmailRule();",
+            }
+        }
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Name"},
+                th {"Recipient Pattern"},
+                th {"Subject Pattern"},
+                th {"Forward To"},
+                th {"Enabled"},
+                th {},
+                th {},
+            },
+            tbody {
+                {rules.iter().enumerate().map(|(idx, rule)| {
+                    let id = rule.id;
+                    let name = &rule.name;
+                    let recipient = rule.recipient_pattern.as_deref().unwrap_or("");
+                    let subject = rule.subject_pattern.as_deref().unwrap_or("");
+                    let forward_to = &rule.forward_to;
+                    let enabled = rule.enabled;
+                    let toggle = if enabled {"Disable"} else {"Enable"};
+                    rsx! {
+                        tr {
+                            key: "email-rule-key-{idx}",
+                            td {"{name}"},
+                            td {"{recipient}"},
+                            td {"{subject}"},
+                            td {"{forward_to}"},
+                            td {"{enabled}"},
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "toggle_rule",
+                                    value: "{toggle}",
+                                    "onclick": "toggleEmailRule('{id}', {!enabled})",
+                                }
+                            },
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "delete_rule",
+                                    value: "Delete",
+                                    "onclick": "deleteEmailRule('{id}')",
+                                }
+                            },
+                        }
+                    }
+                })}
+            }
+        }
+        h3 {"Dry Run"},
+        form {
+            action: "javascript:testEmailRules()",
+            input {
+                "type": "text",
+                name: "test_to_address",
+                id: "test_to_address",
+                placeholder: "to address",
+            },
+            input {
+                "type": "text",
+                name: "test_subject",
+                id: "test_subject",
+                placeholder: "subject",
+            },
+            input {
+                "type": "button",
+                name: "test_rules",
+                value: "Test",
+                "onclick": "testEmailRules();",
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn launch_analytics_body(analytics: LaunchAnalytics) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        LaunchAnalyticsElement,
+        LaunchAnalyticsElementProps { analytics },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn LaunchAnalyticsElement(analytics: LaunchAnalytics) -> Element {
+    let ymd = format_description!("[year]-[month]-[day]");
+    let fmt_pct =
+        |x: Option<f64>| x.map_or_else(|| "n/a".into(), |x| format_sstr!("{:0.1}%", x * 100.0));
+    let total_launches = analytics.total_launches;
+    let spot_launches = analytics.spot_launches;
+    let ondemand_launches = total_launches - spot_launches;
+    let spot_fraction = fmt_pct(analytics.spot_fraction());
+    let failure_rate = fmt_pct(analytics.spot_failure_rate());
+    let lifetime = analytics
+        .average_lifetime_hours
+        .map_or_else(|| "n/a".into(), |h| format_sstr!("{h:0.1} hours"));
+    let max_launches = analytics
+        .weekly_launches
+        .iter()
+        .map(|w| w.launches)
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            tbody {
+                tr {td {"Total Launches"}, td {"{total_launches}"}},
+                tr {td {"Spot / On-Demand"}, td {"{spot_launches} / {ondemand_launches} ({spot_fraction} spot)"}},
+                tr {td {"Spot Request Failure Rate"}, td {"{failure_rate}"}},
+                tr {td {"Average Instance Lifetime"}, td {"{lifetime}"}},
+            }
+        },
+        br {},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Week"},
+                    th {"Launches"},
+                    th {"Spot"},
+                    th {},
+                }
+            },
+            tbody {
+                {analytics.weekly_launches.iter().enumerate().map(|(idx, w)| {
+                    let week = w.week.date().format(ymd).unwrap_or_else(|_| String::new());
+                    let launches = w.launches;
+                    let spot = w.spot_launches;
+                    let width = launches * 300 / max_launches;
+                    rsx! {
+                        tr {
+                            key: "weekly-launches-key-{idx}",
+                            style: "text-align: left;",
+                            td {"{week}"},
+                            td {"{launches}"},
+                            td {"{spot}"},
+                            td {
+                                div {
+                                    style: "background-color: steelblue; height: 12px; width: {width}px;",
+                                }
+                            },
+                        }
+                    }
+                })}
+            }
+        },
+        br {},
+        {launch_count_element("AMI", &analytics.top_amis)},
+        br {},
+        {launch_count_element("Instance Type", &analytics.top_instance_types)},
+    }
+}
+
+fn launch_count_element(title: &str, counts: &[LaunchCount]) -> Element {
+    let max_count = counts.iter().map(|c| c.count).max().unwrap_or(1).max(1);
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"{title}"},
+                    th {"Launches"},
+                    th {},
+                }
+            },
+            tbody {
+                {counts.iter().enumerate().map(|(idx, c)| {
+                    let key = &c.key;
+                    let count = c.count;
+                    let width = count * 300 / max_count;
+                    rsx! {
+                        tr {
+                            key: "launch-count-{title}-{idx}",
+                            style: "text-align: left;",
+                            td {"{key}"},
+                            td {"{count}"},
+                            td {
+                                div {
+                                    style: "background-color: steelblue; height: 12px; width: {width}px;",
+                                }
+                            },
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+/// # Errors
+/// Returns error if formatting fails
+pub fn tasks_body(tasks: Vec<TaskInfo>) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(TasksElement, TasksElementProps { tasks });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn TasksElement(tasks: Vec<TaskInfo>) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Task"},
+                    th {"Status"},
+                    th {"Attempts"},
+                    th {"Started"},
+                    th {"Finished"},
+                }
+            },
+            tbody {
+                {tasks.iter().enumerate().map(|(idx, task)| {
+                    let name = &task.name;
+                    let status = &task.status;
+                    let attempts = task.attempts;
+                    let started = task.started.to_timezone(local_tz);
+                    let finished = task.finished.map_or_else(StackString::new, |f| {
+                        format_sstr!("{}", f.to_timezone(local_tz))
+                    });
+                    rsx! {
+                        tr {
+                            key: "tasks-key-{idx}",
+                            style: "text-align: left;",
+                            td {"{name}"},
+                            td {"{status}"},
+                            td {"{attempts}"},
+                            td {"{started}"},
+                            td {"{finished}"},
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+
+/// Preferences form listing the columns of each resource table that can be
+/// hidden
+/// # Errors
+/// Returns error if formatting fails
+pub async fn get_preferences_page(
+    aws: &AwsAppInterface,
+    preferences: UserPreferences,
+) -> Result<String, Error> {
+    let columns: Vec<(StackString, Vec<StackString>)> = ALL_RESOURCES
+        .iter()
+        .filter(|r| !table_columns(**r).is_empty())
+        .map(|r| {
+            let columns = table_columns(*r).iter().map(|c| (*c).into()).collect();
+            (r.to_str().into(), columns)
+        })
+        .collect();
+    let mut regions: Vec<StackString> = aws.ec2.get_all_regions().await?.into_keys().collect();
+    regions.sort();
+    let current_region: StackString = aws.ec2.region().into();
+    let mut app = VirtualDom::new_with_props(
+        PreferencesElement,
+        PreferencesElementProps {
+            preferences,
+            columns,
+            regions,
+            current_region,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn PreferencesElement(
+    preferences: UserPreferences,
+    columns: Vec<(StackString, Vec<StackString>)>,
+    regions: Vec<StackString>,
+    current_region: StackString,
+) -> Element {
+    let sort = preferences.default_sort.as_deref().unwrap_or("");
+    let rows = preferences
+        .rows_per_page
+        .map_or_else(StackString::new, |r| format_sstr!("{r}"));
+    let default_region = preferences.default_region.as_deref().unwrap_or("");
+    rsx! {
+        h3 {"Preferences"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            tbody {
+                tr {
+                    td {"Instance sort"},
+                    td {
+                        select {
+                            id: "pref_sort",
+                            option {value: "", selected: sort.is_empty(), "default"},
+                            {INSTANCE_SORT_KEYS.iter().enumerate().map(|(idx, key)| {
+                                let key = key.to_str();
+                                rsx! {
+                                    option {key: "pref-sort-{idx}", value: "{key}", selected: sort == key, "{key}"}
+                                }
+                            })},
+                        },
+                        input {"type": "checkbox", id: "pref_descending", checked: preferences.sort_descending},
+                        "descending",
+                    },
+                },
+                tr {
+                    td {"Rows per page"},
+                    td {
+                        input {"type": "number", id: "pref_rows", min: "1", max: "{MAX_ROWS_PER_PAGE}", value: "{rows}", placeholder: "all"},
+                    },
+                },
+                tr {
+                    td {"Default region"},
+                    td {
+                        select {
+                            id: "pref_region",
+                            option {value: "", selected: default_region.is_empty(), "current ({current_region})"},
+                            {regions.iter().enumerate().map(|(idx, region)| {
+                                rsx! {
+                                    option {key: "pref-region-{idx}", value: "{region}", selected: default_region == region.as_str(), "{region}"}
+                                }
+                            })},
+                        },
+                    },
+                },
+            },
+        },
+        h4 {"Visible columns"},
+        {columns.iter().enumerate().map(|(idx, (resource, names))| {
+            let hidden = preferences.hidden_columns(resource);
+            rsx! {
+                div {
+                    key: "pref-columns-{idx}",
+                    b {"{resource}: "},
+                    {names.iter().enumerate().map(|(jdx, name)| {
+                        let checked = !hidden.contains(name);
+                        rsx! {
+                            label {
+                                key: "pref-column-{idx}-{jdx}",
+                                input {
+                                    "type": "checkbox",
+                                    class: "pref-column",
+                                    "data-resource": "{resource}",
+                                    "data-column": "{name}",
+                                    checked: checked,
+                                },
+                                "{name} ",
+                            }
+                        }
+                    })},
+                }
+            }
+        })},
+        input {"type": "button", name: "save_preferences", value: "Save", "onclick": "savePreferences();"},
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn price_alerts_body(alerts: Vec<PriceAlert>, default_region: &str) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        PriceAlertsElement,
+        PriceAlertsElementProps {
+            alerts,
+            default_region: default_region.into(),
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+/// Alerts whose price is currently below their threshold are shown in red
+#[component]
+fn PriceAlertsElement(alerts: Vec<PriceAlert>, default_region: StackString) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
+    rsx! {
+        h3 {"Spot Price Alerts"},
+        form {
+            action: "javascript:createPriceAlert()",
+            input {
+                "type": "text",
+                name: "price_alert_instance_type",
+                id: "price_alert_instance_type",
+                placeholder: "m5.large",
+            },
+            input {
+                "type": "text",
+                name: "price_alert_region",
+                id: "price_alert_region",
+                value: "{default_region}",
+            },
+            input {
+                "type": "number",
+                name: "price_alert_threshold",
+                id: "price_alert_threshold",
+                step: "0.0001",
+                min: "0",
+                placeholder: "0.05",
+            },
+            input {
+                "type": "button",
+                name: "create_price_alert",
+                value: "Add Alert",
+                "onclick": "createPriceAlert();",
+            }
+        }
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Instance Type"},
+                th {"Region"},
+                th {"Threshold"},
+                th {"Last Price"},
+                th {"Below Since"},
+                th {"Enabled"},
+                th {},
+                th {},
+            },
+            tbody {
+                {alerts.iter().enumerate().map(|(idx, alert)| {
+                    let id = alert.id;
+                    let instance_type = &alert.instance_type;
+                    let region = &alert.region;
+                    let threshold = format_sstr!("${:.4}", alert.threshold);
+                    let last_price = alert
+                        .last_price
+                        .map_or_else(StackString::new, |p| format_sstr!("${p:.4}"));
+                    let below_since = alert.triggered_at.map_or_else(StackString::new, |t| {
+                        format_sstr!("{}", t.to_timezone(local_tz))
+                    });
+                    let class = if alert.triggered_at.is_some() {"credential-warning"} else {""};
+                    let enabled = alert.enabled;
+                    let (toggle, toggle_label) = if enabled {
+                        (false, "Disable")
+                    } else {
+                        (true, "Enable")
+                    };
+                    rsx! {
+                        tr {
+                            key: "price-alert-key-{idx}",
+                            style: "text-align: center;",
+                            td {"{instance_type}"},
+                            td {"{region}"},
+                            td {"{threshold}"},
+                            td {class: "{class}", "{last_price}"},
+                            td {"{below_since}"},
+                            td {"{enabled}"},
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "update_price_alert",
+                                    value: "{toggle_label}",
+                                    "onclick": "updatePriceAlert('{id}', {toggle})",
+                                }
+                            },
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "delete_price_alert",
+                                    value: "Delete",
+                                    "onclick": "deletePriceAlert('{id}')",
+                                }
+                            },
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn dr_policies_body(compliance: Vec<(DrPolicy, Vec<DrCompliance>)>) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(DrPoliciesElement, DrPoliciesElementProps { compliance });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+/// Volumes lacking a recent off-region copy are shown in red
+#[component]
+fn DrPoliciesElement(compliance: Vec<(DrPolicy, Vec<DrCompliance>)>) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
+    let entries: Vec<_> = compliance.iter().flat_map(|(_, entries)| entries).collect();
+    rsx! {
+        h3 {"Disaster Recovery Policies"},
+        form {
+            action: "javascript:createDrPolicy()",
+            input {
+                "type": "text",
+                name: "dr_volume_name",
+                id: "dr_volume_name",
+                placeholder: "Volume Name tag",
+            },
+            input {
+                "type": "text",
+                name: "dr_target_region",
+                id: "dr_target_region",
+                placeholder: "us-west-2",
+            },
+            input {
+                "type": "number",
+                name: "dr_max_age_hours",
+                id: "dr_max_age_hours",
+                min: "1",
+                value: "24",
+            },
+            input {
+                "type": "text",
+                name: "dr_kms_key_id",
+                id: "dr_kms_key_id",
+                placeholder: "KMS key (optional)",
+            },
+            label {
+                input {"type": "checkbox", id: "dr_encrypted", checked: true},
+                "Encrypted",
+            },
+            input {
+                "type": "button",
+                name: "create_dr_policy",
+                value: "Add Policy",
+                "onclick": "createDrPolicy();",
+            }
+        }
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Volume Name"},
+                th {"Target Region"},
+                th {"Max Age (hours)"},
+                th {"Encrypted"},
+                th {"KMS Key"},
+                th {},
+            },
+            tbody {
+                {compliance.iter().enumerate().map(|(idx, (policy, _))| {
+                    let id = policy.id;
+                    let volume_name = &policy.volume_name;
+                    let target_region = &policy.target_region;
+                    let max_age_hours = policy.max_age_hours;
+                    let encrypted = policy.encrypted;
+                    let kms_key_id = policy.kms_key_id.as_ref().map_or("", StackString::as_str);
+                    rsx! {
+                        tr {
+                            key: "dr-policy-key-{idx}",
+                            style: "text-align: center;",
+                            td {"{volume_name}"},
+                            td {"{target_region}"},
+                            td {"{max_age_hours}"},
+                            td {"{encrypted}"},
+                            td {"{kms_key_id}"},
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "delete_dr_policy",
+                                    value: "Delete",
+                                    "onclick": "deleteDrPolicy('{id}')",
+                                }
+                            },
+                        }
+                    }
+                })}
+            }
+        }
+        h3 {"Off-Region Copy Compliance"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Volume Name"},
+                th {"Volume ID"},
+                th {"Target Region"},
+                th {"Status"},
+                th {"Latest Copy"},
+                th {"Copied At"},
+            },
+            tbody {
+                {entries.iter().enumerate().map(|(idx, entry)| {
+                    let volume_name = &entry.volume_name;
+                    let volume_id = entry.volume_id.as_ref().map_or("", StackString::as_str);
+                    let target_region = &entry.target_region;
+                    let status = entry.status;
+                    let class = if status.is_compliant() {""} else {"credential-warning"};
+                    let latest_copy = entry.latest_copy.as_ref().map_or("", StackString::as_str);
+                    let copied_at = entry.copied_at.map_or_else(StackString::new, |t| {
+                        format_sstr!("{}", t.to_timezone(local_tz))
+                    });
+                    rsx! {
+                        tr {
+                            key: "dr-compliance-key-{idx}",
+                            style: "text-align: center;",
+                            td {"{volume_name}"},
+                            td {"{volume_id}"},
+                            td {"{target_region}"},
+                            td {class: "{class}", "{status}"},
+                            td {"{latest_copy}"},
+                            td {"{copied_at}"},
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
 
 /// # Errors
 /// Returns error if formatting fails
-pub fn build_spot_request_body(
-    amis: Vec<AmiInfo>,
-    inst_fams: Vec<InstanceFamily>,
-    instances: Vec<InstanceList>,
-    files: Vec<StackString>,
-    keys: Vec<(StackString, StackString)>,
-    config: Config,
+pub fn inventory_changes_body(
+    diff: Option<InventoryDiff>,
+    since: StackString,
 ) -> Result<String, Error> {
     let mut app = VirtualDom::new_with_props(
-        BuildSpotRequestElement,
-        BuildSpotRequestElementProps {
-            amis,
-            inst_fams,
-            instances,
-            files,
-            keys,
-            config,
-        },
+        InventoryChangesElement,
+        InventoryChangesElementProps { diff, since },
     );
     app.rebuild_in_place();
     let mut renderer = dioxus_ssr::Renderer::default();
@@ -1452,169 +5483,309 @@ pub fn build_spot_request_body(
     Ok(buffer)
 }
 
+/// Created resources are shown in green, deleted ones in red
 #[component]
-fn BuildSpotRequestElement(
-    amis: Vec<AmiInfo>,
-    inst_fams: Vec<InstanceFamily>,
-    instances: Vec<InstanceList>,
-    files: Vec<StackString>,
-    keys: Vec<(StackString, StackString)>,
-    config: Config,
-) -> Element {
-    let sec = config.spot_security_group.as_ref().unwrap_or_else(|| {
-        config
-            .default_security_group
-            .as_ref()
-            .expect("NO DEFAULT_SECURITY_GROUP")
-    });
-    let price = config.max_spot_price;
+fn InventoryChangesElement(diff: Option<InventoryDiff>, since: StackString) -> Element {
     rsx! {
+        h3 {"Inventory Changes"},
         form {
-            action: "javascript:createScript()",
-            table {
-                tbody {
-                    tr {
-                        td {"Ami:"},
-                        td {
-                            select {
-                                id: "ami",
-                                {amis.iter().enumerate().map(|(idx, ami)| {
-                                    let id = &ami.id;
-                                    let name = &ami.name;
-                                    rsx! {
-                                        option {
-                                            key: "ami-key-{idx}",
-                                            value: "{id}",
-                                            "{name}"
-                                        }
-                                    }
-                                })}
-                            }
-                        }
+            action: "javascript:inventoryChanges()",
+            input {
+                "type": "text",
+                name: "inventory_since",
+                id: "inventory_since",
+                size: "24",
+                placeholder: "24h, 7d or 2024-06-01",
+                value: "{since}",
+            },
+            input {
+                "type": "button",
+                name: "inventory_changes",
+                value: "Compare",
+                "onclick": "inventoryChanges();",
+            }
+        }
+        {match diff {
+            None => rsx! {
+                div {"No inventory snapshot has been taken yet"}
+            },
+            Some(diff) => {
+                let region = &diff.region;
+                let before = map_date(Some(diff.before.into()));
+                let after = map_date(Some(diff.after.into()));
+                let created = diff.count(ChangeKind::Created);
+                let deleted = diff.count(ChangeKind::Deleted);
+                let modified = diff.count(ChangeKind::Modified);
+                rsx! {
+                    div {
+                        "{region} from {before} to {after}: {created} created, {deleted} deleted, {modified} modified"
                     },
-                    tr {
-                        td {"Instance family"},
-                        td {
-                            select {
-                                id: "inst_fam",
-                                "onchange": "instanceOptions()",
-                                {inst_fams.iter().enumerate().map(|(idx, fam)| {
-                                    let n = &fam.family_name;
-                                    rsx! {
-                                        option {
-                                            key: "inst-fam-key-{idx}",
-                                            value: "{n}",
-                                            "{n}",
-                                        }
-                                    }
-                                })}
-                            }
+                    table {
+                        "border": "1",
+                        class: "dataframe",
+                        thead {
+                            th {"Change"},
+                            th {"Type"},
+                            th {"Id"},
+                            th {"Name"},
+                            th {"Differences"},
                         },
-                    },
-                    tr {
-                        td {"Instance type"},
-                        td {
-                            select {
-                                id: "instance_type",
-                                {instances.iter().enumerate().map(|(idx, i)| {
-                                    let i = &i.instance_type;
-                                    rsx! {
-                                        option {
-                                            key: "instance-type-key-{idx}",
-                                            value: "{i}",
-                                            "{i}",
-                                        }
+                        tbody {
+                            {diff.changes.iter().enumerate().map(|(idx, change)| {
+                                let kind = change.kind.to_str();
+                                let kind_class = match change.kind {
+                                    ChangeKind::Created => "change-created",
+                                    ChangeKind::Deleted => "credential-warning",
+                                    ChangeKind::Modified => "",
+                                };
+                                let resource_type = change.resource_type.to_str();
+                                let id = &change.id;
+                                let name = change.name.as_ref().map_or("", StackString::as_str);
+                                rsx! {
+                                    tr {
+                                        key: "change-key-{idx}",
+                                        style: "text-align: center;",
+                                        td {class: "{kind_class}", "{kind}"},
+                                        td {"{resource_type}"},
+                                        td {"{id}"},
+                                        td {"{name}"},
+                                        td {
+                                            {change.changes.iter().enumerate().map(|(cidx, attr)| {
+                                                let attribute = &attr.attribute;
+                                                let before = attr
+                                                    .before
+                                                    .as_ref()
+                                                    .map_or("(unset)", StackString::as_str);
+                                                let after = attr
+                                                    .after
+                                                    .as_ref()
+                                                    .map_or("(unset)", StackString::as_str);
+                                                rsx! {
+                                                    div {
+                                                        key: "change-attr-key-{idx}-{cidx}",
+                                                        "{attribute}: {before} -> {after}"
+                                                    }
+                                                }
+                                            })}
+                                        },
                                     }
-                                })}
-                            }
-                        }
-                    },
-                    tr {
-                        td {"Security group"},
-                        td {
-                            input {
-                                "type": "text",
-                                name: "security_group",
-                                id: "security_group",
-                                value: "{sec}",
-                            }
+                                }
+                            })}
                         }
-                    },
-                    tr {
-                        td {"Script"},
-                        td {
-                            select {
-                                id: "script",
-                                {files.iter().enumerate().map(|(idx, f)| {
-                                    rsx! {
-                                        option {
-                                            key: "script-key-{idx}",
-                                            value: "{f}",
-                                            "{f}",
-                                        }
-                                    }
-                                })}
-                            }
+                    }
+                }
+            }
+        }}
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn quotas_body(usages: Vec<QuotaUsage>) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(QuotasElement, QuotasElementProps { usages });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+/// Quotas at or above the warning threshold are shown in red
+#[component]
+fn QuotasElement(usages: Vec<QuotaUsage>) -> Element {
+    let threshold = QUOTA_WARNING_THRESHOLD * 100.0;
+    let warnings = usages.iter().filter(|u| u.is_warning()).count();
+    rsx! {
+        h3 {"Service Quotas"},
+        div {"{warnings} quotas at or above {threshold:.0}% of their limit"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Category"},
+                th {"Quota"},
+                th {"Quota Code"},
+                th {"Usage"},
+                th {"Limit"},
+                th {"Utilization"},
+            },
+            tbody {
+                {usages.iter().enumerate().map(|(idx, usage)| {
+                    let category = usage.category.to_str();
+                    let name = &usage.name;
+                    let quota_code = usage.quota_code;
+                    let unit = usage.unit;
+                    let used = format_sstr!("{:.1} {unit}", usage.usage);
+                    let limit = usage
+                        .quota
+                        .map_or_else(|| "unavailable".into(), |q| format_sstr!("{q:.1} {unit}"));
+                    let utilization = usage
+                        .utilization()
+                        .map_or_else(StackString::new, |u| format_sstr!("{:.0}%", u * 100.0));
+                    let class = if usage.is_warning() {"credential-warning"} else {""};
+                    rsx! {
+                        tr {
+                            key: "quota-key-{idx}",
+                            style: "text-align: center;",
+                            td {"{category}"},
+                            td {"{name}"},
+                            td {"{quota_code}"},
+                            td {"{used}"},
+                            td {"{limit}"},
+                            td {class: "{class}", "{utilization}"},
                         }
-                    },
-                    tr {
-                        td {"Key"},
-                        td {
-                            select {
-                                id: "key",
-                                {keys.iter().enumerate().map(|(idx, (k, _))| {
-                                    rsx! {
-                                        option {
-                                            key: "key-{idx}",
-                                            value: "{k}",
-                                            "{k}",
-                                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn replacements_body(
+    instances: Vec<(StackString, StackString)>,
+    replacements: Vec<InstanceReplacement>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        ReplacementsElement,
+        ReplacementsElementProps {
+            instances,
+            replacements,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+/// Failed replacements are shown in red, completed ones in green
+#[component]
+fn ReplacementsElement(
+    instances: Vec<(StackString, StackString)>,
+    replacements: Vec<InstanceReplacement>,
+) -> Element {
+    rsx! {
+        h3 {"Replace Instance"},
+        form {
+            action: "javascript:startReplacement()",
+            select {
+                id: "replacement_instance",
+                {instances.iter().enumerate().map(|(idx, (id, name))| {
+                    rsx! {
+                        option {key: "replacement-instance-key-{idx}", value: "{id}", "{name} {id}"}
+                    }
+                })}
+            },
+            input {
+                "type": "text",
+                name: "replacement_ami",
+                id: "replacement_ami",
+                placeholder: "ami (latest build)",
+            },
+            input {
+                "type": "text",
+                name: "replacement_dns_name",
+                id: "replacement_dns_name",
+                placeholder: "dns name (from launch)",
+            },
+            input {
+                "type": "number",
+                name: "replacement_soak_minutes",
+                id: "replacement_soak_minutes",
+                min: "0",
+                value: "30",
+            },
+            label {
+                input {"type": "checkbox", id: "replacement_terminate_old"},
+                "Terminate old instance",
+            },
+            input {
+                "type": "button",
+                name: "start_replacement",
+                value: "Start",
+                "onclick": "startReplacement();",
+            }
+        }
+        h3 {"Replacements"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Old Instance"},
+                th {"New Instance"},
+                th {"AMI"},
+                th {"DNS Name"},
+                th {"State"},
+                th {"Soak (minutes)"},
+                th {"Terminate Old"},
+                th {"Started"},
+                th {"Updated"},
+                th {"Message"},
+                th {},
+            },
+            tbody {
+                {replacements.iter().enumerate().map(|(idx, replacement)| {
+                    let id = replacement.id;
+                    let old_instance_id = &replacement.old_instance_id;
+                    let new_instance_id =
+                        replacement.new_instance_id.as_ref().map_or("", StackString::as_str);
+                    let ami = &replacement.ami;
+                    let dns_name = replacement.dns_name.as_ref().map_or("", StackString::as_str);
+                    let state = &replacement.state;
+                    let state_class = match replacement.state() {
+                        Ok(ReplacementState::Completed) => "change-created",
+                        Ok(ReplacementState::Failed) | Err(_) => "credential-warning",
+                        Ok(_) => "",
+                    };
+                    let can_cancel = replacement.state().map_or(false, ReplacementState::can_cancel);
+                    let soak_minutes = replacement.soak_minutes;
+                    let terminate_old = replacement.terminate_old;
+                    let created_at = map_date(Some(replacement.created_at.into()));
+                    let updated_at = map_date(Some(replacement.updated_at.into()));
+                    let message = replacement.message.as_ref().map_or("", StackString::as_str);
+                    rsx! {
+                        tr {
+                            key: "replacement-key-{idx}",
+                            style: "text-align: center;",
+                            td {"{old_instance_id}"},
+                            td {"{new_instance_id}"},
+                            td {"{ami}"},
+                            td {"{dns_name}"},
+                            td {class: "{state_class}", "{state}"},
+                            td {"{soak_minutes}"},
+                            td {"{terminate_old}"},
+                            td {"{created_at}"},
+                            td {"{updated_at}"},
+                            td {"{message}"},
+                            td {
+                                if can_cancel {
+                                    input {
+                                        "type": "button",
+                                        name: "cancel_replacement",
+                                        value: "Cancel",
+                                        "onclick": "cancelReplacement('{id}')",
                                     }
-                                })}
-                            }
-                        }
-                    },
-                    tr {
-                        td {"Price"},
-                        td {
-                            input {
-                                "type": "text",
-                                name: "price",
-                                id: "price",
-                                value: "{price}",
-                            }
-                        }
-                    },
-                    tr {
-                        td {"Name"},
-                        td {
-                            input {
-                                "type": "text",
-                                name: "name",
-                                id: "name",
-                            }
-                        }
-                    },
-                    tr {
-                        td {
-                            input {
-                                "type": "button",
-                                name: "create_request",
-                                value: "Request",
-                                "onclick": "requestSpotInstance();",
-                            }
+                                }
+                            },
                         }
-                    },
-                }
+                    }
+                })}
             }
         }
     }
 }
 
-/// # Errors
-/// Returns error if formatting fails
-pub fn textarea_body(entries: Vec<StackString>, id: StackString) -> Result<String, Error> {
-    let mut app = VirtualDom::new_with_props(TextAreaElement, TextAreaElementProps { entries, id });
+pub fn terraform_drift_body(
+    report: Option<DriftReport>,
+    path: Option<StackString>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        TerraformDriftElement,
+        TerraformDriftElementProps { report, path },
+    );
     app.rebuild_in_place();
     let mut renderer = dioxus_ssr::Renderer::default();
     let mut buffer = String::new();
@@ -1622,32 +5793,108 @@ pub fn textarea_body(entries: Vec<StackString>, id: StackString) -> Result<Strin
     Ok(buffer)
 }
 
+/// Missing and modified resources are shown in red
 #[component]
-fn TextAreaElement(entries: Vec<StackString>, id: StackString) -> Element {
-    let rows = entries.len() + 5;
-    let text = entries.join("\n");
+fn TerraformDriftElement(report: Option<DriftReport>, path: Option<StackString>) -> Element {
+    let path_value = path.as_ref().map_or("", StackString::as_str);
     rsx! {
-        textarea {
-            autofocus: "true",
-            readonly: "readonly",
-            name: "message",
-            id: "{id}",
-            rows: "{rows}",
-            cols: "100",
-            "{text}",
+        h3 {"Terraform Drift"},
+        form {
+            action: "javascript:terraformDrift()",
+            input {
+                "type": "text",
+                name: "terraform_state_path",
+                id: "terraform_state_path",
+                size: "60",
+                placeholder: "s3://bucket/terraform.tfstate",
+                value: "{path_value}",
+            },
+            input {
+                "type": "button",
+                name: "terraform_drift",
+                value: "Compare",
+                "onclick": "terraformDrift();",
+            }
         }
+        {match report {
+            None => rsx! {
+                div {"Enter the path of a terraform state, or set terraform_state in the config"}
+            },
+            Some(report) => {
+                let in_sync = report.in_sync;
+                rsx! {
+                    div {"{in_sync} declared resources in sync"},
+                    table {
+                        "border": "1",
+                        class: "dataframe",
+                        thead {
+                            th {"Status"},
+                            th {"Type"},
+                            th {"Id"},
+                            th {"Terraform Address"},
+                            th {"Differences"},
+                        },
+                        tbody {
+                            {report.items.iter().enumerate().map(|(idx, item)| {
+                                let status = item.status.to_str();
+                                let status_class = if item.status == DriftStatus::Unmanaged {
+                                    ""
+                                } else {
+                                    "credential-warning"
+                                };
+                                let resource_type = item.resource_type.to_str();
+                                let id = &item.id;
+                                let address = item.address.as_ref().map_or("", StackString::as_str);
+                                rsx! {
+                                    tr {
+                                        key: "drift-key-{idx}",
+                                        style: "text-align: center;",
+                                        td {class: "{status_class}", "{status}"},
+                                        td {"{resource_type}"},
+                                        td {"{id}"},
+                                        td {"{address}"},
+                                        td {
+                                            {item.differences.iter().enumerate().map(|(didx, diff)| {
+                                                let attribute = &diff.attribute;
+                                                let declared = diff
+                                                    .declared
+                                                    .as_ref()
+                                                    .map_or("(unset)", StackString::as_str);
+                                                let live = diff
+                                                    .live
+                                                    .as_ref()
+                                                    .map_or("(unset)", StackString::as_str);
+                                                rsx! {
+                                                    div {
+                                                        key: "drift-diff-key-{idx}-{didx}",
+                                                        "{attribute}: {declared} -> {live}"
+                                                    }
+                                                }
+                                            })}
+                                        },
+                                    }
+                                }
+                            })}
+                        }
+                    }
+                }
+            }
+        }}
     }
 }
 
 /// # Errors
 /// Returns error if formatting fails
-pub fn instance_status_body(
-    entries: Vec<StackString>,
-    instance: StackString,
+pub fn webhooks_body(
+    webhooks: Vec<Webhook>,
+    deliveries: Vec<WebhookDelivery>,
 ) -> Result<String, Error> {
-    let mut app: VirtualDom = VirtualDom::new_with_props(
-        InstanceStatusElement,
-        InstanceStatusElementProps { entries, instance },
+    let mut app = VirtualDom::new_with_props(
+        WebhooksElement,
+        WebhooksElementProps {
+            webhooks,
+            deliveries,
+        },
     );
     app.rebuild_in_place();
     let mut renderer = dioxus_ssr::Renderer::default();
@@ -1657,43 +5904,133 @@ pub fn instance_status_body(
 }
 
 #[component]
-fn InstanceStatusElement(entries: Vec<StackString>, instance: StackString) -> Element {
-    let rows = entries.len() + 5;
-    let text = entries.join("\n");
+fn WebhooksElement(webhooks: Vec<Webhook>, deliveries: Vec<WebhookDelivery>) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
+    let urls: HashMap<_, _> = webhooks.iter().map(|w| (w.id, w.url.clone())).collect();
     rsx! {
+        h3 {"Webhooks"},
         form {
-            action: "javascript:runCommand('{instance}')",
+            action: "javascript:createWebhook()",
             input {
                 "type": "text",
-                name: "command_text",
-                id: "command_text",
+                name: "webhook_url",
+                id: "webhook_url",
+                placeholder: "https://example.com/hook",
             },
+            {WebhookEvent::ALL.iter().map(|event| {
+                let event = event.to_str();
+                rsx! {
+                    label {
+                        key: "webhook-event-{event}",
+                        input {
+                            "type": "checkbox",
+                            class: "webhook-event",
+                            value: "{event}",
+                            checked: "true",
+                        },
+                        "{event}"
+                    }
+                }
+            })},
             input {
                 "type": "button",
-                name: "run_command",
-                value: "Run",
-                "onclick": "runCommand('{instance}');",
+                name: "create_webhook",
+                value: "Add Webhook",
+                "onclick": "createWebhook();",
             }
         }
-        textarea {
-            autofocus: "true",
-            readonly: "readonly",
-            name: "message",
-            id: "diary_editor_form",
-            rows: "{rows}",
-            cols: "100",
-            "{text}",
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Url"},
+                th {"Events"},
+                th {"Enabled"},
+                th {"Created"},
+                th {},
+            },
+            tbody {
+                {webhooks.iter().enumerate().map(|(idx, webhook)| {
+                    let id = webhook.id;
+                    let url = &webhook.url;
+                    let events = webhook.events.join(", ");
+                    let enabled = webhook.enabled;
+                    let created = webhook.created_at.to_timezone(local_tz);
+                    rsx! {
+                        tr {
+                            key: "webhook-key-{idx}",
+                            td {"{url}"},
+                            td {"{events}"},
+                            td {"{enabled}"},
+                            td {"{created}"},
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "delete_webhook",
+                                    value: "Delete",
+                                    "onclick": "deleteWebhook('{id}')",
+                                }
+                            },
+                        }
+                    }
+                })}
+            }
+        }
+        h3 {"Deliveries"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Url"},
+                th {"Event"},
+                th {"Status"},
+                th {"Attempts"},
+                th {"Response"},
+                th {"Error"},
+                th {"Created"},
+                th {"Next Attempt"},
+            },
+            tbody {
+                {deliveries.iter().enumerate().map(|(idx, delivery)| {
+                    let url = urls.get(&delivery.webhook_id).map_or("", StackString::as_str);
+                    let event = &delivery.event;
+                    let status = &delivery.status;
+                    let attempts = delivery.attempts;
+                    let response = delivery
+                        .response_code
+                        .map_or_else(StackString::new, |c| format_sstr!("{c}"));
+                    let last_error = delivery.last_error.as_deref().unwrap_or("");
+                    let created = delivery.created_at.to_timezone(local_tz);
+                    let next_attempt = if status.as_str() == DELIVERY_PENDING {
+                        format_sstr!("{}", delivery.next_attempt_at.to_timezone(local_tz))
+                    } else {
+                        StackString::new()
+                    };
+                    rsx! {
+                        tr {
+                            key: "webhook-delivery-key-{idx}",
+                            style: "text-align: left;",
+                            td {"{url}"},
+                            td {"{event}"},
+                            td {"{status}"},
+                            td {"{attempts}"},
+                            td {"{response}"},
+                            td {"{last_error}"},
+                            td {"{created}"},
+                            td {"{next_attempt}"},
+                        }
+                    }
+                })}
+            }
         }
     }
 }
 
 /// # Errors
 /// Returns error if formatting fails
-pub fn textarea_fixed_size_body(body: StackString, id: StackString) -> Result<String, Error> {
-    let mut app: VirtualDom = VirtualDom::new_with_props(
-        TextareaFixedSizeElement,
-        TextareaFixedSizeElementProps { body, id },
-    );
+pub fn costs_by_tag_body(tag: StackString, costs: Vec<ResourceCost>) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(CostsByTagElement, CostsByTagElementProps { tag, costs });
     app.rebuild_in_place();
     let mut renderer = dioxus_ssr::Renderer::default();
     let mut buffer = String::new();
@@ -1702,27 +6039,260 @@ pub fn textarea_fixed_size_body(body: StackString, id: StackString) -> Result<St
 }
 
 #[component]
-fn TextareaFixedSizeElement(body: StackString, id: StackString) -> Element {
+fn CostsByTagElement(tag: StackString, costs: Vec<ResourceCost>) -> Element {
+    let summary = summarize_costs(&costs);
+    let total: f64 = summary.iter().map(TagCost::total).sum();
+    let total = format_sstr!("{total:0.2}");
+    let waste: Vec<_> = costs.iter().filter(|c| c.waste).collect();
     rsx! {
-        textarea {
-            autofocus: "true",
-            readonly: "readonly",
-            name: "message",
-            id: "{id}",
-            rows: "50",
-            cols: "100",
-            "{body}",
-        }
+        div {
+            "Tag ",
+            input {"type": "text", id: "cost_tag", value: "{tag}"},
+            input {
+                "type": "button",
+                name: "cost_tag_submit",
+                value: "Attribute",
+                "onclick": "costsByTag(document.getElementById('cost_tag').value);",
+            },
+            " Estimated monthly total ${total}",
+        },
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"{tag}"},
+                    th {"Instances"},
+                    th {"Volumes"},
+                    th {"Snapshots"},
+                    th {"Total"},
+                    th {"Unattached"},
+                }
+            },
+            tbody {
+                {summary.iter().enumerate().map(|(idx, c)| {
+                    let group = &c.group;
+                    let instances = format_sstr!("{:0.2}", c.instances);
+                    let volumes = format_sstr!("{:0.2}", c.volumes);
+                    let snapshots = format_sstr!("{:0.2}", c.snapshots);
+                    let total = format_sstr!("{:0.2}", c.total());
+                    let waste = format_sstr!("{:0.2}", c.waste);
+                    rsx! {
+                        tr {
+                            key: "cost-group-{idx}",
+                            style: "text-align: left;",
+                            td {"{group}"},
+                            td {"${instances}"},
+                            td {"${volumes}"},
+                            td {"${snapshots}"},
+                            td {"${total}"},
+                            td {"${waste}"},
+                        }
+                    }
+                })}
+            }
+        },
+        br {},
+        "Unattached volumes",
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Volume"},
+                    th {"{tag}"},
+                    th {"Monthly Cost"},
+                }
+            },
+            tbody {
+                {waste.iter().enumerate().map(|(idx, c)| {
+                    let id = &c.id;
+                    let group = &c.group;
+                    let cost = format_sstr!("{:0.2}", c.monthly_cost);
+                    rsx! {
+                        tr {
+                            key: "cost-waste-{idx}",
+                            style: "text-align: left;",
+                            td {"{id}"},
+                            td {"{group}"},
+                            td {"${cost}"},
+                        }
+                    }
+                })}
+            }
+        },
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn waste_body(waste: Vec<WasteItem>) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(WasteElement, WasteElementProps { waste });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+#[component]
+fn WasteElement(waste: Vec<WasteItem>) -> Element {
+    let total: f64 = waste.iter().map(|w| w.monthly_cost).sum();
+    let total = format_sstr!("{total:0.2}");
+    rsx! {
+        div {"Estimated monthly waste ${total}"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {},
+                    th {"Kind"},
+                    th {"Resource"},
+                    th {"Name"},
+                    th {"Detail"},
+                    th {"Monthly Cost"},
+                }
+            },
+            tbody {
+                {waste.iter().enumerate().map(|(idx, w)| {
+                    let kind = w.kind.to_str();
+                    let id = &w.id;
+                    let name = &w.name;
+                    let detail = &w.detail;
+                    let cost = format_sstr!("{:0.2}", w.monthly_cost);
+                    let (method, url) = w.kind.cleanup(id);
+                    rsx! {
+                        tr {
+                            key: "waste-{idx}",
+                            style: "text-align: left;",
+                            td {
+                                input {
+                                    "type": "button",
+                                    name: "cleanup",
+                                    value: "Clean up",
+                                    "onclick": "wasteCleanup('{method}', '{url}');",
+                                }
+                            },
+                            td {"{kind}"},
+                            td {"{id}"},
+                            td {"{name}"},
+                            td {"{detail}"},
+                            td {"${cost}"},
+                        }
+                    }
+                })}
+            }
+        },
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn iam_report_body(
+    entries: Vec<CredentialReportEntry>,
+    inactive_days: i64,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        IamReportElement,
+        IamReportElementProps {
+            entries,
+            inactive_days,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+fn days_ago(time: Option<OffsetDateTime>, now: OffsetDateTime) -> StackString {
+    time.map_or_else(
+        || "never".into(),
+        |t| format_sstr!("{} days ago", (now - t).whole_days()),
+    )
+}
 
+#[component]
+fn IamReportElement(entries: Vec<CredentialReportEntry>, inactive_days: i64) -> Element {
+    let now = OffsetDateTime::now_utc();
+    let inactive = entries
+        .iter()
+        .filter(|e| e.is_inactive(now, inactive_days))
+        .count();
+    let without_mfa = entries.iter().filter(|e| !e.mfa_active).count();
+    rsx! {
+        div {
+            "{inactive} users without activity in {inactive_days} days, {without_mfa} without mfa ",
+            {export_link("iam-report", None)},
+        },
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"User"},
+                    th {"Password Last Used"},
+                    th {"MFA"},
+                    th {"Key 1 Last Used"},
+                    th {"Key 2 Last Used"},
+                    th {"Last Activity"},
+                    th {"Flags"},
+                }
+            },
+            tbody {
+                {entries.iter().enumerate().map(|(idx, entry)| {
+                    let user = &entry.user;
+                    let password = if entry.password_enabled {
+                        days_ago(entry.password_last_used, now)
+                    } else {
+                        "disabled".into()
+                    };
+                    let mfa = if entry.mfa_active {"yes"} else {"no"};
+                    let keys: Vec<StackString> = entry.access_keys.iter().map(|k| {
+                        if k.active {
+                            days_ago(k.last_used, now)
+                        } else {
+                            "".into()
+                        }
+                    }).collect();
+                    let key1 = keys.first().cloned().unwrap_or_default();
+                    let key2 = keys.get(1).cloned().unwrap_or_default();
+                    let last_activity = days_ago(entry.last_activity(), now);
+                    let mut flags = Vec::new();
+                    if entry.is_inactive(now, inactive_days) {
+                        flags.push("inactive");
+                    }
+                    if !entry.mfa_active && entry.password_enabled {
+                        flags.push("password without mfa");
+                    }
+                    let flags = flags.join(", ");
+                    rsx! {
+                        tr {
+                            key: "iam-report-{idx}",
+                            style: "text-align: left;",
+                            td {"{user}"},
+                            td {"{password}"},
+                            td {"{mfa}"},
+                            td {"{key1}"},
+                            td {"{key2}"},
+                            td {"{last_activity}"},
+                            td {"{flags}"},
+                        }
+                    }
+                })}
+            }
+        },
     }
 }
 
 /// # Errors
 /// Returns error if formatting fails
-pub fn instance_types_body(instances: Vec<InstanceList>) -> Result<String, Error> {
+pub fn bucket_summary_body(summaries: Vec<BucketSummary>) -> Result<String, Error> {
     let mut app = VirtualDom::new_with_props(
-        InstanceTypesElement,
-        InstanceTypesElementProps { instances },
+        BucketSummaryElement,
+        BucketSummaryElementProps { summaries },
     );
     app.rebuild_in_place();
     let mut renderer = dioxus_ssr::Renderer::default();
@@ -1732,25 +6302,72 @@ pub fn instance_types_body(instances: Vec<InstanceList>) -> Result<String, Error
 }
 
 #[component]
-fn InstanceTypesElement(instances: Vec<InstanceList>) -> Element {
+fn BucketSummaryElement(summaries: Vec<BucketSummary>) -> Element {
+    let ymd = format_description!("[year]-[month]-[day]");
+    let missing = summaries.iter().filter(|s| s.missing_lifecycle()).count();
+    let total_bytes: u64 = summaries
+        .iter()
+        .filter_map(|s| s.usage.map(|u| u.size_bytes))
+        .sum();
+    let total_gb = total_bytes as f64 / 1e9;
     rsx! {
-        {instances.iter().enumerate().map(|(idx, i)| {
-            let i = &i.instance_type;
-            rsx! {
-                option {
-                    key: "instance-type-key-{idx}",
-                    value: "{i}",
-                    "{i}",
+        div {"{missing} buckets without an expiration rule, {total_gb:.2} GB in total"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Bucket"},
+                    th {"Created"},
+                    th {"Objects"},
+                    th {"Size (GB)"},
+                    th {"Lifecycle"},
+                    th {"Encryption"},
                 }
+            },
+            tbody {
+                {summaries.iter().enumerate().map(|(idx, summary)| {
+                    let name = &summary.name;
+                    let created = summary.created.map_or_else(StackString::new, |c| {
+                        c.format(ymd).unwrap_or_default().into()
+                    });
+                    let objects = summary
+                        .usage
+                        .map_or_else(|| "unknown".into(), |u| format_sstr!("{}", u.object_count));
+                    let size = summary.usage.map_or_else(
+                        || "unknown".into(),
+                        |u| format_sstr!("{:.2}", u.size_bytes as f64 / 1e9),
+                    );
+                    let lifecycle = summary.lifecycle_description();
+                    let lifecycle_style = if summary.missing_lifecycle() {"color: red;"} else {""};
+                    let encryption = summary.encryption.as_ref().map_or("none", StackString::as_str);
+                    let label = if summary.inbound_email {" (inbound email)"} else {""};
+                    rsx! {
+                        tr {
+                            key: "bucket-{idx}",
+                            style: "text-align: left;",
+                            td {"{name}{label}"},
+                            td {"{created}"},
+                            td {"{objects}"},
+                            td {"{size}"},
+                            td {style: "{lifecycle_style}", "{lifecycle}"},
+                            td {"{encryption}"},
+                        }
+                    }
+                })}
             }
-        })}
+        },
     }
 }
 
 /// # Errors
-/// Returns error if formatting fails
-pub fn novnc_start_body() -> Result<String, Error> {
-    let mut app = VirtualDom::new(NovncStartElement);
+/// Returns error if rendering fails
+pub fn secrets_body(
+    secrets: Vec<SecretSummary>,
+    prefixes: Vec<StackString>,
+) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(SecretsElement, SecretsElementProps { secrets, prefixes });
     app.rebuild_in_place();
     let mut renderer = dioxus_ssr::Renderer::default();
     let mut buffer = String::new();
@@ -1758,19 +6375,70 @@ pub fn novnc_start_body() -> Result<String, Error> {
     Ok(buffer)
 }
 
+#[component]
+fn SecretsElement(secrets: Vec<SecretSummary>, prefixes: Vec<StackString>) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
+    let prefixes = if prefixes.is_empty() {
+        "none configured, set SECRET_PREFIXES".into()
+    } else {
+        prefixes.join(", ")
+    };
+    rsx! {
+        h3 {"Secrets"},
+        p {"Prefixes: {prefixes}"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                th {"Source"},
+                th {"Name"},
+                th {"Description"},
+                th {"Last Changed"},
+                th {"Last Rotated"},
+                th {"Rotation"},
+            },
+            tbody {
+                {secrets.iter().enumerate().map(|(idx, secret)| {
+                    let source = secret.source;
+                    let name = &secret.name;
+                    let description = secret.description.as_ref().map_or("", StackString::as_str);
+                    let last_changed = secret.last_changed.map_or_else(StackString::new, |t| {
+                        format_sstr!("{}", t.to_timezone(local_tz))
+                    });
+                    let last_rotated = secret.last_rotated.map_or_else(StackString::new, |t| {
+                        format_sstr!("{}", t.to_timezone(local_tz))
+                    });
+                    let rotation = if secret.rotation_enabled {"enabled"} else {"disabled"};
+                    rsx! {
+                        tr {
+                            key: "secret-key-{idx}",
+                            td {"{source}"},
+                            td {"{name}"},
+                            td {"{description}"},
+                            td {"{last_changed}"},
+                            td {"{last_rotated}"},
+                            td {"{rotation}"},
+                        }
+                    }
+                })}
+            }
+        }
+    }
+}
+
 /// # Errors
 /// Returns error if formatting fails
-pub fn novnc_status_body(
-    number: usize,
-    domain: StackString,
-    pids: Vec<usize>,
+pub fn host_body(
+    metrics: HostMetrics,
+    disk_warning_percent: f64,
+    disk_critical_percent: f64,
 ) -> Result<String, Error> {
     let mut app = VirtualDom::new_with_props(
-        NovncStatusElement,
-        NovncStatusElementProps {
-            number,
-            domain,
-            pids,
+        HostElement,
+        HostElementProps {
+            metrics,
+            disk_warning_percent,
+            disk_critical_percent,
         },
     );
     app.rebuild_in_place();
@@ -1780,93 +6448,144 @@ pub fn novnc_status_body(
     Ok(buffer)
 }
 
-#[component]
-fn NovncStatusElement(number: usize, domain: StackString, pids: Vec<usize>) -> Element {
-    rsx! {
-        br {
-            "{number} processes currently running {pids:?}"
-        },
-        a {
-            href: "https://{domain}:8787/vnc.html",
-            target: "_blank",
-            "Connect to NoVNC",
-        }
-        input {
-            "type": "button",
-            name: "novnc",
-            value: "Stop NoVNC",
-            "onclick": "noVncTab('/aws/novnc/stop', 'POST')",
-        }
-    }
-}
-
-#[component]
-fn NovncStartElement() -> Element {
-    rsx! {
-        input {
-            "type": "button",
-            name: "novnc",
-            value: "Start NoVNC",
-            "onclick": "noVncTab('/aws/novnc/start', 'POST')",
-        }
+fn format_bytes(bytes: u64) -> StackString {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
     }
+    format_sstr!("{value:0.1} {}", UNITS[unit])
 }
 
 #[component]
-fn InboundEmailElement(emails: Vec<InboundEmailDB>) -> Element {
+fn HostElement(
+    metrics: HostMetrics,
+    disk_warning_percent: f64,
+    disk_critical_percent: f64,
+) -> Element {
+    let host_name = metrics
+        .host_name
+        .as_ref()
+        .map_or("unknown", StackString::as_str);
+    let uptime = format_sstr!(
+        "{}d {}h {}m",
+        metrics.uptime / 86400,
+        (metrics.uptime % 86400) / 3600,
+        (metrics.uptime % 3600) / 60
+    );
+    let cpu_count = metrics.cpu_count;
+    let cpu_usage = metrics.cpu_usage;
+    let load = format_sstr!(
+        "{:0.2} {:0.2} {:0.2}",
+        metrics.load_average.one,
+        metrics.load_average.five,
+        metrics.load_average.fifteen
+    );
+    let memory = metrics.memory;
+    let memory_used = format_bytes(memory.used);
+    let memory_total = format_bytes(memory.total);
+    let memory_available = format_bytes(memory.available);
+    let memory_percent = memory.used_percent();
+    let swap_used = format_bytes(memory.swap_used);
+    let swap_total = format_bytes(memory.swap_total);
+    let swap_percent = memory.swap_used_percent();
     rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            tbody {
+                tr {th {"Host"}, td {"{host_name}"}},
+                tr {th {"Uptime"}, td {"{uptime}"}},
+                tr {th {"CPUs"}, td {"{cpu_count} ({cpu_usage:0.1}% used)"}},
+                tr {th {"Load Average"}, td {"{load}"}},
+                tr {
+                    th {"Memory"},
+                    td {"{memory_used} / {memory_total} ({memory_percent:0.1}%), {memory_available} available"},
+                },
+                tr {th {"Swap"}, td {"{swap_used} / {swap_total} ({swap_percent:0.1}%)"}},
+            }
+        },
+        br {},
         table {
             "border": "1",
             class: "dataframe",
             thead {
-                th {"Date"},
-                th {"From"},
-                th {"To"},
-                th {"Subject"},
-                th {
-                    input {
-                        "type": "button",
-                        name: "sync",
-                        value: "Sync",
-                        "onclick": "syncEmail()",
+                tr {
+                    th {"Mount"},
+                    th {"Device"},
+                    th {"File System"},
+                    th {"Size"},
+                    th {"Used"},
+                    th {"Available"},
+                    th {"Use%"},
+                    th {"Status"},
+                }
+            },
+            tbody {
+                {metrics.disks.iter().enumerate().map(|(idx, disk)| {
+                    let mount_point = &disk.mount_point;
+                    let device = &disk.device;
+                    let file_system = &disk.file_system;
+                    let size = format_bytes(disk.total_space);
+                    let used = format_bytes(disk.used_space());
+                    let available = format_bytes(disk.available_space);
+                    let used_percent = disk.used_percent();
+                    let alert = disk.alert(disk_warning_percent, disk_critical_percent);
+                    let class = if alert == DiskAlert::Ok {""} else {"credential-warning"};
+                    rsx! {
+                        tr {
+                            key: "host-disk-key-{idx}",
+                            style: "text-align: center;",
+                            td {"{mount_point}"},
+                            td {"{device}"},
+                            td {"{file_system}"},
+                            td {"{size}"},
+                            td {"{used}"},
+                            td {"{available}"},
+                            td {"{used_percent:0.1}%"},
+                            td {class: "{class}", "{alert}"},
+                        }
                     }
+                })}
+            }
+        },
+        br {},
+        table {
+            "border": "1",
+            class: "dataframe",
+            thead {
+                tr {
+                    th {"Interface"},
+                    th {"Received"},
+                    th {"Transmitted"},
+                    th {"Packets In"},
+                    th {"Packets Out"},
+                    th {"Errors In"},
+                    th {"Errors Out"},
                 }
             },
             tbody {
-                {emails.iter().enumerate().map(|(idx, email)| {
-                    let id = &email.id;
-                    let from = &email.from_address;
-                    let to = &email.to_address;
-                    let subject = &email.subject;
-                    let date = &email.date;
+                {metrics.networks.iter().enumerate().map(|(idx, network)| {
+                    let interface = &network.interface;
+                    let received = format_bytes(network.received_bytes);
+                    let transmitted = format_bytes(network.transmitted_bytes);
+                    let received_packets = network.received_packets;
+                    let transmitted_packets = network.transmitted_packets;
+                    let receive_errors = network.receive_errors;
+                    let transmit_errors = network.transmit_errors;
                     rsx! {
                         tr {
-                            key: "email-key-{idx}",
-                            td {
-                                input {
-                                    "type": "button",
-                                    name: "date",
-                                    value: "{date}",
-                                    "onclick": "emailDetail('{id}')",
-                                }
-                            }
-                            td {
-                                "{from}"
-                            }
-                            td {
-                                "{to}"
-                            }
-                            td {
-                                "{subject}"
-                            }
-                            td {
-                                input {
-                                    "type": "button",
-                                    name: "delete",
-                                    value: "Delete",
-                                    "onclick": "deleteEmail('{id}')",
-                                }
-                            }
+                            key: "host-network-key-{idx}",
+                            style: "text-align: center;",
+                            td {"{interface}"},
+                            td {"{received}"},
+                            td {"{transmitted}"},
+                            td {"{received_packets}"},
+                            td {"{transmitted_packets}"},
+                            td {"{receive_errors}"},
+                            td {"{transmit_errors}"},
                         }
                     }
                 })}
@@ -1877,15 +6596,9 @@ fn InboundEmailElement(emails: Vec<InboundEmailDB>) -> Element {
 
 /// # Errors
 /// Returns error if formatting fails
-pub fn inbound_email_body(
-    text: StackString,
-    html: StackString,
-    raw: StackString,
-) -> Result<String, Error> {
-    let mut app = VirtualDom::new_with_props(
-        InboundEmailDetailElement,
-        InboundEmailDetailElementProps { text, html, raw },
-    );
+pub fn launch_status_body(launches: Vec<LaunchProgress>) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(LaunchStatusElement, LaunchStatusElementProps { launches });
     app.rebuild_in_place();
     let mut renderer = dioxus_ssr::Renderer::default();
     let mut buffer = String::new();
@@ -1893,33 +6606,195 @@ pub fn inbound_email_body(
     Ok(buffer)
 }
 
+/// Each phase shows the local time it was reached and the time since the
+/// request was submitted, `data-pending` tells the page to keep refreshing
 #[component]
-fn InboundEmailDetailElement(text: StackString, html: StackString, raw: StackString) -> Element {
-    let rows = text.split('\n').count() + 5;
-    let raw_rows = raw.split('\n').count() + 5;
+fn LaunchStatusElement(launches: Vec<LaunchProgress>) -> Element {
+    let local_tz = DateTimeWrapper::local_tz();
+    let hms = format_description!("[hour]:[minute]:[second]");
+    let pending = launches.iter().any(|l| !l.is_finished());
+    let phase_names: Vec<_> = launches
+        .first()
+        .map(|l| l.phases.iter().map(|p| p.phase.to_str()).collect())
+        .unwrap_or_default();
     rsx! {
-        br {
-            textarea {
-                name: "text-content",
-                id: "text-content-form",
-                rows: "{rows}",
-                cols: "100",
-                "{text}",
-            }
+        div {
+            id: "launch_status",
+            "data-pending": "{pending}",
+            {if launches.is_empty() {
+                rsx! {div {"No launches in the last day"}}
+            } else {
+                rsx! {table {
+                    "border": "1",
+                    class: "dataframe",
+                    thead {
+                        tr {
+                            th {"Instance Type"},
+                            th {"Instance"},
+                            {phase_names.iter().enumerate().map(|(idx, name)| rsx! {
+                                th {key: "launch-phase-key-{idx}", "{name}"}
+                            })},
+                        }
+                    },
+                    tbody {
+                        {launches.iter().enumerate().map(|(idx, launch)| {
+                            let instance_type = &launch.instance_type;
+                            let market = if launch.is_spot {"spot"} else {"ondemand"};
+                            let instance_id = launch.instance_id.as_ref().map_or("", StackString::as_str);
+                            let terminated = if launch.terminated_at.is_some() {" (terminated)"} else {""};
+                            rsx! {
+                                tr {
+                                    key: "launch-status-key-{idx}",
+                                    style: "text-align: center;",
+                                    td {"{instance_type} {market}"},
+                                    td {"{instance_id}{terminated}"},
+                                    {launch.phases.iter().enumerate().map(|(pidx, phase)| {
+                                        let key = format_sstr!("launch-phase-{idx}-{pidx}");
+                                        match &phase.state {
+                                            PhaseState::Done(at) => {
+                                                let time = at
+                                                    .to_timezone(local_tz)
+                                                    .format(hms)
+                                                    .unwrap_or_else(|_| String::new());
+                                                let elapsed = (*at - launch.launched_at).whole_seconds();
+                                                let elapsed = format_sstr!("+{}m {}s", elapsed / 60, elapsed % 60);
+                                                rsx! {td {key: "{key}", "{time} ({elapsed})"}}
+                                            }
+                                            PhaseState::Active => rsx! {td {key: "{key}", b {"in progress"}}},
+                                            PhaseState::Waiting => rsx! {td {key: "{key}"}},
+                                            PhaseState::Failed(message) => rsx! {
+                                                td {key: "{key}", class: "credential-warning", "{message}"}
+                                            },
+                                        }
+                                    })},
+                                }
+                            }
+                        })}
+                    }
+                }}
+            }}
         }
-        br {
-            div {
-                dangerous_inner_html: "{html}",
-            }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn tag_search_body(
+    results: Option<(Vec<TaggedResource>, HashSet<StackString>)>,
+    tag: Option<StackString>,
+) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(TagSearchElement, TagSearchElementProps { results, tag });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer.render_to(&mut buffer, &app)?;
+    Ok(buffer)
+}
+
+fn tagged_resource_action(
+    resource: &TaggedResource,
+    protected: &HashSet<StackString>,
+) -> Option<Element> {
+    let id = resource.id.as_str();
+    if is_protected(protected, id, resource.name.as_ref()) {
+        return None;
+    }
+    match (resource.resource_type, resource.state.as_str()) {
+        (ResourceType::Instances, "running") => Some(rsx! {
+            {action_button("instance_status", "Status", &[("instance", id)])},
+            {action_button("terminate", "Terminate", &[("instance", id)])},
+        }),
+        (ResourceType::Volume, "available") => Some(action_button(
+            "delete_volume",
+            "DeleteVolume",
+            &[("volid", id)],
+        )),
+        (ResourceType::Snapshot, "completed") => Some(action_button(
+            "delete_snapshot",
+            "DeleteSnapshot",
+            &[("snapid", id)],
+        )),
+        (ResourceType::Ami, "available") => {
+            Some(action_button("delete_image", "DeleteImage", &[("ami", id)]))
         }
-        br {
-            textarea {
-                name: "raw-content",
-                id: "raw-content-form",
-                rows: "{raw_rows}",
-                cols: "100",
-                "{raw}",
+        _ => None,
+    }
+}
+
+#[component]
+fn TagSearchElement(
+    results: Option<(Vec<TaggedResource>, HashSet<StackString>)>,
+    tag: Option<StackString>,
+) -> Element {
+    let tag_value = tag.as_ref().map_or("", StackString::as_str);
+    rsx! {
+        h3 {"Tag Search"},
+        form {
+            action: "javascript:tagSearch()",
+            input {
+                "type": "text",
+                name: "tag_query",
+                id: "tag_query",
+                size: "40",
+                placeholder: "project:blog",
+                value: "{tag_value}",
+            },
+            input {
+                "type": "button",
+                name: "tag_search",
+                value: "Search",
+                "onclick": "tagSearch();",
             }
         }
+        {match results {
+            None => rsx! {
+                div {"Search instances, volumes, snapshots and amis by key:value, key=value or key"}
+            },
+            Some((resources, _)) if resources.is_empty() => rsx! {
+                div {"No resources tagged {tag_value}"}
+            },
+            Some((resources, protected)) => rsx! {
+                table {
+                    "border": "1",
+                    class: "dataframe",
+                    thead {
+                        th {"Type"},
+                        th {"Id"},
+                        th {"Name"},
+                        th {"State"},
+                        th {"Tags"},
+                        th {},
+                    },
+                    tbody {
+                        {resources.iter().enumerate().map(|(idx, resource)| {
+                            let resource_type = resource.resource_type.to_str();
+                            let id = &resource.id;
+                            let name = resource.name.as_ref().map_or("", StackString::as_str);
+                            let state = &resource.state;
+                            let tags = resource
+                                .tags
+                                .iter()
+                                .map(|(k, v)| format_sstr!("{k}={v}"))
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            let action = tagged_resource_action(resource, &protected);
+                            rsx! {
+                                tr {
+                                    key: "tag-search-key-{idx}",
+                                    style: "text-align: center;",
+                                    td {"{resource_type}"},
+                                    td {"{id}"},
+                                    td {"{name}"},
+                                    td {"{state}"},
+                                    td {"{tags}"},
+                                    td {{action}},
+                                }
+                            }
+                        })}
+                    }
+                }
+            },
+        }}
     }
 }
@@ -1,17 +1,18 @@
-use crate::logged_user::LOGIN_HTML;
+use crate::{logged_user::LOGIN_HTML, request_id::current_request_id};
 use anyhow::Error as AnyhowError;
 use log::error;
 use postgres_query::Error as PqError;
 use rweb::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, StatusCode},
     openapi::{
-        ComponentDescriptor, ComponentOrInlineSchema, Entity, Response, ResponseEntity, Responses,
+        ComponentDescriptor, ComponentOrInlineSchema, Entity, MediaType, Response, ResponseEntity,
+        Responses,
     },
     reject::{InvalidHeader, MissingCookie, Reject},
-    Rejection, Reply,
+    Rejection, Reply, Schema,
 };
 use serde::Serialize;
-use stack_string::StackString;
+use stack_string::{format_sstr, StackString};
 use std::{
     borrow::Cow,
     convert::Infallible,
@@ -21,6 +22,8 @@ use std::{
 use thiserror::Error;
 use time_tz::system::Error as TzSystemError;
 
+use aws_app_lib::aws_error::AwsErrorInfo;
+
 #[derive(Error, Debug)]
 pub enum ServiceError {
     #[error("Internal Server Error")]
@@ -29,6 +32,16 @@ pub enum ServiceError {
     BadRequest(StackString),
     #[error("Unauthorized")]
     Unauthorized,
+    #[error("Forbidden: {}", _0)]
+    Forbidden(StackString),
+    #[error("Conflict: {}", _0)]
+    Conflict(StackString),
+    /// Message and seconds until the next request is allowed
+    #[error("Too Many Requests: {}", _0)]
+    TooManyRequests(StackString, u64),
+    /// Message and seconds to wait before retrying
+    #[error("Resource Busy: {}", _0)]
+    ResourceBusy(StackString, u64),
     #[error("Anyhow error {0}")]
     AnyhowError(#[from] AnyhowError),
     #[error("io Error {0}")]
@@ -45,10 +58,97 @@ pub enum ServiceError {
 
 impl Reject for ServiceError {}
 
-#[derive(Serialize)]
-struct ErrorMessage<'a> {
+/// Body of every json error response
+#[derive(Serialize, Schema)]
+pub struct ErrorMessage {
+    #[schema(description = "Http Status Code")]
     code: u16,
-    message: &'a str,
+    #[schema(description = "Error Message")]
+    message: StackString,
+    /// Matches the `x-request-id` header and the `request` span in the logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(description = "Request ID (matches x-request-id header)")]
+    request_id: Option<StackString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(description = "Failed AWS Call")]
+    aws_error: Option<AwsError>,
+}
+
+#[derive(Serialize, Schema)]
+pub struct AwsError {
+    #[schema(description = "AWS Error Code")]
+    code: Option<StackString>,
+    #[schema(description = "AWS Error Message")]
+    message: Option<StackString>,
+    #[schema(description = "AWS Request ID")]
+    request_id: Option<StackString>,
+}
+
+impl From<AwsErrorInfo> for AwsError {
+    fn from(info: AwsErrorInfo) -> Self {
+        Self {
+            code: info.code,
+            message: info.message,
+            request_id: info.request_id,
+        }
+    }
+}
+
+/// Http status to report an aws error code with, errors that aren't the
+/// caller's fault are a bad gateway
+#[must_use]
+pub fn aws_error_status(code: Option<&str>) -> StatusCode {
+    let code = match code {
+        Some(code) => code,
+        None => return StatusCode::BAD_GATEWAY,
+    };
+    if code.ends_with("NotFound")
+        || code.ends_with("NotFoundException")
+        || code.starts_with("NoSuch")
+    {
+        StatusCode::NOT_FOUND
+    } else if [
+        "UnauthorizedOperation",
+        "AuthFailure",
+        "AccessDenied",
+        "AccessDeniedException",
+        "OptInRequired",
+        "Blocked",
+    ]
+    .contains(&code)
+    {
+        StatusCode::FORBIDDEN
+    } else if [
+        "Throttling",
+        "ThrottlingException",
+        "RequestLimitExceeded",
+        "TooManyRequestsException",
+        "SlowDown",
+    ]
+    .contains(&code)
+    {
+        StatusCode::TOO_MANY_REQUESTS
+    } else if code.ends_with("AlreadyExists")
+        || code.ends_with("AlreadyExistsException")
+        || code.starts_with("IncorrectState")
+        || code.starts_with("IncorrectInstanceState")
+        || [
+            "DependencyViolation",
+            "ResourceInUseException",
+            "ConflictException",
+        ]
+        .contains(&code)
+    {
+        StatusCode::CONFLICT
+    } else if code.starts_with("Invalid")
+        || code.starts_with("Malformed")
+        || code.starts_with("Missing")
+        || ["ValidationError", "ValidationException"].contains(&code)
+    {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::BAD_GATEWAY
+    }
 }
 
 fn login_html() -> impl Reply {
@@ -59,11 +159,13 @@ fn login_html() -> impl Reply {
 /// Never returns an error
 pub async fn error_response(err: Rejection) -> Result<Box<dyn Reply>, Infallible> {
     let code: StatusCode;
-    let message: &str;
+    let message: StackString;
+    let mut aws_error = None;
+    let mut retry_after = None;
 
     if err.is_not_found() {
         code = StatusCode::NOT_FOUND;
-        message = "NOT FOUND";
+        message = "NOT FOUND".into();
     } else if err.find::<InvalidHeader>().is_some() {
         return Ok(Box::new(login_html()));
     } else if let Some(missing_cookie) = err.find::<MissingCookie>() {
@@ -71,36 +173,71 @@ pub async fn error_response(err: Rejection) -> Result<Box<dyn Reply>, Infallible
             return Ok(Box::new(login_html()));
         }
         code = StatusCode::INTERNAL_SERVER_ERROR;
-        message = "Internal Server Error";
+        message = "Internal Server Error".into();
     } else if let Some(service_err) = err.find::<ServiceError>() {
         match service_err {
             ServiceError::BadRequest(msg) => {
                 code = StatusCode::BAD_REQUEST;
-                message = msg.as_str();
+                message = msg.clone();
             }
             ServiceError::Unauthorized => {
                 return Ok(Box::new(login_html()));
             }
+            ServiceError::Forbidden(msg) => {
+                code = StatusCode::FORBIDDEN;
+                message = msg.clone();
+            }
+            ServiceError::Conflict(msg) => {
+                code = StatusCode::CONFLICT;
+                message = msg.clone();
+            }
+            ServiceError::TooManyRequests(msg, secs) => {
+                code = StatusCode::TOO_MANY_REQUESTS;
+                message = msg.clone();
+                retry_after = Some(*secs);
+            }
+            ServiceError::ResourceBusy(msg, secs) => {
+                code = StatusCode::CONFLICT;
+                message = msg.clone();
+                retry_after = Some(*secs);
+            }
+            ServiceError::AnyhowError(e) if AwsErrorInfo::from_error(e).is_some() => {
+                let info = AwsErrorInfo::from_error(e).unwrap_or_default();
+                error!("AWS error: {:?}", e);
+                code = aws_error_status(info.code.as_deref());
+                message = match (&info.code, &info.message) {
+                    (Some(c), Some(m)) => format_sstr!("{c}: {m}"),
+                    (Some(c), None) => c.clone(),
+                    _ => "AWS request failed".into(),
+                };
+                aws_error = Some(info.into());
+            }
             _ => {
                 error!("Other error: {:?}", service_err);
                 code = StatusCode::INTERNAL_SERVER_ERROR;
-                message = "Internal Server Error, Please try again later";
+                message = "Internal Server Error, Please try again later".into();
             }
         }
     } else if err.find::<rweb::reject::MethodNotAllowed>().is_some() {
         code = StatusCode::METHOD_NOT_ALLOWED;
-        message = "METHOD NOT ALLOWED";
+        message = "METHOD NOT ALLOWED".into();
     } else {
         error!("Unknown error: {:?}", err);
         code = StatusCode::INTERNAL_SERVER_ERROR;
-        message = "Internal Server Error, Please try again later";
+        message = "Internal Server Error, Please try again later".into();
     };
 
     let reply = rweb::reply::json(&ErrorMessage {
         code: code.as_u16(),
         message,
+        request_id: current_request_id(),
+        aws_error,
     });
     let reply = rweb::reply::with_status(reply, code);
+    if let Some(secs) = retry_after {
+        let reply = rweb::reply::with_header(reply, RETRY_AFTER, secs.to_string());
+        return Ok(Box::new(reply));
+    }
 
     Ok(Box::new(reply))
 }
@@ -115,22 +252,36 @@ impl Entity for ServiceError {
 }
 
 impl ResponseEntity for ServiceError {
-    fn describe_responses(_: &mut ComponentDescriptor) -> Responses {
+    fn describe_responses(comp_d: &mut ComponentDescriptor) -> Responses {
         let mut map = Responses::new();
 
         let error_responses = [
             (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error"),
             (StatusCode::BAD_REQUEST, "Bad Request"),
+            (StatusCode::FORBIDDEN, "Forbidden"),
+            (StatusCode::NOT_FOUND, "Not Found"),
+            (StatusCode::CONFLICT, "Conflict or Resource Busy"),
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Rate Limited or Throttled by AWS",
+            ),
+            (StatusCode::BAD_GATEWAY, "AWS Request Failed"),
         ];
+        let schema = ErrorMessage::describe(comp_d);
 
         for (code, msg) in &error_responses {
-            map.insert(
-                Cow::Owned(code.as_str().into()),
-                Response {
-                    description: Cow::Borrowed(*msg),
-                    ..Response::default()
+            let mut response = Response {
+                description: Cow::Borrowed(*msg),
+                ..Response::default()
+            };
+            response.content.insert(
+                Cow::Borrowed("application/json"),
+                MediaType {
+                    schema: Some(schema.clone()),
+                    ..MediaType::default()
                 },
             );
+            map.insert(Cow::Owned(code.as_str().into()), response);
         }
 
         map
@@ -142,7 +293,12 @@ mod test {
     use anyhow::Error;
     use rweb::Reply;
 
-    use crate::errors::{error_response, ServiceError};
+    use aws_sdk_s3::error::ErrorMetadata;
+
+    use crate::{
+        errors::{aws_error_status, error_response, ServiceError},
+        request_id::REQUEST_ID,
+    };
 
     #[tokio::test]
     async fn test_service_error() -> Result<(), Error> {
@@ -150,9 +306,84 @@ mod test {
         let resp = error_response(err).await?.into_response();
         assert_eq!(resp.status().as_u16(), 400);
 
+        let err = ServiceError::Forbidden("missing csrf token".into()).into();
+        let resp = error_response(err).await?.into_response();
+        assert_eq!(resp.status().as_u16(), 403);
+
         let err = ServiceError::InternalServerError.into();
         let resp = error_response(err).await?.into_response();
         assert_eq!(resp.status().as_u16(), 500);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_retry_after() -> Result<(), Error> {
+        let err = ServiceError::TooManyRequests("slow down".into(), 12).into();
+        let resp = error_response(err).await?.into_response();
+        assert_eq!(resp.status().as_u16(), 429);
+        assert_eq!(resp.headers()["retry-after"], "12");
+
+        let err = ServiceError::ResourceBusy("vol-0123 is busy".into(), 5).into();
+        let resp = error_response(err).await?.into_response();
+        assert_eq!(resp.status().as_u16(), 409);
+        assert_eq!(resp.headers()["retry-after"], "5");
+
+        let err = ServiceError::Conflict("conflict".into()).into();
+        let resp = error_response(err).await?.into_response();
+        assert!(resp.headers().get("retry-after").is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_error_request_id() -> Result<(), Error> {
+        let err = ServiceError::BadRequest("TEST ERROR".into()).into();
+        let resp = REQUEST_ID
+            .scope("test-request".into(), error_response(err))
+            .await?
+            .into_response();
+        let body = hyper::body::to_bytes(resp.into_body()).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body["request_id"], "test-request");
+        assert_eq!(body["message"], "TEST ERROR");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_aws_error_response() -> Result<(), Error> {
+        let meta = ErrorMetadata::builder()
+            .code("InvalidInstanceID.NotFound")
+            .message("The instance ID 'i-0123' does not exist")
+            .build();
+        let err = ServiceError::from(Error::from(meta)).into();
+        let resp = error_response(err).await?.into_response();
+        assert_eq!(resp.status().as_u16(), 404);
+        let body = hyper::body::to_bytes(resp.into_body()).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body["aws_error"]["code"], "InvalidInstanceID.NotFound");
+        Ok(())
+    }
+
+    #[test]
+    fn test_aws_error_status() {
+        assert_eq!(
+            aws_error_status(Some("InvalidInstanceID.NotFound")).as_u16(),
+            404
+        );
+        assert_eq!(aws_error_status(Some("NoSuchKey")).as_u16(), 404);
+        assert_eq!(
+            aws_error_status(Some("UnauthorizedOperation")).as_u16(),
+            403
+        );
+        assert_eq!(aws_error_status(Some("RequestLimitExceeded")).as_u16(), 429);
+        assert_eq!(
+            aws_error_status(Some("IncorrectInstanceState")).as_u16(),
+            409
+        );
+        assert_eq!(
+            aws_error_status(Some("InvalidParameterValue")).as_u16(),
+            400
+        );
+        assert_eq!(aws_error_status(Some("InternalError")).as_u16(), 502);
+        assert_eq!(aws_error_status(None).as_u16(), 502);
+    }
 }
@@ -0,0 +1,255 @@
+use bytes::Bytes;
+use futures::{stream, Stream};
+use log::{error, info};
+use once_cell::sync::Lazy;
+use rweb::{
+    filters::{
+        body::{bytes, content_length_limit},
+        method::{get, post},
+        sse::{self, Event},
+        BoxedFilter,
+    },
+    Filter, Rejection, Reply,
+};
+use serde::Serialize;
+use stack_string::{format_sstr, StackString};
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, error::RecvError, Sender};
+use uuid::Uuid;
+
+use aws_app_lib::{
+    inbound_email::InboundEmail,
+    models::InboundEmailDB,
+    resource_type::ResourceType,
+    s3_instance::S3Instance,
+    ses_client::SesInstance,
+    sns_event::{
+        Ec2Event, EcrPushEvent, S3ObjectCreated, SnsMessage, SNS_NOTIFICATION,
+        SNS_SUBSCRIPTION_CONFIRMATION,
+    },
+};
+
+use crate::{app::AppState, errors::ServiceError as Error, logged_user::LoggedUser};
+
+/// Largest sns message accepted, sns itself caps messages at 256KiB
+const MAX_MESSAGE_SIZE: u64 = 512 * 1024;
+
+/// Events applied from `POST /aws/events`, fanned out to every open
+/// `GET /aws/events/stream`
+static INSTANCE_EVENTS: Lazy<Sender<Ec2Event>> = Lazy::new(|| broadcast::channel(64).0);
+
+/// Emails ingested by `POST /aws/inbound-email/notify`, sent to every open
+/// `GET /aws/events/stream`
+static EMAIL_EVENTS: Lazy<Sender<EmailEvent>> = Lazy::new(|| broadcast::channel(64).0);
+
+#[derive(Serialize, Debug, Clone)]
+struct EmailEvent {
+    id: Uuid,
+    from_address: StackString,
+    subject: StackString,
+    is_spam: bool,
+}
+
+impl From<&InboundEmailDB> for EmailEvent {
+    fn from(email: &InboundEmailDB) -> Self {
+        Self {
+            id: email.id,
+            from_address: email.from_address.clone(),
+            subject: email.subject.clone(),
+            is_spam: email.is_spam(),
+        }
+    }
+}
+
+/// `POST /aws/events`, sns deliveries of EventBridge ec2 events. Messages
+/// have to be signed by sns and come from one of `config.sns_topic_arns`,
+/// subscription confirmations from those topics are confirmed.
+pub fn sns_events_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "events")
+        .and(rweb::path::end())
+        .and(post())
+        .and(content_length_limit(MAX_MESSAGE_SIZE))
+        .and(bytes())
+        .and_then({
+            let app = app.clone();
+            move |body: Bytes| {
+                let app = app.clone();
+                async move {
+                    let body = handle_sns_message(&app, &body).await?;
+                    Ok::<_, Rejection>(rweb::reply::html(body))
+                }
+            }
+        })
+        .boxed()
+}
+
+/// Parse `body` as an sns message, which has to be signed by sns and come
+/// from one of `config.sns_topic_arns`
+async fn verified_message(app: &AppState, body: &[u8]) -> Result<SnsMessage, Error> {
+    // sns posts json as text/plain, so the body is parsed here rather than
+    // by the json filter
+    let message: SnsMessage =
+        serde_json::from_slice(body).map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    if !app
+        .aws()
+        .config
+        .sns_topic_arns
+        .iter()
+        .any(|arn| arn == &message.topic_arn)
+    {
+        return Err(Error::Forbidden(format_sstr!(
+            "unknown topic {}",
+            message.topic_arn
+        )));
+    }
+    message
+        .verify()
+        .await
+        .map_err(|e| Error::Forbidden(format_sstr!("{e}")))?;
+    Ok(message)
+}
+
+async fn handle_sns_message(app: &AppState, body: &[u8]) -> Result<&'static str, Error> {
+    let message = verified_message(app, body).await?;
+    let aws = app.aws();
+    match message.message_type.as_str() {
+        SNS_SUBSCRIPTION_CONFIRMATION => {
+            message.confirm_subscription().await?;
+            info!("confirmed sns subscription to {}", message.topic_arn);
+            Ok("confirmed")
+        }
+        SNS_NOTIFICATION => {
+            if let Some(push) = EcrPushEvent::from_message(&message.message)
+                .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?
+            {
+                if let Err(e) = aws.record_ecr_repo(&push.repository).await {
+                    error!("failed to record push to {}: {e}", push.repository);
+                }
+                return Ok("recorded");
+            }
+            let event = match Ec2Event::from_message(&message.message)
+                .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?
+            {
+                Some(event) => event,
+                None => return Ok("ignored"),
+            };
+            let ses = SesInstance::new(&aws_config::load_from_env().await);
+            if let Err(e) = aws.apply_ec2_event(&event, &ses).await {
+                error!("failed to apply {}: {e}", event.description());
+            }
+            // no receivers is not an error, nobody has the page open
+            INSTANCE_EVENTS.send(event).ok();
+            Ok("applied")
+        }
+        _ => Ok("ignored"),
+    }
+}
+
+/// `POST /aws/inbound-email/notify`, sns deliveries of s3 event
+/// notifications for the inbound email bucket. Emails are stored as soon as
+/// they land instead of waiting for a sync. Messages have to be signed by sns
+/// and come from one of `config.sns_topic_arns`.
+pub fn inbound_email_notify_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "inbound-email" / "notify")
+        .and(rweb::path::end())
+        .and(post())
+        .and(content_length_limit(MAX_MESSAGE_SIZE))
+        .and(bytes())
+        .and_then({
+            let app = app.clone();
+            move |body: Bytes| {
+                let app = app.clone();
+                async move {
+                    let body = handle_email_notification(&app, &body).await?;
+                    Ok::<_, Rejection>(rweb::reply::html(body))
+                }
+            }
+        })
+        .boxed()
+}
+
+async fn handle_email_notification(app: &AppState, body: &[u8]) -> Result<&'static str, Error> {
+    let message = verified_message(app, body).await?;
+    match message.message_type.as_str() {
+        SNS_SUBSCRIPTION_CONFIRMATION => {
+            message.confirm_subscription().await?;
+            info!("confirmed sns subscription to {}", message.topic_arn);
+            Ok("confirmed")
+        }
+        SNS_NOTIFICATION => {
+            let objects = S3ObjectCreated::from_message(&message.message)
+                .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+            if objects.is_empty() {
+                return Ok("ignored");
+            }
+            let aws = app.aws();
+            let sdk_config = aws_config::load_from_env().await;
+            let s3 = S3Instance::new(&sdk_config).retry_policy((&aws.config).into());
+            let ses = SesInstance::new(&sdk_config);
+            for object in objects {
+                // an error is returned so that sns retries the delivery,
+                // emails already stored are skipped on the retry
+                if let Some(email) = InboundEmail::ingest_notification(
+                    &aws.config,
+                    &s3,
+                    &ses,
+                    aws.storage.as_ref(),
+                    &object.bucket,
+                    &object.key,
+                )
+                .await?
+                {
+                    info!("ingested s3://{}/{}", object.bucket, object.key);
+                    aws.cache.invalidate([ResourceType::InboundEmail]);
+                    EMAIL_EVENTS.send((&email).into()).ok();
+                }
+            }
+            Ok("ingested")
+        }
+        _ => Ok("ignored"),
+    }
+}
+
+/// Server sent events named `name` with the json of each value sent on
+/// `sender` after subscribing
+fn sse_events<T>(
+    sender: &Sender<T>,
+    name: &'static str,
+) -> impl Stream<Item = Result<Event, Infallible>>
+where
+    T: Serialize + Clone + Send + 'static,
+{
+    stream::unfold(sender.subscribe(), move |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(value) => {
+                    let event = Event::default()
+                        .event(name)
+                        .json_data(&value)
+                        .unwrap_or_else(|_| Event::default().comment("invalid event"));
+                    return Some((Ok::<_, Infallible>(event), rx));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+/// `GET /aws/events/stream`, server sent `instance` events with the json of
+/// each ec2 event applied and `email` events for each email ingested from a
+/// notification
+pub fn event_stream_path() -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "events" / "stream")
+        .and(rweb::path::end())
+        .and(get())
+        .and(LoggedUser::filter())
+        .map(|_: LoggedUser| {
+            let events = stream::select(
+                sse_events(&INSTANCE_EVENTS, "instance"),
+                sse_events(&EMAIL_EVENTS, "email"),
+            );
+            sse::reply(sse::keep_alive().stream(events))
+        })
+        .boxed()
+}
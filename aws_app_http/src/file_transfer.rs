@@ -0,0 +1,438 @@
+use bytes::Buf;
+use futures::{stream, TryStreamExt};
+use hyper::Body;
+use log::info;
+use rweb::{
+    filters::{
+        method::{get, post},
+        multipart::{form, FormData, Part},
+        query::query,
+        BoxedFilter,
+    },
+    http::{
+        header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE},
+        Response, StatusCode,
+    },
+    Filter, Rejection, Reply,
+};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use uuid::Uuid;
+
+use aws_app_lib::{
+    aws_app_interface::AwsAppInterface,
+    csv_export::ExportResource,
+    resource_graph::{GraphNode, ResourceGraph},
+    storage::EmailAttachmentRepo,
+};
+
+use crate::{
+    app::AppState, errors::ServiceError as Error, logged_user::LoggedUser, routes::HttpResult,
+};
+
+/// Largest file accepted by `POST /aws/upload/{instance}`
+const MAX_UPLOAD_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Largest object s3 accepts
+const MAX_S3_UPLOAD_SIZE: u64 = 5 * 1024 * 1024 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct DownloadRequest {
+    path: StackString,
+}
+
+#[derive(Deserialize)]
+struct ExportRequest {
+    search: Option<StackString>,
+}
+
+#[derive(Deserialize)]
+struct GraphRequest {
+    format: Option<StackString>,
+    focus: Option<StackString>,
+}
+
+#[derive(Serialize)]
+struct GraphResponse<'a> {
+    #[serde(flatten)]
+    graph: &'a ResourceGraph,
+    impacted: Vec<&'a GraphNode>,
+}
+
+/// `POST /aws/upload/{instance}`, a multipart form with a `file` field and
+/// an optional `path` field, a `path` ending in `/` is treated as a directory
+pub fn upload_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "upload" / StackString)
+        .and(rweb::path::end())
+        .and(post())
+        .and(LoggedUser::filter())
+        .and(form().max_length(MAX_UPLOAD_SIZE))
+        .and_then({
+            let app = app.clone();
+            move |instance: StackString, _: LoggedUser, form: FormData| {
+                let aws = app.aws();
+                async move {
+                    let remote_path = upload_file(&aws, &instance, form).await?;
+                    let body = format_sstr!("Uploaded {remote_path} to {instance}");
+                    Ok::<_, Rejection>(rweb::reply::with_status(
+                        rweb::reply::html(body),
+                        StatusCode::CREATED,
+                    ))
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `GET /aws/download/{instance}?path=`, returns the file as an attachment
+pub fn download_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "download" / StackString)
+        .and(rweb::path::end())
+        .and(get())
+        .and(LoggedUser::filter())
+        .and(query::<DownloadRequest>())
+        .and_then({
+            let app = app.clone();
+            move |instance: StackString, _: LoggedUser, request: DownloadRequest| {
+                let aws = app.aws();
+                async move {
+                    let data = aws
+                        .download_file(&instance, &request.path)
+                        .await
+                        .map_err(Error::from)?;
+                    let filename = file_name(&request.path).unwrap_or("download");
+                    let disposition = format!("attachment; filename=\"{filename}\"");
+                    let reply =
+                        rweb::reply::with_header(data, CONTENT_TYPE, "application/octet-stream");
+                    Ok::<_, Rejection>(rweb::reply::with_header(
+                        reply,
+                        CONTENT_DISPOSITION,
+                        disposition,
+                    ))
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `GET /aws/inbound-email/attachment/{id}`, streams the attachment from s3
+pub fn attachment_download_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "inbound-email" / "attachment" / Uuid)
+        .and(rweb::path::end())
+        .and(get())
+        .and(LoggedUser::filter())
+        .and_then({
+            let app = app.clone();
+            move |id: Uuid, _: LoggedUser| {
+                let aws = app.aws();
+                async move {
+                    let response = download_attachment(&aws, id).await?;
+                    Ok::<_, Rejection>(response)
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `GET /aws/export/{resource}.csv`, the listing behind the resource table as
+/// csv, `?search=` restricts the prices export to matching instance types
+pub fn export_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "export" / ExportResource)
+        .and(rweb::path::end())
+        .and(get())
+        .and(LoggedUser::filter())
+        .and(query::<ExportRequest>())
+        .and_then({
+            let app = app.clone();
+            move |resource: ExportResource, _: LoggedUser, request: ExportRequest| {
+                let aws = app.aws();
+                async move {
+                    let data = aws
+                        .export_csv(resource, request.search.as_deref())
+                        .await
+                        .map_err(Error::from)?;
+                    let disposition = format!("attachment; filename=\"{resource}.csv\"");
+                    let reply =
+                        rweb::reply::with_header(data, CONTENT_TYPE, "text/csv; charset=utf-8");
+                    Ok::<_, Rejection>(rweb::reply::with_header(
+                        reply,
+                        CONTENT_DISPOSITION,
+                        disposition,
+                    ))
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `POST /aws/create_key_pair/{key_name}`, creates the key pair and returns
+/// the private key as an attachment, aws doesn't keep a copy
+pub fn create_key_pair_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "create_key_pair" / StackString)
+        .and(rweb::path::end())
+        .and(post())
+        .and(LoggedUser::filter())
+        .and_then({
+            let app = app.clone();
+            move |key_name: StackString, _: LoggedUser| {
+                let aws = app.aws();
+                async move {
+                    let private_key = aws.create_key_pair(&key_name).await.map_err(Error::from)?;
+                    let disposition = format!("attachment; filename=\"{key_name}.pem\"");
+                    let reply = rweb::reply::with_header(
+                        private_key.to_string(),
+                        CONTENT_TYPE,
+                        "application/x-pem-file",
+                    );
+                    let reply = rweb::reply::with_header(reply, CACHE_CONTROL, "no-store");
+                    Ok::<_, Rejection>(rweb::reply::with_header(
+                        reply,
+                        CONTENT_DISPOSITION,
+                        disposition,
+                    ))
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `GET /aws/route53/{zone}/export`, the hosted zone as a BIND zone file
+pub fn zone_export_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "route53" / StackString / "export")
+        .and(rweb::path::end())
+        .and(get())
+        .and(LoggedUser::filter())
+        .and_then({
+            let app = app.clone();
+            move |zone: StackString, _: LoggedUser| {
+                let aws = app.aws();
+                async move {
+                    let body = aws.route53.export_zone(&zone).await.map_err(Error::from)?;
+                    let filename = zone.replace(|c: char| !c.is_ascii_alphanumeric(), "");
+                    let disposition = format!("attachment; filename=\"{filename}.zone\"");
+                    let reply =
+                        rweb::reply::with_header(body.to_string(), CONTENT_TYPE, "text/dns");
+                    Ok::<_, Rejection>(rweb::reply::with_header(
+                        reply,
+                        CONTENT_DISPOSITION,
+                        disposition,
+                    ))
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `GET /aws/graph`, how instances, volumes, snapshots, amis, spot requests
+/// and dns records refer to each other as json, or graphviz dot with
+/// `?format=dot`. `?focus={id}` adds the resources which break if `id` is
+/// deleted to the json.
+pub fn graph_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "graph")
+        .and(rweb::path::end())
+        .and(get())
+        .and(LoggedUser::filter())
+        .and(query::<GraphRequest>())
+        .and_then({
+            let app = app.clone();
+            move |_: LoggedUser, request: GraphRequest| {
+                let aws = app.aws();
+                async move {
+                    let graph = aws.resource_graph().await.map_err(Error::from)?;
+                    let response = if request.format.as_deref() == Some("dot") {
+                        rweb::reply::with_header(
+                            graph.to_dot().to_string(),
+                            CONTENT_TYPE,
+                            "text/vnd.graphviz",
+                        )
+                        .into_response()
+                    } else {
+                        let impacted = request
+                            .focus
+                            .as_ref()
+                            .map(|focus| graph.impacted(focus))
+                            .unwrap_or_default();
+                        rweb::reply::json(&GraphResponse {
+                            graph: &graph,
+                            impacted,
+                        })
+                        .into_response()
+                    };
+                    Ok::<_, Rejection>(response)
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `POST /aws/s3/{bucket}/upload`, a multipart form with an optional `key`
+/// field followed by a `file` field, the file is streamed to s3 in parts
+/// rather than held in memory
+pub fn s3_upload_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    rweb::path!("aws" / "s3" / StackString / "upload")
+        .and(rweb::path::end())
+        .and(post())
+        .and(LoggedUser::filter())
+        .and(form().max_length(MAX_S3_UPLOAD_SIZE))
+        .and_then({
+            let app = app.clone();
+            move |bucket: StackString, _: LoggedUser, form: FormData| {
+                let aws = app.aws();
+                async move {
+                    let body = upload_s3_object(&aws, &bucket, form).await?;
+                    Ok::<_, Rejection>(rweb::reply::with_status(
+                        rweb::reply::html(body),
+                        StatusCode::CREATED,
+                    ))
+                }
+            }
+        })
+        .boxed()
+}
+
+async fn download_attachment(aws: &AwsAppInterface, id: Uuid) -> HttpResult<Response<Body>> {
+    let attachment = aws
+        .storage
+        .get_attachment(id)
+        .await?
+        .ok_or_else(|| Error::BadRequest(format_sstr!("No attachment {id}")))?;
+    let (length, body) = aws
+        .s3
+        .download_stream(&attachment.s3_bucket, &attachment.s3_key)
+        .await?;
+    let body = stream::unfold(body, |mut body| async move {
+        body.next().await.map(|chunk| (chunk, body))
+    });
+    let content_type = attachment
+        .content_type
+        .as_ref()
+        .map_or("application/octet-stream", StackString::as_str);
+    let filename = attachment.filename.replace('"', "");
+    let mut builder = Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .header(
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{filename}\""),
+        );
+    if let Some(length) = length {
+        builder = builder.header(CONTENT_LENGTH, length);
+    }
+    builder
+        .body(Body::wrap_stream(body))
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))
+}
+
+async fn upload_file(
+    aws: &AwsAppInterface,
+    instance: &str,
+    mut form: FormData,
+) -> HttpResult<StackString> {
+    let mut path: Option<StackString> = None;
+    let mut upload: Option<(Option<StackString>, Vec<u8>)> = None;
+    while let Some(part) = form
+        .try_next()
+        .await
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?
+    {
+        match part.name() {
+            "path" => {
+                let data = read_part(part).await?;
+                path = Some(StackString::from_utf8_vec(data).map_err(Into::<Error>::into)?);
+            }
+            "file" => {
+                let filename = part.filename().and_then(file_name).map(Into::into);
+                upload = Some((filename, read_part(part).await?));
+            }
+            _ => (),
+        }
+    }
+    let (filename, data) = upload.ok_or_else(|| Error::BadRequest("No file uploaded".into()))?;
+    let remote_path = remote_destination(path.as_deref(), filename.as_deref())?;
+    aws.upload_file(instance, &data, &remote_path)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(remote_path)
+}
+
+async fn upload_s3_object(
+    aws: &AwsAppInterface,
+    bucket: &str,
+    mut form: FormData,
+) -> HttpResult<StackString> {
+    let mut key: Option<StackString> = None;
+    while let Some(part) = form
+        .try_next()
+        .await
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?
+    {
+        match part.name() {
+            "key" => {
+                let data = read_part(part).await?;
+                let data = StackString::from_utf8_vec(data).map_err(Into::<Error>::into)?;
+                key = Some(data.trim().into()).filter(|k: &StackString| !k.is_empty());
+            }
+            "file" => {
+                let key = key
+                    .or_else(|| part.filename().and_then(file_name).map(Into::into))
+                    .ok_or_else(|| Error::BadRequest("Upload has no key".into()))?;
+                let body = part
+                    .stream()
+                    .map_ok(|mut buf| buf.copy_to_bytes(buf.remaining()))
+                    .map_err(Into::into);
+                let result = aws
+                    .s3
+                    .multipart_upload(bucket, &key, None, body, |progress| {
+                        info!("upload s3://{bucket}/{key}: {progress}");
+                    })
+                    .await
+                    .map_err(Into::<Error>::into)?;
+                return Ok(format_sstr!("Uploaded s3://{bucket}/{key} {result}"));
+            }
+            _ => (),
+        }
+    }
+    Err(Error::BadRequest("No file uploaded".into()))
+}
+
+async fn read_part(part: Part) -> HttpResult<Vec<u8>> {
+    part.stream()
+        .try_fold(Vec::new(), |mut buf, chunk| async move {
+            buf.extend_from_slice(chunk.chunk());
+            Ok(buf)
+        })
+        .await
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))
+}
+
+fn file_name(path: &str) -> Option<&str> {
+    path.rsplit('/').next().filter(|f| !f.is_empty())
+}
+
+/// Where an upload ends up, a missing or empty `path` means the home
+/// directory of the ssh user
+fn remote_destination(path: Option<&str>, filename: Option<&str>) -> HttpResult<StackString> {
+    let path = path.map(str::trim).filter(|p| !p.is_empty());
+    match (path, filename) {
+        (Some(path), Some(filename)) if path.ends_with('/') => Ok(format_sstr!("{path}{filename}")),
+        (Some(path), _) if !path.ends_with('/') => Ok(path.into()),
+        (None, Some(filename)) => Ok(filename.into()),
+        _ => Err(Error::BadRequest("Upload has no file name".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file_transfer::remote_destination;
+
+    #[test]
+    fn test_remote_destination() {
+        let dest = remote_destination(Some("/etc/app/"), Some("config.toml")).unwrap();
+        assert_eq!(dest, "/etc/app/config.toml");
+        let dest = remote_destination(Some("/etc/app/app.toml"), Some("config.toml")).unwrap();
+        assert_eq!(dest, "/etc/app/app.toml");
+        let dest = remote_destination(Some(" "), Some("config.toml")).unwrap();
+        assert_eq!(dest, "config.toml");
+        assert!(remote_destination(Some("/etc/app/"), None).is_err());
+        assert!(remote_destination(None, None).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Result, Schema};
+use rweb::{
+    filters::{body::json, method::post, BoxedFilter},
+    Filter, Rejection, Reply,
+};
+use stack_string::StackString;
+use std::{collections::HashMap, sync::Arc};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::OnceCell;
+
+use aws_app_lib::{
+    aws_app_interface::{AwsAppInterface, AwsInstancePrice},
+    ec2_instance::{Ec2InstanceInfo, SnapshotInfo, VolumeInfo},
+    models::InboundEmailDB,
+    storage::InboundEmailRepo,
+};
+
+use crate::{app::AppState, logged_user::LoggedUser};
+
+pub type InventorySchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Upper bound on `inboundEmails(limit:)`
+const MAX_EMAIL_LIMIT: usize = 100;
+
+#[must_use]
+pub fn build_schema() -> InventorySchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription).finish()
+}
+
+/// `POST /aws/graphql`, a graphql request over instances, volumes,
+/// snapshots, prices and inbound email of the active account
+pub fn graphql_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
+    let schema = build_schema();
+    rweb::path!("aws" / "graphql")
+        .and(rweb::path::end())
+        .and(post())
+        .and(LoggedUser::filter())
+        .and(json())
+        .and_then({
+            let app = app.clone();
+            move |_: LoggedUser, request: async_graphql::Request| {
+                let schema = schema.clone();
+                let inventory = Inventory::new(app.aws());
+                async move {
+                    let response = schema.execute(request.data(inventory)).await;
+                    Ok::<_, Rejection>(rweb::reply::json(&response))
+                }
+            }
+        })
+        .boxed()
+}
+
+/// Per request view of the account, volumes and snapshots are listed at
+/// most once however many instances or volumes a query resolves them for
+pub struct Inventory {
+    aws: AwsAppInterface,
+    volumes: OnceCell<Arc<Vec<VolumeInfo>>>,
+    snapshots: OnceCell<Arc<Vec<SnapshotInfo>>>,
+}
+
+impl Inventory {
+    #[must_use]
+    pub fn new(aws: AwsAppInterface) -> Self {
+        Self {
+            aws,
+            volumes: OnceCell::new(),
+            snapshots: OnceCell::new(),
+        }
+    }
+
+    async fn volumes(&self) -> Result<Arc<Vec<VolumeInfo>>> {
+        let volumes = self
+            .volumes
+            .get_or_try_init(|| async {
+                let volumes: Vec<_> = self.aws.ec2.get_all_volumes().await?.collect();
+                Ok::<_, anyhow::Error>(Arc::new(volumes))
+            })
+            .await?;
+        Ok(volumes.clone())
+    }
+
+    async fn snapshots(&self) -> Result<Arc<Vec<SnapshotInfo>>> {
+        let snapshots = self
+            .snapshots
+            .get_or_try_init(|| async {
+                let snapshots: Vec<_> = self.aws.ec2.get_all_snapshots().await?.collect();
+                Ok::<_, anyhow::Error>(Arc::new(snapshots))
+            })
+            .await?;
+        Ok(snapshots.clone())
+    }
+}
+
+fn format_time(t: OffsetDateTime) -> String {
+    t.format(&Rfc3339).unwrap_or_default()
+}
+
+pub struct Tag {
+    key: String,
+    value: String,
+}
+
+#[Object]
+impl Tag {
+    async fn key(&self) -> &str {
+        &self.key
+    }
+
+    async fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+fn tags(tags: &HashMap<StackString, StackString>) -> Vec<Tag> {
+    let mut tags: Vec<_> = tags
+        .iter()
+        .map(|(k, v)| Tag {
+            key: k.to_string(),
+            value: v.to_string(),
+        })
+        .collect();
+    tags.sort_by(|a, b| a.key.cmp(&b.key));
+    tags
+}
+
+pub struct Instance(Ec2InstanceInfo);
+
+#[Object]
+impl Instance {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn dns_name(&self) -> &str {
+        &self.0.dns_name
+    }
+
+    async fn state(&self) -> &str {
+        &self.0.state
+    }
+
+    async fn instance_type(&self) -> &str {
+        &self.0.instance_type
+    }
+
+    async fn availability_zone(&self) -> &str {
+        &self.0.availability_zone
+    }
+
+    async fn launch_time(&self) -> String {
+        format_time(self.0.launch_time.to_offsetdatetime())
+    }
+
+    async fn name(&self) -> Option<&str> {
+        self.0.tags.get("Name").map(StackString::as_str)
+    }
+
+    async fn tags(&self) -> Vec<Tag> {
+        tags(&self.0.tags)
+    }
+
+    /// Volumes attached to the instance
+    async fn volumes(&self, ctx: &Context<'_>) -> Result<Vec<Volume>> {
+        let volumes = ctx.data::<Inventory>()?.volumes().await?;
+        Ok(volumes
+            .iter()
+            .filter(|v| self.0.volumes.contains(&v.id))
+            .cloned()
+            .map(Volume)
+            .collect())
+    }
+}
+
+pub struct Volume(VolumeInfo);
+
+#[Object]
+impl Volume {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn availability_zone(&self) -> &str {
+        &self.0.availability_zone
+    }
+
+    /// Size in GB
+    async fn size(&self) -> i64 {
+        self.0.size
+    }
+
+    async fn iops(&self) -> i64 {
+        self.0.iops
+    }
+
+    async fn state(&self) -> &str {
+        &self.0.state
+    }
+
+    async fn tags(&self) -> Vec<Tag> {
+        tags(&self.0.tags)
+    }
+
+    /// Snapshots taken of the volume
+    async fn snapshots(&self, ctx: &Context<'_>) -> Result<Vec<Snapshot>> {
+        let snapshots = ctx.data::<Inventory>()?.snapshots().await?;
+        Ok(snapshots
+            .iter()
+            .filter(|s| s.volume_id.as_ref() == Some(&self.0.id))
+            .cloned()
+            .map(Snapshot)
+            .collect())
+    }
+}
+
+pub struct Snapshot(SnapshotInfo);
+
+#[Object]
+impl Snapshot {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn volume_id(&self) -> Option<&str> {
+        self.0.volume_id.as_ref().map(StackString::as_str)
+    }
+
+    /// Size in GB of the source volume
+    async fn volume_size(&self) -> i64 {
+        self.0.volume_size
+    }
+
+    async fn state(&self) -> &str {
+        &self.0.state
+    }
+
+    async fn progress(&self) -> &str {
+        &self.0.progress
+    }
+
+    async fn tags(&self) -> Vec<Tag> {
+        tags(&self.0.tags)
+    }
+
+    /// Source volume, `None` once it has been deleted
+    async fn volume(&self, ctx: &Context<'_>) -> Result<Option<Volume>> {
+        let volume_id = match &self.0.volume_id {
+            Some(volume_id) => volume_id,
+            None => return Ok(None),
+        };
+        let volumes = ctx.data::<Inventory>()?.volumes().await?;
+        Ok(volumes
+            .iter()
+            .find(|v| &v.id == volume_id)
+            .cloned()
+            .map(Volume))
+    }
+}
+
+pub struct Price(AwsInstancePrice);
+
+#[Object]
+impl Price {
+    async fn instance_type(&self) -> &str {
+        &self.0.instance_type
+    }
+
+    /// USD per hour
+    async fn ondemand_price(&self) -> Option<f64> {
+        self.0.ondemand_price
+    }
+
+    async fn spot_price(&self) -> Option<f64> {
+        self.0.spot_price
+    }
+
+    async fn reserved_price(&self) -> Option<f64> {
+        self.0.reserved_price
+    }
+
+    async fn ncpu(&self) -> i32 {
+        self.0.ncpu
+    }
+
+    /// Memory in GiB
+    async fn memory(&self) -> f64 {
+        self.0.memory
+    }
+
+    async fn gpu_count(&self) -> i32 {
+        self.0.gpu_count
+    }
+
+    async fn instance_family(&self) -> String {
+        self.0.instance_family.to_string()
+    }
+}
+
+pub struct InboundEmail(InboundEmailDB);
+
+#[Object]
+impl InboundEmail {
+    async fn id(&self) -> String {
+        self.0.id.to_string()
+    }
+
+    async fn from_address(&self) -> &str {
+        &self.0.from_address
+    }
+
+    async fn to_address(&self) -> &str {
+        &self.0.to_address
+    }
+
+    async fn subject(&self) -> &str {
+        &self.0.subject
+    }
+
+    async fn date(&self) -> String {
+        format_time(self.0.date)
+    }
+
+    async fn text_content(&self) -> &str {
+        &self.0.text_content
+    }
+
+    async fn spam_score(&self) -> f64 {
+        self.0.spam_score
+    }
+
+    async fn is_spam(&self) -> bool {
+        self.0.is_spam()
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Instances of the active account, optionally only those in `state`
+    async fn instances(&self, ctx: &Context<'_>, state: Option<String>) -> Result<Vec<Instance>> {
+        let aws = &ctx.data::<Inventory>()?.aws;
+        Ok(aws
+            .ec2
+            .get_all_instances()
+            .await?
+            .filter(|i| {
+                state
+                    .as_ref()
+                    .map_or(true, |s| i.state.as_str() == s.as_str())
+            })
+            .map(Instance)
+            .collect())
+    }
+
+    async fn instance(&self, ctx: &Context<'_>, id: String) -> Result<Option<Instance>> {
+        let aws = &ctx.data::<Inventory>()?.aws;
+        Ok(aws
+            .ec2
+            .get_all_instances()
+            .await?
+            .find(|i| i.id.as_str() == id.as_str())
+            .map(Instance))
+    }
+
+    async fn volumes(&self, ctx: &Context<'_>) -> Result<Vec<Volume>> {
+        let volumes = ctx.data::<Inventory>()?.volumes().await?;
+        Ok(volumes.iter().cloned().map(Volume).collect())
+    }
+
+    async fn snapshots(&self, ctx: &Context<'_>) -> Result<Vec<Snapshot>> {
+        let snapshots = ctx.data::<Inventory>()?.snapshots().await?;
+        Ok(snapshots.iter().cloned().map(Snapshot).collect())
+    }
+
+    /// Prices of instance types matching any of `search`, e.g. `["m5", "t3"]`
+    async fn prices(&self, ctx: &Context<'_>, search: Vec<String>) -> Result<Vec<Price>> {
+        let aws = &ctx.data::<Inventory>()?.aws;
+        let prices = aws.get_ec2_prices(search.as_slice()).await?;
+        Ok(prices.into_iter().map(Price).collect())
+    }
+
+    async fn inbound_emails(
+        &self,
+        ctx: &Context<'_>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<Vec<InboundEmail>> {
+        let aws = &ctx.data::<Inventory>()?.aws;
+        let limit = limit.unwrap_or(MAX_EMAIL_LIMIT).min(MAX_EMAIL_LIMIT);
+        let emails: Vec<_> = aws
+            .storage
+            .get_emails(offset, Some(limit))
+            .await?
+            .into_iter()
+            .map(InboundEmail)
+            .collect();
+        Ok(emails)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphql::build_schema;
+
+    #[test]
+    fn test_schema_relations() {
+        let sdl = build_schema().sdl();
+        assert!(sdl.contains("volumes: [Volume!]!"));
+        assert!(sdl.contains("snapshots: [Snapshot!]!"));
+        assert!(sdl.contains("volume: Volume"));
+        assert!(sdl.contains("inboundEmails(offset: Int, limit: Int): [InboundEmail!]!"));
+    }
+}
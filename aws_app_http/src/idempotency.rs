@@ -0,0 +1,158 @@
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rweb::{filters::header, Filter, Rejection};
+use stack_string::{format_sstr, StackString};
+use std::{any::Any, collections::HashMap, future::Future, sync::Arc};
+use time::{Duration, OffsetDateTime};
+
+use crate::{errors::ServiceError as Error, logged_user::LoggedUser};
+
+pub const IDEMPOTENCY_HEADER: &str = "idempotency-key";
+
+/// How long a completed response is replayed for
+const IDEMPOTENCY_TTL: Duration = Duration::hours(1);
+
+enum Entry {
+    InProgress,
+    Done(Arc<dyn Any + Send + Sync>),
+}
+
+static RESPONSES: Lazy<Mutex<HashMap<StackString, (OffsetDateTime, Entry)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets an in progress key unless its request completed, so a request
+/// that failed, panicked or was dropped when the client disconnected can be
+/// retried straight away
+struct InProgressGuard(Option<StackString>);
+
+impl Drop for InProgressGuard {
+    fn drop(&mut self) {
+        if let Some(key) = self.0.take() {
+            RESPONSES.lock().remove(&key);
+        }
+    }
+}
+
+/// Value of the optional `Idempotency-Key` header
+#[must_use]
+pub fn idempotency_key() -> impl Filter<Extract = (Option<StackString>,), Error = Rejection> + Copy
+{
+    header::optional::<String>(IDEMPOTENCY_HEADER).map(|key: Option<String>| key.map(Into::into))
+}
+
+/// Run `f` once per (`user`, `operation`, `key`): a repeat of a completed
+/// request gets the original response, a repeat of one still running is
+/// refused. Failures, and requests dropped before completing, are forgotten
+/// so that the request can be retried. Without a key `f` is always run.
+/// # Errors
+/// Returns error if `f` fails or the same key is already in progress
+pub async fn idempotent<T, F, Fut>(
+    user: &LoggedUser,
+    operation: &str,
+    key: Option<StackString>,
+    f: F,
+) -> Result<T, Error>
+where
+    T: Clone + Send + Sync + 'static,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let key = match key {
+        Some(key) => format_sstr!("{}:{operation}:{key}", user.email),
+        None => return f().await,
+    };
+    {
+        let now = OffsetDateTime::now_utc();
+        let mut responses = RESPONSES.lock();
+        responses.retain(|_, (created, _)| now - *created < IDEMPOTENCY_TTL);
+        match responses.get(&key) {
+            Some((_, Entry::Done(response))) => {
+                return response.downcast_ref::<T>().cloned().ok_or_else(|| {
+                    Error::Conflict("idempotency key reused for a different request".into())
+                });
+            }
+            Some((_, Entry::InProgress)) => {
+                return Err(Error::Conflict(
+                    "request with this idempotency key is in progress".into(),
+                ));
+            }
+            None => {
+                responses.insert(key.clone(), (now, Entry::InProgress));
+            }
+        }
+    }
+    let mut guard = InProgressGuard(Some(key));
+    let result = f().await;
+    if let Ok(response) = &result {
+        if let Some(key) = guard.0.take() {
+            let entry = Entry::Done(Arc::new(response.clone()));
+            RESPONSES
+                .lock()
+                .insert(key, (OffsetDateTime::now_utc(), entry));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::pending;
+    use rweb_helper::DateTimeType;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+    use time::OffsetDateTime;
+    use tokio::time::timeout;
+    use uuid::Uuid;
+
+    use crate::{errors::ServiceError as Error, idempotency::idempotent, logged_user::LoggedUser};
+
+    #[tokio::test]
+    async fn test_idempotent() -> Result<(), Error> {
+        let user = LoggedUser {
+            email: "user@test".into(),
+            session: Uuid::new_v4().into(),
+            created_at: DateTimeType::from(OffsetDateTime::now_utc()),
+        };
+        let calls = AtomicUsize::new(0);
+        let run = || async { Ok::<_, Error>(calls.fetch_add(1, Ordering::SeqCst)) };
+        let key = Uuid::new_v4().to_string();
+
+        let first = idempotent(&user, "test", Some(key.as_str().into()), run).await?;
+        let second = idempotent(&user, "test", Some(key.as_str().into()), run).await?;
+        assert_eq!(first, second);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        idempotent(&user, "other", Some(key.as_str().into()), run).await?;
+        idempotent(&user, "test", None, run).await?;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let failing = || async { Err::<usize, _>(Error::BadRequest("failed".into())) };
+        let key = Uuid::new_v4().to_string();
+        assert!(
+            idempotent(&user, "test", Some(key.as_str().into()), failing)
+                .await
+                .is_err()
+        );
+        let retried = idempotent(&user, "test", Some(key.as_str().into()), run).await?;
+        assert_eq!(retried, 3);
+
+        // dropped before completing, as when the client disconnects
+        let key = Uuid::new_v4().to_string();
+        assert!(timeout(
+            Duration::from_millis(10),
+            idempotent(
+                &user,
+                "test",
+                Some(key.as_str().into()),
+                pending::<Result<usize, Error>>
+            )
+        )
+        .await
+        .is_err());
+        let retried = idempotent(&user, "test", Some(key.as_str().into()), run).await?;
+        assert_eq!(retried, 4);
+        Ok(())
+    }
+}
@@ -8,12 +8,26 @@
 #![recursion_limit = "256"]
 
 pub mod app;
+pub mod bindings;
+pub mod csrf;
 pub mod elements;
 pub mod errors;
+pub mod events;
+pub mod file_transfer;
+pub mod graphql;
+pub mod idempotency;
 pub mod ipv4addr_wrapper;
 pub mod logged_user;
+pub mod metrics;
+pub mod mutation;
+pub mod rate_limit;
+pub mod request_id;
 pub mod requests;
+pub mod resource_lock;
 pub mod routes;
+pub mod table_preferences;
+pub mod task_supervisor;
+pub mod theme;
 
 use derive_more::{From, Into};
 use rweb::Schema;
@@ -23,7 +37,7 @@ use stack_string::StackString;
 use std::collections::HashMap;
 
 use aws_app_lib::{
-    iam_instance::{IamAccessKey, IamUser},
+    iam_instance::{IamAccessKey, IamUser, MfaDevice},
     resource_type::ResourceType,
 };
 
@@ -46,6 +60,27 @@ struct _IamUserWrapper {
     user_name: StackString,
     #[schema(description = "Tags")]
     tags: HashMap<String, StackString>,
+    #[schema(description = "MFA Devices")]
+    mfa_devices: Vec<MfaDeviceWrapper>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Into, From)]
+pub struct MfaDeviceWrapper(MfaDevice);
+
+derive_rweb_schema!(MfaDeviceWrapper, _MfaDeviceWrapper);
+
+#[allow(dead_code)]
+#[derive(Schema)]
+#[schema(component = "MfaDevice")]
+struct _MfaDeviceWrapper {
+    #[schema(description = "User Name")]
+    user_name: Option<StackString>,
+    #[schema(description = "Serial Number or Arn")]
+    serial_number: StackString,
+    #[schema(description = "Enabled DateTime")]
+    enable_date: Option<DateTimeType>,
+    #[schema(description = "Virtual MFA Device")]
+    is_virtual: bool,
 }
 
 #[derive(Serialize, Deserialize, Into, From)]
@@ -108,13 +143,25 @@ enum _ResourceTypeWrapper {
     SystemD,
     #[serde(rename = "inbound-email")]
     InboundEmail,
+    #[serde(rename = "sqs")]
+    Sqs,
+    #[serde(rename = "backup")]
+    Backup,
+    #[serde(rename = "lambda")]
+    Lambda,
+    #[serde(rename = "acm")]
+    Acm,
+    #[serde(rename = "docker")]
+    Docker,
+    #[serde(rename = "ecs")]
+    Ecs,
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
-        IamAccessKeyWrapper, IamUserWrapper, ResourceTypeWrapper, _IamAccessKeyWrapper,
-        _IamUserWrapper, _ResourceTypeWrapper,
+        IamAccessKeyWrapper, IamUserWrapper, MfaDeviceWrapper, ResourceTypeWrapper,
+        _IamAccessKeyWrapper, _IamUserWrapper, _MfaDeviceWrapper, _ResourceTypeWrapper,
     };
     use rweb_helper::derive_rweb_test;
 
@@ -122,6 +169,7 @@ mod test {
     fn test_types() {
         derive_rweb_test!(IamUserWrapper, _IamUserWrapper);
         derive_rweb_test!(IamAccessKeyWrapper, _IamAccessKeyWrapper);
+        derive_rweb_test!(MfaDeviceWrapper, _MfaDeviceWrapper);
         derive_rweb_test!(ResourceTypeWrapper, _ResourceTypeWrapper);
     }
 }
@@ -2,7 +2,6 @@ pub use authorized_users::{
     get_random_key, get_secrets, token::Token, AuthorizedUser, AuthorizedUser as ExternalUser,
     AUTHORIZED_USERS, JWT_SECRET, KEY_LENGTH, LOGIN_HTML, SECRET_KEY,
 };
-use futures::TryStreamExt;
 use log::debug;
 use maplit::hashmap;
 use rweb::{filters::cookie::cookie, Filter, Rejection, Schema};
@@ -16,9 +15,10 @@ use std::{
     str::FromStr,
 };
 use time::OffsetDateTime;
+use tracing::Span;
 use uuid::Uuid;
 
-use aws_app_lib::{models::AuthorizedUsers as AuthorizedUsersDB, pgpool::PgPool};
+use aws_app_lib::storage::{AuthorizedUsersRepo, Storage};
 
 use crate::errors::ServiceError as Error;
 
@@ -54,6 +54,10 @@ impl LoggedUser {
                     .map(|_| user)
                     .map_err(rweb::reject::custom)
             })
+            .map(|user: Self| {
+                Span::current().record("user", user.email.as_str());
+                user
+            })
     }
 }
 
@@ -92,7 +96,7 @@ impl FromStr for LoggedUser {
 
 /// # Errors
 /// Returns error if `get_authorized_users` fails
-pub async fn fill_from_db(pool: &PgPool) -> Result<(), Error> {
+pub async fn fill_from_db(storage: &dyn Storage) -> Result<(), Error> {
     if let Ok("true") = var("TESTENV").as_ref().map(String::as_str) {
         AUTHORIZED_USERS.update_users(hashmap! {
             "user@test".into() => ExternalUser {
@@ -104,7 +108,7 @@ pub async fn fill_from_db(pool: &PgPool) -> Result<(), Error> {
         });
         return Ok(());
     }
-    let most_recent_user_db = AuthorizedUsersDB::get_most_recent(pool).await?;
+    let most_recent_user_db = storage.get_most_recent_user_change().await?;
     let existing_users = AUTHORIZED_USERS.get_users();
     let most_recent_user = existing_users.values().map(|i| i.created_at).max();
     if most_recent_user_db.is_some()
@@ -115,9 +119,11 @@ pub async fn fill_from_db(pool: &PgPool) -> Result<(), Error> {
     }
     debug!("most_recent_user_db {most_recent_user_db:?} most_recent_user {most_recent_user:?}");
 
-    let result: Result<HashMap<StackString, _>, _> = AuthorizedUsersDB::get_authorized_users(pool)
+    let users: HashMap<StackString, _> = storage
+        .get_authorized_users()
         .await?
-        .map_ok(|u| {
+        .into_iter()
+        .map(|u| {
             (
                 u.email.clone(),
                 ExternalUser {
@@ -128,9 +134,7 @@ pub async fn fill_from_db(pool: &PgPool) -> Result<(), Error> {
                 },
             )
         })
-        .try_collect()
-        .await;
-    let users = result?;
+        .collect();
     AUTHORIZED_USERS.update_users(users);
     debug!("AUTHORIZED_USERS {:?}", *AUTHORIZED_USERS);
     Ok(())
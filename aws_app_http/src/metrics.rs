@@ -0,0 +1,228 @@
+use anyhow::Error as AnyhowError;
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_gauge, register_gauge_vec, register_histogram_vec, register_int_counter_vec,
+    register_int_gauge_vec, Encoder, Gauge, GaugeVec, HistogramVec, IntCounterVec, IntGaugeVec,
+    TextEncoder,
+};
+use rweb::filters::log::Info;
+use stack_string::{format_sstr, StackString};
+use std::convert::TryFrom;
+use time::OffsetDateTime;
+
+use aws_app_lib::{
+    aws_app_interface::INSTANCE_LIST_UPDATED, retry::retry_metrics, sysinfo_instance::HostMetrics,
+};
+
+use crate::errors::ServiceError as Error;
+
+static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aws_app_http_requests_total",
+        "Number of http requests by route, method and status",
+        &["route", "method", "status"]
+    )
+    .expect("Failed to register aws_app_http_requests_total")
+});
+
+static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aws_app_http_request_duration_seconds",
+        "Http request latency by route and method",
+        &["route", "method"]
+    )
+    .expect("Failed to register aws_app_http_request_duration_seconds")
+});
+
+static AWS_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aws_app_http_aws_errors_total",
+        "Number of failed aws operations, labelled by the route that issued them",
+        &["operation"]
+    )
+    .expect("Failed to register aws_app_http_aws_errors_total")
+});
+
+static BACKGROUND_TASK_RUNS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aws_app_http_background_task_runs_total",
+        "Number of background task runs by task and result",
+        &["task", "result"]
+    )
+    .expect("Failed to register aws_app_http_background_task_runs_total")
+});
+
+static INSTANCE_LIST_AGE: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "aws_app_http_instance_list_age_seconds",
+        "Seconds since the cached instance list was last refreshed"
+    )
+    .expect("Failed to register aws_app_http_instance_list_age_seconds")
+});
+
+static AWS_RETRIES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aws_app_http_aws_retries",
+        "Retry loop outcomes for aws calls since startup",
+        &["outcome"]
+    )
+    .expect("Failed to register aws_app_http_aws_retries")
+});
+
+static HOST_LOAD_AVERAGE: Lazy<GaugeVec> = Lazy::new(|| {
+    register_gauge_vec!(
+        "aws_app_host_load_average",
+        "Load average of the host over the last 1, 5 and 15 minutes",
+        &["period"]
+    )
+    .expect("Failed to register aws_app_host_load_average")
+});
+
+static HOST_CPU_USAGE: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "aws_app_host_cpu_usage_percent",
+        "Percent of all cpus used since the previous scrape"
+    )
+    .expect("Failed to register aws_app_host_cpu_usage_percent")
+});
+
+static HOST_MEMORY: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aws_app_host_memory_bytes",
+        "Host memory and swap in bytes",
+        &["kind"]
+    )
+    .expect("Failed to register aws_app_host_memory_bytes")
+});
+
+static HOST_DISK: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aws_app_host_disk_bytes",
+        "Size and free space of each mounted file system",
+        &["mount_point", "kind"]
+    )
+    .expect("Failed to register aws_app_host_disk_bytes")
+});
+
+static HOST_NETWORK: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aws_app_host_network",
+        "Network counters since boot by interface",
+        &["interface", "counter"]
+    )
+    .expect("Failed to register aws_app_host_network")
+});
+
+/// Collapse path parameters so that e.g. `/aws/crontab_logs/root` and
+/// `/aws/crontab_logs/user` share a label
+pub fn route_label(path: &str) -> StackString {
+    let segments: Vec<_> = path.split('/').filter(|s| !s.is_empty()).take(2).collect();
+    format_sstr!("/{}", segments.join("/"))
+}
+
+pub fn record_request(info: Info) {
+    let route = route_label(info.path());
+    let method = info.method().as_str();
+    let status = info.status();
+    HTTP_REQUESTS
+        .with_label_values(&[route.as_str(), method, status.as_str()])
+        .inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[route.as_str(), method])
+        .observe(info.elapsed().as_secs_f64());
+    if status.is_server_error() {
+        AWS_ERRORS.with_label_values(&[route.as_str()]).inc();
+    }
+}
+
+pub fn record_background_task(task: &str, success: bool) {
+    let result = if success { "success" } else { "failure" };
+    BACKGROUND_TASK_RUNS
+        .with_label_values(&[task, result])
+        .inc();
+}
+
+fn record_host_metrics(host: &HostMetrics) {
+    fn int(value: u64) -> i64 {
+        i64::try_from(value).unwrap_or(i64::MAX)
+    }
+    for (period, value) in [
+        ("1m", host.load_average.one),
+        ("5m", host.load_average.five),
+        ("15m", host.load_average.fifteen),
+    ] {
+        HOST_LOAD_AVERAGE.with_label_values(&[period]).set(value);
+    }
+    HOST_CPU_USAGE.set(host.cpu_usage.into());
+    for (kind, value) in [
+        ("total", host.memory.total),
+        ("used", host.memory.used),
+        ("available", host.memory.available),
+        ("swap_total", host.memory.swap_total),
+        ("swap_used", host.memory.swap_used),
+    ] {
+        HOST_MEMORY.with_label_values(&[kind]).set(int(value));
+    }
+    for disk in &host.disks {
+        for (kind, value) in [
+            ("total", disk.total_space),
+            ("available", disk.available_space),
+        ] {
+            HOST_DISK
+                .with_label_values(&[disk.mount_point.as_str(), kind])
+                .set(int(value));
+        }
+    }
+    for network in &host.networks {
+        for (counter, value) in [
+            ("received_bytes", network.received_bytes),
+            ("transmitted_bytes", network.transmitted_bytes),
+            ("received_packets", network.received_packets),
+            ("transmitted_packets", network.transmitted_packets),
+            ("receive_errors", network.receive_errors),
+            ("transmit_errors", network.transmit_errors),
+        ] {
+            HOST_NETWORK
+                .with_label_values(&[network.interface.as_str(), counter])
+                .set(int(value));
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if encoding fails
+pub async fn get_metrics(host: &HostMetrics) -> Result<String, Error> {
+    record_host_metrics(host);
+    if let Some(updated) = *INSTANCE_LIST_UPDATED.read().await {
+        let age = OffsetDateTime::now_utc() - updated;
+        INSTANCE_LIST_AGE.set(age.as_seconds_f64());
+    }
+    let retries = retry_metrics();
+    for (outcome, count) in [
+        ("retry", retries.attempts),
+        ("throttled", retries.throttled),
+        ("exhausted", retries.exhausted),
+        ("permanent", retries.permanent),
+    ] {
+        AWS_RETRIES
+            .with_label_values(&[outcome])
+            .set(i64::try_from(count).unwrap_or(i64::MAX));
+    }
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .map_err(AnyhowError::from)?;
+    String::from_utf8(buffer).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::route_label;
+
+    #[test]
+    fn test_route_label() {
+        assert_eq!(route_label("/aws/index.html"), "/aws/index.html");
+        assert_eq!(route_label("/aws/crontab_logs/root"), "/aws/crontab_logs");
+        assert_eq!(route_label("/"), "/");
+    }
+}
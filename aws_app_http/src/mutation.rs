@@ -0,0 +1,320 @@
+use rweb::{
+    filters::header,
+    http::StatusCode,
+    openapi::{
+        ComponentDescriptor, ComponentOrInlineSchema, Entity, MediaType, Response, ResponseEntity,
+        Responses,
+    },
+    Filter, Rejection, Reply, Schema,
+};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::borrow::Cow;
+
+use crate::request_id::current_request_id;
+
+#[derive(Serialize, Deserialize, Schema, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MutationStatus {
+    #[serde(rename = "finished")]
+    Finished,
+    #[serde(rename = "created")]
+    Created,
+    #[serde(rename = "updated")]
+    Updated,
+    #[serde(rename = "deleted")]
+    Deleted,
+    /// Runs on as a background task
+    #[serde(rename = "started")]
+    Started,
+    #[serde(rename = "not_found")]
+    NotFound,
+}
+
+impl MutationStatus {
+    pub const ALL: [Self; 6] = [
+        Self::Finished,
+        Self::Created,
+        Self::Updated,
+        Self::Deleted,
+        Self::Started,
+        Self::NotFound,
+    ];
+
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Finished => "finished",
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Deleted => "deleted",
+            Self::Started => "started",
+            Self::NotFound => "not_found",
+        }
+    }
+
+    /// `NO_CONTENT` can't carry a json body
+    fn json_status(self) -> StatusCode {
+        match self {
+            Self::Created => StatusCode::CREATED,
+            Self::Started => StatusCode::ACCEPTED,
+            Self::Finished | Self::Updated | Self::Deleted => StatusCode::OK,
+            Self::NotFound => StatusCode::NOT_FOUND,
+        }
+    }
+
+    fn html_status(self) -> StatusCode {
+        match self {
+            Self::Finished | Self::Created | Self::Started => StatusCode::CREATED,
+            Self::Updated => StatusCode::OK,
+            Self::Deleted | Self::NotFound => StatusCode::NO_CONTENT,
+        }
+    }
+}
+
+/// Machine readable outcome of a mutating request
+#[derive(Serialize, Deserialize, Schema, Clone, Debug, PartialEq)]
+pub struct MutationResult {
+    #[schema(description = "Operation", example = r#""delete_volume""#)]
+    pub operation: StackString,
+    #[schema(description = "Resource ID", example = r#""vol-0123456789abcdef0""#)]
+    pub resource_id: StackString,
+    #[schema(description = "Status", example = r#""deleted""#)]
+    pub status: MutationStatus,
+    #[schema(description = "Message", example = r#""Deleted""#)]
+    pub message: StackString,
+    #[schema(
+        description = "Request ID",
+        example = r#""9b2f6c1e-2f0a-4d7e-8a51-0c3d9e6f4b21""#
+    )]
+    pub request_id: Option<StackString>,
+}
+
+/// Reply of a mutating route: a `MutationResult` as json when the request
+/// prefers `application/json`, otherwise the plain message with the status
+/// the route has always used so browser form posts are unchanged
+pub struct MutationResponse {
+    result: MutationResult,
+    json: bool,
+    html_status: StatusCode,
+}
+
+impl MutationResponse {
+    #[must_use]
+    pub fn new(
+        json: bool,
+        operation: &str,
+        resource_id: impl Into<StackString>,
+        status: MutationStatus,
+        message: impl Into<StackString>,
+    ) -> Self {
+        Self {
+            result: MutationResult {
+                operation: operation.into(),
+                resource_id: resource_id.into(),
+                status,
+                message: message.into(),
+                request_id: current_request_id(),
+            },
+            json,
+            html_status: status.html_status(),
+        }
+    }
+
+    /// Html status of routes which don't use the default of their status,
+    /// has to be one of the html codes in `response_codes` to be documented
+    #[must_use]
+    pub fn html_status(mut self, html_status: StatusCode) -> Self {
+        debug_assert!(
+            response_codes().contains(&(html_status, "text/html")),
+            "{html_status} not in response_codes"
+        );
+        self.html_status = html_status;
+        self
+    }
+}
+
+impl Reply for MutationResponse {
+    fn into_response(self) -> rweb::reply::Response {
+        if self.json {
+            let status = self.result.status.json_status();
+            rweb::reply::with_status(rweb::reply::json(&self.result), status).into_response()
+        } else {
+            let body = rweb::reply::html(self.result.message.to_string());
+            rweb::reply::with_status(body, self.html_status).into_response()
+        }
+    }
+}
+
+impl Entity for MutationResponse {
+    fn type_name() -> Cow<'static, str> {
+        MutationResult::type_name()
+    }
+    fn describe(comp_d: &mut ComponentDescriptor) -> ComponentOrInlineSchema {
+        MutationResult::describe(comp_d)
+    }
+}
+
+impl ResponseEntity for MutationResponse {
+    fn describe_responses(comp_d: &mut ComponentDescriptor) -> Responses {
+        let mut map = Responses::new();
+        for (code, content_type) in response_codes() {
+            let response = map
+                .entry(Cow::Owned(code.as_str().into()))
+                .or_insert_with(|| Response {
+                    description: Cow::Borrowed(
+                        code.canonical_reason().unwrap_or("Mutation Result"),
+                    ),
+                    ..Response::default()
+                });
+            // NO_CONTENT has no body
+            if code == StatusCode::NO_CONTENT {
+                continue;
+            }
+            let schema = if content_type == "application/json" {
+                MutationResult::describe(comp_d)
+            } else {
+                String::describe(comp_d)
+            };
+            response.content.insert(
+                Cow::Borrowed(content_type),
+                MediaType {
+                    schema: Some(schema),
+                    ..MediaType::default()
+                },
+            );
+        }
+        map
+    }
+}
+
+/// Status codes and content types a `MutationResponse` can be sent with,
+/// json when requested with `Accept: application/json`, html otherwise.
+/// Html replies keep the status each route has always answered with:
+/// 201 for creates, started and finished actions, 200 for updates, 204 for
+/// deletes, and `MutationResponse::html_status` where a route differs
+fn response_codes() -> Vec<(StatusCode, &'static str)> {
+    let mut codes: Vec<_> = MutationStatus::ALL
+        .iter()
+        .flat_map(|status| {
+            [
+                (status.json_status(), "application/json"),
+                (status.html_status(), "text/html"),
+            ]
+        })
+        .collect();
+    codes.sort_by_key(|(code, content_type)| (code.as_u16(), *content_type));
+    codes.dedup();
+    codes
+}
+
+/// Whether an `Accept` header ranks `application/json` above `text/html`,
+/// wildcards count towards html so requests not asking for json get html
+#[must_use]
+pub fn prefers_json(accept: &str) -> bool {
+    let mut json = 0.0;
+    let mut html = 0.0;
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let media_type = params.next().unwrap_or("").trim().to_lowercase();
+        let quality = params
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            "application/json" => json = quality.max(json),
+            "text/html" | "text/*" | "*/*" => html = quality.max(html),
+            _ => {}
+        }
+    }
+    json > html
+}
+
+/// Whether the response should be json, see `prefers_json`
+#[must_use]
+pub fn accepts_json() -> impl Filter<Extract = (bool,), Error = Rejection> + Copy {
+    header::optional::<String>("accept")
+        .map(|accept: Option<String>| accept.map_or(false, |accept| prefers_json(&accept)))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use rweb::{http::StatusCode, Reply};
+
+    use crate::mutation::{prefers_json, response_codes, MutationResponse, MutationStatus};
+
+    #[test]
+    fn test_prefers_json() {
+        assert!(prefers_json("application/json"));
+        assert!(prefers_json("application/json, text/plain, */*;q=0.1"));
+        assert!(!prefers_json("*/*"));
+        assert!(!prefers_json(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+        ));
+        assert!(!prefers_json("text/html, application/json"));
+        assert!(prefers_json("text/html;q=0.5, application/json"));
+        assert!(!prefers_json("application/json;q=0"));
+    }
+
+    #[test]
+    fn test_response_codes() {
+        let codes: Vec<_> = response_codes()
+            .into_iter()
+            .map(|(code, content_type)| (code.as_u16(), content_type))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                (200, "application/json"),
+                (200, "text/html"),
+                (201, "application/json"),
+                (201, "text/html"),
+                (202, "application/json"),
+                (204, "text/html"),
+                (404, "application/json"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mutation_response() -> Result<(), Error> {
+        let response = MutationResponse::new(
+            true,
+            "delete_volume",
+            "vol-1234",
+            MutationStatus::Deleted,
+            "Deleted",
+        )
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let result: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(result["operation"], "delete_volume");
+        assert_eq!(result["resource_id"], "vol-1234");
+        assert_eq!(result["status"], "deleted");
+        assert_eq!(result["message"], "Deleted");
+
+        let response = MutationResponse::new(
+            false,
+            "delete_volume",
+            "vol-1234",
+            MutationStatus::Deleted,
+            "Deleted",
+        )
+        .into_response();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = MutationResponse::new(
+            false,
+            "modify_volume",
+            "vol-1234",
+            MutationStatus::Finished,
+            "Finished",
+        )
+        .into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        assert_eq!(&body[..], b"Finished");
+        Ok(())
+    }
+}
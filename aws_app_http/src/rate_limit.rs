@@ -0,0 +1,125 @@
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rweb::{filters::cookie, http::Method, Filter, Rejection};
+use stack_string::{format_sstr, StackString};
+use std::{collections::HashMap, time::Instant};
+
+use crate::{csrf::is_mutating, errors::ServiceError as Error, logged_user::LoggedUser};
+
+/// Token bucket holding up to `capacity` requests, refilled continuously at
+/// `per_minute` requests a minute
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, per_minute: u32, now: Instant) -> Self {
+        Self {
+            capacity: f64::from(capacity),
+            per_second: f64::from(per_minute) / 60.0,
+            tokens: f64::from(capacity),
+            updated: now,
+        }
+    }
+
+    /// Take one token, or return the whole seconds until one is available
+    fn take(&mut self, now: Instant) -> Result<(), u64> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - self.tokens) / self.per_second;
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            Err(wait.ceil().max(1.0) as u64)
+        }
+    }
+}
+
+static BUCKETS: Lazy<Mutex<HashMap<StackString, TokenBucket>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// # Errors
+/// Returns `Error::TooManyRequests` with the seconds to wait once `user` has
+/// used up their bucket
+pub fn check_rate_limit(user: &str, capacity: u32, per_minute: u32) -> Result<(), Error> {
+    let now = Instant::now();
+    let mut buckets = BUCKETS.lock();
+    buckets
+        .entry(user.into())
+        .or_insert_with(|| TokenBucket::new(capacity, per_minute, now))
+        .take(now)
+        .map_err(|retry_after| {
+            Error::TooManyRequests(
+                format_sstr!("rate limit of {per_minute} changes a minute exceeded"),
+                retry_after,
+            )
+        })
+}
+
+/// Rate limits POST, PUT, PATCH and DELETE requests per logged in user.
+/// Requests without a valid session are passed through to be rejected by
+/// `LoggedUser::filter`.
+#[must_use]
+pub fn rate_limit_filter(
+    capacity: u32,
+    per_minute: u32,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    rweb::method()
+        .and(cookie::optional::<String>("jwt"))
+        .and_then(move |method: Method, jwt: Option<String>| async move {
+            if !is_mutating(&method) {
+                return Ok(());
+            }
+            let user: Option<LoggedUser> = jwt.and_then(|jwt| jwt.parse().ok());
+            if let Some(user) = user {
+                check_rate_limit(&user.email, capacity, per_minute)
+                    .map_err(rweb::reject::custom)?;
+            }
+            Ok::<_, Rejection>(())
+        })
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        errors::ServiceError as Error,
+        rate_limit::{check_rate_limit, TokenBucket},
+    };
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2, 6, start);
+        assert!(bucket.take(start).is_ok());
+        assert!(bucket.take(start).is_ok());
+        // one token every 10 seconds
+        assert_eq!(bucket.take(start), Err(10));
+        assert_eq!(bucket.take(start + Duration::from_secs(4)), Err(6));
+        assert!(bucket.take(start + Duration::from_secs(10)).is_ok());
+        // refilling stops at capacity
+        let later = start + Duration::from_secs(3600);
+        assert!(bucket.take(later).is_ok());
+        assert!(bucket.take(later).is_ok());
+        assert!(bucket.take(later).is_err());
+    }
+
+    #[test]
+    fn test_check_rate_limit() {
+        assert!(check_rate_limit("limited@test", 1, 1).is_ok());
+        match check_rate_limit("limited@test", 1, 1) {
+            Err(Error::TooManyRequests(_, retry_after)) => assert!(retry_after <= 60),
+            other => panic!("expected rate limit, got {other:?}"),
+        }
+        assert!(check_rate_limit("other@test", 1, 1).is_ok());
+    }
+}
@@ -0,0 +1,98 @@
+use hyper::{header::HeaderValue, service::Service, Body, Request, Response};
+use stack_string::{format_sstr, StackString};
+use std::convert::Infallible;
+use tokio::time::Instant;
+use tracing::{field::Empty, info, info_span, Instrument};
+use uuid::Uuid;
+
+use crate::metrics::route_label;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    pub(crate) static REQUEST_ID: StackString;
+}
+
+/// Id of the request being handled, `None` outside of `traced_request`
+#[must_use]
+pub fn current_request_id() -> Option<StackString> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// An incoming `x-request-id` (e.g. set by a proxy) is kept when it looks like
+/// an id, otherwise a new one is made
+fn request_id(req: &Request<Body>) -> StackString {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| {
+            !v.is_empty()
+                && v.len() <= 64
+                && v.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        .map_or_else(|| format_sstr!("{}", Uuid::new_v4()), Into::into)
+}
+
+/// Handle `req` inside a `request` span carrying the request id, method and
+/// route (`LoggedUser::filter` fills in the user), the id is returned in the
+/// `x-request-id` header and in error bodies
+/// # Errors
+/// Never returns an error
+pub async fn traced_request<S>(
+    mut service: S,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible>,
+{
+    let id = request_id(&req);
+    let span = info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        route = %route_label(req.uri().path()),
+        user = Empty,
+    );
+    let start = Instant::now();
+    let mut response = REQUEST_ID
+        .scope(id.clone(), service.call(req))
+        .instrument(span.clone())
+        .await?;
+    info!(
+        parent: &span,
+        status = response.status().as_u16(),
+        elapsed_ms = start.elapsed().as_millis() as u64,
+        "finished"
+    );
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Request};
+
+    use crate::request_id::{request_id, REQUEST_ID_HEADER};
+
+    #[test]
+    fn test_request_id() {
+        let req = Request::builder()
+            .header(REQUEST_ID_HEADER, "proxy-1234")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(request_id(&req), "proxy-1234");
+
+        let req = Request::builder()
+            .header(REQUEST_ID_HEADER, "<script>")
+            .body(Body::empty())
+            .unwrap();
+        let id = request_id(&req);
+        assert_ne!(id, "<script>");
+        assert_eq!(id.len(), 36);
+
+        let req = Request::builder().body(Body::empty()).unwrap();
+        assert_ne!(request_id(&req), request_id(&req));
+    }
+}
@@ -7,7 +7,11 @@ use stack_string::{format_sstr, StackString};
 use std::fmt::Display;
 use tokio::try_join;
 
-use aws_app_lib::{aws_app_interface::AwsAppInterface, ec2_instance::AmiInfo};
+use aws_app_lib::{
+    aws_app_interface::AwsAppInterface,
+    decommission::DecommissionOptions,
+    ec2_instance::{AmiInfo, UbuntuImageQuery},
+};
 
 use crate::errors::ServiceError as Error;
 
@@ -28,6 +32,19 @@ async fn get_latest_ubuntu_ami(
         .map_err(Into::into)
 }
 
+#[cached(
+    ty = "SizedCache<StackString, Vec<AmiInfo>>",
+    create = "{ SizedCache::with_size(10) }",
+    convert = r#"{ format_sstr!("{}-{}-{}", query.release, query.arch, query.minimal) }"#,
+    result = true
+)]
+pub async fn get_ubuntu_images(
+    app: &AwsAppInterface,
+    query: &UbuntuImageQuery,
+) -> Result<Vec<AmiInfo>, Error> {
+    app.ec2.get_ubuntu_images(query).await.map_err(Into::into)
+}
+
 pub fn print_tags(tags: impl IntoIterator<Item = (impl Display, impl Display)>) -> StackString {
     tags.into_iter()
         .map(|(k, v)| format_sstr!("{k} = {v}"))
@@ -39,6 +56,36 @@ pub fn print_tags(tags: impl IntoIterator<Item = (impl Display, impl Display)>)
 pub struct TerminateRequest {
     #[schema(description = "Instance ID or Name Tag")]
     pub instance: StackString,
+    #[schema(description = "Override Terminate Protection (admin only)")]
+    pub force: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct DecommissionRequest {
+    #[schema(description = "Instance ID or Name Tag")]
+    pub instance: StackString,
+    #[schema(description = "Override Terminate Protection (admin only)")]
+    pub force: Option<bool>,
+    #[schema(description = "Delete Non-Root Volumes")]
+    pub delete_volumes: Option<bool>,
+    #[schema(description = "Delete Route53 Records Pointing at the Instance")]
+    pub delete_dns_records: Option<bool>,
+    #[schema(description = "Cancel Spot Requests of the Instance")]
+    pub cancel_spot_requests: Option<bool>,
+    #[schema(description = "Snapshot the Root Volume Before Terminating")]
+    pub final_snapshot: Option<bool>,
+}
+
+impl DecommissionRequest {
+    #[must_use]
+    pub fn options(&self) -> DecommissionOptions {
+        DecommissionOptions {
+            delete_volumes: self.delete_volumes.unwrap_or(false),
+            delete_dns_records: self.delete_dns_records.unwrap_or(false),
+            cancel_spot_requests: self.cancel_spot_requests.unwrap_or(false),
+            final_snapshot: self.final_snapshot.unwrap_or(false),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Schema)]
@@ -59,6 +106,8 @@ pub struct DeleteImageRequest {
 pub struct DeleteVolumeRequest {
     #[schema(description = "Volume ID")]
     pub volid: StackString,
+    #[schema(description = "Override Delete Protection (admin only)")]
+    pub force: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Schema)]
@@ -83,6 +132,26 @@ pub struct CreateSnapshotRequest {
     pub name: Option<StackString>,
 }
 
+#[derive(Serialize, Deserialize, Schema)]
+pub struct CopySnapshotRequest {
+    #[schema(description = "Snapshot ID")]
+    pub snapid: StackString,
+    #[schema(description = "Destination Region (defaults to the current region)")]
+    pub region: Option<StackString>,
+    #[schema(description = "Encrypt the Copy")]
+    pub encrypted: Option<bool>,
+    #[schema(description = "KMS Key ID (defaults to the EBS default key)")]
+    pub kms_key_id: Option<StackString>,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct ReencryptVolumeRequest {
+    #[schema(description = "Volume ID")]
+    pub volid: StackString,
+    #[schema(description = "KMS Key ID (defaults to the EBS default key)")]
+    pub kms_key_id: Option<StackString>,
+}
+
 #[derive(Serialize, Deserialize, Schema)]
 pub struct TagItemRequest {
     #[schema(description = "Resource ID")]
@@ -91,6 +160,28 @@ pub struct TagItemRequest {
     pub tag: StackString,
 }
 
+#[derive(Serialize, Deserialize, Schema)]
+pub struct BatchRequest {
+    #[schema(description = "Resource IDs")]
+    pub ids: Vec<StackString>,
+    #[schema(description = "Override Protection (admin only)")]
+    pub force: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct BatchTagRequest {
+    #[schema(description = "Resource IDs")]
+    pub ids: Vec<StackString>,
+    #[schema(description = "Name Tag")]
+    pub tag: StackString,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct EcrHistoryRequest {
+    #[schema(description = "ECR Repository Name")]
+    pub repo: StackString,
+}
+
 #[derive(Serialize, Deserialize, Schema)]
 pub struct DeleteEcrImageRequest {
     #[schema(description = "ECR Repository Name")]
@@ -99,6 +190,36 @@ pub struct DeleteEcrImageRequest {
     pub imageid: StackString,
 }
 
+#[derive(Serialize, Deserialize, Schema)]
+pub struct SqsQueueRequest {
+    #[schema(description = "SQS Queue URL")]
+    pub queue_url: StackString,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct SqsPeekRequest {
+    #[schema(description = "SQS Queue URL")]
+    pub queue_url: StackString,
+    #[schema(description = "Peek Even Though the Queue has a Redrive Policy")]
+    pub force: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct BackupAssignRequest {
+    #[schema(description = "Backup Plan ID")]
+    pub plan_id: StackString,
+    #[schema(description = "Comma Separated Volume IDs")]
+    pub volumes: StackString,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct LambdaInvokeRequest {
+    #[schema(description = "Lambda Function Name")]
+    pub function_name: StackString,
+    #[schema(description = "JSON Payload")]
+    pub payload: StackString,
+}
+
 #[derive(Serialize, Deserialize, Schema)]
 pub struct StatusRequest {
     #[schema(description = "Instance ID or Name Tag")]
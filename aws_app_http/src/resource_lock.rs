@@ -0,0 +1,56 @@
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use stack_string::{format_sstr, StackString};
+use std::collections::HashSet;
+
+use crate::errors::ServiceError as Error;
+
+/// Seconds a client is told to wait before retrying a busy resource
+const RESOURCE_RETRY_AFTER: u64 = 5;
+
+static LOCKED_RESOURCES: Lazy<Mutex<HashSet<StackString>>> =
+    Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Held while a request changes a resource, released on drop
+#[derive(Debug)]
+pub struct ResourceGuard(StackString);
+
+impl Drop for ResourceGuard {
+    fn drop(&mut self) {
+        LOCKED_RESOURCES.lock().remove(&self.0);
+    }
+}
+
+/// Claim `resource` (an instance, volume, snapshot or image id) for the
+/// duration of a change, so that two requests don't race the same aws calls
+/// # Errors
+/// Returns `Error::ResourceBusy` if another request holds `resource`
+pub fn lock_resource(resource: &str) -> Result<ResourceGuard, Error> {
+    if LOCKED_RESOURCES.lock().insert(resource.into()) {
+        Ok(ResourceGuard(resource.into()))
+    } else {
+        Err(Error::ResourceBusy(
+            format_sstr!("{resource} is being changed by another request"),
+            RESOURCE_RETRY_AFTER,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{errors::ServiceError as Error, resource_lock::lock_resource};
+
+    #[test]
+    fn test_lock_resource() -> Result<(), Error> {
+        let guard = lock_resource("vol-0123456789abcdef0")?;
+        assert!(matches!(
+            lock_resource("vol-0123456789abcdef0"),
+            Err(Error::ResourceBusy(..))
+        ));
+        let other = lock_resource("vol-0fedcba9876543210")?;
+        drop(guard);
+        let _guard = lock_resource("vol-0123456789abcdef0")?;
+        drop(other);
+        Ok(())
+    }
+}
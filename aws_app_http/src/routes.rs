@@ -1,44 +1,99 @@
-use anyhow::format_err;
-use futures::TryStreamExt;
+use anyhow::{format_err, Error as AnyhowError};
+use futures::{stream, Future, StreamExt};
 use maplit::hashmap;
-use rweb::{delete, get, patch, post, Json, Query, Rejection, Schema};
+use rweb::{delete, get, http::StatusCode, patch, post, Json, Query, Rejection, Schema};
 use rweb_helper::{
-    html_response::HtmlResponse as HtmlBase, json_response::JsonResponse as JsonBase, RwebResponse,
-    UuidWrapper,
+    html_response::HtmlResponse as HtmlBase, json_response::JsonResponse as JsonBase, DateTimeType,
+    RwebResponse, UuidWrapper,
 };
 use serde::{Deserialize, Serialize};
 use stack_string::{format_sstr, StackString};
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    path::Path,
+    sync::Arc,
+};
+use time::OffsetDateTime;
 use tokio::{
     fs::{read_to_string, remove_file, File},
     io::AsyncWriteExt,
-    task::spawn,
+    join,
     time::{sleep, Duration},
+    try_join,
 };
 
 use aws_app_lib::{
-    ec2_instance::{AmiInfo, SpotRequest},
+    aws_app_interface::AwsAppInterface,
+    config::Config,
+    crontab::{crontab_diff, install_user_crontab, read_user_crontab, validate_crontab},
+    docker_instance::ContainerAction,
+    dr_policy::DrPolicy,
+    ec2_instance::{
+        validate_public_key, AmiInfo, DataVolume, InstanceTenancy, SpotRequest, UbuntuImageQuery,
+        VolumeSpec,
+    },
+    email_forward::{matching_rules, validate_rule},
+    email_reply::send_reply,
     inbound_email::InboundEmail,
-    models::{InboundEmailDB, InstanceFamily, InstanceList},
+    instance_filter::{InstanceFilter, InstanceSortKey},
+    instance_metadata::MetadataClient,
+    inventory::parse_since,
+    models::{EmailForwardRule, InstanceFamily, PriceHistory},
+    price_alert::PriceAlert,
+    price_forecast::SpotForecast,
+    pricing_instance::UpdateSource,
+    resource_type::ResourceType,
+    route53_instance::{HealthCheckTarget, RecordRouting, RoutingRecord},
     s3_instance::S3Instance,
+    schema::latest_migration,
+    ses_client::SesInstance,
+    ssh_instance::HostKeyMismatch,
+    storage::{
+        AuthorizedUsersRepo, DrPolicyRepo, EmailAttachmentRepo, EmailForwardRuleRepo,
+        InboundEmailRepo, InstanceFamilyRepo, InstancePricingRepo, InstanceReplacementRepo,
+        LaunchHistoryRepo, PriceAlertRepo, SchemaRepo, SentEmailRepo, UpdateStatusRepo,
+        UserPreferencesRepo, WebhookRepo,
+    },
+    sts_instance::TemporaryCredentials,
+    systemd_instance::{health_probes, restart_impact},
+    tag_search::TagQuery,
+    waste::WastePolicy,
+    webhook::{Webhook, WebhookEvent},
 };
 
 use super::{
     app::AppState,
+    csrf::csrf_token,
     elements::{
-        build_spot_request_body, edit_script_body, get_frontpage, get_index, inbound_email_body,
-        instance_family_body, instance_status_body, instance_types_body, novnc_start_body,
-        novnc_status_body, prices_body, textarea_body, textarea_fixed_size_body,
+        bucket_summary_body, build_spot_request_body, costs_by_tag_body, crontab_diff_body,
+        decommission_plan_body, dr_policies_body, ecr_history_body, edit_crontab_body,
+        edit_script_body, email_rules_body, get_cached_frontpage, get_dashboard, get_index,
+        get_preferences_page, host_body, iam_report_body, inbound_email_body, instance_family_body,
+        instance_list_body, instance_metadata_body, instance_status_body, instance_types_body,
+        inventory_changes_body, lambda_invoke_body, launch_analytics_body, launch_status_body,
+        novnc_start_body, novnc_status_body, price_alerts_body, prices_body, quotas_body,
+        replacements_body, secrets_body, ses_identities_body, systemd_dependencies_body,
+        systemd_restart_body, systemd_restart_preview_body, tag_search_body, tasks_body,
+        terraform_drift_body, textarea_body, textarea_fixed_size_body, ubuntu_images_body,
+        waste_body, webhooks_body,
     },
     errors::ServiceError as Error,
+    idempotency::{idempotency_key, idempotent},
     ipv4addr_wrapper::Ipv4AddrWrapper,
     logged_user::LoggedUser,
+    mutation::{accepts_json, MutationResponse, MutationStatus},
     requests::{
-        CommandRequest, CreateImageRequest, CreateSnapshotRequest, DeleteEcrImageRequest,
-        DeleteImageRequest, DeleteSnapshotRequest, DeleteVolumeRequest, ModifyVolumeRequest,
-        StatusRequest, TagItemRequest, TerminateRequest,
+        get_ubuntu_images, BackupAssignRequest, BatchRequest, BatchTagRequest, CommandRequest,
+        CopySnapshotRequest, CreateImageRequest, CreateSnapshotRequest, DecommissionRequest,
+        DeleteEcrImageRequest, DeleteImageRequest, DeleteSnapshotRequest, DeleteVolumeRequest,
+        EcrHistoryRequest, LambdaInvokeRequest, ModifyVolumeRequest, ReencryptVolumeRequest,
+        SqsPeekRequest, SqsQueueRequest, StatusRequest, TagItemRequest, TerminateRequest,
     },
-    IamAccessKeyWrapper, IamUserWrapper, ResourceTypeWrapper,
+    resource_lock::lock_resource,
+    table_preferences::{table_columns, HiddenColumns},
+    theme::Theme,
+    ResourceTypeWrapper,
 };
 
 pub type WarpResult<T> = Result<T, Rejection>;
@@ -51,10 +106,35 @@ struct AwsIndexResponse(HtmlBase<StackString, Error>);
 #[get("/aws/index.html")]
 #[openapi(description = "AWS App Main Page")]
 pub async fn sync_frontpage(
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] data: AppState,
 ) -> WarpResult<AwsIndexResponse> {
-    let body = get_index(&data.aws).await?;
+    let theme = data
+        .aws()
+        .storage
+        .get_theme(&user.email)
+        .await
+        .map_err(Into::<Error>::into)?
+        .and_then(|t| t.parse().ok())
+        .unwrap_or_default();
+    let preferences = data
+        .aws()
+        .storage
+        .get_user_preferences(&user.email)
+        .await
+        .map_err(Into::<Error>::into)?;
+    if let Some(region) = &preferences.default_region {
+        if data.aws().ec2.region() != region.as_str() {
+            data.set_region(region).await.map_err(Into::<Error>::into)?;
+        }
+    }
+    let body = get_index(
+        &data.aws(),
+        theme,
+        csrf_token(user.session.into()),
+        &preferences,
+    )
+    .await?;
     Ok(HtmlBase::new(body).into())
 }
 
@@ -62,6 +142,8 @@ pub async fn sync_frontpage(
 pub struct ResourceRequest {
     #[schema(description = "Resource Type")]
     resource: ResourceTypeWrapper,
+    #[schema(description = "Bypass Resource Cache")]
+    refresh: Option<bool>,
 }
 
 #[derive(RwebResponse)]
@@ -71,53 +153,261 @@ struct AwsListResponse(HtmlBase<StackString, Error>);
 #[get("/aws/list")]
 #[openapi(description = "List AWS Resources")]
 pub async fn list(
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] data: AppState,
     query: Query<ResourceRequest>,
 ) -> WarpResult<AwsListResponse> {
     let query = query.into_inner();
-    let body = get_frontpage(query.resource.into(), &data.aws).await?;
+    let refresh = query.refresh.unwrap_or(false);
+    let preferences = data
+        .aws()
+        .storage
+        .get_user_preferences(&user.email)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body =
+        get_cached_frontpage(query.resource.into(), &data.aws(), refresh, &preferences).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct InstanceListRequest {
+    #[schema(description = "Instance State")]
+    pub state: Option<StackString>,
+    #[schema(description = "Substring of the Name Tag")]
+    pub name: Option<StackString>,
+    #[schema(description = "Instance Type")]
+    pub instance_type: Option<StackString>,
+    #[schema(description = "Availability Zone")]
+    pub availability_zone: Option<StackString>,
+    #[schema(
+        description = "Sort Key (state, launch_time, name, instance_type, availability_zone)"
+    )]
+    pub sort: Option<StackString>,
+    #[schema(description = "Sort Descending")]
+    pub descending: Option<bool>,
+}
+
+impl InstanceListRequest {
+    fn into_filter(self) -> Result<InstanceFilter, Error> {
+        let sort = match self.sort.as_deref().filter(|s| !s.is_empty()) {
+            Some(sort) => sort
+                .parse::<InstanceSortKey>()
+                .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?,
+            None => InstanceSortKey::default(),
+        };
+        Ok(InstanceFilter {
+            state: self.state,
+            name: self.name,
+            instance_type: self.instance_type,
+            availability_zone: self.availability_zone,
+            sort,
+            descending: self.descending.unwrap_or(false),
+        })
+    }
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Filtered Instance List", content = "html")]
+struct InstanceListResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/instance_list")]
+#[openapi(description = "List Instances Filtered and Sorted Server Side")]
+pub async fn instance_list(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+    query: Query<InstanceListRequest>,
+) -> WarpResult<InstanceListResponse> {
+    let filter = query.into_inner().into_filter()?;
+    let aws = data.aws();
+    let preferences = aws
+        .storage
+        .get_user_preferences(&user.email)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let hidden = HiddenColumns::new(
+        ResourceType::Instances,
+        &preferences.hidden_columns(ResourceType::Instances.to_str()),
+    );
+    let instances = aws
+        .get_filtered_instances(&filter)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let protected = aws
+        .get_protected_resources()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = instance_list_body(instances, protected, filter, hidden)?.into();
     Ok(HtmlBase::new(body).into())
 }
 
+#[derive(Serialize, Deserialize, Schema)]
+pub struct DashboardRequest {
+    #[schema(description = "Bypass Resource Cache")]
+    refresh: Option<bool>,
+}
+
 #[derive(RwebResponse)]
-#[response(description = "Deleted", content = "html", status = "NO_CONTENT")]
-struct DeletedResource(HtmlBase<&'static str, Error>);
+#[response(description = "Dashboard", content = "html")]
+struct AwsDashboardResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/dashboard")]
+#[openapi(description = "All AWS Resources")]
+pub async fn dashboard(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+    query: Query<DashboardRequest>,
+) -> WarpResult<AwsDashboardResponse> {
+    let refresh = query.into_inner().refresh.unwrap_or(false);
+    let preferences = data
+        .aws()
+        .storage
+        .get_user_preferences(&user.email)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = get_dashboard(&data.aws(), refresh, &preferences).await?;
+    Ok(HtmlBase::new(body).into())
+}
 
 #[delete("/aws/terminate")]
 #[openapi(description = "Terminate Ec2 Instance")]
 pub async fn terminate(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    #[filter = "idempotency_key"] key: Option<StackString>,
+    query: Query<TerminateRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    let _guard = lock_resource(&query.instance)?;
+    let aws = data.aws();
+    if !override_protection(&user, query.force, &aws.config)? {
+        let protected = aws
+            .find_protected_instances(&[&query.instance])
+            .await
+            .map_err(Into::<Error>::into)?;
+        refuse_protected(&protected)?;
+    }
+    let instance = query.instance.clone();
+    idempotent(&user, "terminate", key, || async {
+        aws.terminate(&[query.instance])
+            .await
+            .map_err(Into::<Error>::into)
+    })
+    .await?;
+    Ok(MutationResponse::new(
+        json,
+        "terminate",
+        instance,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Decommission Plan", content = "html")]
+struct DecommissionPlanResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/decommission")]
+#[openapi(description = "List What Decommissioning an Instance Removes")]
+pub async fn decommission_plan(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
     query: Query<TerminateRequest>,
-) -> WarpResult<DeletedResource> {
+) -> WarpResult<DecommissionPlanResponse> {
     let query = query.into_inner();
-    data.aws
-        .terminate(&[query.instance])
+    let plan = data
+        .aws()
+        .decommission_plan(&query.instance)
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new("Deleted").into())
+    let body = decommission_plan_body(plan)?.into();
+    Ok(HtmlBase::new(body).into())
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Image ID", content = "html", status = "CREATED")]
-struct CreateImageResponse(HtmlBase<String, Error>);
+#[response(description = "Decommissioned Instance", content = "html")]
+struct DecommissionResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/decommission")]
+#[openapi(description = "Terminate Ec2 Instance and Clean Up What Refers to It")]
+pub async fn decommission(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+    query: Query<DecommissionRequest>,
+) -> WarpResult<DecommissionResponse> {
+    let query = query.into_inner();
+    let _guard = lock_resource(&query.instance)?;
+    let aws = data.aws();
+    if !override_protection(&user, query.force, &aws.config)? {
+        let protected = aws
+            .find_protected_instances(&[&query.instance])
+            .await
+            .map_err(Into::<Error>::into)?;
+        refuse_protected(&protected)?;
+    }
+    let steps = aws
+        .decommission(&query.instance, query.options())
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = textarea_body(steps, "decommission-steps".into())?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+/// `force` skips the protected resource check, only admins may set it
+fn override_protection(
+    user: &LoggedUser,
+    force: Option<bool>,
+    config: &Config,
+) -> HttpResult<bool> {
+    if force != Some(true) {
+        return Ok(false);
+    }
+    if config.is_admin(&user.email) {
+        Ok(true)
+    } else {
+        Err(Error::Unauthorized)
+    }
+}
+
+fn refuse_protected(protected: &[StackString]) -> HttpResult<()> {
+    if protected.is_empty() {
+        Ok(())
+    } else {
+        let protected: Vec<_> = protected.iter().map(StackString::as_str).collect();
+        Err(Error::BadRequest(format_sstr!(
+            "{} protected, an admin must pass force=true",
+            protected.join(", ")
+        )))
+    }
+}
 
 #[post("/aws/create_image")]
 #[openapi(description = "Create EC2 AMI Image")]
 pub async fn create_image(
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    #[filter = "idempotency_key"] key: Option<StackString>,
     query: Query<CreateImageRequest>,
-) -> WarpResult<CreateImageResponse> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
-    let body: String = data
-        .aws
-        .create_image(query.inst_id, query.name)
-        .await
-        .map_err(Into::<Error>::into)?
-        .map_or_else(|| "failed to create ami".into(), Into::into);
-    Ok(HtmlBase::new(body).into())
+    let _guard = lock_resource(&query.inst_id)?;
+    let ami_id = idempotent(&user, "create_image", key, || async {
+        data.aws()
+            .create_image(query.inst_id, query.name)
+            .await
+            .map_err(Into::<Error>::into)?
+            .ok_or_else(|| Error::BadRequest("failed to create ami".into()))
+    })
+    .await?;
+    Ok(MutationResponse::new(
+        json,
+        "create_image",
+        ami_id.clone(),
+        MutationStatus::Created,
+        ami_id,
+    ))
 }
 
 #[delete("/aws/delete_image")]
@@ -125,48 +415,75 @@ pub async fn create_image(
 pub async fn delete_image(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<DeleteImageRequest>,
-) -> WarpResult<DeletedResource> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
-    data.aws
+    let _guard = lock_resource(&query.ami)?;
+    data.aws()
         .delete_image(&query.ami)
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new("Deleted").into())
+    Ok(MutationResponse::new(
+        json,
+        "delete_image",
+        query.ami,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
 }
 
 #[delete("/aws/delete_volume")]
 #[openapi(description = "Delete EC2 Volume")]
 pub async fn delete_volume(
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<DeleteVolumeRequest>,
-) -> WarpResult<DeletedResource> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
-    data.aws
-        .delete_ebs_volume(&query.volid)
+    let _guard = lock_resource(&query.volid)?;
+    let aws = data.aws();
+    if !override_protection(&user, query.force, &aws.config)? {
+        let protected = aws
+            .find_protected_volumes(&[&query.volid])
+            .await
+            .map_err(Into::<Error>::into)?;
+        refuse_protected(&protected)?;
+    }
+    aws.delete_ebs_volume(&query.volid)
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new("Finished").into())
+    Ok(MutationResponse::new(
+        json,
+        "delete_volume",
+        query.volid,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Finished", content = "html", status = "CREATED")]
-struct FinishedResource(HtmlBase<&'static str, Error>);
-
 #[patch("/aws/modify_volume")]
 #[openapi(description = "Modify EC2 Volume")]
 pub async fn modify_volume(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<ModifyVolumeRequest>,
-) -> WarpResult<FinishedResource> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
-    data.aws
+    let _guard = lock_resource(&query.volid)?;
+    data.aws()
         .modify_ebs_volume(&query.volid, query.size)
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new("Finished").into())
+    Ok(MutationResponse::new(
+        json,
+        "modify_volume",
+        query.volid,
+        MutationStatus::Finished,
+        "Finished",
+    ))
 }
 
 #[delete("/aws/delete_snapshot")]
@@ -174,36 +491,197 @@ pub async fn modify_volume(
 pub async fn delete_snapshot(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<DeleteSnapshotRequest>,
-) -> WarpResult<DeletedResource> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
-    data.aws
+    let _guard = lock_resource(&query.snapid)?;
+    data.aws()
         .delete_ebs_snapshot(&query.snapid)
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new("Deleted").into())
+    Ok(MutationResponse::new(
+        json,
+        "delete_snapshot",
+        query.snapid,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct ImportKeyPairRequest {
+    #[schema(description = "Key Name")]
+    pub key_name: StackString,
+    #[schema(description = "OpenSSH Public Key")]
+    pub public_key: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Imported Key Pair",
+    content = "html",
+    status = "CREATED"
+)]
+struct ImportKeyPairResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/import_key_pair")]
+#[openapi(description = "Import an OpenSSH Public Key as an EC2 Key Pair")]
+pub async fn import_key_pair(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    req: Json<ImportKeyPairRequest>,
+) -> WarpResult<ImportKeyPairResponse> {
+    let req = req.into_inner();
+    validate_public_key(&req.public_key).map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let fingerprint = data
+        .aws()
+        .import_key_pair(&req.key_name, &req.public_key)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(format_sstr!("Imported {} {fingerprint}", req.key_name)).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct DeleteKeyPairRequest {
+    #[schema(description = "Key Name")]
+    pub key_name: StackString,
+}
+
+#[delete("/aws/delete_key_pair")]
+#[openapi(description = "Delete EC2 Key Pair")]
+pub async fn delete_key_pair(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<DeleteKeyPairRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    data.aws()
+        .delete_key_pair(&query.key_name)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(MutationResponse::new(
+        json,
+        "delete_key_pair",
+        query.key_name,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
 }
 
 #[post("/aws/create_snapshot")]
 #[openapi(description = "Create EC2 Snapshot")]
 pub async fn create_snapshot(
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    #[filter = "idempotency_key"] key: Option<StackString>,
     query: Query<CreateSnapshotRequest>,
-) -> WarpResult<FinishedResource> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
+    let _guard = lock_resource(&query.volid)?;
 
     let tags = if let Some(name) = &query.name {
         hashmap! {"Name".into() => name.clone()}
     } else {
         HashMap::default()
     };
-    data.aws
-        .create_ebs_snapshot(query.volid.as_str(), &tags)
+    idempotent(&user, "create_snapshot", key, || async {
+        data.aws()
+            .create_ebs_snapshot(query.volid.as_str(), &tags)
+            .await
+            .map_err(Into::<Error>::into)
+    })
+    .await?;
+
+    Ok(MutationResponse::new(
+        json,
+        "create_snapshot",
+        query.volid,
+        MutationStatus::Finished,
+        "Finished",
+    ))
+}
+
+#[post("/aws/copy_snapshot")]
+#[openapi(description = "Copy EC2 Snapshot, optionally to another region")]
+pub async fn copy_snapshot(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    #[filter = "idempotency_key"] key: Option<StackString>,
+    query: Query<CopySnapshotRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    let _guard = lock_resource(&query.snapid)?;
+    let aws = data.aws();
+    let region = query
+        .region
+        .as_ref()
+        .map(StackString::as_str)
+        .filter(|r| !r.is_empty())
+        .unwrap_or_else(|| aws.ec2.region());
+    let kms_key_id = query
+        .kms_key_id
+        .as_ref()
+        .map(StackString::as_str)
+        .filter(|k| !k.is_empty());
+    idempotent(&user, "copy_snapshot", key, || async {
+        aws.copy_snapshot(
+            query.snapid.as_str(),
+            region,
+            query.encrypted.unwrap_or(false),
+            kms_key_id,
+        )
         .await
-        .map_err(Into::<Error>::into)?;
+        .map_err(Into::<Error>::into)
+    })
+    .await?;
 
-    Ok(HtmlBase::new("Finished").into())
+    Ok(MutationResponse::new(
+        json,
+        "copy_snapshot",
+        query.snapid,
+        MutationStatus::Finished,
+        "Finished",
+    ))
+}
+
+#[post("/aws/reencrypt_volume")]
+#[openapi(description = "Replace an Unencrypted EBS Volume with an Encrypted Copy")]
+pub async fn reencrypt_volume(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<ReencryptVolumeRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    let plan = data
+        .aws()
+        .reencrypt_plan(&query.volid, query.kms_key_id)
+        .await
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let volume_id = plan.volume_id.clone();
+    let aws = data.aws();
+    data.tasks.spawn("reencrypt_volume", 0, move || {
+        let aws = aws.clone();
+        let volume_id = plan.volume_id.clone();
+        let kms_key_id = plan.kms_key_id.clone();
+        async move {
+            let _guard = lock_resource(&volume_id)?;
+            aws.reencrypt_volume(&volume_id, kms_key_id)
+                .await
+                .map(|_| ())
+        }
+    });
+    Ok(MutationResponse::new(
+        json,
+        "reencrypt_volume",
+        volume_id,
+        MutationStatus::Started,
+        "Started, see Tasks",
+    ))
 }
 
 #[patch("/aws/tag_item")]
@@ -211,10 +689,18 @@ pub async fn create_snapshot(
 pub async fn tag_item(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<TagItemRequest>,
-) -> WarpResult<FinishedResource> {
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([
+        ResourceType::Instances,
+        ResourceType::Volume,
+        ResourceType::Snapshot,
+        ResourceType::Ami,
+    ]);
     let query = query.into_inner();
-    data.aws
+    let _guard = lock_resource(&query.id)?;
+    data.aws()
         .ec2
         .tag_ec2_instance(
             query.id.as_str(),
@@ -224,7 +710,13 @@ pub async fn tag_item(
         )
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new("Finished").into())
+    Ok(MutationResponse::new(
+        json,
+        "tag_item",
+        query.id,
+        MutationStatus::Finished,
+        "Finished",
+    ))
 }
 
 #[delete("/aws/delete_ecr_image")]
@@ -232,15 +724,23 @@ pub async fn tag_item(
 pub async fn delete_ecr_image(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<DeleteEcrImageRequest>,
-) -> WarpResult<DeletedResource> {
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([ResourceType::Ecr]);
     let query = query.into_inner();
-    data.aws
+    data.aws()
         .ecr
-        .delete_ecr_images(&query.reponame, &[query.imageid])
+        .delete_ecr_images(&query.reponame, &[query.imageid.clone()])
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new("Deleted").into())
+    Ok(MutationResponse::new(
+        json,
+        "delete_ecr_image",
+        query.imageid,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
 }
 
 #[delete("/aws/cleanup_ecr_images")]
@@ -248,13 +748,21 @@ pub async fn delete_ecr_image(
 pub async fn cleanup_ecr_images(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
-) -> WarpResult<DeletedResource> {
-    data.aws
+    #[filter = "accepts_json"] json: bool,
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([ResourceType::Ecr]);
+    data.aws()
         .ecr
         .cleanup_ecr_images()
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new("Deleted").into())
+    Ok(MutationResponse::new(
+        json,
+        "cleanup_ecr_images",
+        "ecr",
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
 }
 
 #[derive(Serialize, Deserialize, Schema)]
@@ -276,7 +784,7 @@ pub async fn edit_script(
 ) -> WarpResult<EditScriptResponse> {
     let query = query.into_inner();
     let fname = &query.filename;
-    let filename = data.aws.config.script_directory.join(fname);
+    let filename = data.aws().config.script_directory.join(fname);
     let text = if filename.exists() {
         read_to_string(&filename)
             .await
@@ -301,15 +809,23 @@ pub struct ReplaceData {
 pub async fn replace_script(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     req: Json<ReplaceData>,
-) -> WarpResult<FinishedResource> {
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([ResourceType::Script]);
     let req = req.into_inner();
-    let filename = data.aws.config.script_directory.join(&req.filename);
+    let filename = data.aws().config.script_directory.join(&req.filename);
     let mut f = File::create(&filename).await.map_err(Into::<Error>::into)?;
     f.write_all(req.text.as_bytes())
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new("Finished").into())
+    Ok(MutationResponse::new(
+        json,
+        "replace_script",
+        req.filename,
+        MutationStatus::Finished,
+        "Finished",
+    ))
 }
 
 #[delete("/aws/delete_script")]
@@ -317,14 +833,22 @@ pub async fn replace_script(
 pub async fn delete_script(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<ScriptFilename>,
-) -> WarpResult<DeletedResource> {
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([ResourceType::Script]);
     let query = query.into_inner();
-    let filename = data.aws.config.script_directory.join(&query.filename);
+    let filename = data.aws().config.script_directory.join(&query.filename);
     if filename.exists() {
         remove_file(&filename).await.map_err(Into::<Error>::into)?;
     }
-    Ok(HtmlBase::new("Deleted").into())
+    Ok(MutationResponse::new(
+        json,
+        "delete_script",
+        query.filename,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
 }
 
 #[derive(Serialize, Deserialize, Debug, Schema)]
@@ -364,7 +888,7 @@ pub async fn build_spot_request(
     query: Query<SpotBuilder>,
 ) -> WarpResult<BuildSpotResponse> {
     let query = query.into_inner();
-    let mut amis: Vec<AmiInfo> = Box::pin(data.aws.get_all_ami_tags())
+    let mut amis: Vec<AmiInfo> = Box::pin(data.aws().get_all_ami_tags())
         .await
         .map_err(Into::<Error>::into)?
         .into_iter()
@@ -376,11 +900,10 @@ pub async fn build_spot_request(
         move_element_to_front(&mut amis, |ami| &ami.id == query_ami);
     }
 
-    let mut inst_fams: Vec<InstanceFamily> = InstanceFamily::get_all(&data.aws.pool, Some(true))
-        .await
-        .map_err(Into::<Error>::into)?
-        .and_then(|fam| async move { Ok(fam) })
-        .try_collect()
+    let mut inst_fams: Vec<InstanceFamily> = data
+        .aws()
+        .storage
+        .get_instance_families(Some(true))
         .await
         .map_err(Into::<Error>::into)?;
 
@@ -393,34 +916,43 @@ pub async fn build_spot_request(
     }
 
     let inst = query.inst.unwrap_or_else(|| "t3".into());
-    let instances: Vec<InstanceList> = InstanceList::get_by_instance_family(&inst, &data.aws.pool)
-        .await
-        .map_err(Into::<Error>::into)?
-        .try_collect()
+    let instances = data
+        .aws()
+        .get_instance_family_prices(&inst)
         .await
         .map_err(Into::<Error>::into)?;
 
-    let mut files = data.aws.get_all_scripts();
+    let mut files = data.aws().get_all_scripts();
 
     if let Some(script) = &query.script {
         move_element_to_front(&mut files, |f| f == script);
     }
 
     let keys: Vec<(StackString, StackString)> = data
-        .aws
+        .aws()
         .ec2
         .get_all_key_pairs()
         .await
         .map_err(Into::<Error>::into)?
         .collect();
 
+    let instance_profiles: Vec<StackString> = data
+        .aws()
+        .iam
+        .list_instance_profiles()
+        .await
+        .map_err(Into::<Error>::into)?
+        .map(|p| p.instance_profile_name)
+        .collect();
+
     let body = build_spot_request_body(
         amis,
         inst_fams,
         instances,
         files,
         keys,
-        data.aws.config.clone(),
+        instance_profiles,
+        data.aws().config.clone(),
     )?
     .into();
 
@@ -443,11 +975,54 @@ pub struct SpotRequestData {
     pub price: StackString,
     #[schema(description = "Spot Request Name Tag")]
     pub name: StackString,
+    #[schema(description = "Number of Instances")]
+    pub count: Option<usize>,
+    #[schema(description = "Further Instance Types")]
+    pub extra_instance_types: Option<Vec<StackString>>,
+    #[schema(description = "Availability Zones")]
+    pub availability_zones: Option<Vec<StackString>>,
+    #[schema(description = "Maximum Combined Price")]
+    pub max_total_price: Option<StackString>,
+    #[schema(description = "Re-request Instances Terminated by AWS")]
+    pub auto_recover: Option<bool>,
+    #[schema(description = "Instance Tenancy (default or dedicated)")]
+    pub tenancy: Option<StackString>,
+    #[schema(description = "Launch EBS Optimized Instances")]
+    pub ebs_optimized: Option<bool>,
+    #[schema(description = "Root Volume Size (GB)")]
+    pub root_volume_size: Option<i32>,
+    #[schema(description = "Root Volume Type")]
+    pub root_volume_type: Option<StackString>,
+    #[schema(description = "Root Volume IOPS")]
+    pub root_volume_iops: Option<i32>,
+    #[schema(description = "Data Volumes as DEVICE:SIZE[:TYPE][:keep]")]
+    pub data_volumes: Option<Vec<StackString>>,
+    #[schema(description = "Instance Profile Name")]
+    pub instance_profile: Option<StackString>,
+    #[schema(description = "Require IMDSv2 Session Tokens")]
+    pub require_imdsv2: Option<bool>,
+    #[schema(description = "Route53 Host Name to Point at the Instance")]
+    pub dns_name: Option<StackString>,
 }
 
-impl From<SpotRequestData> for SpotRequest {
-    fn from(item: SpotRequestData) -> Self {
-        Self {
+impl TryFrom<SpotRequestData> for SpotRequest {
+    type Error = Error;
+
+    fn try_from(item: SpotRequestData) -> Result<Self, Self::Error> {
+        let tenancy = match item.tenancy {
+            Some(tenancy) => tenancy
+                .parse()
+                .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?,
+            None => InstanceTenancy::default(),
+        };
+        let data_volumes = item
+            .data_volumes
+            .unwrap_or_default()
+            .iter()
+            .map(|v| v.parse::<DataVolume>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+        Ok(Self {
             ami: item.ami,
             instance_type: item.instance_type,
             security_group: item.security_group,
@@ -455,30 +1030,264 @@ impl From<SpotRequestData> for SpotRequest {
             key_name: item.key_name,
             price: item.price.parse().ok(),
             tags: hashmap! { "Name".into() => item.name },
-        }
+            count: item.count.unwrap_or(1),
+            extra_instance_types: item.extra_instance_types.unwrap_or_default(),
+            availability_zones: item.availability_zones.unwrap_or_default(),
+            max_total_price: item.max_total_price.and_then(|p| p.parse().ok()),
+            auto_recover: item.auto_recover.unwrap_or(false),
+            tenancy,
+            ebs_optimized: item.ebs_optimized.unwrap_or(false),
+            root_volume: VolumeSpec::from_options(
+                item.root_volume_size,
+                item.root_volume_type.filter(|t| !t.is_empty()),
+                item.root_volume_iops,
+            ),
+            data_volumes,
+            instance_profile: item.instance_profile.filter(|p| !p.is_empty()),
+            require_imdsv2: item.require_imdsv2.unwrap_or(false),
+            dns_name: item.dns_name.filter(|n| !n.is_empty()),
+        })
     }
 }
 
 #[post("/aws/request_spot")]
 pub async fn request_spot(
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    #[filter = "idempotency_key"] key: Option<StackString>,
     req: Json<SpotRequestData>,
-) -> WarpResult<FinishedResource> {
-    let req: SpotRequest = req.into_inner().into();
+) -> WarpResult<MutationResponse> {
+    data.aws()
+        .cache
+        .invalidate([ResourceType::Spot, ResourceType::Instances]);
+    let mut req: SpotRequest = req.into_inner().try_into()?;
+    data.aws()
+        .check_name_tag(ResourceType::Spot, &mut req.tags)
+        .map_err(Into::<Error>::into)?;
+    data.aws()
+        .check_spot_request(&req)
+        .await
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
     let tags = Arc::new(req.tags.clone());
-    for spot_id in data
-        .aws
-        .ec2
-        .request_spot_instance(&req)
+    let require_imdsv2 = req.require_imdsv2;
+    let spot_ids: Vec<StackString> = idempotent(&user, "request_spot", key, || async {
+        let mut spot_ids = Vec::new();
+        for launch in data
+            .aws()
+            .ec2
+            .request_spot_instance(&req)
+            .await
+            .map_err(Into::<Error>::into)?
+        {
+            spot_ids.push(launch.spot_id.clone());
+            data.aws()
+                .record_spot_launch(&req, &launch)
+                .await
+                .map_err(Into::<Error>::into)?;
+            let ec2 = data.aws().ec2.clone();
+            let tags = tags.clone();
+            data.tasks.spawn("tag_spot_instance", 3, move || {
+                let ec2 = ec2.clone();
+                let tags = tags.clone();
+                let spot_id = launch.spot_id.clone();
+                async move {
+                    ec2.tag_spot_instance(&spot_id, &tags, require_imdsv2, 1000)
+                        .await
+                }
+            });
+        }
+        Ok(spot_ids)
+    })
+    .await?;
+    Ok(MutationResponse::new(
+        json,
+        "request_spot",
+        spot_ids.join(","),
+        MutationStatus::Finished,
+        "Finished",
+    ))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct AutoRecoverRequest {
+    #[schema(description = "Instance ID")]
+    pub instance_id: StackString,
+    #[schema(description = "Re-request the Instance if Terminated by AWS")]
+    pub enable: bool,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Auto Recover Flag Set", content = "html")]
+struct AutoRecoverResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/auto_recover")]
+#[openapi(description = "Set Spot Instance Auto Recovery")]
+pub async fn auto_recover(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    req: Json<AutoRecoverRequest>,
+) -> WarpResult<AutoRecoverResponse> {
+    let req = req.into_inner();
+    let updated = data
+        .aws()
+        .storage
+        .set_auto_recover(&req.instance_id, req.enable)
         .await
-        .map_err(Into::<Error>::into)?
-    {
-        let ec2 = data.aws.ec2.clone();
-        let tags = tags.clone();
-        spawn(async move { ec2.tag_spot_instance(&spot_id, &tags, 1000).await });
+        .map_err(Into::<Error>::into)?;
+    if !updated {
+        return Err(Error::BadRequest(format_sstr!(
+            "{} is not a running spot instance launched from this app",
+            req.instance_id
+        ))
+        .into());
     }
-    Ok(HtmlBase::new("Finished").into())
+    let state = if req.enable { "enabled" } else { "disabled" };
+    Ok(HtmlBase::new(format_sstr!(
+        "auto recovery {state} for {}",
+        req.instance_id
+    ))
+    .into())
+}
+
+/// Number of items of a batch request acted on at once
+const BATCH_CONCURRENCY: usize = 4;
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct BatchItemResult {
+    #[schema(description = "Resource ID")]
+    pub id: StackString,
+    #[schema(description = "Whether the Action Succeeded")]
+    pub success: bool,
+    #[schema(description = "Error Message")]
+    pub message: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Per Item Results")]
+struct BatchResponse(JsonBase<Vec<BatchItemResult>, Error>);
+
+/// Run `f` on every id not in `protected`, in the order given
+async fn run_batch<F, Fut>(
+    ids: Vec<StackString>,
+    protected: &[StackString],
+    f: F,
+) -> Vec<BatchItemResult>
+where
+    F: Fn(StackString) -> Fut,
+    Fut: Future<Output = Result<(), AnyhowError>>,
+{
+    let f = &f;
+    stream::iter(ids.into_iter().map(|id| {
+        let is_protected = protected.contains(&id);
+        async move {
+            let result = if is_protected {
+                Err(format_err!("{id} is protected"))
+            } else {
+                match lock_resource(&id) {
+                    Ok(_guard) => f(id.clone()).await,
+                    Err(e) => Err(format_err!("{e}")),
+                }
+            };
+            BatchItemResult {
+                id,
+                success: result.is_ok(),
+                message: result.err().map(|e| format_sstr!("{e}")),
+            }
+        }
+    }))
+    .buffered(BATCH_CONCURRENCY)
+    .collect()
+    .await
+}
+
+#[post("/aws/batch/terminate")]
+#[openapi(description = "Terminate Several Ec2 Instances")]
+pub async fn batch_terminate(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+    req: Json<BatchRequest>,
+) -> WarpResult<BatchResponse> {
+    let req = req.into_inner();
+    let aws = data.aws();
+    let protected = if override_protection(&user, req.force, &aws.config)? {
+        Vec::new()
+    } else {
+        aws.find_protected_instances(&req.ids)
+            .await
+            .map_err(Into::<Error>::into)?
+    };
+    let aws = &aws;
+    let results = run_batch(req.ids, &protected, |id| async move {
+        aws.terminate(&[id]).await
+    })
+    .await;
+    Ok(JsonBase::new(results).into())
+}
+
+#[post("/aws/batch/delete_volume")]
+#[openapi(description = "Delete Several EC2 Volumes")]
+pub async fn batch_delete_volume(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+    req: Json<BatchRequest>,
+) -> WarpResult<BatchResponse> {
+    let req = req.into_inner();
+    let aws = data.aws();
+    let protected = if override_protection(&user, req.force, &aws.config)? {
+        Vec::new()
+    } else {
+        aws.find_protected_volumes(&req.ids)
+            .await
+            .map_err(Into::<Error>::into)?
+    };
+    let aws = &aws;
+    let results = run_batch(req.ids, &protected, |id| async move {
+        aws.delete_ebs_volume(&id).await
+    })
+    .await;
+    Ok(JsonBase::new(results).into())
+}
+
+#[post("/aws/batch/delete_snapshot")]
+#[openapi(description = "Delete Several EC2 Snapshots")]
+pub async fn batch_delete_snapshot(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    req: Json<BatchRequest>,
+) -> WarpResult<BatchResponse> {
+    let req = req.into_inner();
+    let aws = &data.aws();
+    let results = run_batch(req.ids, &[], |id| async move {
+        aws.delete_ebs_snapshot(&id).await
+    })
+    .await;
+    Ok(JsonBase::new(results).into())
+}
+
+#[post("/aws/batch/tag")]
+#[openapi(description = "Set the Name Tag of Several EC2 Resources")]
+pub async fn batch_tag(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    req: Json<BatchTagRequest>,
+) -> WarpResult<BatchResponse> {
+    let req = req.into_inner();
+    let aws = &data.aws();
+    aws.cache.invalidate([
+        ResourceType::Instances,
+        ResourceType::Volume,
+        ResourceType::Snapshot,
+        ResourceType::Ami,
+    ]);
+    let tag = &req.tag;
+    let results = run_batch(req.ids, &[], |id| async move {
+        aws.ec2
+            .tag_ec2_instance(id.as_str(), &hashmap! {"Name".into() => tag.clone()})
+            .await
+    })
+    .await;
+    Ok(JsonBase::new(results).into())
 }
 
 #[derive(Serialize, Deserialize, Schema)]
@@ -487,28 +1296,28 @@ pub struct CancelSpotRequest {
     pub spot_id: StackString,
 }
 
-#[derive(RwebResponse)]
-#[response(
-    description = "Cancelled Spot",
-    content = "html",
-    status = "NO_CONTENT"
-)]
-struct CancelledResponse(HtmlBase<StackString, Error>);
-
 #[delete("/aws/cancel_spot")]
 #[openapi(description = "Cancel Spot Request")]
 pub async fn cancel_spot(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<CancelSpotRequest>,
-) -> WarpResult<CancelledResponse> {
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([ResourceType::Spot]);
     let query = query.into_inner();
-    data.aws
+    data.aws()
         .ec2
         .cancel_spot_instance_request(&[query.spot_id.clone()])
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new(format_sstr!("cancelled {}", query.spot_id)).into())
+    Ok(MutationResponse::new(
+        json,
+        "cancel_spot",
+        query.spot_id,
+        MutationStatus::Deleted,
+        "Cancelled",
+    ))
 }
 
 #[derive(Serialize, Deserialize, Schema)]
@@ -531,47 +1340,185 @@ pub async fn get_prices(
     let query = query.into_inner();
 
     let body = if let Some(search) = query.search {
-        let prices = data
-            .aws
-            .get_ec2_prices(&[search])
-            .await
-            .map_err(Into::<Error>::into)?;
-        prices_body(prices)?.into()
+        let (prices, forecasts) = try_join!(
+            data.aws().get_ec2_prices(&[&search]),
+            data.aws().get_spot_forecasts(&[&search]),
+        )
+        .map_err(Into::<Error>::into)?;
+        prices_body(prices, forecasts, search)?.into()
     } else {
-        let mut inst_fam: Vec<InstanceFamily> = InstanceFamily::get_all(&data.aws.pool, None)
-            .await
-            .map_err(Into::<Error>::into)?
-            .try_collect()
+        let mut inst_fam: Vec<InstanceFamily> = data
+            .aws()
+            .storage
+            .get_instance_families(None)
             .await
             .map_err(Into::<Error>::into)?;
         move_element_to_front(&mut inst_fam, |fam| fam.family_name == "m5");
-        instance_family_body(inst_fam)?.into()
+        let update_status = data
+            .aws()
+            .storage
+            .get_update_status()
+            .await
+            .map_err(Into::<Error>::into)?;
+        instance_family_body(inst_fam, update_status)?.into()
     };
 
     Ok(HtmlBase::new(body).into())
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Update", content = "html", status = "CREATED")]
-struct UpdateResponse(HtmlBase<StackString, Error>);
-
-#[post("/aws/update")]
-#[openapi(description = "Update Data")]
-pub async fn update(
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
-    #[data] data: AppState,
-) -> WarpResult<UpdateResponse> {
-    let entries: Vec<StackString> = data
-        .aws
-        .update()
-        .await
-        .map_err(Into::<Error>::into)?
-        .collect();
-    let body = textarea_body(entries, "diary_editor_form".into())?.into();
-    Ok(HtmlBase::new(body).into())
+#[derive(Serialize, Deserialize, Schema)]
+pub struct PriceHistoryRequest {
+    #[schema(description = "Instance Type")]
+    pub instance_type: StackString,
 }
 
-#[derive(RwebResponse)]
+#[derive(Serialize, Deserialize, Schema)]
+pub struct PriceHistoryEntry {
+    #[schema(description = "Instance Type")]
+    pub instance_type: StackString,
+    #[schema(description = "Price Type (ondemand, reserved, spot)")]
+    pub price_type: StackString,
+    #[schema(description = "Price (USD/hr)")]
+    pub price: f64,
+    #[schema(description = "Effective Date of the Price")]
+    pub price_timestamp: DateTimeType,
+    #[schema(description = "When the Price was Recorded")]
+    pub recorded_at: DateTimeType,
+}
+
+impl From<PriceHistory> for PriceHistoryEntry {
+    fn from(item: PriceHistory) -> Self {
+        Self {
+            instance_type: item.instance_type,
+            price_type: item.price_type,
+            price: item.price,
+            price_timestamp: item.price_timestamp.into(),
+            recorded_at: item.recorded_at.into(),
+        }
+    }
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Price History")]
+struct PriceHistoryResponse(JsonBase<Vec<PriceHistoryEntry>, Error>);
+
+#[get("/aws/api/price_history")]
+#[openapi(description = "Recorded Ondemand, Reserved and Spot Prices for an Instance Type")]
+pub async fn price_history(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<PriceHistoryRequest>,
+) -> WarpResult<PriceHistoryResponse> {
+    let query = query.into_inner();
+    let history = data
+        .aws()
+        .storage
+        .get_price_history(&query.instance_type)
+        .await
+        .map_err(Into::<Error>::into)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok(JsonBase::new(history).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct SpotForecastRequest {
+    #[schema(description = "Instance Type Prefix")]
+    pub search: Option<StackString>,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct SpotForecastEntry {
+    #[schema(description = "Instance Type")]
+    pub instance_type: StackString,
+    #[schema(description = "Number of Recorded Spot Prices")]
+    pub samples: usize,
+    #[schema(description = "Latest Spot Price (USD/hr)")]
+    pub current: f64,
+    #[schema(description = "Mean Spot Price over the Window (USD/hr)")]
+    pub mean: f64,
+    #[schema(description = "Mean Spot Price over the Last Day (USD/hr)")]
+    pub rolling_mean: f64,
+    #[schema(description = "Median Spot Price (USD/hr)")]
+    pub p50: f64,
+    #[schema(description = "90th Percentile Spot Price (USD/hr)")]
+    pub p90: f64,
+    #[schema(description = "Maximum Spot Price (USD/hr)")]
+    pub max: f64,
+    #[schema(description = "Standard Deviation Relative to the Mean")]
+    pub volatility: f64,
+    #[schema(description = "Ondemand Price (USD/hr)")]
+    pub ondemand_price: Option<f64>,
+    #[schema(description = "Interruption Risk (low, medium, high, unknown)")]
+    pub risk: StackString,
+    #[schema(description = "Suggested Maximum Spot Price (USD/hr)")]
+    pub suggested_bid: f64,
+}
+
+impl From<SpotForecast> for SpotForecastEntry {
+    fn from(item: SpotForecast) -> Self {
+        Self {
+            instance_type: item.instance_type,
+            samples: item.samples,
+            current: item.current,
+            mean: item.mean,
+            rolling_mean: item.rolling_mean,
+            p50: item.p50,
+            p90: item.p90,
+            max: item.max,
+            volatility: item.volatility,
+            ondemand_price: item.ondemand_price,
+            risk: item.risk.to_str().into(),
+            suggested_bid: item.suggested_bid,
+        }
+    }
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Spot Price Forecast")]
+struct SpotForecastResponse(JsonBase<Vec<SpotForecastEntry>, Error>);
+
+#[get("/aws/api/spot_forecast")]
+#[openapi(description = "Spot Price Forecast from the Recorded Spot Prices")]
+pub async fn spot_forecast(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<SpotForecastRequest>,
+) -> WarpResult<SpotForecastResponse> {
+    let search: Vec<_> = query.into_inner().search.into_iter().collect();
+    let forecasts = data
+        .aws()
+        .get_spot_forecasts(&search)
+        .await
+        .map_err(Into::<Error>::into)?
+        .into_values()
+        .map(Into::into)
+        .collect();
+    Ok(JsonBase::new(forecasts).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Update", content = "html", status = "CREATED")]
+struct UpdateResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/update")]
+#[openapi(description = "Update Data")]
+pub async fn update(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<UpdateResponse> {
+    let entries: Vec<StackString> = data
+        .aws()
+        .update(UpdateSource::Scrape, None, &[])
+        .await
+        .map_err(Into::<Error>::into)?
+        .collect();
+    let body = textarea_body(entries, "diary_editor_form".into())?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
 #[response(description = "Instance Status", content = "html")]
 struct InstanceStatusResponse(HtmlBase<StackString, Error>);
 
@@ -583,20 +1530,62 @@ pub async fn instance_status(
     query: Query<StatusRequest>,
 ) -> WarpResult<InstanceStatusResponse> {
     let query = query.into_inner();
-    let entries = match tokio::time::timeout(
+    let result = match tokio::time::timeout(
         tokio::time::Duration::from_secs(60),
-        data.aws.get_status(&query.instance),
+        data.aws().get_status(&query.instance),
     )
     .await
     {
         Ok(x) => x,
         Err(_) => Err(format_err!("Timeout")),
-    }
-    .map_err(Into::<Error>::into)?;
-    let body = instance_status_body(entries, query.instance)?.into();
+    };
+    let (entries, host_key_error) = split_host_key_error(result)?;
+    let body = instance_status_body(entries, query.instance, host_key_error)?.into();
     Ok(HtmlBase::new(body).into())
 }
 
+/// Host key failures are shown with a reset button instead of as an error
+fn split_host_key_error(
+    result: Result<Vec<StackString>, AnyhowError>,
+) -> HttpResult<(Vec<StackString>, Option<StackString>)> {
+    match result {
+        Ok(entries) => Ok((entries, None)),
+        Err(e) => match e.downcast_ref::<HostKeyMismatch>() {
+            Some(mismatch) => Ok((Vec::new(), Some(format_sstr!("{mismatch}")))),
+            None => Err(e.into()),
+        },
+    }
+}
+
+#[post("/aws/reset_host_key")]
+#[openapi(description = "Forget and Refetch the SSH Host Key of an Ec2 Instance")]
+pub async fn reset_host_key(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<StatusRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    let found = data
+        .aws()
+        .reset_host_key(&query.instance)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let message = if found {
+        "host key updated from console output"
+    } else {
+        "no host key in console output, the next connection will record the key it is offered"
+    };
+    Ok(MutationResponse::new(
+        json,
+        "reset_host_key",
+        query.instance,
+        MutationStatus::Updated,
+        message,
+    )
+    .html_status(StatusCode::CREATED))
+}
+
 #[derive(RwebResponse)]
 #[response(
     description = "Run Command on Instance",
@@ -613,24 +1602,24 @@ pub async fn command(
     payload: Json<CommandRequest>,
 ) -> WarpResult<CommandResponse> {
     let payload = payload.into_inner();
-    let entries = match tokio::time::timeout(
+    let result = match tokio::time::timeout(
         tokio::time::Duration::from_secs(60),
-        data.aws.run_command(&payload.instance, &payload.command),
+        data.aws().run_command(&payload.instance, &payload.command),
     )
     .await
     {
         Ok(x) => x,
         Err(_) => Err(format_err!("Timeout")),
-    }
-    .map_err(Into::<Error>::into)?;
+    };
+    let (entries, host_key_error) = split_host_key_error(result)?;
 
-    let body = instance_status_body(entries, payload.instance)?.into();
+    let body = instance_status_body(entries, payload.instance, host_key_error)?.into();
     Ok(HtmlBase::new(body).into())
 }
 
 #[derive(Serialize, Deserialize, Schema)]
 pub struct InstancesRequest {
-    #[schema(description = "Instance ID or Name Tag")]
+    #[schema(description = "Instance Family")]
     pub inst: StackString,
 }
 
@@ -639,24 +1628,55 @@ pub struct InstancesRequest {
 struct InstancesResponse(HtmlBase<String, Error>);
 
 #[get("/aws/instances")]
-#[openapi(description = "List Ec2 Instances")]
+#[openapi(description = "List Instance Types in a Family with Spot and On-Demand Prices")]
 pub async fn get_instances(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
     query: Query<InstancesRequest>,
 ) -> WarpResult<InstancesResponse> {
     let query = query.into_inner();
-    let instances: Vec<InstanceList> =
-        InstanceList::get_by_instance_family(&query.inst, &data.aws.pool)
-            .await
-            .map_err(Into::<Error>::into)?
-            .try_collect()
-            .await
-            .map_err(Into::<Error>::into)?;
+    let instances = data
+        .aws()
+        .get_instance_family_prices(&query.inst)
+        .await
+        .map_err(Into::<Error>::into)?;
     let body = instance_types_body(instances)?;
     Ok(HtmlBase::new(body).into())
 }
 
+#[derive(Serialize, Deserialize, Schema)]
+pub struct UbuntuImagesRequest {
+    #[schema(description = "Ubuntu Release, e.g. noble-24.04")]
+    pub release: StackString,
+    #[schema(description = "Architecture (amd64 or arm64)")]
+    pub arch: StackString,
+    #[schema(description = "Minimal Cloud Images")]
+    pub minimal: Option<bool>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Ubuntu Images", content = "html")]
+struct UbuntuImagesResponse(HtmlBase<String, Error>);
+
+#[get("/aws/ubuntu_images")]
+#[openapi(description = "List Canonical's Ubuntu Images of a Release and Architecture")]
+pub async fn ubuntu_images(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<UbuntuImagesRequest>,
+) -> WarpResult<UbuntuImagesResponse> {
+    let query = query.into_inner();
+    let mut image_query = UbuntuImageQuery::new(query.release, query.arch);
+    image_query.minimal = query.minimal.unwrap_or(false);
+    image_query
+        .validate()
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let mut images = get_ubuntu_images(&data.aws(), &image_query).await?;
+    images.truncate(10);
+    let body = ubuntu_images_body(images)?;
+    Ok(HtmlBase::new(body).into())
+}
+
 #[derive(RwebResponse)]
 #[response(description = "Start NoVNC", content = "html", status = "CREATED")]
 struct NovncStartResponse(HtmlBase<StackString, Error>);
@@ -667,8 +1687,8 @@ pub async fn novnc_launcher(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
 ) -> WarpResult<NovncStartResponse> {
-    if let Some(novnc_path) = &data.aws.config.novnc_path {
-        let certdir = Path::new("/etc/letsencrypt/live/").join(&data.aws.config.domain);
+    if let Some(novnc_path) = &data.aws().config.novnc_path {
+        let certdir = Path::new("/etc/letsencrypt/live/").join(&data.aws().config.domain);
         let cert = certdir.join("fullchain.pem");
         let key = certdir.join("privkey.pem");
         data.novnc
@@ -681,33 +1701,49 @@ pub async fn novnc_launcher(
             .get_websock_pids()
             .await
             .map_err(Into::<Error>::into)?;
-        let body = novnc_status_body(number, data.aws.config.domain.clone(), pids)?.into();
+        let body = novnc_status_body(number, data.aws().config.domain.clone(), pids)?.into();
         Ok(HtmlBase::new(body).into())
     } else {
         Ok(HtmlBase::new("NoVNC not configured".into()).into())
     }
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Stop NoVNC", content = "html", status = "CREATED")]
-struct NovncStopResponse(HtmlBase<StackString, Error>);
-
+/// The html reply is the stop output in a textarea for the novnc tab, json
+/// gets it as plain text
 #[post("/aws/novnc/stop")]
 #[openapi(description = "Stop NoVNC Service")]
 pub async fn novnc_shutdown(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
-) -> WarpResult<NovncStopResponse> {
-    if data.aws.config.novnc_path.is_none() {
-        return Ok(HtmlBase::new("NoVNC not configured".into()).into());
+    #[filter = "accepts_json"] json: bool,
+) -> WarpResult<MutationResponse> {
+    if data.aws().config.novnc_path.is_none() {
+        return Ok(MutationResponse::new(
+            json,
+            "novnc_shutdown",
+            "novnc",
+            MutationStatus::NotFound,
+            "NoVNC not configured",
+        )
+        .html_status(StatusCode::CREATED));
     }
     let output = data
         .novnc
         .novnc_stop_request()
         .await
         .map_err(Into::<Error>::into)?;
-    let body = textarea_body(output, "novnc-stop".into())?.into();
-    Ok(HtmlBase::new(body).into())
+    let message: StackString = if json {
+        output.join("\n").into()
+    } else {
+        textarea_body(output, "novnc-stop".into())?.into()
+    };
+    Ok(MutationResponse::new(
+        json,
+        "novnc_shutdown",
+        "novnc",
+        MutationStatus::Finished,
+        message,
+    ))
 }
 
 #[derive(RwebResponse)]
@@ -720,7 +1756,7 @@ pub async fn novnc_status(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
 ) -> WarpResult<NovncStatusResponse> {
-    if data.aws.config.novnc_path.is_none() {
+    if data.aws().config.novnc_path.is_none() {
         return Ok(HtmlBase::new("NoVNC not configured".into()).into());
     }
     let number = data.novnc.get_novnc_status().await;
@@ -732,7 +1768,7 @@ pub async fn novnc_status(
             .get_websock_pids()
             .await
             .map_err(Into::<Error>::into)?;
-        novnc_status_body(number, data.aws.config.domain.clone(), pids)?.into()
+        novnc_status_body(number, data.aws().config.domain.clone(), pids)?.into()
     };
     Ok(HtmlBase::new(body).into())
 }
@@ -747,55 +1783,123 @@ pub async fn user(#[filter = "LoggedUser::filter"] user: LoggedUser) -> WarpResu
     Ok(JsonBase::new(user).into())
 }
 
+#[derive(Serialize, Deserialize, Schema)]
+pub struct CsrfToken {
+    #[schema(description = "Value for the X-CSRF-Token Header")]
+    pub csrf_token: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Csrf Token")]
+struct CsrfTokenResponse(JsonBase<CsrfToken, Error>);
+
+#[get("/aws/api/csrf_token")]
+#[openapi(description = "Csrf Token Required by Mutating Requests in this Session")]
+pub async fn get_csrf_token(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+) -> WarpResult<CsrfTokenResponse> {
+    let csrf_token = csrf_token(user.session.into());
+    Ok(JsonBase::new(CsrfToken { csrf_token }).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct HealthReport {
+    #[schema(description = "Database Reachable")]
+    pub database: bool,
+    #[schema(description = "Most Recently Applied Migration")]
+    pub schema_version: Option<i64>,
+    #[schema(description = "Latest Migration Embedded in this Build")]
+    pub expected_schema_version: Option<i64>,
+    #[schema(description = "Aws Credentials Valid (GetCallerIdentity)")]
+    pub aws_credentials: bool,
+    #[schema(description = "Errors from Failed Checks")]
+    pub errors: Vec<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Service Health")]
+struct HealthResponse(JsonBase<HealthReport, Error>);
+
+#[get("/aws/health")]
+#[openapi(description = "Database Connectivity, Schema Version and Aws Credential Validity")]
+pub async fn health(#[data] data: AppState) -> WarpResult<HealthResponse> {
+    let aws = data.aws();
+    let (schema, identity) = join!(aws.storage.schema_version(), aws.sts.get_caller_identity());
+    let mut errors = Vec::new();
+    let database = schema.is_ok();
+    let schema_version = match schema {
+        Ok(version) => version,
+        Err(e) => {
+            errors.push(format_sstr!("database: {e}"));
+            None
+        }
+    };
+    if let Err(e) = &identity {
+        errors.push(format_sstr!("aws: {e}"));
+    }
+    let report = HealthReport {
+        database,
+        schema_version,
+        expected_schema_version: latest_migration().map(|m| m.version),
+        aws_credentials: identity.is_ok(),
+        errors,
+    };
+    Ok(JsonBase::new(report).into())
+}
+
 #[derive(Serialize, Deserialize, Schema)]
 pub struct CreateUserRequest {
     #[schema(description = "User Name")]
     pub user_name: StackString,
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Created Iam User", status = "CREATED")]
-struct CreateUserResponse(JsonBase<IamUserWrapper, Error>);
-
 #[post("/aws/create_user")]
 #[openapi(description = "Create IAM User")]
 pub async fn create_user(
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    #[filter = "idempotency_key"] key: Option<StackString>,
     query: Query<CreateUserRequest>,
-) -> WarpResult<CreateUserResponse> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
-    let user = data
-        .aws
-        .create_user(query.user_name.as_str())
-        .await
-        .map_err(Into::<Error>::into)?
-        .ok_or_else(|| Error::BadRequest("create user failed".into()))?;
-    let resp = JsonBase::new(user.into());
-    Ok(resp.into())
+    let iam_user = idempotent(&user, "create_user", key, || async {
+        data.aws()
+            .create_user(query.user_name.as_str())
+            .await
+            .map_err(Into::<Error>::into)?
+            .ok_or_else(|| Error::BadRequest("create user failed".into()))
+    })
+    .await?;
+    Ok(MutationResponse::new(
+        json,
+        "create_user",
+        iam_user.user_name,
+        MutationStatus::Created,
+        iam_user.arn,
+    ))
 }
 
-#[derive(RwebResponse)]
-#[response(
-    description = "Delete Iam User",
-    content = "html",
-    status = "NO_CONTENT"
-)]
-struct DeleteUserResponse(HtmlBase<StackString, Error>);
-
 #[delete("/aws/delete_user")]
 #[openapi(description = "Delete IAM User")]
 pub async fn delete_user(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<CreateUserRequest>,
-) -> WarpResult<DeleteUserResponse> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
-    data.aws
+    data.aws()
         .delete_user(query.user_name.as_str())
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new(format_sstr!("{} deleted", query.user_name)).into())
+    Ok(MutationResponse::new(
+        json,
+        "delete_user",
+        query.user_name,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
 }
 
 #[derive(Serialize, Deserialize, Schema)]
@@ -806,56 +1910,50 @@ pub struct AddUserToGroupRequest {
     pub group_name: StackString,
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Add User to Group", content = "html")]
-struct AddUserGroupResponse(HtmlBase<StackString, Error>);
-
 #[patch("/aws/add_user_to_group")]
 #[openapi(description = "Add IAM User to Group")]
 pub async fn add_user_to_group(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<AddUserToGroupRequest>,
-) -> WarpResult<AddUserGroupResponse> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
-    data.aws
+    data.aws()
         .add_user_to_group(query.user_name.as_str(), query.group_name.as_str())
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new(format_sstr!(
-        "added {} to {}",
+    let message = format_sstr!("added {} to {}", query.user_name, query.group_name);
+    Ok(MutationResponse::new(
+        json,
+        "add_user_to_group",
         query.user_name,
-        query.group_name
+        MutationStatus::Updated,
+        message,
     ))
-    .into())
 }
 
-#[derive(RwebResponse)]
-#[response(
-    description = "Remove User to Group",
-    content = "html",
-    status = "NO_CONTENT"
-)]
-struct RemoveUserGroupResponse(HtmlBase<StackString, Error>);
-
 #[delete("/aws/remove_user_from_group")]
 #[openapi(description = "Remove IAM User from Group")]
 pub async fn remove_user_from_group(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<AddUserToGroupRequest>,
-) -> WarpResult<RemoveUserGroupResponse> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
-    data.aws
+    data.aws()
         .remove_user_from_group(query.user_name.as_str(), query.group_name.as_str())
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new(format_sstr!(
-        "removed {} from {}",
+    let message = format_sstr!("removed {} from {}", query.user_name, query.group_name);
+    Ok(MutationResponse::new(
+        json,
+        "remove_user_from_group",
         query.user_name,
-        query.group_name
+        MutationStatus::Deleted,
+        message,
     ))
-    .into())
 }
 
 #[derive(Serialize, Deserialize, Schema)]
@@ -866,52 +1964,113 @@ pub struct DeleteAccesssKeyRequest {
     pub access_key_id: StackString,
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Create Access Key", status = "CREATED")]
-struct CreateKeyResponse(JsonBase<Option<IamAccessKeyWrapper>, Error>);
-
+/// The secret is only available now, so the message carries it
 #[post("/aws/create_access_key")]
 #[openapi(description = "Create Access Key for IAM User")]
 pub async fn create_access_key(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<CreateUserRequest>,
-) -> WarpResult<CreateKeyResponse> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
     let access_key = data
-        .aws
+        .aws()
         .create_access_key(query.user_name.as_str())
         .await
-        .map_err(Into::<Error>::into)?;
-    Ok(JsonBase::new(access_key.map(Into::into)).into())
+        .map_err(Into::<Error>::into)?
+        .ok_or_else(|| Error::BadRequest("create access key failed".into()))?;
+    let message = format_sstr!(
+        "created {} for {}, secret access key {}",
+        access_key.access_key_id,
+        access_key.user_name,
+        access_key.access_key_secret
+    );
+    Ok(MutationResponse::new(
+        json,
+        "create_access_key",
+        access_key.access_key_id,
+        MutationStatus::Created,
+        message,
+    ))
 }
 
-#[derive(RwebResponse)]
-#[response(
-    description = "Delete Access Key",
-    content = "html",
-    status = "NO_CONTENT"
-)]
-struct DeleteKeyResponse(HtmlBase<StackString, Error>);
-
 #[delete("/aws/delete_access_key")]
 #[openapi(description = "Delete Access Key for IAM User")]
 pub async fn delete_access_key(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<DeleteAccesssKeyRequest>,
-) -> WarpResult<DeleteKeyResponse> {
+) -> WarpResult<MutationResponse> {
     let query = query.into_inner();
-    data.aws
+    data.aws()
         .delete_access_key(query.user_name.as_str(), query.access_key_id.as_str())
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new(format_sstr!(
-        "delete {} for {}",
+    Ok(MutationResponse::new(
+        json,
+        "delete_access_key",
         query.access_key_id,
-        query.user_name
+        MutationStatus::Deleted,
+        "Deleted",
     ))
-    .into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct VendCredentialsRequest {
+    #[schema(description = "Policy Name (file in policy_directory without .json)")]
+    pub policy: StackString,
+    #[schema(description = "Duration in Minutes (15 to 720, default 60)")]
+    pub duration_minutes: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct VendedCredentials {
+    #[schema(description = "Access Key ID")]
+    pub access_key_id: StackString,
+    #[schema(description = "Secret Access Key")]
+    pub secret_access_key: StackString,
+    #[schema(description = "Session Token")]
+    pub session_token: StackString,
+    #[schema(description = "Expiration DateTime")]
+    pub expiration: DateTimeType,
+    #[schema(description = "Shell Exports for a Launch Script")]
+    pub env_exports: StackString,
+}
+
+impl From<TemporaryCredentials> for VendedCredentials {
+    fn from(item: TemporaryCredentials) -> Self {
+        let env_exports = item.to_env_exports();
+        Self {
+            access_key_id: item.access_key_id,
+            secret_access_key: item.secret_access_key,
+            session_token: item.session_token,
+            expiration: item.expiration.into(),
+            env_exports,
+        }
+    }
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Temporary Credentials", status = "CREATED")]
+struct VendCredentialsResponse(JsonBase<VendedCredentials, Error>);
+
+#[post("/aws/vend_credentials")]
+#[openapi(description = "Generate Short Lived Credentials Scoped to a Policy")]
+pub async fn vend_credentials(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    req: Json<VendCredentialsRequest>,
+) -> WarpResult<VendCredentialsResponse> {
+    let req = req.into_inner();
+    let minutes = req.duration_minutes.unwrap_or(60).clamp(15, 720);
+    let credentials = data
+        .aws()
+        .vend_credentials(&req.policy, time::Duration::minutes(minutes))
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(JsonBase::new(credentials.into()).into())
 }
 
 #[derive(Serialize, Deserialize, Schema)]
@@ -926,19 +2085,17 @@ pub struct UpdateDnsNameRequest {
     new_ip: Ipv4AddrWrapper,
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Update Dns", status = "CREATED", content = "html")]
-struct UpdateDnsResponse(HtmlBase<StackString, Error>);
-
 #[patch("/aws/update_dns_name")]
 #[openapi(description = "Update DNS Name")]
 pub async fn update_dns_name(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<UpdateDnsNameRequest>,
-) -> WarpResult<UpdateDnsResponse> {
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([ResourceType::Route53]);
     let query = query.into_inner();
-    data.aws
+    data.aws()
         .route53
         .update_dns_record(
             &query.zone,
@@ -948,11 +2105,182 @@ pub async fn update_dns_name(
         )
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new(format_sstr!(
+    let message = format_sstr!(
         "update {} from {} to {}",
         query.dns_name,
         query.old_ip,
         query.new_ip
+    );
+    Ok(MutationResponse::new(
+        json,
+        "update_dns_name",
+        query.dns_name,
+        MutationStatus::Updated,
+        message,
+    )
+    .html_status(StatusCode::CREATED))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct HealthCheckRequest {
+    #[schema(description = "Check Type (HTTP, HTTPS or TCP)")]
+    pub check_type: Option<StackString>,
+    #[schema(description = "IPv4 Address or Domain Name")]
+    pub host: StackString,
+    #[schema(description = "Port")]
+    pub port: Option<i32>,
+    #[schema(description = "Resource Path (HTTP and HTTPS only)")]
+    pub resource_path: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Create Route53 Health Check",
+    content = "html",
+    status = "CREATED"
+)]
+struct CreateHealthCheckResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/route53/health_check")]
+#[openapi(description = "Create a Route53 Health Check")]
+pub async fn create_health_check(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<HealthCheckRequest>,
+) -> WarpResult<CreateHealthCheckResponse> {
+    let payload = payload.into_inner();
+    let check_type = payload
+        .check_type
+        .as_ref()
+        .map_or("HTTPS", StackString::as_str);
+    let target = HealthCheckTarget::new(
+        check_type,
+        &payload.host,
+        payload.port,
+        payload.resource_path.as_deref(),
+    )
+    .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let aws = data.aws();
+    aws.cache.invalidate([ResourceType::Route53]);
+    let id = aws
+        .route53
+        .create_health_check(target)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(format_sstr!("created health check {id}")).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct HealthCheckIdRequest {
+    #[schema(description = "Health Check ID")]
+    pub id: StackString,
+}
+
+#[delete("/aws/route53/health_check")]
+#[openapi(description = "Delete a Route53 Health Check")]
+pub async fn delete_health_check(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<HealthCheckIdRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    let aws = data.aws();
+    aws.cache.invalidate([ResourceType::Route53]);
+    aws.route53
+        .delete_health_check(&query.id)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(MutationResponse::new(
+        json,
+        "delete_health_check",
+        query.id,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct RoutingRecordRequest {
+    #[schema(description = "Route53 Zone")]
+    pub zone: StackString,
+    #[schema(description = "DNS Name")]
+    pub dns_name: StackString,
+    #[schema(description = "IPv4 Address (defaults to the current ip)")]
+    pub ip: Option<StackString>,
+    #[schema(description = "Set Identifier")]
+    pub set_identifier: StackString,
+    #[schema(description = "Routing Policy (primary, secondary or weighted)")]
+    pub policy: StackString,
+    #[schema(description = "Weight (weighted policy only)")]
+    pub weight: Option<i64>,
+    #[schema(description = "Health Check ID")]
+    pub health_check_id: Option<StackString>,
+    #[schema(description = "TTL in seconds")]
+    pub ttl: Option<i64>,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Create Failover or Weighted Record",
+    content = "html",
+    status = "CREATED"
+)]
+struct CreateRoutingRecordResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/route53/record")]
+#[openapi(description = "Create a Failover or Weighted A Record")]
+pub async fn create_routing_record(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<RoutingRecordRequest>,
+) -> WarpResult<CreateRoutingRecordResponse> {
+    let payload = payload.into_inner();
+    let routing = match payload.policy.as_str() {
+        "primary" => RecordRouting::FailoverPrimary,
+        "secondary" => RecordRouting::FailoverSecondary,
+        "weighted" => RecordRouting::Weighted(payload.weight.unwrap_or(1)),
+        p => return Err(Error::BadRequest(format_sstr!("Unknown routing policy {p}")).into()),
+    };
+    if payload.set_identifier.trim().is_empty() {
+        return Err(Error::BadRequest("Record needs a set identifier".into()).into());
+    }
+    let aws = data.aws();
+    let ip = match payload
+        .ip
+        .as_deref()
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+    {
+        Some(ip) => ip
+            .parse()
+            .map_err(|e| Error::BadRequest(format_sstr!("Invalid ip {ip}: {e}")))?,
+        None => aws
+            .route53
+            .get_ip_address()
+            .await
+            .map_err(Into::<Error>::into)?,
+    };
+    let mut name = payload.dns_name.clone();
+    if !name.ends_with('.') {
+        name.push('.');
+    }
+    let record = RoutingRecord {
+        name,
+        ip,
+        set_identifier: payload.set_identifier,
+        routing,
+        health_check_id: payload.health_check_id.filter(|id| !id.trim().is_empty()),
+        ttl: payload.ttl.unwrap_or(60),
+    };
+    aws.cache.invalidate([ResourceType::Route53]);
+    aws.route53
+        .create_routing_record(&payload.zone, record)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(format_sstr!(
+        "created {routing} record {} {ip}",
+        payload.dns_name
     ))
     .into())
 }
@@ -965,6 +2293,10 @@ enum SystemdActions {
     Stop,
     #[serde(rename = "restart")]
     Restart,
+    #[serde(rename = "enable")]
+    Enable,
+    #[serde(rename = "disable")]
+    Disable,
 }
 
 impl SystemdActions {
@@ -973,6 +2305,8 @@ impl SystemdActions {
             Self::Start => "start",
             Self::Stop => "stop",
             Self::Restart => "restart",
+            Self::Enable => "enable",
+            Self::Disable => "disable",
         }
     }
 }
@@ -985,29 +2319,53 @@ struct SystemdAction {
     service: StackString,
 }
 
-#[derive(RwebResponse)]
-#[response(
-    description = "Systemd Action Output",
-    status = "CREATED",
-    content = "html"
-)]
-struct SystemdActionResponse(HtmlBase<StackString, Error>);
-
 #[post("/aws/systemd_action")]
 #[openapi(description = "Perform Systemd Action")]
 pub async fn systemd_action(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     query: Query<SystemdAction>,
-) -> WarpResult<SystemdActionResponse> {
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([ResourceType::SystemD]);
     let query = query.into_inner();
-    let output = data
-        .aws
-        .systemd
-        .service_action(query.action.as_str(), &query.service)
-        .await
-        .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new(output).into())
+    let systemd = &data.aws().systemd;
+    let output = match query.action {
+        SystemdActions::Enable | SystemdActions::Disable => {
+            if !systemd.is_managed(&query.service) {
+                return Err(Error::BadRequest(format_sstr!(
+                    "{} is not a configured unit",
+                    query.service
+                ))
+                .into());
+            }
+            let enable = matches!(query.action, SystemdActions::Enable);
+            systemd.unit_file_action(enable, &query.service).await
+        }
+        _ => {
+            systemd
+                .service_action(query.action.as_str(), &query.service)
+                .await
+        }
+    }
+    .map_err(Into::<Error>::into)?;
+    let output = if let SystemdActions::Start | SystemdActions::Restart = query.action {
+        let probes = health_probes(&data.aws().config.systemd_health_checks);
+        let outcome = systemd
+            .check_health(&query.service, probes.get(&query.service))
+            .await
+            .map_err(Into::<Error>::into)?;
+        format_sstr!("{output}\n{} {outcome}", query.service)
+    } else {
+        output
+    };
+    Ok(MutationResponse::new(
+        json,
+        "systemd_action",
+        query.service,
+        MutationStatus::Finished,
+        output,
+    ))
 }
 
 #[derive(RwebResponse)]
@@ -1024,31 +2382,277 @@ pub async fn systemd_restart_all(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
 ) -> WarpResult<SystemdRestartAllResponse> {
-    let mut output = Vec::new();
-    let blacklist_service = &["nginx"];
-    let aws_service = "aws-app-http".into();
-    for service in &data.aws.config.systemd_services {
-        if service == &aws_service || blacklist_service.contains(&service.as_str()) {
-            continue;
-        }
-        output.push(
-            data.aws
-                .systemd
-                .service_action("restart", service)
-                .await
-                .map_err(Into::<Error>::into)?,
-        );
-    }
-    if data.aws.config.systemd_services.contains(&aws_service) {
-        spawn(async move {
-            sleep(Duration::from_secs(1)).await;
-            data.aws
-                .systemd
-                .service_action("restart", "aws-app-http")
-                .await
+    let aws = data.aws();
+    aws.cache.invalidate([ResourceType::SystemD]);
+    let order = aws
+        .systemd
+        .restart_plan(&aws.config)
+        .await
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let output = restart_services_report(data, order).await?;
+    Ok(HtmlBase::new(output).into())
+}
+
+/// Restart `order`, leaving the unit serving this request for last: it's
+/// restarted in the background once every other service is healthy
+async fn restart_services_report(data: AppState, order: Vec<StackString>) -> HttpResult<String> {
+    let aws = data.aws();
+    let self_unit = aws.config.systemd_self_unit.clone();
+    let restart_self = order.iter().any(|s| s == &self_unit);
+    let results = aws
+        .systemd
+        .restart_services(&aws.config, order, &[self_unit.as_str()])
+        .await?;
+    let healthy = results.iter().all(|r| r.outcome.is_healthy());
+    let note = if !healthy {
+        let failed: Vec<_> = results
+            .iter()
+            .filter(|r| !r.outcome.is_healthy())
+            .map(|r| r.service.as_str())
+            .collect();
+        Some(format_sstr!(
+            "stopped restart, unhealthy services: {}",
+            failed.join(", ")
+        ))
+    } else if restart_self {
+        let systemd = aws.systemd.clone();
+        let unit = self_unit.clone();
+        data.tasks.spawn_detached("restart_aws_service", move || {
+            let systemd = systemd.clone();
+            let unit = unit.clone();
+            async move {
+                sleep(Duration::from_secs(1)).await;
+                systemd.service_action("restart", &unit).await?;
+                Ok(())
+            }
         });
+        Some(format_sstr!("restarting {self_unit} in the background"))
+    } else {
+        None
+    };
+    systemd_restart_body(results, note)
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct SystemdServiceRequest {
+    #[schema(description = "SystemD Service")]
+    service: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Systemd Dependency Graph", content = "html")]
+struct SystemdDependenciesResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/systemd_dependencies")]
+#[openapi(description = "Show Dependencies Between Systemd Services")]
+pub async fn systemd_dependencies(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<SystemdDependenciesResponse> {
+    let graph = data
+        .aws()
+        .systemd
+        .get_dependency_graph()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = systemd_dependencies_body(graph)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Systemd Restart Preview", content = "html")]
+struct SystemdRestartPreviewResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/systemd_restart_preview")]
+#[openapi(description = "Preview Services Affected by a Restart")]
+pub async fn systemd_restart_preview(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<SystemdServiceRequest>,
+) -> WarpResult<SystemdRestartPreviewResponse> {
+    let query = query.into_inner();
+    let graph = data
+        .aws()
+        .systemd
+        .get_dependency_graph()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let affected = restart_impact(&query.service, &graph).map_err(Into::<Error>::into)?;
+    let body = systemd_restart_preview_body(query.service, affected)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[post("/aws/systemd_restart_dependents")]
+#[openapi(description = "Restart a Systemd Service and its Dependents")]
+pub async fn systemd_restart_dependents(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<SystemdServiceRequest>,
+) -> WarpResult<SystemdRestartAllResponse> {
+    data.aws().cache.invalidate([ResourceType::SystemD]);
+    let query = query.into_inner();
+    let graph = data
+        .aws()
+        .systemd
+        .get_dependency_graph()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let order = restart_impact(&query.service, &graph).map_err(Into::<Error>::into)?;
+    let output = restart_services_report(data, order).await?;
+    Ok(HtmlBase::new(output).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Peek SQS Messages", content = "html")]
+struct SqsPeekResponse(HtmlBase<StackString, Error>);
+
+/// Peeking receives the messages, which counts towards the `maxReceiveCount`
+/// of a redrive policy, so queues with one are only peeked with `force=true`
+#[post("/aws/sqs_peek")]
+#[openapi(
+    description = "Peek at SQS Queue Messages, increments their receive count so may move \
+                   them to the dead letter queue of a queue with a redrive policy"
+)]
+pub async fn sqs_peek(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<SqsPeekRequest>,
+) -> WarpResult<SqsPeekResponse> {
+    let query = query.into_inner();
+    let sqs = &data.aws().sqs;
+    if query.force != Some(true) {
+        let info = sqs
+            .get_queue_info(&query.queue_url)
+            .await
+            .map_err(Into::<Error>::into)?;
+        if let Some(redrive_policy) = &info.redrive_policy {
+            return Err(Error::BadRequest(format_sstr!(
+                "{} has redrive policy {redrive_policy}, peeking increments the receive count \
+                 and can move messages to the dead letter queue, pass force=true to peek anyway",
+                info.queue_name
+            ))
+            .into());
+        }
     }
-    Ok(HtmlBase::new(output.join("\n")).into())
+    let entries: Vec<StackString> = sqs
+        .peek_messages(query.queue_url, 10)
+        .await
+        .map_err(Into::<Error>::into)?
+        .map(|m| {
+            let sent = m
+                .sent_timestamp
+                .map_or_else(StackString::new, |t| format_sstr!("{t}"));
+            format_sstr!("{} {sent} {} {}", m.message_id, m.receive_count, m.body)
+        })
+        .collect();
+    let body = textarea_body(entries, "sqs-messages".into())?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[delete("/aws/sqs_purge")]
+#[openapi(description = "Purge SQS Queue")]
+pub async fn sqs_purge(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<SqsQueueRequest>,
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([ResourceType::Sqs]);
+    let query = query.into_inner();
+    data.aws()
+        .sqs
+        .purge_queue(query.queue_url.clone())
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(MutationResponse::new(
+        json,
+        "sqs_purge",
+        query.queue_url,
+        MutationStatus::Finished,
+        "Purged",
+    )
+    .html_status(StatusCode::NO_CONTENT))
+}
+
+#[delete("/aws/sqs_delete")]
+#[openapi(description = "Delete SQS Queue")]
+pub async fn sqs_delete(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<SqsQueueRequest>,
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([ResourceType::Sqs]);
+    let query = query.into_inner();
+    data.aws()
+        .sqs
+        .delete_queue(query.queue_url.clone())
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(MutationResponse::new(
+        json,
+        "sqs_delete",
+        query.queue_url,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Assigned Volumes to Backup Plan",
+    status = "CREATED",
+    content = "html"
+)]
+struct BackupAssignResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/backup_assign")]
+#[openapi(description = "Assign EBS Volumes to AWS Backup Plan")]
+pub async fn backup_assign(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<BackupAssignRequest>,
+) -> WarpResult<BackupAssignResponse> {
+    data.aws().cache.invalidate([ResourceType::Backup]);
+    let query = query.into_inner();
+    let volumes = query
+        .volumes
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    let selection_id = data
+        .aws()
+        .backup
+        .assign_volumes_to_plan(&data.aws().config, query.plan_id, volumes)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(selection_id).into())
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Invoke Lambda Function",
+    content = "html",
+    status = "CREATED"
+)]
+struct LambdaInvokeResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/lambda_invoke")]
+#[openapi(description = "Invoke Lambda Function with JSON Payload")]
+pub async fn lambda_invoke(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<LambdaInvokeRequest>,
+) -> WarpResult<LambdaInvokeResponse> {
+    let payload = payload.into_inner();
+    let result = data
+        .aws()
+        .lambda
+        .invoke(payload.function_name, &payload.payload)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = lambda_invoke_body(result)?.into();
+    Ok(HtmlBase::new(body).into())
 }
 
 #[derive(RwebResponse)]
@@ -1063,7 +2667,7 @@ pub async fn systemd_logs(
     service: StackString,
 ) -> WarpResult<SystemdLogResponse> {
     let entries: Vec<StackString> = data
-        .aws
+        .aws()
         .systemd
         .get_service_logs(&service)
         .await
@@ -1087,9 +2691,9 @@ pub async fn crontab_logs(
     #[data] data: AppState,
 ) -> WarpResult<CrontabLogResponse> {
     let crontab_path = if crontab_type == "user" {
-        &data.aws.config.user_crontab
+        &data.aws().config.user_crontab
     } else {
-        &data.aws.config.root_crontab
+        &data.aws().config.root_crontab
     };
     let body = if crontab_path.exists() {
         textarea_fixed_size_body(
@@ -1106,6 +2710,241 @@ pub async fn crontab_logs(
     Ok(HtmlBase::new(body).into())
 }
 
+#[derive(RwebResponse)]
+#[response(description = "Edit Crontab", content = "html")]
+struct EditCrontabResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/crontab/edit")]
+#[openapi(description = "Edit the Crontab of the User Running the App")]
+pub async fn edit_crontab(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+) -> WarpResult<EditCrontabResponse> {
+    let text = read_user_crontab().await.map_err(Into::<Error>::into)?;
+    let body = edit_crontab_body(text)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct CrontabData {
+    #[schema(description = "Crontab Text")]
+    pub text: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Crontab Diff", content = "html")]
+struct CrontabPreviewResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/crontab/preview")]
+#[openapi(description = "Validate a Crontab and Diff it Against the Installed One")]
+pub async fn crontab_preview(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    req: Json<CrontabData>,
+) -> WarpResult<CrontabPreviewResponse> {
+    let req = req.into_inner();
+    validate_crontab(&req.text).map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let current = read_user_crontab().await.map_err(Into::<Error>::into)?;
+    let body = crontab_diff_body(crontab_diff(&current, &req.text))?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Crontab Installed",
+    content = "html",
+    status = "CREATED"
+)]
+struct CrontabInstallResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/crontab")]
+#[openapi(description = "Validate and Install the Crontab of the User Running the App")]
+pub async fn install_crontab(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+    req: Json<CrontabData>,
+) -> WarpResult<CrontabInstallResponse> {
+    let req = req.into_inner();
+    validate_crontab(&req.text).map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let diff = install_user_crontab(data.aws().storage.as_ref(), &user.email, &req.text)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = crontab_diff_body(diff)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema, Clone, Copy)]
+enum DockerActions {
+    #[serde(rename = "start")]
+    Start,
+    #[serde(rename = "stop")]
+    Stop,
+    #[serde(rename = "restart")]
+    Restart,
+}
+
+impl From<DockerActions> for ContainerAction {
+    fn from(item: DockerActions) -> Self {
+        match item {
+            DockerActions::Start => Self::Start,
+            DockerActions::Stop => Self::Stop,
+            DockerActions::Restart => Self::Restart,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct DockerAction {
+    #[schema(description = "Docker Action")]
+    action: DockerActions,
+    #[schema(description = "Container Name or ID")]
+    container: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Docker Action Output",
+    status = "CREATED",
+    content = "html"
+)]
+struct DockerActionResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/docker/action")]
+#[openapi(description = "Start, Stop or Restart a Docker Container")]
+pub async fn docker_action(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<DockerAction>,
+) -> WarpResult<DockerActionResponse> {
+    data.aws().cache.invalidate([ResourceType::Docker]);
+    let query = query.into_inner();
+    let action: ContainerAction = query.action.into();
+    data.aws()
+        .docker
+        .container_action(action, &query.container)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(format_sstr!("{action} {}", query.container)).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct DockerLogsQuery {
+    #[schema(description = "Container Name or ID")]
+    container: StackString,
+    #[schema(description = "Number of Lines (default 200)")]
+    tail: Option<usize>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Get Docker Container Logs", content = "html")]
+struct DockerLogsResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/docker/logs")]
+#[openapi(description = "Get Docker Container Logs")]
+pub async fn docker_logs(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<DockerLogsQuery>,
+) -> WarpResult<DockerLogsResponse> {
+    let query = query.into_inner();
+    let entries = data
+        .aws()
+        .docker
+        .container_logs(&query.container, query.tail.unwrap_or(200))
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = textarea_body(entries, "docker-logs".into())?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "ECR Image History", content = "html")]
+struct EcrHistoryResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/ecr/history")]
+#[openapi(description = "Pushes and Tag Movements of an ECR Repository")]
+pub async fn ecr_history(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<EcrHistoryRequest>,
+) -> WarpResult<EcrHistoryResponse> {
+    let repo = query.into_inner().repo;
+    let events = data
+        .aws()
+        .ecr_history(&repo)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = ecr_history_body(repo, events)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct DockerPullQuery {
+    #[schema(description = "ECR Repository")]
+    reponame: StackString,
+    #[schema(description = "Image Tag")]
+    tag: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Docker Pull Output",
+    status = "CREATED",
+    content = "html"
+)]
+struct DockerPullResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/docker/pull")]
+#[openapi(description = "Pull an ECR Image into the Local Docker Daemon")]
+pub async fn docker_pull(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<DockerPullQuery>,
+) -> WarpResult<DockerPullResponse> {
+    let query = query.into_inner();
+    if !data.aws().docker.is_available() {
+        return Err(Error::BadRequest("docker is not available on this host".into()).into());
+    }
+    let status = data
+        .aws()
+        .pull_ecr_image(&query.reponame, &query.tag)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(status).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct EcsRedeployQuery {
+    #[schema(description = "ECS Cluster")]
+    cluster: StackString,
+    #[schema(description = "ECS Service")]
+    service: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "ECS Redeploy Output",
+    status = "CREATED",
+    content = "html"
+)]
+struct EcsRedeployResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/ecs/redeploy")]
+#[openapi(description = "Force a New Deployment of an ECS Service")]
+pub async fn ecs_redeploy(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<EcsRedeployQuery>,
+) -> WarpResult<EcsRedeployResponse> {
+    data.aws().cache.invalidate([ResourceType::Ecs]);
+    let query = query.into_inner();
+    let output = data
+        .aws()
+        .ecs
+        .force_new_deployment(&query.cluster, &query.service)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(output).into())
+}
+
 #[derive(RwebResponse)]
 #[response(description = "Get Inbound Email Detail", content = "html")]
 struct InboundEmailDetailResponse(HtmlBase<String, Error>);
@@ -1116,75 +2955,1598 @@ pub async fn inbound_email_detail(
     #[data] data: AppState,
     id: UuidWrapper,
 ) -> WarpResult<InboundEmailDetailResponse> {
-    let body = if let Some(email) = InboundEmailDB::get_by_id(&data.aws.pool, id.into())
+    let body = if let Some(email) = data
+        .aws()
+        .storage
+        .get_email(id.into())
         .await
         .map_err(Into::<Error>::into)?
     {
-        inbound_email_body(email.text_content, email.html_content, email.raw_email)?
+        let attachments = data
+            .aws()
+            .storage
+            .get_attachments_by_email(email.id)
+            .await
+            .map_err(Into::<Error>::into)?;
+        let sent = data
+            .aws()
+            .storage
+            .get_sent_emails_by_thread(email.thread_id)
+            .await
+            .map_err(Into::<Error>::into)?;
+        inbound_email_body(
+            email.id,
+            email.text_content,
+            email.html_content,
+            email.raw_email,
+            attachments,
+            sent,
+        )?
     } else {
         String::new()
     };
     Ok(HtmlBase::new(body).into())
 }
 
-#[derive(RwebResponse)]
-#[response(
-    description = "Delete Inbound Email",
-    content = "html",
-    status = "NO_CONTENT"
-)]
-struct DeleteEmailResponse(HtmlBase<&'static str, Error>);
-
 #[delete("/aws/inbound-email/{id}")]
 pub async fn inbound_email_delete(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
     id: UuidWrapper,
-) -> WarpResult<DeleteEmailResponse> {
+) -> WarpResult<MutationResponse> {
+    data.aws().cache.invalidate([ResourceType::InboundEmail]);
     let id = id.into();
-    let body = if let Some(email) = InboundEmailDB::get_by_id(&data.aws.pool, id)
+    let (status, message) = if let Some(email) = data
+        .aws()
+        .storage
+        .get_email(id)
         .await
         .map_err(Into::<Error>::into)?
     {
-        InboundEmailDB::delete_entry_by_id(id, &data.aws.pool)
+        data.aws()
+            .storage
+            .delete_email(id)
             .await
             .map_err(Into::<Error>::into)?;
-        data.aws
+        data.aws()
             .s3
             .delete_key(&email.s3_bucket, &email.s3_key)
             .await
             .map_err(Into::<Error>::into)?;
-        "Deleted"
+        (MutationStatus::Deleted, "Deleted")
     } else {
-        "Id Not Found"
+        (MutationStatus::NotFound, "Id Not Found")
     };
-    Ok(HtmlBase::new(body).into())
+    Ok(MutationResponse::new(
+        json,
+        "inbound_email_delete",
+        format_sstr!("{id}"),
+        status,
+        message,
+    ))
 }
 
-#[derive(RwebResponse)]
-#[response(
-    description = "Sync Inbound Email",
-    content = "html",
-    status = "CREATED"
-)]
-struct SyncEmailResponse(HtmlBase<StackString, Error>);
+#[derive(Serialize, Deserialize, Schema)]
+pub struct SpamFeedbackRequest {
+    #[schema(description = "Inbound Email ID")]
+    pub id: UuidWrapper,
+    #[schema(description = "Whether the Email is Spam")]
+    pub spam: bool,
+}
 
-#[post("/aws/inbound-email/sync")]
-pub async fn sync_inboud_email(
+#[post("/aws/inbound-email/spam")]
+#[openapi(description = "Mark an Inbound Email as Spam or Not Spam")]
+pub async fn inbound_email_spam_feedback(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<SpamFeedbackRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    let aws = data.aws();
+    aws.cache.invalidate([ResourceType::InboundEmail]);
+    let id = query.id.into();
+    let updated = aws
+        .storage
+        .set_spam_feedback(id, query.spam)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let (status, message) = if updated == 0 {
+        (MutationStatus::NotFound, "Id Not Found")
+    } else if query.spam {
+        (MutationStatus::Updated, "Marked as Spam")
+    } else {
+        (MutationStatus::Updated, "Marked as Not Spam")
+    };
+    Ok(MutationResponse::new(
+        json,
+        "inbound_email_spam_feedback",
+        format_sstr!("{id}"),
+        status,
+        message,
+    )
+    .html_status(StatusCode::CREATED))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct EmailReplyRequest {
+    #[schema(description = "Reply Text, the Original Email is Quoted Below It")]
+    pub body: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Reply to Inbound Email",
+    content = "html",
+    status = "CREATED"
+)]
+struct EmailReplyResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/inbound-email/{id}/reply")]
+#[openapi(description = "Reply to an Inbound Email via SES")]
+pub async fn inbound_email_reply(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] data: AppState,
-) -> WarpResult<SyncEmailResponse> {
+    id: UuidWrapper,
+    req: Json<EmailReplyRequest>,
+) -> WarpResult<EmailReplyResponse> {
+    let req = req.into_inner();
+    let aws = data.aws();
+    let email = aws
+        .storage
+        .get_email(id.into())
+        .await
+        .map_err(Into::<Error>::into)?
+        .ok_or_else(|| Error::BadRequest("Id Not Found".into()))?;
+    if req.body.trim().is_empty() {
+        return Err(Error::BadRequest("Reply is empty".into()).into());
+    }
+    aws.cache.invalidate([ResourceType::InboundEmail]);
     let sdk_config = aws_config::load_from_env().await;
-    let s3 = S3Instance::new(&sdk_config);
-    let (new_keys, new_attachments) = InboundEmail::sync_db(&data.aws.config, &s3, &data.aws.pool)
+    let ses = SesInstance::new(&sdk_config);
+    let sent = send_reply(&ses, aws.storage.as_ref(), &email, &req.body)
         .await
-        .map_err(Into::<Error>::into)
-        .map(|(k, a)| (k.join("\n"), a.join("\n")))?;
-    let new_records = InboundEmail::parse_dmarc_records(&data.aws.config, &s3, &data.aws.pool)
+        .map_err(Into::<Error>::into)?;
+    let body = format_sstr!("Sent reply to {}", sent.to_address);
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Host Instance Metadata", content = "html")]
+struct InstanceSelfResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/self")]
+#[openapi(description = "EC2 Instance Metadata of the Host Running this App")]
+pub async fn instance_self(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+) -> WarpResult<InstanceSelfResponse> {
+    let metadata = MetadataClient::new()
+        .map_err(Into::<Error>::into)?
+        .get_metadata()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = instance_metadata_body(metadata)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Email Forwarding Rules", content = "html")]
+struct EmailRulesResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/email_rules")]
+#[openapi(description = "Rules Forwarding Inbound Email to External Addresses")]
+pub async fn email_rules(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<EmailRulesResponse> {
+    let rules = data
+        .aws()
+        .storage
+        .get_forward_rules()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = email_rules_body(rules)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct EmailRuleRequest {
+    #[schema(description = "Rule ID (update an existing rule)")]
+    pub id: Option<UuidWrapper>,
+    #[schema(description = "Rule Name")]
+    pub name: Option<StackString>,
+    #[schema(description = "Recipient Regex")]
+    pub recipient_pattern: Option<StackString>,
+    #[schema(description = "Subject Regex")]
+    pub subject_pattern: Option<StackString>,
+    #[schema(description = "Forward To Email Address")]
+    pub forward_to: Option<StackString>,
+    #[schema(description = "Rule Enabled")]
+    pub enabled: Option<bool>,
+}
+
+fn non_empty(s: Option<StackString>) -> Option<StackString> {
+    s.filter(|s| !s.trim().is_empty())
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Save Email Forwarding Rule",
+    content = "html",
+    status = "CREATED"
+)]
+struct SaveEmailRuleResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/email_rules")]
+#[openapi(description = "Create or Update an Email Forwarding Rule")]
+pub async fn save_email_rule(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<EmailRuleRequest>,
+) -> WarpResult<SaveEmailRuleResponse> {
+    let payload = payload.into_inner();
+    let aws = data.aws();
+    let mut rule = if let Some(id) = payload.id {
+        let id = id.into();
+        let mut rule = aws
+            .storage
+            .get_forward_rule(id)
+            .await
+            .map_err(Into::<Error>::into)?
+            .ok_or_else(|| Error::BadRequest(format_sstr!("No rule {id}")))?;
+        if let Some(name) = non_empty(payload.name) {
+            rule.name = name;
+        }
+        if payload.recipient_pattern.is_some() {
+            rule.recipient_pattern = non_empty(payload.recipient_pattern);
+        }
+        if payload.subject_pattern.is_some() {
+            rule.subject_pattern = non_empty(payload.subject_pattern);
+        }
+        if let Some(forward_to) = non_empty(payload.forward_to) {
+            rule.forward_to = forward_to;
+        }
+        rule
+    } else {
+        let name =
+            non_empty(payload.name).ok_or_else(|| Error::BadRequest("Rule needs a name".into()))?;
+        let forward_to = non_empty(payload.forward_to)
+            .ok_or_else(|| Error::BadRequest("Rule needs a forward_to address".into()))?;
+        EmailForwardRule::new(
+            name,
+            non_empty(payload.recipient_pattern),
+            non_empty(payload.subject_pattern),
+            forward_to,
+        )
+    };
+    if let Some(enabled) = payload.enabled {
+        rule.enabled = enabled;
+    }
+    validate_rule(&rule).map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    aws.storage
+        .upsert_forward_rule(&rule)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(format_sstr!("Saved {}", rule.name)).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct EmailRuleIdRequest {
+    #[schema(description = "Rule ID")]
+    pub id: UuidWrapper,
+}
+
+#[delete("/aws/email_rules")]
+#[openapi(description = "Delete an Email Forwarding Rule")]
+pub async fn delete_email_rule(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<EmailRuleIdRequest>,
+) -> WarpResult<MutationResponse> {
+    let id = query.into_inner().id.into();
+    let deleted = data
+        .aws()
+        .storage
+        .delete_forward_rule(id)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let (status, message) = if deleted == 0 {
+        (MutationStatus::NotFound, "Id Not Found")
+    } else {
+        (MutationStatus::Deleted, "Deleted")
+    };
+    Ok(MutationResponse::new(
+        json,
+        "delete_email_rule",
+        format_sstr!("{id}"),
+        status,
+        message,
+    ))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct EmailRuleTestRequest {
+    #[schema(description = "Inbound Email ID (overrides to_address and subject)")]
+    pub email_id: Option<UuidWrapper>,
+    #[schema(description = "To Address")]
+    pub to_address: Option<StackString>,
+    #[schema(description = "Subject")]
+    pub subject: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Email Forwarding Rules Dry Run", content = "html")]
+struct TestEmailRulesResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/email_rules/test")]
+#[openapi(description = "Show which Forwarding Rules an Email would Match, nothing is Sent")]
+pub async fn test_email_rules(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<EmailRuleTestRequest>,
+) -> WarpResult<TestEmailRulesResponse> {
+    let payload = payload.into_inner();
+    let aws = data.aws();
+    let (to_address, subject) = if let Some(id) = payload.email_id {
+        let id = id.into();
+        let email = aws
+            .storage
+            .get_email(id)
+            .await
+            .map_err(Into::<Error>::into)?
+            .ok_or_else(|| Error::BadRequest(format_sstr!("No email {id}")))?;
+        (email.to_address, email.subject)
+    } else {
+        (
+            payload.to_address.unwrap_or_default(),
+            payload.subject.unwrap_or_default(),
+        )
+    };
+    let rules = aws
+        .storage
+        .get_forward_rules()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let matches = matching_rules(&rules, &to_address, &subject).map_err(Into::<Error>::into)?;
+    let mut body = format_sstr!(
+        "to {to_address} subject {subject}: {} rules match",
+        matches.len()
+    );
+    for rule in matches {
+        body.push_str(&format_sstr!("\n{} -> {}", rule.name, rule.forward_to));
+    }
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Delete Attachments of Deleted Emails",
+    content = "html",
+    status = "CREATED"
+)]
+struct DeleteOrphanedAttachmentsResponse(HtmlBase<StackString, Error>);
+
+#[delete("/aws/inbound-email/attachments")]
+#[openapi(description = "Delete Attachments of Deleted Inbound Emails")]
+pub async fn delete_orphaned_attachments(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<DeleteOrphanedAttachmentsResponse> {
+    let aws = data.aws();
+    let deleted = InboundEmail::delete_orphaned_attachments(&aws.s3, aws.storage.as_ref())
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = format_sstr!(
+        "Deleted {} attachments\n{}",
+        deleted.len(),
+        deleted.join("\n")
+    );
+    Ok(HtmlBase::new(body).into())
+}
+
+#[post("/aws/inbound-email/sync")]
+pub async fn sync_inboud_email(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+) -> WarpResult<MutationResponse> {
+    let aws = data.aws();
+    aws.cache.invalidate([ResourceType::InboundEmail]);
+    let sdk_config = aws_config::load_from_env().await;
+    let s3 = S3Instance::new(&sdk_config).retry_policy((&aws.config).into());
+    let ses = SesInstance::new(&sdk_config);
+    let (new_keys, new_attachments) =
+        InboundEmail::sync_db(&aws.config, &s3, &ses, aws.storage.as_ref())
+            .await
+            .map_err(Into::<Error>::into)
+            .map(|(k, a)| (k.join("\n"), a.join("\n")))?;
+    let new_records = InboundEmail::parse_dmarc_records(&aws.config, &s3, aws.storage.as_ref())
         .await
         .map_err(Into::<Error>::into)?
         .len();
-    let body =
-        format!("keys {new_keys}\n\nattachments {new_attachments}\n dmarc_records {new_records}");
-    Ok(HtmlBase::new(body.into()).into())
+    let message = format_sstr!(
+        "keys {new_keys}\n\nattachments {new_attachments}\n dmarc_records {new_records}"
+    );
+    Ok(MutationResponse::new(
+        json,
+        "sync_inbound_email",
+        "inbound-email",
+        MutationStatus::Finished,
+        message,
+    ))
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Launch Analytics", content = "html")]
+struct LaunchAnalyticsResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/launch_analytics")]
+#[openapi(description = "Historical Launch Analytics")]
+pub async fn launch_analytics(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<LaunchAnalyticsResponse> {
+    data.aws()
+        .sync_launch_history()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let analytics = data
+        .aws()
+        .storage
+        .get_launch_analytics(26, 10)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = launch_analytics_body(analytics)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Launch Progress", content = "html")]
+struct LaunchStatusResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/launch_status")]
+#[openapi(description = "Phases of the Launches of the Last Day")]
+pub async fn launch_status(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<LaunchStatusResponse> {
+    let launches = data
+        .aws()
+        .get_launch_progress(24)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = launch_status_body(launches)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct CostsByTagRequest {
+    #[schema(description = "Tag Key to Attribute Costs on (default Name)")]
+    pub tag: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Estimated Monthly Cost per Tag", content = "html")]
+struct CostsByTagResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/costs_by_tag")]
+#[openapi(description = "Estimated Monthly Cost of Instances, Volumes and Snapshots per Tag")]
+pub async fn costs_by_tag(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<CostsByTagRequest>,
+) -> WarpResult<CostsByTagResponse> {
+    let tag = query.into_inner().tag.unwrap_or_else(|| "Name".into());
+    let costs = data
+        .aws()
+        .get_costs_by_tag(&tag)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = costs_by_tag_body(tag, costs)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct WasteRequest {
+    #[schema(description = "Report Instances Stopped for More Days")]
+    pub stopped_days: Option<i64>,
+    #[schema(description = "Report AMIs Not Launched for More Months")]
+    pub ami_months: Option<i64>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Unused Resources", content = "html")]
+struct WasteResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/waste")]
+#[openapi(description = "Unused Resources and Their Estimated Monthly Cost")]
+pub async fn waste_report(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<WasteRequest>,
+) -> WarpResult<WasteResponse> {
+    let query = query.into_inner();
+    let aws = data.aws();
+    let mut policy = WastePolicy::from(&aws.config);
+    if let Some(days) = query.stopped_days {
+        policy.stopped_instance_days = days;
+    }
+    if let Some(months) = query.ami_months {
+        policy.unused_ami_months = months;
+    }
+    let novnc_started_at = data.novnc.get_started_at().await;
+    let waste = aws
+        .get_waste_report(&policy, novnc_started_at)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = waste_body(waste)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct IamReportRequest {
+    #[schema(description = "Flag Users Without Activity for More Days")]
+    pub inactive_days: Option<i64>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "IAM Credential Report", content = "html")]
+struct IamReportResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/iam_report")]
+#[openapi(description = "IAM Credential Report with Password, Access Key and MFA Usage")]
+pub async fn iam_report(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<IamReportRequest>,
+) -> WarpResult<IamReportResponse> {
+    let aws = data.aws();
+    let inactive_days = query
+        .into_inner()
+        .inactive_days
+        .unwrap_or(aws.config.iam_inactive_days);
+    let entries = aws
+        .get_credential_report()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = iam_report_body(entries, inactive_days)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "S3 Bucket Summary", content = "html")]
+struct BucketSummaryResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/buckets")]
+#[openapi(description = "S3 Buckets with Lifecycle Rules, Encryption and Size")]
+pub async fn bucket_summary(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<BucketSummaryResponse> {
+    let summaries = data
+        .aws()
+        .get_bucket_summaries()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = bucket_summary_body(summaries)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct ReleaseAddressRequest {
+    #[schema(description = "Elastic IP Allocation ID")]
+    pub allocation_id: StackString,
+}
+
+#[delete("/aws/release_address")]
+#[openapi(description = "Release Elastic IP")]
+pub async fn release_address(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<ReleaseAddressRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    data.aws()
+        .ec2
+        .release_elastic_ip(query.allocation_id.clone())
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(MutationResponse::new(
+        json,
+        "release_address",
+        query.allocation_id,
+        MutationStatus::Deleted,
+        "Released",
+    ))
+}
+
+#[derive(RwebResponse)]
+#[response(description = "SES Identities and Receipt Rules", content = "html")]
+struct SesIdentitiesResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/ses/identities")]
+#[openapi(description = "SES Domain Identities, DKIM Status and Receipt Rule Sets")]
+pub async fn ses_identities(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<SesIdentitiesResponse> {
+    let aws = data.aws();
+    let (identities, rule_sets) = try_join!(
+        aws.ses_admin.list_identities(),
+        aws.ses_admin.list_receipt_rule_sets(),
+    )
+    .map_err(Into::<Error>::into)?;
+    let bucket = aws.config.inbound_email_bucket.clone();
+    let body = ses_identities_body(identities, rule_sets, bucket)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct SesIdentityRequest {
+    #[schema(description = "Domain or Email Address")]
+    pub identity: StackString,
+}
+
+#[post("/aws/ses/identities")]
+#[openapi(description = "Start Verification of an SES Domain or Email Identity")]
+pub async fn ses_verify_identity(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<SesIdentityRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    let identity = data
+        .aws()
+        .ses_admin
+        .verify_identity(&query.identity)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let mut body = format_sstr!(
+        "{} verification {}",
+        identity.name,
+        identity.verification_status
+    );
+    for (name, value) in identity.dkim_records() {
+        body.push_str(&format_sstr!("\nCNAME {name} {value}"));
+    }
+    Ok(MutationResponse::new(
+        json,
+        "ses_verify_identity",
+        identity.name,
+        MutationStatus::Started,
+        body,
+    ))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct ReceiptRuleRequest {
+    #[schema(description = "Receipt Rule Set Name")]
+    pub rule_set: StackString,
+    #[schema(description = "Receipt Rule Name")]
+    pub rule_name: StackString,
+    #[schema(description = "Comma Separated Recipients (addresses or domains)")]
+    pub recipients: Option<StackString>,
+}
+
+#[post("/aws/ses/receipt_rule")]
+#[openapi(description = "Add a Receipt Rule Storing Inbound Email in the Inbound Email Bucket")]
+pub async fn ses_create_receipt_rule(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    payload: Json<ReceiptRuleRequest>,
+) -> WarpResult<MutationResponse> {
+    let payload = payload.into_inner();
+    let aws = data.aws();
+    let bucket = aws
+        .config
+        .inbound_email_bucket
+        .as_ref()
+        .ok_or_else(|| Error::BadRequest("No Inbound Email Bucket".into()))?;
+    let recipients: Vec<&str> = payload
+        .recipients
+        .as_ref()
+        .map(|r| {
+            r.split(',')
+                .map(str::trim)
+                .filter(|r| !r.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    aws.ses_admin
+        .create_inbound_rule(&payload.rule_set, &payload.rule_name, &recipients, bucket)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(MutationResponse::new(
+        json,
+        "ses_create_receipt_rule",
+        payload.rule_name,
+        MutationStatus::Created,
+        "Created",
+    ))
+}
+
+#[delete("/aws/ses/receipt_rule")]
+#[openapi(description = "Delete an SES Receipt Rule")]
+pub async fn ses_delete_receipt_rule(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<ReceiptRuleRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    data.aws()
+        .ses_admin
+        .delete_receipt_rule(&query.rule_set, &query.rule_name)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(MutationResponse::new(
+        json,
+        "ses_delete_receipt_rule",
+        query.rule_name,
+        MutationStatus::Deleted,
+        "Deleted",
+    ))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct ReceiptRuleSetRequest {
+    #[schema(description = "Receipt Rule Set Name")]
+    pub rule_set: StackString,
+}
+
+#[post("/aws/ses/receipt_rule_set/activate")]
+#[openapi(description = "Make an SES Receipt Rule Set the Active One")]
+pub async fn ses_activate_rule_set(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<ReceiptRuleSetRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    data.aws()
+        .ses_admin
+        .activate_receipt_rule_set(&query.rule_set)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(MutationResponse::new(
+        json,
+        "ses_activate_rule_set",
+        query.rule_set,
+        MutationStatus::Finished,
+        "Activated",
+    ))
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Background Tasks", content = "html")]
+struct TasksResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/tasks")]
+#[openapi(description = "Status of Background Tasks")]
+pub async fn tasks(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<TasksResponse> {
+    let body = tasks_body(data.tasks.list_tasks())?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct AccountRequest {
+    #[schema(description = "Account Profile Name, omit for the default account")]
+    pub account: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Switched Account", content = "html", status = "CREATED")]
+struct AccountResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/account")]
+#[openapi(description = "Switch Active AWS Account")]
+pub async fn switch_account(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<AccountRequest>,
+) -> WarpResult<AccountResponse> {
+    let account = payload.into_inner().account.filter(|a| !a.is_empty());
+    data.set_account(account.as_deref())
+        .await
+        .map_err(Into::<Error>::into)?;
+    let account = account.unwrap_or_else(|| "default".into());
+    Ok(HtmlBase::new(format_sstr!("switched to {account}")).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct ThemeRequest {
+    #[schema(description = "Theme, light or dark")]
+    pub theme: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Saved Theme", content = "html", status = "CREATED")]
+struct ThemeResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/theme")]
+#[openapi(description = "Save the Index Page Theme for the Logged In User")]
+pub async fn set_theme(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<ThemeRequest>,
+) -> WarpResult<ThemeResponse> {
+    let theme: Theme = payload
+        .into_inner()
+        .theme
+        .parse()
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    data.aws()
+        .storage
+        .set_theme(&user.email, theme.to_str())
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(format_sstr!("theme {theme}")).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "User Preferences", content = "html")]
+struct PreferencesResponse(HtmlBase<String, Error>);
+
+#[get("/aws/preferences")]
+#[openapi(description = "Table and Region Preferences of the Logged In User")]
+pub async fn preferences(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<PreferencesResponse> {
+    let aws = data.aws();
+    let preferences = aws
+        .storage
+        .get_user_preferences(&user.email)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = get_preferences_page(&aws, preferences).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct PreferencesRequest {
+    #[schema(description = "Hidden Column Names by Resource")]
+    pub hidden_columns: HashMap<StackString, Vec<StackString>>,
+    #[schema(
+        description = "Instance Sort Key (state, launch_time, name, instance_type, availability_zone)"
+    )]
+    pub default_sort: Option<StackString>,
+    #[schema(description = "Sort Instances Descending")]
+    pub sort_descending: Option<bool>,
+    #[schema(description = "Rows per Page, All Rows if Empty")]
+    pub rows_per_page: Option<i32>,
+    #[schema(description = "Region Selected When Opening the Index Page")]
+    pub default_region: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Saved Preferences",
+    content = "html",
+    status = "CREATED"
+)]
+struct SavePreferencesResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/preferences")]
+#[openapi(description = "Save Table and Region Preferences of the Logged In User")]
+pub async fn save_preferences(
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<PreferencesRequest>,
+) -> WarpResult<SavePreferencesResponse> {
+    let payload = payload.into_inner();
+    let aws = data.aws();
+    let non_empty = |v: Option<StackString>| v.filter(|v| !v.trim().is_empty());
+    for (resource, columns) in &payload.hidden_columns {
+        let resource: ResourceType = resource
+            .parse()
+            .map_err(|_| Error::BadRequest(format_sstr!("unknown resource {resource}")))?;
+        if let Some(column) = columns
+            .iter()
+            .find(|c| !table_columns(resource).contains(&c.as_str()))
+        {
+            return Err(Error::BadRequest(format_sstr!(
+                "{} has no column {column}",
+                resource.to_str()
+            ))
+            .into());
+        }
+    }
+    let mut preferences = aws
+        .storage
+        .get_user_preferences(&user.email)
+        .await
+        .map_err(Into::<Error>::into)?;
+    preferences
+        .set_hidden_columns(payload.hidden_columns)
+        .map_err(Into::<Error>::into)?;
+    preferences.default_sort = non_empty(payload.default_sort);
+    preferences.sort_descending = payload.sort_descending.unwrap_or(false);
+    preferences.rows_per_page = payload.rows_per_page;
+    preferences.default_region = non_empty(payload.default_region);
+    preferences
+        .validate()
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    if let Some(region) = &preferences.default_region {
+        let regions = aws
+            .ec2
+            .get_all_regions()
+            .await
+            .map_err(Into::<Error>::into)?;
+        if !regions.contains_key(region) {
+            return Err(Error::BadRequest(format_sstr!("unknown region {region}")).into());
+        }
+    }
+    aws.storage
+        .upsert_user_preferences(&preferences)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new("saved preferences".into()).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Webhooks and Delivery Log", content = "html")]
+struct WebhooksResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/webhooks")]
+#[openapi(description = "Webhook Subscriptions and Recent Deliveries")]
+pub async fn webhooks(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<WebhooksResponse> {
+    let aws = data.aws();
+    let (webhooks, deliveries) = try_join!(
+        aws.storage.get_webhooks(),
+        aws.storage.get_recent_deliveries(100)
+    )
+    .map_err(Into::<Error>::into)?;
+    let body = webhooks_body(webhooks, deliveries)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct WebhookRequest {
+    #[schema(description = "Subscriber Url")]
+    pub url: StackString,
+    #[schema(description = "Events (instance.state_changed, spot.fulfilled, email.received)")]
+    pub events: Vec<StackString>,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct WebhookCreated {
+    #[schema(description = "Webhook ID")]
+    pub id: UuidWrapper,
+    #[schema(description = "Subscriber Url")]
+    pub url: StackString,
+    #[schema(description = "Signing Secret, only returned on creation")]
+    pub secret: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Created Webhook", status = "CREATED")]
+struct CreateWebhookResponse(JsonBase<WebhookCreated, Error>);
+
+#[post("/aws/webhooks")]
+#[openapi(description = "Subscribe a Url to Resource Events")]
+pub async fn create_webhook(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<WebhookRequest>,
+) -> WarpResult<CreateWebhookResponse> {
+    let payload = payload.into_inner();
+    let events: Vec<WebhookEvent> = payload
+        .events
+        .iter()
+        .map(|e| e.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let webhook =
+        Webhook::new(&payload.url, &events).map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    data.aws()
+        .storage
+        .insert_webhook(&webhook)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(JsonBase::new(WebhookCreated {
+        id: webhook.id.into(),
+        url: webhook.url,
+        secret: webhook.secret,
+    })
+    .into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct WebhookIdRequest {
+    #[schema(description = "Webhook ID")]
+    pub id: UuidWrapper,
+}
+
+#[delete("/aws/webhooks")]
+#[openapi(description = "Delete a Webhook and its Delivery Log")]
+pub async fn delete_webhook(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<WebhookIdRequest>,
+) -> WarpResult<MutationResponse> {
+    let id = query.into_inner().id.into();
+    let deleted = data
+        .aws()
+        .storage
+        .delete_webhook(id)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let (status, message) = if deleted == 0 {
+        (MutationStatus::NotFound, "Id Not Found")
+    } else {
+        (MutationStatus::Deleted, "Deleted")
+    };
+    Ok(MutationResponse::new(
+        json,
+        "delete_webhook",
+        format_sstr!("{id}"),
+        status,
+        message,
+    ))
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Spot Price Alerts", content = "html")]
+struct PriceAlertsResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/price_alerts")]
+#[openapi(description = "Spot Price Alert Rules")]
+pub async fn price_alerts(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<PriceAlertsResponse> {
+    let alerts = data
+        .aws()
+        .storage
+        .get_price_alerts()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = price_alerts_body(alerts, &data.aws().config.aws_region_name)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct PriceAlertRequest {
+    #[schema(description = "Instance Type")]
+    pub instance_type: StackString,
+    #[schema(description = "Region (defaults to the configured region)")]
+    pub region: Option<StackString>,
+    #[schema(description = "Alert when the spot price drops below this ($/hr)")]
+    pub threshold: f64,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Created Price Alert",
+    content = "html",
+    status = "CREATED"
+)]
+struct CreatePriceAlertResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/price_alerts")]
+#[openapi(description = "Add a Spot Price Alert Rule")]
+pub async fn create_price_alert(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<PriceAlertRequest>,
+) -> WarpResult<CreatePriceAlertResponse> {
+    let payload = payload.into_inner();
+    let region = payload
+        .region
+        .as_ref()
+        .filter(|r| !r.is_empty())
+        .unwrap_or(&data.aws().config.aws_region_name);
+    let alert = PriceAlert::new(&payload.instance_type, region, payload.threshold)
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    data.aws()
+        .storage
+        .insert_price_alert(&alert)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(format_sstr!("added alert {alert}")).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct PriceAlertUpdateRequest {
+    #[schema(description = "Price Alert ID")]
+    pub id: UuidWrapper,
+    #[schema(description = "Enabled")]
+    pub enabled: bool,
+}
+
+#[patch("/aws/price_alerts")]
+#[openapi(description = "Enable or Disable a Spot Price Alert Rule")]
+pub async fn update_price_alert(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<PriceAlertUpdateRequest>,
+) -> WarpResult<MutationResponse> {
+    let query = query.into_inner();
+    let id = query.id.into();
+    let updated = data
+        .aws()
+        .storage
+        .set_price_alert_enabled(id, query.enabled)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let (status, message) = if updated == 0 {
+        (MutationStatus::NotFound, "Id Not Found")
+    } else {
+        (MutationStatus::Updated, "Updated")
+    };
+    Ok(MutationResponse::new(
+        json,
+        "update_price_alert",
+        format_sstr!("{id}"),
+        status,
+        message,
+    )
+    .html_status(StatusCode::OK))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct PriceAlertIdRequest {
+    #[schema(description = "Price Alert ID")]
+    pub id: UuidWrapper,
+}
+
+#[delete("/aws/price_alerts")]
+#[openapi(description = "Delete a Spot Price Alert Rule")]
+pub async fn delete_price_alert(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<PriceAlertIdRequest>,
+) -> WarpResult<MutationResponse> {
+    let id = query.into_inner().id.into();
+    let deleted = data
+        .aws()
+        .storage
+        .delete_price_alert(id)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let (status, message) = if deleted == 0 {
+        (MutationStatus::NotFound, "Id Not Found")
+    } else {
+        (MutationStatus::Deleted, "Deleted")
+    };
+    Ok(MutationResponse::new(
+        json,
+        "delete_price_alert",
+        format_sstr!("{id}"),
+        status,
+        message,
+    ))
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Managed Secrets", content = "html")]
+struct SecretsResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/secrets")]
+#[openapi(
+    description = "Names and Rotation Dates of Secrets and Parameters under the Managed Prefixes"
+)]
+pub async fn secrets(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<SecretsResponse> {
+    let aws = data.aws();
+    let secrets = aws
+        .list_managed_secrets()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = secrets_body(secrets, aws.config.secret_prefixes.clone())?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Host Metrics", content = "html")]
+struct HostResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/host")]
+#[openapi(description = "Load, Memory, Disk and Network Usage of the Host")]
+pub async fn host(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<HostResponse> {
+    let aws = data.aws();
+    let metrics = aws
+        .sysinfo
+        .get_host_metrics(&aws.config.host_ignored_file_systems);
+    let body = host_body(
+        metrics,
+        aws.config.disk_warning_percent,
+        aws.config.disk_critical_percent,
+    )?
+    .into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Disaster Recovery Policies", content = "html")]
+struct DrPoliciesResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/dr")]
+#[openapi(description = "Disaster Recovery Policies and Off-Region Copy Compliance")]
+pub async fn dr_policies(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<DrPoliciesResponse> {
+    let compliance = data
+        .aws()
+        .dr_compliance()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = dr_policies_body(compliance)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct DrPolicyRequest {
+    #[schema(description = "Name tag of the volumes to copy")]
+    pub volume_name: StackString,
+    #[schema(description = "Region the copies are kept in")]
+    pub target_region: StackString,
+    #[schema(description = "Encrypt the copies (default true)")]
+    pub encrypted: Option<bool>,
+    #[schema(description = "KMS Key ID (defaults to the EBS default key)")]
+    pub kms_key_id: Option<StackString>,
+    #[schema(description = "Maximum age of the newest copy in hours")]
+    pub max_age_hours: i32,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Created Disaster Recovery Policy",
+    content = "html",
+    status = "CREATED"
+)]
+struct CreateDrPolicyResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/dr")]
+#[openapi(description = "Add a Disaster Recovery Policy")]
+pub async fn create_dr_policy(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<DrPolicyRequest>,
+) -> WarpResult<CreateDrPolicyResponse> {
+    let payload = payload.into_inner();
+    let policy = DrPolicy::new(
+        &payload.volume_name,
+        &payload.target_region,
+        payload.encrypted.unwrap_or(true),
+        payload.kms_key_id,
+        payload.max_age_hours,
+    )
+    .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    data.aws()
+        .storage
+        .insert_dr_policy(&policy)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(format_sstr!("added policy {policy}")).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct DrPolicyIdRequest {
+    #[schema(description = "Disaster Recovery Policy ID")]
+    pub id: UuidWrapper,
+}
+
+#[delete("/aws/dr")]
+#[openapi(description = "Delete a Disaster Recovery Policy")]
+pub async fn delete_dr_policy(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    #[filter = "accepts_json"] json: bool,
+    query: Query<DrPolicyIdRequest>,
+) -> WarpResult<MutationResponse> {
+    let id = query.into_inner().id.into();
+    let deleted = data
+        .aws()
+        .storage
+        .delete_dr_policy(id)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let (status, message) = if deleted == 0 {
+        (MutationStatus::NotFound, "Id Not Found")
+    } else {
+        (MutationStatus::Deleted, "Deleted")
+    };
+    Ok(MutationResponse::new(
+        json,
+        "delete_dr_policy",
+        format_sstr!("{id}"),
+        status,
+        message,
+    ))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct TerraformDriftRequest {
+    #[schema(description = "Terraform State Path (local path or s3://bucket/key)")]
+    pub path: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Terraform Drift Report", content = "html")]
+struct TerraformDriftResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/terraform_drift")]
+#[openapi(description = "Compare Terraform State with Live Instances, Volumes and DNS Records")]
+pub async fn terraform_drift(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<TerraformDriftRequest>,
+) -> WarpResult<TerraformDriftResponse> {
+    let aws = data.aws();
+    let path = query
+        .into_inner()
+        .path
+        .filter(|p| !p.is_empty())
+        .or_else(|| aws.config.terraform_state.clone());
+    let report = match &path {
+        Some(path) => Some(
+            aws.terraform_drift(Some(path))
+                .await
+                .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?,
+        ),
+        None => None,
+    };
+    let body = terraform_drift_body(report, path)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct InventoryChangesRequest {
+    #[schema(description = "Compare Against the Inventory at (e.g. 24h, 7d, 2024-06-01)")]
+    pub since: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Inventory Changes", content = "html")]
+struct InventoryChangesResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/changes")]
+#[openapi(description = "Resources Created, Deleted or Modified Since an Earlier Inventory")]
+pub async fn inventory_changes(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<InventoryChangesRequest>,
+) -> WarpResult<InventoryChangesResponse> {
+    let since = query
+        .into_inner()
+        .since
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "24h".into());
+    let at = parse_since(&since, OffsetDateTime::now_utc())
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let diff = data
+        .aws()
+        .inventory_changes(at)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = inventory_changes_body(diff, since)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Instance Replacements", content = "html")]
+struct ReplacementsResponse(HtmlBase<StackString, Error>);
+
+async fn replacements_page(aws: &AwsAppInterface) -> Result<String, Error> {
+    let (instances, replacements) = try_join!(
+        aws.ec2.get_all_instances(),
+        aws.storage.get_recent_replacements(50)
+    )?;
+    let instances: Vec<_> = instances
+        .filter(|inst| inst.state == "running")
+        .map(|inst| {
+            let name = inst.tags.get("Name").cloned().unwrap_or_default();
+            (inst.id, name)
+        })
+        .collect();
+    replacements_body(instances, replacements)
+}
+
+#[get("/aws/replacements")]
+#[openapi(description = "Blue/Green Instance Replacements")]
+pub async fn replacements(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<ReplacementsResponse> {
+    let body = replacements_page(&data.aws()).await?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct ReplacementRequest {
+    #[schema(description = "Instance ID to Replace")]
+    pub instance_id: StackString,
+    #[schema(description = "AMI to Launch (defaults to the newest cataloged build)")]
+    pub ami: Option<StackString>,
+    #[schema(description = "DNS Name to Move (defaults to that of the original launch)")]
+    pub dns_name: Option<StackString>,
+    #[schema(description = "Terminate the Old Instance after the Soak Period")]
+    pub terminate_old: Option<bool>,
+    #[schema(description = "Minutes to Keep Both Instances after the DNS Swap (default 30)")]
+    pub soak_minutes: Option<i32>,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Started Instance Replacement",
+    content = "html",
+    status = "CREATED"
+)]
+struct StartReplacementResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/replacements")]
+#[openapi(description = "Replace an Instance with One Launched from the Latest AMI")]
+pub async fn start_replacement(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    payload: Json<ReplacementRequest>,
+) -> WarpResult<StartReplacementResponse> {
+    let payload = payload.into_inner();
+    let aws = data.aws();
+    let non_empty = |s: Option<StackString>| s.filter(|s| !s.trim().is_empty());
+    let ami = non_empty(payload.ami);
+    let dns_name = non_empty(payload.dns_name);
+    aws.start_replacement(
+        &payload.instance_id,
+        ami.as_deref(),
+        dns_name.as_deref(),
+        payload.terminate_old.unwrap_or(false),
+        payload.soak_minutes.unwrap_or(30),
+    )
+    .await
+    .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let body = replacements_page(&aws).await?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct ReplacementIdRequest {
+    #[schema(description = "Replacement ID")]
+    pub id: UuidWrapper,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Cancelled Instance Replacement", content = "html")]
+struct CancelReplacementResponse(HtmlBase<StackString, Error>);
+
+#[post("/aws/replacements/cancel")]
+#[openapi(description = "Cancel an Instance Replacement before the Old Instance is Terminated")]
+pub async fn cancel_replacement(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<ReplacementIdRequest>,
+) -> WarpResult<CancelReplacementResponse> {
+    let aws = data.aws();
+    aws.cancel_replacement(query.into_inner().id.into())
+        .await
+        .map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+    let body = replacements_page(&aws).await?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Service Quota Usage", content = "html")]
+struct QuotasResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/quotas")]
+#[openapi(description = "Usage of vCPU, EBS Storage, Elastic IP and Snapshot Quotas")]
+pub async fn quotas(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+) -> WarpResult<QuotasResponse> {
+    let usages = data
+        .aws()
+        .quota_usage()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = quotas_body(usages)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct TagSearchRequest {
+    #[schema(description = "Tag to Search for as key:value, key=value or key")]
+    pub tag: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Resources Matching a Tag", content = "html")]
+struct TagSearchResponse(HtmlBase<StackString, Error>);
+
+#[get("/aws/search")]
+#[openapi(description = "Search Instances, Volumes, Snapshots and AMIs by Tag")]
+pub async fn tag_search(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] data: AppState,
+    query: Query<TagSearchRequest>,
+) -> WarpResult<TagSearchResponse> {
+    let aws = data.aws();
+    let tag = query.into_inner().tag.filter(|t| !t.trim().is_empty());
+    let results = match &tag {
+        Some(tag) => {
+            let query = TagQuery::parse(tag).map_err(|e| Error::BadRequest(format_sstr!("{e}")))?;
+            let (results, protected) =
+                try_join!(aws.search_tags(&query), aws.get_protected_resources())
+                    .map_err(Into::<Error>::into)?;
+            Some((results, protected))
+        }
+        None => None,
+    };
+    let body = tag_search_body(results, tag)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use time::macros::datetime;
+
+    use crate::routes::{PriceHistoryEntry, SpotForecastEntry, SpotRequestData};
+
+    #[test]
+    fn test_client_types_match_api() -> Result<(), Error> {
+        let mut request =
+            aws_app_client::SpotRequest::new("ami-0001", "m5.large", "0.05", "worker");
+        request.count = Some(2);
+        request.data_volumes = Some(vec!["/dev/xvdb:100:gp3".into()]);
+        let data: SpotRequestData = serde_json::from_value(serde_json::to_value(&request)?)?;
+        assert_eq!(data.instance_type, "m5.large");
+        assert_eq!(data.count, Some(2));
+        assert_eq!(data.data_volumes, request.data_volumes);
+        assert_eq!(data.tenancy, None);
+
+        let forecast = SpotForecastEntry {
+            instance_type: "m5.large".into(),
+            samples: 10,
+            current: 0.03,
+            mean: 0.032,
+            rolling_mean: 0.031,
+            p50: 0.031,
+            p90: 0.04,
+            max: 0.05,
+            volatility: 0.1,
+            ondemand_price: Some(0.096),
+            risk: "low".into(),
+            suggested_bid: 0.044,
+        };
+        let forecast: aws_app_client::SpotForecast =
+            serde_json::from_value(serde_json::to_value(&forecast)?)?;
+        assert_eq!(forecast.risk, "low");
+        assert_eq!(forecast.ondemand_price, Some(0.096));
+
+        let recorded_at = datetime!(2024-06-01 00:05 UTC);
+        let entry = PriceHistoryEntry {
+            instance_type: "m5.large".into(),
+            price_type: "spot".into(),
+            price: 0.031,
+            price_timestamp: datetime!(2024-06-01 00:00 UTC).into(),
+            recorded_at: recorded_at.into(),
+        };
+        let entry: aws_app_client::PriceHistoryEntry =
+            serde_json::from_value(serde_json::to_value(&entry)?)?;
+        assert_eq!(entry.recorded_at, recorded_at);
+        Ok(())
+    }
 }
@@ -0,0 +1,189 @@
+use stack_string::StackString;
+use std::collections::BTreeSet;
+
+use aws_app_lib::resource_type::ResourceType;
+
+/// Header names of the columns the user can hide in the main table of each
+/// resource, columns without a header (action buttons) and columns holding
+/// inputs read by a button in another column are always shown
+#[must_use]
+pub fn table_columns(resource: ResourceType) -> &'static [&'static str] {
+    match resource {
+        ResourceType::Instances => &[
+            "Instance Id",
+            "Public Hostname",
+            "State",
+            "Name",
+            "Instance Type",
+            "Created At",
+            "Availability Zone",
+        ],
+        ResourceType::Reserved => &[
+            "Reserved Instance Id",
+            "Price",
+            "Instance Type",
+            "State",
+            "Availability Zone",
+        ],
+        ResourceType::Spot => &[
+            "Spot Request Id",
+            "Price",
+            "AMI",
+            "Instance Type",
+            "Spot Type",
+            "Status",
+        ],
+        ResourceType::Ami => &[
+            "AMI",
+            "Name",
+            "State",
+            "Snapshot ID",
+            "Base Release",
+            "Build Script",
+            "Build Date",
+            "Lineage",
+        ],
+        ResourceType::Volume => &[
+            "Volume ID",
+            "Availability Zone",
+            "IOPS",
+            "State",
+            "Encrypted",
+            "Tags",
+        ],
+        ResourceType::Snapshot => &[
+            "Snapshot ID",
+            "Size",
+            "State",
+            "Progress",
+            "Encrypted",
+            "Tags",
+            "Copy To Region",
+        ],
+        ResourceType::Ecr => &["ECR Repo", "Tag", "Digest", "Pushed At", "Image Size"],
+        ResourceType::Key => &["Key Name", "Key Fingerprint"],
+        ResourceType::User => &["User ID", "Create Date", "User Name", "Arn", "MFA"],
+        ResourceType::Group => &["Group ID", "Create Date", "Group Name", "Arn"],
+        ResourceType::AccessKey => &["Key ID", "User Name", "Create Date", "Status"],
+        ResourceType::Route53 => &[
+            "Zone ID",
+            "DNS Name",
+            "Type",
+            "TTL",
+            "Values",
+            "Routing",
+            "Health Check",
+        ],
+        ResourceType::SystemD => &["Name", "Status", "Health", "Memory"],
+        ResourceType::Sqs => &[
+            "Queue Name",
+            "Messages",
+            "In Flight",
+            "Delayed",
+            "Visibility Timeout",
+            "Created",
+        ],
+        ResourceType::Lambda => &[
+            "Function",
+            "Runtime",
+            "Memory",
+            "Timeout",
+            "Code Size",
+            "Last Modified",
+            "Logs",
+            "Invoke",
+        ],
+        ResourceType::Acm => &[
+            "Domain",
+            "Alternative Names",
+            "Status",
+            "Type",
+            "In Use",
+            "Expires",
+            "Days Left",
+        ],
+        ResourceType::Docker => &["Name", "Image", "State", "Status", "Ports", "Created"],
+        ResourceType::Ecs => &[
+            "Cluster",
+            "Status",
+            "Services",
+            "Running Tasks",
+            "Pending Tasks",
+            "Container Instances",
+        ],
+        ResourceType::Backup => &[
+            "Backup Plan",
+            "Plan ID",
+            "Last Execution",
+            "Assign Volumes",
+        ],
+        ResourceType::Script | ResourceType::InboundEmail | ResourceType::All => &[],
+    }
+}
+
+/// Columns of a resource table left out when it is rendered
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HiddenColumns(BTreeSet<&'static str>);
+
+impl HiddenColumns {
+    /// The names in `hidden` which are columns of the table of `resource`
+    #[must_use]
+    pub fn new(resource: ResourceType, hidden: &[StackString]) -> Self {
+        Self(
+            table_columns(resource)
+                .iter()
+                .filter(|column| hidden.iter().any(|h| h == *column))
+                .copied()
+                .collect(),
+        )
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether `column` is rendered
+    #[must_use]
+    pub fn show(&self, column: &str) -> bool {
+        !self.0.contains(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use aws_app_lib::resource_type::{ResourceType, ALL_RESOURCES};
+
+    use crate::table_preferences::{table_columns, HiddenColumns};
+
+    #[test]
+    fn test_table_columns() {
+        for resource in ALL_RESOURCES {
+            let columns = table_columns(resource);
+            let unique: HashSet<_> = columns.iter().collect();
+            assert_eq!(unique.len(), columns.len(), "{resource}");
+            assert!(columns.iter().all(|c| !c.is_empty()), "{resource}");
+        }
+        assert!(table_columns(ResourceType::InboundEmail).is_empty());
+        assert!(!table_columns(ResourceType::Volume).contains(&"Size"));
+    }
+
+    #[test]
+    fn test_hidden_columns() {
+        let hidden = HiddenColumns::new(
+            ResourceType::Ami,
+            &["Snapshot ID".into(), "Missing".into(), "".into()],
+        );
+        assert!(!hidden.show("Snapshot ID"));
+        assert!(hidden.show("AMI"));
+        assert!(hidden.show("Missing"));
+        assert_eq!(
+            hidden,
+            HiddenColumns::new(ResourceType::Ami, &["Snapshot ID".into()])
+        );
+        assert!(HiddenColumns::new(ResourceType::Ami, &[]).is_empty());
+        assert!(HiddenColumns::new(ResourceType::Script, &["AMI".into()]).is_empty());
+    }
+}
@@ -0,0 +1,238 @@
+use anyhow::Error;
+use futures::future::join_all;
+use log::{error, warn};
+use parking_lot::Mutex;
+use stack_string::{format_sstr, StackString};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tokio::{
+    task::{spawn, JoinHandle},
+    time::{sleep, timeout},
+};
+use uuid::Uuid;
+
+use crate::metrics::record_background_task;
+
+/// Number of finished tasks kept around for `/aws/tasks`
+const MAX_FINISHED_TASKS: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+    Running,
+    Retrying(StackString),
+    Succeeded,
+    Failed(StackString),
+}
+
+impl TaskStatus {
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed(_))
+    }
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Running => f.write_str("running"),
+            Self::Retrying(e) => write!(f, "retrying ({e})"),
+            Self::Succeeded => f.write_str("succeeded"),
+            Self::Failed(e) => write!(f, "failed ({e})"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskInfo {
+    pub id: Uuid,
+    pub name: StackString,
+    pub status: TaskStatus,
+    pub attempts: usize,
+    pub started: OffsetDateTime,
+    pub finished: Option<OffsetDateTime>,
+}
+
+/// Keeps track of background jobs spawned by request handlers, so their
+/// progress can be inspected and they can be drained on shutdown
+#[derive(Clone, Default)]
+pub struct TaskSupervisor {
+    tasks: Arc<Mutex<BTreeMap<Uuid, TaskInfo>>>,
+    handles: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    shutting_down: Arc<AtomicBool>,
+}
+
+impl fmt::Debug for TaskSupervisor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TaskSupervisor")
+    }
+}
+
+impl TaskSupervisor {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn a job that is awaited on shutdown, failures are retried up to
+    /// `max_retries` times with exponential backoff
+    pub fn spawn<F, Fut>(&self, name: &str, max_retries: usize, f: F) -> Uuid
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        let (id, handle) = self.spawn_impl(name, max_retries, f);
+        let mut handles = self.handles.lock();
+        handles.retain(|h| !h.is_finished());
+        handles.push(handle);
+        id
+    }
+
+    /// Spawn a job that is tracked but not awaited on shutdown, e.g. one that
+    /// restarts this service
+    pub fn spawn_detached<F, Fut>(&self, name: &str, f: F) -> Uuid
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.spawn_impl(name, 0, f).0
+    }
+
+    fn spawn_impl<F, Fut>(&self, name: &str, max_retries: usize, f: F) -> (Uuid, JoinHandle<()>)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        if self.shutting_down.load(Ordering::SeqCst) {
+            warn!("spawning {name} during shutdown");
+        }
+        let id = Uuid::new_v4();
+        let info = TaskInfo {
+            id,
+            name: name.into(),
+            status: TaskStatus::Running,
+            attempts: 0,
+            started: OffsetDateTime::now_utc(),
+            finished: None,
+        };
+        self.tasks.lock().insert(id, info);
+        let tasks = self.tasks.clone();
+        let name: StackString = name.into();
+        let handle = spawn(async move {
+            let mut attempt = 0;
+            let status = loop {
+                attempt += 1;
+                if let Some(info) = tasks.lock().get_mut(&id) {
+                    info.attempts = attempt;
+                }
+                match f().await {
+                    Ok(()) => break TaskStatus::Succeeded,
+                    Err(e) if attempt <= max_retries => {
+                        warn!("{name} attempt {attempt} failed {e}");
+                        if let Some(info) = tasks.lock().get_mut(&id) {
+                            info.status = TaskStatus::Retrying(format_sstr!("{e}"));
+                        }
+                        sleep(Duration::from_secs(1 << attempt.min(6))).await;
+                    }
+                    Err(e) => {
+                        error!("{name} failed {e}");
+                        break TaskStatus::Failed(format_sstr!("{e}"));
+                    }
+                }
+            };
+            record_background_task(&name, status == TaskStatus::Succeeded);
+            let mut tasks = tasks.lock();
+            if let Some(info) = tasks.get_mut(&id) {
+                info.status = status;
+                info.finished = Some(OffsetDateTime::now_utc());
+            }
+            prune_finished(&mut tasks);
+        });
+        (id, handle)
+    }
+
+    /// Tasks sorted by start time, most recent first
+    #[must_use]
+    pub fn list_tasks(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<_> = self.tasks.lock().values().cloned().collect();
+        tasks.sort_by(|a, b| b.started.cmp(&a.started));
+        tasks
+    }
+
+    /// Wait for outstanding tasks to finish, giving up after `max_wait`
+    pub async fn drain(&self, max_wait: Duration) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        let handles: Vec<_> = self.handles.lock().drain(..).collect();
+        if handles.is_empty() {
+            return;
+        }
+        let running = handles.len();
+        if timeout(max_wait, join_all(handles)).await.is_err() {
+            error!("timed out waiting for {running} background tasks");
+        }
+    }
+}
+
+fn prune_finished(tasks: &mut BTreeMap<Uuid, TaskInfo>) {
+    let mut finished: Vec<_> = tasks
+        .values()
+        .filter_map(|t| t.finished.map(|f| (f, t.id)))
+        .collect();
+    if finished.len() <= MAX_FINISHED_TASKS {
+        return;
+    }
+    finished.sort();
+    let excess = finished.len() - MAX_FINISHED_TASKS;
+    for (_, id) in finished.into_iter().take(excess) {
+        tasks.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{format_err, Error};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use crate::task_supervisor::{TaskStatus, TaskSupervisor};
+
+    #[tokio::test]
+    async fn test_task_supervisor_retry() -> Result<(), Error> {
+        let supervisor = TaskSupervisor::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let id = supervisor.spawn("test_retry", 1, {
+            let calls = calls.clone();
+            move || {
+                let calls = calls.clone();
+                async move {
+                    if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                        Err(format_err!("transient"))
+                    } else {
+                        Ok(())
+                    }
+                }
+            }
+        });
+        supervisor.drain(Duration::from_secs(10)).await;
+        let tasks = supervisor.list_tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, id);
+        assert_eq!(tasks[0].attempts, 2);
+        assert_eq!(tasks[0].status, TaskStatus::Succeeded);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+}
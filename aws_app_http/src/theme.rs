@@ -0,0 +1,153 @@
+use anyhow::{format_err, Error};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::{fmt, path::Path, str::FromStr};
+
+use aws_app_lib::config::Config;
+
+const DEFAULT_STYLE: &str = include_str!("../../templates/style.css");
+const DEFAULT_SCRIPTS: &str = include_str!("../../templates/scripts.js");
+
+static TEMPLATES: OnceCell<Templates> = OnceCell::new();
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl Theme {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Light => "light",
+            Self::Dark => "dark",
+        }
+    }
+
+    /// Class set on `body`, the dark styles are scoped to `.theme-dark`
+    #[must_use]
+    pub fn body_class(self) -> &'static str {
+        match self {
+            Self::Light => "theme-light",
+            Self::Dark => "theme-dark",
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for Theme {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "light" => Ok(Self::Light),
+            "dark" => Ok(Self::Dark),
+            _ => Err(format_err!("{s} is not a Theme")),
+        }
+    }
+}
+
+/// Stylesheet and scripts inlined into the index page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Templates {
+    pub style: StackString,
+    pub scripts: StackString,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self {
+            style: DEFAULT_STYLE.into(),
+            scripts: DEFAULT_SCRIPTS.into(),
+        }
+    }
+}
+
+impl Templates {
+    /// Files missing from `directory` fall back to the built-in template
+    /// # Errors
+    /// Returns error if `directory` doesn't exist or a file can't be read
+    pub fn from_directory(directory: &Path) -> Result<Self, Error> {
+        if !directory.is_dir() {
+            return Err(format_err!(
+                "template directory {} does not exist",
+                directory.display()
+            ));
+        }
+        let read = |name: &str, default: &str| -> Result<StackString, Error> {
+            let path = directory.join(name);
+            if path.exists() {
+                Ok(std::fs::read_to_string(&path)?.into())
+            } else {
+                Ok(default.into())
+            }
+        };
+        Ok(Self {
+            style: read("style.css", DEFAULT_STYLE)?,
+            scripts: read("scripts.js", DEFAULT_SCRIPTS)?,
+        })
+    }
+}
+
+/// Load `config.template_directory` once at startup, later calls are no-ops
+/// # Errors
+/// Returns error if the template directory can't be read
+pub fn load_templates(config: &Config) -> Result<(), Error> {
+    let templates = match &config.template_directory {
+        Some(directory) => Templates::from_directory(directory)?,
+        None => Templates::default(),
+    };
+    TEMPLATES.set(templates).ok();
+    Ok(())
+}
+
+fn templates() -> &'static Templates {
+    TEMPLATES.get_or_init(Templates::default)
+}
+
+#[must_use]
+pub fn stylesheet() -> &'static str {
+    templates().style.as_str()
+}
+
+#[must_use]
+pub fn scripts() -> &'static str {
+    templates().scripts.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use std::fs;
+    use tempfile::TempDir;
+
+    use crate::theme::{Templates, Theme, DEFAULT_SCRIPTS};
+
+    #[test]
+    fn test_theme() -> Result<(), Error> {
+        assert_eq!("dark".parse::<Theme>()?, Theme::Dark);
+        assert_eq!(Theme::Light.to_string(), "light");
+        assert_eq!(Theme::default().body_class(), "theme-light");
+        assert!("blue".parse::<Theme>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_templates_from_directory() -> Result<(), Error> {
+        let directory = TempDir::new()?;
+        fs::write(directory.path().join("style.css"), "body {color: red;}")?;
+        let templates = Templates::from_directory(directory.path())?;
+        assert_eq!(templates.style, "body {color: red;}");
+        assert_eq!(templates.scripts, DEFAULT_SCRIPTS);
+        assert!(Templates::from_directory(&directory.path().join("missing")).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{format_err, Error};
+use stack_string::StackString;
+use std::str::FromStr;
+
+use crate::config::Config;
+
+/// A named account reached by assuming `role_arn`, parsed from
+/// `account_profiles` entries of the form
+/// `name=role_arn;external_id=...;region=...`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountProfile {
+    pub name: StackString,
+    pub role_arn: StackString,
+    pub external_id: Option<StackString>,
+    pub region: Option<StackString>,
+}
+
+impl AccountProfile {
+    /// # Errors
+    /// Returns error if an entry is malformed
+    pub fn all(config: &Config) -> Result<Vec<Self>, Error> {
+        config.account_profiles.iter().map(|s| s.parse()).collect()
+    }
+
+    /// # Errors
+    /// Returns error if no profile named `name` exists
+    pub fn from_config(config: &Config, name: &str) -> Result<Self, Error> {
+        Self::all(config)?
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format_err!("No account profile {name}"))
+    }
+}
+
+impl FromStr for AccountProfile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, rest) = s
+            .split_once('=')
+            .ok_or_else(|| format_err!("Invalid account profile {s}"))?;
+        let mut fields = rest.split(';');
+        let role_arn = fields
+            .next()
+            .filter(|r| r.starts_with("arn:"))
+            .ok_or_else(|| format_err!("Invalid role arn in account profile {s}"))?;
+        let mut profile = Self {
+            name: name.trim().into(),
+            role_arn: role_arn.trim().into(),
+            external_id: None,
+            region: None,
+        };
+        for field in fields {
+            match field.split_once('=') {
+                Some(("external_id", v)) => profile.external_id = Some(v.trim().into()),
+                Some(("region", v)) => profile.region = Some(v.trim().into()),
+                _ => return Err(format_err!("Invalid field {field} in account profile {s}")),
+            }
+        }
+        Ok(profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::account_profile::AccountProfile;
+
+    #[test]
+    fn test_parse_account_profile() -> Result<(), Error> {
+        let profile: AccountProfile =
+            "prod=arn:aws:iam::123456789012:role/admin;external_id=xyz;region=us-west-2".parse()?;
+        assert_eq!(profile.name, "prod");
+        assert_eq!(profile.role_arn, "arn:aws:iam::123456789012:role/admin");
+        assert_eq!(profile.external_id.as_deref(), Some("xyz"));
+        assert_eq!(profile.region.as_deref(), Some("us-west-2"));
+
+        let profile: AccountProfile = "dev=arn:aws:iam::210987654321:role/dev".parse()?;
+        assert_eq!(profile.external_id, None);
+        assert!("dev".parse::<AccountProfile>().is_err());
+        assert!("dev=arn:aws:iam::210987654321:role/dev;foo=bar"
+            .parse::<AccountProfile>()
+            .is_err());
+        Ok(())
+    }
+}
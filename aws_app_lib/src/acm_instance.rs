@@ -0,0 +1,203 @@
+use anyhow::Error;
+use aws_config::SdkConfig;
+use aws_sdk_acm::{
+    types::{CertificateSummary, Filters, KeyAlgorithm},
+    Client as AcmClient,
+};
+use aws_types::region::Region;
+use serde::Serialize;
+use stack_string::StackString;
+use std::fmt;
+use time::OffsetDateTime;
+
+/// Certificates expiring within this many days are flagged on the dashboard
+/// unless `certificate_expiry_days` is set
+pub const DEFAULT_EXPIRY_DAYS: i64 = 30;
+
+#[derive(Clone)]
+pub struct AcmInstance {
+    acm_client: AcmClient,
+}
+
+impl fmt::Debug for AcmInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AcmInstance")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CertificateInfo {
+    pub certificate_arn: StackString,
+    pub domain_name: StackString,
+    pub alternative_names: Vec<StackString>,
+    pub status: StackString,
+    pub certificate_type: StackString,
+    pub in_use: bool,
+    pub not_after: Option<OffsetDateTime>,
+}
+
+impl CertificateInfo {
+    fn from_summary(summary: CertificateSummary) -> Option<Self> {
+        let domain_name: StackString = summary.domain_name?.into();
+        let alternative_names = summary
+            .subject_alternative_name_summaries
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|name| name.as_str() != domain_name.as_str())
+            .map(Into::into)
+            .collect();
+        Some(Self {
+            certificate_arn: summary.certificate_arn?.into(),
+            domain_name,
+            alternative_names,
+            status: summary
+                .status
+                .map_or_else(|| "UNKNOWN".into(), |s| s.as_str().into()),
+            certificate_type: summary
+                .r#type
+                .map_or_else(StackString::new, |t| t.as_str().into()),
+            in_use: summary.in_use.unwrap_or(false),
+            not_after: summary
+                .not_after
+                .and_then(|d| OffsetDateTime::from_unix_timestamp(d.secs()).ok()),
+        })
+    }
+
+    /// Whole days from `now` until the certificate expires, negative once it
+    /// has expired, `None` for certificates that were never issued
+    #[must_use]
+    pub fn days_until_expiry(&self, now: OffsetDateTime) -> Option<i64> {
+        self.not_after.map(|t| (t - now).whole_days())
+    }
+
+    /// Issued certificates expiring (or already expired) within `days` of
+    /// `now`
+    #[must_use]
+    pub fn expires_within(&self, days: i64, now: OffsetDateTime) -> bool {
+        self.status != "REVOKED"
+            && self
+                .days_until_expiry(now)
+                .map_or(false, |remaining| remaining < days)
+    }
+}
+
+impl AcmInstance {
+    #[must_use]
+    pub fn new(sdk_config: &SdkConfig) -> Self {
+        Self {
+            acm_client: AcmClient::from_conf(sdk_config.into()),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
+        let region: String = region.as_ref().into();
+        let region = Region::new(region);
+        let conf = self.acm_client.config().to_builder().region(region).build();
+        self.acm_client = AcmClient::from_conf(conf);
+        Ok(())
+    }
+
+    /// Every certificate of every key type, sorted by expiry with
+    /// certificates that were never issued last
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_certificates(&self) -> Result<Vec<CertificateInfo>, Error> {
+        // acm only lists rsa 1024/2048 certificates unless key types are given
+        let includes = Filters::builder()
+            .set_key_types(Some(
+                KeyAlgorithm::values()
+                    .iter()
+                    .map(|k| KeyAlgorithm::from(*k))
+                    .collect(),
+            ))
+            .build();
+        let mut certificates = Vec::new();
+        let mut next_token = None;
+        loop {
+            let result = self
+                .acm_client
+                .list_certificates()
+                .includes(includes.clone())
+                .set_next_token(next_token)
+                .send()
+                .await?;
+            certificates.extend(
+                result
+                    .certificate_summary_list
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(CertificateInfo::from_summary),
+            );
+            next_token = result.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        certificates.sort_by_key(|c| (c.not_after.is_none(), c.not_after));
+        Ok(certificates)
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn expiring_certificates(&self, days: i64) -> Result<Vec<CertificateInfo>, Error> {
+        let now = OffsetDateTime::now_utc();
+        let certificates = self.list_certificates().await?;
+        Ok(certificates
+            .into_iter()
+            .filter(|c| c.expires_within(days, now))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use time::{macros::datetime, Duration};
+
+    use crate::acm_instance::{AcmInstance, CertificateInfo, DEFAULT_EXPIRY_DAYS};
+
+    fn certificate(status: &str, days: Option<i64>) -> CertificateInfo {
+        let now = datetime!(2024-06-01 00:00 UTC);
+        CertificateInfo {
+            certificate_arn: "arn:aws:acm:us-east-1:123456789012:certificate/abc".into(),
+            domain_name: "novnc.example.com".into(),
+            alternative_names: Vec::new(),
+            status: status.into(),
+            certificate_type: "AMAZON_ISSUED".into(),
+            in_use: true,
+            not_after: days.map(|d| now + Duration::days(d)),
+        }
+    }
+
+    #[test]
+    fn test_expires_within() {
+        let now = datetime!(2024-06-01 00:00 UTC);
+        let soon = certificate("ISSUED", Some(10));
+        assert_eq!(soon.days_until_expiry(now), Some(10));
+        assert!(soon.expires_within(DEFAULT_EXPIRY_DAYS, now));
+
+        let later = certificate("ISSUED", Some(90));
+        assert!(!later.expires_within(DEFAULT_EXPIRY_DAYS, now));
+
+        let expired = certificate("EXPIRED", Some(-3));
+        assert_eq!(expired.days_until_expiry(now), Some(-3));
+        assert!(expired.expires_within(DEFAULT_EXPIRY_DAYS, now));
+
+        assert!(!certificate("PENDING_VALIDATION", None).expires_within(DEFAULT_EXPIRY_DAYS, now));
+        assert!(!certificate("REVOKED", Some(5)).expires_within(DEFAULT_EXPIRY_DAYS, now));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_list_certificates() -> Result<(), Error> {
+        let sdk_config = aws_config::load_from_env().await;
+        let acm = AcmInstance::new(&sdk_config);
+        let certificates = acm.list_certificates().await?;
+        assert!(certificates
+            .windows(2)
+            .all(|w| w[1].not_after.is_none() || w[0].not_after <= w[1].not_after));
+        Ok(())
+    }
+}
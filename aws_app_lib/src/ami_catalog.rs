@@ -0,0 +1,231 @@
+use anyhow::Error;
+use log::debug;
+use serde_json::Value;
+use stack_string::StackString;
+use std::{collections::HashMap, path::Path};
+use time::OffsetDateTime;
+
+use crate::{
+    ec2_instance::{Ec2Instance, UbuntuImageQuery},
+    models::{AmiCatalogEntry, LaunchHistory},
+    storage::{AmiCatalogRepo, LaunchHistoryRepo, Storage},
+};
+
+/// Ubuntu release of a Canonical image name, e.g. `noble-24.04` for
+/// `ubuntu/images/hvm-ssd-gp3/ubuntu-noble-24.04-amd64-server-20240423`
+#[must_use]
+pub fn base_release_from_name(name: &str) -> Option<StackString> {
+    let image = name.rsplit('/').next()?.strip_prefix("ubuntu-")?;
+    let mut parts = image.splitn(3, '-');
+    let codename = parts.next()?;
+    let version = parts.next()?;
+    let release: StackString = [codename, version].join("-").into();
+    UbuntuImageQuery::new(release.clone(), "amd64")
+        .validate()
+        .ok()
+        .map(|()| release)
+}
+
+/// Build script file name of a launch, taken from its recorded
+/// `SpotRequest`
+fn launch_script(launch: &LaunchHistory) -> Option<StackString> {
+    let script = launch
+        .launch_params
+        .as_ref()?
+        .get("script")
+        .and_then(Value::as_str)?;
+    let fname = Path::new(script).file_name()?.to_string_lossy();
+    if fname.is_empty() {
+        None
+    } else {
+        Some(fname.as_ref().into())
+    }
+}
+
+/// Record the build of `ami_id` from `instance_id` in the catalog. The
+/// parent ami and build script come from the launch history of the
+/// instance, the base release is inherited from a cataloged parent or read
+/// from the name of a Canonical parent.
+/// # Errors
+/// Returns error if db query fails
+pub async fn record_image_build(
+    ec2: &Ec2Instance,
+    storage: &dyn Storage,
+    instance_id: &str,
+    ami_id: &str,
+    name: &str,
+) -> Result<AmiCatalogEntry, Error> {
+    let launch = storage.get_launch_by_instance_id(instance_id).await?;
+    let parent_ami = launch.as_ref().map(|l| l.ami.clone());
+    let build_script = launch.as_ref().and_then(launch_script);
+    let base_release = match &parent_ami {
+        Some(parent) => match storage.get_ami_catalog_entry(parent).await? {
+            Some(entry) => entry.base_release,
+            None => match ec2.get_image_name(parent).await {
+                Ok(parent_name) => base_release_from_name(&parent_name),
+                Err(e) => {
+                    debug!("no name for parent ami {parent}: {e}");
+                    None
+                }
+            },
+        },
+        None => None,
+    };
+    let entry = AmiCatalogEntry {
+        ami_id: ami_id.into(),
+        name: name.into(),
+        base_release,
+        build_script,
+        build_date: OffsetDateTime::now_utc(),
+        parent_ami,
+        instance_id: Some(instance_id.into()),
+    };
+    storage.upsert_ami_catalog_entry(&entry).await?;
+    Ok(entry)
+}
+
+/// Ancestors of `ami_id` recorded in the catalog, closest parent first
+#[must_use]
+pub fn lineage<'a>(
+    catalog: &'a HashMap<StackString, AmiCatalogEntry>,
+    ami_id: &str,
+) -> Vec<&'a AmiCatalogEntry> {
+    let mut ancestors: Vec<&AmiCatalogEntry> = Vec::new();
+    let mut current = catalog.get(ami_id).and_then(|e| e.parent_ami.as_ref());
+    while let Some(parent) = current {
+        match catalog.get(parent) {
+            Some(entry) if entry.ami_id != ami_id && !ancestors.contains(&entry) => {
+                ancestors.push(entry);
+                current = entry.parent_ami.as_ref();
+            }
+            _ => break,
+        }
+    }
+    ancestors
+}
+
+/// Newest cataloged build to replace instances of `ami_id` with: images
+/// built on top of it, or built with the same script from the same base
+/// release when it's cataloged itself. `None` if there is nothing newer.
+#[must_use]
+pub fn latest_build<'a>(
+    catalog: &'a HashMap<StackString, AmiCatalogEntry>,
+    ami_id: &str,
+) -> Option<&'a AmiCatalogEntry> {
+    let current = catalog.get(ami_id);
+    let same_build = |entry: &AmiCatalogEntry| {
+        current.map_or(false, |current| {
+            current.build_script.is_some()
+                && current.base_release.is_some()
+                && entry.build_script == current.build_script
+                && entry.base_release == current.base_release
+        })
+    };
+    catalog
+        .values()
+        .filter(|entry| entry.ami_id != ami_id)
+        .filter(|entry| {
+            same_build(entry)
+                || lineage(catalog, &entry.ami_id)
+                    .iter()
+                    .any(|ancestor| ancestor.ami_id == ami_id)
+                || entry.parent_ami.as_deref() == Some(ami_id)
+        })
+        .filter(|entry| current.map_or(true, |current| entry.build_date > current.build_date))
+        .max_by_key(|entry| entry.build_date)
+}
+
+#[cfg(test)]
+mod tests {
+    use stack_string::StackString;
+    use std::collections::HashMap;
+    use time::{Duration, OffsetDateTime};
+
+    use crate::{
+        ami_catalog::{base_release_from_name, latest_build, lineage},
+        models::AmiCatalogEntry,
+    };
+
+    fn entry(ami_id: &str, parent_ami: Option<&str>) -> AmiCatalogEntry {
+        AmiCatalogEntry {
+            ami_id: ami_id.into(),
+            name: ami_id.into(),
+            base_release: Some("noble-24.04".into()),
+            build_script: Some("build_rust.sh".into()),
+            build_date: OffsetDateTime::now_utc(),
+            parent_ami: parent_ami.map(Into::into),
+            instance_id: None,
+        }
+    }
+
+    #[test]
+    fn test_base_release_from_name() {
+        assert_eq!(
+            base_release_from_name(
+                "ubuntu/images/hvm-ssd-gp3/ubuntu-noble-24.04-amd64-server-20240423"
+            ),
+            Some("noble-24.04".into())
+        );
+        assert_eq!(
+            base_release_from_name(
+                "ubuntu-minimal/images/hvm-ssd/ubuntu-jammy-22.04-arm64-minimal-20240301"
+            ),
+            Some("jammy-22.04".into())
+        );
+        assert_eq!(base_release_from_name("ddboline_rust_tmpfs_2024"), None);
+    }
+
+    #[test]
+    fn test_lineage() {
+        let catalog: HashMap<StackString, AmiCatalogEntry> = vec![
+            entry("ami-c", Some("ami-b")),
+            entry("ami-b", Some("ami-a")),
+            entry("ami-a", Some("ami-ubuntu")),
+            entry("ami-x", Some("ami-y")),
+            entry("ami-y", Some("ami-x")),
+        ]
+        .into_iter()
+        .map(|e| (e.ami_id.clone(), e))
+        .collect();
+
+        let ids: Vec<_> = lineage(&catalog, "ami-c")
+            .into_iter()
+            .map(|e| e.ami_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["ami-b", "ami-a"]);
+
+        let ids: Vec<_> = lineage(&catalog, "ami-x")
+            .into_iter()
+            .map(|e| e.ami_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["ami-y"]);
+        assert!(lineage(&catalog, "ami-ubuntu").is_empty());
+    }
+
+    #[test]
+    fn test_latest_build() {
+        let now = OffsetDateTime::now_utc();
+        let mut entries = vec![
+            entry("ami-a", Some("ami-ubuntu")),
+            entry("ami-b", Some("ami-a")),
+            entry("ami-c", Some("ami-b")),
+            entry("ami-d", Some("ami-ubuntu")),
+            entry("ami-other", Some("ami-ubuntu")),
+        ];
+        for (idx, entry) in entries.iter_mut().enumerate() {
+            entry.build_date = now - Duration::days(10 - idx as i64);
+        }
+        entries[4].build_script = Some("build_other.sh".into());
+        let catalog: HashMap<StackString, AmiCatalogEntry> =
+            entries.into_iter().map(|e| (e.ami_id.clone(), e)).collect();
+
+        let latest = |ami_id: &str| latest_build(&catalog, ami_id).map(|e| e.ami_id.as_str());
+        // descendants of a stock image
+        assert_eq!(latest("ami-ubuntu"), Some("ami-other"));
+        // a rebuild with the same script and release is newer than ami-c
+        assert_eq!(latest("ami-a"), Some("ami-d"));
+        assert_eq!(latest("ami-d"), None);
+        assert_eq!(latest("ami-other"), None);
+        assert_eq!(latest("ami-unknown"), None);
+    }
+}
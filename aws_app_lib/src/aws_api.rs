@@ -0,0 +1,317 @@
+//! Traits over the aws calls that the list views, dashboard and ddns daemon
+//! are built on, implemented by the sdk backed `*Instance` wrappers and by
+//! `MockAws` so that logic can be tested without live credentials.
+use anyhow::{format_err, Error};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use stack_string::StackString;
+use std::{collections::HashMap, net::Ipv4Addr, sync::Arc};
+
+use crate::{
+    ec2_instance::{Ec2Instance, Ec2InstanceInfo},
+    ecr_instance::{EcrInstance, ImageInfo},
+    iam_instance::{IamGroup, IamInstance, IamUser},
+    route53_instance::{DnsRecord, Route53Instance},
+};
+
+#[async_trait]
+pub trait Ec2Api: Send + Sync {
+    /// # Errors
+    /// Returns error if aws api call fails
+    async fn get_all_instances(&self) -> Result<Vec<Ec2InstanceInfo>, Error>;
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    async fn get_availability_zones(&self) -> Result<Vec<StackString>, Error>;
+}
+
+#[async_trait]
+pub trait EcrApi: Send + Sync {
+    /// # Errors
+    /// Returns error if aws api call fails
+    async fn get_all_repositories(&self) -> Result<Vec<StackString>, Error>;
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    async fn get_all_images(&self, reponame: &str) -> Result<Vec<ImageInfo>, Error>;
+}
+
+#[async_trait]
+pub trait IamApi: Send + Sync {
+    /// # Errors
+    /// Returns error if aws api call fails
+    async fn list_users(&self) -> Result<Vec<IamUser>, Error>;
+
+    /// The named user, or the caller when `user_name` is `None`
+    /// # Errors
+    /// Returns error if aws api call fails
+    async fn get_user(&self, user_name: Option<&str>) -> Result<Option<IamUser>, Error>;
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    async fn list_groups(&self) -> Result<Vec<IamGroup>, Error>;
+}
+
+#[async_trait]
+pub trait Route53Api: Send + Sync {
+    /// Public ip of this host
+    /// # Errors
+    /// Returns error if the lookup fails
+    async fn get_ip_address(&self) -> Result<Ipv4Addr, Error>;
+
+    /// A records of every hosted zone, paired with the zone id
+    /// # Errors
+    /// Returns error if aws api call fails
+    async fn list_all_dns_records(&self) -> Result<Vec<(String, DnsRecord)>, Error>;
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    async fn update_dns_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        old_ip: Ipv4Addr,
+        new_ip: Ipv4Addr,
+    ) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl Ec2Api for Ec2Instance {
+    async fn get_all_instances(&self) -> Result<Vec<Ec2InstanceInfo>, Error> {
+        Ok(Ec2Instance::get_all_instances(self).await?.collect())
+    }
+
+    async fn get_availability_zones(&self) -> Result<Vec<StackString>, Error> {
+        Ok(Ec2Instance::get_availability_zones(self)
+            .await?
+            .map(Into::into)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl EcrApi for EcrInstance {
+    async fn get_all_repositories(&self) -> Result<Vec<StackString>, Error> {
+        Ok(EcrInstance::get_all_repositories(self).await?.collect())
+    }
+
+    async fn get_all_images(&self, reponame: &str) -> Result<Vec<ImageInfo>, Error> {
+        Ok(EcrInstance::get_all_images(self, reponame).await?.collect())
+    }
+}
+
+#[async_trait]
+impl IamApi for IamInstance {
+    async fn list_users(&self) -> Result<Vec<IamUser>, Error> {
+        Ok(IamInstance::list_users(self).await?.collect())
+    }
+
+    async fn get_user(&self, user_name: Option<&str>) -> Result<Option<IamUser>, Error> {
+        IamInstance::get_user(self, user_name).await
+    }
+
+    async fn list_groups(&self) -> Result<Vec<IamGroup>, Error> {
+        Ok(IamInstance::list_groups(self).await?.collect())
+    }
+}
+
+#[async_trait]
+impl Route53Api for Route53Instance {
+    async fn get_ip_address(&self) -> Result<Ipv4Addr, Error> {
+        Route53Instance::get_ip_address(self).await
+    }
+
+    async fn list_all_dns_records(&self) -> Result<Vec<(String, DnsRecord)>, Error> {
+        Route53Instance::list_all_dns_records(self).await
+    }
+
+    async fn update_dns_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        old_ip: Ipv4Addr,
+        new_ip: Ipv4Addr,
+    ) -> Result<(), Error> {
+        Route53Instance::update_dns_record(self, zone_id, name, old_ip, new_ip).await
+    }
+}
+
+/// Canned responses for every api trait, `dns_records` is updated in place
+/// by `update_dns_record`
+#[derive(Clone, Debug)]
+pub struct MockAws {
+    pub instances: Vec<Ec2InstanceInfo>,
+    pub availability_zones: Vec<StackString>,
+    pub images: HashMap<StackString, Vec<ImageInfo>>,
+    pub users: Vec<IamUser>,
+    /// `user_id` of the caller, returned by `get_user(None)`
+    pub current_user_id: Option<StackString>,
+    pub groups: Vec<IamGroup>,
+    pub ip_address: Ipv4Addr,
+    pub dns_records: Arc<Mutex<Vec<(String, DnsRecord)>>>,
+}
+
+impl Default for MockAws {
+    fn default() -> Self {
+        Self {
+            instances: Vec::new(),
+            availability_zones: Vec::new(),
+            images: HashMap::new(),
+            users: Vec::new(),
+            current_user_id: None,
+            groups: Vec::new(),
+            ip_address: Ipv4Addr::LOCALHOST,
+            dns_records: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl MockAws {
+    /// Responses loaded from the json fixtures in `tests/data`
+    /// # Errors
+    /// Returns error if a fixture fails to parse
+    pub fn from_fixtures() -> Result<Self, Error> {
+        let instances = serde_json::from_str(include_str!("../../tests/data/ec2_instances.json"))?;
+        let users: Vec<IamUser> =
+            serde_json::from_str(include_str!("../../tests/data/iam_users.json"))?;
+        let groups = serde_json::from_str(include_str!("../../tests/data/iam_groups.json"))?;
+        let images: Vec<ImageInfo> =
+            serde_json::from_str(include_str!("../../tests/data/ecr_images.json"))?;
+        let dns_records =
+            serde_json::from_str(include_str!("../../tests/data/route53_records.json"))?;
+        let mut image_map: HashMap<StackString, Vec<ImageInfo>> = HashMap::new();
+        for image in images {
+            image_map.entry(image.repo.clone()).or_default().push(image);
+        }
+        Ok(Self {
+            instances,
+            availability_zones: vec!["us-east-1a".into(), "us-east-1b".into()],
+            images: image_map,
+            current_user_id: users.first().map(|u| u.user_id.clone()),
+            users,
+            groups,
+            ip_address: Ipv4Addr::new(192, 0, 2, 10),
+            dns_records: Arc::new(Mutex::new(dns_records)),
+        })
+    }
+}
+
+#[async_trait]
+impl Ec2Api for MockAws {
+    async fn get_all_instances(&self) -> Result<Vec<Ec2InstanceInfo>, Error> {
+        Ok(self.instances.clone())
+    }
+
+    async fn get_availability_zones(&self) -> Result<Vec<StackString>, Error> {
+        Ok(self.availability_zones.clone())
+    }
+}
+
+#[async_trait]
+impl EcrApi for MockAws {
+    async fn get_all_repositories(&self) -> Result<Vec<StackString>, Error> {
+        let mut repos: Vec<_> = self.images.keys().cloned().collect();
+        repos.sort();
+        Ok(repos)
+    }
+
+    async fn get_all_images(&self, reponame: &str) -> Result<Vec<ImageInfo>, Error> {
+        Ok(self.images.get(reponame).cloned().unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl IamApi for MockAws {
+    async fn list_users(&self) -> Result<Vec<IamUser>, Error> {
+        Ok(self.users.clone())
+    }
+
+    async fn get_user(&self, user_name: Option<&str>) -> Result<Option<IamUser>, Error> {
+        let user = self.users.iter().find(|u| match user_name {
+            Some(name) => u.user_name == name,
+            None => self.current_user_id.as_ref() == Some(&u.user_id),
+        });
+        Ok(user.cloned())
+    }
+
+    async fn list_groups(&self) -> Result<Vec<IamGroup>, Error> {
+        Ok(self.groups.clone())
+    }
+}
+
+#[async_trait]
+impl Route53Api for MockAws {
+    async fn get_ip_address(&self) -> Result<Ipv4Addr, Error> {
+        Ok(self.ip_address)
+    }
+
+    async fn list_all_dns_records(&self) -> Result<Vec<(String, DnsRecord)>, Error> {
+        Ok(self.dns_records.lock().clone())
+    }
+
+    async fn update_dns_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        old_ip: Ipv4Addr,
+        new_ip: Ipv4Addr,
+    ) -> Result<(), Error> {
+        let old_ip = old_ip.to_string();
+        let name = name.trim_end_matches('.');
+        let mut records = self.dns_records.lock();
+        let (_, record) = records
+            .iter_mut()
+            .find(|(zone, r)| zone == zone_id && r.dnsname == name && r.ip == old_ip)
+            .ok_or_else(|| format_err!("No record found"))?;
+        record.ip = new_ip.to_string();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use std::net::Ipv4Addr;
+
+    use crate::aws_api::{EcrApi, IamApi, MockAws, Route53Api};
+
+    #[tokio::test]
+    async fn test_mock_ecr() -> Result<(), Error> {
+        let mock = MockAws::from_fixtures()?;
+        let repos = mock.get_all_repositories().await?;
+        assert_eq!(repos, vec!["aws_app_rust", "diary_app_rust"]);
+        let images = mock.get_all_images("aws_app_rust").await?;
+        assert_eq!(images.len(), 2);
+        assert!(mock.get_all_images("missing").await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_iam_get_user() -> Result<(), Error> {
+        let mock = MockAws::from_fixtures()?;
+        let user = mock.get_user(Some("ci-deploy")).await?.unwrap();
+        assert_eq!(user.user_id, "AIDAEXAMPLE0000000002");
+        assert!(mock.get_user(Some("nobody")).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_update_dns_record() -> Result<(), Error> {
+        let mock = MockAws::from_fixtures()?;
+        let old_ip = Ipv4Addr::new(192, 0, 2, 1);
+        let new_ip = Ipv4Addr::new(192, 0, 2, 20);
+        mock.update_dns_record("Z0EXAMPLE", "home.example.com.", old_ip, new_ip)
+            .await?;
+        let records = mock.list_all_dns_records().await?;
+        let (_, home) = records
+            .iter()
+            .find(|(_, r)| r.dnsname == "home.example.com")
+            .unwrap();
+        assert_eq!(home.ip, "192.0.2.20");
+        assert!(mock
+            .update_dns_record("Z0EXAMPLE", "home.example.com.", old_ip, new_ip)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -1,41 +1,133 @@
 use anyhow::{format_err, Error};
 use aws_config::SdkConfig;
-use futures::{future::try_join_all, stream::FuturesUnordered, TryStreamExt};
+use bollard::auth::DockerCredentials;
+use futures::{
+    future::{join_all, try_join_all},
+    stream::FuturesUnordered,
+    TryStreamExt,
+};
 use itertools::Itertools;
+use log::{debug, error, info, warn};
+use maplit::hashmap;
 use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::{json, Value};
 use stack_string::{format_sstr, StackString};
 use std::{
-    collections::{HashMap, HashSet},
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::Display,
+    net::Ipv4Addr,
     sync::Arc,
 };
 use stdout_channel::StdoutChannel;
-use time::OffsetDateTime;
+use tempfile::TempDir;
+use time::{Duration, OffsetDateTime};
 use time_tz::OffsetDateTimeExt;
-use tokio::{sync::RwLock, try_join};
+use tokio::{fs, join, sync::RwLock, time::sleep, try_join};
+use uuid::Uuid;
 use walkdir::WalkDir;
 
 use crate::{
+    account_profile::AccountProfile,
+    acm_instance::{AcmInstance, CertificateInfo},
+    ami_catalog::{latest_build, record_image_build},
+    backup_instance::BackupInstance,
+    bucket_summary::{sort_by_size, BucketSummary},
     config::Config,
+    cost_attribution::{attribute_costs, CostRates, ResourceCost},
+    credential_report::CredentialReportEntry,
+    csv_export::{to_csv, ExportResource},
     date_time_wrapper::DateTimeWrapper,
-    ec2_instance::{AmiInfo, Ec2Instance, Ec2InstanceInfo, InstanceRequest, SpotRequest},
-    ecr_instance::EcrInstance,
+    db_backup::DbBackup,
+    decommission::{DecommissionOptions, DecommissionPlan},
+    docker_instance::DockerInstance,
+    dr_policy::{DrAction, DrCompliance, DrPolicy, DR_SOURCE_SNAPSHOT_TAG, DR_SOURCE_VOLUME_TAG},
+    ec2_instance::{
+        validate_public_key, validate_volumes, AmiInfo, Ec2Instance, Ec2InstanceInfo,
+        InstanceRequest, SnapshotInfo, SpotLaunch, SpotRequest,
+    },
+    ecr_history::{repo_timeline, EcrImageEvent, EcrImageHistory},
+    ecr_instance::{EcrInstance, ImageInfo},
+    ecs_instance::EcsInstance,
+    hooks::{HookEvent, Hooks},
     iam_instance::{IamAccessKey, IamInstance, IamUser},
     instance_family::InstanceFamilies,
-    models::{AwsGeneration, InstanceFamily, InstanceList, InstancePricing},
+    instance_filter::InstanceFilter,
+    inventory::{InventoryDiff, InventoryItem, InventorySnapshot},
+    known_hosts::{parse_console_host_keys, KnownHosts},
+    lambda_instance::LambdaInstance,
+    launch_progress::LaunchProgress,
+    models::{
+        AuditLog, AwsGeneration, InstancePricing, LaunchHistory, RECOVERY_EXHAUSTED,
+        RECOVERY_RECOVERED, RECOVERY_SKIPPED,
+    },
+    naming_policy::NamingPolicy,
+    notification::send_notification,
+    output_format::OutputFormat,
     pgpool::PgPool,
-    pricing_instance::PricingInstance,
+    price_forecast::{spot_forecasts, SpotForecast, FORECAST_WINDOW_DAYS},
+    pricing_instance::{PricingInstance, UpdateSource},
+    quota_instance::{ebs_usage, quota_usages, vcpu_usage, QuotaInstance, QuotaUsage, VcpuClass},
+    reencrypt::ReencryptPlan,
+    replacement::{InstanceReplacement, ReplacementState},
+    resource_cache::ResourceCache,
+    resource_graph::ResourceGraph,
     resource_type::ResourceType,
-    route53_instance::{DnsRecord, Route53Instance},
+    route53_instance::{hosted_zone_for, validate_host_name, DnsRecord, Route53Instance},
     s3_instance::S3Instance,
+    schema::schema_version,
     scrape_instance_info::scrape_instance_info,
-    ssh_instance::SSHInstance,
+    secrets_instance::{SecretSummary, SecretsInstance},
+    ses_admin::SesAdminInstance,
+    ses_client::SesInstance,
+    setup_check::{CloudInitStatus, SetupOutcome, SETUP_CHECK_OK, SETUP_DONE},
+    sns_event::Ec2Event,
+    sqs_instance::SqsInstance,
+    ssh_instance::{HostKeyCheck, Multiplex, SSHInstance},
+    storage::{
+        AmiCatalogRepo, AuditLogRepo, DrPolicyRepo, EcrHistoryRepo, InboundEmailRepo,
+        InstanceFamilyRepo, InstanceListRepo, InstancePricingRepo, InstanceReplacementRepo,
+        InventorySnapshotRepo, LaunchHistoryRepo, PriceAlertRepo, ProtectedResourceRepo, Storage,
+        StorageBackend, UpdateStatusRepo,
+    },
+    sts_instance::{IdentityBanner, StsInstance, TemporaryCredentials},
     sysinfo_instance::SysinfoInstance,
     systemd_instance::SystemdInstance,
+    tag_search::{search_tags, TagQuery, TaggedResource},
+    terraform_drift::{ComparedResource, DriftReport, TerraformState},
+    waste::{WasteInventory, WasteItem, WastePolicy},
+    watch::{StateWatcher, WatchRow, CLEAR_SCREEN, WATCH_RESOURCES},
+    webhook::{enqueue_webhooks, ResourceStates, WebhookEvent},
 };
 
 pub static INSTANCE_LIST: Lazy<RwLock<Arc<Vec<Ec2InstanceInfo>>>> =
     Lazy::new(|| RwLock::new(Arc::new(Vec::new())));
+pub static INSTANCE_LIST_UPDATED: Lazy<RwLock<Option<OffsetDateTime>>> =
+    Lazy::new(|| RwLock::new(None));
+
+/// `update_status` task refreshing instance families and types
+pub const UPDATE_TASK_INSTANCES: &str = "instance_data";
+/// `update_status` task refreshing ondemand, spot and reserved prices
+pub const UPDATE_TASK_PRICING: &str = "pricing";
+/// `update_status` task backing up the database to s3
+pub const UPDATE_TASK_DB_BACKUP: &str = "db_backup";
+/// Ttl of the records pointed at newly launched instances
+const LAUNCH_DNS_TTL: i64 = 300;
+/// Seconds between checks for the volumes of a decommissioned instance to
+/// detach
+const DETACH_POLL_SECS: u64 = 5;
+/// Checks before giving up on the volumes of a decommissioned instance
+const DETACH_POLL_LIMIT: usize = 60;
+/// Seconds between polls for a snapshot to complete
+const SNAPSHOT_POLL_SECS: u64 = 15;
+/// Polls before giving up on a snapshot, two hours
+const SNAPSHOT_POLL_LIMIT: usize = 480;
+/// Status of a spot request whose instance was terminated by its owner rather
+/// than interrupted by aws
+const SPOT_TERMINATED_BY_USER: &str = "instance-terminated-by-user";
+/// Unchanged spot prices are recorded at most this often
+const SPOT_RECORD_INTERVAL: Duration = Duration::hours(1);
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct AwsInstancePrice {
@@ -43,16 +135,48 @@ pub struct AwsInstancePrice {
     pub ondemand_price: Option<f64>,
     pub spot_price: Option<f64>,
     pub reserved_price: Option<f64>,
+    /// Change in the ondemand price over the last week
+    pub ondemand_trend: Option<f64>,
     pub ncpu: i32,
     pub memory: f64,
+    pub gpu_count: i32,
+    pub gpu_type: Option<StackString>,
+    pub network_performance: Option<StackString>,
     pub instance_family: InstanceFamilies,
     pub data_url: Option<StackString>,
 }
 
+impl AwsInstancePrice {
+    /// `ondemand_trend` as e.g. `+$0.0040 (+4.2%)`, empty without a week of
+    /// history
+    #[must_use]
+    pub fn ondemand_trend_label(&self) -> StackString {
+        match (self.ondemand_trend, self.ondemand_price) {
+            (Some(trend), _) if trend.abs() < 1e-6 => "0".into(),
+            (Some(trend), Some(current)) => {
+                let sign = if trend > 0.0 { '+' } else { '-' };
+                let previous = current - trend;
+                let delta = trend.abs();
+                if previous > 0.0 {
+                    let percent = 100.0 * delta / previous;
+                    format_sstr!("{sign}${delta:0.4} ({sign}{percent:0.1}%)")
+                } else {
+                    format_sstr!("{sign}${delta:0.4}")
+                }
+            }
+            _ => StackString::new(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct AwsAppInterface {
     pub config: Config,
+    /// Only used for the migrations and database backups, which need the
+    /// postgres storage backend
     pub pool: PgPool,
+    /// Every table goes through here, see `Config::storage_backend`
+    pub storage: Arc<dyn Storage>,
     pub ec2: Ec2Instance,
     pub ecr: EcrInstance,
     pub iam: IamInstance,
@@ -61,27 +185,109 @@ pub struct AwsAppInterface {
     pub systemd: SystemdInstance,
     pub sysinfo: SysinfoInstance,
     pub s3: S3Instance,
+    pub sqs: SqsInstance,
+    pub ses_admin: SesAdminInstance,
+    pub backup: BackupInstance,
+    pub lambda: LambdaInstance,
+    pub acm: AcmInstance,
+    pub docker: DockerInstance,
+    pub ecs: EcsInstance,
+    pub sts: StsInstance,
+    pub secrets: SecretsInstance,
+    pub quotas: QuotaInstance,
     pub stdout: StdoutChannel<StackString>,
+    pub cache: ResourceCache<StackString>,
+    pub output_format: OutputFormat,
+    pub account: Option<StackString>,
 }
 
 impl AwsAppInterface {
     #[must_use]
-    pub fn new(config: Config, sdk_config: &SdkConfig, pool: PgPool) -> Self {
+    pub fn new(
+        config: Config,
+        sdk_config: &SdkConfig,
+        pool: PgPool,
+        storage: Arc<dyn Storage>,
+    ) -> Self {
         Self {
             ec2: Ec2Instance::new(&config, sdk_config),
             ecr: EcrInstance::new(&config, sdk_config),
             iam: IamInstance::new(sdk_config),
             route53: Route53Instance::new(sdk_config),
             pricing: PricingInstance::new(sdk_config),
-            systemd: SystemdInstance::new(&config.systemd_services),
+            systemd: SystemdInstance::new(&config.systemd_services)
+                .timers(&config.systemd_timers)
+                .sockets(&config.systemd_sockets),
             sysinfo: SysinfoInstance::new(&config.systemd_services),
-            s3: S3Instance::new(sdk_config),
+            s3: S3Instance::new(sdk_config).retry_policy((&config).into()),
+            sqs: SqsInstance::new(sdk_config),
+            ses_admin: SesAdminInstance::new(sdk_config),
+            backup: BackupInstance::new(&config, sdk_config),
+            lambda: LambdaInstance::new(&config, sdk_config),
+            acm: AcmInstance::new(sdk_config),
+            docker: DockerInstance::new(&config),
+            ecs: EcsInstance::new(&config, sdk_config),
+            sts: StsInstance::new(sdk_config),
+            secrets: SecretsInstance::new(sdk_config),
+            quotas: QuotaInstance::new(sdk_config),
+            cache: ResourceCache::new(Duration::seconds(config.resource_cache_ttl)),
             config,
             pool,
+            storage,
             stdout: StdoutChannel::new(),
+            output_format: OutputFormat::default(),
+            account: None,
         }
     }
 
+    /// Account, arn and region requests are made as and when the
+    /// credentials expire
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_identity_banner(&self) -> Result<IdentityBanner, Error> {
+        let (identity, credential_expiry) = try_join!(
+            self.sts.get_caller_identity(),
+            self.sts.get_credential_expiry()
+        )?;
+        let region = self
+            .sts
+            .region()
+            .map_or_else(|| self.config.aws_region_name.clone(), Into::into);
+        Ok(IdentityBanner {
+            identity,
+            region,
+            credential_expiry,
+        })
+    }
+
+    /// Rebuild every sdk client with credentials for the named account
+    /// profile, `None` switches back to the default credentials
+    /// # Errors
+    /// Returns error if the profile does not exist
+    pub async fn set_account(&mut self, name: Option<&str>) -> Result<(), Error> {
+        let base_config = aws_config::load_from_env().await;
+        let (sdk_config, account) = match name {
+            Some(name) => {
+                let profile = AccountProfile::from_config(&self.config, name)?;
+                let sdk_config = StsInstance::assume_role_config(&base_config, &profile).await;
+                (sdk_config, Some(profile.name))
+            }
+            None => (base_config, None),
+        };
+        let mut app = Self::new(
+            self.config.clone(),
+            &sdk_config,
+            self.pool.clone(),
+            self.storage.clone(),
+        );
+        app.stdout = self.stdout.clone();
+        app.output_format = self.output_format;
+        app.account = account;
+        *self = app;
+        *INSTANCE_LIST.write().await = Arc::new(Vec::new());
+        Ok(())
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
@@ -89,35 +295,252 @@ impl AwsAppInterface {
         self.ec2.set_region(region).await?;
         self.ecr.set_region(region).await?;
         self.route53.set_region(region).await?;
+        self.sqs.set_region(region).await?;
+        self.ses_admin.set_region(region).await?;
+        self.backup.set_region(region).await?;
+        self.lambda.set_region(region).await?;
+        self.acm.set_region(region).await?;
+        self.ecs.set_region(region).await?;
+        self.secrets.set_region(region).await?;
+        self.quotas.set_region(region).await?;
         Ok(())
     }
 
+    /// Names and rotation dates of the secrets under `config.secret_prefixes`
     /// # Errors
     /// Returns error if aws api call fails
-    pub async fn update(&self) -> Result<impl Iterator<Item = StackString>, Error> {
-        let (hvm, pv) = try_join!(
-            scrape_instance_info(AwsGeneration::HVM, &self.pool),
-            scrape_instance_info(AwsGeneration::PV, &self.pool),
-        )?;
-        let iter = hvm.into_iter().chain(pv.into_iter());
-        Ok(iter)
+    pub async fn list_managed_secrets(&self) -> Result<Vec<SecretSummary>, Error> {
+        let futures = self
+            .config
+            .secret_prefixes
+            .iter()
+            .map(|prefix| self.secrets.list_prefix(prefix));
+        let mut secrets: Vec<_> = try_join_all(futures).await?.into_iter().flatten().collect();
+        secrets.sort_by(|a, b| a.name.cmp(&b.name));
+        secrets.dedup_by(|a, b| a.source == b.source && a.name == b.name);
+        Ok(secrets)
+    }
+
+    /// Refresh the instance type list, `region` (defaulting to
+    /// `aws_region_name`) and `families` only apply to `UpdateSource::Api`
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn update(
+        &self,
+        source: UpdateSource,
+        region: Option<&str>,
+        families: &[StackString],
+    ) -> Result<impl Iterator<Item = StackString>, Error> {
+        let output: Vec<StackString> = match source {
+            UpdateSource::Scrape => {
+                let (hvm, pv) = try_join!(
+                    scrape_instance_info(AwsGeneration::HVM, self.storage.as_ref()),
+                    scrape_instance_info(AwsGeneration::PV, self.storage.as_ref()),
+                )?;
+                hvm.into_iter().chain(pv.into_iter()).collect()
+            }
+            UpdateSource::Api => {
+                let region = region.unwrap_or(self.config.aws_region_name.as_str());
+                let output = self
+                    .pricing
+                    .update_from_api(region, families, self.storage.as_ref())
+                    .await?;
+                self.storage
+                    .prune_price_history(self.config.price_history_days)
+                    .await?;
+                output
+            }
+        };
+        Ok(output.into_iter())
+    }
+
+    /// Refresh instance data and then prices, recording the outcome of each in
+    /// `update_status`. Pricing is attempted even if the instance update
+    /// fails.
+    /// # Errors
+    /// Returns error if either update fails or the status can't be recorded
+    pub async fn scheduled_update(&self) -> Result<(), Error> {
+        let instances = self
+            .update(UpdateSource::Scrape, None, &[])
+            .await
+            .map(|entries| debug!("updated {} instance entries", entries.count()));
+        self.record_update_status(UPDATE_TASK_INSTANCES, &instances)
+            .await?;
+
+        let pricing = match self
+            .pricing
+            .update_all_prices(&self.config.aws_region_name, self.storage.as_ref())
+            .await
+        {
+            Ok(number_of_updates) => {
+                debug!("{number_of_updates} price updates");
+                self.storage
+                    .prune_price_history(self.config.price_history_days)
+                    .await
+                    .map(|_| ())
+            }
+            Err(e) => Err(e),
+        };
+        self.record_update_status(UPDATE_TASK_PRICING, &pricing)
+            .await?;
+        instances.and(pricing)
+    }
+
+    fn db_backup_bucket(&self) -> Result<&str, Error> {
+        self.config
+            .db_backup_bucket
+            .as_deref()
+            .ok_or_else(|| format_err!("db_backup_bucket is not configured"))
+    }
+
+    /// Migrations and database backups are only implemented for Postgres
+    /// # Errors
+    /// Returns error if `storage_backend` is not `postgres`
+    pub fn postgres_pool(&self) -> Result<&PgPool, Error> {
+        match self.config.storage_backend {
+            StorageBackend::Postgres => Ok(&self.pool),
+            StorageBackend::Sqlite => Err(format_err!(
+                "migrations and database backups need the postgres storage backend"
+            )),
+        }
+    }
+
+    /// Dump the database to `db_backup_bucket`, returns the key of the backup
+    /// # Errors
+    /// Returns error if db query or s3 upload fails
+    pub async fn backup_database(&self) -> Result<StackString, Error> {
+        let bucket = self.db_backup_bucket()?;
+        let pool = self.postgres_pool()?;
+        let version = schema_version(pool).await?.map(|m| m.version);
+        let backup = DbBackup::dump(pool, version).await?;
+        let key = DbBackup::key_name(
+            &self.config.db_backup_prefix,
+            backup.manifest.created_at.to_offsetdatetime(),
+        );
+        let data = backup.encode()?;
+        debug!(
+            "backing up {} rows ({} bytes) to s3://{bucket}/{key}",
+            backup.rows.len(),
+            data.len()
+        );
+        self.s3.upload_bytes(data.into(), bucket, &key).await?;
+        Ok(key)
+    }
+
+    /// Backup job run on `db_backup_schedule`, the outcome is recorded in
+    /// `update_status`
+    /// # Errors
+    /// Returns error if the backup or recording its status fails
+    pub async fn scheduled_db_backup(&self) -> Result<(), Error> {
+        let result = self
+            .backup_database()
+            .await
+            .map(|key| debug!("database backed up to {key}"));
+        self.record_update_status(UPDATE_TASK_DB_BACKUP, &result)
+            .await?;
+        result
+    }
+
+    /// Keys of the backups in `db_backup_bucket`, oldest first
+    /// # Errors
+    /// Returns error if s3 api fails
+    pub async fn list_database_backups(&self) -> Result<Vec<StackString>, Error> {
+        let bucket = self.db_backup_bucket()?;
+        let mut keys: Vec<StackString> = self
+            .s3
+            .get_list_of_keys(bucket, Some(&self.config.db_backup_prefix))
+            .await?
+            .into_iter()
+            .filter_map(|o| o.key.map(Into::into))
+            .filter(|k: &StackString| k.ends_with(".jsonl.gz"))
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Restore the backup `key`, or the latest one. The backup is verified
+    /// before anything is written, and must come from the schema version of
+    /// this database unless `force` is set.
+    /// # Errors
+    /// Returns error if the backup is missing, corrupt or from another
+    /// schema version, or the restore fails
+    pub async fn restore_database(
+        &self,
+        key: Option<&str>,
+        truncate: bool,
+        force: bool,
+    ) -> Result<(StackString, u64), Error> {
+        let bucket = self.db_backup_bucket()?;
+        let pool = self.postgres_pool()?;
+        let key: StackString = match key {
+            Some(key) => key.into(),
+            None => self
+                .list_database_backups()
+                .await?
+                .pop()
+                .ok_or_else(|| format_err!("no backups in s3://{bucket}"))?,
+        };
+        let data = self.s3.download_to_bytes(bucket, &key).await?;
+        let backup = DbBackup::decode(&data).map_err(|e| format_err!("{key}: {e}"))?;
+        let current = schema_version(pool).await?.map(|m| m.version);
+        if backup.manifest.schema_version != current && !force {
+            return Err(format_err!(
+                "{key} is from schema version {:?}, the database is at {current:?}",
+                backup.manifest.schema_version
+            ));
+        }
+        let inserted = backup.restore(pool, truncate).await?;
+        Ok((key, inserted))
+    }
+
+    async fn record_update_status(
+        &self,
+        task: &str,
+        result: &Result<(), Error>,
+    ) -> Result<(), Error> {
+        let error = result.as_ref().err().map(|e| format_sstr!("{e}"));
+        self.storage
+            .record_update_status(task, error.as_deref())
+            .await
     }
 
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn fill_instance_list(&self) -> Result<(), Error> {
         let mut instances: Vec<_> = self.ec2.get_all_instances().await?.collect();
-        if !instances.is_empty() {
-            instances.sort_by_key(|inst| inst.launch_time);
-            instances.sort_by_key(|inst| &inst.state != "running");
-        }
+        InstanceFilter::default().sort(&mut instances);
         *INSTANCE_LIST.write().await = Arc::new(instances);
+        INSTANCE_LIST_UPDATED
+            .write()
+            .await
+            .replace(OffsetDateTime::now_utc());
         Ok(())
     }
 
+    /// Instances matching `filter` in its order, pages are filtered as they
+    /// arrive so only the matching instances are held. `INSTANCE_LIST` is
+    /// left untouched.
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_filtered_instances(
+        &self,
+        filter: &InstanceFilter,
+    ) -> Result<Vec<Ec2InstanceInfo>, Error> {
+        let mut instances = Vec::new();
+        let mut pages = Box::pin(self.ec2.get_instances_stream(filter));
+        while let Some(page) = pages.try_next().await? {
+            instances.extend(page.into_iter().filter(|inst| filter.matches(inst)));
+        }
+        filter.sort(&mut instances);
+        Ok(instances)
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn process_resource(&self, resource: ResourceType) -> Result<(), Error> {
+        if self.output_format == OutputFormat::Json {
+            return self.process_resource_json(resource).await;
+        }
         match resource {
             ResourceType::Instances | ResourceType::All => {
                 self.fill_instance_list().await?;
@@ -320,12 +743,17 @@ impl AwsAppInterface {
             }
             ResourceType::User => {
                 let users = self
-                    .iam
-                    .list_users()
+                    .list_users_with_mfa()
                     .await?
+                    .into_iter()
                     .map(|u| {
+                        let mfa = if u.mfa_devices.is_empty() {
+                            "no-mfa"
+                        } else {
+                            "mfa"
+                        };
                         format_sstr!(
-                            "{} {} {:30} {:60}",
+                            "{} {} {:30} {:60} {mfa}",
                             u.user_id,
                             u.create_date,
                             u.user_name,
@@ -388,14 +816,35 @@ impl AwsAppInterface {
                     .list_all_dns_records()
                     .await?
                     .into_iter()
-                    .map(|(zone, DnsRecord { dnsname, ip })| {
-                        format_sstr!("{zone} {dnsname} {ip} {current_ip}")
+                    .map(|(zone, record)| {
+                        let DnsRecord {
+                            dnsname,
+                            record_type,
+                            values,
+                            ..
+                        } = &record;
+                        let ttl = record.ttl.map_or_else(|| "alias".into(), |t| t.to_string());
+                        let values = if values.is_empty() {
+                            record.ip.clone()
+                        } else {
+                            values.join(",")
+                        };
+                        let routing = record
+                            .routing
+                            .map_or_else(String::new, |r| format!(" ({r})"));
+                        format_sstr!(
+                            "{zone} {dnsname} {record_type} {ttl} {values} {current_ip}{routing}"
+                        )
                     })
                     .join("\n");
                 self.stdout.send(format_sstr!("---\nDNS:\n{dns_records}"));
             }
             ResourceType::SystemD => {
-                let services = self.systemd.list_running_services().await?;
+                let (services, timers, sockets) = try_join!(
+                    self.systemd.list_running_services(),
+                    self.systemd.list_timers(),
+                    self.systemd.list_sockets(),
+                )?;
                 for service in &self.config.systemd_services {
                     if let Some(val) = services.get(service) {
                         self.stdout.send(format_sstr!("{service} {val}"));
@@ -403,248 +852,2779 @@ impl AwsAppInterface {
                         self.stdout.send(format_sstr!("{service} not running"));
                     }
                 }
+                if !timers.is_empty() {
+                    let timers = timers.iter().join("\n");
+                    self.stdout.send(format_sstr!("---\nTimers:\n{timers}"));
+                }
+                if !sockets.is_empty() {
+                    let sockets = sockets.iter().join("\n");
+                    self.stdout.send(format_sstr!("---\nSockets:\n{sockets}"));
+                }
             }
             ResourceType::InboundEmail => {}
+            ResourceType::Sqs => {
+                let queues = self
+                    .sqs
+                    .list_queue_info()
+                    .await?
+                    .into_iter()
+                    .map(|q| {
+                        format_sstr!(
+                            "{:30} {} {} {} {}",
+                            q.queue_name,
+                            q.approximate_messages,
+                            q.approximate_messages_in_flight,
+                            q.approximate_messages_delayed,
+                            q.queue_url,
+                        )
+                    })
+                    .join("\n");
+                if queues.is_empty() {
+                    return Ok(());
+                }
+                self.stdout.send(format_sstr!("---\nSQS Queues:\n{queues}"));
+            }
+            ResourceType::Backup => {
+                let (plans, resources, points) = try_join!(
+                    self.backup.list_backup_plans(),
+                    self.backup.list_protected_resources(),
+                    self.backup.list_recovery_points(),
+                )?;
+                let plans = plans
+                    .map(|p| {
+                        format_sstr!(
+                            "{} {:30} {}",
+                            p.plan_id,
+                            p.plan_name,
+                            map_date(p.last_execution_date)
+                        )
+                    })
+                    .join("\n");
+                let resources = resources
+                    .map(|r| {
+                        format_sstr!(
+                            "{} {:60} {}",
+                            r.resource_type,
+                            r.resource_arn,
+                            map_date(r.last_backup_time)
+                        )
+                    })
+                    .join("\n");
+                let points = points
+                    .into_iter()
+                    .map(|p| {
+                        format_sstr!(
+                            "{} {} {} {} {:0.2} MB",
+                            p.vault_name,
+                            p.resource_arn,
+                            p.status,
+                            map_date(p.creation_date),
+                            p.backup_size,
+                        )
+                    })
+                    .join("\n");
+                self.stdout
+                    .send(format_sstr!("---\nBackup Plans:\n{plans}"));
+                self.stdout
+                    .send(format_sstr!("---\nProtected Resources:\n{resources}"));
+                self.stdout
+                    .send(format_sstr!("---\nRecovery Points:\n{points}"));
+            }
+            ResourceType::Lambda => {
+                let functions = self
+                    .lambda
+                    .list_functions()
+                    .await?
+                    .map(|f| {
+                        format_sstr!(
+                            "{:30} {:12} {} MB {} s {}",
+                            f.function_name,
+                            f.runtime,
+                            f.memory_size,
+                            f.timeout,
+                            f.last_modified,
+                        )
+                    })
+                    .join("\n");
+                if functions.is_empty() {
+                    return Ok(());
+                }
+                self.stdout
+                    .send(format_sstr!("---\nLambda Functions:\n{functions}"));
+            }
+            ResourceType::Acm => {
+                let now = OffsetDateTime::now_utc();
+                let expiry_days = self.config.certificate_expiry_days;
+                let certificates = self
+                    .acm
+                    .list_certificates()
+                    .await?
+                    .into_iter()
+                    .map(|c| {
+                        format_sstr!(
+                            "{:40} {:20} {} {}{}",
+                            c.domain_name,
+                            c.status,
+                            map_date(c.not_after.map(Into::into)),
+                            c.days_until_expiry(now)
+                                .map_or_else(StackString::new, |d| format_sstr!("{d} days")),
+                            if c.expires_within(expiry_days, now) {
+                                " EXPIRING"
+                            } else {
+                                ""
+                            },
+                        )
+                    })
+                    .join("\n");
+                if certificates.is_empty() {
+                    return Ok(());
+                }
+                self.stdout
+                    .send(format_sstr!("---\nCertificates:\n{certificates}"));
+            }
+            ResourceType::Docker => {
+                let containers = self
+                    .docker
+                    .list_containers()
+                    .await?
+                    .into_iter()
+                    .map(|c| {
+                        format_sstr!(
+                            "{:30} {:50} {:10} {:20} {}",
+                            c.name,
+                            c.image,
+                            c.state,
+                            c.status,
+                            c.ports.join(","),
+                        )
+                    })
+                    .join("\n");
+                if containers.is_empty() {
+                    return Ok(());
+                }
+                self.stdout
+                    .send(format_sstr!("---\nDocker Containers:\n{containers}"));
+            }
+            ResourceType::Ecs => {
+                let services = self
+                    .ecs
+                    .list_all_services()
+                    .await?
+                    .into_iter()
+                    .map(|s| {
+                        format_sstr!(
+                            "{:20} {:30} {:30} {:10} {}/{} running {}",
+                            s.cluster_name,
+                            s.service_name,
+                            s.task_definition,
+                            s.launch_type,
+                            s.running_count,
+                            s.desired_count,
+                            s.rollout_state,
+                        )
+                    })
+                    .join("\n");
+                if services.is_empty() {
+                    return Ok(());
+                }
+                self.stdout
+                    .send(format_sstr!("---\nECS Services:\n{services}"));
+            }
         };
         Ok(())
     }
 
-    #[must_use]
-    pub fn get_all_scripts(&self) -> Vec<StackString> {
-        let mut files: Vec<_> = WalkDir::new(&self.config.script_directory)
-            .same_file_system(true)
-            .into_iter()
-            .filter_map(|entry| {
-                entry.ok().and_then(|entry| {
-                    if entry.file_type().is_dir() {
-                        None
-                    } else {
-                        entry
-                            .path()
-                            .file_name()
-                            .map(|f| f.to_string_lossy().as_ref().into())
-                    }
-                })
-            })
-            .collect();
-        files.sort();
-        files
-    }
-
+    /// Emit one json object per resource: `{"resource": ..., "items": [...]}`
     /// # Errors
     /// Returns error if aws api call fails
-    pub async fn list(
-        &self,
-        resources: impl IntoIterator<Item = &ResourceType>,
-    ) -> Result<(), Error> {
-        let mut visited_resources = HashSet::new();
-
-        let futures: FuturesUnordered<_> = [ResourceType::Instances]
-            .iter()
-            .chain(resources.into_iter())
-            .map(|resource| {
-                let resource = *resource;
-                let visit_resource = visited_resources.insert(resource);
-                async move {
-                    if visit_resource {
-                        Box::pin(self.process_resource(resource)).await?;
-                    }
-                    Ok(())
-                }
-            })
-            .collect();
-
-        futures.try_collect().await
+    pub async fn process_resource_json(&self, resource: ResourceType) -> Result<(), Error> {
+        match self.get_resource_json(resource).await? {
+            Some(listing) => self.send_json(&listing),
+            None => Ok(()),
+        }
     }
 
+    /// Estimated monthly cost of every instance, volume and snapshot,
+    /// attributed to the value of their `tag`
     /// # Errors
-    /// Returns error if aws api call fails
-    pub async fn terminate(
-        &self,
-        instance_ids: impl IntoIterator<Item = impl AsRef<str>>,
-    ) -> Result<(), Error> {
+    /// Returns error if aws api call or db query fails
+    pub async fn get_costs_by_tag(&self, tag: &str) -> Result<Vec<ResourceCost>, Error> {
         self.fill_instance_list().await?;
-        let name_map = get_name_map().await?;
-        let mapped_inst_ids: Vec<_> = instance_ids
-            .into_iter()
-            .map(|id| map_or_val(&name_map, &id).to_string())
-            .collect();
-        self.ec2.terminate_instance(&mapped_inst_ids).await
+        let instances = INSTANCE_LIST.read().await.clone();
+        let (volumes, snapshots, prices) = try_join!(
+            self.ec2.get_all_volumes(),
+            self.ec2.get_all_snapshots(),
+            self.storage.get_prices(),
+        )?;
+        let volumes: Vec<_> = volumes.collect();
+        let snapshots: Vec<_> = snapshots.collect();
+        let rates = CostRates {
+            instance_hourly: prices
+                .into_iter()
+                .filter(|p| p.price_type == "ondemand")
+                .map(|p| (p.instance_type, p.price))
+                .collect(),
+            volume_gb_month: self.config.volume_gb_month_price,
+            snapshot_gb_month: self.config.snapshot_gb_month_price,
+        };
+        Ok(attribute_costs(
+            tag, &instances, &volumes, &snapshots, &rates,
+        ))
     }
 
+    /// Unused resources and their estimated monthly cost, the novnc session
+    /// is run by the http server so its start time is passed in
     /// # Errors
-    /// Returns error if aws api call fails
-    pub async fn connect(&self, instance_id: impl AsRef<str>) -> Result<(), Error> {
+    /// Returns error if aws api call or db query fails
+    pub async fn get_waste_report(
+        &self,
+        policy: &WastePolicy,
+        novnc_started_at: Option<OffsetDateTime>,
+    ) -> Result<Vec<WasteItem>, Error> {
         self.fill_instance_list().await?;
-        let name_map = get_name_map().await?;
-        let id_host_map = get_id_host_map().await?;
-        let inst_id = map_or_val(&name_map, &instance_id);
-        if let Some(host) = id_host_map.get(inst_id) {
-            self.stdout.send(format_sstr!("ssh ubuntu@{host}"));
-        }
-        Ok(())
+        let instances = INSTANCE_LIST.read().await.to_vec();
+        let (volumes, snapshots, elastic_ips, amis, ami_last_launch) = try_join!(
+            self.ec2.get_all_volumes(),
+            self.ec2.get_all_snapshots(),
+            self.ec2.get_elastic_ips(),
+            self.ec2.get_ami_tags(),
+            self.storage.get_last_launch_by_ami(),
+        )?;
+        let inventory = WasteInventory {
+            instances,
+            volumes: volumes.collect(),
+            snapshots: snapshots.collect(),
+            elastic_ips: elastic_ips.collect(),
+            amis: amis.collect(),
+            ami_last_launch,
+            novnc_started_at,
+        };
+        Ok(inventory.find_waste(policy, OffsetDateTime::now_utc()))
     }
 
+    /// Csv rendering of the same listing the ssr table for `resource` is
+    /// built from, prices are limited to the instance types starting with
+    /// `search` (all of them if it is `None`)
     /// # Errors
-    /// Returns error if aws api call fails
-    pub async fn get_status(
+    /// Returns error if aws api call or db query fails
+    pub async fn export_csv(
         &self,
-        instance_id: impl AsRef<str>,
-    ) -> Result<Vec<StackString>, Error> {
-        self.run_command(instance_id, "tail /var/log/cloud-init-output.log")
-            .await
+        resource: ExportResource,
+        search: Option<&str>,
+    ) -> Result<String, Error> {
+        let output = match resource {
+            ExportResource::Instances => {
+                self.fill_instance_list().await?;
+                let instances = INSTANCE_LIST.read().await.clone();
+                to_csv(&instances)
+            }
+            ExportResource::Volumes => {
+                to_csv(&self.ec2.get_all_volumes().await?.collect::<Vec<_>>())
+            }
+            ExportResource::Snapshots => {
+                to_csv(&self.ec2.get_all_snapshots().await?.collect::<Vec<_>>())
+            }
+            ExportResource::Prices => to_csv(&self.get_ec2_prices(&[search.unwrap_or("")]).await?),
+            ExportResource::Ecr => {
+                let futures = self
+                    .ecr
+                    .get_all_repositories()
+                    .await?
+                    .map(|repo| async move {
+                        let images: Vec<_> =
+                            self.ecr.get_all_images(repo.as_str()).await?.collect();
+                        Ok(images)
+                    });
+                let results: Result<Vec<_>, Error> = try_join_all(futures).await;
+                to_csv(&results?.into_iter().flatten().collect::<Vec<_>>())
+            }
+            ExportResource::InboundEmail => {
+                let emails: Vec<_> = self.storage.get_emails(None, None).await?;
+                to_csv(&emails)
+            }
+            ExportResource::IamReport => to_csv(&self.get_credential_report().await?),
+        };
+        Ok(output)
     }
 
+    /// Lifecycle rules, default encryption and size of every bucket, largest
+    /// first. Sizes are summed from listing each bucket.
     /// # Errors
-    /// Returns error if aws api call fails
-    pub async fn run_command(
+    /// Returns error if the buckets can't be listed
+    pub async fn get_bucket_summaries(&self) -> Result<Vec<BucketSummary>, Error> {
+        let buckets = self.s3.get_list_of_buckets().await?;
+        let summaries = buckets.into_iter().filter_map(|bucket| {
+            let name: StackString = bucket.name?.into();
+            let created = bucket
+                .creation_date
+                .and_then(|d| OffsetDateTime::from_unix_timestamp(d.secs()).ok());
+            Some(async move {
+                let (lifecycle_rules, encryption, usage) = join!(
+                    self.s3.get_bucket_lifecycle(&name),
+                    self.s3.get_bucket_encryption(&name),
+                    self.s3.get_bucket_usage(&name),
+                );
+                let log_error = |e: Error| warn!("bucket {name}: {e}");
+                let inbound_email = self.config.inbound_email_bucket.as_ref() == Some(&name);
+                BucketSummary {
+                    created,
+                    lifecycle_rules: lifecycle_rules.map_err(log_error).ok(),
+                    encryption: encryption.map_err(log_error).ok().flatten(),
+                    usage: usage.map_err(log_error).ok(),
+                    inbound_email,
+                    name,
+                }
+            })
+        });
+        let mut summaries = join_all(summaries).await;
+        sort_by_size(&mut summaries);
+        Ok(summaries)
+    }
+
+    /// Temporary credentials scoped to the policy document
+    /// `{policy_directory}/{policy}.json`, from `AssumeRole` if
+    /// `credential_role_arn` is configured and `GetFederationToken`
+    /// otherwise
+    /// # Errors
+    /// Returns error if the policy doesn't exist or aws api call fails
+    pub async fn vend_credentials(
         &self,
-        instance_id: impl AsRef<str>,
-        command: impl AsRef<str>,
-    ) -> Result<Vec<StackString>, Error> {
+        policy: &str,
+        duration: Duration,
+    ) -> Result<TemporaryCredentials, Error> {
+        if policy.is_empty()
+            || policy.len() > 32
+            || !policy
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format_err!("invalid policy name {policy}"));
+        }
+        let path = self
+            .config
+            .policy_directory
+            .join(format_sstr!("{policy}.json"));
+        let document = fs::read_to_string(&path)
+            .await
+            .map_err(|e| format_err!("failed to read policy {}: {e}", path.display()))?;
+        let credentials = match &self.config.credential_role_arn {
+            Some(role_arn) => {
+                let session_name = format_sstr!("aws-app-{policy}");
+                self.sts
+                    .assume_role(role_arn, &session_name, Some(&document), duration)
+                    .await?
+            }
+            None => {
+                self.sts
+                    .get_federation_token(policy, &document, duration)
+                    .await?
+            }
+        };
+        debug!(
+            "vended credentials {} for {policy} until {}",
+            credentials.access_key_id, credentials.expiration
+        );
+        Ok(credentials)
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails or the report can't be parsed
+    pub async fn get_credential_report(&self) -> Result<Vec<CredentialReportEntry>, Error> {
+        let content = self.iam.get_credential_report().await?;
+        CredentialReportEntry::parse_report(&content)
+    }
+
+    /// Returns `{"resource": ..., "items": [...]}`, or `None` for resource
+    /// types without a json listing
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_resource_json(&self, resource: ResourceType) -> Result<Option<Value>, Error> {
+        let items = match resource {
+            ResourceType::Instances | ResourceType::All => {
+                self.fill_instance_list().await?;
+                let instances = INSTANCE_LIST.read().await.clone();
+                json!(*instances)
+            }
+            ResourceType::Reserved => {
+                json!(self.ec2.get_reserved_instances().await?.collect::<Vec<_>>())
+            }
+            ResourceType::Spot => {
+                json!(self
+                    .ec2
+                    .get_spot_instance_requests()
+                    .await?
+                    .collect::<Vec<_>>())
+            }
+            ResourceType::Ami => json!(self.ec2.get_ami_tags().await?.collect::<Vec<_>>()),
+            ResourceType::Key => {
+                let keys: Vec<_> = self
+                    .ec2
+                    .get_all_key_pairs()
+                    .await?
+                    .map(|(key_name, fingerprint)| {
+                        json!({"key_name": key_name, "fingerprint": fingerprint})
+                    })
+                    .collect();
+                json!(keys)
+            }
+            ResourceType::Volume => json!(self.ec2.get_all_volumes().await?.collect::<Vec<_>>()),
+            ResourceType::Snapshot => {
+                json!(self.ec2.get_all_snapshots().await?.collect::<Vec<_>>())
+            }
+            ResourceType::Ecr => {
+                let futures = self
+                    .ecr
+                    .get_all_repositories()
+                    .await?
+                    .map(|repo| async move {
+                        let images: Vec<_> =
+                            self.ecr.get_all_images(repo.as_str()).await?.collect();
+                        Ok(images)
+                    });
+                let results: Result<Vec<_>, Error> = try_join_all(futures).await;
+                json!(results?.into_iter().flatten().collect::<Vec<_>>())
+            }
+            ResourceType::Script => json!(self.get_all_scripts()),
+            ResourceType::User => json!(self.list_users_with_mfa().await?),
+            ResourceType::Group => json!(self.iam.list_groups().await?.collect::<Vec<_>>()),
+            ResourceType::AccessKey => {
+                let futures = self
+                    .iam
+                    .list_users()
+                    .await?
+                    .map(|user| async move { self.iam.list_access_keys(user.user_name).await });
+                let results: Result<Vec<Vec<_>>, Error> = try_join_all(futures).await;
+                let keys: Vec<_> = results?
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|key| {
+                        let create_date: DateTimeWrapper = OffsetDateTime::from_unix_timestamp(
+                            key.create_date?.as_secs_f64() as i64,
+                        )
+                        .ok()?
+                        .into();
+                        Some(json!({
+                            "access_key_id": key.access_key_id?,
+                            "user_name": key.user_name?,
+                            "create_date": create_date,
+                            "status": key.status?.as_str(),
+                        }))
+                    })
+                    .collect();
+                json!(keys)
+            }
+            ResourceType::Route53 => {
+                let dns_records: Vec<_> = self
+                    .route53
+                    .list_all_dns_records()
+                    .await?
+                    .into_iter()
+                    .map(|(zone, record)| {
+                        json!({
+                            "zone": zone,
+                            "dnsname": record.dnsname,
+                            "record_type": record.record_type,
+                            "ttl": record.ttl,
+                            "values": record.values,
+                            "alias_target": record.alias_target,
+                            "ip": record.ip,
+                            "set_identifier": record.set_identifier,
+                            "routing": record.routing.map(|r| r.to_string()),
+                            "health_check_id": record.health_check_id,
+                        })
+                    })
+                    .collect();
+                json!(dns_records)
+            }
+            ResourceType::SystemD => {
+                let (services, timers, sockets) = try_join!(
+                    self.systemd.list_running_services(),
+                    self.systemd.list_timers(),
+                    self.systemd.list_sockets(),
+                )?;
+                let services: BTreeMap<_, _> = self
+                    .config
+                    .systemd_services
+                    .iter()
+                    .map(|service| {
+                        let status = services
+                            .get(service)
+                            .map_or_else(|| "not running".into(), ToString::to_string);
+                        (service, status)
+                    })
+                    .collect();
+                json!({"services": services, "timers": timers, "sockets": sockets})
+            }
+            ResourceType::InboundEmail => return Ok(None),
+            ResourceType::Sqs => json!(self.sqs.list_queue_info().await?),
+            ResourceType::Backup => {
+                let (plans, resources, points) = try_join!(
+                    self.backup.list_backup_plans(),
+                    self.backup.list_protected_resources(),
+                    self.backup.list_recovery_points(),
+                )?;
+                json!({
+                    "plans": plans.collect::<Vec<_>>(),
+                    "protected_resources": resources.collect::<Vec<_>>(),
+                    "recovery_points": points,
+                })
+            }
+            ResourceType::Lambda => json!(self.lambda.list_functions().await?.collect::<Vec<_>>()),
+            ResourceType::Acm => json!(self.acm.list_certificates().await?),
+            ResourceType::Docker => json!(self.docker.list_containers().await?),
+            ResourceType::Ecs => {
+                let (clusters, services) =
+                    try_join!(self.ecs.list_clusters(), self.ecs.list_all_services())?;
+                json!({"clusters": clusters, "services": services})
+            }
+        };
+        Ok(Some(json!({"resource": resource, "items": items})))
+    }
+
+    /// # Errors
+    /// Returns error if serialization fails
+    pub fn send_json<T: Serialize>(&self, value: &T) -> Result<(), Error> {
+        let line = serde_json::to_string(value)?;
+        self.stdout.send(line);
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn get_all_scripts(&self) -> Vec<StackString> {
+        let mut files: Vec<_> = WalkDir::new(&self.config.script_directory)
+            .same_file_system(true)
+            .into_iter()
+            .filter_map(|entry| {
+                entry.ok().and_then(|entry| {
+                    if entry.file_type().is_dir() {
+                        None
+                    } else {
+                        entry
+                            .path()
+                            .file_name()
+                            .map(|f| f.to_string_lossy().as_ref().into())
+                    }
+                })
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list(
+        &self,
+        resources: impl IntoIterator<Item = &ResourceType>,
+    ) -> Result<(), Error> {
+        let mut visited_resources = HashSet::new();
+
+        let futures: FuturesUnordered<_> = [ResourceType::Instances]
+            .iter()
+            .chain(resources.into_iter())
+            .map(|resource| {
+                let resource = *resource;
+                let visit_resource = visited_resources.insert(resource);
+                async move {
+                    if visit_resource {
+                        Box::pin(self.process_resource(resource)).await?;
+                    }
+                    Ok(())
+                }
+            })
+            .collect();
+
+        futures.try_collect().await
+    }
+
+    /// Rows of a resource in `WATCH_RESOURCES` keyed by id with its state
+    /// # Errors
+    /// Returns error if aws api call fails or `resource` has no state
+    pub async fn watch_rows(&self, resource: ResourceType) -> Result<Vec<WatchRow>, Error> {
+        let rows = match resource {
+            ResourceType::Instances => {
+                self.fill_instance_list().await?;
+                INSTANCE_LIST
+                    .read()
+                    .await
+                    .iter()
+                    .map(|inst| {
+                        let name = inst.tags.get("Name").map_or("", StackString::as_str);
+                        WatchRow {
+                            id: inst.id.clone(),
+                            state: inst.state.clone(),
+                            line: format_sstr!(
+                                "{} {} {name} {} {} {}",
+                                inst.id,
+                                inst.state,
+                                inst.instance_type,
+                                inst.availability_zone,
+                                inst.dns_name
+                            ),
+                        }
+                    })
+                    .collect()
+            }
+            ResourceType::Spot => self
+                .ec2
+                .get_spot_instance_requests()
+                .await?
+                .map(|req| WatchRow {
+                    line: format_sstr!(
+                        "{} {} {} {} {}",
+                        req.id,
+                        req.status,
+                        req.instance_type,
+                        req.price,
+                        req.instance_id.as_ref().map_or("", StackString::as_str)
+                    ),
+                    id: req.id,
+                    state: req.status,
+                })
+                .collect(),
+            ResourceType::Volume => self
+                .ec2
+                .get_all_volumes()
+                .await?
+                .map(|vol| WatchRow {
+                    line: format_sstr!(
+                        "{} {} {} GB {} {}",
+                        vol.id,
+                        vol.state,
+                        vol.size,
+                        vol.availability_zone,
+                        print_tags(&vol.tags)
+                    ),
+                    id: vol.id,
+                    state: vol.state,
+                })
+                .collect(),
+            ResourceType::Snapshot => self
+                .ec2
+                .get_all_snapshots()
+                .await?
+                .map(|snap| WatchRow {
+                    line: format_sstr!(
+                        "{} {} {} {} GB {}",
+                        snap.id,
+                        snap.state,
+                        snap.progress,
+                        snap.volume_size,
+                        print_tags(&snap.tags)
+                    ),
+                    id: snap.id,
+                    state: snap.state,
+                })
+                .collect(),
+            resource => {
+                return Err(format_err!("{resource} can't be watched"));
+            }
+        };
+        Ok(rows)
+    }
+
+    /// Clear the terminal and list instances and `resources` every `interval`
+    /// seconds until interrupted, highlighting state changes since the
+    /// previous refresh
+    /// # Errors
+    /// Returns error if a resource can't be watched or an aws api call fails
+    pub async fn watch(&self, resources: &[ResourceType], interval: u64) -> Result<(), Error> {
+        if self.output_format == OutputFormat::Json {
+            return Err(format_err!("--watch only supports text output"));
+        }
+        let mut watched = vec![ResourceType::Instances];
+        for resource in resources {
+            if !WATCH_RESOURCES.contains(resource) {
+                return Err(format_err!(
+                    "{resource} can't be watched, use one of {}",
+                    WATCH_RESOURCES.iter().join(",")
+                ));
+            }
+            if !watched.contains(resource) {
+                watched.push(*resource);
+            }
+        }
+        let mut watcher = StateWatcher::default();
+        loop {
+            let sections: Vec<_> = try_join_all(watched.iter().map(|resource| async move {
+                let rows = self.watch_rows(*resource).await?;
+                Ok::<_, Error>((resource.to_str(), rows))
+            }))
+            .await?;
+            let lines = watcher.render(&sections);
+            let local_tz = DateTimeWrapper::local_tz();
+            self.stdout.send(format_sstr!(
+                "{CLEAR_SCREEN}every {interval}s, last refresh {}\n{}",
+                OffsetDateTime::now_utc().to_timezone(local_tz),
+                lines.join("\n")
+            ));
+            sleep(std::time::Duration::from_secs(interval)).await;
+        }
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn terminate(
+        &self,
+        instance_ids: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<(), Error> {
+        self.fill_instance_list().await?;
+        let name_map = get_name_map().await?;
+        let mapped_inst_ids: Vec<StackString> = instance_ids
+            .into_iter()
+            .map(|id| map_or_val(&name_map, &id).into())
+            .collect();
+        let hooks = Hooks::from_config(&self.config)?;
+        if hooks.has_event(HookEvent::PreTerminate) {
+            let instances = INSTANCE_LIST.read().await.clone();
+            for instance_id in &mapped_inst_ids {
+                let context = match instances
+                    .iter()
+                    .find(|inst| inst.id == instance_id.as_str())
+                {
+                    Some(inst) => json!({
+                        "instance_id": inst.id,
+                        "name": inst.tags.get("Name"),
+                        "instance_type": inst.instance_type,
+                        "state": inst.state,
+                        "dns_name": inst.dns_name,
+                        "availability_zone": inst.availability_zone,
+                    }),
+                    None => json!({"instance_id": instance_id}),
+                };
+                hooks
+                    .run(
+                        self.storage.as_ref(),
+                        HookEvent::PreTerminate,
+                        instance_id,
+                        &context,
+                    )
+                    .await?;
+            }
+        }
+        self.cache
+            .invalidate([ResourceType::Instances, ResourceType::Volume]);
+        if let Err(e) = self.close_ssh_masters(&mapped_inst_ids).await {
+            warn!("failed to close ssh connections {e}");
+        }
+        self.ec2.terminate_instance(&mapped_inst_ids).await?;
+        self.storage.set_terminated(&mapped_inst_ids).await
+    }
+
+    /// Read a terraform state from a local path or `s3://bucket/key`
+    async fn load_terraform_state(&self, path: &str) -> Result<TerraformState, Error> {
+        let state = match path.strip_prefix("s3://") {
+            Some(location) => {
+                let (bucket, key) = location
+                    .split_once('/')
+                    .ok_or_else(|| format_err!("invalid s3 path {path}"))?;
+                self.s3.download_to_string(bucket, key).await?
+            }
+            None => fs::read_to_string(path)
+                .await
+                .map_err(|e| format_err!("failed to read {path}: {e}"))?,
+        };
+        TerraformState::from_json(&state)
+    }
+
+    /// Compare the instances, volumes and dns records declared in the
+    /// terraform state at `path`, or `terraform_state` if unset, with those in
+    /// aws
+    /// # Errors
+    /// Returns error if the state can't be read or aws api call fails
+    pub async fn terraform_drift(&self, path: Option<&str>) -> Result<DriftReport, Error> {
+        let path = path
+            .or(self.config.terraform_state.as_deref())
+            .ok_or_else(|| {
+                format_err!("no terraform state given and terraform_state is not configured")
+            })?;
+        let (state, instances, volumes, dns_records) = try_join!(
+            self.load_terraform_state(path),
+            self.ec2.get_all_instances(),
+            self.ec2.get_all_volumes(),
+            self.route53.list_all_dns_records(),
+        )?;
+        let live: Vec<_> = instances
+            .filter(|inst| inst.state != "terminated")
+            .map(|inst| ComparedResource::from_instance(&inst))
+            .chain(volumes.map(|vol| ComparedResource::from_volume(&vol)))
+            .chain(
+                dns_records
+                    .iter()
+                    .map(|(zone_id, record)| ComparedResource::from_dns_record(zone_id, record)),
+            )
+            .collect();
+        Ok(DriftReport::new(&state.declared(), &live))
+    }
+
+    /// Instances, volumes, snapshots, amis, spot requests and dns records
+    /// linked by what refers to what
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn resource_graph(&self) -> Result<ResourceGraph, Error> {
+        let (instances, volumes, snapshots, amis, spot_requests, dns_records) = try_join!(
+            self.ec2.get_all_instances(),
+            self.ec2.get_all_volumes(),
+            self.ec2.get_all_snapshots(),
+            self.ec2.get_ami_tags(),
+            self.ec2.get_spot_instance_requests(),
+            self.route53.list_all_dns_records(),
+        )?;
+        let instances: Vec<_> = instances
+            .filter(|inst| inst.state != "terminated")
+            .collect();
+        let volumes: Vec<_> = volumes.collect();
+        let snapshots: Vec<_> = snapshots.collect();
+        let amis: Vec<_> = amis.collect();
+        let spot_requests: Vec<_> = spot_requests.collect();
+        Ok(ResourceGraph::new(
+            &instances,
+            &volumes,
+            &snapshots,
+            &amis,
+            &spot_requests,
+            &dns_records,
+        ))
+    }
+
+    /// Everything `decommission` can clean up for `instance`, an id or name
+    /// # Errors
+    /// Returns error if aws api call fails or the instance doesn't exist
+    pub async fn decommission_plan(&self, instance: &str) -> Result<DecommissionPlan, Error> {
+        self.fill_instance_list().await?;
+        let name_map = get_name_map().await?;
+        let instance_id = map_or_val(&name_map, &instance);
+        let instance = self
+            .ec2
+            .get_all_instances()
+            .await?
+            .find(|inst| inst.id == instance_id)
+            .ok_or_else(|| format_err!("instance {instance_id} not found"))?;
+        let (details, dns_records, spot_requests) = try_join!(
+            self.ec2.get_instance_details(&instance.id),
+            self.route53.list_all_dns_records(),
+            self.ec2.get_spot_instance_requests(),
+        )?;
+        let spot_requests: Vec<_> = spot_requests.collect();
+        Ok(DecommissionPlan::new(
+            &instance,
+            &details,
+            &dns_records,
+            &spot_requests,
+        ))
+    }
+
+    /// Terminate `instance` along with the cleanup `options` selects from
+    /// its plan: a final snapshot of the root volume is started and spot
+    /// requests are cancelled first, dns records and the remaining volumes
+    /// are deleted once the instance is gone. Returns the steps taken.
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn decommission(
+        &self,
+        instance: &str,
+        options: DecommissionOptions,
+    ) -> Result<Vec<StackString>, Error> {
+        let plan = self.decommission_plan(instance).await?;
+        if options.final_snapshot {
+            if let Some(root_volume) = &plan.root_volume {
+                self.ec2
+                    .create_ebs_snapshot(root_volume.as_str(), &plan.snapshot_tags())
+                    .await?;
+            }
+        }
+        if options.cancel_spot_requests && !plan.spot_requests.is_empty() {
+            self.ec2
+                .cancel_spot_instance_request(&plan.spot_requests)
+                .await?;
+        }
+        self.terminate(&[&plan.instance_id]).await?;
+        if options.delete_dns_records {
+            for (zone_id, record) in &plan.dns_records {
+                self.route53.delete_dns_record(zone_id, record).await?;
+            }
+        }
+        if options.delete_volumes && !plan.volumes.is_empty() {
+            self.wait_for_volumes_available(&plan.volumes).await?;
+            for volume in &plan.volumes {
+                self.ec2.delete_ebs_volume(volume.as_str()).await?;
+            }
+        }
+        self.cache.invalidate([
+            ResourceType::Volume,
+            ResourceType::Snapshot,
+            ResourceType::Spot,
+            ResourceType::Route53,
+        ]);
+        let steps = plan.steps(options);
+        self.storage
+            .insert_audit_log(&AuditLog::new(
+                "decommission",
+                plan.instance_id.clone(),
+                Some(steps.join("\n").into()),
+            ))
+            .await?;
+        Ok(steps)
+    }
+
+    /// Volumes of a terminating instance only become available once it has
+    /// shut down
+    async fn wait_for_volumes_available(&self, volumes: &[StackString]) -> Result<(), Error> {
+        for _ in 0..DETACH_POLL_LIMIT {
+            let available = self
+                .ec2
+                .get_all_volumes()
+                .await?
+                .filter(|v| volumes.contains(&v.id) && v.state == "available")
+                .count();
+            if available == volumes.len() {
+                return Ok(());
+            }
+            sleep(std::time::Duration::from_secs(DETACH_POLL_SECS)).await;
+        }
+        Err(format_err!("volumes {} did not detach", volumes.join(" ")))
+    }
+
+    async fn wait_for_snapshot(&self, snapid: &str) -> Result<(), Error> {
+        for _ in 0..SNAPSHOT_POLL_LIMIT {
+            let state = self
+                .ec2
+                .get_all_snapshots()
+                .await?
+                .find(|s| s.id == snapid)
+                .map(|s| s.state);
+            match state.as_ref().map(StackString::as_str) {
+                Some("completed") => return Ok(()),
+                Some("error") => return Err(format_err!("snapshot {snapid} failed")),
+                _ => sleep(std::time::Duration::from_secs(SNAPSHOT_POLL_SECS)).await,
+            }
+        }
+        Err(format_err!("snapshot {snapid} did not complete"))
+    }
+
+    /// Check that `volid`, an id or name, can be re-encrypted
+    /// # Errors
+    /// Returns error if aws api call fails or the volume can't be re-encrypted
+    pub async fn reencrypt_plan(
+        &self,
+        volid: impl AsRef<str>,
+        kms_key_id: Option<StackString>,
+    ) -> Result<ReencryptPlan, Error> {
+        let vol_map = self.get_volume_map().await?;
+        let volid = map_or_val(&vol_map, &volid);
+        let volume = self
+            .ec2
+            .get_all_volumes()
+            .await?
+            .find(|v| v.id == volid)
+            .ok_or_else(|| format_err!("volume {volid} not found"))?;
+        let instance_state = match &volume.attachment {
+            Some((instance_id, _)) => self
+                .ec2
+                .get_all_instances()
+                .await?
+                .find(|i| &i.id == instance_id)
+                .map(|i| i.state),
+            None => None,
+        };
+        ReencryptPlan::new(
+            &volume,
+            instance_state.as_ref().map(StackString::as_str),
+            kms_key_id,
+        )
+    }
+
+    /// Replace an unencrypted volume with an encrypted copy attached in its
+    /// place, returns the id of the new volume. Each step waits for the
+    /// previous one, so this takes as long as two snapshots of the volume.
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn reencrypt_volume(
+        &self,
+        volid: impl AsRef<str>,
+        kms_key_id: Option<StackString>,
+    ) -> Result<StackString, Error> {
+        let plan = self.reencrypt_plan(volid, kms_key_id).await?;
+        let tags = plan.tags();
+        let snapid = self
+            .ec2
+            .create_ebs_snapshot(plan.volume_id.as_str(), &tags)
+            .await?
+            .ok_or_else(|| format_err!("no snapshot created of {}", plan.volume_id))?;
+        self.wait_for_snapshot(&snapid).await?;
+        let copy_id = self
+            .ec2
+            .copy_snapshot(
+                self.ec2.region(),
+                &snapid,
+                true,
+                plan.kms_key_id.as_ref().map(StackString::as_str),
+                &tags,
+            )
+            .await?;
+        self.wait_for_snapshot(&copy_id).await?;
+        let new_volid = self
+            .ec2
+            .create_volume_from_snapshot(
+                plan.availability_zone.as_str(),
+                copy_id.as_str(),
+                &plan.volume_type,
+                &tags,
+            )
+            .await?;
+        self.wait_for_volumes_available(&[new_volid.clone()])
+            .await?;
+        if let Some((instance_id, device)) = &plan.attachment {
+            self.ec2.detach_ebs_volume(plan.volume_id.as_str()).await?;
+            self.wait_for_volumes_available(&[plan.volume_id.clone()])
+                .await?;
+            self.ec2
+                .attach_ebs_volume(new_volid.as_str(), instance_id.as_str(), device.as_str())
+                .await?;
+        }
+        self.ec2.delete_ebs_snapshot(snapid.as_str()).await?;
+        self.cache.invalidate([
+            ResourceType::Volume,
+            ResourceType::Snapshot,
+            ResourceType::Instances,
+        ]);
+        self.storage
+            .insert_audit_log(&AuditLog::new(
+                "reencrypt_volume",
+                plan.volume_id.clone(),
+                Some(format_sstr!("replaced by {new_volid} from {copy_id}")),
+            ))
+            .await?;
+        Ok(new_volid)
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn stop(
+        &self,
+        instance_ids: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<(), Error> {
+        self.fill_instance_list().await?;
+        let name_map = get_name_map().await?;
+        let mapped_inst_ids: Vec<_> = instance_ids
+            .into_iter()
+            .map(|id| map_or_val(&name_map, &id).to_string())
+            .collect();
+        self.cache.invalidate([ResourceType::Instances]);
+        self.ec2.stop_instance(&mapped_inst_ids).await
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn connect(&self, instance_id: impl AsRef<str>) -> Result<(), Error> {
+        if let Some(args) = self.ssh_args(instance_id).await? {
+            let args = args.join(" ");
+            self.stdout.send(format_sstr!("ssh {args}"));
+        }
+        Ok(())
+    }
+
+    /// Arguments to `ssh` connecting to the instance, `None` if it has no
+    /// public hostname
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn ssh_args(
+        &self,
+        instance_id: impl AsRef<str>,
+    ) -> Result<Option<Vec<StackString>>, Error> {
         self.fill_instance_list().await?;
         let name_map = get_name_map().await?;
         let id_host_map = get_id_host_map().await?;
         let inst_id = map_or_val(&name_map, &instance_id);
-        if let Some(host) = id_host_map.get(inst_id) {
-            SSHInstance::new("ubuntu", host, 22)
-                .await
-                .run_command_stream_stdout(command)
-                .await
+        Ok(id_host_map.get(inst_id).map(|host| {
+            let mut args = self.known_hosts().ssh_options(inst_id, false).to_vec();
+            args.push(format_sstr!("ubuntu@{host}"));
+            args
+        }))
+    }
+
+    fn known_hosts(&self) -> KnownHosts {
+        KnownHosts::new(&self.config.known_hosts_path)
+    }
+
+    /// Record the host keys the instance printed to its console, if none are
+    /// found the old entry is dropped so the next connection records the key
+    /// it is offered. Returns whether keys were found.
+    /// # Errors
+    /// Returns error if aws api call fails or known_hosts can't be written
+    pub async fn refresh_host_key(&self, instance_id: &str) -> Result<bool, Error> {
+        let keys = self
+            .ec2
+            .get_console_output(instance_id)
+            .await?
+            .map(|output| parse_console_host_keys(&output))
+            .unwrap_or_default();
+        let known_hosts = self.known_hosts();
+        if keys.is_empty() {
+            known_hosts.remove(instance_id).await?;
+            Ok(false)
+        } else {
+            known_hosts.set_host_keys(instance_id, &keys).await?;
+            Ok(true)
+        }
+    }
+
+    /// Forget the recorded host key of an instance and fetch it again
+    /// # Errors
+    /// Returns error if aws api call fails or known_hosts can't be written
+    pub async fn reset_host_key(&self, instance_id: impl AsRef<str>) -> Result<bool, Error> {
+        self.fill_instance_list().await?;
+        let name_map = get_name_map().await?;
+        let inst_id = map_or_val(&name_map, &instance_id);
+        self.refresh_host_key(inst_id).await
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_status(
+        &self,
+        instance_id: impl AsRef<str>,
+    ) -> Result<Vec<StackString>, Error> {
+        self.run_command(instance_id, "tail /var/log/cloud-init-output.log")
+            .await
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn run_command(
+        &self,
+        instance_id: impl AsRef<str>,
+        command: impl AsRef<str>,
+    ) -> Result<Vec<StackString>, Error> {
+        if let Some(ssh) = self.ssh_instance(instance_id).await? {
+            ssh.run_command_stream_stdout(command).await
         } else {
             Ok(Vec::new())
         }
     }
 
+    /// Write `data` to `remote_path` on a running instance
+    /// # Errors
+    /// Returns error if the instance isn't running or scp fails
+    pub async fn upload_file(
+        &self,
+        instance_id: impl AsRef<str>,
+        data: &[u8],
+        remote_path: &str,
+    ) -> Result<(), Error> {
+        let instance_id = instance_id.as_ref();
+        let ssh = self
+            .ssh_instance(instance_id)
+            .await?
+            .ok_or_else(|| format_err!("No running instance {instance_id}"))?;
+        let tempdir = TempDir::new()?;
+        let local = tempdir.path().join("upload");
+        fs::write(&local, data).await?;
+        ssh.upload_file(&local, remote_path).await
+    }
+
+    /// Fetch the contents of `remote_path` from a running instance
+    /// # Errors
+    /// Returns error if the instance isn't running or scp fails
+    pub async fn download_file(
+        &self,
+        instance_id: impl AsRef<str>,
+        remote_path: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let instance_id = instance_id.as_ref();
+        let ssh = self
+            .ssh_instance(instance_id)
+            .await?
+            .ok_or_else(|| format_err!("No running instance {instance_id}"))?;
+        let tempdir = TempDir::new()?;
+        let local = tempdir.path().join("download");
+        ssh.download_file(remote_path, &local).await?;
+        fs::read(&local).await.map_err(Into::into)
+    }
+
+    /// Ssh connection to the instance with its host key checked against the
+    /// managed known_hosts file, `None` if it has no public hostname
+    async fn ssh_instance(
+        &self,
+        instance_id: impl AsRef<str>,
+    ) -> Result<Option<SSHInstance>, Error> {
+        self.fill_instance_list().await?;
+        let name_map = get_name_map().await?;
+        let id_host_map = get_id_host_map().await?;
+        let inst_id = map_or_val(&name_map, &instance_id);
+        let host = match id_host_map.get(inst_id) {
+            Some(host) => host,
+            None => return Ok(None),
+        };
+        let known_hosts = self.known_hosts();
+        let strict = known_hosts.contains(inst_id).await?
+            || self.refresh_host_key(inst_id).await.unwrap_or_else(|e| {
+                debug!("failed to fetch host key for {inst_id} {e}");
+                false
+            });
+        let check = HostKeyCheck {
+            known_hosts,
+            alias: inst_id.into(),
+            strict,
+        };
+        let ssh = SSHInstance::new("ubuntu", host, 22)
+            .await
+            .with_host_key_check(check)
+            .with_multiplex(self.multiplex());
+        Ok(Some(ssh))
+    }
+
+    fn multiplex(&self) -> Option<Multiplex> {
+        Multiplex::new(
+            &self.config.ssh_control_dir,
+            self.config.ssh_control_persist,
+        )
+    }
+
+    /// Close the ssh master connections to `instance_ids` so a new instance
+    /// given the same hostname isn't reached through a stale connection
+    async fn close_ssh_masters(&self, instance_ids: &[impl AsRef<str>]) -> Result<(), Error> {
+        if self.multiplex().is_none() {
+            return Ok(());
+        }
+        let id_host_map = get_id_host_map().await?;
+        for instance_id in instance_ids {
+            if let Some(host) = id_host_map.get(instance_id.as_ref()) {
+                SSHInstance::new("ubuntu", host, 22)
+                    .await
+                    .with_multiplex(self.multiplex())
+                    .close_master()
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_ec2_prices(
+        &self,
+        search: &[impl AsRef<str>],
+    ) -> Result<Vec<AwsInstancePrice>, Error> {
+        let instance_families: HashMap<_, _> = self
+            .storage
+            .get_instance_families(None)
+            .await?
+            .into_iter()
+            .map(|f| (f.family_name.clone(), f))
+            .collect();
+        let instance_list: HashMap<_, _> = self
+            .storage
+            .get_instances()
+            .await?
+            .into_iter()
+            .map(|i| (i.instance_type.clone(), i))
+            .collect();
+        let inst_list: Vec<_> = instance_list
+            .keys()
+            .filter_map(|inst| {
+                if search.iter().any(|s| inst.starts_with(s.as_ref())) {
+                    Some(inst.clone())
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let spot_prices = self.ec2.get_latest_spot_inst_prices(&inst_list).await?;
+
+        let prices: HashMap<_, _> = self
+            .storage
+            .get_prices()
+            .await?
+            .into_iter()
+            .map(|p| ((p.instance_type.clone(), p.price_type.clone()), p))
+            .collect();
+        self.record_spot_prices(&spot_prices, &prices).await?;
+        let week_ago = OffsetDateTime::now_utc() - Duration::days(7);
+        let previous_prices: HashMap<_, _> = self
+            .storage
+            .get_prices_as_of(week_ago)
+            .await?
+            .into_iter()
+            .map(|p| ((p.instance_type.clone(), p.price_type.clone()), p.price))
+            .collect();
+
+        let prices: Result<Vec<_>, Error> = inst_list
+            .into_iter()
+            .map(|inst| {
+                let ond_price = prices
+                    .get(&(inst.clone(), "ondemand".into()))
+                    .map(|x| x.price);
+                let res_price = prices
+                    .get(&(inst.clone(), "reserved".into()))
+                    .map(|x| x.price);
+                let ondemand_trend = ond_price.and_then(|current| {
+                    previous_prices
+                        .get(&(inst.clone(), "ondemand".into()))
+                        .map(|previous| current - previous)
+                });
+                let spot_price = spot_prices.get(inst.as_str());
+                let instance_metadata = instance_list
+                    .get(&inst)
+                    .ok_or_else(|| format_err!("this should be impossible {}", inst))?;
+                let inst_fam = inst
+                    .split('.')
+                    .next()
+                    .ok_or_else(|| format_err!("invalid instance name {}", inst))?;
+                let inst_fam = instance_families
+                    .get(inst_fam)
+                    .ok_or_else(|| format_err!("inst_fam {} does not exist", inst_fam))?;
+                let instance_family = inst_fam.family_type.parse()?;
+
+                Ok(AwsInstancePrice {
+                    instance_type: inst,
+                    ondemand_price: ond_price,
+                    spot_price: spot_price.map(|x| f64::from(*x)),
+                    reserved_price: res_price,
+                    ondemand_trend,
+                    ncpu: instance_metadata.n_cpu,
+                    memory: instance_metadata.memory_gib,
+                    gpu_count: instance_metadata.gpu_count,
+                    gpu_type: instance_metadata.gpu_type.clone(),
+                    network_performance: instance_metadata.network_performance.clone(),
+                    instance_family,
+                    data_url: inst_fam.data_url.clone(),
+                })
+            })
+            .collect();
+        let mut prices = prices?;
+        prices.sort_by_key(|p| (p.ncpu, p.memory as i64));
+        Ok(prices)
+    }
+
+    /// Append `spot_prices` to the price history when they differ from the
+    /// `stored` spot price or it was recorded over `SPOT_RECORD_INTERVAL` ago,
+    /// so that browsing the prices page doesn't flood the history
+    async fn record_spot_prices(
+        &self,
+        spot_prices: &HashMap<StackString, f32>,
+        stored: &HashMap<(StackString, StackString), InstancePricing>,
+    ) -> Result<(), Error> {
+        let now = OffsetDateTime::now_utc();
+        for (instance_type, price) in spot_prices {
+            let price = f64::from(*price);
+            let unchanged =
+                stored
+                    .get(&(instance_type.clone(), "spot".into()))
+                    .map_or(false, |p| {
+                        (p.price - price).abs() < f64::EPSILON
+                            && now - p.price_timestamp < SPOT_RECORD_INTERVAL
+                    });
+            if !unchanged {
+                let spot = InstancePricing::new(instance_type, price, "spot", now);
+                self.storage.upsert_price(&spot).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forecasts from the recorded spot prices of the instance types starting
+    /// with any of `search`
     /// # Errors
-    /// Returns error if aws api call fails
-    pub async fn get_ec2_prices(
+    /// Returns error if db query fails
+    pub async fn get_spot_forecasts(
         &self,
         search: &[impl AsRef<str>],
+    ) -> Result<BTreeMap<StackString, SpotForecast>, Error> {
+        let now = OffsetDateTime::now_utc();
+        let matches = |instance_type: &str| {
+            search.is_empty() || search.iter().any(|s| instance_type.starts_with(s.as_ref()))
+        };
+        let history: Vec<_> = self
+            .storage
+            .get_price_type_history("spot", now - Duration::days(FORECAST_WINDOW_DAYS))
+            .await?
+            .into_iter()
+            .filter(|h| matches(&h.instance_type))
+            .collect();
+        let ondemand_prices: HashMap<_, _> = self
+            .storage
+            .get_prices()
+            .await?
+            .into_iter()
+            .filter(|p| p.price_type == "ondemand")
+            .map(|p| (p.instance_type, p.price))
+            .collect();
+        Ok(spot_forecasts(&history, &ondemand_prices, now))
+    }
+
+    /// Prices for every member of `instance_family`, cheapest spot price
+    /// first
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_instance_family_prices(
+        &self,
+        instance_family: &str,
     ) -> Result<Vec<AwsInstancePrice>, Error> {
-        let instance_families: HashMap<_, _> = InstanceFamily::get_all(&self.pool, None)
+        let search = format_sstr!("{instance_family}.");
+        let mut prices = self.get_ec2_prices(&[search]).await?;
+        sort_by_spot_price(&mut prices);
+        Ok(prices)
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn print_ec2_prices(&self, search: &[impl AsRef<str>]) -> Result<(), Error> {
+        let mut prices: Vec<_> = self
+            .get_ec2_prices(search)
+            .await?
+            .into_iter()
+            .map(|price| {
+                let mut outstr = vec![format_sstr!("{:14} ", price.instance_type)];
+                match price.ondemand_price {
+                    Some(p) => outstr.push(format_sstr!("ond: ${p:0.4}/hr   ")),
+                    None => outstr.push(format_sstr!("{:4} {:9}   ", "", "")),
+                }
+                match price.spot_price {
+                    Some(p) => outstr.push(format_sstr!("spot: ${p:0.4}/hr   ")),
+                    None => outstr.push(format_sstr!("{:5} {:9}    ", "", "")),
+                }
+                match price.reserved_price {
+                    Some(p) => outstr.push(format_sstr!("res: ${p:0.4}/hr   ")),
+                    None => outstr.push(format_sstr!("{:4} {:9}   ", "", "")),
+                }
+                outstr.push(format_sstr!("cpu: {:2}   ", price.ncpu));
+                outstr.push(format_sstr!("mem: {:2}   ", price.memory));
+                if price.gpu_count > 0 {
+                    let gpu_type = price.gpu_type.as_ref().map_or("", StackString::as_str);
+                    outstr.push(format_sstr!("gpu: {} x {gpu_type}   ", price.gpu_count));
+                }
+                outstr.push(format_sstr!("inst_fam: {}", price.instance_family));
+                (price.ncpu, price.memory as i64, outstr.join(""))
+            })
+            .collect();
+        prices.sort();
+
+        let outstrings: Vec<_> = prices.into_iter().map(|(_, _, line)| line).collect();
+        self.stdout.send(outstrings.join("\n"));
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn delete_image(&self, ami: &str) -> Result<(), Error> {
+        let ami_map = self.ec2.get_ami_map().await?;
+        let ami = ami_map.get(ami).map_or(ami, AsRef::as_ref);
+        self.cache
+            .invalidate([ResourceType::Ami, ResourceType::Snapshot]);
+        self.ec2.delete_image(ami).await
+    }
+
+    /// Returns the spot requests made
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn request_spot_instance(
+        &self,
+        req: &mut SpotRequest,
+    ) -> Result<Vec<SpotLaunch>, Error> {
+        self.check_name_tag(ResourceType::Spot, &mut req.tags)?;
+        let ami_map = self.ec2.get_ami_map().await?;
+        if let Some(a) = ami_map.get(&req.ami) {
+            req.ami = a.clone();
+        }
+        self.check_spot_request(req).await?;
+        let hooks = Hooks::from_config(&self.config)?;
+        if hooks.has_event(HookEvent::PreLaunch) {
+            let context = serde_json::to_value(&*req)?;
+            hooks
+                .run(
+                    self.storage.as_ref(),
+                    HookEvent::PreLaunch,
+                    &req.ami,
+                    &context,
+                )
+                .await?;
+        }
+        self.cache
+            .invalidate([ResourceType::Spot, ResourceType::Instances]);
+        let launches = self.ec2.request_spot_instance(req).await?;
+        for launch in &launches {
+            self.record_spot_launch(req, launch).await?;
+        }
+        let futures = launches.iter().map(|launch| {
+            self.ec2
+                .tag_spot_instance(&launch.spot_id, &req.tags, req.require_imdsv2, 20)
+        });
+        try_join_all(futures).await?;
+        Ok(launches)
+    }
+
+    /// Check that the instance types of `req` are offered in its zones,
+    /// support the architecture of its ami and fit the spot vCPU quota, aws
+    /// only reports any of these once the spot request fails to be fulfilled
+    /// # Errors
+    /// Returns error if aws api call fails or the request can't be fulfilled
+    pub async fn check_spot_request(&self, req: &SpotRequest) -> Result<(), Error> {
+        validate_volumes(req.root_volume.as_ref(), &req.data_volumes)?;
+        if let Some(dns_name) = &req.dns_name {
+            if req.count > 1 {
+                return Err(format_err!(
+                    "{dns_name} can only be registered for a single instance"
+                ));
+            }
+            self.check_launch_dns_name(dns_name).await?;
+        }
+        let instance_types = req.instance_types();
+        let (availability, ami_architecture) = try_join!(
+            self.ec2.get_instance_type_availability(&instance_types),
+            self.ec2.get_image_architecture(&req.ami),
+        )?;
+        req.check_availability(&availability, &ami_architecture)?;
+        self.check_spot_vcpu_quota(req).await
+    }
+
+    /// Usage of the vCPU, EBS storage, elastic ip and snapshot quotas of the
+    /// current region against their applied values
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn quota_usage(&self) -> Result<Vec<QuotaUsage>, Error> {
+        let (vcpus, volumes, snapshots, elastic_ips) = try_join!(
+            self.running_vcpus(),
+            self.ec2.get_all_volumes(),
+            self.ec2.get_all_snapshots(),
+            self.ec2.get_elastic_ips(),
+        )?;
+        let volumes: Vec<_> = volumes.collect();
+        let usages = quota_usages(
+            &vcpus,
+            &ebs_usage(&volumes),
+            elastic_ips.count(),
+            snapshots.count(),
+        );
+        let futures = usages.into_iter().map(|mut usage| async move {
+            match self
+                .quotas
+                .get_quota(usage.service_code, usage.quota_code)
+                .await
+            {
+                Ok(quota) => usage.quota = quota,
+                Err(e) => debug!("quota {} unavailable: {e}", usage.quota_code),
+            }
+            usage
+        });
+        Ok(join_all(futures).await)
+    }
+
+    /// vCPUs of pending and running instances by class and whether they're
+    /// spot instances
+    async fn running_vcpus(&self) -> Result<BTreeMap<(VcpuClass, bool), f64>, Error> {
+        let (instances, spot_requests) = try_join!(
+            self.ec2.get_all_instances(),
+            self.ec2.get_spot_instance_requests()
+        )?;
+        let spot_instances: HashSet<_> = spot_requests.filter_map(|req| req.instance_id).collect();
+        let instances: Vec<_> = instances
+            .filter(|inst| inst.state == "pending" || inst.state == "running")
+            .map(|inst| {
+                let spot = spot_instances.contains(&inst.id);
+                (inst.instance_type, spot)
+            })
+            .collect();
+        let instance_types: Vec<_> = instances
+            .iter()
+            .map(|(instance_type, _)| instance_type.clone())
+            .unique()
+            .collect();
+        let vcpus = self.ec2.get_instance_type_vcpus(&instance_types).await?;
+        Ok(vcpu_usage(
+            instances
+                .iter()
+                .map(|(instance_type, spot)| (instance_type.as_str(), *spot)),
+            &vcpus,
+        ))
+    }
+
+    /// The spot vCPU quota of each instance class of `req` must leave room
+    /// for its instances, quotas which can't be looked up are skipped
+    /// # Errors
+    /// Returns error if aws api call fails or the request would exceed the
+    /// quota
+    pub async fn check_spot_vcpu_quota(&self, req: &SpotRequest) -> Result<(), Error> {
+        let instance_types = req.instance_types();
+        let (vcpus, usage) = try_join!(
+            self.ec2.get_instance_type_vcpus(&instance_types),
+            self.running_vcpus(),
+        )?;
+        let mut requested: BTreeMap<VcpuClass, f64> = BTreeMap::new();
+        for instance_type in &instance_types {
+            let (class, n) = match (
+                VcpuClass::from_instance_type(instance_type),
+                vcpus.get(instance_type),
+            ) {
+                (Some(class), Some(n)) => (class, f64::from(*n)),
+                _ => continue,
+            };
+            let entry = requested.entry(class).or_insert(0.0);
+            *entry = entry.max(n * req.count.max(1) as f64);
+        }
+        for (class, needed) in requested {
+            let in_use = usage.get(&(class, true)).copied().unwrap_or(0.0);
+            let mut quota_usage = match QuotaUsage::vcpus(class, true, in_use) {
+                Some(quota_usage) => quota_usage,
+                None => continue,
+            };
+            match self
+                .quotas
+                .get_quota(quota_usage.service_code, quota_usage.quota_code)
+                .await
+            {
+                Ok(quota) => quota_usage.quota = quota,
+                Err(e) => {
+                    warn!("spot vCPU quota of {class} instances unavailable: {e}");
+                    continue;
+                }
+            }
+            if quota_usage.exceeded_by(needed) {
+                return Err(format_err!(
+                    "spot vCPU quota of {class} instances is {}, {in_use} in use and the request \
+                     needs up to {needed}",
+                    quota_usage.quota.unwrap_or(0.0)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// A launch's dns name must be a host name within one of the hosted zones
+    /// # Errors
+    /// Returns error if aws api call fails or `dns_name` can't be registered
+    pub async fn check_launch_dns_name(&self, dns_name: &str) -> Result<(), Error> {
+        validate_host_name(dns_name)?;
+        let zones = self.route53.get_hosted_zones().await?;
+        hosted_zone_for(
+            dns_name,
+            zones.iter().map(|z| (z.id.as_str(), z.name.as_str())),
+        )
+        .ok_or_else(|| format_err!("no hosted zone for {dns_name}"))?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn record_spot_launch(
+        &self,
+        req: &SpotRequest,
+        spot_launch: &SpotLaunch,
+    ) -> Result<(), Error> {
+        let mut launch =
+            LaunchHistory::new(spot_launch.instance_type.clone(), req.ami.clone(), true);
+        launch.spot_request_id = Some(spot_launch.spot_id.clone());
+        launch.launch_params = Some(serde_json::to_value(req)?);
+        launch.auto_recover = req.auto_recover;
+        launch.dns_name.clone_from(&req.dns_name);
+        self.storage.insert_launch(&launch).await
+    }
+
+    /// Apply an instance event pushed by EventBridge. The state of a known
+    /// instance is updated in place, an unknown instance refreshes the whole
+    /// list. Spot interruption warnings are sent as notifications.
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn apply_ec2_event(&self, event: &Ec2Event, ses: &SesInstance) -> Result<(), Error> {
+        match event {
+            Ec2Event::StateChange { instance_id, state } => {
+                self.cache.invalidate([ResourceType::Instances]);
+                let updated = {
+                    let mut instances = INSTANCE_LIST.write().await;
+                    let mut updated = instances.to_vec();
+                    match updated.iter_mut().find(|inst| &inst.id == instance_id) {
+                        Some(inst) => {
+                            inst.state = state.clone();
+                            *instances = Arc::new(updated);
+                            true
+                        }
+                        None => false,
+                    }
+                };
+                if !updated {
+                    self.fill_instance_list().await?;
+                }
+                if state == "terminated" {
+                    self.storage
+                        .record_termination(instance_id, OffsetDateTime::now_utc())
+                        .await?;
+                }
+            }
+            Ec2Event::SpotInterruption { .. } => {
+                send_notification(
+                    &self.config,
+                    ses,
+                    "Spot instance interruption",
+                    &event.description(),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pull `repo:tag` from this account's ecr registry into the local docker
+    /// daemon, returns the final status reported by docker
+    /// # Errors
+    /// Returns error if aws or docker api call fails
+    pub async fn pull_ecr_image(&self, repo: &str, tag: &str) -> Result<StackString, Error> {
+        let login = self.ecr.get_registry_login().await?;
+        let image = login.image(repo, tag);
+        let credentials = DockerCredentials {
+            username: Some(login.username.into()),
+            password: Some(login.password.into()),
+            serveraddress: Some(login.registry.into()),
+            ..DockerCredentials::default()
+        };
+        let status = self.docker.pull_image(&image, Some(credentials)).await?;
+        self.storage
+            .insert_audit_log(&AuditLog::new("docker_pull", image, Some(status.clone())))
+            .await?;
+        Ok(status)
+    }
+
+    /// Add `ecr_image_history` rows for the images among `images` which are
+    /// new or have been retagged, returns the number of rows added
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn record_ecr_images(&self, images: &[ImageInfo]) -> Result<usize, Error> {
+        let latest = self.storage.get_latest_ecr_history().await?;
+        let changes = EcrImageHistory::changes(&latest, images, OffsetDateTime::now_utc());
+        for change in &changes {
+            self.storage.insert_ecr_history(change).await?;
+        }
+        Ok(changes.len())
+    }
+
+    /// Record the current images of `repo`, e.g. after a push event
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn record_ecr_repo(&self, repo: &str) -> Result<usize, Error> {
+        let images: Vec<_> = self.ecr.get_all_images(repo).await?.collect();
+        self.cache.invalidate([ResourceType::Ecr]);
+        self.record_ecr_images(&images).await
+    }
+
+    /// Pushes and retags of `repo`, most recent first
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn ecr_history(&self, repo: &str) -> Result<Vec<EcrImageEvent>, Error> {
+        let history = self.storage.get_ecr_history(repo).await?;
+        Ok(repo_timeline(&history))
+    }
+
+    /// Check the enabled price alerts against the latest spot prices of their
+    /// regions, one notification lists every alert whose price has just
+    /// dropped below its threshold. Returns the number of alerts triggered.
+    /// # Errors
+    /// Returns error if aws api call, db query or notification fails
+    pub async fn check_price_alerts(&self, ses: &SesInstance) -> Result<usize, Error> {
+        let mut alerts = self.storage.get_enabled_price_alerts().await?;
+        if alerts.is_empty() {
+            return Ok(0);
+        }
+        let mut instance_types: BTreeMap<StackString, HashSet<StackString>> = BTreeMap::new();
+        for alert in &alerts {
+            instance_types
+                .entry(alert.region.clone())
+                .or_default()
+                .insert(alert.instance_type.clone());
+        }
+        let mut prices = HashMap::new();
+        for (region, types) in instance_types {
+            let mut ec2 = self.ec2.clone();
+            ec2.set_region(&region).await?;
+            for (instance_type, price) in ec2.get_latest_spot_inst_prices(&types).await? {
+                prices.insert((region.clone(), instance_type), f64::from(price));
+            }
+        }
+        let now = OffsetDateTime::now_utc();
+        let mut triggered = Vec::new();
+        for alert in &mut alerts {
+            let key = (alert.region.clone(), alert.instance_type.clone());
+            let price = match prices.get(&key) {
+                Some(price) => *price,
+                None => continue,
+            };
+            if alert.evaluate(price, now) {
+                triggered.push(alert.alert_line());
+            }
+            self.storage.update_price_alert_state(alert).await?;
+        }
+        if triggered.is_empty() {
+            return Ok(0);
+        }
+        let subject = format_sstr!("{} spot prices below alert threshold", triggered.len());
+        send_notification(&self.config, ses, &subject, &triggered.join("\n")).await?;
+        Ok(triggered.len())
+    }
+
+    /// Off-region copy state of the volumes covered by each dr policy
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn dr_compliance(&self) -> Result<Vec<(DrPolicy, Vec<DrCompliance>)>, Error> {
+        let policies = self.storage.get_dr_policies().await?;
+        if policies.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (volumes, snapshots) =
+            try_join!(self.ec2.get_all_volumes(), self.ec2.get_all_snapshots())?;
+        let volumes: Vec<_> = volumes.collect();
+        let snapshots: Vec<_> = snapshots.collect();
+        let mut copies: HashMap<StackString, Vec<SnapshotInfo>> = HashMap::new();
+        for policy in &policies {
+            if copies.contains_key(&policy.target_region) {
+                continue;
+            }
+            let mut target = self.ec2.clone();
+            target.set_region(&policy.target_region).await?;
+            let region_copies = target
+                .get_all_snapshots()
+                .await?
+                .filter(|s| s.tags.contains_key(DR_SOURCE_VOLUME_TAG))
+                .collect();
+            copies.insert(policy.target_region.clone(), region_copies);
+        }
+        let now = OffsetDateTime::now_utc();
+        Ok(policies
+            .into_iter()
+            .map(|policy| {
+                let entries = policy.evaluate(
+                    &volumes,
+                    &snapshots,
+                    copies
+                        .get(&policy.target_region)
+                        .map_or(&[][..], Vec::as_slice),
+                    now,
+                );
+                (policy, entries)
+            })
+            .collect())
+    }
+
+    /// Start a snapshot copy, or a snapshot to copy on a later run, for each
+    /// volume lacking a recent copy in its policy's target region. Returns
+    /// the number of copies and snapshots started.
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn enforce_dr_policies(&self) -> Result<usize, Error> {
+        let mut started = 0;
+        for (policy, entries) in self.dr_compliance().await? {
+            for entry in entries {
+                let (volume_id, action) = match (entry.volume_id, entry.action) {
+                    (Some(volume_id), Some(action)) => (volume_id, action),
+                    _ => continue,
+                };
+                let details = match action {
+                    DrAction::CopySnapshot(snapshot_id) => {
+                        let tags = hashmap! {
+                            "Name".into() => format_sstr!("{}-dr", policy.volume_name),
+                            DR_SOURCE_VOLUME_TAG.into() => volume_id.clone(),
+                            DR_SOURCE_SNAPSHOT_TAG.into() => snapshot_id.clone(),
+                        };
+                        let mut target = self.ec2.clone();
+                        target.set_region(&policy.target_region).await?;
+                        let copy_id = target
+                            .copy_snapshot(
+                                self.ec2.region(),
+                                &snapshot_id,
+                                policy.encrypted,
+                                policy.kms_key_id.as_ref().map(StackString::as_str),
+                                &tags,
+                            )
+                            .await?;
+                        format_sstr!(
+                            "copy {snapshot_id} to {copy_id} in {}",
+                            policy.target_region
+                        )
+                    }
+                    DrAction::CreateSnapshot => {
+                        let tags = hashmap! {
+                            "Name".into() => format_sstr!("{}-dr", policy.volume_name),
+                        };
+                        match self
+                            .ec2
+                            .create_ebs_snapshot(volume_id.as_str(), &tags)
+                            .await?
+                        {
+                            Some(snapshot_id) => format_sstr!("snapshot {snapshot_id}"),
+                            None => continue,
+                        }
+                    }
+                };
+                info!("dr policy {policy}: {volume_id} {details}");
+                self.storage
+                    .insert_audit_log(&AuditLog::new("dr_policy", volume_id, Some(details)))
+                    .await?;
+                started += 1;
+            }
+        }
+        if started > 0 {
+            self.cache.invalidate([ResourceType::Snapshot]);
+        }
+        Ok(started)
+    }
+
+    /// Send one notification listing the acm certificates expiring within
+    /// `certificate_expiry_days`, returns the number of certificates listed
+    /// # Errors
+    /// Returns error if aws api call or notification fails
+    pub async fn check_certificate_expiry(&self, ses: &SesInstance) -> Result<usize, Error> {
+        let now = OffsetDateTime::now_utc();
+        let certificates = self
+            .acm
+            .expiring_certificates(self.config.certificate_expiry_days)
+            .await?;
+        if certificates.is_empty() {
+            return Ok(0);
+        }
+        let body = certificates
+            .iter()
+            .map(|c| certificate_expiry_line(c, now))
+            .join("\n");
+        let subject = format_sstr!(
+            "{} acm certificates expire within {} days",
+            certificates.len(),
+            self.config.certificate_expiry_days
+        );
+        send_notification(&self.config, ses, &subject, &body).await?;
+        Ok(certificates.len())
+    }
+
+    /// Re-request spot instances flagged `auto_recover` which aws terminated,
+    /// with the parameters they were launched with. A launch is given up on
+    /// after `spot_recovery_max_attempts` failed requests, a notification is
+    /// sent once it is either recovered or given up on. Returns the number of
+    /// instances recovered.
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn recover_spot_instances(&self, ses: &SesInstance) -> Result<usize, Error> {
+        self.sync_launch_history().await?;
+        let launches = self.storage.get_recoverable_launches().await?;
+        if launches.is_empty() {
+            return Ok(0);
+        }
+        let spot_statuses: HashMap<_, _> = self
+            .ec2
+            .get_spot_instance_requests()
+            .await?
+            .map(|req| (req.id, req.status))
+            .collect();
+        let mut recovered = 0;
+        for mut launch in launches {
+            let instance_id = launch.instance_id.clone().unwrap_or_default();
+            let terminated_by_user = launch
+                .spot_request_id
+                .as_ref()
+                .and_then(|id| spot_statuses.get(id))
+                .map_or(false, |status| status == SPOT_TERMINATED_BY_USER);
+            if terminated_by_user {
+                launch.recovery_status = Some(RECOVERY_SKIPPED.into());
+                self.storage.update_launch(&launch).await?;
+                continue;
+            }
+            let (subject, body) = match self.request_spot_recovery(&launch).await {
+                Ok(()) => {
+                    recovered += 1;
+                    launch.recovery_status = Some(RECOVERY_RECOVERED.into());
+                    (
+                        "Spot instance recovered",
+                        format_sstr!(
+                            "{instance_id} ({}) was terminated by aws and has been re-requested",
+                            launch.instance_type
+                        ),
+                    )
+                }
+                Err(e) => {
+                    launch.recovery_attempts += 1;
+                    if launch.recovery_attempts < self.config.spot_recovery_max_attempts {
+                        warn!(
+                            "spot recovery of {instance_id} failed (attempt {}): {e}",
+                            launch.recovery_attempts
+                        );
+                        self.storage.update_launch(&launch).await?;
+                        continue;
+                    }
+                    launch.recovery_status = Some(RECOVERY_EXHAUSTED.into());
+                    (
+                        "Spot instance recovery failed",
+                        format_sstr!(
+                            "gave up re-requesting {instance_id} ({}) after {} attempts: {e}",
+                            launch.instance_type,
+                            launch.recovery_attempts
+                        ),
+                    )
+                }
+            };
+            self.storage.update_launch(&launch).await?;
+            self.storage
+                .insert_audit_log(&AuditLog::new(
+                    "spot_recovery",
+                    instance_id,
+                    Some(body.clone()),
+                ))
+                .await?;
+            // the outcome is already recorded, a failed notification is only logged
+            if let Err(e) = send_notification(&self.config, ses, subject, &body).await {
+                error!("failed to send spot recovery notification: {e}");
+            }
+        }
+        Ok(recovered)
+    }
+
+    /// Request a single replacement for `launch`, of the instance type it was
+    /// allocated and at most the price each instance of the original request
+    /// was allowed
+    async fn request_spot_recovery(&self, launch: &LaunchHistory) -> Result<(), Error> {
+        let params = launch
+            .launch_params
+            .clone()
+            .ok_or_else(|| format_err!("no launch parameters recorded"))?;
+        let mut req: SpotRequest = serde_json::from_value(params)?;
+        req.price = req.instance_price();
+        req.max_total_price = None;
+        req.instance_type = launch.instance_type.clone();
+        req.count = 1;
+        req.auto_recover = true;
+        self.request_spot_instance(&mut req).await.map(|_| ())
+    }
+
+    /// Start replacing `instance_id` by a new instance launched with the
+    /// parameters, tags and script it was launched with, from `ami` or else
+    /// the newest cataloged build of its ami. The replacement is advanced by
+    /// `advance_replacements`.
+    /// # Errors
+    /// Returns error if the instance has no recorded launch, is already being
+    /// replaced, or aws api call or db query fails
+    pub async fn start_replacement(
+        &self,
+        instance_id: &str,
+        ami: Option<&str>,
+        dns_name: Option<&str>,
+        terminate_old: bool,
+        soak_minutes: i32,
+    ) -> Result<InstanceReplacement, Error> {
+        if soak_minutes < 0 {
+            return Err(format_err!("soak period can't be negative"));
+        }
+        let launch = self
+            .storage
+            .get_launch_by_instance_id(instance_id)
+            .await?
+            .filter(|launch| launch.launch_params.is_some())
+            .ok_or_else(|| format_err!("no launch parameters recorded for {instance_id}"))?;
+        if launch.terminated_at.is_some() {
+            return Err(format_err!("{instance_id} is terminated"));
+        }
+        if !self
+            .storage
+            .get_active_replacements_by_instance(instance_id)
             .await?
-            .and_then(|f| async move { Ok((f.family_name.clone(), f)) })
-            .try_collect()
+            .is_empty()
+        {
+            return Err(format_err!("{instance_id} is already being replaced"));
+        }
+        let ami: StackString = match ami {
+            Some(ami) => {
+                let ami_map = self.ec2.get_ami_map().await?;
+                ami_map.get(ami).map_or_else(|| ami.into(), Clone::clone)
+            }
+            None => {
+                let catalog: HashMap<_, _> = self
+                    .storage
+                    .get_ami_catalog()
+                    .await?
+                    .into_iter()
+                    .map(|entry| (entry.ami_id.clone(), entry))
+                    .collect();
+                latest_build(&catalog, &launch.ami)
+                    .map_or_else(|| launch.ami.clone(), |entry| entry.ami_id.clone())
+            }
+        };
+        let dns_name: Option<StackString> =
+            dns_name.map(Into::into).or_else(|| launch.dns_name.clone());
+        if let Some(dns_name) = &dns_name {
+            self.check_launch_dns_name(dns_name).await?;
+        }
+        let replacement =
+            InstanceReplacement::new(instance_id, ami, dns_name, terminate_old, soak_minutes);
+        self.storage.insert_replacement(&replacement).await?;
+        self.storage
+            .insert_audit_log(&AuditLog::new(
+                "replacement_started",
+                instance_id,
+                Some(format_sstr!("{} {}", replacement.id, replacement.ami)),
+            ))
             .await?;
-        let instance_list: HashMap<_, _> = InstanceList::get_all_instances(&self.pool)
+        Ok(replacement)
+    }
+
+    /// Cancel a replacement before its old instance is terminated, an already
+    /// launched new instance is kept
+    /// # Errors
+    /// Returns error if the replacement can't be cancelled or db query fails
+    pub async fn cancel_replacement(&self, id: Uuid) -> Result<InstanceReplacement, Error> {
+        let mut replacement = self
+            .storage
+            .get_replacement(id)
+            .await?
+            .ok_or_else(|| format_err!("no replacement {id}"))?;
+        let state = replacement.state()?;
+        if !state.can_cancel() {
+            return Err(format_err!(
+                "replacement {id} can't be cancelled once {state}"
+            ));
+        }
+        replacement.message = Some(format_sstr!("cancelled while {state}"));
+        if !replacement
+            .claim(self.storage.as_ref(), ReplacementState::Cancelled)
             .await?
-            .map_ok(|i| (i.instance_type.clone(), i))
-            .try_collect()
+        {
+            return Err(format_err!(
+                "replacement {id} moved on from {state}, reload to see its state"
+            ));
+        }
+        self.storage
+            .insert_audit_log(&AuditLog::new(
+                "replacement_cancelled",
+                replacement.old_instance_id.clone(),
+                Some(format_sstr!("{id}")),
+            ))
             .await?;
-        let inst_list: Vec<_> = instance_list
-            .keys()
-            .filter_map(|inst| {
-                if search.iter().any(|s| inst.starts_with(s.as_ref())) {
-                    Some(inst.clone())
+        Ok(replacement)
+    }
+
+    /// Move active replacements as far through their steps as they can go
+    /// right now. Replacements whose step fails are marked failed and a
+    /// notification is sent. Returns the number of replacements which
+    /// changed state.
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn advance_replacements(&self, ses: &SesInstance) -> Result<usize, Error> {
+        let replacements = self.storage.get_active_replacements().await?;
+        if replacements.is_empty() {
+            return Ok(0);
+        }
+        self.sync_launch_history().await?;
+        let mut advanced = 0;
+        for mut replacement in replacements {
+            let initial_state = replacement.state.clone();
+            let result = loop {
+                match self.advance_replacement(&mut replacement).await {
+                    Ok(true) => {}
+                    Ok(false) => break Ok(()),
+                    Err(e) => break Err(e),
+                }
+            };
+            let e = match result {
+                Ok(()) => {
+                    if replacement.state == initial_state {
+                        continue;
+                    }
+                    advanced += 1;
+                    if replacement.state()? == ReplacementState::Completed {
+                        self.storage
+                            .insert_audit_log(&AuditLog::new(
+                                "replacement_completed",
+                                replacement.old_instance_id.clone(),
+                                replacement.new_instance_id.clone(),
+                            ))
+                            .await?;
+                    }
+                    continue;
+                }
+                Err(e) => e,
+            };
+            advanced += 1;
+            let body = format_sstr!(
+                "replacement of {} failed while {}: {e}",
+                replacement.old_instance_id,
+                replacement.state
+            );
+            replacement.message = Some(format_sstr!("{e}"));
+            // cancelled while the step ran
+            if !replacement
+                .claim(self.storage.as_ref(), ReplacementState::Failed)
+                .await?
+            {
+                continue;
+            }
+            self.storage
+                .insert_audit_log(&AuditLog::new(
+                    "replacement_failed",
+                    replacement.old_instance_id.clone(),
+                    Some(body.clone()),
+                ))
+                .await?;
+            // the outcome is already recorded, a failed notification is only logged
+            if let Err(e) =
+                send_notification(&self.config, ses, "Instance replacement failed", &body).await
+            {
+                error!("failed to send instance replacement notification: {e}");
+            }
+        }
+        Ok(advanced)
+    }
+
+    /// Run the current step of `replacement`, returns whether it moved on to
+    /// the next one. Each move is claimed before the side effects of the step
+    /// it leads to, see `InstanceReplacement::claim`
+    async fn advance_replacement(
+        &self,
+        replacement: &mut InstanceReplacement,
+    ) -> Result<bool, Error> {
+        let now = OffsetDateTime::now_utc();
+        match replacement.state()? {
+            ReplacementState::Pending => {
+                let launch = self
+                    .storage
+                    .get_launch_by_instance_id(&replacement.old_instance_id)
+                    .await?
+                    .ok_or_else(|| {
+                        format_err!("no launch recorded for {}", replacement.old_instance_id)
+                    })?;
+                let params = launch
+                    .launch_params
+                    .ok_or_else(|| format_err!("no launch parameters recorded"))?;
+                let mut req: SpotRequest = serde_json::from_value(params)?;
+                req.ami = replacement.ami.clone();
+                req.price = req.instance_price();
+                req.max_total_price = None;
+                req.instance_type = launch.instance_type;
+                req.count = 1;
+                // the record is only moved once the new instance is healthy
+                req.dns_name = None;
+                if !replacement
+                    .claim(self.storage.as_ref(), ReplacementState::Launching)
+                    .await?
+                {
+                    return Ok(false);
+                }
+                let spot_id = self
+                    .request_spot_instance(&mut req)
+                    .await?
+                    .into_iter()
+                    .next()
+                    .map(|launch| launch.spot_id)
+                    .ok_or_else(|| format_err!("no spot request made"))?;
+                replacement.spot_request_id = Some(spot_id);
+                self.storage
+                    .update_replacement_spot_request_id(&replacement)
+                    .await?;
+                Ok(true)
+            }
+            ReplacementState::Launching => {
+                let spot_id = replacement.spot_request_id.clone().unwrap_or_default();
+                let instance_id = self
+                    .storage
+                    .get_launch_by_spot_request_id(&spot_id)
+                    .await?
+                    .and_then(|launch| launch.instance_id);
+                match instance_id {
+                    Some(instance_id) => {
+                        replacement.new_instance_id = Some(instance_id);
+                        replacement
+                            .claim(self.storage.as_ref(), ReplacementState::HealthCheck)
+                            .await
+                    }
+                    None => {
+                        let timeout = Duration::minutes(self.config.setup_timeout_minutes);
+                        if now - replacement.updated_at > timeout {
+                            return Err(format_err!(
+                                "spot request {spot_id} wasn't fulfilled within {} minutes",
+                                self.config.setup_timeout_minutes
+                            ));
+                        }
+                        Ok(false)
+                    }
+                }
+            }
+            ReplacementState::HealthCheck => {
+                let instance_id = replacement.new_instance_id.clone().unwrap_or_default();
+                let launch = self
+                    .storage
+                    .get_launch_by_instance_id(&instance_id)
+                    .await?
+                    .ok_or_else(|| format_err!("no launch recorded for {instance_id}"))?;
+                if launch.terminated_at.is_some() {
+                    return Err(format_err!("{instance_id} was terminated"));
+                }
+                match launch.setup_status.as_deref() {
+                    None => return Ok(false),
+                    Some(SETUP_DONE) => {}
+                    Some(status) => {
+                        return Err(format_err!(
+                            "setup of {instance_id} {status}: {}",
+                            launch.setup_message.as_deref().unwrap_or("")
+                        ));
+                    }
+                }
+                if replacement.dns_name.is_some() {
+                    replacement
+                        .claim(self.storage.as_ref(), ReplacementState::SwappingDns)
+                        .await
                 } else {
-                    None
+                    replacement.dns_swapped_at = Some(now);
+                    replacement
+                        .claim(self.storage.as_ref(), ReplacementState::Soaking)
+                        .await
+                }
+            }
+            ReplacementState::SwappingDns => {
+                let instance_id = replacement.new_instance_id.clone().unwrap_or_default();
+                let dns_name = replacement.dns_name.clone().unwrap_or_default();
+                let public_ip: Ipv4Addr = self
+                    .ec2
+                    .get_instance_details(&instance_id)
+                    .await?
+                    .public_ip
+                    .and_then(|ip| ip.parse().ok())
+                    .ok_or_else(|| format_err!("{instance_id} has no public ip"))?;
+                let zones = self.route53.get_hosted_zones().await?;
+                let zone_id = hosted_zone_for(
+                    &dns_name,
+                    zones.iter().map(|z| (z.id.as_str(), z.name.as_str())),
+                )
+                .ok_or_else(|| format_err!("no hosted zone for {dns_name}"))?;
+                replacement.dns_swapped_at = Some(now);
+                if !replacement
+                    .claim(self.storage.as_ref(), ReplacementState::Soaking)
+                    .await?
+                {
+                    return Ok(false);
+                }
+                self.route53
+                    .upsert_a_record(zone_id, &dns_name, public_ip, LAUNCH_DNS_TTL)
+                    .await?;
+                self.cache.invalidate([ResourceType::Route53]);
+                self.storage
+                    .insert_audit_log(&AuditLog::new(
+                        "replacement_dns",
+                        dns_name,
+                        Some(format_sstr!("{instance_id} {public_ip}")),
+                    ))
+                    .await?;
+                Ok(true)
+            }
+            ReplacementState::Soaking => {
+                if !replacement.soak_finished(now) {
+                    return Ok(false);
                 }
-            })
-            .collect();
+                let state = if replacement.terminate_old {
+                    ReplacementState::TerminatingOld
+                } else {
+                    ReplacementState::Completed
+                };
+                replacement.claim(self.storage.as_ref(), state).await
+            }
+            // not cancellable, claimed when the soak period ended
+            ReplacementState::TerminatingOld => {
+                self.terminate([&replacement.old_instance_id]).await?;
+                replacement
+                    .claim(self.storage.as_ref(), ReplacementState::Completed)
+                    .await
+            }
+            ReplacementState::Completed
+            | ReplacementState::Failed
+            | ReplacementState::Cancelled => Ok(false),
+        }
+    }
 
-        let spot_prices = self.ec2.get_latest_spot_inst_prices(&inst_list).await?;
+    /// Compare instance states and spot request statuses with the previous
+    /// poll and queue webhook deliveries for changes, fulfilled spot requests
+    /// and completed snapshots run their hooks. Returns the number of events
+    /// found.
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn poll_resource_events(&self, states: &mut ResourceStates) -> Result<usize, Error> {
+        let (instances, spot_requests) = try_join!(
+            self.ec2.get_all_instances(),
+            self.ec2.get_spot_instance_requests()
+        )?;
+        let events = states.update(instances, spot_requests);
+        let hooks = Hooks::from_config(&self.config)?;
+        for payload in &events {
+            enqueue_webhooks(self.storage.as_ref(), payload).await?;
+            if payload.event == WebhookEvent::SpotRequestFulfilled {
+                hooks
+                    .run(
+                        self.storage.as_ref(),
+                        HookEvent::SpotFulfilled,
+                        &payload.resource_id,
+                        &payload.detail,
+                    )
+                    .await?;
+            }
+        }
+        if hooks.has_event(HookEvent::SnapshotCompleted) {
+            let snapshots = self.ec2.get_all_snapshots().await?;
+            for snapshot in states.completed_snapshots(snapshots) {
+                let context = json!({
+                    "snapshot_id": snapshot.id,
+                    "volume_id": snapshot.volume_id,
+                    "volume_size": snapshot.volume_size,
+                    "name": snapshot.tags.get("Name"),
+                    "encrypted": snapshot.encrypted,
+                });
+                hooks
+                    .run(
+                        self.storage.as_ref(),
+                        HookEvent::SnapshotCompleted,
+                        &snapshot.id,
+                        &context,
+                    )
+                    .await?;
+            }
+        }
+        Ok(events.len())
+    }
 
-        let prices: HashMap<_, _> = InstancePricing::get_all(&self.pool)
-            .await?
-            .map_ok(|p| ((p.instance_type.clone(), p.price_type.clone()), p))
-            .try_collect()
+    /// Persist the instances, volumes, snapshots, dns records and iam users
+    /// of the current region and drop snapshots older than
+    /// `inventory_retention_days`. Returns the number of resources recorded.
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn record_inventory_snapshot(&self) -> Result<usize, Error> {
+        let (instances, volumes, snapshots, dns_records, users) = try_join!(
+            self.ec2.get_all_instances(),
+            self.ec2.get_all_volumes(),
+            self.ec2.get_all_snapshots(),
+            self.route53.list_all_dns_records(),
+            self.iam.list_users(),
+        )?;
+        let items: Vec<_> = instances
+            .filter(|inst| inst.state != "terminated")
+            .map(|inst| InventoryItem::from_instance(&inst))
+            .chain(volumes.map(|vol| InventoryItem::from_volume(&vol)))
+            .chain(snapshots.map(|snap| InventoryItem::from_snapshot(&snap)))
+            .chain(
+                dns_records
+                    .iter()
+                    .map(|(zone_id, record)| InventoryItem::from_dns_record(zone_id, record)),
+            )
+            .chain(users.map(|user| InventoryItem::from_iam_user(&user)))
+            .collect();
+        self.storage
+            .insert_inventory(&InventorySnapshot::new(self.ec2.region(), &items)?)
             .await?;
+        let cutoff =
+            OffsetDateTime::now_utc() - Duration::days(self.config.inventory_retention_days);
+        self.storage.delete_inventory_before(cutoff).await?;
+        Ok(items.len())
+    }
 
-        let prices: Result<Vec<_>, Error> = inst_list
-            .into_iter()
-            .map(|inst| {
-                let ond_price = prices
-                    .get(&(inst.clone(), "ondemand".into()))
-                    .map(|x| x.price);
-                let res_price = prices
-                    .get(&(inst.clone(), "reserved".into()))
-                    .map(|x| x.price);
-                let spot_price = spot_prices.get(inst.as_str());
-                let instance_metadata = instance_list
-                    .get(&inst)
-                    .ok_or_else(|| format_err!("this should be impossible {}", inst))?;
-                let inst_fam = inst
-                    .split('.')
-                    .next()
-                    .ok_or_else(|| format_err!("invalid instance name {}", inst))?;
-                let inst_fam = instance_families
-                    .get(inst_fam)
-                    .ok_or_else(|| format_err!("inst_fam {} does not exist", inst_fam))?;
-                let instance_family = inst_fam.family_type.parse()?;
+    /// Changes between the inventory snapshot closest to `since` and the
+    /// latest one of the current region, `None` if no snapshot was taken yet
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn inventory_changes(
+        &self,
+        since: OffsetDateTime,
+    ) -> Result<Option<InventoryDiff>, Error> {
+        let region = self.ec2.region();
+        let (baseline, latest) = try_join!(
+            self.storage.get_inventory_baseline(region, since),
+            self.storage.get_latest_inventory(region),
+        )?;
+        match (baseline, latest) {
+            (Some(baseline), Some(latest)) => InventoryDiff::new(&baseline, &latest).map(Some),
+            _ => Ok(None),
+        }
+    }
 
-                Ok(AwsInstancePrice {
-                    instance_type: inst,
-                    ondemand_price: ond_price,
-                    spot_price: spot_price.map(|x| f64::from(*x)),
-                    reserved_price: res_price,
-                    ncpu: instance_metadata.n_cpu,
-                    memory: instance_metadata.memory_gib,
-                    instance_family,
-                    data_url: inst_fam.data_url.clone(),
-                })
+    /// Poll cloud-init on newly launched instances until it reports
+    /// completion, then run `setup_check_command` if one is configured. The
+    /// setup duration, measured at the check which saw it finish, and outcome
+    /// are recorded on the launch, failures and timeouts are audited and
+    /// notified. Returns the number of launches whose setup finished.
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn check_instance_setup(&self, ses: &SesInstance) -> Result<usize, Error> {
+        self.sync_launch_history().await?;
+        match self.register_launch_dns().await {
+            Ok(registered) if registered > 0 => info!("registered dns of {registered} launches"),
+            Ok(_) => {}
+            Err(e) => error!("launch dns registration failed: {e}"),
+        }
+        let launches = self.storage.get_pending_setup_launches().await?;
+        if launches.is_empty() {
+            return Ok(0);
+        }
+        let instances: HashMap<_, _> = INSTANCE_LIST
+            .read()
+            .await
+            .iter()
+            .map(|inst| {
+                (
+                    inst.id.clone(),
+                    (inst.state.clone(), inst.launch_time.to_offsetdatetime()),
+                )
             })
             .collect();
-        let mut prices = prices?;
-        prices.sort_by_key(|p| (p.ncpu, p.memory as i64));
-        Ok(prices)
+        let timeout = Duration::minutes(self.config.setup_timeout_minutes);
+        let now = OffsetDateTime::now_utc();
+        let mut finished = 0;
+        for mut launch in launches {
+            let instance_id = launch.instance_id.clone().unwrap_or_default();
+            let (state, launch_time) = match instances.get(&instance_id) {
+                Some((state, launch_time)) => (state, *launch_time),
+                None => continue,
+            };
+            let elapsed = now - launch_time;
+            let status = if state == "running" {
+                self.cloud_init_status(&instance_id).await
+            } else {
+                CloudInitStatus::Unknown
+            };
+            let check_ok = match (&status, &self.config.setup_check_command) {
+                (CloudInitStatus::Done, Some(command)) => {
+                    Some(self.setup_check_passes(&instance_id, command).await)
+                }
+                _ => None,
+            };
+            let outcome = SetupOutcome::evaluate(&status, check_ok, elapsed, timeout);
+            let cloud_init_done = status == CloudInitStatus::Done && launch.cloud_init_at.is_none();
+            if cloud_init_done {
+                launch.cloud_init_at = Some(now);
+            }
+            let setup_status = match outcome.to_status() {
+                Some(setup_status) => setup_status,
+                None => {
+                    if cloud_init_done {
+                        self.storage.update_launch(&launch).await?;
+                    }
+                    continue;
+                }
+            };
+            finished += 1;
+            launch.setup_status = Some(setup_status.into());
+            launch.setup_seconds = Some(elapsed.whole_seconds() as i32);
+            if outcome == SetupOutcome::Done {
+                launch.healthy_at = Some(now);
+            }
+            let (subject, body) = match &outcome {
+                SetupOutcome::Failed(message) => {
+                    launch.setup_message = Some(message.clone());
+                    (
+                        "Instance setup failed",
+                        format_sstr!(
+                            "setup of {instance_id} ({}) failed after {} minutes: {message}",
+                            launch.instance_type,
+                            elapsed.whole_minutes()
+                        ),
+                    )
+                }
+                SetupOutcome::TimedOut => (
+                    "Instance setup timed out",
+                    format_sstr!(
+                        "setup of {instance_id} ({}) didn't finish within {} minutes, cloud-init \
+                         status {status:?}",
+                        launch.instance_type,
+                        self.config.setup_timeout_minutes
+                    ),
+                ),
+                SetupOutcome::Done | SetupOutcome::Pending => {
+                    self.storage.update_launch(&launch).await?;
+                    continue;
+                }
+            };
+            self.storage.update_launch(&launch).await?;
+            self.storage
+                .insert_audit_log(&AuditLog::new(
+                    "instance_setup",
+                    instance_id,
+                    Some(body.clone()),
+                ))
+                .await?;
+            // the outcome is already recorded, a failed notification is only logged
+            if let Err(e) = send_notification(&self.config, ses, subject, &body).await {
+                error!("failed to send instance setup notification: {e}");
+            }
+        }
+        Ok(finished)
     }
 
+    /// Point the dns name requested with a launch at its instance's public ip
+    /// once it is running. Returns the number of records written.
     /// # Errors
-    /// Returns error if aws api call fails
-    pub async fn print_ec2_prices(&self, search: &[impl AsRef<str>]) -> Result<(), Error> {
-        let mut prices: Vec<_> = self
-            .get_ec2_prices(search)
-            .await?
-            .into_iter()
-            .map(|price| {
-                let mut outstr = vec![format_sstr!("{:14} ", price.instance_type)];
-                match price.ondemand_price {
-                    Some(p) => outstr.push(format_sstr!("ond: ${p:0.4}/hr   ")),
-                    None => outstr.push(format_sstr!("{:4} {:9}   ", "", "")),
-                }
-                match price.spot_price {
-                    Some(p) => outstr.push(format_sstr!("spot: ${p:0.4}/hr   ")),
-                    None => outstr.push(format_sstr!("{:5} {:9}    ", "", "")),
+    /// Returns error if aws api call or db query fails
+    pub async fn register_launch_dns(&self) -> Result<usize, Error> {
+        let launches = self.storage.get_pending_dns_launches().await?;
+        if launches.is_empty() {
+            return Ok(0);
+        }
+        let running: HashSet<_> = INSTANCE_LIST
+            .read()
+            .await
+            .iter()
+            .filter(|inst| inst.state == "running")
+            .map(|inst| inst.id.clone())
+            .collect();
+        let zones = self.route53.get_hosted_zones().await?;
+        let mut registered = 0;
+        for mut launch in launches {
+            let (instance_id, dns_name) = match (&launch.instance_id, &launch.dns_name) {
+                (Some(instance_id), Some(dns_name)) if running.contains(instance_id) => {
+                    (instance_id.clone(), dns_name.clone())
                 }
-                match price.reserved_price {
-                    Some(p) => outstr.push(format_sstr!("res: ${p:0.4}/hr   ")),
-                    None => outstr.push(format_sstr!("{:4} {:9}   ", "", "")),
+                _ => continue,
+            };
+            let public_ip: Ipv4Addr = match self
+                .ec2
+                .get_instance_details(&instance_id)
+                .await?
+                .public_ip
+                .and_then(|ip| ip.parse().ok())
+            {
+                Some(public_ip) => public_ip,
+                None => continue,
+            };
+            let zone_id = match hosted_zone_for(
+                &dns_name,
+                zones.iter().map(|z| (z.id.as_str(), z.name.as_str())),
+            ) {
+                Some(zone_id) => zone_id,
+                None => {
+                    warn!("no hosted zone for {dns_name} of {instance_id}");
+                    continue;
                 }
-                outstr.push(format_sstr!("cpu: {:2}   ", price.ncpu));
-                outstr.push(format_sstr!("mem: {:2}   ", price.memory));
-                outstr.push(format_sstr!("inst_fam: {}", price.instance_family));
-                (price.ncpu, price.memory as i64, outstr.join(""))
-            })
-            .collect();
-        prices.sort();
+            };
+            self.route53
+                .upsert_a_record(zone_id, &dns_name, public_ip, LAUNCH_DNS_TTL)
+                .await?;
+            launch.dns_registered_at = Some(OffsetDateTime::now_utc());
+            self.storage.update_launch(&launch).await?;
+            self.storage
+                .insert_audit_log(&AuditLog::new(
+                    "launch_dns",
+                    dns_name,
+                    Some(format_sstr!("{instance_id} {public_ip}")),
+                ))
+                .await?;
+            registered += 1;
+        }
+        if registered > 0 {
+            self.cache.invalidate([ResourceType::Route53]);
+        }
+        Ok(registered)
+    }
 
-        let outstrings: Vec<_> = prices.into_iter().map(|(_, _, line)| line).collect();
-        self.stdout.send(outstrings.join("\n"));
-        Ok(())
+    /// Instances whose ssh isn't up yet report `Unknown`
+    async fn cloud_init_status(&self, instance_id: &str) -> CloudInitStatus {
+        match self
+            .run_command(instance_id, "cloud-init status --long")
+            .await
+        {
+            Ok(lines) => CloudInitStatus::parse(&lines),
+            Err(e) => {
+                debug!("cloud-init status of {instance_id} unavailable: {e}");
+                CloudInitStatus::Unknown
+            }
+        }
     }
 
-    /// # Errors
-    /// Returns error if aws api call fails
-    pub async fn delete_image(&self, ami: &str) -> Result<(), Error> {
-        let ami_map = self.ec2.get_ami_map().await?;
-        let ami = ami_map.get(ami).map_or(ami, AsRef::as_ref);
-        self.ec2.delete_image(ami).await
+    async fn setup_check_passes(&self, instance_id: &str, command: &str) -> bool {
+        let command = format_sstr!("{command} && echo {SETUP_CHECK_OK}");
+        match self.run_command(instance_id, &command).await {
+            Ok(lines) => lines.iter().any(|l| l.trim() == SETUP_CHECK_OK),
+            Err(e) => {
+                debug!("setup check of {instance_id} failed: {e}");
+                false
+            }
+        }
     }
 
+    /// Update spot request status and termination time of recorded launches
     /// # Errors
-    /// Returns error if aws api call fails
-    pub async fn request_spot_instance(&self, req: &mut SpotRequest) -> Result<(), Error> {
-        let ami_map = self.ec2.get_ami_map().await?;
-        if let Some(a) = ami_map.get(&req.ami) {
-            req.ami = a.clone();
+    /// Returns error if aws api call or db query fails
+    pub async fn sync_launch_history(&self) -> Result<(), Error> {
+        let open_launches = self.storage.get_open_launches().await?;
+        if open_launches.is_empty() {
+            return Ok(());
         }
-        if let Some(spot_id) = self.ec2.request_spot_instance(req).await?.next() {
-            self.ec2.tag_spot_instance(&spot_id, &req.tags, 20).await?;
+        let spot_requests: HashMap<_, _> =
+            if open_launches.iter().any(LaunchHistory::is_pending_spot) {
+                self.ec2
+                    .get_spot_instance_requests()
+                    .await?
+                    .map(|req| (req.id.clone(), req))
+                    .collect()
+            } else {
+                HashMap::new()
+            };
+        self.fill_instance_list().await?;
+        let instance_states: HashMap<_, _> = INSTANCE_LIST
+            .read()
+            .await
+            .iter()
+            .map(|inst| (inst.id.clone(), inst.state.clone()))
+            .collect();
+        let now = OffsetDateTime::now_utc();
+        for mut launch in open_launches {
+            let mut changed = false;
+            if launch.is_pending_spot() {
+                if let Some(req) = launch
+                    .spot_request_id
+                    .as_ref()
+                    .and_then(|id| spot_requests.get(id))
+                {
+                    if req.status != launch.status || req.instance_id.is_some() {
+                        launch.status = req.status.clone();
+                        launch.instance_id = req.instance_id.clone();
+                        if launch.instance_id.is_some() {
+                            launch.fulfilled_at = Some(now);
+                        }
+                        changed = true;
+                    }
+                }
+            } else if let Some(instance_id) = &launch.instance_id {
+                match instance_states.get(instance_id) {
+                    Some(state) if state != "terminated" => {
+                        if state == "running" && launch.running_at.is_none() {
+                            launch.running_at = Some(now);
+                            changed = true;
+                        }
+                    }
+                    _ => {
+                        launch.terminated_at = Some(now);
+                        changed = true;
+                    }
+                }
+            }
+            if changed {
+                self.storage.update_launch(&launch).await?;
+            }
         }
         Ok(())
     }
 
+    /// Instances, volumes, snapshots and amis tagged as `query` asks
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn search_tags(&self, query: &TagQuery) -> Result<Vec<TaggedResource>, Error> {
+        let (instances, volumes, snapshots, amis) = try_join!(
+            self.ec2.get_all_instances(),
+            self.ec2.get_all_volumes(),
+            self.ec2.get_all_snapshots(),
+            self.ec2.get_ami_tags(),
+        )?;
+        let instances: Vec<_> = instances.collect();
+        let volumes: Vec<_> = volumes.collect();
+        let snapshots: Vec<_> = snapshots.collect();
+        let amis: Vec<_> = amis.collect();
+        Ok(search_tags(query, &instances, &volumes, &snapshots, &amis))
+    }
+
+    /// Phases of the launches made in the last `hours`, most recent first
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_launch_progress(&self, hours: i64) -> Result<Vec<LaunchProgress>, Error> {
+        let since = OffsetDateTime::now_utc() - Duration::hours(hours);
+        let launches = self.storage.get_recent_launches(since).await?;
+        Ok(launches.iter().map(LaunchProgress::new).collect())
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn run_ec2_instance(&self, req: &mut InstanceRequest) -> Result<(), Error> {
@@ -652,8 +3632,19 @@ impl AwsAppInterface {
         if let Some(a) = ami_map.get(&req.ami) {
             req.ami = a.clone();
         }
+        if let Some(dns_name) = &req.dns_name {
+            self.check_launch_dns_name(dns_name).await?;
+        }
 
-        self.ec2.run_ec2_instance(req).await
+        self.cache
+            .invalidate([ResourceType::Instances, ResourceType::Volume]);
+        for instance_id in self.ec2.run_ec2_instance(req).await? {
+            let mut launch = LaunchHistory::new(req.instance_type.clone(), req.ami.clone(), false);
+            launch.instance_id = Some(instance_id);
+            launch.dns_name.clone_from(&req.dns_name);
+            self.storage.insert_launch(&launch).await?;
+        }
+        Ok(())
     }
 
     /// # Errors
@@ -661,12 +3652,44 @@ impl AwsAppInterface {
     pub async fn create_image(
         &self,
         inst_id: impl AsRef<str>,
-        name: impl Into<String>,
+        name: impl AsRef<str>,
     ) -> Result<Option<StackString>, Error> {
+        let name = self.check_name(ResourceType::Ami, name.as_ref())?;
         self.fill_instance_list().await?;
         let name_map = get_name_map().await?;
         let inst_id = map_or_val(&name_map, &inst_id);
-        self.ec2.create_image(inst_id, name).await
+        self.cache
+            .invalidate([ResourceType::Ami, ResourceType::Snapshot]);
+        let ami_id = self.ec2.create_image(inst_id, name.as_str()).await?;
+        if let Some(ami_id) = &ami_id {
+            if let Err(e) =
+                record_image_build(&self.ec2, self.storage.as_ref(), inst_id, ami_id, &name).await
+            {
+                error!("failed to catalog {ami_id}: {e}");
+            }
+        }
+        Ok(ami_id)
+    }
+
+    /// Validate a new resource name against the configured naming policy
+    /// # Errors
+    /// Returns error if name does not match the naming convention
+    pub fn check_name(&self, resource: ResourceType, name: &str) -> Result<StackString, Error> {
+        NamingPolicy::from_config(&self.config)?.check(resource, name)
+    }
+
+    /// Validate (and possibly fix) the `Name` tag of a new resource
+    /// # Errors
+    /// Returns error if name does not match the naming convention
+    pub fn check_name_tag(
+        &self,
+        resource: ResourceType,
+        tags: &mut HashMap<StackString, StackString>,
+    ) -> Result<(), Error> {
+        if let Some(name) = tags.get_mut("Name") {
+            *name = self.check_name(resource, name.as_str())?;
+        }
+        Ok(())
     }
 
     async fn get_snapshot_map(&self) -> Result<HashMap<StackString, StackString>, Error> {
@@ -686,10 +3709,107 @@ impl AwsAppInterface {
         zoneid: impl Into<String>,
         size: Option<i32>,
         snapid: Option<impl AsRef<str>>,
+        name: Option<impl AsRef<str>>,
     ) -> Result<Option<StackString>, Error> {
+        let name = name
+            .map(|n| self.check_name(ResourceType::Volume, n.as_ref()))
+            .transpose()?;
         let snap_map = self.get_snapshot_map().await?;
         let snapid = snapid.map(|s| map_or_val(&snap_map, &s).to_string());
-        self.ec2.create_ebs_volume(zoneid, size, snapid).await
+        self.cache.invalidate([ResourceType::Volume]);
+        let volid = self.ec2.create_ebs_volume(zoneid, size, snapid).await?;
+        if let (Some(volid), Some(name)) = (&volid, name) {
+            let tags = hashmap! {"Name".into() => name};
+            self.ec2.tag_ec2_instance(volid.as_str(), &tags).await?;
+        }
+        Ok(volid)
+    }
+
+    /// Ids and Name tags from `Config::protected_resources` and the
+    /// `protected_resource` table
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_protected_resources(&self) -> Result<HashSet<StackString>, Error> {
+        let mut protected: HashSet<StackString> =
+            self.config.protected_resources.iter().cloned().collect();
+        protected.extend(
+            self.storage
+                .get_protected_resources()
+                .await?
+                .into_iter()
+                .map(|r| r.resource_id),
+        );
+        Ok(protected)
+    }
+
+    /// Returns the requested instances which are protected from termination
+    /// # Errors
+    /// Returns error if db query or aws api call fails
+    pub async fn find_protected_instances(
+        &self,
+        instance_ids: &[impl AsRef<str>],
+    ) -> Result<Vec<StackString>, Error> {
+        let protected = self.get_protected_resources().await?;
+        if protected.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.fill_instance_list().await?;
+        let name_map = get_name_map().await?;
+        let instances = INSTANCE_LIST.read().await.clone();
+        Ok(instance_ids
+            .iter()
+            .filter_map(|id| {
+                let id = map_or_val(&name_map, id);
+                let name = instances
+                    .iter()
+                    .find(|inst| inst.id == id)
+                    .and_then(|inst| inst.tags.get("Name"));
+                if is_protected(&protected, id, name) {
+                    Some(id.into())
+                } else {
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Returns the requested volumes which are protected, either directly or
+    /// by being attached to a protected instance
+    /// # Errors
+    /// Returns error if db query or aws api call fails
+    pub async fn find_protected_volumes(
+        &self,
+        volume_ids: &[impl AsRef<str>],
+    ) -> Result<Vec<StackString>, Error> {
+        let protected = self.get_protected_resources().await?;
+        if protected.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.fill_instance_list().await?;
+        let protected_attachments: HashSet<StackString> = INSTANCE_LIST
+            .read()
+            .await
+            .iter()
+            .filter(|inst| is_protected(&protected, &inst.id, inst.tags.get("Name")))
+            .flat_map(|inst| inst.volumes.iter().cloned())
+            .collect();
+        let volumes: Vec<_> = self.ec2.get_all_volumes().await?.collect();
+        Ok(volume_ids
+            .iter()
+            .filter_map(|id| {
+                let id = id.as_ref();
+                let volume = volumes.iter().find(|v| {
+                    v.id == id || v.tags.get("Name").map(StackString::as_str) == Some(id)
+                })?;
+                if protected_attachments.contains(&volume.id)
+                    || is_protected(&protected, &volume.id, volume.tags.get("Name"))
+                {
+                    Some(volume.id.clone())
+                } else {
+                    None
+                }
+            })
+            .collect())
     }
 
     async fn get_volume_map(&self) -> Result<HashMap<StackString, StackString>, Error> {
@@ -707,6 +3827,7 @@ impl AwsAppInterface {
     pub async fn delete_ebs_volume(&self, volid: impl AsRef<str>) -> Result<(), Error> {
         let vol_map = self.get_volume_map().await?;
         let volid = map_or_val(&vol_map, &volid);
+        self.cache.invalidate([ResourceType::Volume]);
         self.ec2.delete_ebs_volume(volid).await
     }
 
@@ -723,6 +3844,8 @@ impl AwsAppInterface {
         let name_map = get_name_map().await?;
         let volid = map_or_val(&vol_map, &volid);
         let instid = map_or_val(&name_map, &instid);
+        self.cache
+            .invalidate([ResourceType::Volume, ResourceType::Instances]);
         self.ec2.attach_ebs_volume(volid, instid, device).await
     }
 
@@ -731,6 +3854,8 @@ impl AwsAppInterface {
     pub async fn detach_ebs_volume(&self, volid: impl AsRef<str>) -> Result<(), Error> {
         let vol_map = self.get_volume_map().await?;
         let volid = map_or_val(&vol_map, &volid);
+        self.cache
+            .invalidate([ResourceType::Volume, ResourceType::Instances]);
         self.ec2.detach_ebs_volume(volid).await
     }
 
@@ -739,6 +3864,8 @@ impl AwsAppInterface {
     pub async fn modify_ebs_volume(&self, volid: impl AsRef<str>, size: i32) -> Result<(), Error> {
         let vol_map = self.get_volume_map().await?;
         let volid = map_or_val(&vol_map, &volid);
+        self.cache
+            .invalidate([ResourceType::Volume, ResourceType::Instances]);
         self.ec2.modify_ebs_volume(volid, size).await
     }
 
@@ -749,9 +3876,75 @@ impl AwsAppInterface {
         volid: impl AsRef<str>,
         tags: &HashMap<StackString, StackString>,
     ) -> Result<Option<StackString>, Error> {
+        let mut tags = tags.clone();
+        self.check_name_tag(ResourceType::Snapshot, &mut tags)?;
         let vol_map = self.get_volume_map().await?;
         let volid = map_or_val(&vol_map, &volid);
-        self.ec2.create_ebs_snapshot(volid, tags).await
+        self.cache.invalidate([ResourceType::Snapshot]);
+        self.ec2.create_ebs_snapshot(volid, &tags).await
+    }
+
+    /// Copy `snapid` (an id or Name tag) into `region`, which may be the
+    /// current region, returns the id of the copy
+    /// # Errors
+    /// Returns error if aws api call or db query fails
+    pub async fn copy_snapshot(
+        &self,
+        snapid: impl AsRef<str>,
+        region: &str,
+        encrypted: bool,
+        kms_key_id: Option<&str>,
+    ) -> Result<StackString, Error> {
+        let snap_map = self.get_snapshot_map().await?;
+        let snapid = map_or_val(&snap_map, &snapid);
+        let mut target = self.ec2.clone();
+        target.set_region(region).await?;
+        let copy_id = target
+            .copy_snapshot(
+                self.ec2.region(),
+                snapid,
+                encrypted,
+                kms_key_id,
+                &HashMap::new(),
+            )
+            .await?;
+        self.cache.invalidate([ResourceType::Snapshot]);
+        self.storage
+            .insert_audit_log(&AuditLog::new(
+                "copy_snapshot",
+                snapid,
+                Some(format_sstr!("{copy_id} in {region}")),
+            ))
+            .await?;
+        Ok(copy_id)
+    }
+
+    /// Create a key pair, the returned private key can't be retrieved again
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn create_key_pair(&self, key_name: &str) -> Result<StackString, Error> {
+        self.cache.invalidate([ResourceType::Key]);
+        self.ec2.create_key_pair(key_name).await
+    }
+
+    /// Import an openssh public key, returns the fingerprint
+    /// # Errors
+    /// Returns error if the key is malformed or aws api call fails
+    pub async fn import_key_pair(
+        &self,
+        key_name: &str,
+        public_key: &str,
+    ) -> Result<StackString, Error> {
+        validate_public_key(public_key)?;
+        self.cache.invalidate([ResourceType::Key]);
+        self.ec2.import_key_pair(key_name, public_key).await
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn delete_key_pair(&self, key_name: &str) -> Result<(), Error> {
+        self.cache.invalidate([ResourceType::Key]);
+        self.ec2.delete_key_pair(key_name).await
     }
 
     /// # Errors
@@ -759,6 +3952,7 @@ impl AwsAppInterface {
     pub async fn delete_ebs_snapshot(&self, snapid: impl AsRef<str>) -> Result<(), Error> {
         let snap_map = self.get_snapshot_map().await?;
         let snapid = map_or_val(&snap_map, &snapid);
+        self.cache.invalidate([ResourceType::Snapshot]);
         self.ec2.delete_ebs_snapshot(snapid).await
     }
 
@@ -787,16 +3981,44 @@ impl AwsAppInterface {
 
     /// # Errors
     /// Returns error if aws api call fails
-    pub async fn create_user(
-        &self,
-        user_name: impl Into<String>,
-    ) -> Result<Option<IamUser>, Error> {
+    pub async fn create_user(&self, user_name: impl AsRef<str>) -> Result<Option<IamUser>, Error> {
+        let user_name = self.check_name(ResourceType::User, user_name.as_ref())?;
+        self.cache.invalidate([ResourceType::User]);
         self.iam.create_user(user_name).await
     }
 
+    /// Users with their mfa devices, devices also listed by
+    /// `list_virtual_mfa_devices` are marked as virtual
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_users_with_mfa(&self) -> Result<Vec<IamUser>, Error> {
+        let (users, virtual_devices) =
+            try_join!(self.iam.list_users(), self.iam.list_virtual_mfa_devices())?;
+        let virtual_serials: HashSet<_> = virtual_devices.map(|d| d.serial_number).collect();
+        let virtual_serials = &virtual_serials;
+        let futures = users.map(|mut user| async move {
+            user.mfa_devices = self
+                .iam
+                .list_mfa_devices(user.user_name.as_str())
+                .await?
+                .map(|mut device| {
+                    device.is_virtual = virtual_serials.contains(&device.serial_number);
+                    device
+                })
+                .collect();
+            Ok(user)
+        });
+        try_join_all(futures).await
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn delete_user(&self, user_name: impl Into<String>) -> Result<(), Error> {
+        self.cache.invalidate([
+            ResourceType::User,
+            ResourceType::Group,
+            ResourceType::AccessKey,
+        ]);
         self.iam.delete_user(user_name).await
     }
 
@@ -807,6 +4029,8 @@ impl AwsAppInterface {
         user_name: impl Into<String>,
         group_name: impl Into<String>,
     ) -> Result<(), Error> {
+        self.cache
+            .invalidate([ResourceType::User, ResourceType::Group]);
         self.iam.add_user_to_group(user_name, group_name).await
     }
 
@@ -817,6 +4041,8 @@ impl AwsAppInterface {
         user_name: impl Into<String>,
         group_name: impl Into<String>,
     ) -> Result<(), Error> {
+        self.cache
+            .invalidate([ResourceType::User, ResourceType::Group]);
         self.iam.remove_user_from_group(user_name, group_name).await
     }
 
@@ -826,6 +4052,7 @@ impl AwsAppInterface {
         &self,
         user_name: impl Into<String>,
     ) -> Result<Option<IamAccessKey>, Error> {
+        self.cache.invalidate([ResourceType::AccessKey]);
         self.iam.create_access_key(user_name).await
     }
 
@@ -836,6 +4063,7 @@ impl AwsAppInterface {
         user_name: impl Into<String>,
         access_key_id: impl Into<String>,
     ) -> Result<(), Error> {
+        self.cache.invalidate([ResourceType::AccessKey]);
         self.iam.delete_access_key(user_name, access_key_id).await
     }
 }
@@ -845,6 +4073,30 @@ fn print_tags(tags: &HashMap<impl Display, impl Display>) -> StackString {
     results.join(", ").into()
 }
 
+fn certificate_expiry_line(certificate: &CertificateInfo, now: OffsetDateTime) -> StackString {
+    let in_use = if certificate.in_use {
+        "in use"
+    } else {
+        "unused"
+    };
+    match certificate.days_until_expiry(now) {
+        Some(days) if days < 0 => format_sstr!(
+            "{} ({in_use}) expired {} days ago",
+            certificate.domain_name,
+            -days
+        ),
+        Some(days) => format_sstr!(
+            "{} ({in_use}) expires in {days} days",
+            certificate.domain_name
+        ),
+        None => format_sstr!("{} ({in_use}) has no expiry", certificate.domain_name),
+    }
+}
+
+fn map_date(date: Option<DateTimeWrapper>) -> StackString {
+    date.map_or_else(|| "never".into(), |d| format_sstr!("{d}"))
+}
+
 fn map_or_val<'a>(
     name_map: &'a HashMap<StackString, impl AsRef<str>>,
     id: &'a impl AsRef<str>,
@@ -885,17 +4137,74 @@ async fn get_id_host_map() -> Result<HashMap<StackString, StackString>, Error> {
     Ok(id_host_map)
 }
 
+/// A resource is protected if either its id or its Name tag is listed
+#[must_use]
+pub fn is_protected(
+    protected: &HashSet<StackString>,
+    id: &str,
+    name: Option<&StackString>,
+) -> bool {
+    protected.contains(id) || name.map_or(false, |n| protected.contains(n))
+}
+
+/// Sort by spot price then on-demand price, types without a spot price go
+/// last
+pub fn sort_by_spot_price(prices: &mut [AwsInstancePrice]) {
+    fn cmp_price(a: Option<f64>, b: Option<f64>) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+    prices.sort_by(|a, b| {
+        cmp_price(a.spot_price, b.spot_price)
+            .then_with(|| cmp_price(a.ondemand_price, b.ondemand_price))
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Error;
     use stack_string::StackString;
-    use std::sync::Arc;
+    use std::{collections::HashSet, sync::Arc};
+    use time::{macros::datetime, Duration};
 
     use crate::{
-        aws_app_interface::{get_id_host_map, get_name_map, INSTANCE_LIST},
+        acm_instance::CertificateInfo,
+        aws_app_interface::{
+            certificate_expiry_line, get_id_host_map, get_name_map, is_protected,
+            sort_by_spot_price, AwsInstancePrice, INSTANCE_LIST,
+        },
         ec2_instance::Ec2InstanceInfo,
+        instance_family::InstanceFamilies,
     };
 
+    #[test]
+    fn test_certificate_expiry_line() {
+        let now = datetime!(2024-06-01 00:00 UTC);
+        let mut certificate = CertificateInfo {
+            certificate_arn: "arn:aws:acm:us-east-1:123456789012:certificate/abc".into(),
+            domain_name: "novnc.example.com".into(),
+            alternative_names: Vec::new(),
+            status: "ISSUED".into(),
+            certificate_type: "AMAZON_ISSUED".into(),
+            in_use: true,
+            not_after: Some(now + Duration::days(12)),
+        };
+        assert_eq!(
+            certificate_expiry_line(&certificate, now),
+            "novnc.example.com (in use) expires in 12 days"
+        );
+        certificate.in_use = false;
+        certificate.not_after = Some(now - Duration::days(2));
+        assert_eq!(
+            certificate_expiry_line(&certificate, now),
+            "novnc.example.com (unused) expired 2 days ago"
+        );
+    }
+
     #[tokio::test]
     async fn test_get_id_host_map() -> Result<(), Error> {
         let js = include_str!("../../tests/data/ec2_instances.json");
@@ -924,4 +4233,79 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_is_protected() {
+        let protected: HashSet<StackString> = ["ddbolineinthecloud".into(), "vol-1234".into()]
+            .into_iter()
+            .collect();
+        let name: StackString = "ddbolineinthecloud".into();
+        assert!(is_protected(&protected, "i-05c99b55b3acf8606", Some(&name)));
+        assert!(is_protected(&protected, "vol-1234", None));
+        assert!(!is_protected(&protected, "i-0123", None));
+        let other: StackString = "scratch".into();
+        assert!(!is_protected(&protected, "vol-5678", Some(&other)));
+    }
+
+    #[test]
+    fn test_sort_by_spot_price() {
+        let price = |instance_type: &str, spot_price, ondemand_price| AwsInstancePrice {
+            instance_type: instance_type.into(),
+            ondemand_price,
+            spot_price,
+            reserved_price: None,
+            ondemand_trend: None,
+            ncpu: 2,
+            memory: 8.0,
+            gpu_count: 0,
+            gpu_type: None,
+            network_performance: None,
+            instance_family: InstanceFamilies::GeneralPurpose,
+            data_url: None,
+        };
+        let mut prices = vec![
+            price("m5.xlarge", None, Some(0.192)),
+            price("m5.large", Some(0.04), Some(0.096)),
+            price("m5.2xlarge", None, None),
+            price("m5.4xlarge", Some(0.3), Some(0.768)),
+            price("m5.metal", Some(0.04), Some(0.05)),
+        ];
+        sort_by_spot_price(&mut prices);
+        let types: Vec<_> = prices.iter().map(|p| p.instance_type.as_str()).collect();
+        assert_eq!(
+            types,
+            vec![
+                "m5.metal",
+                "m5.large",
+                "m5.4xlarge",
+                "m5.xlarge",
+                "m5.2xlarge"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ondemand_trend_label() {
+        let mut price = AwsInstancePrice {
+            instance_type: "m5.large".into(),
+            ondemand_price: Some(0.1),
+            spot_price: None,
+            reserved_price: None,
+            ondemand_trend: None,
+            ncpu: 2,
+            memory: 8.0,
+            gpu_count: 0,
+            gpu_type: None,
+            network_performance: None,
+            instance_family: InstanceFamilies::GeneralPurpose,
+            data_url: None,
+        };
+        assert_eq!(price.ondemand_trend_label(), "");
+        price.ondemand_trend = Some(0.0);
+        assert_eq!(price.ondemand_trend_label(), "0");
+        price.ondemand_trend = Some(0.02);
+        assert_eq!(price.ondemand_trend_label(), "+$0.0200 (+25.0%)");
+        price.ondemand_trend = Some(-0.02);
+        assert_eq!(price.ondemand_trend_label(), "-$0.0200 (-16.7%)");
+    }
 }
@@ -1,53 +1,174 @@
 use anyhow::{format_err, Error};
 use aws_sdk_route53::types::RrType;
-use clap::Parser;
-use futures::{future, stream::FuturesUnordered, TryStreamExt};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::{generate, Shell};
+use clap_mangen::Man;
+use futures::{stream::FuturesUnordered, TryStreamExt};
 use itertools::Itertools;
-use log::debug;
-use refinery::embed_migrations;
+use log::{debug, error};
+use serde_json::Value;
 use stack_string::{format_sstr, StackString};
-use std::{net::Ipv4Addr, path::PathBuf, sync::Arc};
-use tokio::io::{stdin, AsyncReadExt};
+use std::{io::Write, net::Ipv4Addr, path::PathBuf, sync::Arc};
+use tokio::{
+    fs,
+    io::{stdin, AsyncReadExt, AsyncWriteExt},
+    time::{sleep, Duration},
+};
 
 use crate::{
     aws_app_interface::AwsAppInterface,
     config::Config,
+    ddns::update_ddns_records,
     inbound_email::InboundEmail,
     instance_opt::InstanceOpt,
-    models::{InstanceFamily, InstanceList},
+    models::ProtectedResource,
     novnc_instance::NoVncInstance,
+    output_format::OutputFormat,
     pgpool::PgPool,
+    pricing_instance::UpdateSource,
+    remote_client::{device_login, RemoteClient},
     resource_type::{ResourceType, ALL_RESOURCES},
     s3_instance::S3Instance,
+    schema::{applied_migrations, pending_migrations, run_migrations, MigrateAction},
+    ses_client::SesInstance,
     spot_request_opt::{get_tags, SpotRequestOpt},
+    storage::{
+        open_storage, InstanceFamilyRepo, InstanceListRepo, InstancePricingRepo,
+        ProtectedResourceRepo,
+    },
     sysinfo_instance::SysinfoInstance,
     systemd_instance::SystemdInstance,
+    telemetry::init_tracing,
+    tui::TuiApp,
 };
 
-embed_migrations!("../migrations");
+/// Name the completions and man page are generated for
+const BIN_NAME: &str = "aws-app-rust";
+
+const LIST_EXAMPLES: &str = r"Examples:
+  aws-app-rust list -r instances
+  aws-app-rust list -r volume,snapshot --all-regions
+  aws-app-rust --output json list -r spot
+  aws-app-rust list -r spot,volume --watch 10";
+const TERMINATE_EXAMPLES: &str = r"Examples:
+  aws-app-rust terminate -i i-0123456789abcdef0
+  aws-app-rust terminate -i my-instance,other-instance";
+const REQUEST_EXAMPLES: &str = r"Examples:
+  aws-app-rust request -a ami-0123456789abcdef0 -i m5.large --price 0.05 -t my-instance
+  aws-app-rust request -a ami-0123456789abcdef0 -i c6i.xlarge -c 2 --extra-instance-types c5.xlarge,c6a.xlarge
+  aws-app-rust request -a ami-0123456789abcdef0 -i t3.small --data-volume /dev/sdf:100:gp3";
+const RUN_EXAMPLES: &str = r"Examples:
+  aws-app-rust run -a ami-0123456789abcdef0 -i t3.micro -t my-instance
+  aws-app-rust run -a ami-0123456789abcdef0 -i m6i.large --shutdown-behavior terminate --require-imdsv2";
+const PRICE_EXAMPLES: &str = r"Examples:
+  aws-app-rust price -s m5
+  aws-app-rust price -s t3,t4g,c6i";
+const CREATE_SNAPSHOT_EXAMPLES: &str = r"Examples:
+  aws-app-rust create-snapshot --volid vol-0123456789abcdef0 -t backup-2024";
+const TAG_EXAMPLES: &str = r"Examples:
+  aws-app-rust tag -i i-0123456789abcdef0 -t my-instance
+  aws-app-rust tag -i vol-0123456789abcdef0 -t data-volume,Owner:ops";
+const CONNECT_EXAMPLES: &str = r"Examples:
+  aws-app-rust connect -i my-instance
+  $(aws-app-rust connect -i i-0123456789abcdef0)";
+const TUI_EXAMPLES: &str = r"Examples:
+  aws-app-rust tui
+  aws-app-rust tui -r 10";
+const CONFIG_EXAMPLES: &str = r"Examples:
+  aws-app-rust config show
+  aws-app-rust --config ./staging.toml --set aws_region_name=us-west-2 config show
+  aws-app-rust --output json config show";
+const COMPLETIONS_EXAMPLES: &str = r"Examples:
+  aws-app-rust completions bash > /etc/bash_completion.d/aws-app-rust
+  aws-app-rust completions zsh > ~/.zfunc/_aws-app-rust
+  aws-app-rust completions fish > ~/.config/fish/completions/aws-app-rust.fish
+  aws-app-rust completions man > aws-app-rust.1";
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionTarget {
+    Bash,
+    Zsh,
+    Fish,
+    /// Roff man page
+    Man,
+}
 
 #[derive(Parser, Debug, Clone)]
+struct AwsAppCli {
+    #[clap(long, global = true, default_value = "text")]
+    /// Output format for list subcommands, possible values are: text, json
+    output: OutputFormat,
+    #[clap(long, global = true)]
+    /// Talk to the aws-app-http api at `remote_url` instead of aws directly,
+    /// requires running `login` first
+    remote: bool,
+    #[clap(long, global = true)]
+    /// Name of an entry in `account_profiles` whose role should be assumed
+    account: Option<StackString>,
+    #[clap(long, global = true)]
+    /// Toml config file, defaults to `$AWS_APP_CONFIG` or
+    /// ~/.config/aws_app_rust/config.toml
+    config: Option<PathBuf>,
+    #[clap(long = "set", global = true, value_name = "FIELD=VALUE")]
+    /// Override a config field, takes precedence over the config file and
+    /// environment variables
+    overrides: Vec<StackString>,
+    #[clap(subcommand)]
+    command: AwsAppOpts,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigAction {
+    /// Print the effective configuration with secrets redacted
+    Show,
+}
+
+#[derive(Subcommand, Debug, Clone)]
 pub enum AwsAppOpts {
     /// Update metadata
-    Update,
+    Update {
+        #[clap(long, default_value = "scrape")]
+        /// Where to get instance types and prices, possible values are:
+        /// scrape, api
+        source: UpdateSource,
+        #[clap(short, long)]
+        /// Region to price with `--source api`, defaults to `aws_region_name`
+        region: Option<StackString>,
+        #[clap(short, long, use_value_delimiter = true, value_delimiter = ',')]
+        /// Instance families to load with `--source api`, e.g. t3,m6i
+        family: Vec<StackString>,
+    },
     /// List information about resources
+    #[clap(after_help = LIST_EXAMPLES)]
     List {
         #[clap(short, use_value_delimiter = true, value_delimiter = ',')]
         /// Possible values are:
         /// -r instances,reserved,spot,ami,volume,snapshot,ecr,key,script,user,
-        /// group,access-key,route53,systemd
+        /// group,access-key,route53,systemd,sqs,backup,lambda,acm,docker,ecs
         resources: Vec<ResourceType>,
         #[clap(short, long)]
         /// List all regions
         all_regions: bool,
+        #[clap(short, long)]
+        /// Refresh every `watch` seconds, highlighting state changes, only for
+        /// instances,spot,volume,snapshot
+        watch: Option<u64>,
+    },
+    /// List spot instance requests and their instances
+    Spot {
+        #[clap(short, long)]
+        /// Refresh every `watch` seconds, highlighting state changes
+        watch: Option<u64>,
     },
     /// Terminate a running ec2 instance
+    #[clap(after_help = TERMINATE_EXAMPLES)]
     Terminate {
         #[clap(short, long, use_value_delimiter = true, value_delimiter = ',')]
         /// Instance IDs
         instance_ids: Vec<StackString>,
     },
     /// Request a new spot instance
+    #[clap(after_help = REQUEST_EXAMPLES)]
     Request(SpotRequestOpt),
     /// Cancel Spot Request
     CancelRequest {
@@ -55,8 +176,10 @@ pub enum AwsAppOpts {
         instance_ids: Vec<StackString>,
     },
     /// Run a new ec2 instance
+    #[clap(after_help = RUN_EXAMPLES)]
     Run(InstanceOpt),
     /// Get On-demand/Reserved and Spot instance pricing
+    #[clap(after_help = PRICE_EXAMPLES)]
     Price {
         #[clap(short, long, use_value_delimiter = true, value_delimiter = ',')]
         search: Vec<StackString>,
@@ -91,6 +214,9 @@ pub enum AwsAppOpts {
         zoneid: StackString,
         #[clap(long)]
         snapid: Option<StackString>,
+        #[clap(short, long)]
+        /// Name tag for new volume
+        name: Option<StackString>,
     },
     /// Create new User
     CreateUser {
@@ -123,6 +249,7 @@ pub enum AwsAppOpts {
         size: i32,
     },
     /// Create EBS Snapshot
+    #[clap(after_help = CREATE_SNAPSHOT_EXAMPLES)]
     CreateSnapshot {
         #[clap(long)]
         volid: StackString,
@@ -140,7 +267,68 @@ pub enum AwsAppOpts {
         #[clap(long)]
         snapid: StackString,
     },
+    /// Replace an unencrypted volume with an encrypted copy, a volume in use
+    /// is swapped in place which needs its instance stopped
+    ReencryptVolume {
+        #[clap(long)]
+        volid: StackString,
+        /// Kms key to encrypt with, the default ebs key if unset
+        #[clap(short, long)]
+        kms_key_id: Option<StackString>,
+    },
+    /// Copy a snapshot, to another region with `--region`
+    CopySnapshot {
+        #[clap(long)]
+        snapid: StackString,
+        /// Destination region, defaults to the current region
+        #[clap(short, long)]
+        region: Option<StackString>,
+        /// Encrypt the copy
+        #[clap(short, long)]
+        encrypted: bool,
+        /// Kms key to encrypt with, the default ebs key if unset
+        #[clap(short, long)]
+        kms_key_id: Option<StackString>,
+    },
+    /// Compare a terraform state with the instances, volumes and dns records
+    /// in aws
+    TerraformDrift {
+        /// Local path or `s3://bucket/key`, defaults to `terraform_state`
+        #[clap(short, long)]
+        path: Option<StackString>,
+    },
+    /// Create a key pair, the private key is written to `output` (default
+    /// `{key_name}.pem`) as aws won't return it again
+    CreateKeyPair {
+        #[clap(short, long)]
+        key_name: StackString,
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Import an openssh public key file as a key pair
+    ImportKeyPair {
+        #[clap(short, long)]
+        key_name: StackString,
+        #[clap(short, long)]
+        public_key: PathBuf,
+    },
+    /// Delete a key pair
+    DeleteKeyPair {
+        #[clap(short, long)]
+        key_name: StackString,
+    },
+    /// Upload a file to s3 in parts, for files of any size
+    S3Upload {
+        #[clap(short, long)]
+        bucket: StackString,
+        /// Defaults to the file name
+        #[clap(short, long)]
+        key: Option<StackString>,
+        #[clap(short, long)]
+        file: PathBuf,
+    },
     /// Tag Resource
+    #[clap(after_help = TAG_EXAMPLES)]
     Tag {
         #[clap(short, long)]
         id: StackString,
@@ -163,6 +351,7 @@ pub enum AwsAppOpts {
     /// Cleanup ECR Images
     CleanupEcrImages,
     /// Print ssh command to connect to instance
+    #[clap(after_help = CONNECT_EXAMPLES)]
     Connect {
         #[clap(short, long)]
         /// Instance ID
@@ -173,6 +362,13 @@ pub enum AwsAppOpts {
         /// Instance ID
         instance_id: StackString,
     },
+    /// Forget the recorded ssh host key of an instance and fetch it again
+    /// from the console output
+    ResetHostKey {
+        #[clap(short, long)]
+        /// Instance ID
+        instance_id: StackString,
+    },
     UpdateDns {
         #[clap(short, long)]
         zone: StackString,
@@ -181,6 +377,15 @@ pub enum AwsAppOpts {
         #[clap(short, long)]
         new_ip: Option<Ipv4Addr>,
     },
+    /// Keep the records in `ddns_records` pointing at the public ip of this
+    /// host, checking every `interval` seconds
+    Ddns {
+        #[clap(short, long, default_value = "300")]
+        interval: u64,
+        #[clap(long)]
+        /// Check once and exit
+        once: bool,
+    },
     UpdatePricing,
     Systemd {
         #[clap(short, long)]
@@ -197,22 +402,136 @@ pub enum AwsAppOpts {
         key: Option<PathBuf>,
     },
     RunMigrations,
+    /// Manage database migrations, possible values are: run, pending, info
+    Migrate {
+        #[clap(default_value = "run")]
+        action: MigrateAction,
+    },
     SyncEmail,
+    /// Back up the application database to db_backup_bucket
+    Backup {
+        /// List the existing backups instead
+        #[clap(short, long)]
+        list: bool,
+    },
+    /// Restore the application database from a backup in db_backup_bucket
+    Restore {
+        /// Backup to restore, defaults to the latest
+        #[clap(short, long)]
+        key: Option<StackString>,
+        /// Empty the tables first rather than only adding missing rows
+        #[clap(long)]
+        truncate: bool,
+        /// Restore a backup taken at a different schema version
+        #[clap(long)]
+        force: bool,
+    },
+    /// Protect an instance or volume, by id or Name tag, from terminate and
+    /// delete requests made through aws-app-http
+    Protect {
+        resource_id: StackString,
+        #[clap(short, long)]
+        reason: Option<StackString>,
+    },
+    /// Remove a resource added with `protect`
+    Unprotect {
+        resource_id: StackString,
+    },
+    /// List protected resources from the config and the database
+    ListProtected,
+    /// Interactive instance list with live refresh and a price browser
+    #[clap(after_help = TUI_EXAMPLES)]
+    Tui {
+        #[clap(short, long, default_value = "30")]
+        /// Seconds between instance list refreshes
+        refresh: u64,
+    },
+    /// Inspect the layered configuration
+    #[clap(after_help = CONFIG_EXAMPLES)]
+    Config {
+        #[clap(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print shell completions or the man page
+    #[clap(after_help = COMPLETIONS_EXAMPLES)]
+    Completions {
+        /// Possible values are: bash, zsh, fish, man
+        target: CompletionTarget,
+    },
+    /// Authenticate against the auth service with a device code
+    Login,
+    /// Remove the saved remote session
+    Logout,
 }
 
 impl AwsAppOpts {
     /// # Errors
     /// Returns error if api call fails
     pub async fn process_args() -> Result<(), Error> {
-        let opts = Self::parse();
-        let config = Config::init_config()?;
+        let AwsAppCli {
+            output,
+            remote,
+            account,
+            config: config_file,
+            overrides,
+            command: opts,
+        } = AwsAppCli::parse();
+        if let Self::Completions { target } = opts {
+            return write_completions(target, &mut std::io::stdout());
+        }
+        let config = Config::init_config_from(config_file.as_deref(), &overrides).await?;
+        let _tracing = init_tracing(&config, BIN_NAME)?;
+        match opts {
+            Self::Config {
+                action: ConfigAction::Show,
+            } => {
+                let redacted = config.redacted()?;
+                let text = if output == OutputFormat::Json {
+                    serde_json::to_string_pretty(&redacted)?
+                } else {
+                    toml::to_string(&redacted)?
+                };
+                println!("{text}");
+                return Ok(());
+            }
+            Self::Login => {
+                device_login(&config, |auth| {
+                    println!(
+                        "Open {} and enter code {}",
+                        auth.verification_uri, auth.user_code
+                    );
+                })
+                .await?;
+                println!("Logged in");
+                return Ok(());
+            }
+            Self::Logout => {
+                if config.session_path.exists() {
+                    fs::remove_file(&config.session_path).await?;
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        if remote {
+            return opts.process_remote(&config, output).await;
+        }
         let pool = PgPool::new(&config.database_url)?;
+        let storage = open_storage(&config, &pool)?;
         let sdk_config = aws_config::load_from_env().await;
-        let app = AwsAppInterface::new(config, &sdk_config, pool);
+        let mut app = AwsAppInterface::new(config, &sdk_config, pool, storage);
+        app.output_format = output;
+        if let Some(account) = &account {
+            app.set_account(Some(account)).await?;
+        }
 
         let result = match opts {
-            Self::Update => {
-                for line in app.update().await? {
+            Self::Update {
+                source,
+                region,
+                family,
+            } => {
+                for line in app.update(source, region.as_deref(), &family).await? {
                     app.stdout.send(line);
                 }
                 Ok(())
@@ -220,12 +539,19 @@ impl AwsAppOpts {
             Self::List {
                 resources,
                 all_regions,
+                watch,
             } => {
                 let resources = if resources.first() == Some(&ResourceType::All) {
                     ALL_RESOURCES.to_vec()
                 } else {
                     resources
                 };
+                if let Some(interval) = watch {
+                    if all_regions {
+                        return Err(format_err!("--watch can't be combined with --all-regions"));
+                    }
+                    return app.watch(&resources, interval).await;
+                }
                 let resources = Arc::new(resources);
                 if all_regions {
                     let futures: FuturesUnordered<_> = app
@@ -247,11 +573,15 @@ impl AwsAppOpts {
                     app.list(resources.iter()).await
                 }
             }
+            Self::Spot { watch } => match watch {
+                Some(interval) => app.watch(&[ResourceType::Spot], interval).await,
+                None => app.list(&[ResourceType::Spot]).await,
+            },
             Self::Terminate { instance_ids } => app.terminate(&instance_ids).await,
-            Self::Request(req) => {
-                app.request_spot_instance(&mut req.into_spot_request(&app.config)?)
-                    .await
-            }
+            Self::Request(req) => app
+                .request_spot_instance(&mut req.into_spot_request(&app.config)?)
+                .await
+                .map(|_| ()),
             Self::CancelRequest { instance_ids } => {
                 app.ec2.cancel_spot_instance_request(&instance_ids).await
             }
@@ -261,45 +591,58 @@ impl AwsAppOpts {
             }
             Self::Price { search } => app.print_ec2_prices(&search).await,
             Self::ListFamilies => {
-                let mut stream = Box::pin(InstanceFamily::get_all(&app.pool, None).await?);
-                while let Some(fam) = stream.try_next().await? {
-                    app.stdout
-                        .send(format_sstr!("{:5} {}", fam.family_name, fam.family_type));
+                for fam in app.storage.get_instance_families(None).await? {
+                    if app.output_format == OutputFormat::Json {
+                        app.send_json(&fam)?;
+                    } else {
+                        app.stdout
+                            .send(format_sstr!("{:5} {}", fam.family_name, fam.family_type));
+                    }
                 }
                 Ok(())
             }
             Self::ListInstances { search } => {
-                let mut instances: Vec<_> = InstanceList::get_all_instances(&app.pool)
+                let mut instances: Vec<_> = app
+                    .storage
+                    .get_instances()
                     .await?
-                    .try_filter(|inst| {
-                        future::ready({
-                            if search.is_empty() {
-                                true
-                            } else {
-                                search
-                                    .iter()
-                                    .any(|s| inst.instance_type.contains(s.as_str()))
-                            }
-                        })
+                    .into_iter()
+                    .filter(|inst| {
+                        search.is_empty()
+                            || search
+                                .iter()
+                                .any(|s| inst.instance_type.contains(s.as_str()))
                     })
-                    .try_collect()
-                    .await?;
+                    .collect();
                 instances.sort_by_key(|i| i.n_cpu);
                 instances.sort_by(|x, y| {
                     let x = x.instance_type.split('.').next().unwrap_or("");
                     let y = y.instance_type.split('.').next().unwrap_or("");
                     x.cmp(y)
                 });
-                for inst in instances {
-                    app.stdout.send(format_sstr!(
-                        "{:18} cpu: {:3} mem: {:6.2} {}",
-                        inst.instance_type,
-                        inst.n_cpu,
-                        inst.memory_gib,
-                        inst.generation,
-                    ));
+                if app.output_format == OutputFormat::Json {
+                    app.send_json(&instances)
+                } else {
+                    for inst in instances {
+                        let gpu = if inst.gpu_count > 0 {
+                            format_sstr!(
+                                " gpu: {} x {}",
+                                inst.gpu_count,
+                                inst.gpu_type.as_ref().map_or("", StackString::as_str)
+                            )
+                        } else {
+                            StackString::new()
+                        };
+                        app.stdout.send(format_sstr!(
+                            "{:18} cpu: {:3} mem: {:6.2} {}{gpu}",
+                            inst.instance_type,
+                            inst.n_cpu,
+                            inst.memory_gib,
+                            inst.generation,
+                        ));
+                    }
+                    Ok(())
                 }
-                Ok(())
             }
             Self::CreateImage { instance_id, name } => {
                 if let Some(id) = app.create_image(instance_id, name).await? {
@@ -312,10 +655,11 @@ impl AwsAppOpts {
                 size,
                 zoneid,
                 snapid,
+                name,
             } => {
                 app.stdout
                     .send(format_sstr!("{size:?} {zoneid} {snapid:?}"));
-                if let Some(id) = app.create_ebs_volume(zoneid, size, snapid).await? {
+                if let Some(id) = app.create_ebs_volume(zoneid, size, snapid, name).await? {
                     app.stdout.send(format_sstr!("Created Volume {id}"));
                 }
                 Ok(())
@@ -341,12 +685,131 @@ impl AwsAppOpts {
                 Ok(())
             }
             Self::DeleteSnapshot { snapid } => app.delete_ebs_snapshot(snapid).await,
+            Self::ReencryptVolume { volid, kms_key_id } => {
+                let plan = app.reencrypt_plan(&volid, kms_key_id.clone()).await?;
+                for step in plan.steps() {
+                    app.stdout.send(step);
+                }
+                let id = app.reencrypt_volume(&volid, kms_key_id).await?;
+                app.stdout.send(format_sstr!("Replaced {volid} with {id}"));
+                Ok(())
+            }
+            Self::CopySnapshot {
+                snapid,
+                region,
+                encrypted,
+                kms_key_id,
+            } => {
+                let region = region.unwrap_or_else(|| app.ec2.region().into());
+                let id = app
+                    .copy_snapshot(
+                        snapid,
+                        &region,
+                        encrypted,
+                        kms_key_id.as_ref().map(StackString::as_str),
+                    )
+                    .await?;
+                app.stdout.send(format_sstr!("Copying to {id} in {region}"));
+                Ok(())
+            }
+            Self::TerraformDrift { path } => {
+                let report = app.terraform_drift(path.as_deref()).await?;
+                for item in &report.items {
+                    let address = item.address.as_ref().map_or("", StackString::as_str);
+                    app.stdout.send(format_sstr!(
+                        "{} {} {} {address}",
+                        item.status.to_str(),
+                        item.resource_type.to_str(),
+                        item.id
+                    ));
+                    for diff in &item.differences {
+                        let declared = diff
+                            .declared
+                            .as_ref()
+                            .map_or("(unset)", StackString::as_str);
+                        let live = diff.live.as_ref().map_or("(unset)", StackString::as_str);
+                        app.stdout
+                            .send(format_sstr!("    {}: {declared} -> {live}", diff.attribute));
+                    }
+                }
+                app.stdout
+                    .send(format_sstr!("{} resources in sync", report.in_sync));
+                Ok(())
+            }
+            Self::CreateKeyPair { key_name, output } => {
+                let output =
+                    output.unwrap_or_else(|| format_sstr!("{key_name}.pem").as_str().into());
+                let mut f = fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(&output)
+                    .await?;
+                match app.create_key_pair(&key_name).await {
+                    Ok(private_key) => {
+                        f.write_all(private_key.as_bytes()).await?;
+                        app.stdout.send(format_sstr!(
+                            "Created key pair {key_name}, private key written to {}",
+                            output.display()
+                        ));
+                        Ok(())
+                    }
+                    Err(e) => {
+                        fs::remove_file(&output).await?;
+                        Err(e)
+                    }
+                }
+            }
+            Self::ImportKeyPair {
+                key_name,
+                public_key,
+            } => {
+                let public_key = fs::read_to_string(&public_key).await?;
+                let fingerprint = app.import_key_pair(&key_name, &public_key).await?;
+                app.stdout
+                    .send(format_sstr!("Imported key pair {key_name} {fingerprint}"));
+                Ok(())
+            }
+            Self::DeleteKeyPair { key_name } => app.delete_key_pair(&key_name).await,
+            Self::S3Upload { bucket, key, file } => {
+                let key = match key {
+                    Some(key) => key,
+                    None => file
+                        .file_name()
+                        .and_then(|f| f.to_str())
+                        .ok_or_else(|| format_err!("No file name {}", file.display()))?
+                        .into(),
+                };
+                let result = app
+                    .s3
+                    .upload_file_multipart(&file, &bucket, &key, |progress| {
+                        app.stdout.send(format_sstr!("{progress}"));
+                    })
+                    .await?;
+                app.stdout
+                    .send(format_sstr!("Uploaded s3://{bucket}/{key} {result}"));
+                Ok(())
+            }
             Self::Tag { id, tags } => app.ec2.tag_ec2_instance(id, &get_tags(&tags)).await,
             Self::DeleteEcrImages { reponame, imageids } => {
                 app.ecr.delete_ecr_images(reponame, &imageids).await
             }
             Self::CleanupEcrImages => app.ecr.cleanup_ecr_images().await,
             Self::Connect { instance_id } => app.connect(instance_id).await,
+            Self::Tui { refresh } => {
+                TuiApp::new(app.clone(), Duration::from_secs(refresh))
+                    .run()
+                    .await
+            }
+            Self::ResetHostKey { instance_id } => {
+                if !app.reset_host_key(&instance_id).await? {
+                    app.stdout.send(format_sstr!(
+                        "no host key in console output for {instance_id}, the key offered on the \
+                         next connection will be recorded"
+                    ));
+                }
+                Ok(())
+            }
             Self::Status { instance_id } => {
                 for line in app.get_status(instance_id).await? {
                     app.stdout.send(line);
@@ -380,13 +843,46 @@ impl AwsAppOpts {
                     .update_dns_record(&zone, &record_name, old_ip, new_ip)
                     .await
             }
+            Self::Ddns { interval, once } => {
+                if app.config.ddns_records.is_empty() {
+                    return Err(format_err!("ddns_records is not configured"));
+                }
+                let ses = SesInstance::new(&sdk_config);
+                loop {
+                    match update_ddns_records(&app.config, &app.route53, &ses, app.storage.as_ref())
+                        .await
+                    {
+                        Ok(updates) => {
+                            for update in updates {
+                                app.stdout.send(format_sstr!("updated {update}"));
+                            }
+                        }
+                        Err(e) if once => return Err(e),
+                        // keep the daemon running through transient api failures
+                        Err(e) => error!("ddns update failed: {e}"),
+                    }
+                    if once {
+                        break;
+                    }
+                    sleep(Duration::from_secs(interval)).await;
+                }
+                Ok(())
+            }
             Self::UpdatePricing => {
-                let number_of_updates = app.pricing.update_all_prices(&app.pool).await?;
+                let number_of_updates = app
+                    .pricing
+                    .update_all_prices(&app.config.aws_region_name, app.storage.as_ref())
+                    .await?;
+                app.storage
+                    .prune_price_history(app.config.price_history_days)
+                    .await?;
                 app.stdout.send(format_sstr!("{number_of_updates} updates"));
                 Ok(())
             }
             Self::Systemd { pattern } => {
-                let systemd = SystemdInstance::new(&app.config.systemd_services);
+                let systemd = SystemdInstance::new(&app.config.systemd_services)
+                    .timers(&app.config.systemd_timers)
+                    .sockets(&app.config.systemd_sockets);
                 if let Some(pattern) = &pattern {
                     let stat = systemd.get_service_status(pattern).await?;
                     let log = systemd
@@ -405,6 +901,12 @@ impl AwsAppOpts {
                             app.stdout.send(format_sstr!("{service} not running"));
                         }
                     }
+                    for timer in systemd.list_timers().await? {
+                        app.stdout.send(format_sstr!("{timer}"));
+                    }
+                    for socket in systemd.list_sockets().await? {
+                        app.stdout.send(format_sstr!("{socket}"));
+                    }
                 }
                 Ok(())
             }
@@ -444,20 +946,99 @@ impl AwsAppOpts {
                 Ok(())
             }
             Self::RunMigrations => {
-                let mut client = app.pool.get().await?;
-                migrations::runner().run_async(&mut **client).await?;
+                run_migrations(app.postgres_pool()?).await?;
                 Ok(())
             }
+            Self::Migrate { action } => {
+                let pool = app.postgres_pool()?;
+                let migrations = match action {
+                    MigrateAction::Run => run_migrations(pool).await?,
+                    MigrateAction::Pending => pending_migrations(pool).await?,
+                    MigrateAction::Info => applied_migrations(pool).await?,
+                };
+                if app.output_format == OutputFormat::Json {
+                    return app.send_json(&migrations);
+                }
+                let mut lines: Vec<_> = migrations.iter().map(|m| format_sstr!("{m}")).collect();
+                match action {
+                    MigrateAction::Run if lines.is_empty() => {
+                        lines.push("no pending migrations".into());
+                    }
+                    MigrateAction::Pending if lines.is_empty() => {
+                        lines.push("database is up to date".into());
+                    }
+                    MigrateAction::Info => match migrations.last() {
+                        Some(last) => lines.push(last.rollback_hint()),
+                        None => lines.push("no migrations applied".into()),
+                    },
+                    _ => {}
+                }
+                app.stdout.send(lines.join("\n"));
+                Ok(())
+            }
+            Self::Backup { list } => {
+                if list {
+                    let keys = app.list_database_backups().await?;
+                    app.stdout.send(keys.join("\n"));
+                } else {
+                    let key = app.backup_database().await?;
+                    app.stdout.send(format_sstr!("Backed up database to {key}"));
+                }
+                Ok(())
+            }
+            Self::Restore {
+                key,
+                truncate,
+                force,
+            } => {
+                let (key, inserted) = app
+                    .restore_database(key.as_deref(), truncate, force)
+                    .await?;
+                app.stdout
+                    .send(format_sstr!("Restored {inserted} rows from {key}"));
+                Ok(())
+            }
+            Self::Protect {
+                resource_id,
+                reason,
+            } => {
+                app.storage
+                    .upsert_protected_resource(&ProtectedResource::new(resource_id, reason))
+                    .await
+            }
+            Self::Unprotect { resource_id } => {
+                let deleted = app.storage.delete_protected_resource(&resource_id).await?;
+                if deleted == 0 {
+                    app.stdout.send(format_sstr!(
+                        "{resource_id} was not protected in the database"
+                    ));
+                }
+                Ok(())
+            }
+            Self::ListProtected => {
+                let mut protected: Vec<_> =
+                    app.get_protected_resources().await?.into_iter().collect();
+                protected.sort();
+                if app.output_format == OutputFormat::Json {
+                    app.send_json(&protected)
+                } else {
+                    app.stdout.send(protected.join("\n"));
+                    Ok(())
+                }
+            }
+            Self::Login | Self::Logout | Self::Config { .. } | Self::Completions { .. } => Ok(()),
             Self::SyncEmail => {
                 let sdk_config = aws_config::load_from_env().await;
-                let s3 = S3Instance::new(&sdk_config);
+                let s3 = S3Instance::new(&sdk_config).retry_policy((&app.config).into());
+                let ses = SesInstance::new(&sdk_config);
                 let (new_keys, new_attachments) =
-                    InboundEmail::sync_db(&app.config, &s3, &app.pool)
+                    InboundEmail::sync_db(&app.config, &s3, &ses, app.storage.as_ref())
                         .await
                         .map(|(k, a)| (k.join("\n"), a.join("\n")))?;
-                let new_records = InboundEmail::parse_dmarc_records(&app.config, &s3, &app.pool)
-                    .await?
-                    .len();
+                let new_records =
+                    InboundEmail::parse_dmarc_records(&app.config, &s3, app.storage.as_ref())
+                        .await?
+                        .len();
                 app.stdout.send(format_sstr!(
                     "new {new_keys}\n\nattachments {new_attachments}\n{new_records}",
                 ));
@@ -467,13 +1048,61 @@ impl AwsAppOpts {
         result?;
         app.stdout.close().await.map_err(Into::into)
     }
+
+    /// Run a subcommand through the aws-app-http api, only the subcommands
+    /// exposed by the api are supported
+    async fn process_remote(self, config: &Config, output: OutputFormat) -> Result<(), Error> {
+        let client = RemoteClient::from_config(config).await?;
+        match self {
+            Self::List { resources, .. } => {
+                let resources = if resources.first() == Some(&ResourceType::All) {
+                    ALL_RESOURCES.to_vec()
+                } else {
+                    resources
+                };
+                for resource in resources {
+                    let listing = client.list(resource).await?;
+                    if output == OutputFormat::Json {
+                        println!("{listing}");
+                    } else if let Some(items) = listing.get("items").and_then(Value::as_array) {
+                        for item in items {
+                            println!("{resource} {item}");
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Self::Terminate { instance_ids } => {
+                for instance_id in &instance_ids {
+                    client.terminate(instance_id).await?;
+                }
+                Ok(())
+            }
+            opts => Err(format_err!("{opts:?} is not supported with --remote")),
+        }
+    }
+}
+
+/// Write completions for `target` shell, or the man page, to `buf`
+/// # Errors
+/// Returns error if writing to `buf` fails
+pub fn write_completions(target: CompletionTarget, buf: &mut impl Write) -> Result<(), Error> {
+    let mut cmd = AwsAppCli::command().name(BIN_NAME);
+    let shell = match target {
+        CompletionTarget::Bash => Shell::Bash,
+        CompletionTarget::Zsh => Shell::Zsh,
+        CompletionTarget::Fish => Shell::Fish,
+        CompletionTarget::Man => return Man::new(cmd).render(buf).map_err(Into::into),
+    };
+    generate(shell, &mut cmd, BIN_NAME, buf);
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use anyhow::Error;
 
-    use crate::aws_app_opts::get_tags;
+    use crate::aws_app_opts::{get_tags, write_completions, CompletionTarget};
 
     #[test]
     fn test_get_tags() -> Result<(), Error> {
@@ -485,4 +1114,20 @@ mod tests {
         assert_eq!(tags.get("LastName").map(Into::into), Some("NoWhere"));
         Ok(())
     }
+
+    #[test]
+    fn test_write_completions() -> Result<(), Error> {
+        let mut buf = Vec::new();
+        write_completions(CompletionTarget::Bash, &mut buf)?;
+        let bash = String::from_utf8(buf)?;
+        assert!(bash.contains("aws-app-rust"));
+        assert!(bash.contains("create-snapshot"));
+
+        let mut buf = Vec::new();
+        write_completions(CompletionTarget::Man, &mut buf)?;
+        let man = String::from_utf8(buf)?;
+        assert!(man.starts_with(".ie"));
+        assert!(man.contains(".TH aws-app-rust"));
+        Ok(())
+    }
 }
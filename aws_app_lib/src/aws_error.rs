@@ -0,0 +1,136 @@
+//! Code, message and request id of a failed aws call, recovered from the
+//! `anyhow::Error` it was converted into so callers can report more than the
+//! sdk's `service error`.
+use anyhow::Error;
+use aws_sdk_s3::error::{ErrorMetadata, ProvideErrorMetadata};
+use serde::Serialize;
+use stack_string::StackString;
+
+use crate::retry::ClassifiedError;
+
+/// Key the sdk stores the `x-amzn-RequestId` of a response under
+const AWS_REQUEST_ID: &str = "aws_request_id";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct AwsErrorInfo {
+    pub code: Option<StackString>,
+    pub message: Option<StackString>,
+    pub request_id: Option<StackString>,
+}
+
+impl AwsErrorInfo {
+    #[must_use]
+    pub fn from_metadata(meta: &ErrorMetadata) -> Self {
+        Self {
+            code: meta.code().map(Into::into),
+            message: meta.message().map(Into::into),
+            request_id: meta.extra(AWS_REQUEST_ID).map(Into::into),
+        }
+    }
+
+    #[must_use]
+    pub fn from_provider(err: &impl ProvideErrorMetadata) -> Self {
+        Self::from_metadata(err.meta())
+    }
+
+    /// The aws service error behind `err`, `None` for anything that isn't
+    /// one (network failures, local errors, ...)
+    #[must_use]
+    pub fn from_error(err: &Error) -> Option<Self> {
+        if let Some(info) = err
+            .downcast_ref::<ClassifiedError>()
+            .and_then(|e| e.info.as_ref())
+        {
+            return Some(info.clone());
+        }
+        // unmodeled errors (all of ec2's) keep their metadata as the source
+        if let Some(meta) = err.chain().find_map(|e| e.downcast_ref::<ErrorMetadata>()) {
+            return Some(Self::from_metadata(meta)).filter(|info| info.code.is_some());
+        }
+        // modeled errors only display as `Code: message`
+        let mut chain = err.chain();
+        if chain.next()?.to_string() != "service error" {
+            return None;
+        }
+        Self::from_display(&chain.next()?.to_string())
+    }
+
+    fn from_display(display: &str) -> Option<Self> {
+        let (code, message) = match display.split_once(": ") {
+            Some((code, message)) => (code, Some(message.into())),
+            None => (display, None),
+        };
+        if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric() || c == '.') {
+            return None;
+        }
+        Some(Self {
+            code: Some(code.into()),
+            message,
+            request_id: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{format_err, Error};
+    use aws_sdk_s3::error::ErrorMetadata;
+    use std::fmt;
+
+    use crate::aws_error::AwsErrorInfo;
+
+    #[derive(Debug)]
+    struct ServiceError(ModeledError);
+
+    impl fmt::Display for ServiceError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("service error")
+        }
+    }
+
+    impl std::error::Error for ServiceError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[derive(Debug)]
+    struct ModeledError(&'static str);
+
+    impl fmt::Display for ModeledError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+
+    impl std::error::Error for ModeledError {}
+
+    #[test]
+    fn test_from_metadata() {
+        let meta = ErrorMetadata::builder()
+            .code("InvalidInstanceID.NotFound")
+            .message("The instance ID 'i-0123' does not exist")
+            .custom("aws_request_id", "abc-123")
+            .build();
+        let err: Error = meta.into();
+        let info = AwsErrorInfo::from_error(&err).unwrap();
+        assert_eq!(info.code.as_deref(), Some("InvalidInstanceID.NotFound"));
+        assert_eq!(info.request_id.as_deref(), Some("abc-123"));
+    }
+
+    #[test]
+    fn test_from_display() {
+        let err: Error =
+            ServiceError(ModeledError("NoSuchKey: The specified key does not exist.")).into();
+        let info = AwsErrorInfo::from_error(&err).unwrap();
+        assert_eq!(info.code.as_deref(), Some("NoSuchKey"));
+        assert_eq!(
+            info.message.as_deref(),
+            Some("The specified key does not exist.")
+        );
+
+        let err: Error = ServiceError(ModeledError("unhandled error (Thing)")).into();
+        assert_eq!(AwsErrorInfo::from_error(&err), None);
+        assert_eq!(AwsErrorInfo::from_error(&format_err!("No such file")), None);
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::{format_err, Error};
+use aws_config::SdkConfig;
+use aws_sdk_backup::{primitives::DateTime, types::BackupSelection, Client as BackupClient};
+use aws_types::region::Region;
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+use time::OffsetDateTime;
+
+use crate::{config::Config, date_time_wrapper::DateTimeWrapper};
+
+#[derive(Clone)]
+pub struct BackupInstance {
+    backup_client: BackupClient,
+    region: StackString,
+}
+
+impl fmt::Debug for BackupInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("BackupInstance")
+    }
+}
+
+impl BackupInstance {
+    #[must_use]
+    pub fn new(config: &Config, sdk_config: &SdkConfig) -> Self {
+        let region = sdk_config
+            .region()
+            .map_or_else(|| config.aws_region_name.clone(), |r| r.as_ref().into());
+        Self {
+            backup_client: BackupClient::from_conf(sdk_config.into()),
+            region,
+        }
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
+        let region: String = region.as_ref().into();
+        self.region = region.as_str().into();
+        let region = Region::new(region);
+        let conf = self
+            .backup_client
+            .config()
+            .to_builder()
+            .region(region)
+            .build();
+        self.backup_client = BackupClient::from_conf(conf);
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_backup_plans(&self) -> Result<impl Iterator<Item = BackupPlanInfo>, Error> {
+        self.backup_client
+            .list_backup_plans()
+            .send()
+            .await
+            .map_err(Into::into)
+            .map(|r| {
+                r.backup_plans_list
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|plan| {
+                        Some(BackupPlanInfo {
+                            plan_id: plan.backup_plan_id?.into(),
+                            plan_name: plan.backup_plan_name?.into(),
+                            creation_date: plan.creation_date.as_ref().and_then(from_datetime),
+                            last_execution_date: plan
+                                .last_execution_date
+                                .as_ref()
+                                .and_then(from_datetime),
+                        })
+                    })
+            })
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_protected_resources(
+        &self,
+    ) -> Result<impl Iterator<Item = ProtectedResourceInfo>, Error> {
+        self.backup_client
+            .list_protected_resources()
+            .send()
+            .await
+            .map_err(Into::into)
+            .map(|r| {
+                r.results.unwrap_or_default().into_iter().filter_map(|res| {
+                    Some(ProtectedResourceInfo {
+                        resource_arn: res.resource_arn?.into(),
+                        resource_type: res.resource_type.unwrap_or_default().into(),
+                        last_backup_time: res.last_backup_time.as_ref().and_then(from_datetime),
+                    })
+                })
+            })
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_recovery_points(&self) -> Result<Vec<RecoveryPointInfo>, Error> {
+        let vaults = self
+            .backup_client
+            .list_backup_vaults()
+            .send()
+            .await?
+            .backup_vault_list
+            .unwrap_or_default();
+        let futures = vaults
+            .into_iter()
+            .filter_map(|vault| vault.backup_vault_name)
+            .map(|vault_name| async move {
+                let points = self
+                    .backup_client
+                    .list_recovery_points_by_backup_vault()
+                    .backup_vault_name(&vault_name)
+                    .send()
+                    .await?
+                    .recovery_points
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|point| {
+                        Some(RecoveryPointInfo {
+                            vault_name: vault_name.as_str().into(),
+                            recovery_point_arn: point.recovery_point_arn?.into(),
+                            resource_arn: point.resource_arn.unwrap_or_default().into(),
+                            status: point
+                                .status
+                                .map_or_else(StackString::new, |s| s.as_str().into()),
+                            creation_date: point.creation_date.as_ref().and_then(from_datetime),
+                            backup_size: point.backup_size_in_bytes.map_or(0.0, |s| s as f64 / 1e6),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                Ok(points)
+            });
+        let results: Result<Vec<_>, Error> = try_join_all(futures).await;
+        let mut points: Vec<_> = results?.into_iter().flatten().collect();
+        points.sort_by_key(|p| std::cmp::Reverse(p.creation_date));
+        Ok(points)
+    }
+
+    /// Assign ebs volumes to an existing backup plan, returns the selection id
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn assign_volumes_to_plan(
+        &self,
+        config: &Config,
+        plan_id: impl Into<String>,
+        volume_ids: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<StackString, Error> {
+        let owner_id = config
+            .my_owner_id
+            .as_ref()
+            .ok_or_else(|| format_err!("No owner id"))?;
+        let iam_role_arn = config.backup_iam_role_arn.as_ref().map_or_else(
+            || {
+                format_sstr!(
+                    "arn:aws:iam::{owner_id}:role/service-role/AWSBackupDefaultServiceRole"
+                )
+            },
+            Clone::clone,
+        );
+        let resources: Vec<String> = volume_ids
+            .into_iter()
+            .map(|volid| {
+                format_sstr!(
+                    "arn:aws:ec2:{}:{owner_id}:volume/{}",
+                    self.region,
+                    volid.as_ref()
+                )
+                .into()
+            })
+            .collect();
+        if resources.is_empty() {
+            return Err(format_err!("No volumes to assign"));
+        }
+        let selection_name = format_sstr!(
+            "aws-app-rust-{}",
+            OffsetDateTime::now_utc().unix_timestamp()
+        );
+        let selection = BackupSelection::builder()
+            .selection_name(selection_name)
+            .iam_role_arn(iam_role_arn)
+            .set_resources(Some(resources))
+            .build()?;
+        let selection_id = self
+            .backup_client
+            .create_backup_selection()
+            .backup_plan_id(plan_id)
+            .backup_selection(selection)
+            .send()
+            .await?
+            .selection_id
+            .ok_or_else(|| format_err!("No selection id"))?;
+        Ok(selection_id.into())
+    }
+}
+
+fn from_datetime(d: &DateTime) -> Option<DateTimeWrapper> {
+    OffsetDateTime::from_unix_timestamp(d.secs())
+        .ok()
+        .map(Into::into)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupPlanInfo {
+    pub plan_id: StackString,
+    pub plan_name: StackString,
+    pub creation_date: Option<DateTimeWrapper>,
+    pub last_execution_date: Option<DateTimeWrapper>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProtectedResourceInfo {
+    pub resource_arn: StackString,
+    pub resource_type: StackString,
+    pub last_backup_time: Option<DateTimeWrapper>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryPointInfo {
+    pub vault_name: StackString,
+    pub recovery_point_arn: StackString,
+    pub resource_arn: StackString,
+    pub status: StackString,
+    pub creation_date: Option<DateTimeWrapper>,
+    pub backup_size: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::{backup_instance::BackupInstance, config::Config};
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_list_backup_plans() -> Result<(), Error> {
+        let config = Config::init_config()?;
+        let sdk_config = aws_config::load_from_env().await;
+        let backup = BackupInstance::new(&config, &sdk_config);
+        let plans: Vec<_> = backup.list_backup_plans().await?.collect();
+        assert!(plans.iter().all(|p| !p.plan_id.is_empty()));
+        Ok(())
+    }
+}
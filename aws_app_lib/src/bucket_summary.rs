@@ -0,0 +1,142 @@
+use stack_string::{format_sstr, StackString};
+use time::OffsetDateTime;
+
+/// A rule of a bucket lifecycle configuration
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LifecycleRuleInfo {
+    pub id: StackString,
+    pub enabled: bool,
+    /// Objects are deleted this many days after creation
+    pub expiration_days: Option<i32>,
+    /// Storage classes objects move to, and after how many days
+    pub transitions: Vec<(StackString, i32)>,
+}
+
+/// Number and total size of the objects in a bucket
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BucketUsage {
+    pub object_count: u64,
+    pub size_bytes: u64,
+}
+
+/// Lifecycle, encryption and size of a bucket, parts aws refused to return
+/// (typically buckets in another region) are left as `None`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BucketSummary {
+    pub name: StackString,
+    pub created: Option<OffsetDateTime>,
+    pub lifecycle_rules: Option<Vec<LifecycleRuleInfo>>,
+    /// Default server side encryption algorithm
+    pub encryption: Option<StackString>,
+    pub usage: Option<BucketUsage>,
+    /// Bucket configured as `inbound_email_bucket`
+    pub inbound_email: bool,
+}
+
+impl BucketSummary {
+    /// True if the bucket has at least one enabled rule that expires objects
+    #[must_use]
+    pub fn has_expiration(&self) -> bool {
+        self.lifecycle_rules.as_ref().map_or(false, |rules| {
+            rules
+                .iter()
+                .any(|r| r.enabled && r.expiration_days.is_some())
+        })
+    }
+
+    /// Objects in the bucket accumulate without ever being expired
+    #[must_use]
+    pub fn missing_lifecycle(&self) -> bool {
+        self.lifecycle_rules.is_some() && !self.has_expiration()
+    }
+
+    /// Short description of the enabled rules, e.g. `expire 30d, GLACIER 7d`
+    #[must_use]
+    pub fn lifecycle_description(&self) -> StackString {
+        let rules = match &self.lifecycle_rules {
+            Some(rules) => rules,
+            None => return "unknown".into(),
+        };
+        let mut parts = Vec::new();
+        for rule in rules.iter().filter(|r| r.enabled) {
+            if let Some(days) = rule.expiration_days {
+                parts.push(format_sstr!("expire {days}d"));
+            }
+            for (storage_class, days) in &rule.transitions {
+                parts.push(format_sstr!("{storage_class} {days}d"));
+            }
+        }
+        if parts.is_empty() {
+            "none".into()
+        } else {
+            parts.join(", ").into()
+        }
+    }
+}
+
+/// Largest buckets first, buckets of unknown size last
+pub fn sort_by_size(summaries: &mut [BucketSummary]) {
+    summaries.sort_by(|a, b| {
+        let size = |s: &BucketSummary| s.usage.map(|u| u.size_bytes);
+        size(b).cmp(&size(a)).then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucket_summary::{sort_by_size, BucketSummary, BucketUsage, LifecycleRuleInfo};
+
+    #[test]
+    fn test_bucket_summary() {
+        let expiring = BucketSummary {
+            name: "logs".into(),
+            lifecycle_rules: Some(vec![
+                LifecycleRuleInfo {
+                    id: "disabled".into(),
+                    enabled: false,
+                    expiration_days: Some(1),
+                    ..LifecycleRuleInfo::default()
+                },
+                LifecycleRuleInfo {
+                    id: "archive".into(),
+                    enabled: true,
+                    expiration_days: Some(365),
+                    transitions: vec![("GLACIER".into(), 30)],
+                },
+            ]),
+            usage: Some(BucketUsage {
+                object_count: 10,
+                size_bytes: 100,
+            }),
+            ..BucketSummary::default()
+        };
+        assert!(expiring.has_expiration());
+        assert!(!expiring.missing_lifecycle());
+        assert_eq!(expiring.lifecycle_description(), "expire 365d, GLACIER 30d");
+
+        let email = BucketSummary {
+            name: "email".into(),
+            lifecycle_rules: Some(Vec::new()),
+            usage: Some(BucketUsage {
+                object_count: 1000,
+                size_bytes: 10_000,
+            }),
+            inbound_email: true,
+            ..BucketSummary::default()
+        };
+        assert!(email.missing_lifecycle());
+        assert_eq!(email.lifecycle_description(), "none");
+
+        let other_region = BucketSummary {
+            name: "elsewhere".into(),
+            ..BucketSummary::default()
+        };
+        assert!(!other_region.missing_lifecycle());
+        assert_eq!(other_region.lifecycle_description(), "unknown");
+
+        let mut summaries = vec![other_region, expiring, email];
+        sort_by_size(&mut summaries);
+        let names: Vec<_> = summaries.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["email", "logs", "elsewhere"]);
+    }
+}
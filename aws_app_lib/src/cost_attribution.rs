@@ -0,0 +1,228 @@
+use stack_string::StackString;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::ec2_instance::{Ec2InstanceInfo, SnapshotInfo, VolumeInfo};
+
+/// Hours in an average month, as used by aws for monthly estimates
+pub const HOURS_PER_MONTH: f64 = 730.0;
+
+/// Group of resources without the tag being attributed on
+pub const UNTAGGED: &str = "(untagged)";
+
+/// Prices the estimate is built from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CostRates {
+    /// Hourly ondemand price of each instance type
+    pub instance_hourly: HashMap<StackString, f64>,
+    pub volume_gb_month: f64,
+    pub snapshot_gb_month: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CostResource {
+    Instance,
+    Volume,
+    Snapshot,
+}
+
+impl CostResource {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Instance => "instance",
+            Self::Volume => "volume",
+            Self::Snapshot => "snapshot",
+        }
+    }
+}
+
+/// Estimated monthly cost of a single resource
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResourceCost {
+    pub resource: CostResource,
+    pub id: StackString,
+    /// Value of the tag, `UNTAGGED` if the resource doesn't have it
+    pub group: StackString,
+    pub monthly_cost: f64,
+    /// Unattached volume, paid for without being used
+    pub waste: bool,
+}
+
+/// Estimated monthly cost of all resources sharing a tag value
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TagCost {
+    pub group: StackString,
+    pub instances: f64,
+    pub volumes: f64,
+    pub snapshots: f64,
+    pub waste: f64,
+}
+
+impl TagCost {
+    #[must_use]
+    pub fn total(&self) -> f64 {
+        self.instances + self.volumes + self.snapshots
+    }
+}
+
+fn tag_value(tags: &HashMap<StackString, StackString>, tag: &str) -> Option<StackString> {
+    tags.get(tag).filter(|v| !v.is_empty()).cloned()
+}
+
+/// Estimate the monthly cost of every resource and attribute it to the value
+/// of its `tag`. Only running instances are charged for compute, volumes
+/// without the tag inherit it from the instance they are attached to, and
+/// snapshots are charged for their full volume size (an upper bound, as
+/// snapshots are incremental).
+#[must_use]
+pub fn attribute_costs(
+    tag: &str,
+    instances: &[Ec2InstanceInfo],
+    volumes: &[VolumeInfo],
+    snapshots: &[SnapshotInfo],
+    rates: &CostRates,
+) -> Vec<ResourceCost> {
+    let mut volume_owner: HashMap<&str, StackString> = HashMap::new();
+    let mut costs = Vec::new();
+    for instance in instances {
+        let group = tag_value(&instance.tags, tag).unwrap_or_else(|| UNTAGGED.into());
+        for volume in &instance.volumes {
+            volume_owner.insert(volume.as_str(), group.clone());
+        }
+        let hourly = if instance.state == "running" {
+            rates
+                .instance_hourly
+                .get(&instance.instance_type)
+                .copied()
+                .unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        costs.push(ResourceCost {
+            resource: CostResource::Instance,
+            id: instance.id.clone(),
+            group,
+            monthly_cost: hourly * HOURS_PER_MONTH,
+            waste: false,
+        });
+    }
+    for volume in volumes {
+        let group = tag_value(&volume.tags, tag)
+            .or_else(|| volume_owner.get(volume.id.as_str()).cloned())
+            .unwrap_or_else(|| UNTAGGED.into());
+        costs.push(ResourceCost {
+            resource: CostResource::Volume,
+            id: volume.id.clone(),
+            group,
+            monthly_cost: volume.size as f64 * rates.volume_gb_month,
+            waste: volume.state == "available",
+        });
+    }
+    for snapshot in snapshots {
+        costs.push(ResourceCost {
+            resource: CostResource::Snapshot,
+            id: snapshot.id.clone(),
+            group: tag_value(&snapshot.tags, tag).unwrap_or_else(|| UNTAGGED.into()),
+            monthly_cost: snapshot.volume_size as f64 * rates.snapshot_gb_month,
+            waste: false,
+        });
+    }
+    costs
+}
+
+/// Totals per tag value, most expensive first
+#[must_use]
+pub fn summarize_costs(costs: &[ResourceCost]) -> Vec<TagCost> {
+    let mut groups: BTreeMap<&str, TagCost> = BTreeMap::new();
+    for cost in costs {
+        let entry = groups
+            .entry(cost.group.as_str())
+            .or_insert_with(|| TagCost {
+                group: cost.group.clone(),
+                ..TagCost::default()
+            });
+        match cost.resource {
+            CostResource::Instance => entry.instances += cost.monthly_cost,
+            CostResource::Volume => entry.volumes += cost.monthly_cost,
+            CostResource::Snapshot => entry.snapshots += cost.monthly_cost,
+        }
+        if cost.waste {
+            entry.waste += cost.monthly_cost;
+        }
+    }
+    let mut groups: Vec<_> = groups.into_values().collect();
+    groups.sort_by(|a, b| b.total().total_cmp(&a.total()));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+    use std::collections::HashMap;
+
+    use crate::{
+        cost_attribution::{
+            attribute_costs, summarize_costs, CostRates, HOURS_PER_MONTH, UNTAGGED,
+        },
+        ec2_instance::{Ec2InstanceInfo, SnapshotInfo, VolumeInfo},
+    };
+
+    fn instance(id: &str, state: &str, tags: HashMap<&str, &str>) -> Ec2InstanceInfo {
+        Ec2InstanceInfo {
+            id: id.into(),
+            state: state.into(),
+            instance_type: "t3.micro".into(),
+            tags: tags
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+            ..Ec2InstanceInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_attribute_costs() {
+        let mut running = instance("i-0001", "running", hashmap! {"project" => "web"});
+        running.volumes.push("vol-0001".into());
+        let instances = [running, instance("i-0002", "stopped", HashMap::new())];
+        let volumes = [
+            VolumeInfo {
+                id: "vol-0001".into(),
+                size: 10,
+                state: "in-use".into(),
+                ..VolumeInfo::default()
+            },
+            VolumeInfo {
+                id: "vol-0002".into(),
+                size: 100,
+                state: "available".into(),
+                tags: hashmap! {"project".into() => "web".into()},
+                ..VolumeInfo::default()
+            },
+        ];
+        let snapshots = [SnapshotInfo {
+            id: "snap-0001".into(),
+            volume_size: 20,
+            ..SnapshotInfo::default()
+        }];
+        let rates = CostRates {
+            instance_hourly: hashmap! {"t3.micro".into() => 0.01},
+            volume_gb_month: 0.1,
+            snapshot_gb_month: 0.05,
+        };
+        let costs = attribute_costs("project", &instances, &volumes, &snapshots, &rates);
+        assert_eq!(costs.len(), 5);
+        assert!(costs[1].monthly_cost.abs() < 1e-9);
+        assert_eq!(costs[2].group, "web");
+        assert!(costs[3].waste);
+
+        let summary = summarize_costs(&costs);
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].group, "web");
+        assert!((summary[0].instances - 0.01 * HOURS_PER_MONTH).abs() < 1e-9);
+        assert!((summary[0].volumes - 11.0).abs() < 1e-9);
+        assert!((summary[0].waste - 10.0).abs() < 1e-9);
+        assert_eq!(summary[1].group, UNTAGGED);
+        assert!((summary[1].total() - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::{format_err, Error};
+use stack_string::{format_sstr, StackString};
+use std::collections::HashMap;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+use crate::csv_export::CsvRecord;
+
+/// One of the two access keys the credential report lists per user
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessKeyUsage {
+    pub active: bool,
+    pub last_rotated: Option<OffsetDateTime>,
+    pub last_used: Option<OffsetDateTime>,
+}
+
+/// A row of the iam credential report, the account root user is listed as
+/// `<root_account>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CredentialReportEntry {
+    pub user: StackString,
+    pub arn: StackString,
+    pub user_creation_time: Option<OffsetDateTime>,
+    pub password_enabled: bool,
+    pub password_last_used: Option<OffsetDateTime>,
+    pub mfa_active: bool,
+    pub access_keys: Vec<AccessKeyUsage>,
+}
+
+/// Timestamps are ISO 8601, missing values are `N/A`, `no_information` or
+/// `not_supported`
+fn parse_time(value: Option<&&str>) -> Option<OffsetDateTime> {
+    value.and_then(|v| OffsetDateTime::parse(v, &Rfc3339).ok())
+}
+
+fn parse_bool(value: Option<&&str>) -> bool {
+    value == Some(&"true")
+}
+
+impl CredentialReportEntry {
+    /// Parse the csv returned by `GetCredentialReport`, columns are looked up
+    /// by header name
+    /// # Errors
+    /// Returns error if the report has no header or a row has the wrong
+    /// number of columns
+    pub fn parse_report(content: &str) -> Result<Vec<Self>, Error> {
+        let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+        let header: Vec<_> = lines
+            .next()
+            .ok_or_else(|| format_err!("empty credential report"))?
+            .split(',')
+            .collect();
+        lines
+            .map(|line| {
+                let fields: Vec<_> = line.split(',').collect();
+                if fields.len() != header.len() {
+                    return Err(format_err!("malformed credential report line {line}"));
+                }
+                let row: HashMap<&str, &str> =
+                    header.iter().copied().zip(fields.into_iter()).collect();
+                let access_keys = (1..=2)
+                    .map(|idx| AccessKeyUsage {
+                        active: parse_bool(
+                            row.get(format_sstr!("access_key_{idx}_active").as_str()),
+                        ),
+                        last_rotated: parse_time(
+                            row.get(format_sstr!("access_key_{idx}_last_rotated").as_str()),
+                        ),
+                        last_used: parse_time(
+                            row.get(format_sstr!("access_key_{idx}_last_used_date").as_str()),
+                        ),
+                    })
+                    .collect();
+                Ok(Self {
+                    user: row.get("user").copied().unwrap_or("").into(),
+                    arn: row.get("arn").copied().unwrap_or("").into(),
+                    user_creation_time: parse_time(row.get("user_creation_time")),
+                    password_enabled: parse_bool(row.get("password_enabled")),
+                    password_last_used: parse_time(row.get("password_last_used")),
+                    mfa_active: parse_bool(row.get("mfa_active")),
+                    access_keys,
+                })
+            })
+            .collect()
+    }
+
+    /// Most recent console login or access key use
+    #[must_use]
+    pub fn last_activity(&self) -> Option<OffsetDateTime> {
+        self.access_keys
+            .iter()
+            .filter_map(|k| k.last_used)
+            .chain(self.password_last_used)
+            .max()
+    }
+
+    /// No activity within `days`, users created since are given the benefit
+    /// of the doubt
+    #[must_use]
+    pub fn is_inactive(&self, now: OffsetDateTime, days: i64) -> bool {
+        let cutoff = now - Duration::days(days);
+        match self.last_activity().or(self.user_creation_time) {
+            Some(last) => last < cutoff,
+            None => true,
+        }
+    }
+
+    /// Days since the last console login or access key use
+    #[must_use]
+    pub fn days_since_activity(&self, now: OffsetDateTime) -> Option<i64> {
+        self.last_activity().map(|t| (now - t).whole_days())
+    }
+}
+
+fn format_age(time: Option<OffsetDateTime>, now: OffsetDateTime) -> StackString {
+    time.map_or_else(StackString::new, |t| {
+        format_sstr!("{}", (now - t).whole_days())
+    })
+}
+
+impl CsvRecord for CredentialReportEntry {
+    fn csv_header() -> &'static [&'static str] {
+        &[
+            "user",
+            "arn",
+            "password_enabled",
+            "password_last_used_days",
+            "mfa_active",
+            "access_key_1_active",
+            "access_key_1_last_used_days",
+            "access_key_2_active",
+            "access_key_2_last_used_days",
+            "last_activity_days",
+        ]
+    }
+
+    fn csv_fields(&self) -> Vec<StackString> {
+        let now = OffsetDateTime::now_utc();
+        let mut fields = vec![
+            self.user.clone(),
+            self.arn.clone(),
+            format_sstr!("{}", self.password_enabled),
+            format_age(self.password_last_used, now),
+            format_sstr!("{}", self.mfa_active),
+        ];
+        for key in &self.access_keys {
+            fields.push(format_sstr!("{}", key.active));
+            fields.push(format_age(key.last_used, now));
+        }
+        fields.push(format_age(self.last_activity(), now));
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use time::macros::datetime;
+
+    use crate::credential_report::CredentialReportEntry;
+
+    static REPORT: &str = "user,arn,user_creation_time,password_enabled,password_last_used,\
+                           password_last_changed,password_next_rotation,mfa_active,\
+                           access_key_1_active,access_key_1_last_rotated,\
+                           access_key_1_last_used_date,access_key_1_last_used_region,\
+                           access_key_1_last_used_service,access_key_2_active,\
+                           access_key_2_last_rotated,access_key_2_last_used_date,\
+                           access_key_2_last_used_region,access_key_2_last_used_service,\
+                           cert_1_active,cert_1_last_rotated,cert_2_active,cert_2_last_rotated
+<root_account>,arn:aws:iam::123456789012:root,2019-01-01T00:00:00+00:00,not_supported,2024-05-30T12:00:00+00:00,not_supported,not_supported,true,false,N/A,N/A,N/A,N/A,false,N/A,N/A,N/A,N/A,false,N/A,false,N/A
+ci,arn:aws:iam::123456789012:user/ci,2020-01-01T00:00:00+00:00,false,N/A,N/A,N/A,false,true,2020-01-01T00:00:00+00:00,2023-12-01T00:00:00+00:00,us-east-1,s3,false,N/A,N/A,N/A,N/A,false,N/A,false,N/A
+new,arn:aws:iam::123456789012:user/new,2024-05-20T00:00:00+00:00,true,no_information,N/A,N/A,false,false,N/A,N/A,N/A,N/A,false,N/A,N/A,N/A,N/A,false,N/A,false,N/A
+";
+
+    #[test]
+    fn test_parse_report() -> Result<(), Error> {
+        let now = datetime!(2024-06-01 00:00:00 UTC);
+        let entries = CredentialReportEntry::parse_report(REPORT)?;
+        assert_eq!(entries.len(), 3);
+
+        let root = &entries[0];
+        assert!(root.mfa_active);
+        assert!(!root.password_enabled);
+        assert_eq!(root.days_since_activity(now), Some(1));
+        assert!(!root.is_inactive(now, 90));
+
+        let ci = &entries[1];
+        assert!(ci.access_keys[0].active);
+        assert!(!ci.access_keys[1].active);
+        assert_eq!(ci.last_activity(), Some(datetime!(2023-12-01 00:00:00 UTC)));
+        assert!(ci.is_inactive(now, 90));
+
+        let new = &entries[2];
+        assert_eq!(new.last_activity(), None);
+        assert!(!new.is_inactive(now, 90));
+
+        assert!(CredentialReportEntry::parse_report("").is_err());
+        assert!(CredentialReportEntry::parse_report("user,arn\na,b,c").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::{format_err, Error};
+use stack_string::StackString;
+use std::{fmt, str::FromStr};
+use time::{Date, Duration, OffsetDateTime, Time};
+
+/// Bound on the number of steps `next_after` takes before deciding a schedule
+/// never fires (e.g. `0 0 31 2 *`)
+const MAX_STEPS: usize = 100_000;
+
+/// Five field cron expression (minute, hour, day of month, month, day of
+/// week), evaluated in UTC. Each field accepts `*`, `n`, `a-b`, `*/n`,
+/// `a-b/n` and comma separated lists of those.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: StackString,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+fn parse_value(s: &str, min: u8, max: u8) -> Result<u8, Error> {
+    let value: u8 = s
+        .parse()
+        .map_err(|_| format_err!("{s} is not a valid cron value"))?;
+    if value < min || value > max {
+        return Err(format_err!("{value} is not in {min}-{max}"));
+    }
+    Ok(value)
+}
+
+/// Bitmask of the values matched by one field
+fn parse_field(field: &str, min: u8, max: u8) -> Result<u64, Error> {
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, parse_value(step, 1, max)?),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, min, max)?, parse_value(end, min, max)?)
+        } else {
+            let value = parse_value(range, min, max)?;
+            (value, value)
+        };
+        if start > end {
+            return Err(format_err!("{part} is an empty range"));
+        }
+        for value in (start..=end).step_by(step.into()) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn matches(mask: u64, value: u8) -> bool {
+    mask & (1 << value) != 0
+}
+
+impl CronSchedule {
+    fn matches_day(&self, date: Date) -> bool {
+        let dom = matches(self.days_of_month, date.day());
+        let dow = matches(self.days_of_week, date.weekday().number_days_from_sunday());
+        // as in cron, a restricted day of month and day of week are or'ed
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+
+    /// First minute strictly after `after` matching the schedule
+    #[must_use]
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let after = after.to_offset(time::UtcOffset::UTC);
+        let mut t = after.replace_time(Time::from_hms(after.hour(), after.minute(), 0).ok()?)
+            + Duration::minutes(1);
+        for _ in 0..MAX_STEPS {
+            if !matches(self.months, t.month().into()) {
+                let (year, month) = match t.month() {
+                    time::Month::December => (t.year() + 1, time::Month::January),
+                    m => (t.year(), m.next()),
+                };
+                t = Date::from_calendar_date(year, month, 1)
+                    .ok()?
+                    .midnight()
+                    .assume_utc();
+            } else if !self.matches_day(t.date()) {
+                t = t.date().next_day()?.midnight().assume_utc();
+            } else if !matches(self.hours, t.hour()) {
+                t = t.replace_time(Time::from_hms(t.hour(), 0, 0).ok()?) + Duration::hours(1);
+            } else if !matches(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<_> = s.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format_err!("{s} is not a five field cron expression"));
+        }
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        // both 0 and 7 are sunday
+        if matches(days_of_week, 7) {
+            days_of_week |= 1;
+        }
+        Ok(Self {
+            expression: fields.join(" ").into(),
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*",
+        })
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use time::macros::datetime;
+
+    use crate::cron_schedule::CronSchedule;
+
+    #[test]
+    fn test_cron_schedule_next_after() -> Result<(), Error> {
+        let every_six_hours: CronSchedule = "15 */6 * * *".parse()?;
+        assert_eq!(
+            every_six_hours.next_after(datetime!(2024-03-10 06:15:00 UTC)),
+            Some(datetime!(2024-03-10 12:15:00 UTC))
+        );
+        assert_eq!(
+            every_six_hours.next_after(datetime!(2024-03-10 23:59:30 UTC)),
+            Some(datetime!(2024-03-11 00:15:00 UTC))
+        );
+
+        let weekdays: CronSchedule = "0 3 * * 1-5".parse()?;
+        // 2024-03-09 is a saturday
+        assert_eq!(
+            weekdays.next_after(datetime!(2024-03-09 12:00:00 UTC)),
+            Some(datetime!(2024-03-11 03:00:00 UTC))
+        );
+
+        let new_year: CronSchedule = "0 0 1 1 *".parse()?;
+        assert_eq!(
+            new_year.next_after(datetime!(2024-03-09 12:00:00 UTC)),
+            Some(datetime!(2025-01-01 00:00:00 UTC))
+        );
+
+        let never: CronSchedule = "0 0 31 2 *".parse()?;
+        assert_eq!(never.next_after(datetime!(2024-03-09 12:00:00 UTC)), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cron_schedule_parse_errors() {
+        assert!("* * * *".parse::<CronSchedule>().is_err());
+        assert!("60 * * * *".parse::<CronSchedule>().is_err());
+        assert!("5-1 * * * *".parse::<CronSchedule>().is_err());
+        assert!("*/0 * * * *".parse::<CronSchedule>().is_err());
+        assert_eq!(
+            "0  4 * * 0".parse::<CronSchedule>().unwrap().to_string(),
+            "0 4 * * 0"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::{format_err, Error};
+use similar::TextDiff;
+use stack_string::{format_sstr, StackString};
+use std::process::Stdio;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::{
+    cron_schedule::CronSchedule,
+    models::AuditLog,
+    storage::{AuditLogRepo, Storage},
+};
+
+const SPECIAL_SCHEDULES: [&str; 8] = [
+    "@reboot",
+    "@yearly",
+    "@annually",
+    "@monthly",
+    "@weekly",
+    "@daily",
+    "@midnight",
+    "@hourly",
+];
+
+/// Check one crontab line, blank lines, comments and `NAME=value`
+/// environment assignments are accepted as is
+fn validate_line(line: &str) -> Result<(), Error> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(());
+    }
+    let mut fields = line.split_whitespace();
+    let first = fields.next().unwrap_or("");
+    if first.starts_with('@') {
+        if !SPECIAL_SCHEDULES.contains(&first) {
+            return Err(format_err!("{first} is not a cron schedule"));
+        }
+        if fields.next().is_none() {
+            return Err(format_err!("no command"));
+        }
+        return Ok(());
+    }
+    if let Some((name, _)) = line.split_once('=') {
+        let name = name.trim();
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Ok(());
+        }
+    }
+    let schedule: Vec<_> = line.split_whitespace().take(5).collect();
+    schedule.join(" ").parse::<CronSchedule>()?;
+    if line.split_whitespace().nth(5).is_none() {
+        return Err(format_err!("no command"));
+    }
+    Ok(())
+}
+
+/// # Errors
+/// Returns error listing every invalid line of `text`
+pub fn validate_crontab(text: &str) -> Result<(), Error> {
+    let errors: Vec<StackString> = text
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            validate_line(line)
+                .err()
+                .map(|e| format_sstr!("line {}: {e}", idx + 1))
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format_err!("{}", errors.join("\n")))
+    }
+}
+
+/// Unified diff from `current` to `proposed`, empty when they're the same
+#[must_use]
+pub fn crontab_diff(current: &str, proposed: &str) -> StackString {
+    TextDiff::from_lines(current, proposed)
+        .unified_diff()
+        .header("current", "proposed")
+        .to_string()
+        .into()
+}
+
+/// Current crontab of the user running the app, empty when there is none
+/// # Errors
+/// Returns error if spawn of crontab fails
+pub async fn read_user_crontab() -> Result<StackString, Error> {
+    let output = Command::new("crontab").arg("-l").output().await?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).as_ref().into())
+    } else {
+        // crontab exits with 1 and "no crontab for user" when there is none
+        Ok(StackString::new())
+    }
+}
+
+/// Validate `text` and install it with `crontab -` as the crontab of the
+/// user running the app, the diff is recorded in the audit log against
+/// `user`.  Returns the diff, nothing is installed when it's empty.
+/// # Errors
+/// Returns error if `text` has invalid lines, spawn of crontab fails or
+/// crontab rejects it
+pub async fn install_user_crontab(
+    storage: &dyn Storage,
+    user: &str,
+    text: &str,
+) -> Result<StackString, Error> {
+    validate_crontab(text)?;
+    let mut text = text.to_string();
+    if !text.ends_with('\n') {
+        // cron ignores a last line without a newline
+        text.push('\n');
+    }
+    let current = read_user_crontab().await?;
+    let diff = crontab_diff(&current, &text);
+    if diff.is_empty() {
+        return Ok(diff);
+    }
+    let mut child = Command::new("crontab")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(format_err!(
+            "crontab rejected the update: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    storage
+        .insert_audit_log(&AuditLog::new("crontab_update", user, Some(diff.clone())))
+        .await?;
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crontab::{crontab_diff, validate_crontab};
+
+    #[test]
+    fn test_validate_crontab() {
+        let crontab = "# backups\nMAILTO=\"\"\nPATH=/usr/local/bin:/usr/bin\n\n15 */6 * * * \
+                       /usr/bin/aws-app-rust update\n@reboot /usr/bin/novnc\n0 3 * * 1-5 \
+                       backup.sh > /dev/null 2>&1\n";
+        assert!(validate_crontab(crontab).is_ok());
+
+        let e = validate_crontab("60 * * * * date\n* * * * *\n@often date\n").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "line 1: 60 is not in 0-59\nline 2: no command\nline 3: @often is not a cron \
+             schedule"
+        );
+        assert!(validate_crontab("* * * date").is_err());
+    }
+
+    #[test]
+    fn test_crontab_diff() {
+        let current = "MAILTO=\"\"\n0 3 * * * backup.sh\n";
+        assert_eq!(crontab_diff(current, current), "");
+        let diff = crontab_diff(current, "MAILTO=\"\"\n0 4 * * * backup.sh\n");
+        assert!(diff.contains("-0 3 * * * backup.sh"));
+        assert!(diff.contains("+0 4 * * * backup.sh"));
+        assert!(diff.starts_with("--- current\n+++ proposed\n"));
+    }
+}
@@ -0,0 +1,314 @@
+use anyhow::{format_err, Error};
+use stack_string::{format_sstr, StackString};
+use std::{collections::HashMap, fmt, str::FromStr};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    aws_app_interface::AwsInstancePrice,
+    ec2_instance::{Ec2InstanceInfo, SnapshotInfo, VolumeInfo},
+    ecr_instance::ImageInfo,
+    models::InboundEmailDB,
+};
+
+/// Resources that can be downloaded as csv from `/aws/export/{resource}.csv`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportResource {
+    Instances,
+    Volumes,
+    Snapshots,
+    Prices,
+    Ecr,
+    InboundEmail,
+    IamReport,
+}
+
+impl ExportResource {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Instances => "instances",
+            Self::Volumes => "volumes",
+            Self::Snapshots => "snapshots",
+            Self::Prices => "prices",
+            Self::Ecr => "ecr",
+            Self::InboundEmail => "inbound-email",
+            Self::IamReport => "iam-report",
+        }
+    }
+}
+
+impl fmt::Display for ExportResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for ExportResource {
+    type Err = Error;
+
+    /// Accepts the name with or without a trailing `.csv`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_end_matches(".csv") {
+            "instances" => Ok(Self::Instances),
+            "volumes" => Ok(Self::Volumes),
+            "snapshots" => Ok(Self::Snapshots),
+            "prices" => Ok(Self::Prices),
+            "ecr" => Ok(Self::Ecr),
+            "inbound-email" => Ok(Self::InboundEmail),
+            "iam-report" => Ok(Self::IamReport),
+            _ => Err(format_err!("{s} cannot be exported")),
+        }
+    }
+}
+
+/// A row of an exported table, the columns follow the ssr tables
+pub trait CsvRecord {
+    fn csv_header() -> &'static [&'static str];
+
+    fn csv_fields(&self) -> Vec<StackString>;
+}
+
+/// Quote `field` if it contains a separator, quote or line break (RFC 4180)
+#[must_use]
+pub fn csv_escape(field: &str) -> StackString {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format_sstr!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+/// Header line followed by one line per record, lines end in `\r\n`
+#[must_use]
+pub fn to_csv<T: CsvRecord>(records: &[T]) -> String {
+    let mut output = T::csv_header().join(",");
+    output.push_str("\r\n");
+    for record in records {
+        let fields: Vec<_> = record
+            .csv_fields()
+            .iter()
+            .map(|f| csv_escape(f.as_str()))
+            .collect();
+        output.push_str(&fields.join(","));
+        output.push_str("\r\n");
+    }
+    output
+}
+
+fn format_tags(tags: &HashMap<StackString, StackString>) -> StackString {
+    let mut tags: Vec<_> = tags.iter().map(|(k, v)| format_sstr!("{k}={v}")).collect();
+    tags.sort();
+    tags.join("; ").into()
+}
+
+fn format_datetime(datetime: OffsetDateTime) -> StackString {
+    datetime
+        .format(&Rfc3339)
+        .map_or_else(|_| format_sstr!("{datetime}"), Into::into)
+}
+
+fn format_option<T: fmt::Display>(value: Option<T>) -> StackString {
+    value.map_or_else(StackString::new, |v| format_sstr!("{v}"))
+}
+
+impl CsvRecord for Ec2InstanceInfo {
+    fn csv_header() -> &'static [&'static str] {
+        &[
+            "id",
+            "name",
+            "dns_name",
+            "state",
+            "instance_type",
+            "launch_time",
+            "availability_zone",
+            "volumes",
+            "tags",
+        ]
+    }
+
+    fn csv_fields(&self) -> Vec<StackString> {
+        vec![
+            self.id.clone(),
+            self.tags.get("Name").cloned().unwrap_or_default(),
+            self.dns_name.clone(),
+            self.state.clone(),
+            self.instance_type.clone(),
+            format_datetime(self.launch_time.to_offsetdatetime()),
+            self.availability_zone.clone(),
+            self.volumes.join("; ").into(),
+            format_tags(&self.tags),
+        ]
+    }
+}
+
+impl CsvRecord for VolumeInfo {
+    fn csv_header() -> &'static [&'static str] {
+        &["id", "availability_zone", "size", "iops", "state", "tags"]
+    }
+
+    fn csv_fields(&self) -> Vec<StackString> {
+        vec![
+            self.id.clone(),
+            self.availability_zone.clone(),
+            format_sstr!("{}", self.size),
+            format_sstr!("{}", self.iops),
+            self.state.clone(),
+            format_tags(&self.tags),
+        ]
+    }
+}
+
+impl CsvRecord for SnapshotInfo {
+    fn csv_header() -> &'static [&'static str] {
+        &["id", "volume_size", "state", "progress", "tags"]
+    }
+
+    fn csv_fields(&self) -> Vec<StackString> {
+        vec![
+            self.id.clone(),
+            format_sstr!("{}", self.volume_size),
+            self.state.clone(),
+            self.progress.clone(),
+            format_tags(&self.tags),
+        ]
+    }
+}
+
+impl CsvRecord for AwsInstancePrice {
+    fn csv_header() -> &'static [&'static str] {
+        &[
+            "instance_type",
+            "ondemand_price",
+            "spot_price",
+            "reserved_price",
+            "ncpu",
+            "memory",
+            "gpu_count",
+            "gpu_type",
+            "network_performance",
+            "instance_family",
+        ]
+    }
+
+    fn csv_fields(&self) -> Vec<StackString> {
+        vec![
+            self.instance_type.clone(),
+            format_option(self.ondemand_price),
+            format_option(self.spot_price),
+            format_option(self.reserved_price),
+            format_sstr!("{}", self.ncpu),
+            format_sstr!("{}", self.memory),
+            format_sstr!("{}", self.gpu_count),
+            format_option(self.gpu_type.as_ref()),
+            format_option(self.network_performance.as_ref()),
+            format_sstr!("{}", self.instance_family),
+        ]
+    }
+}
+
+impl CsvRecord for ImageInfo {
+    fn csv_header() -> &'static [&'static str] {
+        &["repo", "tags", "digest", "pushed_at", "image_size_mb"]
+    }
+
+    fn csv_fields(&self) -> Vec<StackString> {
+        vec![
+            self.repo.clone(),
+            self.tags.join("; ").into(),
+            self.digest.clone(),
+            format_datetime(self.pushed_at),
+            format_sstr!("{:.2}", self.image_size),
+        ]
+    }
+}
+
+/// Message bodies and the raw email are left out, they are available from
+/// the inbound email page
+impl CsvRecord for InboundEmailDB {
+    fn csv_header() -> &'static [&'static str] {
+        &[
+            "id",
+            "date",
+            "from_address",
+            "to_address",
+            "subject",
+            "spam_score",
+            "verdicts",
+            "s3_bucket",
+            "s3_key",
+            "thread_id",
+        ]
+    }
+
+    fn csv_fields(&self) -> Vec<StackString> {
+        vec![
+            format_sstr!("{}", self.id),
+            format_datetime(self.date),
+            self.from_address.clone(),
+            self.to_address.clone(),
+            self.subject.clone(),
+            format_sstr!("{}", self.spam_score),
+            self.verdicts.clone().unwrap_or_default(),
+            self.s3_bucket.clone(),
+            self.s3_key.clone(),
+            format_sstr!("{}", self.thread_id),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::hashmap;
+
+    use crate::{
+        csv_export::{csv_escape, to_csv, ExportResource},
+        ec2_instance::VolumeInfo,
+    };
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(csv_escape("plain"), "plain");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let volumes = [VolumeInfo {
+            id: "vol-0123".into(),
+            availability_zone: "us-east-1a".into(),
+            size: 8,
+            iops: 100,
+            state: "in-use".into(),
+            tags: hashmap! {"Name".into() => "root, main".into()},
+            volume_type: "gp3".into(),
+            encrypted: true,
+            attachment: None,
+        }];
+        assert_eq!(
+            to_csv(&volumes),
+            "id,availability_zone,size,iops,state,tags\r\nvol-0123,us-east-1a,8,100,in-use,\"Name=root, \
+             main\"\r\n"
+        );
+    }
+
+    #[test]
+    fn test_export_resource() -> Result<(), Error> {
+        assert_eq!(
+            "instances.csv".parse::<ExportResource>()?,
+            ExportResource::Instances
+        );
+        assert_eq!(
+            "inbound-email".parse::<ExportResource>()?,
+            ExportResource::InboundEmail
+        );
+        assert_eq!(
+            "iam-report.csv".parse::<ExportResource>()?,
+            ExportResource::IamReport
+        );
+        assert!("users.csv".parse::<ExportResource>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::{format_err, Error};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use stack_string::{format_sstr, StackString};
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Write},
+};
+use time::{macros::format_description, OffsetDateTime};
+
+use crate::{date_time_wrapper::DateTimeWrapper, pgpool::PgPool};
+
+/// Tables managed by this crate, in the order they are restored
+pub const BACKUP_TABLES: [&str; 18] = [
+    "instance_family",
+    "instance_list",
+    "instance_pricing",
+    "price_history",
+    "authorized_users",
+    "inbound_email",
+    "email_attachment",
+    "email_forward_rule",
+    "dmarc_records",
+    "launch_history",
+    "protected_resource",
+    "audit_log",
+    "update_status",
+    "webhooks",
+    "webhook_deliveries",
+    "price_alerts",
+    "dr_policies",
+    "ecr_image_history",
+];
+
+/// First line of a backup, used to check the rows that follow
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupManifest {
+    pub created_at: DateTimeWrapper,
+    /// Latest migration applied to the database the backup was taken from
+    pub schema_version: Option<i64>,
+    /// Number of rows of each table
+    pub tables: BTreeMap<StackString, u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackupRow {
+    pub table: StackString,
+    pub row: Value,
+}
+
+/// Gzipped json lines, the manifest followed by one line per row
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbBackup {
+    pub manifest: BackupManifest,
+    pub rows: Vec<BackupRow>,
+}
+
+impl DbBackup {
+    /// S3 key of a backup taken at `created_at`, keys sort chronologically
+    #[must_use]
+    pub fn key_name(prefix: &str, created_at: OffsetDateTime) -> StackString {
+        let timestamp = created_at
+            .format(format_description!(
+                "[year][month][day]T[hour][minute][second]Z"
+            ))
+            .unwrap_or_default();
+        format_sstr!("{prefix}db_backup_{timestamp}.jsonl.gz")
+    }
+
+    /// # Errors
+    /// Returns error if serialization fails
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, &self.manifest)?;
+        encoder.write_all(b"\n")?;
+        for row in &self.rows {
+            serde_json::to_writer(&mut encoder, row)?;
+            encoder.write_all(b"\n")?;
+        }
+        encoder.finish().map_err(Into::into)
+    }
+
+    /// Decode and verify a backup, the gzip checksum catches corruption and
+    /// the manifest row counts catch truncation
+    /// # Errors
+    /// Returns error if the backup is corrupt or doesn't match its manifest
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        let mut lines = BufReader::new(GzDecoder::new(data)).lines();
+        let manifest: BackupManifest = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => return Err(format_err!("empty backup")),
+        };
+        let rows = lines
+            .filter(|l| l.as_ref().map_or(true, |l| !l.is_empty()))
+            .map(|line| serde_json::from_str(&line?).map_err(Into::into))
+            .collect::<Result<Vec<BackupRow>, Error>>()?;
+        let backup = Self { manifest, rows };
+        backup.verify()?;
+        Ok(backup)
+    }
+
+    /// Row counts match the manifest and every table is one `restore` knows
+    /// # Errors
+    /// Returns error describing the first mismatch
+    pub fn verify(&self) -> Result<(), Error> {
+        let mut counts: BTreeMap<&str, u64> = BTreeMap::new();
+        for row in &self.rows {
+            *counts.entry(row.table.as_str()).or_default() += 1;
+        }
+        for table in self.manifest.tables.keys() {
+            if !BACKUP_TABLES.contains(&table.as_str()) {
+                return Err(format_err!("unknown table {table} in backup"));
+            }
+        }
+        for table in counts.keys() {
+            if !self.manifest.tables.contains_key(*table) {
+                return Err(format_err!("table {table} is missing from the manifest"));
+            }
+        }
+        for (table, expected) in &self.manifest.tables {
+            let found = counts.get(table.as_str()).copied().unwrap_or(0);
+            if found != *expected {
+                return Err(format_err!(
+                    "table {table} has {found} rows, manifest expects {expected}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Dump every table in `BACKUP_TABLES` as json rows
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn dump(pool: &PgPool, schema_version: Option<i64>) -> Result<Self, Error> {
+        let mut conn = pool.get().await?;
+        // a repeatable read transaction gives a consistent snapshot of all tables
+        let tran = conn
+            .build_transaction()
+            .isolation_level(tokio_postgres::IsolationLevel::RepeatableRead)
+            .read_only(true)
+            .start()
+            .await?;
+        let mut tables = BTreeMap::new();
+        let mut rows = Vec::new();
+        for table in BACKUP_TABLES {
+            let query = format_sstr!("SELECT row_to_json(t) FROM {table} t");
+            let results = tran.query(query.as_str(), &[]).await?;
+            tables.insert(table.into(), results.len() as u64);
+            for result in results {
+                rows.push(BackupRow {
+                    table: table.into(),
+                    row: result.try_get(0)?,
+                });
+            }
+        }
+        tran.commit().await?;
+        Ok(Self {
+            manifest: BackupManifest {
+                created_at: DateTimeWrapper::now(),
+                schema_version,
+                tables,
+            },
+            rows,
+        })
+    }
+
+    /// Insert the rows in a single transaction, rows whose key already exists
+    /// are skipped unless `truncate` empties the tables first. Returns the
+    /// number of rows inserted.
+    /// # Errors
+    /// Returns error if db query fails, nothing is restored in that case
+    pub async fn restore(&self, pool: &PgPool, truncate: bool) -> Result<u64, Error> {
+        let mut conn = pool.get().await?;
+        let tran = conn.transaction().await?;
+        if truncate {
+            let tables: Vec<_> = self
+                .manifest
+                .tables
+                .keys()
+                .map(StackString::as_str)
+                .collect();
+            let query = format_sstr!("TRUNCATE {}", tables.join(", "));
+            tran.execute(query.as_str(), &[]).await?;
+        }
+        let mut inserted = 0;
+        for table in BACKUP_TABLES {
+            let query = format_sstr!(
+                "INSERT INTO {table} SELECT * FROM json_populate_record(NULL::{table}, $1) ON \
+                 CONFLICT DO NOTHING"
+            );
+            let statement = tran.prepare(query.as_str()).await?;
+            for row in self.rows.iter().filter(|r| r.table == table) {
+                inserted += tran.execute(&statement, &[&row.row]).await?;
+            }
+        }
+        tran.commit().await?;
+        Ok(inserted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::btreemap;
+    use serde_json::json;
+    use time::macros::datetime;
+
+    use crate::{
+        date_time_wrapper::DateTimeWrapper,
+        db_backup::{BackupManifest, BackupRow, DbBackup},
+    };
+
+    fn backup() -> DbBackup {
+        DbBackup {
+            manifest: BackupManifest {
+                created_at: DateTimeWrapper::from_offsetdatetime(
+                    datetime!(2024-06-01 00:00:00 UTC),
+                ),
+                schema_version: Some(21),
+                tables: btreemap! {
+                    "audit_log".into() => 2,
+                    "update_status".into() => 0,
+                },
+            },
+            rows: vec![
+                BackupRow {
+                    table: "audit_log".into(),
+                    row: json!({"id": 1, "action": "terminate"}),
+                },
+                BackupRow {
+                    table: "audit_log".into(),
+                    row: json!({"id": 2, "action": "tag\nwith newline"}),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_backup_roundtrip() -> Result<(), Error> {
+        let backup = backup();
+        let data = backup.encode()?;
+        assert_eq!(DbBackup::decode(&data)?, backup);
+
+        let mut corrupt = data.clone();
+        let last = corrupt.len() - 5;
+        corrupt[last] ^= 0xff;
+        assert!(DbBackup::decode(&corrupt).is_err());
+        assert!(DbBackup::decode(&[]).is_err());
+
+        assert_eq!(
+            DbBackup::key_name("db/", datetime!(2024-06-01 12:30:05 UTC)),
+            "db/db_backup_20240601T123005Z.jsonl.gz"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup_verify() {
+        let mut truncated = backup();
+        truncated.rows.pop();
+        assert!(truncated.verify().is_err());
+
+        let mut unknown = backup();
+        unknown.manifest.tables.insert("users".into(), 0);
+        assert!(unknown.verify().is_err());
+
+        let mut unlisted = backup();
+        unlisted.rows.push(BackupRow {
+            table: "launch_history".into(),
+            row: json!({}),
+        });
+        assert!(unlisted.verify().is_err());
+    }
+}
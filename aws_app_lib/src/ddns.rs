@@ -0,0 +1,195 @@
+use anyhow::Error;
+use log::{error, info};
+use stack_string::{format_sstr, StackString};
+use std::{fmt, net::Ipv4Addr};
+
+use crate::{
+    aws_api::Route53Api,
+    config::Config,
+    models::AuditLog,
+    notification::send_notification,
+    route53_instance::DnsRecord,
+    ses_client::SesInstance,
+    storage::{AuditLogRepo, Storage},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsUpdate {
+    pub zone: StackString,
+    pub dnsname: StackString,
+    pub old_ip: Ipv4Addr,
+    pub new_ip: Ipv4Addr,
+}
+
+impl fmt::Display for DnsUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} -> {}",
+            self.zone, self.dnsname, self.old_ip, self.new_ip
+        )
+    }
+}
+
+/// Records named in `names` which don't point at `current_ip`, records that
+/// are part of a failover or weighted set are left alone
+#[must_use]
+pub fn stale_records(
+    records: &[(String, DnsRecord)],
+    names: &[impl AsRef<str>],
+    current_ip: Ipv4Addr,
+) -> Vec<DnsUpdate> {
+    records
+        .iter()
+        .filter(|(_, record)| record.is_a_record() && record.routing.is_none())
+        .filter(|(_, record)| {
+            let name = record.dnsname.trim_end_matches('.');
+            names
+                .iter()
+                .any(|n| n.as_ref().trim_end_matches('.') == name)
+        })
+        .filter_map(|(zone, record)| {
+            let old_ip: Ipv4Addr = record.ip.parse().ok()?;
+            if old_ip == current_ip {
+                return None;
+            }
+            Some(DnsUpdate {
+                zone: zone.as_str().into(),
+                dnsname: record.dnsname.as_str().into(),
+                old_ip,
+                new_ip: current_ip,
+            })
+        })
+        .collect()
+}
+
+/// Point the records in `names` at the current public ip
+/// # Errors
+/// Returns error if aws api fails
+pub async fn apply_ddns_updates(
+    route53: &dyn Route53Api,
+    names: &[impl AsRef<str> + Sync],
+) -> Result<Vec<DnsUpdate>, Error> {
+    let current_ip = route53.get_ip_address().await?;
+    let records = route53.list_all_dns_records().await?;
+    let updates = stale_records(&records, names, current_ip);
+    for update in &updates {
+        let name = format_sstr!("{}.", update.dnsname);
+        route53
+            .update_dns_record(&update.zone, &name, update.old_ip, update.new_ip)
+            .await?;
+        info!("ddns updated {update}");
+    }
+    Ok(updates)
+}
+
+/// Point every record in `Config::ddns_records` at the current public ip,
+/// each change is recorded in the audit log and a notification is sent
+/// # Errors
+/// Returns error if aws api or db query fails
+pub async fn update_ddns_records(
+    config: &Config,
+    route53: &dyn Route53Api,
+    ses: &SesInstance,
+    storage: &dyn Storage,
+) -> Result<Vec<DnsUpdate>, Error> {
+    if config.ddns_records.is_empty() {
+        return Ok(Vec::new());
+    }
+    let updates = apply_ddns_updates(route53, &config.ddns_records).await?;
+    for update in &updates {
+        storage
+            .insert_audit_log(&AuditLog::new(
+                "ddns_update",
+                &update.dnsname,
+                Some(format_sstr!("{update}")),
+            ))
+            .await?;
+    }
+    if !updates.is_empty() {
+        let body = updates
+            .iter()
+            .map(|u| format_sstr!("{} {} -> {}", u.dnsname, u.old_ip, u.new_ip))
+            .collect::<Vec<_>>()
+            .join("\n");
+        // the records are already updated, a failed notification is only logged
+        if let Err(e) = send_notification(config, ses, "DNS records updated", &body).await {
+            error!("failed to send ddns notification: {e}");
+        }
+    }
+    Ok(updates)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use std::net::Ipv4Addr;
+
+    use crate::{
+        aws_api::{MockAws, Route53Api},
+        ddns::{apply_ddns_updates, stale_records},
+        route53_instance::{DnsRecord, RecordRouting},
+    };
+
+    fn record(dnsname: &str, ip: &str, routing: Option<RecordRouting>) -> DnsRecord {
+        DnsRecord {
+            dnsname: dnsname.into(),
+            ip: ip.into(),
+            set_identifier: routing.map(|_| "primary".into()),
+            routing,
+            health_check_id: None,
+            record_type: "A".into(),
+            ttl: Some(300),
+            values: vec![ip.into()],
+            alias_target: None,
+        }
+    }
+
+    #[test]
+    fn test_stale_records() {
+        let current_ip = Ipv4Addr::new(192, 0, 2, 10);
+        let records = vec![
+            ("Z1".into(), record("home.example.com", "192.0.2.1", None)),
+            ("Z1".into(), record("up.example.com", "192.0.2.10", None)),
+            ("Z1".into(), record("other.example.com", "192.0.2.1", None)),
+            (
+                "Z1".into(),
+                record(
+                    "failover.example.com",
+                    "192.0.2.1",
+                    Some(RecordRouting::FailoverPrimary),
+                ),
+            ),
+        ];
+        let names = [
+            "home.example.com.",
+            "up.example.com",
+            "failover.example.com",
+        ];
+        let updates = stale_records(&records, &names, current_ip);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].dnsname, "home.example.com");
+        assert_eq!(updates[0].old_ip, Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(
+            updates[0].to_string(),
+            "Z1 home.example.com 192.0.2.1 -> 192.0.2.10"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_ddns_updates() -> Result<(), Error> {
+        let mock = MockAws::from_fixtures()?;
+        let names = ["home.example.com", "www.example.com", "app.example.com"];
+        let updates = apply_ddns_updates(&mock, &names).await?;
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].dnsname, "home.example.com");
+
+        let records = mock.list_all_dns_records().await?;
+        assert!(records
+            .iter()
+            .filter(|(_, r)| r.routing.is_none())
+            .all(|(_, r)| r.ip == "192.0.2.10"));
+        assert!(apply_ddns_updates(&mock, &names).await?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use maplit::hashmap;
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::collections::HashMap;
+
+use crate::{
+    ec2_instance::{Ec2InstanceInfo, InstanceDetails, SpotInstanceRequestInfo},
+    route53_instance::DnsRecord,
+};
+
+/// Cleanup run along with terminating an instance, each step is opt in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecommissionOptions {
+    /// Delete the attached volumes other than the root volume
+    pub delete_volumes: bool,
+    pub delete_dns_records: bool,
+    pub cancel_spot_requests: bool,
+    /// Snapshot the root volume before terminating
+    pub final_snapshot: bool,
+}
+
+/// What refers to an instance and outlives its termination
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DecommissionPlan {
+    pub instance_id: StackString,
+    pub name: Option<StackString>,
+    pub root_volume: Option<StackString>,
+    /// Attached volumes other than the root volume
+    pub volumes: Vec<StackString>,
+    /// Records pointing only at the instance, along with their hosted zone id
+    pub dns_records: Vec<(String, DnsRecord)>,
+    /// Spot requests which launched the instance
+    pub spot_requests: Vec<StackString>,
+}
+
+impl DecommissionPlan {
+    #[must_use]
+    pub fn new(
+        instance: &Ec2InstanceInfo,
+        details: &InstanceDetails,
+        dns_records: &[(String, DnsRecord)],
+        spot_requests: &[SpotInstanceRequestInfo],
+    ) -> Self {
+        let addresses: Vec<&str> = details
+            .public_ip
+            .iter()
+            .chain(details.private_ip.iter())
+            .map(StackString::as_str)
+            .collect();
+        let volumes = instance
+            .volumes
+            .iter()
+            .filter(|v| details.root_volume.as_ref() != Some(*v))
+            .cloned()
+            .collect();
+        let dns_records = dns_records
+            .iter()
+            .filter(|(_, record)| points_at(record, &addresses, &instance.dns_name))
+            .cloned()
+            .collect();
+        let spot_requests = spot_requests
+            .iter()
+            .filter(|s| s.instance_id.as_ref() == Some(&instance.id))
+            .map(|s| s.id.clone())
+            .collect();
+        Self {
+            instance_id: instance.id.clone(),
+            name: instance.tags.get("Name").cloned(),
+            root_volume: details.root_volume.clone(),
+            volumes,
+            dns_records,
+            spot_requests,
+        }
+    }
+
+    /// Tags of the final snapshot of the root volume
+    #[must_use]
+    pub fn snapshot_tags(&self) -> HashMap<StackString, StackString> {
+        let name = self.name.as_ref().unwrap_or(&self.instance_id);
+        hashmap! {
+            "Name".into() => format_sstr!("{name}-final"),
+            "instance-id".into() => self.instance_id.clone(),
+        }
+    }
+
+    /// One line per change `options` will make, in the order they are made
+    #[must_use]
+    pub fn steps(&self, options: DecommissionOptions) -> Vec<StackString> {
+        let mut steps = Vec::new();
+        if options.final_snapshot {
+            if let Some(root_volume) = &self.root_volume {
+                steps.push(format_sstr!("snapshot root volume {root_volume}"));
+            }
+        }
+        if options.cancel_spot_requests {
+            for spot_request in &self.spot_requests {
+                steps.push(format_sstr!("cancel spot request {spot_request}"));
+            }
+        }
+        match &self.name {
+            Some(name) => steps.push(format_sstr!("terminate {} ({name})", self.instance_id)),
+            None => steps.push(format_sstr!("terminate {}", self.instance_id)),
+        }
+        if options.delete_dns_records {
+            for (_, record) in &self.dns_records {
+                steps.push(format_sstr!(
+                    "delete {} record {} -> {}",
+                    record.record_type,
+                    record.dnsname,
+                    record.values.join(" ")
+                ));
+            }
+        }
+        if options.delete_volumes {
+            for volume in &self.volumes {
+                steps.push(format_sstr!("delete volume {volume}"));
+            }
+        }
+        steps
+    }
+}
+
+/// `A` and `AAAA` records whose every value is one of `addresses`, and
+/// `CNAME` records pointing at the instance's public dns name. Records which
+/// also point elsewhere are left alone.
+fn points_at(record: &DnsRecord, addresses: &[&str], dns_name: &str) -> bool {
+    if record.values.is_empty() {
+        return false;
+    }
+    match record.record_type.as_str() {
+        "A" | "AAAA" => record
+            .values
+            .iter()
+            .all(|v| addresses.contains(&v.as_str())),
+        "CNAME" => {
+            !dns_name.is_empty()
+                && record
+                    .values
+                    .iter()
+                    .all(|v| v.trim_end_matches('.') == dns_name)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+
+    use crate::{
+        decommission::{DecommissionOptions, DecommissionPlan},
+        ec2_instance::{Ec2InstanceInfo, InstanceDetails, SpotInstanceRequestInfo},
+        route53_instance::DnsRecord,
+    };
+
+    fn record(dnsname: &str, record_type: &str, values: &[&str]) -> (String, DnsRecord) {
+        let values: Vec<String> = values.iter().map(|v| (*v).into()).collect();
+        let record = DnsRecord {
+            dnsname: dnsname.into(),
+            ip: values.first().cloned().unwrap_or_default(),
+            set_identifier: None,
+            routing: None,
+            health_check_id: None,
+            record_type: record_type.into(),
+            ttl: Some(300),
+            values,
+            alias_target: None,
+        };
+        ("Z0123456789".into(), record)
+    }
+
+    #[test]
+    fn test_decommission_plan() {
+        let instance = Ec2InstanceInfo {
+            id: "i-0001".into(),
+            dns_name: "ec2-1-2-3-4.compute-1.amazonaws.com".into(),
+            state: "running".into(),
+            tags: hashmap! {"Name".into() => "build".into()},
+            volumes: vec!["vol-root".into(), "vol-data".into()],
+            public_ip: Some("1.2.3.4".into()),
+            private_ip: Some("10.0.0.5".into()),
+            ..Ec2InstanceInfo::default()
+        };
+        let details = InstanceDetails {
+            root_volume: Some("vol-root".into()),
+            public_ip: Some("1.2.3.4".into()),
+            private_ip: Some("10.0.0.5".into()),
+        };
+        let dns_records = [
+            record("build.example.com", "A", &["1.2.3.4"]),
+            record("internal.example.com", "A", &["10.0.0.5"]),
+            record("pool.example.com", "A", &["1.2.3.4", "5.6.7.8"]),
+            record(
+                "alias.example.com",
+                "CNAME",
+                &["ec2-1-2-3-4.compute-1.amazonaws.com."],
+            ),
+            record("txt.example.com", "TXT", &["1.2.3.4"]),
+        ];
+        let spot = |id: &str, instance_id: &str| SpotInstanceRequestInfo {
+            id: id.into(),
+            instance_id: Some(instance_id.into()),
+            ..SpotInstanceRequestInfo::default()
+        };
+        let spot_requests = [spot("sir-0001", "i-0001"), spot("sir-0002", "i-0002")];
+        let plan = DecommissionPlan::new(&instance, &details, &dns_records, &spot_requests);
+
+        assert_eq!(plan.volumes, vec!["vol-data"]);
+        let names: Vec<_> = plan.dns_records.iter().map(|(_, r)| &r.dnsname).collect();
+        assert_eq!(
+            names,
+            vec![
+                "build.example.com",
+                "internal.example.com",
+                "alias.example.com"
+            ]
+        );
+        assert_eq!(plan.spot_requests, vec!["sir-0001"]);
+        assert_eq!(plan.snapshot_tags()["Name"], "build-final");
+
+        assert_eq!(
+            plan.steps(DecommissionOptions::default()),
+            vec!["terminate i-0001 (build)"]
+        );
+        let options = DecommissionOptions {
+            delete_volumes: true,
+            delete_dns_records: true,
+            cancel_spot_requests: true,
+            final_snapshot: true,
+        };
+        assert_eq!(
+            plan.steps(options),
+            vec![
+                "snapshot root volume vol-root",
+                "cancel spot request sir-0001",
+                "terminate i-0001 (build)",
+                "delete A record build.example.com -> 1.2.3.4",
+                "delete A record internal.example.com -> 10.0.0.5",
+                "delete CNAME record alias.example.com -> ec2-1-2-3-4.compute-1.amazonaws.com.",
+                "delete volume vol-data",
+            ]
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::{format_err, Error};
+use bollard::{
+    auth::DockerCredentials,
+    container::{
+        ListContainersOptions, LogsOptions, RestartContainerOptions, StartContainerOptions,
+        StopContainerOptions,
+    },
+    image::CreateImageOptions,
+    models::{ContainerSummary, Port, PortTypeEnum},
+    Docker, API_DEFAULT_VERSION,
+};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::{fmt, path::PathBuf, str::FromStr};
+use time::OffsetDateTime;
+
+use crate::config::Config;
+
+/// Seconds a docker api call may take, pulls included
+const DOCKER_TIMEOUT: u64 = 300;
+
+#[derive(Clone)]
+pub struct DockerInstance {
+    socket: PathBuf,
+}
+
+impl fmt::Debug for DockerInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("DockerInstance")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContainerAction {
+    #[serde(rename = "start")]
+    Start,
+    #[serde(rename = "stop")]
+    Stop,
+    #[serde(rename = "restart")]
+    Restart,
+}
+
+impl ContainerAction {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Start => "start",
+            Self::Stop => "stop",
+            Self::Restart => "restart",
+        }
+    }
+}
+
+impl fmt::Display for ContainerAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for ContainerAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(Self::Start),
+            "stop" => Ok(Self::Stop),
+            "restart" => Ok(Self::Restart),
+            _ => Err(format_err!("{s} is not a ContainerAction")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ContainerInfo {
+    pub id: StackString,
+    pub name: StackString,
+    pub image: StackString,
+    pub state: StackString,
+    pub status: StackString,
+    pub ports: Vec<StackString>,
+    pub created: Option<OffsetDateTime>,
+}
+
+impl ContainerInfo {
+    fn from_summary(summary: ContainerSummary) -> Option<Self> {
+        let id: StackString = summary.id?.into();
+        // names are reported with a leading slash, fall back to the short id
+        let name = summary.names.unwrap_or_default().first().map_or_else(
+            || id.chars().take(12).collect::<String>().into(),
+            |n| n.trim_start_matches('/').into(),
+        );
+        let mut ports: Vec<StackString> = summary
+            .ports
+            .unwrap_or_default()
+            .iter()
+            .map(format_port)
+            .collect();
+        ports.sort();
+        ports.dedup();
+        Some(Self {
+            id,
+            name,
+            image: summary.image.unwrap_or_default().into(),
+            state: summary.state.unwrap_or_default().into(),
+            status: summary.status.unwrap_or_default().into(),
+            ports,
+            created: summary
+                .created
+                .and_then(|c| OffsetDateTime::from_unix_timestamp(c).ok()),
+        })
+    }
+
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.state == "running"
+    }
+}
+
+/// `0.0.0.0:8080->80/tcp` for published ports, `80/tcp` otherwise
+fn format_port(port: &Port) -> StackString {
+    let protocol = match port.typ {
+        Some(PortTypeEnum::UDP) => "udp",
+        Some(PortTypeEnum::SCTP) => "sctp",
+        _ => "tcp",
+    };
+    match port.public_port {
+        Some(public_port) => format_sstr!(
+            "{}:{public_port}->{}/{protocol}",
+            port.ip.as_deref().unwrap_or("0.0.0.0"),
+            port.private_port
+        ),
+        None => format_sstr!("{}/{protocol}", port.private_port),
+    }
+}
+
+impl DockerInstance {
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        Self {
+            socket: config.docker_socket.clone(),
+        }
+    }
+
+    /// Whether the docker socket exists, listing is skipped on hosts without
+    /// docker
+    #[must_use]
+    pub fn is_available(&self) -> bool {
+        self.socket.exists()
+    }
+
+    fn client(&self) -> Result<Docker, Error> {
+        let socket = self
+            .socket
+            .to_str()
+            .ok_or_else(|| format_err!("invalid docker socket {}", self.socket.display()))?;
+        Docker::connect_with_socket(socket, DOCKER_TIMEOUT, API_DEFAULT_VERSION).map_err(Into::into)
+    }
+
+    /// All containers, running or not, an empty list when docker isn't
+    /// installed
+    /// # Errors
+    /// Returns error if docker api call fails
+    pub async fn list_containers(&self) -> Result<Vec<ContainerInfo>, Error> {
+        if !self.is_available() {
+            return Ok(Vec::new());
+        }
+        let options = ListContainersOptions::<String> {
+            all: true,
+            ..ListContainersOptions::default()
+        };
+        let mut containers: Vec<_> = self
+            .client()?
+            .list_containers(Some(options))
+            .await?
+            .into_iter()
+            .filter_map(ContainerInfo::from_summary)
+            .collect();
+        containers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(containers)
+    }
+
+    /// # Errors
+    /// Returns error if docker api call fails
+    pub async fn container_action(
+        &self,
+        action: ContainerAction,
+        container: &str,
+    ) -> Result<(), Error> {
+        let docker = self.client()?;
+        match action {
+            ContainerAction::Start => {
+                docker
+                    .start_container(container, None::<StartContainerOptions<String>>)
+                    .await?;
+            }
+            ContainerAction::Stop => {
+                docker
+                    .stop_container(container, None::<StopContainerOptions>)
+                    .await?;
+            }
+            ContainerAction::Restart => {
+                docker
+                    .restart_container(container, None::<RestartContainerOptions>)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Last `tail` lines of stdout and stderr, oldest first
+    /// # Errors
+    /// Returns error if docker api call fails
+    pub async fn container_logs(
+        &self,
+        container: &str,
+        tail: usize,
+    ) -> Result<Vec<StackString>, Error> {
+        let options = LogsOptions::<String> {
+            stdout: true,
+            stderr: true,
+            timestamps: true,
+            tail: format!("{tail}"),
+            ..LogsOptions::default()
+        };
+        self.client()?
+            .logs(container, Some(options))
+            .map_ok(|line| StackString::from(line.to_string().trim_end()))
+            .try_collect()
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Pull `image` (`repository:tag`), with `credentials` for private
+    /// registries such as ecr. Returns the final status reported by docker.
+    /// # Errors
+    /// Returns error if docker api call fails
+    pub async fn pull_image(
+        &self,
+        image: &str,
+        credentials: Option<DockerCredentials>,
+    ) -> Result<StackString, Error> {
+        let options = CreateImageOptions {
+            from_image: image,
+            ..CreateImageOptions::default()
+        };
+        let statuses: Vec<_> = self
+            .client()?
+            .create_image(Some(options), None, credentials)
+            .try_collect()
+            .await?;
+        Ok(statuses
+            .into_iter()
+            .filter_map(|s| s.status)
+            .last()
+            .map_or_else(|| format_sstr!("pulled {image}"), Into::into))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use bollard::models::{ContainerSummary, Port, PortTypeEnum};
+
+    use crate::docker_instance::{format_port, ContainerAction, ContainerInfo};
+
+    #[test]
+    fn test_format_port() {
+        let port = Port {
+            ip: Some("0.0.0.0".into()),
+            private_port: 80,
+            public_port: Some(8080),
+            typ: Some(PortTypeEnum::TCP),
+        };
+        assert_eq!(format_port(&port), "0.0.0.0:8080->80/tcp");
+        let port = Port {
+            ip: None,
+            private_port: 53,
+            public_port: None,
+            typ: Some(PortTypeEnum::UDP),
+        };
+        assert_eq!(format_port(&port), "53/udp");
+    }
+
+    #[test]
+    fn test_container_info() -> Result<(), Error> {
+        let summary = ContainerSummary {
+            id: Some("4f66ad9a0b2e1c3d5e7f".into()),
+            names: Some(vec!["/novnc".into()]),
+            image: Some("123456789012.dkr.ecr.us-east-1.amazonaws.com/novnc:latest".into()),
+            state: Some("running".into()),
+            status: Some("Up 2 hours".into()),
+            created: Some(1_717_556_400),
+            ..ContainerSummary::default()
+        };
+        let container = ContainerInfo::from_summary(summary).unwrap();
+        assert_eq!(container.name, "novnc");
+        assert!(container.is_running());
+        assert_eq!(
+            container.created.map(|c| c.unix_timestamp()),
+            Some(1_717_556_400)
+        );
+
+        let summary = ContainerSummary {
+            id: Some("4f66ad9a0b2e1c3d5e7f".into()),
+            ..ContainerSummary::default()
+        };
+        let container = ContainerInfo::from_summary(summary).unwrap();
+        assert_eq!(container.name, "4f66ad9a0b2e");
+        assert!(!container.is_running());
+
+        assert_eq!(
+            "restart".parse::<ContainerAction>()?,
+            ContainerAction::Restart
+        );
+        assert!("kill".parse::<ContainerAction>().is_err());
+        Ok(())
+    }
+}
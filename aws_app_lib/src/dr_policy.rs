@@ -0,0 +1,402 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::Serialize;
+use stack_string::StackString;
+use std::fmt;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    ec2_instance::{SnapshotInfo, VolumeInfo},
+    pgpool::PgPool,
+};
+
+/// Tag on a disaster recovery copy naming the volume it protects
+pub const DR_SOURCE_VOLUME_TAG: &str = "dr-source-volume";
+/// Tag on a disaster recovery copy naming the snapshot it was copied from
+pub const DR_SOURCE_SNAPSHOT_TAG: &str = "dr-source-snapshot";
+
+/// Volumes whose `Name` tag is `volume_name` must have a snapshot copy in
+/// `target_region` no older than `max_age_hours`
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Serialize)]
+pub struct DrPolicy {
+    pub id: Uuid,
+    pub volume_name: StackString,
+    pub target_region: StackString,
+    pub encrypted: bool,
+    /// Key the copies are encrypted with, the account's default ebs key if
+    /// unset
+    pub kms_key_id: Option<StackString>,
+    pub max_age_hours: i32,
+    pub created_at: OffsetDateTime,
+}
+
+impl fmt::Display for DrPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} copied to {} every {} hours",
+            self.volume_name, self.target_region, self.max_age_hours
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum DrStatus {
+    /// A completed copy is recent enough
+    Compliant,
+    /// A copy is in progress
+    Copying,
+    /// The newest copy is older than the policy allows
+    Stale,
+    /// The volume has never been copied
+    Missing,
+    /// No volume has the policy's `Name` tag
+    NoVolume,
+}
+
+impl DrStatus {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Compliant => "compliant",
+            Self::Copying => "copying",
+            Self::Stale => "stale",
+            Self::Missing => "missing",
+            Self::NoVolume => "no volume",
+        }
+    }
+
+    #[must_use]
+    pub fn is_compliant(self) -> bool {
+        matches!(self, Self::Compliant | Self::Copying)
+    }
+}
+
+impl fmt::Display for DrStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+/// What enforcing a policy does next for a volume lacking a recent copy
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum DrAction {
+    /// Copy this completed snapshot to the target region
+    CopySnapshot(StackString),
+    /// No recent snapshot to copy, take one first
+    CreateSnapshot,
+}
+
+/// Off-region copy state of one volume covered by a policy
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DrCompliance {
+    pub policy_id: Uuid,
+    pub volume_name: StackString,
+    pub volume_id: Option<StackString>,
+    pub target_region: StackString,
+    pub status: DrStatus,
+    /// Newest copy in the target region
+    pub latest_copy: Option<StackString>,
+    pub copied_at: Option<OffsetDateTime>,
+    pub action: Option<DrAction>,
+}
+
+fn newest<'a>(snapshots: impl Iterator<Item = &'a SnapshotInfo>) -> Option<&'a SnapshotInfo> {
+    snapshots.max_by_key(|s| s.start_time)
+}
+
+impl DrPolicy {
+    /// # Errors
+    /// Returns error if `volume_name` or `target_region` are empty or
+    /// `max_age_hours` isn't positive
+    pub fn new(
+        volume_name: &str,
+        target_region: &str,
+        encrypted: bool,
+        kms_key_id: Option<StackString>,
+        max_age_hours: i32,
+    ) -> Result<Self, Error> {
+        if volume_name.trim().is_empty() {
+            return Err(format_err!("dr policy needs a volume name"));
+        }
+        if target_region.trim().is_empty() {
+            return Err(format_err!("dr policy needs a target region"));
+        }
+        if max_age_hours < 1 {
+            return Err(format_err!("max_age_hours must be greater than zero"));
+        }
+        Ok(Self {
+            id: Uuid::new_v4(),
+            volume_name: volume_name.trim().into(),
+            target_region: target_region.trim().into(),
+            encrypted,
+            kms_key_id: kms_key_id.filter(|k| !k.is_empty()),
+            max_age_hours,
+            created_at: OffsetDateTime::now_utc(),
+        })
+    }
+
+    /// Copy state of every volume named `volume_name` given the `snapshots`
+    /// of the source region and the `copies` in the target region, one
+    /// `NoVolume` entry if there are none
+    #[must_use]
+    pub fn evaluate(
+        &self,
+        volumes: &[VolumeInfo],
+        snapshots: &[SnapshotInfo],
+        copies: &[SnapshotInfo],
+        now: OffsetDateTime,
+    ) -> Vec<DrCompliance> {
+        let cutoff = now - Duration::hours(self.max_age_hours.into());
+        let entries: Vec<_> = volumes
+            .iter()
+            .filter(|v| v.tags.get("Name") == Some(&self.volume_name))
+            .map(|volume| {
+                let volume_copies: Vec<_> = copies
+                    .iter()
+                    .filter(|c| c.tags.get(DR_SOURCE_VOLUME_TAG) == Some(&volume.id))
+                    .collect();
+                let latest_copy = newest(volume_copies.iter().copied());
+                let is_recent = |s: &SnapshotInfo| s.start_time.map_or(false, |t| t >= cutoff);
+                let status = if volume_copies
+                    .iter()
+                    .any(|c| c.state == "completed" && is_recent(c))
+                {
+                    DrStatus::Compliant
+                } else if volume_copies.iter().any(|c| c.state == "pending") {
+                    DrStatus::Copying
+                } else if volume_copies.is_empty() {
+                    DrStatus::Missing
+                } else {
+                    DrStatus::Stale
+                };
+                let action = if status.is_compliant() {
+                    None
+                } else {
+                    let volume_snapshots: Vec<_> = snapshots
+                        .iter()
+                        .filter(|s| s.volume_id.as_ref() == Some(&volume.id))
+                        .collect();
+                    if volume_snapshots.iter().any(|s| s.state == "pending") {
+                        // wait for the snapshot in progress
+                        None
+                    } else {
+                        match newest(
+                            volume_snapshots
+                                .iter()
+                                .copied()
+                                .filter(|s| s.state == "completed" && is_recent(s)),
+                        ) {
+                            Some(snapshot) => Some(DrAction::CopySnapshot(snapshot.id.clone())),
+                            None => Some(DrAction::CreateSnapshot),
+                        }
+                    }
+                };
+                DrCompliance {
+                    policy_id: self.id,
+                    volume_name: self.volume_name.clone(),
+                    volume_id: Some(volume.id.clone()),
+                    target_region: self.target_region.clone(),
+                    status,
+                    latest_copy: latest_copy.map(|c| c.id.clone()),
+                    copied_at: latest_copy.and_then(|c| c.start_time),
+                    action,
+                }
+            })
+            .collect();
+        if entries.is_empty() {
+            vec![DrCompliance {
+                policy_id: self.id,
+                volume_name: self.volume_name.clone(),
+                volume_id: None,
+                target_region: self.target_region.clone(),
+                status: DrStatus::NoVolume,
+                latest_copy: None,
+                copied_at: None,
+                action: None,
+            }]
+        } else {
+            entries
+        }
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM dr_policies ORDER BY volume_name, target_region");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO dr_policies (
+                    id, volume_name, target_region, encrypted, kms_key_id, max_age_hours,
+                    created_at
+                ) VALUES (
+                    $id, $volume_name, $target_region, $encrypted, $kms_key_id,
+                    $max_age_hours, $created_at
+                )
+            ",
+            id = self.id,
+            volume_name = self.volume_name,
+            target_region = self.target_region,
+            encrypted = self.encrypted,
+            kms_key_id = self.kms_key_id,
+            max_age_hours = self.max_age_hours,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn delete_entry(pool: &PgPool, id: Uuid) -> Result<u64, Error> {
+        let query = query!("DELETE FROM dr_policies WHERE id = $id", id = id);
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::hashmap;
+    use time::{macros::datetime, Duration, OffsetDateTime};
+
+    use crate::{
+        dr_policy::{DrAction, DrPolicy, DrStatus, DR_SOURCE_VOLUME_TAG},
+        ec2_instance::{SnapshotInfo, VolumeInfo},
+    };
+
+    fn snapshot(
+        id: &str,
+        volume_id: &str,
+        state: &str,
+        start_time: OffsetDateTime,
+    ) -> SnapshotInfo {
+        SnapshotInfo {
+            id: id.into(),
+            volume_id: Some(volume_id.into()),
+            state: state.into(),
+            start_time: Some(start_time),
+            ..SnapshotInfo::default()
+        }
+    }
+
+    fn copy(id: &str, volume_id: &str, state: &str, start_time: OffsetDateTime) -> SnapshotInfo {
+        SnapshotInfo {
+            tags: hashmap! {DR_SOURCE_VOLUME_TAG.into() => volume_id.into()},
+            ..snapshot(id, "vol-ffffffff", state, start_time)
+        }
+    }
+
+    #[test]
+    fn test_new_dr_policy() -> Result<(), Error> {
+        let policy = DrPolicy::new(" db-data ", "us-west-2", true, Some("".into()), 24)?;
+        assert_eq!(policy.volume_name, "db-data");
+        assert_eq!(policy.kms_key_id, None);
+        assert_eq!(
+            policy.to_string(),
+            "db-data copied to us-west-2 every 24 hours"
+        );
+        assert!(DrPolicy::new("", "us-west-2", true, None, 24).is_err());
+        assert!(DrPolicy::new("db-data", " ", true, None, 24).is_err());
+        assert!(DrPolicy::new("db-data", "us-west-2", true, None, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate() -> Result<(), Error> {
+        let now = datetime!(2024-06-02 00:00 UTC);
+        let policy = DrPolicy::new("db-data", "us-west-2", false, None, 24)?;
+        let volume = |id: &str| VolumeInfo {
+            id: id.into(),
+            tags: hashmap! {"Name".into() => "db-data".into()},
+            ..VolumeInfo::default()
+        };
+        let volumes = [
+            volume("vol-fresh"),
+            volume("vol-copying"),
+            volume("vol-stale"),
+            volume("vol-new"),
+            volume("vol-unsnapped"),
+            VolumeInfo {
+                id: "vol-other".into(),
+                ..VolumeInfo::default()
+            },
+        ];
+        let snapshots = [
+            snapshot(
+                "snap-stale",
+                "vol-stale",
+                "completed",
+                now - Duration::hours(2),
+            ),
+            snapshot("snap-new", "vol-new", "completed", now - Duration::hours(1)),
+            snapshot(
+                "snap-old",
+                "vol-unsnapped",
+                "completed",
+                now - Duration::days(3),
+            ),
+        ];
+        let copies = [
+            copy(
+                "snap-c1",
+                "vol-fresh",
+                "completed",
+                now - Duration::hours(3),
+            ),
+            copy("snap-c2", "vol-fresh", "completed", now - Duration::days(2)),
+            copy(
+                "snap-c3",
+                "vol-copying",
+                "pending",
+                now - Duration::hours(1),
+            ),
+            copy("snap-c4", "vol-stale", "completed", now - Duration::days(2)),
+        ];
+        let entries = policy.evaluate(&volumes, &snapshots, &copies, now);
+        let statuses: Vec<_> = entries
+            .iter()
+            .map(|e| (e.volume_id.as_deref().unwrap(), e.status, e.action.clone()))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("vol-fresh", DrStatus::Compliant, None),
+                ("vol-copying", DrStatus::Copying, None),
+                (
+                    "vol-stale",
+                    DrStatus::Stale,
+                    Some(DrAction::CopySnapshot("snap-stale".into()))
+                ),
+                (
+                    "vol-new",
+                    DrStatus::Missing,
+                    Some(DrAction::CopySnapshot("snap-new".into()))
+                ),
+                (
+                    "vol-unsnapped",
+                    DrStatus::Missing,
+                    Some(DrAction::CreateSnapshot)
+                ),
+            ]
+        );
+        assert_eq!(entries[0].latest_copy.as_deref(), Some("snap-c1"));
+        assert_eq!(entries[0].copied_at, Some(now - Duration::hours(3)));
+
+        let entries = policy.evaluate(&[], &snapshots, &copies, now);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].status, DrStatus::NoVolume);
+        assert!(!entries[0].status.is_compliant());
+        Ok(())
+    }
+}
@@ -1,33 +1,122 @@
 use anyhow::{format_err, Error};
 use aws_config::SdkConfig;
 use aws_sdk_ec2::{
-    primitives::DateTime,
+    primitives::{Blob, DateTime},
     types::{
-        Filter, InstanceType, RequestSpotLaunchSpecification, ResourceType, Tag, TagSpecification,
-        VolumeType,
+        BlockDeviceMapping, EbsBlockDevice, Filter, HttpTokensState,
+        IamInstanceProfileSpecification, InstanceMetadataEndpointState,
+        InstanceMetadataOptionsRequest, InstanceType, LocationType, Placement,
+        RequestSpotLaunchSpecification, ResourceType, ShutdownBehavior as Ec2ShutdownBehavior,
+        SpotPlacement, Tag, TagSpecification, Tenancy, VolumeType,
     },
     Client as Ec2Client,
 };
 use aws_types::region::Region;
-use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD},
+    Engine,
+};
+use futures::{
+    stream::{self, Stream},
+    TryStreamExt,
+};
 use itertools::Itertools;
 use log::debug;
 use maplit::hashmap;
 use serde::{Deserialize, Serialize};
 use stack_string::{format_sstr, StackString};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt,
     fs::read_to_string,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 use time::{Duration, OffsetDateTime, UtcOffset};
 use tokio::{task::spawn, time::sleep};
 
-use crate::{config::Config, date_time_wrapper::DateTimeWrapper};
+use crate::{config::Config, date_time_wrapper::DateTimeWrapper, instance_filter::InstanceFilter};
 
 static UBUNTU_OWNER: &str = "099720109477";
 
+/// Ubuntu LTS releases offered when building a spot request
+pub const UBUNTU_RELEASES: [&str; 2] = ["jammy-22.04", "noble-24.04"];
+
+pub const UBUNTU_ARCHES: [&str; 2] = ["amd64", "arm64"];
+
+/// Canonical's published images of one Ubuntu release and architecture
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UbuntuImageQuery {
+    /// `codename-version`, e.g. `noble-24.04`
+    pub release: StackString,
+    pub arch: StackString,
+    /// Minimal cloud images instead of the server images
+    pub minimal: bool,
+}
+
+impl UbuntuImageQuery {
+    #[must_use]
+    pub fn new(release: impl Into<StackString>, arch: impl Into<StackString>) -> Self {
+        Self {
+            release: release.into(),
+            arch: arch.into(),
+            minimal: false,
+        }
+    }
+
+    /// # Errors
+    /// Returns error if release isn't `codename-version` or arch isn't
+    /// `amd64` or `arm64`
+    pub fn validate(&self) -> Result<(), Error> {
+        let valid_release = self
+            .release
+            .split_once('-')
+            .map_or(false, |(codename, version)| {
+                !codename.is_empty()
+                    && codename.chars().all(|c| c.is_ascii_lowercase())
+                    && version.split_once('.').map_or(false, |(major, minor)| {
+                        [major, minor]
+                            .iter()
+                            .all(|v| v.len() == 2 && v.chars().all(|c| c.is_ascii_digit()))
+                    })
+            });
+        if !valid_release {
+            return Err(format_err!(
+                "{} is not an ubuntu release like noble-24.04",
+                self.release
+            ));
+        }
+        if !UBUNTU_ARCHES.contains(&self.arch.as_str()) {
+            return Err(format_err!("{} is not one of amd64, arm64", self.arch));
+        }
+        Ok(())
+    }
+
+    /// Pattern the `name` filter of `describe_images` matches against
+    #[must_use]
+    pub fn name_pattern(&self) -> StackString {
+        let Self {
+            release,
+            arch,
+            minimal,
+        } = self;
+        if *minimal {
+            format_sstr!("ubuntu-minimal/images/hvm-ssd*/ubuntu-{release}-{arch}-minimal-*")
+        } else {
+            format_sstr!("ubuntu/images/hvm-ssd*/ubuntu-{release}-{arch}-server*")
+        }
+    }
+}
+
+/// Volume types that can be attached at launch
+const LAUNCH_VOLUME_TYPES: [&str; 7] = ["gp2", "gp3", "io1", "io2", "st1", "sc1", "standard"];
+
+/// Volume types whose iops can be provisioned
+const PROVISIONED_IOPS_VOLUME_TYPES: [&str; 3] = ["gp3", "io1", "io2"];
+
+/// Instances requested per `describe_instances` call (ec2 allows 5 to 1000)
+const INSTANCE_PAGE_SIZE: i32 = 200;
+
 #[derive(Clone)]
 pub struct Ec2Instance {
     ec2_client: Ec2Client,
@@ -60,11 +149,16 @@ impl Ec2Instance {
         let region: String = region.as_ref().into();
         let region = Region::new(region);
         self.region = region.clone();
-        let sdk_config = aws_config::from_env().region(region).load().await;
-        self.ec2_client = Ec2Client::from_conf((&sdk_config).into());
+        let conf = self.ec2_client.config().to_builder().region(region).build();
+        self.ec2_client = Ec2Client::from_conf(conf);
         Ok(())
     }
 
+    #[must_use]
+    pub fn region(&self) -> &str {
+        self.region.as_ref()
+    }
+
     pub fn set_owner_id(&mut self, owner_id: impl Into<StackString>) -> Option<StackString> {
         self.my_owner_id.replace(owner_id.into())
     }
@@ -99,28 +193,31 @@ impl Ec2Instance {
                                     block.ebs.and_then(|b| b.snapshot_id.map(Into::into))
                                 })
                                 .collect(),
+                            creation_date: image.creation_date.map(Into::into),
+                            tags: image
+                                .tags
+                                .unwrap_or_default()
+                                .into_iter()
+                                .filter_map(|tag| Some((tag.key?.into(), tag.value?.into())))
+                                .collect(),
                         })
                     })
             })
             .map_err(Into::into)
     }
 
+    /// Images Canonical has published matching `query`, most recently
+    /// published first
     /// # Errors
     /// Returns error if aws api call fails
-    pub async fn get_latest_ubuntu_ami(
-        &self,
-        ubuntu_release: impl fmt::Display,
-        arch: impl fmt::Display,
-    ) -> Result<Option<AmiInfo>, Error> {
+    pub async fn get_ubuntu_images(&self, query: &UbuntuImageQuery) -> Result<Vec<AmiInfo>, Error> {
         let owner_filter = Filter::builder()
             .name("owner-id")
             .values(UBUNTU_OWNER)
             .build();
         let name_filter = Filter::builder()
             .name("name")
-            .values(format_sstr!(
-                "ubuntu/images/hvm-ssd*/ubuntu-{ubuntu_release}-{arch}-server*"
-            ))
+            .values(query.name_pattern())
             .build();
         let resp = self
             .ec2_client
@@ -130,7 +227,7 @@ impl Ec2Instance {
             .send()
             .await?;
 
-        let image = resp
+        let mut images: Vec<_> = resp
             .images
             .unwrap_or_default()
             .into_iter()
@@ -144,13 +241,26 @@ impl Ec2Instance {
                         .into_iter()
                         .filter_map(|block| block.ebs.and_then(|b| b.snapshot_id.map(Into::into)))
                         .collect(),
+                    creation_date: image.creation_date.map(Into::into),
+                    tags: HashMap::new(),
                 })
             })
-            .minmax_by(|x, y| x.name.cmp(&y.name))
-            .into_option()
-            .map(|(_, x)| x);
+            .collect();
+        // creation dates are rfc3339 strings, which sort chronologically
+        images.sort_by(|x, y| (&y.creation_date, &y.name).cmp(&(&x.creation_date, &x.name)));
+        Ok(images)
+    }
 
-        Ok(image)
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_latest_ubuntu_ami(
+        &self,
+        ubuntu_release: impl fmt::Display,
+        arch: impl fmt::Display,
+    ) -> Result<Option<AmiInfo>, Error> {
+        let query = UbuntuImageQuery::new(format_sstr!("{ubuntu_release}"), format_sstr!("{arch}"));
+        let images = self.get_ubuntu_images(&query).await?;
+        Ok(images.into_iter().next())
     }
 
     /// # Errors
@@ -212,57 +322,124 @@ impl Ec2Instance {
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn get_all_instances(&self) -> Result<impl Iterator<Item = Ec2InstanceInfo>, Error> {
-        self.ec2_client
+        let filter = InstanceFilter::default();
+        let instances: Vec<_> = self.get_instances_stream(&filter).try_concat().await?;
+        Ok(instances.into_iter())
+    }
+
+    /// Single page of `describe_instances` with the ec2 side of `filter`
+    /// applied, pass the returned token to get the next page
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_instances_page(
+        &self,
+        filter: &InstanceFilter,
+        next_token: Option<StackString>,
+    ) -> Result<InstancePage, Error> {
+        let result = self
+            .ec2_client
             .describe_instances()
+            .set_filters(Some(filter.ec2_filters()))
+            .max_results(INSTANCE_PAGE_SIZE)
+            .set_next_token(next_token.map(Into::into))
             .send()
-            .await
-            .map(|instances| {
-                instances
-                    .reservations
+            .await?;
+        let instances = result
+            .reservations
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|res| res.instances)
+            .flatten()
+            .filter_map(|inst| {
+                let tags: HashMap<_, _> = inst
+                    .tags
                     .unwrap_or_default()
                     .into_iter()
-                    .filter_map(|res| {
-                        res.instances.map(|instances| {
-                            instances.into_iter().filter_map(|inst| {
-                                let tags: HashMap<_, _> = inst
-                                    .tags
-                                    .unwrap_or_default()
-                                    .into_iter()
-                                    .filter_map(|tag| Some((tag.key?.into(), tag.value?.into())))
-                                    .collect();
-                                let volumes = inst
-                                    .block_device_mappings
-                                    .unwrap_or_default()
-                                    .into_iter()
-                                    .filter_map(|bm| {
-                                        let ebs = bm.ebs?.volume_id?;
-                                        Some(ebs.into())
-                                    })
-                                    .collect();
-                                Some(Ec2InstanceInfo {
-                                    id: inst.instance_id?.into(),
-                                    dns_name: inst.public_dns_name?.into(),
-                                    state: inst.state?.name?.as_str().into(),
-                                    instance_type: inst.instance_type?.as_str().into(),
-                                    availability_zone: inst.placement?.availability_zone?.into(),
-                                    launch_time: inst
-                                        .launch_time
-                                        .and_then(|t| {
-                                            OffsetDateTime::from_unix_timestamp(
-                                                t.as_secs_f64() as i64
-                                            )
-                                            .ok()
-                                        })
-                                        .map(|t| t.to_offset(UtcOffset::UTC).into())?,
-                                    tags,
-                                    volumes,
-                                })
-                            })
-                        })
+                    .filter_map(|tag| Some((tag.key?.into(), tag.value?.into())))
+                    .collect();
+                let volumes = inst
+                    .block_device_mappings
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|bm| {
+                        let ebs = bm.ebs?.volume_id?;
+                        Some(ebs.into())
                     })
-                    .flatten()
+                    .collect();
+                Some(Ec2InstanceInfo {
+                    id: inst.instance_id?.into(),
+                    dns_name: inst.public_dns_name?.into(),
+                    state: inst.state?.name?.as_str().into(),
+                    instance_type: inst.instance_type?.as_str().into(),
+                    availability_zone: inst.placement?.availability_zone?.into(),
+                    launch_time: inst
+                        .launch_time
+                        .and_then(|t| {
+                            OffsetDateTime::from_unix_timestamp(t.as_secs_f64() as i64).ok()
+                        })
+                        .map(|t| t.to_offset(UtcOffset::UTC).into())?,
+                    tags,
+                    volumes,
+                    public_ip: inst.public_ip_address.map(Into::into),
+                    private_ip: inst.private_ip_address.map(Into::into),
+                })
             })
-            .map_err(Into::into)
+            .collect();
+        Ok(InstancePage {
+            instances,
+            next_token: result.next_token.filter(|t| !t.is_empty()).map(Into::into),
+        })
+    }
+
+    /// Root volume and addresses of `instance_id`
+    /// # Errors
+    /// Returns error if aws api call fails or the instance doesn't exist
+    pub async fn get_instance_details(&self, instance_id: &str) -> Result<InstanceDetails, Error> {
+        let instance = self
+            .ec2_client
+            .describe_instances()
+            .instance_ids(instance_id)
+            .send()
+            .await?
+            .reservations
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|res| res.instances)
+            .flatten()
+            .next()
+            .ok_or_else(|| format_err!("instance {instance_id} not found"))?;
+        let root_device = instance.root_device_name.unwrap_or_default();
+        let root_volume = instance
+            .block_device_mappings
+            .unwrap_or_default()
+            .into_iter()
+            .find(|bm| bm.device_name.as_deref() == Some(root_device.as_str()))
+            .and_then(|bm| bm.ebs?.volume_id)
+            .map(Into::into);
+        Ok(InstanceDetails {
+            root_volume,
+            public_ip: instance.public_ip_address.map(Into::into),
+            private_ip: instance.private_ip_address.map(Into::into),
+        })
+    }
+
+    /// Pages of instances matching the ec2 side of `filter`, each page is
+    /// only requested once the previous one has been consumed
+    pub fn get_instances_stream<'a>(
+        &'a self,
+        filter: &'a InstanceFilter,
+    ) -> impl Stream<Item = Result<Vec<Ec2InstanceInfo>, Error>> + 'a {
+        stream::try_unfold(
+            Some(None),
+            move |next_token: Option<Option<StackString>>| async move {
+                let next_token = match next_token {
+                    Some(next_token) => next_token,
+                    None => return Ok(None),
+                };
+                let page = self.get_instances_page(filter, next_token).await?;
+                Ok(Some((page.instances, page.next_token.map(Some))))
+            },
+        )
     }
 
     /// # Errors
@@ -316,6 +493,191 @@ impl Ec2Instance {
             .map_err(Into::into)
     }
 
+    /// Availability zones offering each of `instance_types` and the
+    /// architectures they support, unknown instance types are left out
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_instance_type_availability(
+        &self,
+        instance_types: &[StackString],
+    ) -> Result<InstanceTypeAvailability, Error> {
+        let instance_types: Vec<String> = instance_types.iter().map(ToString::to_string).collect();
+        let filter = Filter::builder()
+            .name("instance-type")
+            .set_values(Some(instance_types))
+            .build();
+        let offerings = self
+            .ec2_client
+            .describe_instance_type_offerings()
+            .location_type(LocationType::AvailabilityZone)
+            .filters(filter.clone())
+            .send()
+            .await?
+            .instance_type_offerings
+            .unwrap_or_default();
+        let mut availability = InstanceTypeAvailability::default();
+        for offering in offerings {
+            if let (Some(instance_type), Some(zone)) = (offering.instance_type, offering.location) {
+                availability
+                    .zones
+                    .entry(instance_type.as_str().into())
+                    .or_default()
+                    .insert(zone.into());
+            }
+        }
+        let instance_types = self
+            .ec2_client
+            .describe_instance_types()
+            .filters(filter)
+            .send()
+            .await?
+            .instance_types
+            .unwrap_or_default();
+        for info in instance_types {
+            if let (Some(instance_type), Some(processor)) =
+                (info.instance_type, info.processor_info)
+            {
+                availability.architectures.insert(
+                    instance_type.as_str().into(),
+                    processor
+                        .supported_architectures
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|a| a.as_str().into())
+                        .collect(),
+                );
+            }
+        }
+        Ok(availability)
+    }
+
+    /// Default vCPU count of each of `instance_types`
+    /// # Errors
+    /// Returns error if aws api call fails or an instance type isn't known to
+    /// aws, so it can't be counted as 0 vCPUs
+    pub async fn get_instance_type_vcpus(
+        &self,
+        instance_types: &[StackString],
+    ) -> Result<HashMap<StackString, i32>, Error> {
+        if instance_types.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let values: Vec<String> = instance_types.iter().map(ToString::to_string).collect();
+        let filter = Filter::builder()
+            .name("instance-type")
+            .set_values(Some(values))
+            .build();
+        let mut vcpus = HashMap::new();
+        let mut next_token = None;
+        loop {
+            let result = self
+                .ec2_client
+                .describe_instance_types()
+                .filters(filter.clone())
+                .set_next_token(next_token)
+                .send()
+                .await?;
+            vcpus.extend(
+                result
+                    .instance_types
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|info| {
+                        let vcpus = info.v_cpu_info?.default_v_cpus?;
+                        Some((info.instance_type?.as_str().into(), vcpus))
+                    }),
+            );
+            next_token = result.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        if let Some(missing) = instance_types.iter().find(|t| !vcpus.contains_key(*t)) {
+            return Err(format_err!("no vCPU count for instance type {missing}"));
+        }
+        Ok(vcpus)
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails or the ami doesn't exist
+    pub async fn get_image_root_device(&self, ami: &str) -> Result<StackString, Error> {
+        let filter = Filter::builder().name("image-id").values(ami).build();
+        self.ec2_client
+            .describe_images()
+            .filters(filter)
+            .send()
+            .await?
+            .images
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|image| image.root_device_name)
+            .map(Into::into)
+            .ok_or_else(|| format_err!("ami {ami} not found"))
+    }
+
+    /// Block device mappings overriding the root volume of `ami` and adding
+    /// `data_volumes`, empty if neither is customized
+    async fn get_block_device_mappings(
+        &self,
+        ami: &str,
+        root_volume: Option<&VolumeSpec>,
+        data_volumes: &[DataVolume],
+    ) -> Result<Option<Vec<BlockDeviceMapping>>, Error> {
+        validate_volumes(root_volume, data_volumes)?;
+        let mut mappings = Vec::new();
+        if let Some(root_volume) = root_volume {
+            let root_device = self.get_image_root_device(ami).await?;
+            mappings.push(root_volume.block_device_mapping(&root_device, true));
+        }
+        for data_volume in data_volumes {
+            mappings.push(
+                data_volume.volume.block_device_mapping(
+                    &data_volume.device_name,
+                    data_volume.delete_on_termination,
+                ),
+            );
+        }
+        Ok(if mappings.is_empty() {
+            None
+        } else {
+            Some(mappings)
+        })
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails or the ami doesn't exist
+    pub async fn get_image_architecture(&self, ami: &str) -> Result<StackString, Error> {
+        let filter = Filter::builder().name("image-id").values(ami).build();
+        self.ec2_client
+            .describe_images()
+            .filters(filter)
+            .send()
+            .await?
+            .images
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|image| image.architecture)
+            .map(|a| a.as_str().into())
+            .ok_or_else(|| format_err!("ami {ami} not found"))
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails or the ami doesn't exist
+    pub async fn get_image_name(&self, ami: &str) -> Result<StackString, Error> {
+        let filter = Filter::builder().name("image-id").values(ami).build();
+        self.ec2_client
+            .describe_images()
+            .filters(filter)
+            .send()
+            .await?
+            .images
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|image| image.name)
+            .map(Into::into)
+            .ok_or_else(|| format_err!("ami {ami} not found"))
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn get_latest_spot_inst_prices(
@@ -416,6 +778,15 @@ impl Ec2Instance {
                             .into_iter()
                             .filter_map(|t| Some((t.key?.into(), t.value?.into())))
                             .collect(),
+                        volume_type: v
+                            .volume_type
+                            .map_or_else(StackString::new, |t| t.as_str().into()),
+                        encrypted: v.encrypted.unwrap_or(false),
+                        attachment: v
+                            .attachments
+                            .unwrap_or_default()
+                            .into_iter()
+                            .find_map(|a| Some((a.instance_id?.into(), a.device?.into()))),
                     })
                 })
             })
@@ -443,9 +814,14 @@ impl Ec2Instance {
                     .filter_map(|snap| {
                         Some(SnapshotInfo {
                             id: snap.snapshot_id?.into(),
+                            volume_id: snap.volume_id.map(Into::into),
                             volume_size: snap.volume_size?.into(),
                             state: snap.state?.as_str().into(),
                             progress: snap.progress?.into(),
+                            start_time: snap
+                                .start_time
+                                .and_then(|t| OffsetDateTime::from_unix_timestamp(t.secs()).ok()),
+                            encrypted: snap.encrypted.unwrap_or(false),
                             tags: snap
                                 .tags
                                 .unwrap_or_default()
@@ -477,39 +853,103 @@ impl Ec2Instance {
             .map_err(Into::into)
     }
 
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn stop_instance(
+        &self,
+        instance_ids: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<(), Error> {
+        let instance_ids = instance_ids
+            .into_iter()
+            .map(|s| s.as_ref().to_string())
+            .collect();
+        self.ec2_client
+            .stop_instances()
+            .set_instance_ids(Some(instance_ids))
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
+    /// Decoded console output, `None` until the instance has written any
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_console_output(
+        &self,
+        instance_id: impl Into<String>,
+    ) -> Result<Option<StackString>, Error> {
+        let output = self
+            .ec2_client
+            .get_console_output()
+            .instance_id(instance_id)
+            .send()
+            .await?;
+        output
+            .output
+            .map(|output| {
+                let bytes = STANDARD.decode(output)?;
+                Ok(String::from_utf8_lossy(&bytes).as_ref().into())
+            })
+            .transpose()
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn request_spot_instance(
         &self,
         spot: &SpotRequest,
-    ) -> Result<impl Iterator<Item = String>, Error> {
+    ) -> Result<Vec<SpotLaunch>, Error> {
         let user_data = get_user_data_from_script(&self.script_dir, &spot.script)?;
-        let instance_type: InstanceType = spot.instance_type.parse()?;
-        let launch_specification = RequestSpotLaunchSpecification::builder()
-            .image_id(&spot.ami)
-            .instance_type(instance_type)
-            .security_group_ids(&spot.security_group)
-            .user_data(STANDARD_NO_PAD.encode(&user_data))
-            .key_name(&spot.key_name)
-            .build();
-        let mut builder = self
-            .ec2_client
-            .request_spot_instances()
-            .instance_count(1)
-            .launch_specification(launch_specification);
-        if let Some(spot_price) = spot.price {
-            builder = builder.spot_price(format_sstr!("{spot_price}"));
+        let user_data = STANDARD_NO_PAD.encode(&user_data);
+        let block_device_mappings = self
+            .get_block_device_mappings(&spot.ami, spot.root_volume.as_ref(), &spot.data_volumes)
+            .await?;
+        let spot_price = spot.instance_price();
+        let mut launches = Vec::new();
+        for (instance_type, availability_zone, count) in spot.allocation() {
+            let mut launch_specification = RequestSpotLaunchSpecification::builder()
+                .image_id(&spot.ami)
+                .instance_type(instance_type.parse::<InstanceType>()?)
+                .security_group_ids(&spot.security_group)
+                .user_data(&user_data)
+                .key_name(&spot.key_name)
+                .ebs_optimized(spot.ebs_optimized)
+                .set_block_device_mappings(block_device_mappings.clone())
+                .set_iam_instance_profile(
+                    spot.instance_profile
+                        .as_ref()
+                        .map(|p| instance_profile_specification(p)),
+                );
+            if availability_zone.is_some() || spot.tenancy != InstanceTenancy::Default {
+                let placement = SpotPlacement::builder()
+                    .set_availability_zone(availability_zone.as_ref().map(ToString::to_string))
+                    .tenancy(spot.tenancy.to_ec2())
+                    .build();
+                launch_specification = launch_specification.placement(placement);
+            }
+            let mut builder = self
+                .ec2_client
+                .request_spot_instances()
+                .instance_count(count as i32)
+                .launch_specification(launch_specification.build());
+            if let Some(spot_price) = spot_price {
+                builder = builder.spot_price(format_sstr!("{spot_price}"));
+            }
+            let requests = builder
+                .send()
+                .await?
+                .spot_instance_requests
+                .unwrap_or_default();
+            launches.extend(requests.into_iter().filter_map(|result| {
+                Some(SpotLaunch {
+                    spot_id: result.spot_instance_request_id?.into(),
+                    instance_type: instance_type.clone(),
+                    availability_zone: availability_zone.clone(),
+                })
+            }));
         }
-        builder
-            .send()
-            .await
-            .map(|req| {
-                req.spot_instance_requests
-                    .unwrap_or_default()
-                    .into_iter()
-                    .filter_map(|result| result.spot_instance_request_id)
-            })
-            .map_err(Into::into)
+        Ok(launches)
     }
 
     /// # Errors
@@ -552,8 +992,10 @@ impl Ec2Instance {
         &self,
         spot_instance_request_id: &str,
         tags: &HashMap<StackString, StackString>,
+        require_imdsv2: bool,
         iterations: usize,
     ) -> Result<(), Error> {
+        let mut imdsv2_required = !require_imdsv2;
         sleep(std::time::Duration::from_secs(2)).await;
         for i in 0..iterations {
             let reqs: HashMap<_, _> = self
@@ -570,6 +1012,12 @@ impl Ec2Instance {
                 .map(|inst| (inst.id.clone(), inst))
                 .collect();
             if let Some(Some(instance_id)) = reqs.get(spot_instance_request_id) {
+                // spot launch specifications have no metadata options, they're
+                // set as soon as the instance exists
+                if !imdsv2_required {
+                    self.require_imdsv2(instance_id).await?;
+                    imdsv2_required = true;
+                }
                 debug!("tag {} with {:?}", instance_id, tags);
                 self.tag_ec2_instance(instance_id, tags).await?;
                 if let Some(inst) = instances.get(instance_id) {
@@ -593,6 +1041,21 @@ impl Ec2Instance {
         Ok(())
     }
 
+    /// Disable metadata access without a session token (IMDSv1)
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn require_imdsv2(&self, instance_id: &str) -> Result<(), Error> {
+        self.ec2_client
+            .modify_instance_metadata_options()
+            .instance_id(instance_id)
+            .http_endpoint(InstanceMetadataEndpointState::Enabled)
+            .http_tokens(HttpTokensState::Required)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn cancel_spot_instance_request(
@@ -635,9 +1098,23 @@ impl Ec2Instance {
 
     /// # Errors
     /// Returns error if aws api call fails
-    pub async fn run_ec2_instance(&self, request: &InstanceRequest) -> Result<(), Error> {
+    pub async fn run_ec2_instance(
+        &self,
+        request: &InstanceRequest,
+    ) -> Result<Vec<StackString>, Error> {
         let user_data = get_user_data_from_script(&self.script_dir, &request.script)?;
         let instance_type: InstanceType = request.instance_type.parse()?;
+        let block_device_mappings = self
+            .get_block_device_mappings(
+                &request.ami,
+                request.root_volume.as_ref(),
+                &request.data_volumes,
+            )
+            .await?;
+        let placement = Placement::builder()
+            .set_availability_zone(request.availability_zone.as_ref().map(ToString::to_string))
+            .tenancy(request.tenancy.to_ec2())
+            .build();
         let req = self
             .ec2_client
             .run_instances()
@@ -648,14 +1125,38 @@ impl Ec2Instance {
             .key_name(&request.key_name)
             .security_group_ids(&request.security_group)
             .user_data(STANDARD_NO_PAD.encode(&user_data))
+            .placement(placement)
+            .ebs_optimized(request.ebs_optimized)
+            .set_metadata_options(if request.require_imdsv2 {
+                Some(
+                    InstanceMetadataOptionsRequest::builder()
+                        .http_endpoint(InstanceMetadataEndpointState::Enabled)
+                        .http_tokens(HttpTokensState::Required)
+                        .build(),
+                )
+            } else {
+                None
+            })
+            .set_block_device_mappings(block_device_mappings)
+            .set_iam_instance_profile(
+                request
+                    .instance_profile
+                    .as_ref()
+                    .map(|p| instance_profile_specification(p)),
+            )
+            .set_instance_initiated_shutdown_behavior(
+                request.shutdown_behavior.map(ShutdownBehavior::to_ec2),
+            )
             .send()
             .await?;
+        let mut instance_ids = Vec::new();
         for inst in req.instances.unwrap_or_default() {
             if let Some(inst) = inst.instance_id {
                 self.tag_ec2_instance(&inst, &request.tags).await?;
+                instance_ids.push(inst.into());
             }
         }
-        Ok(())
+        Ok(instance_ids)
     }
 
     /// # Errors
@@ -697,6 +1198,47 @@ impl Ec2Instance {
             .map_err(Into::into)
     }
 
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_elastic_ips(&self) -> Result<impl Iterator<Item = ElasticIpInfo>, Error> {
+        self.ec2_client
+            .describe_addresses()
+            .send()
+            .await
+            .map(|a| {
+                a.addresses
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|addr| {
+                        Some(ElasticIpInfo {
+                            allocation_id: addr.allocation_id?.into(),
+                            public_ip: addr.public_ip?.into(),
+                            instance_id: addr.instance_id.map(Into::into),
+                            association_id: addr.association_id.map(Into::into),
+                            tags: addr
+                                .tags
+                                .unwrap_or_default()
+                                .into_iter()
+                                .filter_map(|tag| Some((tag.key?.into(), tag.value?.into())))
+                                .collect(),
+                        })
+                    })
+            })
+            .map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn release_elastic_ip(&self, allocation_id: impl Into<String>) -> Result<(), Error> {
+        self.ec2_client
+            .release_address()
+            .allocation_id(allocation_id)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn create_ebs_volume(
@@ -718,6 +1260,43 @@ impl Ec2Instance {
             .map_err(Into::into)
     }
 
+    /// Create a volume of `volume_type` from `snapid`, the volume is
+    /// encrypted if the snapshot is
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn create_volume_from_snapshot(
+        &self,
+        zoneid: impl Into<String>,
+        snapid: impl Into<String>,
+        volume_type: &str,
+        tags: &HashMap<StackString, StackString>,
+    ) -> Result<StackString, Error> {
+        let mut builder = self
+            .ec2_client
+            .create_volume()
+            .availability_zone(zoneid)
+            .snapshot_id(snapid)
+            .volume_type(VolumeType::from(volume_type));
+        if !tags.is_empty() {
+            let tags: Vec<_> = tags
+                .iter()
+                .map(|(k, v)| Tag::builder().key(k).value(v).build())
+                .collect();
+            builder = builder.tag_specifications(
+                TagSpecification::builder()
+                    .resource_type(ResourceType::Volume)
+                    .set_tags(Some(tags))
+                    .build(),
+            );
+        }
+        builder
+            .send()
+            .await?
+            .volume_id
+            .map(Into::into)
+            .ok_or_else(|| format_err!("no volume id returned"))
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn delete_ebs_volume(&self, volid: impl Into<String>) -> Result<(), Error> {
@@ -815,6 +1394,50 @@ impl Ec2Instance {
             .map_err(Into::into)
     }
 
+    /// Copy `snapshot_id` from `source_region` into the region of this
+    /// client. With `encrypted` set the copy is encrypted with `kms_key_id`,
+    /// or the account's default ebs key. Returns the id of the copy.
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn copy_snapshot(
+        &self,
+        source_region: &str,
+        snapshot_id: &str,
+        encrypted: bool,
+        kms_key_id: Option<&str>,
+        tags: &HashMap<StackString, StackString>,
+    ) -> Result<StackString, Error> {
+        let mut builder = self
+            .ec2_client
+            .copy_snapshot()
+            .source_region(source_region)
+            .source_snapshot_id(snapshot_id)
+            .description(format!("copy of {snapshot_id} from {source_region}"));
+        if encrypted {
+            builder = builder
+                .encrypted(true)
+                .set_kms_key_id(kms_key_id.map(Into::into));
+        }
+        if !tags.is_empty() {
+            let tags: Vec<_> = tags
+                .iter()
+                .map(|(k, v)| Tag::builder().key(k).value(v).build())
+                .collect();
+            builder = builder.tag_specifications(
+                TagSpecification::builder()
+                    .resource_type(ResourceType::Snapshot)
+                    .set_tags(Some(tags))
+                    .build(),
+            );
+        }
+        builder
+            .send()
+            .await?
+            .snapshot_id
+            .map(Into::into)
+            .ok_or_else(|| format_err!("no snapshot id returned copying {snapshot_id}"))
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn delete_ebs_snapshot(&self, snapid: impl Into<String>) -> Result<(), Error> {
@@ -847,6 +1470,332 @@ impl Ec2Instance {
             })
             .map_err(Into::into)
     }
+
+    /// Create a new rsa key pair, the private key is only ever returned by
+    /// this call
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn create_key_pair(&self, key_name: impl Into<String>) -> Result<StackString, Error> {
+        let key_name = key_name.into();
+        self.ec2_client
+            .create_key_pair()
+            .key_name(&key_name)
+            .send()
+            .await?
+            .key_material
+            .map(Into::into)
+            .ok_or_else(|| format_err!("no private key returned for {key_name}"))
+    }
+
+    /// Import an openssh formatted public key, returns the fingerprint
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn import_key_pair(
+        &self,
+        key_name: impl Into<String>,
+        public_key: &str,
+    ) -> Result<StackString, Error> {
+        self.ec2_client
+            .import_key_pair()
+            .key_name(key_name)
+            .public_key_material(Blob::new(public_key.trim().as_bytes()))
+            .send()
+            .await
+            .map(|k| k.key_fingerprint.unwrap_or_default().into())
+            .map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn delete_key_pair(&self, key_name: impl Into<String>) -> Result<(), Error> {
+        self.ec2_client
+            .delete_key_pair()
+            .key_name(key_name)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+}
+
+/// Key types ec2 accepts for `import_key_pair`
+const PUBLIC_KEY_TYPES: [&str; 5] = [
+    "ssh-rsa",
+    "ssh-ed25519",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+];
+
+/// Check that `public_key` is a single openssh formatted public key
+/// (`type base64 [comment]`)
+/// # Errors
+/// Returns error if the key is malformed
+pub fn validate_public_key(public_key: &str) -> Result<(), Error> {
+    let public_key = public_key.trim();
+    if public_key.lines().count() != 1 {
+        return Err(format_err!("expected a single public key line"));
+    }
+    let mut fields = public_key.split_whitespace();
+    let key_type = fields.next().unwrap_or("");
+    if !PUBLIC_KEY_TYPES.contains(&key_type) {
+        return Err(format_err!("unsupported key type {key_type}"));
+    }
+    let key_data = fields
+        .next()
+        .ok_or_else(|| format_err!("missing key data"))?;
+    STANDARD
+        .decode(key_data)
+        .map_err(|e| format_err!("invalid key data {e}"))?;
+    Ok(())
+}
+
+/// Whether instances share hardware with other accounts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstanceTenancy {
+    #[default]
+    Default,
+    Dedicated,
+}
+
+impl InstanceTenancy {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Dedicated => "dedicated",
+        }
+    }
+
+    fn to_ec2(self) -> Tenancy {
+        match self {
+            Self::Default => Tenancy::Default,
+            Self::Dedicated => Tenancy::Dedicated,
+        }
+    }
+}
+
+impl fmt::Display for InstanceTenancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for InstanceTenancy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "dedicated" => Ok(Self::Dedicated),
+            _ => Err(format_err!("{s} is not an InstanceTenancy")),
+        }
+    }
+}
+
+/// What happens when an instance shuts itself down, aws stops ebs backed
+/// instances unless told otherwise. Spot requests always terminate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShutdownBehavior {
+    Stop,
+    Terminate,
+}
+
+impl ShutdownBehavior {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Stop => "stop",
+            Self::Terminate => "terminate",
+        }
+    }
+
+    fn to_ec2(self) -> Ec2ShutdownBehavior {
+        match self {
+            Self::Stop => Ec2ShutdownBehavior::Stop,
+            Self::Terminate => Ec2ShutdownBehavior::Terminate,
+        }
+    }
+}
+
+impl fmt::Display for ShutdownBehavior {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for ShutdownBehavior {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stop" => Ok(Self::Stop),
+            "terminate" => Ok(Self::Terminate),
+            _ => Err(format_err!("{s} is not a ShutdownBehavior")),
+        }
+    }
+}
+
+/// Size, type and iops of an ebs volume, unset values are left to the ami
+/// (root volume) or aws defaults
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeSpec {
+    pub size_gb: Option<i32>,
+    pub volume_type: Option<StackString>,
+    pub iops: Option<i32>,
+}
+
+impl VolumeSpec {
+    /// `None` unless at least one option is given
+    #[must_use]
+    pub fn from_options(
+        size_gb: Option<i32>,
+        volume_type: Option<StackString>,
+        iops: Option<i32>,
+    ) -> Option<Self> {
+        if size_gb.is_none() && volume_type.is_none() && iops.is_none() {
+            None
+        } else {
+            Some(Self {
+                size_gb,
+                volume_type,
+                iops,
+            })
+        }
+    }
+
+    /// # Errors
+    /// Returns error if the volume type is unknown or its iops can't be set
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(size_gb) = self.size_gb {
+            if size_gb < 1 {
+                return Err(format_err!("volume size {size_gb} must be at least 1 GB"));
+            }
+        }
+        if let Some(volume_type) = &self.volume_type {
+            if !LAUNCH_VOLUME_TYPES.contains(&volume_type.as_str()) {
+                return Err(format_err!("unknown volume type {volume_type}"));
+            }
+        }
+        if self.iops.is_some() {
+            let volume_type = self.volume_type.as_ref().map_or("", StackString::as_str);
+            if !PROVISIONED_IOPS_VOLUME_TYPES.contains(&volume_type) {
+                return Err(format_err!(
+                    "iops can only be set for {} volumes",
+                    PROVISIONED_IOPS_VOLUME_TYPES.join("/")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn block_device_mapping(
+        &self,
+        device_name: &str,
+        delete_on_termination: bool,
+    ) -> BlockDeviceMapping {
+        let ebs = EbsBlockDevice::builder()
+            .set_volume_size(self.size_gb)
+            .set_volume_type(
+                self.volume_type
+                    .as_ref()
+                    .map(|t| VolumeType::from(t.as_str())),
+            )
+            .set_iops(self.iops)
+            .delete_on_termination(delete_on_termination)
+            .build();
+        BlockDeviceMapping::builder()
+            .device_name(device_name)
+            .ebs(ebs)
+            .build()
+    }
+}
+
+/// An additional ebs volume created at launch
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataVolume {
+    /// e.g. `/dev/sdf`
+    pub device_name: StackString,
+    pub volume: VolumeSpec,
+    pub delete_on_termination: bool,
+}
+
+impl FromStr for DataVolume {
+    type Err = Error;
+
+    /// `DEVICE:SIZE[:TYPE][:keep]`, e.g. `/dev/sdf:100:gp3`, volumes are
+    /// deleted with the instance unless `keep` is given
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split(':');
+        let device_name = fields.next().unwrap_or("").trim();
+        if device_name.is_empty() {
+            return Err(format_err!("data volume {s} has no device name"));
+        }
+        let size_gb: i32 = fields
+            .next()
+            .ok_or_else(|| format_err!("data volume {s} has no size"))?
+            .trim()
+            .parse()
+            .map_err(|e| format_err!("invalid size of data volume {s}: {e}"))?;
+        let mut data_volume = Self {
+            device_name: device_name.into(),
+            volume: VolumeSpec {
+                size_gb: Some(size_gb),
+                ..VolumeSpec::default()
+            },
+            delete_on_termination: true,
+        };
+        for field in fields.map(str::trim) {
+            if field == "keep" {
+                data_volume.delete_on_termination = false;
+            } else {
+                data_volume.volume.volume_type = Some(field.into());
+            }
+        }
+        data_volume.volume.validate()?;
+        Ok(data_volume)
+    }
+}
+
+/// `profile` is either an instance profile name or its arn
+fn instance_profile_specification(profile: &str) -> IamInstanceProfileSpecification {
+    let builder = IamInstanceProfileSpecification::builder();
+    if profile.starts_with("arn:") {
+        builder.arn(profile)
+    } else {
+        builder.name(profile)
+    }
+    .build()
+}
+
+/// # Errors
+/// Returns error if a volume is invalid or two volumes share a device name
+pub fn validate_volumes(
+    root_volume: Option<&VolumeSpec>,
+    data_volumes: &[DataVolume],
+) -> Result<(), Error> {
+    if let Some(root_volume) = root_volume {
+        root_volume.validate()?;
+    }
+    let mut devices = HashSet::new();
+    for data_volume in data_volumes {
+        data_volume.volume.validate()?;
+        if data_volume.volume.size_gb.is_none() {
+            return Err(format_err!(
+                "data volume {} has no size",
+                data_volume.device_name
+            ));
+        }
+        if !devices.insert(data_volume.device_name.as_str()) {
+            return Err(format_err!(
+                "device {} is mapped twice",
+                data_volume.device_name
+            ));
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -857,6 +1806,25 @@ pub struct InstanceRequest {
     pub security_group: StackString,
     pub script: PathBuf,
     pub tags: HashMap<StackString, StackString>,
+    /// Zone to launch in, aws picks one if unset
+    pub availability_zone: Option<StackString>,
+    #[serde(default)]
+    pub tenancy: InstanceTenancy,
+    #[serde(default)]
+    pub ebs_optimized: bool,
+    pub shutdown_behavior: Option<ShutdownBehavior>,
+    /// Overrides of the ami's root volume
+    pub root_volume: Option<VolumeSpec>,
+    #[serde(default)]
+    pub data_volumes: Vec<DataVolume>,
+    /// Name or arn of the instance profile to attach
+    pub instance_profile: Option<StackString>,
+    /// Only allow metadata requests with a session token (IMDSv2)
+    #[serde(default)]
+    pub require_imdsv2: bool,
+    /// Host name pointed at the instance's public ip once it is running
+    #[serde(default)]
+    pub dns_name: Option<StackString>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -868,6 +1836,153 @@ pub struct SpotRequest {
     pub key_name: StackString,
     pub price: Option<f32>,
     pub tags: HashMap<StackString, StackString>,
+    /// Number of instances to request, zero is treated as one
+    pub count: usize,
+    /// Further instance types to spread the request across
+    pub extra_instance_types: Vec<StackString>,
+    /// Availability zones to spread the request across
+    pub availability_zones: Vec<StackString>,
+    /// Upper bound on the combined hourly price of all instances
+    pub max_total_price: Option<f32>,
+    /// Re-request instances terminated by aws
+    #[serde(default)]
+    pub auto_recover: bool,
+    #[serde(default)]
+    pub tenancy: InstanceTenancy,
+    #[serde(default)]
+    pub ebs_optimized: bool,
+    /// Overrides of the ami's root volume
+    #[serde(default)]
+    pub root_volume: Option<VolumeSpec>,
+    #[serde(default)]
+    pub data_volumes: Vec<DataVolume>,
+    /// Name or arn of the instance profile to attach
+    #[serde(default)]
+    pub instance_profile: Option<StackString>,
+    /// Only allow metadata requests with a session token (IMDSv2), applied
+    /// once the request is fulfilled
+    #[serde(default)]
+    pub require_imdsv2: bool,
+    /// Host name pointed at the instance's public ip once it is running,
+    /// only allowed when requesting a single instance
+    #[serde(default)]
+    pub dns_name: Option<StackString>,
+}
+
+impl SpotRequest {
+    /// Distribute `count` instances round-robin over every combination of
+    /// instance type and availability zone, returns `(type, zone, count)`
+    #[must_use]
+    pub fn allocation(&self) -> Vec<(StackString, Option<StackString>, usize)> {
+        let instance_types = self.instance_types();
+        let zones: Vec<Option<StackString>> = if self.availability_zones.is_empty() {
+            vec![None]
+        } else {
+            self.availability_zones.iter().cloned().map(Some).collect()
+        };
+        let mut allocation: Vec<_> = instance_types
+            .iter()
+            .flat_map(|t| zones.iter().map(move |z| (t.clone(), z.clone(), 0)))
+            .collect();
+        let slots = allocation.len();
+        for i in 0..self.count.max(1) {
+            allocation[i % slots].2 += 1;
+        }
+        allocation.retain(|(_, _, count)| *count > 0);
+        allocation
+    }
+
+    /// `instance_type` followed by the distinct `extra_instance_types`
+    #[must_use]
+    pub fn instance_types(&self) -> Vec<StackString> {
+        let mut instance_types = vec![self.instance_type.clone()];
+        for instance_type in &self.extra_instance_types {
+            if !instance_types.contains(instance_type) {
+                instance_types.push(instance_type.clone());
+            }
+        }
+        instance_types
+    }
+
+    /// Every instance type must be offered in each requested zone (or
+    /// anywhere in the region if no zone is requested) and support the
+    /// architecture of the ami
+    /// # Errors
+    /// Returns error listing every instance type that can't be launched
+    pub fn check_availability(
+        &self,
+        availability: &InstanceTypeAvailability,
+        ami_architecture: &str,
+    ) -> Result<(), Error> {
+        let mut problems = Vec::new();
+        for instance_type in self.instance_types() {
+            let zones = match availability.zones.get(&instance_type) {
+                Some(zones) if !zones.is_empty() => zones,
+                _ => {
+                    problems.push(format_sstr!(
+                        "{instance_type} is not offered in this region"
+                    ));
+                    continue;
+                }
+            };
+            let missing: Vec<_> = self
+                .availability_zones
+                .iter()
+                .filter(|z| !zones.contains(*z))
+                .map(StackString::as_str)
+                .collect();
+            if !missing.is_empty() {
+                let offered = zones.iter().map(StackString::as_str).sorted().join(", ");
+                problems.push(format_sstr!(
+                    "{instance_type} is not offered in {}, only in {offered}",
+                    missing.join(", ")
+                ));
+            }
+            if let Some(architectures) = availability.architectures.get(&instance_type) {
+                if !architectures.iter().any(|a| a == ami_architecture) {
+                    problems.push(format_sstr!(
+                        "{instance_type} supports {}, ami {} is {ami_architecture}",
+                        architectures.join("/"),
+                        self.ami
+                    ));
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(format_err!("{}", problems.join("; ")))
+        }
+    }
+
+    /// Bid per instance, the lower of `price` and an even share of
+    /// `max_total_price`
+    #[must_use]
+    pub fn instance_price(&self) -> Option<f32> {
+        let share = self
+            .max_total_price
+            .map(|total| total / self.count.max(1) as f32);
+        match (self.price, share) {
+            (Some(price), Some(share)) => Some(price.min(share)),
+            (price, share) => price.or(share),
+        }
+    }
+}
+
+/// Zones and architectures of instance types, see
+/// `get_instance_type_availability`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstanceTypeAvailability {
+    pub zones: HashMap<StackString, HashSet<StackString>>,
+    pub architectures: HashMap<StackString, Vec<StackString>>,
+}
+
+/// A spot request created by `request_spot_instance`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpotLaunch {
+    pub spot_id: StackString,
+    pub instance_type: StackString,
+    pub availability_zone: Option<StackString>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -876,6 +1991,28 @@ pub struct AmiInfo {
     pub name: StackString,
     pub state: StackString,
     pub snapshot_ids: Vec<StackString>,
+    /// Iso 8601 timestamp the image was registered at
+    #[serde(default)]
+    pub creation_date: Option<StackString>,
+    #[serde(default)]
+    pub tags: HashMap<StackString, StackString>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ElasticIpInfo {
+    pub allocation_id: StackString,
+    pub public_ip: StackString,
+    pub instance_id: Option<StackString>,
+    pub association_id: Option<StackString>,
+    pub tags: HashMap<StackString, StackString>,
+}
+
+/// Instances from one `describe_instances` call, `next_token` is `None` on
+/// the last page
+#[derive(Debug, Clone)]
+pub struct InstancePage {
+    pub instances: Vec<Ec2InstanceInfo>,
+    pub next_token: Option<StackString>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -888,6 +2025,35 @@ pub struct Ec2InstanceInfo {
     pub launch_time: DateTimeWrapper,
     pub tags: HashMap<StackString, StackString>,
     pub volumes: Vec<StackString>,
+    #[serde(default)]
+    pub public_ip: Option<StackString>,
+    #[serde(default)]
+    pub private_ip: Option<StackString>,
+}
+
+impl Default for Ec2InstanceInfo {
+    fn default() -> Self {
+        Self {
+            id: StackString::new(),
+            dns_name: StackString::new(),
+            state: StackString::new(),
+            instance_type: StackString::new(),
+            availability_zone: StackString::new(),
+            launch_time: DateTimeWrapper::from_offsetdatetime(OffsetDateTime::UNIX_EPOCH),
+            tags: HashMap::new(),
+            volumes: Vec::new(),
+            public_ip: None,
+            private_ip: None,
+        }
+    }
+}
+
+/// Details of a single instance not carried by `Ec2InstanceInfo`
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct InstanceDetails {
+    pub root_volume: Option<StackString>,
+    pub public_ip: Option<StackString>,
+    pub private_ip: Option<StackString>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
@@ -918,15 +2084,23 @@ pub struct VolumeInfo {
     pub iops: i64,
     pub state: StackString,
     pub tags: HashMap<StackString, StackString>,
+    pub volume_type: StackString,
+    pub encrypted: bool,
+    /// Instance and device the volume is attached to
+    pub attachment: Option<(StackString, StackString)>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct SnapshotInfo {
     pub id: StackString,
+    /// Volume the snapshot was taken of
+    pub volume_id: Option<StackString>,
     pub volume_size: i64,
     pub state: StackString,
     pub progress: StackString,
     pub tags: HashMap<StackString, StackString>,
+    pub start_time: Option<OffsetDateTime>,
+    pub encrypted: bool,
 }
 
 /// # Errors
@@ -951,13 +2125,185 @@ pub fn get_user_data_from_script(
 mod tests {
     use anyhow::Error;
     use log::debug;
+    use maplit::{hashmap, hashset};
     use std::path::Path;
 
     use crate::{
+        aws_api::{Ec2Api, MockAws},
         config::Config,
-        ec2_instance::{get_user_data_from_script, Ec2Instance},
+        ec2_instance::{
+            get_user_data_from_script, validate_public_key, validate_volumes, DataVolume,
+            Ec2Instance, InstanceTenancy, InstanceTypeAvailability, ShutdownBehavior, SpotRequest,
+            UbuntuImageQuery, VolumeSpec,
+        },
     };
 
+    #[test]
+    fn test_ubuntu_image_query() -> Result<(), Error> {
+        let mut query = UbuntuImageQuery::new("noble-24.04", "arm64");
+        query.validate()?;
+        assert_eq!(
+            query.name_pattern(),
+            "ubuntu/images/hvm-ssd*/ubuntu-noble-24.04-arm64-server*"
+        );
+        query.minimal = true;
+        assert_eq!(
+            query.name_pattern(),
+            "ubuntu-minimal/images/hvm-ssd*/ubuntu-noble-24.04-arm64-minimal-*"
+        );
+
+        assert!(UbuntuImageQuery::new("jammy-22.04", "amd64")
+            .validate()
+            .is_ok());
+        assert!(UbuntuImageQuery::new("noble", "amd64").validate().is_err());
+        assert!(UbuntuImageQuery::new("noble-24.04*", "amd64")
+            .validate()
+            .is_err());
+        assert!(UbuntuImageQuery::new("noble-24.04", "i386")
+            .validate()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_spot_request_allocation() {
+        let mut req = SpotRequest {
+            instance_type: "t3.micro".into(),
+            price: Some(0.5),
+            ..SpotRequest::default()
+        };
+        assert_eq!(req.allocation(), vec![("t3.micro".into(), None, 1)]);
+        assert_eq!(req.instance_price(), Some(0.5));
+
+        req.count = 5;
+        req.extra_instance_types = vec!["t3a.micro".into(), "t3.micro".into()];
+        req.availability_zones = vec!["us-east-1a".into(), "us-east-1b".into()];
+        req.max_total_price = Some(1.25);
+        assert_eq!(
+            req.allocation(),
+            vec![
+                ("t3.micro".into(), Some("us-east-1a".into()), 2),
+                ("t3.micro".into(), Some("us-east-1b".into()), 1),
+                ("t3a.micro".into(), Some("us-east-1a".into()), 1),
+                ("t3a.micro".into(), Some("us-east-1b".into()), 1),
+            ]
+        );
+        assert_eq!(req.instance_price(), Some(0.25));
+    }
+
+    #[test]
+    fn test_spot_request_check_availability() {
+        let availability = InstanceTypeAvailability {
+            zones: hashmap! {
+                "t3.micro".into() => hashset! {"us-east-1a".into(), "us-east-1b".into()},
+                "t4g.micro".into() => hashset! {"us-east-1a".into()},
+            },
+            architectures: hashmap! {
+                "t3.micro".into() => vec!["i386".into(), "x86_64".into()],
+                "t4g.micro".into() => vec!["arm64".into()],
+            },
+        };
+        let mut req = SpotRequest {
+            ami: "ami-1234".into(),
+            instance_type: "t3.micro".into(),
+            availability_zones: vec!["us-east-1b".into()],
+            ..SpotRequest::default()
+        };
+        assert!(req.check_availability(&availability, "x86_64").is_ok());
+
+        let err = req.check_availability(&availability, "arm64").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "t3.micro supports i386/x86_64, ami ami-1234 is arm64"
+        );
+
+        req.instance_type = "t4g.micro".into();
+        let err = req.check_availability(&availability, "arm64").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "t4g.micro is not offered in us-east-1b, only in us-east-1a"
+        );
+
+        req.availability_zones.clear();
+        req.extra_instance_types = vec!["x9.huge".into()];
+        let err = req.check_availability(&availability, "arm64").unwrap_err();
+        assert_eq!(err.to_string(), "x9.huge is not offered in this region");
+    }
+
+    #[test]
+    fn test_placement_options() -> Result<(), Error> {
+        for tenancy in [InstanceTenancy::Default, InstanceTenancy::Dedicated] {
+            assert_eq!(tenancy.to_str().parse::<InstanceTenancy>()?, tenancy);
+        }
+        assert!("host".parse::<InstanceTenancy>().is_err());
+        for behavior in [ShutdownBehavior::Stop, ShutdownBehavior::Terminate] {
+            assert_eq!(behavior.to_str().parse::<ShutdownBehavior>()?, behavior);
+        }
+        assert!("hibernate".parse::<ShutdownBehavior>().is_err());
+
+        // launch parameters recorded before placement options existed
+        let req: SpotRequest = serde_json::from_str(
+            r#"{"ami":"ami-1234","instance_type":"t3.micro","security_group":"sg-1",
+                "script":"setup_aws.sh","key_name":"key","price":null,"tags":{},"count":1,
+                "extra_instance_types":[],"availability_zones":[],"max_total_price":null}"#,
+        )?;
+        assert_eq!(req.tenancy, InstanceTenancy::Default);
+        assert!(!req.ebs_optimized);
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_volume_from_str() -> Result<(), Error> {
+        let volume: DataVolume = "/dev/sdf:100".parse()?;
+        assert_eq!(volume.device_name, "/dev/sdf");
+        assert_eq!(volume.volume.size_gb, Some(100));
+        assert!(volume.delete_on_termination);
+
+        let volume: DataVolume = "/dev/sdg:500:st1:keep".parse()?;
+        assert_eq!(volume.volume.volume_type.as_deref(), Some("st1"));
+        assert!(!volume.delete_on_termination);
+
+        assert!("/dev/sdf".parse::<DataVolume>().is_err());
+        assert!("/dev/sdf:big".parse::<DataVolume>().is_err());
+        assert!("/dev/sdf:10:gp9".parse::<DataVolume>().is_err());
+        assert!(":10".parse::<DataVolume>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_volumes() -> Result<(), Error> {
+        let root = VolumeSpec {
+            size_gb: Some(50),
+            volume_type: Some("gp3".into()),
+            iops: Some(6000),
+        };
+        let data: Vec<DataVolume> = vec!["/dev/sdf:100".parse()?, "/dev/sdg:10:gp2".parse()?];
+        assert!(validate_volumes(Some(&root), &data).is_ok());
+
+        let gp2_iops = VolumeSpec {
+            volume_type: Some("gp2".into()),
+            ..root.clone()
+        };
+        assert!(validate_volumes(Some(&gp2_iops), &[]).is_err());
+
+        let duplicate: Vec<DataVolume> = vec!["/dev/sdf:100".parse()?, "/dev/sdf:10".parse()?];
+        assert!(validate_volumes(None, &duplicate).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_public_key() {
+        let key =
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIGbLuMa0hzVJBRsvNCkwcqQAKXK0Ie2dVs1p2Xx0eDPi \
+                   user@host";
+        assert!(validate_public_key(key).is_ok());
+        assert!(validate_public_key(&format!("{key}\n")).is_ok());
+        assert!(validate_public_key("ssh-dss AAAAB3NzaC1kc3M=").is_err());
+        assert!(validate_public_key("ssh-rsa not*base64").is_err());
+        assert!(validate_public_key("ssh-rsa").is_err());
+        assert!(validate_public_key(&format!("{key}\n{key}")).is_err());
+    }
+
     #[test]
     fn test_get_user_data_from_script() -> Result<(), Error> {
         let user_data = get_user_data_from_script(
@@ -969,17 +2315,28 @@ mod tests {
         Ok(())
     }
 
-    #[tokio::test]
-    async fn test_get_all_instances() -> Result<(), Error> {
-        let config = Config::init_config()?;
-        let sdk_config = aws_config::load_from_env().await;
-        let ec2 = Ec2Instance::new(&config, &sdk_config);
-        let instances: Vec<_> = ec2.get_all_instances().await?.collect();
+    async fn check_get_all_instances(ec2: &dyn Ec2Api) -> Result<(), Error> {
+        let instances = ec2.get_all_instances().await?;
 
         assert!(instances.len() > 0);
 
-        let result: Vec<_> = ec2.get_availability_zones().await?.collect();
+        let result = ec2.get_availability_zones().await?;
         assert_eq!(result[0].as_str(), "us-east-1a");
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_get_all_instances() -> Result<(), Error> {
+        let mock = MockAws::from_fixtures()?;
+        check_get_all_instances(&mock).await
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_get_all_instances_live() -> Result<(), Error> {
+        let config = Config::init_config()?;
+        let sdk_config = aws_config::load_from_env().await;
+        let ec2 = Ec2Instance::new(&config, &sdk_config);
+        check_get_all_instances(&ec2).await
+    }
 }
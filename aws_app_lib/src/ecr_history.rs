@@ -0,0 +1,264 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::Serialize;
+use stack_string::StackString;
+use std::collections::{BTreeSet, HashMap};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{ecr_instance::ImageInfo, pgpool::PgPool};
+
+/// Tags of an image as seen at `recorded_at`, a row is added when a digest
+/// first appears and whenever its tags change
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Serialize)]
+pub struct EcrImageHistory {
+    pub id: Uuid,
+    pub repo: StackString,
+    pub digest: StackString,
+    pub tags: Vec<StackString>,
+    /// Image size in MB
+    pub image_size: f64,
+    pub pushed_at: OffsetDateTime,
+    pub recorded_at: OffsetDateTime,
+}
+
+impl EcrImageHistory {
+    #[must_use]
+    pub fn new(image: &ImageInfo, recorded_at: OffsetDateTime) -> Self {
+        let mut tags = image.tags.clone();
+        tags.sort();
+        Self {
+            id: Uuid::new_v4(),
+            repo: image.repo.clone(),
+            digest: image.digest.clone(),
+            tags,
+            image_size: image.image_size,
+            pushed_at: image.pushed_at,
+            recorded_at,
+        }
+    }
+
+    /// Rows to add for `images` given the `latest` row of each digest: images
+    /// never seen before and images whose tags have changed
+    #[must_use]
+    pub fn changes(latest: &[Self], images: &[ImageInfo], now: OffsetDateTime) -> Vec<Self> {
+        let known: HashMap<(&str, &str), BTreeSet<&str>> = latest
+            .iter()
+            .map(|h| {
+                (
+                    (h.repo.as_str(), h.digest.as_str()),
+                    h.tags.iter().map(StackString::as_str).collect(),
+                )
+            })
+            .collect();
+        images
+            .iter()
+            .filter(|image| {
+                let tags: BTreeSet<&str> = image.tags.iter().map(StackString::as_str).collect();
+                known.get(&(image.repo.as_str(), image.digest.as_str())) != Some(&tags)
+            })
+            .map(|image| Self::new(image, now))
+            .collect()
+    }
+
+    /// Most recent row of every digest
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_latest(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT DISTINCT ON (repo, digest) *
+                FROM ecr_image_history
+                ORDER BY repo, digest, recorded_at DESC
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_repo(pool: &PgPool, repo: &str) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM ecr_image_history WHERE repo = $repo ORDER BY recorded_at",
+            repo = repo,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO ecr_image_history (
+                    id, repo, digest, tags, image_size, pushed_at, recorded_at
+                ) VALUES (
+                    $id, $repo, $digest, $tags, $image_size, $pushed_at, $recorded_at
+                )
+            ",
+            id = self.id,
+            repo = self.repo,
+            digest = self.digest,
+            tags = self.tags,
+            image_size = self.image_size,
+            pushed_at = self.pushed_at,
+            recorded_at = self.recorded_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// A push or retag in the history of a repository
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EcrImageEvent {
+    pub recorded_at: OffsetDateTime,
+    pub digest: StackString,
+    pub tags: Vec<StackString>,
+    pub image_size: f64,
+    /// Change in size from the previously pushed image, `None` for the first
+    /// push and for retags
+    pub size_delta: Option<f64>,
+    /// True when the digest was first seen, false when only its tags changed
+    pub pushed: bool,
+    /// Tags which pointed at another digest before, with that digest
+    pub moved_tags: Vec<(StackString, StackString)>,
+}
+
+/// Events of one repository's `history`, most recent first
+#[must_use]
+pub fn repo_timeline(history: &[EcrImageHistory]) -> Vec<EcrImageEvent> {
+    let mut history: Vec<_> = history.iter().collect();
+    history.sort_by_key(|h| h.recorded_at);
+    let mut tag_digests: HashMap<&str, &str> = HashMap::new();
+    let mut seen: BTreeSet<&str> = BTreeSet::new();
+    let mut last_size = None;
+    let mut events = Vec::with_capacity(history.len());
+    for entry in history {
+        let pushed = seen.insert(entry.digest.as_str());
+        let size_delta = if pushed {
+            let delta = last_size.map(|size| entry.image_size - size);
+            last_size = Some(entry.image_size);
+            delta
+        } else {
+            None
+        };
+        let moved_tags = entry
+            .tags
+            .iter()
+            .filter_map(|tag| {
+                let previous = tag_digests.insert(tag.as_str(), entry.digest.as_str())?;
+                if previous == entry.digest.as_str() {
+                    None
+                } else {
+                    Some((tag.clone(), previous.into()))
+                }
+            })
+            .collect();
+        events.push(EcrImageEvent {
+            recorded_at: entry.recorded_at,
+            digest: entry.digest.clone(),
+            tags: entry.tags.clone(),
+            image_size: entry.image_size,
+            size_delta,
+            pushed,
+            moved_tags,
+        });
+    }
+    events.reverse();
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use stack_string::StackString;
+    use time::{macros::datetime, Duration};
+
+    use crate::{
+        ecr_history::{repo_timeline, EcrImageHistory},
+        ecr_instance::ImageInfo,
+    };
+
+    fn image(digest: &str, tags: &[&str], image_size: f64) -> ImageInfo {
+        ImageInfo {
+            repo: "app".into(),
+            digest: digest.into(),
+            tags: tags.iter().map(|t| (*t).into()).collect(),
+            pushed_at: datetime!(2024-06-01 00:00 UTC),
+            image_size,
+        }
+    }
+
+    #[test]
+    fn test_changes() {
+        let now = datetime!(2024-06-01 00:00 UTC);
+        let latest = [
+            EcrImageHistory::new(&image("sha256:aaaa", &["latest", "v1"], 100.0), now),
+            EcrImageHistory::new(&image("sha256:bbbb", &[], 90.0), now),
+        ];
+        let images = [
+            image("sha256:aaaa", &["v1", "latest"], 100.0),
+            image("sha256:bbbb", &["v0"], 90.0),
+            image("sha256:cccc", &["v2"], 120.0),
+        ];
+        let changes = EcrImageHistory::changes(&latest, &images, now);
+        let digests: Vec<_> = changes.iter().map(|c| c.digest.as_str()).collect();
+        assert_eq!(digests, vec!["sha256:bbbb", "sha256:cccc"]);
+        assert_eq!(changes[0].tags, vec!["v0"]);
+    }
+
+    #[test]
+    fn test_repo_timeline() {
+        let start = datetime!(2024-06-01 00:00 UTC);
+        let history = [
+            EcrImageHistory::new(&image("sha256:aaaa", &["latest", "v1"], 100.0), start),
+            EcrImageHistory::new(
+                &image("sha256:bbbb", &["latest", "v2"], 120.5),
+                start + Duration::days(1),
+            ),
+            EcrImageHistory::new(
+                &image("sha256:aaaa", &["v1"], 100.0),
+                start + Duration::days(1),
+            ),
+            EcrImageHistory::new(
+                &image("sha256:cccc", &["latest"], 110.5),
+                start + Duration::days(2),
+            ),
+        ];
+        let timeline = repo_timeline(&history);
+        assert_eq!(timeline.len(), 4);
+
+        assert_eq!(timeline[3].size_delta, None);
+        assert!(timeline[3].pushed);
+        assert!(timeline[3].moved_tags.is_empty());
+
+        let retag = timeline.iter().find(|e| !e.pushed).expect("retag event");
+        assert_eq!(retag.digest, "sha256:aaaa");
+        assert_eq!(retag.size_delta, None);
+
+        assert_eq!(timeline[0].digest, "sha256:cccc");
+        assert_eq!(timeline[0].size_delta, Some(-10.0));
+        assert_eq!(
+            timeline[0].moved_tags,
+            vec![(
+                StackString::from("latest"),
+                StackString::from("sha256:bbbb")
+            )]
+        );
+        let second_push = timeline
+            .iter()
+            .find(|e| e.digest == "sha256:bbbb")
+            .expect("second push");
+        assert_eq!(second_push.size_delta, Some(20.5));
+        assert_eq!(
+            second_push.moved_tags,
+            vec![(
+                StackString::from("latest"),
+                StackString::from("sha256:aaaa")
+            )]
+        );
+    }
+}
@@ -1,8 +1,10 @@
-use anyhow::Error;
+use anyhow::{format_err, Error};
 use aws_config::SdkConfig;
 use aws_sdk_ecr::{types::ImageIdentifier, Client as EcrClient};
 use aws_types::region::Region;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use futures::{stream::FuturesUnordered, TryStreamExt};
+use serde::{Deserialize, Serialize};
 use stack_string::{format_sstr, StackString};
 use std::{fmt, sync::Arc};
 use time::{Duration, OffsetDateTime};
@@ -43,8 +45,8 @@ impl EcrInstance {
         let region: String = region.as_ref().into();
         let region = Region::new(region);
         self.region = region.clone();
-        let sdk_config = aws_config::from_env().region(region).load().await;
-        self.ecr_client = EcrClient::from_conf((&sdk_config).into());
+        let conf = self.ecr_client.config().to_builder().region(region).build();
+        self.ecr_client = EcrClient::from_conf(conf);
         Ok(())
     }
 
@@ -133,6 +135,30 @@ impl EcrInstance {
             .map(|_| ())
     }
 
+    /// Registry host (e.g. `123456789012.dkr.ecr.us-east-1.amazonaws.com`)
+    /// with the user name and password docker logs in to it with
+    /// # Errors
+    /// Returns error if aws api call fails or the token can't be decoded
+    pub async fn get_registry_login(&self) -> Result<RegistryLogin, Error> {
+        let data = self
+            .ecr_client
+            .get_authorization_token()
+            .send()
+            .await?
+            .authorization_data
+            .unwrap_or_default()
+            .into_iter()
+            .next()
+            .ok_or_else(|| format_err!("no ecr authorization data"))?;
+        let token = data
+            .authorization_token
+            .ok_or_else(|| format_err!("no ecr authorization token"))?;
+        let endpoint = data
+            .proxy_endpoint
+            .ok_or_else(|| format_err!("no ecr proxy endpoint"))?;
+        RegistryLogin::from_token(&endpoint, &token)
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn cleanup_ecr_images(&self) -> Result<(), Error> {
@@ -155,7 +181,47 @@ impl EcrInstance {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Clone, PartialEq)]
+pub struct RegistryLogin {
+    pub registry: StackString,
+    pub username: StackString,
+    pub password: StackString,
+}
+
+impl fmt::Debug for RegistryLogin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RegistryLogin({}@{})", self.username, self.registry)
+    }
+}
+
+impl RegistryLogin {
+    /// `token` is the base64 encoded `user:password` returned by
+    /// `GetAuthorizationToken`, `endpoint` the registry url
+    fn from_token(endpoint: &str, token: &str) -> Result<Self, Error> {
+        let decoded = STANDARD.decode(token)?;
+        let decoded = String::from_utf8(decoded)?;
+        let (username, password) = decoded
+            .split_once(':')
+            .ok_or_else(|| format_err!("invalid ecr authorization token"))?;
+        let registry = endpoint
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/');
+        Ok(Self {
+            registry: registry.into(),
+            username: username.into(),
+            password: password.into(),
+        })
+    }
+
+    /// Full reference of `tag` in the repository `repo` of this registry
+    #[must_use]
+    pub fn image(&self, repo: &str, tag: &str) -> StackString {
+        format_sstr!("{}/{repo}:{tag}", self.registry)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ImageInfo {
     pub repo: StackString,
     pub digest: StackString,
@@ -187,3 +253,28 @@ impl ImageInfo {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::ecr_instance::RegistryLogin;
+
+    #[test]
+    fn test_registry_login_from_token() -> Result<(), Error> {
+        // base64 of "AWS:secret-password"
+        let login = RegistryLogin::from_token(
+            "https://123456789012.dkr.ecr.us-east-1.amazonaws.com",
+            "QVdTOnNlY3JldC1wYXNzd29yZA==",
+        )?;
+        assert_eq!(login.username, "AWS");
+        assert_eq!(login.password, "secret-password");
+        assert_eq!(
+            login.image("novnc", "latest"),
+            "123456789012.dkr.ecr.us-east-1.amazonaws.com/novnc:latest"
+        );
+        assert!(!format!("{login:?}").contains("secret-password"));
+        assert!(RegistryLogin::from_token("https://x", "bm9jb2xvbg==").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::Error;
+use aws_config::SdkConfig;
+use aws_sdk_ecs::{
+    types::{Cluster, ContainerDefinition, LogDriver, Service},
+    Client as EcsClient,
+};
+use aws_types::region::Region;
+use futures::future::try_join_all;
+use serde::Serialize;
+use stack_string::{format_sstr, StackString};
+use std::{collections::HashMap, fmt};
+
+use crate::{config::Config, lambda_instance::log_group_url};
+
+/// `DescribeServices` accepts at most this many services per call
+const DESCRIBE_SERVICES_LIMIT: usize = 10;
+
+#[derive(Clone)]
+pub struct EcsInstance {
+    ecs_client: EcsClient,
+    region: StackString,
+}
+
+impl fmt::Debug for EcsInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("EcsInstance")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EcsClusterInfo {
+    pub cluster_name: StackString,
+    pub cluster_arn: StackString,
+    pub status: StackString,
+    pub active_services: i32,
+    pub running_tasks: i32,
+    pub pending_tasks: i32,
+    pub container_instances: i32,
+}
+
+impl EcsClusterInfo {
+    fn from_cluster(cluster: Cluster) -> Option<Self> {
+        Some(Self {
+            cluster_name: cluster.cluster_name?.into(),
+            cluster_arn: cluster.cluster_arn.unwrap_or_default().into(),
+            status: cluster.status.unwrap_or_default().into(),
+            active_services: cluster.active_services_count,
+            running_tasks: cluster.running_tasks_count,
+            pending_tasks: cluster.pending_tasks_count,
+            container_instances: cluster.registered_container_instances_count,
+        })
+    }
+}
+
+/// Cloudwatch log group a container of a task definition logs to
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TaskLogLink {
+    pub container: StackString,
+    pub log_group: StackString,
+    pub url: StackString,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EcsServiceInfo {
+    pub cluster_name: StackString,
+    pub service_name: StackString,
+    pub status: StackString,
+    pub launch_type: StackString,
+    /// `family:revision` of the task definition
+    pub task_definition: StackString,
+    pub task_definition_arn: StackString,
+    pub desired_count: i32,
+    pub running_count: i32,
+    pub pending_count: i32,
+    /// Rollout state of the primary deployment
+    pub rollout_state: StackString,
+    pub deployments: usize,
+    pub log_links: Vec<TaskLogLink>,
+}
+
+impl EcsServiceInfo {
+    fn from_service(service: Service) -> Option<Self> {
+        let cluster_arn = service.cluster_arn.unwrap_or_default();
+        let task_definition_arn: StackString = service.task_definition.unwrap_or_default().into();
+        let deployments = service.deployments.unwrap_or_default();
+        let rollout_state = deployments
+            .iter()
+            .find(|d| d.status.as_deref() == Some("PRIMARY"))
+            .and_then(|d| d.rollout_state.as_ref())
+            .map_or_else(StackString::new, |r| r.as_str().into());
+        Some(Self {
+            cluster_name: arn_resource_name(&cluster_arn).into(),
+            service_name: service.service_name?.into(),
+            status: service.status.unwrap_or_default().into(),
+            launch_type: service
+                .launch_type
+                .map_or_else(|| "CAPACITY_PROVIDER".into(), |l| l.as_str().into()),
+            task_definition: arn_resource_name(&task_definition_arn).into(),
+            task_definition_arn,
+            desired_count: service.desired_count,
+            running_count: service.running_count,
+            pending_count: service.pending_count,
+            rollout_state,
+            deployments: deployments.len(),
+            log_links: Vec::new(),
+        })
+    }
+
+    /// Fewer tasks running than desired
+    #[must_use]
+    pub fn is_degraded(&self) -> bool {
+        self.running_count < self.desired_count
+    }
+}
+
+/// Last path segment of an ecs arn, e.g. the cluster name of
+/// `arn:aws:ecs:us-east-1:123456789012:cluster/default`
+fn arn_resource_name(arn: &str) -> &str {
+    arn.rsplit('/').next().unwrap_or(arn)
+}
+
+/// Log links of the containers using the `awslogs` driver
+fn container_log_links(region: &str, containers: &[ContainerDefinition]) -> Vec<TaskLogLink> {
+    containers
+        .iter()
+        .filter_map(|container| {
+            let log_configuration = container.log_configuration()?;
+            if *log_configuration.log_driver() != LogDriver::Awslogs {
+                return None;
+            }
+            let options = log_configuration.options()?;
+            let log_group = options.get("awslogs-group")?;
+            let region = options.get("awslogs-region").map_or(region, String::as_str);
+            Some(TaskLogLink {
+                container: container.name().unwrap_or_default().into(),
+                log_group: log_group.as_str().into(),
+                url: log_group_url(region, log_group),
+            })
+        })
+        .collect()
+}
+
+impl EcsInstance {
+    #[must_use]
+    pub fn new(config: &Config, sdk_config: &SdkConfig) -> Self {
+        let region = sdk_config
+            .region()
+            .map_or_else(|| config.aws_region_name.clone(), |r| r.as_ref().into());
+        Self {
+            ecs_client: EcsClient::from_conf(sdk_config.into()),
+            region,
+        }
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
+        let region: String = region.as_ref().into();
+        self.region = region.as_str().into();
+        let region = Region::new(region);
+        let conf = self.ecs_client.config().to_builder().region(region).build();
+        self.ecs_client = EcsClient::from_conf(conf);
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_clusters(&self) -> Result<Vec<EcsClusterInfo>, Error> {
+        let mut cluster_arns = Vec::new();
+        let mut next_token = None;
+        loop {
+            let result = self
+                .ecs_client
+                .list_clusters()
+                .set_next_token(next_token)
+                .send()
+                .await?;
+            cluster_arns.extend(result.cluster_arns.unwrap_or_default());
+            next_token = result.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        if cluster_arns.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut clusters: Vec<_> = self
+            .ecs_client
+            .describe_clusters()
+            .set_clusters(Some(cluster_arns))
+            .send()
+            .await?
+            .clusters
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(EcsClusterInfo::from_cluster)
+            .collect();
+        clusters.sort_by(|a, b| a.cluster_name.cmp(&b.cluster_name));
+        Ok(clusters)
+    }
+
+    /// Services of `cluster` without their log links
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_services(&self, cluster: &str) -> Result<Vec<EcsServiceInfo>, Error> {
+        let mut service_arns = Vec::new();
+        let mut next_token = None;
+        loop {
+            let result = self
+                .ecs_client
+                .list_services()
+                .cluster(cluster)
+                .set_next_token(next_token)
+                .send()
+                .await?;
+            service_arns.extend(result.service_arns.unwrap_or_default());
+            next_token = result.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        let futures = service_arns
+            .chunks(DESCRIBE_SERVICES_LIMIT)
+            .map(|chunk| async move {
+                self.ecs_client
+                    .describe_services()
+                    .cluster(cluster)
+                    .set_services(Some(chunk.to_vec()))
+                    .send()
+                    .await
+                    .map_err(Into::<Error>::into)
+                    .map(|r| r.services.unwrap_or_default())
+            });
+        let mut services: Vec<_> = try_join_all(futures)
+            .await?
+            .into_iter()
+            .flatten()
+            .filter_map(EcsServiceInfo::from_service)
+            .collect();
+        services.sort_by(|a, b| a.service_name.cmp(&b.service_name));
+        Ok(services)
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_task_log_links(
+        &self,
+        task_definition: &str,
+    ) -> Result<Vec<TaskLogLink>, Error> {
+        let containers = self
+            .ecs_client
+            .describe_task_definition()
+            .task_definition(task_definition)
+            .send()
+            .await?
+            .task_definition
+            .and_then(|t| t.container_definitions)
+            .unwrap_or_default();
+        Ok(container_log_links(&self.region, &containers))
+    }
+
+    /// Services of every cluster along with the log links of their task
+    /// definitions
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_all_services(&self) -> Result<Vec<EcsServiceInfo>, Error> {
+        let clusters = self.list_clusters().await?;
+        let futures = clusters.iter().map(|c| self.list_services(&c.cluster_arn));
+        let mut services: Vec<_> = try_join_all(futures).await?.into_iter().flatten().collect();
+        let mut task_definitions: Vec<_> = services
+            .iter()
+            .map(|s| s.task_definition_arn.clone())
+            .filter(|t| !t.is_empty())
+            .collect();
+        task_definitions.sort();
+        task_definitions.dedup();
+        let futures = task_definitions
+            .into_iter()
+            .map(|task_definition| async move {
+                let links = self.get_task_log_links(&task_definition).await?;
+                Ok::<_, Error>((task_definition, links))
+            });
+        let log_links: HashMap<_, _> = try_join_all(futures).await?.into_iter().collect();
+        for service in &mut services {
+            if let Some(links) = log_links.get(&service.task_definition_arn) {
+                service.log_links.clone_from(links);
+            }
+        }
+        Ok(services)
+    }
+
+    /// Replace the running tasks of `service` with new ones, picking up an
+    /// updated image of the same tag
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn force_new_deployment(
+        &self,
+        cluster: &str,
+        service: &str,
+    ) -> Result<StackString, Error> {
+        let deployment = self
+            .ecs_client
+            .update_service()
+            .cluster(cluster)
+            .service(service)
+            .force_new_deployment(true)
+            .send()
+            .await?
+            .service
+            .and_then(|s| s.deployments)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|d| d.status.as_deref() == Some("PRIMARY"))
+            .and_then(|d| d.id);
+        Ok(deployment.map_or_else(
+            || format_sstr!("redeploying {cluster}/{service}"),
+            |id| format_sstr!("redeploying {cluster}/{service} as {id}"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_ecs::types::{ContainerDefinition, LogConfiguration, LogDriver};
+
+    use crate::ecs_instance::{arn_resource_name, container_log_links};
+
+    #[test]
+    fn test_arn_resource_name() {
+        assert_eq!(
+            arn_resource_name("arn:aws:ecs:us-east-1:123456789012:cluster/default"),
+            "default"
+        );
+        assert_eq!(
+            arn_resource_name("arn:aws:ecs:us-east-1:123456789012:task-definition/novnc:7"),
+            "novnc:7"
+        );
+        assert_eq!(arn_resource_name("default"), "default");
+    }
+
+    #[test]
+    fn test_container_log_links() {
+        let awslogs = LogConfiguration::builder()
+            .log_driver(LogDriver::Awslogs)
+            .options("awslogs-group", "/ecs/novnc")
+            .options("awslogs-stream-prefix", "ecs")
+            .build()
+            .unwrap();
+        let splunk = LogConfiguration::builder()
+            .log_driver(LogDriver::Splunk)
+            .build()
+            .unwrap();
+        let containers = [
+            ContainerDefinition::builder()
+                .name("novnc")
+                .log_configuration(awslogs)
+                .build(),
+            ContainerDefinition::builder()
+                .name("sidecar")
+                .log_configuration(splunk)
+                .build(),
+            ContainerDefinition::builder().name("nolog").build(),
+        ];
+        let links = container_log_links("us-east-1", &containers);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].container, "novnc");
+        assert_eq!(links[0].log_group, "/ecs/novnc");
+        assert_eq!(
+            links[0].url,
+            "https://us-east-1.console.aws.amazon.com/cloudwatch/home?region=us-east-1#logsV2:\
+             log-groups/log-group/$252Fecs$252Fnovnc"
+        );
+    }
+}
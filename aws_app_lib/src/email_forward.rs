@@ -0,0 +1,259 @@
+use anyhow::{format_err, Error};
+use log::debug;
+use regex::{Regex, RegexBuilder};
+use stack_string::StackString;
+
+use crate::{
+    config::Config,
+    models::{EmailForwardRule, InboundEmailDB},
+    ses_client::SesInstance,
+    storage::{EmailForwardRuleRepo, Storage},
+};
+
+/// Headers replaced when forwarding, SES only sends from verified addresses
+/// and rejects messages carrying the original signature
+const DROPPED_HEADERS: [&str; 9] = [
+    "from",
+    "to",
+    "cc",
+    "bcc",
+    "reply-to",
+    "return-path",
+    "sender",
+    "dkim-signature",
+    "message-id",
+];
+
+fn pattern(pattern: &str) -> Result<Regex, Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(Into::into)
+}
+
+/// # Errors
+/// Returns error if the rule has no pattern, a pattern is not a valid regex
+/// or `forward_to` is not an email address
+pub fn validate_rule(rule: &EmailForwardRule) -> Result<(), Error> {
+    if rule.recipient_pattern.is_none() && rule.subject_pattern.is_none() {
+        return Err(format_err!(
+            "Rule {} needs a recipient or subject pattern",
+            rule.name
+        ));
+    }
+    for p in rule
+        .recipient_pattern
+        .iter()
+        .chain(rule.subject_pattern.iter())
+    {
+        pattern(p)?;
+    }
+    if !rule.forward_to.contains('@') {
+        return Err(format_err!("{} is not an email address", rule.forward_to));
+    }
+    Ok(())
+}
+
+/// # Errors
+/// Returns error if a pattern is not a valid regex
+pub fn rule_matches(
+    rule: &EmailForwardRule,
+    to_address: &str,
+    subject: &str,
+) -> Result<bool, Error> {
+    if !rule.enabled || (rule.recipient_pattern.is_none() && rule.subject_pattern.is_none()) {
+        return Ok(false);
+    }
+    if let Some(p) = &rule.recipient_pattern {
+        if !pattern(p)?.is_match(to_address) {
+            return Ok(false);
+        }
+    }
+    if let Some(p) = &rule.subject_pattern {
+        if !pattern(p)?.is_match(subject) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Rules that would forward an email to `to_address` with `subject`
+/// # Errors
+/// Returns error if a pattern is not a valid regex
+pub fn matching_rules<'a>(
+    rules: &'a [EmailForwardRule],
+    to_address: &str,
+    subject: &str,
+) -> Result<Vec<&'a EmailForwardRule>, Error> {
+    let mut matches = Vec::new();
+    for rule in rules {
+        if rule_matches(rule, to_address, subject)? {
+            matches.push(rule);
+        }
+    }
+    Ok(matches)
+}
+
+/// Rewrite the headers of `raw_email` so it can be sent from `from` to `to`,
+/// the original sender becomes the `Reply-To` and the body is untouched
+#[must_use]
+pub fn forwarded_message(raw_email: &str, from: &str, to: &str) -> String {
+    let newline = if raw_email.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let separator = if newline == "\r\n" {
+        "\r\n\r\n"
+    } else {
+        "\n\n"
+    };
+    let (headers, body) = raw_email.split_once(separator).unwrap_or((raw_email, ""));
+
+    let mut original_from: Option<String> = None;
+    let mut kept = Vec::new();
+    let mut keep = true;
+    let mut in_from = false;
+    for line in headers.split(newline) {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if in_from {
+                if let Some(f) = original_from.as_mut() {
+                    f.push(' ');
+                    f.push_str(line.trim());
+                }
+            }
+        } else {
+            let name = line.split(':').next().unwrap_or("").trim().to_lowercase();
+            keep = !DROPPED_HEADERS.contains(&name.as_str());
+            in_from = name == "from";
+            if in_from {
+                original_from = line.split_once(':').map(|(_, v)| v.trim().into());
+            }
+        }
+        if keep {
+            kept.push(line);
+        }
+    }
+
+    let mut message = format!("From: {from}{newline}To: {to}{newline}");
+    if let Some(original_from) = original_from {
+        message.push_str(&format!("Reply-To: {original_from}{newline}"));
+    }
+    for line in kept {
+        message.push_str(line);
+        message.push_str(newline);
+    }
+    message.push_str(newline);
+    message.push_str(body);
+    message
+}
+
+/// Forward `email` to every matching rule's address, spam is never
+/// forwarded. Returns the addresses it was sent to.
+/// # Errors
+/// Returns error if db query or ses api call fails
+pub async fn forward_email(
+    config: &Config,
+    ses: &SesInstance,
+    storage: &dyn Storage,
+    email: &InboundEmailDB,
+) -> Result<Vec<StackString>, Error> {
+    if email.is_spam() {
+        return Ok(Vec::new());
+    }
+    let from = match &config.email_forward_from {
+        Some(from) => from,
+        None => {
+            debug!("email_forward_from not set, not forwarding {}", email.id);
+            return Ok(Vec::new());
+        }
+    };
+    let rules = storage.get_forward_rules().await?;
+    let mut forwarded = Vec::new();
+    for rule in matching_rules(&rules, &email.to_address, &email.subject)? {
+        let message = forwarded_message(&email.raw_email, from, &rule.forward_to);
+        ses.send_raw_email(from.as_str(), rule.forward_to.as_str(), message.as_bytes())
+            .await?;
+        forwarded.push(rule.forward_to.clone());
+    }
+    Ok(forwarded)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::{
+        email_forward::{forwarded_message, matching_rules, rule_matches, validate_rule},
+        models::EmailForwardRule,
+    };
+
+    const RAW_EMAIL: &str = "\
+From: Alice <alice@example.com>
+To: bob@inbound.example.org
+DKIM-Signature: v=1; a=rsa-sha256;
+ b=abcdef
+Subject: Invoice 1234
+Content-Type: text/plain
+
+Please pay the invoice
+From the desk of Alice
+";
+
+    #[test]
+    fn test_rule_matches() -> Result<(), Error> {
+        let invoices = EmailForwardRule::new(
+            "invoices",
+            Some("^bob@".into()),
+            Some("invoice".into()),
+            "accounting@example.net",
+        );
+        let mut catch_all = EmailForwardRule::new(
+            "catch all",
+            Some(r"@inbound\.example\.org$".into()),
+            None,
+            "me@example.net",
+        );
+        validate_rule(&invoices)?;
+        validate_rule(&catch_all)?;
+
+        assert!(rule_matches(
+            &invoices,
+            "bob@inbound.example.org",
+            "INVOICE 1234"
+        )?);
+        assert!(!rule_matches(
+            &invoices,
+            "bob@inbound.example.org",
+            "Hello"
+        )?);
+        assert!(!rule_matches(
+            &invoices,
+            "carol@inbound.example.org",
+            "Invoice"
+        )?);
+
+        catch_all.enabled = false;
+        let rules = [invoices.clone(), catch_all.clone()];
+        let matches = matching_rules(&rules, "bob@inbound.example.org", "Invoice 1")?;
+        assert_eq!(matches, vec![&invoices]);
+
+        let no_pattern = EmailForwardRule::new("empty", None, None, "me@example.net");
+        assert!(validate_rule(&no_pattern).is_err());
+        let bad_regex = EmailForwardRule::new("bad", Some("(".into()), None, "me@example.net");
+        assert!(validate_rule(&bad_regex).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_forwarded_message() {
+        let message = forwarded_message(RAW_EMAIL, "forward@example.org", "me@example.net");
+        let (headers, body) = message.split_once("\n\n").unwrap();
+        assert_eq!(
+            headers,
+            "From: forward@example.org\nTo: me@example.net\nReply-To: Alice \
+             <alice@example.com>\nSubject: Invoice 1234\nContent-Type: text/plain"
+        );
+        assert_eq!(body, "Please pay the invoice\nFrom the desk of Alice\n");
+    }
+}
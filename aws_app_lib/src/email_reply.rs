@@ -0,0 +1,213 @@
+use anyhow::{format_err, Error};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use mail_parser::MessageParser;
+use stack_string::{format_sstr, StackString};
+use time::{format_description::well_known::Rfc2822, OffsetDateTime};
+
+use crate::{
+    email_thread::ThreadHeaders,
+    models::{InboundEmailDB, SentEmail},
+    ses_client::SesInstance,
+    storage::{SentEmailRepo, Storage},
+};
+
+/// `subject` with a single `Re: ` prefix
+#[must_use]
+pub fn reply_subject(subject: &str) -> StackString {
+    let subject = subject.trim();
+    if subject
+        .get(..3)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("re:"))
+    {
+        subject.into()
+    } else {
+        format_sstr!("Re: {subject}")
+    }
+}
+
+/// `text` with every line prefixed by `> `
+#[must_use]
+pub fn quote_text(text: &str) -> String {
+    text.trim_end()
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                ">".into()
+            } else {
+                format!("> {line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+/// RFC 2047 encoded word for header values that aren't plain ascii
+fn encode_header(value: &str) -> StackString {
+    if value.is_ascii() {
+        value.into()
+    } else {
+        format_sstr!("=?UTF-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+fn check_header(value: &str) -> Result<&str, Error> {
+    if value.contains(|c| c == '\r' || c == '\n') {
+        return Err(format_err!("Invalid header value {value:?}"));
+    }
+    Ok(value)
+}
+
+/// Plain text reply to `email`, `thread` holds the threading headers of
+/// `email` so the reply's `In-Reply-To` and `References` put it in the same
+/// conversation for the recipient
+/// # Errors
+/// Returns error if an address contains a line break
+pub fn reply_message(
+    email: &InboundEmailDB,
+    thread: &ThreadHeaders,
+    from: &str,
+    to: &str,
+    body: &str,
+    date: OffsetDateTime,
+) -> Result<String, Error> {
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\n",
+        check_header(from)?,
+        check_header(to)?,
+        encode_header(&reply_subject(&email.subject)),
+        date.format(&Rfc2822)?,
+    );
+    if let Some(message_id) = &thread.message_id {
+        let mut references: Vec<_> = thread
+            .references
+            .iter()
+            .filter(|r| *r != message_id)
+            .map(|r| format_sstr!("<{r}>"))
+            .collect();
+        references.push(format_sstr!("<{message_id}>"));
+        message.push_str(&format!(
+            "In-Reply-To: <{message_id}>\r\nReferences: {}\r\n",
+            references.join(" ")
+        ));
+    }
+    message.push_str(
+        "MIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\
+         Content-Transfer-Encoding: 8bit\r\n\r\n",
+    );
+    message.push_str(&body.trim_end().lines().collect::<Vec<_>>().join("\r\n"));
+    let original_date = email.date.format(&Rfc2822)?;
+    message.push_str(&format!(
+        "\r\n\r\nOn {original_date}, {} wrote:\r\n{}\r\n",
+        email.from_address,
+        quote_text(&email.text_content)
+    ));
+    Ok(message)
+}
+
+/// Reply to `email` from the address it was sent to, addressed to its
+/// `Reply-To` or sender, and record the reply in `sent_email`
+/// # Errors
+/// Returns error if `body` is empty, the ses api call or db query fails
+pub async fn send_reply(
+    ses: &SesInstance,
+    storage: &dyn Storage,
+    email: &InboundEmailDB,
+    body: &str,
+) -> Result<SentEmail, Error> {
+    if body.trim().is_empty() {
+        return Err(format_err!("Reply is empty"));
+    }
+    let message = MessageParser::default()
+        .parse(email.raw_email.as_bytes())
+        .ok_or_else(|| format_err!("Failed to parse email {}", email.id))?;
+    let thread = ThreadHeaders::from_message(&message);
+    let to: StackString = message
+        .reply_to()
+        .and_then(|a| a.first())
+        .and_then(|a| a.address())
+        .map_or_else(|| email.from_address.clone(), Into::into);
+    let from = &email.to_address;
+
+    let raw = reply_message(email, &thread, from, &to, body, OffsetDateTime::now_utc())?;
+    let message_id = ses
+        .send_raw_email(from.as_str(), to.as_str(), raw.as_bytes())
+        .await?;
+
+    let mut sent = SentEmail::new(email, from, to, reply_subject(&email.subject), body);
+    sent.message_id = Some(message_id);
+    storage.insert_sent_email(&sent).await?;
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{format_err, Error};
+    use mail_parser::MessageParser;
+    use std::convert::TryInto;
+    use time::macros::datetime;
+
+    use crate::{
+        email_reply::{quote_text, reply_message, reply_subject},
+        email_thread::ThreadHeaders,
+        inbound_email::InboundEmail,
+    };
+
+    const ORIGINAL: &str = "From: postmaster@example.org\r\n\
+        To: dmarc@example.com\r\n\
+        Subject: DMARC failures\r\n\
+        Message-ID: <b2@example.org>\r\n\
+        References: <a1@example.com>\r\n\
+        \r\n\
+        Your mail fails SPF.\r\n\
+        \r\n\
+        Regards\r\n";
+
+    #[test]
+    fn test_reply_subject() {
+        assert_eq!(reply_subject("DMARC failures"), "Re: DMARC failures");
+        assert_eq!(reply_subject("RE: DMARC failures"), "RE: DMARC failures");
+        assert_eq!(reply_subject("Привет"), "Re: Привет");
+        assert_eq!(reply_subject("ré"), "Re: ré");
+        assert_eq!(quote_text("a\n\nb\n"), "> a\r\n>\r\n> b");
+    }
+
+    #[test]
+    fn test_reply_message() -> Result<(), Error> {
+        let message = MessageParser::default()
+            .parse(ORIGINAL.as_bytes())
+            .ok_or_else(|| format_err!("failed to parse"))?;
+        let thread = ThreadHeaders::from_message(&message);
+        let email: InboundEmail = message.try_into()?;
+        let email = email.into_db("bucket", "key");
+
+        let raw = reply_message(
+            &email,
+            &thread,
+            "dmarc@example.com",
+            "postmaster@example.org",
+            "Fixed, thanks.\n",
+            datetime!(2024-06-01 12:00 UTC),
+        )?;
+        let reply = MessageParser::default()
+            .parse(raw.as_bytes())
+            .ok_or_else(|| format_err!("failed to parse reply"))?;
+        assert_eq!(reply.subject(), Some("Re: DMARC failures"));
+        let headers = ThreadHeaders::from_message(&reply);
+        assert_eq!(headers.in_reply_to.as_deref(), Some("b2@example.org"));
+        assert_eq!(headers.references, vec!["a1@example.com", "b2@example.org"]);
+        let text = reply.body_text(0).ok_or_else(|| format_err!("no body"))?;
+        assert!(text.starts_with("Fixed, thanks."));
+        assert!(text.contains("> Your mail fails SPF."));
+
+        assert!(reply_message(
+            &email,
+            &thread,
+            "dmarc@example.com",
+            "a@example.org\r\nBcc: b@example.org",
+            "hi",
+            datetime!(2024-06-01 12:00 UTC),
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::Error;
+use mail_parser::{HeaderValue, Message};
+use stack_string::StackString;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    models::{InboundEmailDB, SentEmail},
+    storage::{InboundEmailRepo, SentEmailRepo, Storage},
+};
+
+/// Threading headers of an email, message ids are stored without the
+/// surrounding angle brackets
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThreadHeaders {
+    pub message_id: Option<StackString>,
+    pub in_reply_to: Option<StackString>,
+    pub references: Vec<StackString>,
+}
+
+impl ThreadHeaders {
+    #[must_use]
+    pub fn from_message(message: &Message) -> Self {
+        Self {
+            message_id: message.message_id().map(Into::into),
+            in_reply_to: message_ids(message.in_reply_to()).into_iter().next(),
+            references: message_ids(message.references()),
+        }
+    }
+
+    /// Ids of the emails this one replies to, closest ancestor first
+    #[must_use]
+    pub fn parent_ids(&self) -> Vec<StackString> {
+        let mut ids: Vec<StackString> = self.in_reply_to.iter().cloned().collect();
+        for id in self.references.iter().rev() {
+            if !ids.contains(id) {
+                ids.push(id.clone());
+            }
+        }
+        ids
+    }
+}
+
+fn message_ids(value: &HeaderValue) -> Vec<StackString> {
+    match value {
+        HeaderValue::Text(id) => vec![id.as_ref().into()],
+        HeaderValue::TextList(ids) => ids.iter().map(|id| id.as_ref().into()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Put `email` in the thread of the closest known email (or reply sent from
+/// the dashboard) it replies to, and pull in the replies to it that were
+/// synced before it
+/// # Errors
+/// Returns error if db query fails
+pub async fn assign_thread(
+    email: &mut InboundEmailDB,
+    headers: &ThreadHeaders,
+    storage: &dyn Storage,
+) -> Result<(), Error> {
+    for parent in headers.parent_ids() {
+        let thread_id = match storage.get_email_thread_id(&parent).await? {
+            Some(thread_id) => Some(thread_id),
+            None => storage.get_sent_thread_id(&parent).await?,
+        };
+        if let Some(thread_id) = thread_id {
+            email.thread_id = thread_id;
+            break;
+        }
+    }
+    if let Some(message_id) = &email.message_id {
+        storage.merge_replies(message_id, email.thread_id).await?;
+    }
+    Ok(())
+}
+
+/// Emails sharing a `thread_id` and the replies sent to them, oldest first
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmailThread {
+    pub thread_id: Uuid,
+    pub emails: Vec<InboundEmailDB>,
+    pub sent: Vec<SentEmail>,
+}
+
+/// Received or sent message of a thread
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThreadEntry<'a> {
+    Received(&'a InboundEmailDB),
+    Sent(&'a SentEmail),
+}
+
+impl ThreadEntry<'_> {
+    #[must_use]
+    pub fn date(&self) -> OffsetDateTime {
+        match self {
+            Self::Received(email) => email.date,
+            Self::Sent(sent) => sent.sent_at,
+        }
+    }
+}
+
+impl EmailThread {
+    /// Most recent received email of the thread, shown when the thread is
+    /// collapsed
+    #[must_use]
+    pub fn latest(&self) -> Option<&InboundEmailDB> {
+        self.emails.last()
+    }
+
+    #[must_use]
+    pub fn last_date(&self) -> Option<OffsetDateTime> {
+        self.latest().map(|e| e.date)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.emails.len() + self.sent.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every message except `latest`, newest first
+    #[must_use]
+    pub fn earlier(&self) -> Vec<ThreadEntry> {
+        let received = self.emails.len().saturating_sub(1);
+        let mut entries: Vec<_> = self.emails[..received]
+            .iter()
+            .map(ThreadEntry::Received)
+            .chain(self.sent.iter().map(ThreadEntry::Sent))
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.date()));
+        entries
+    }
+}
+
+/// Group `emails` and the replies `sent` to them into conversations, ordered
+/// by their most recent received email
+#[must_use]
+pub fn group_threads(emails: Vec<InboundEmailDB>, sent: Vec<SentEmail>) -> Vec<EmailThread> {
+    let mut threads: Vec<EmailThread> = Vec::new();
+    for email in emails {
+        match threads.iter_mut().find(|t| t.thread_id == email.thread_id) {
+            Some(thread) => thread.emails.push(email),
+            None => threads.push(EmailThread {
+                thread_id: email.thread_id,
+                emails: vec![email],
+                sent: Vec::new(),
+            }),
+        }
+    }
+    for reply in sent {
+        if let Some(thread) = threads.iter_mut().find(|t| t.thread_id == reply.thread_id) {
+            thread.sent.push(reply);
+        }
+    }
+    for thread in &mut threads {
+        thread.emails.sort_by_key(|e| e.date);
+        thread.sent.sort_by_key(|e| e.sent_at);
+    }
+    threads.sort_by_key(EmailThread::last_date);
+    threads
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{format_err, Error};
+    use mail_parser::MessageParser;
+    use std::convert::TryInto;
+    use time::{macros::datetime, Duration};
+    use uuid::Uuid;
+
+    use crate::{
+        email_thread::{group_threads, ThreadEntry, ThreadHeaders},
+        inbound_email::InboundEmail,
+        models::SentEmail,
+    };
+
+    const REPLY: &str = "From: postmaster@example.org\r\n\
+        To: dmarc@example.com\r\n\
+        Subject: Re: Re: DMARC failures\r\n\
+        Message-ID: <c3@example.org>\r\n\
+        In-Reply-To: <b2@example.com>\r\n\
+        References: <a1@example.org> <b2@example.com>\r\n\
+        \r\n\
+        Fixed now.\r\n";
+
+    #[test]
+    fn test_thread_headers() -> Result<(), Error> {
+        let message = MessageParser::default()
+            .parse(REPLY.as_bytes())
+            .ok_or_else(|| format_err!("failed to parse"))?;
+        let headers = ThreadHeaders::from_message(&message);
+        assert_eq!(headers.message_id.as_deref(), Some("c3@example.org"));
+        assert_eq!(headers.in_reply_to.as_deref(), Some("b2@example.com"));
+        assert_eq!(headers.references.len(), 2);
+        assert_eq!(
+            headers.parent_ids(),
+            vec!["b2@example.com", "a1@example.org"]
+        );
+
+        let message = MessageParser::default()
+            .parse(b"From: a@example.org\r\nTo: b@example.com\r\nSubject: hi\r\n\r\nhi\r\n")
+            .ok_or_else(|| format_err!("failed to parse"))?;
+        let headers = ThreadHeaders::from_message(&message);
+        assert_eq!(headers, ThreadHeaders::default());
+        assert!(headers.parent_ids().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_threads() -> Result<(), Error> {
+        let start = datetime!(2024-06-01 00:00 UTC);
+        let thread_id = Uuid::new_v4();
+
+        let mut emails = Vec::new();
+        for (minutes, thread) in [(10, thread_id), (0, thread_id), (5, Uuid::new_v4())] {
+            let message = MessageParser::default()
+                .parse(REPLY.as_bytes())
+                .ok_or_else(|| format_err!("failed to parse"))?;
+            let email: InboundEmail = message.try_into()?;
+            let mut email = email.into_db("bucket", "key");
+            email.date = start + Duration::minutes(minutes);
+            email.thread_id = thread;
+            emails.push(email);
+        }
+
+        let threads = group_threads(emails);
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].emails.len(), 1);
+        assert_eq!(threads[1].thread_id, thread_id);
+        assert_eq!(threads[1].emails[0].date, start);
+        assert_eq!(threads[1].last_date(), Some(start + Duration::minutes(10)));
+        Ok(())
+    }
+}
@@ -0,0 +1,413 @@
+use anyhow::{format_err, Error};
+use log::{debug, warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use stack_string::{format_sstr, StackString};
+use std::{collections::HashSet, fmt, process::Stdio, str::FromStr, time::Duration};
+use tokio::{process::Command, time::timeout};
+
+use crate::{
+    config::Config,
+    models::AuditLog,
+    storage::{AuditLogRepo, Storage},
+};
+
+/// Characters of hook output kept in the audit log
+const MAX_OUTPUT_LEN: usize = 1000;
+
+/// Lifecycle points hooks run at
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    /// Before a spot request is made, a failing hook cancels the request
+    PreLaunch,
+    /// Before instances are terminated, a failing hook cancels the
+    /// termination
+    PreTerminate,
+    SpotFulfilled,
+    SnapshotCompleted,
+}
+
+impl HookEvent {
+    pub const ALL: [Self; 4] = [
+        Self::PreLaunch,
+        Self::PreTerminate,
+        Self::SpotFulfilled,
+        Self::SnapshotCompleted,
+    ];
+
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::PreLaunch => "pre_launch",
+            Self::PreTerminate => "pre_terminate",
+            Self::SpotFulfilled => "spot_fulfilled",
+            Self::SnapshotCompleted => "snapshot_completed",
+        }
+    }
+
+    /// Whether a failing hook stops the action it runs before
+    #[must_use]
+    pub fn is_blocking(self) -> bool {
+        matches!(self, Self::PreLaunch | Self::PreTerminate)
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for HookEvent {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|e| e.to_str() == s)
+            .copied()
+            .ok_or_else(|| format_err!("{s} is not a HookEvent"))
+    }
+}
+
+/// What a hook runs: urls are POSTed the event as json, anything else is run
+/// with `sh -c`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookTarget {
+    Command(StackString),
+    Http(StackString),
+}
+
+impl From<&str> for HookTarget {
+    fn from(s: &str) -> Self {
+        let s = s.trim();
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Self::Http(s.into())
+        } else {
+            Self::Command(s.into())
+        }
+    }
+}
+
+impl fmt::Display for HookTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Command(command) | Self::Http(command) => f.write_str(command),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    pub event: HookEvent,
+    pub target: HookTarget,
+}
+
+impl FromStr for Hook {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (event, target) = s
+            .split_once('=')
+            .ok_or_else(|| format_err!("Invalid hook {s}"))?;
+        let event: HookEvent = event.trim().parse()?;
+        if target.trim().is_empty() {
+            return Err(format_err!("Invalid hook {s}"));
+        }
+        Ok(Self {
+            event,
+            target: target.into(),
+        })
+    }
+}
+
+impl Hook {
+    /// Run the hook for `resource_id`, returns its output
+    /// # Errors
+    /// Returns error if the command exits unsuccessfully, the url returns an
+    /// error status or the hook times out
+    pub async fn run(
+        &self,
+        resource_id: &str,
+        context: &Value,
+        time_limit: Duration,
+    ) -> Result<StackString, Error> {
+        match &self.target {
+            HookTarget::Command(command) => {
+                let output = Command::new("sh")
+                    .args(["-c", command.as_str()])
+                    .envs(hook_env(self.event, resource_id, context))
+                    .stdin(Stdio::null())
+                    .kill_on_drop(true)
+                    .output();
+                let output = timeout(time_limit, output)
+                    .await
+                    .map_err(|_| format_err!("timed out after {time_limit:?}"))??;
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                if output.status.success() {
+                    Ok(truncate_output(&stdout))
+                } else {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    Err(format_err!(
+                        "exited with {}: {}",
+                        output.status,
+                        truncate_output(&format_sstr!("{stdout}{stderr}"))
+                    ))
+                }
+            }
+            HookTarget::Http(url) => {
+                let body = json!({
+                    "event": self.event,
+                    "resource_id": resource_id,
+                    "context": context,
+                });
+                let resp = Client::new()
+                    .post(url.as_str())
+                    .timeout(time_limit)
+                    .json(&body)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(format_sstr!("{}", resp.status()))
+            }
+        }
+    }
+}
+
+/// Hooks configured in `hooks`, leaving out the events listed in
+/// `disabled_hooks`
+#[derive(Debug, Clone)]
+pub struct Hooks {
+    hooks: Vec<Hook>,
+    disabled: HashSet<HookEvent>,
+    timeout: Duration,
+}
+
+impl Hooks {
+    /// Parse `hooks` entries of the form `event=command` or `event=url`,
+    /// e.g. `pre_terminate=/usr/local/bin/drain.sh`
+    /// # Errors
+    /// Returns error if an entry is malformed or names an unknown event
+    pub fn from_config(config: &Config) -> Result<Self, Error> {
+        let hooks = config
+            .hooks
+            .iter()
+            .map(|entry| entry.parse())
+            .collect::<Result<_, Error>>()?;
+        let disabled = config
+            .disabled_hooks
+            .iter()
+            .map(|event| event.trim().parse())
+            .collect::<Result<_, Error>>()?;
+        Ok(Self {
+            hooks,
+            disabled,
+            timeout: Duration::from_secs(config.hook_timeout),
+        })
+    }
+
+    /// Enabled hooks of `event`
+    pub fn for_event(&self, event: HookEvent) -> impl Iterator<Item = &Hook> {
+        let enabled = !self.disabled.contains(&event);
+        self.hooks
+            .iter()
+            .filter(move |hook| enabled && hook.event == event)
+    }
+
+    #[must_use]
+    pub fn has_event(&self, event: HookEvent) -> bool {
+        self.for_event(event).next().is_some()
+    }
+
+    /// Run the enabled hooks of `event` in the order configured, each run is
+    /// recorded in the audit log. Failures of blocking events stop at the
+    /// first failing hook, other failures are only logged.
+    /// # Errors
+    /// Returns error if db query fails or a hook of a blocking event fails
+    pub async fn run(
+        &self,
+        storage: &dyn Storage,
+        event: HookEvent,
+        resource_id: &str,
+        context: &Value,
+    ) -> Result<(), Error> {
+        for hook in self.for_event(event) {
+            debug!("running {event} hook {} for {resource_id}", hook.target);
+            let result = hook.run(resource_id, context, self.timeout).await;
+            let details = match &result {
+                Ok(output) => format_sstr!("{}: ok {output}", hook.target),
+                Err(e) => format_sstr!("{}: failed {e}", hook.target),
+            };
+            storage
+                .insert_audit_log(&AuditLog::new(
+                    format_sstr!("hook_{event}"),
+                    resource_id,
+                    Some(details),
+                ))
+                .await?;
+            if let Err(e) = result {
+                if event.is_blocking() {
+                    return Err(format_err!("{event} hook {} failed: {e}", hook.target));
+                }
+                warn!("{event} hook {} failed: {e}", hook.target);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Environment a command hook runs with: `AWS_APP_HOOK_EVENT`,
+/// `AWS_APP_RESOURCE_ID`, the whole context as json in
+/// `AWS_APP_HOOK_CONTEXT` and each scalar field of the context as
+/// `AWS_APP_<FIELD>`
+#[must_use]
+pub fn hook_env(
+    event: HookEvent,
+    resource_id: &str,
+    context: &Value,
+) -> Vec<(StackString, StackString)> {
+    let mut env = vec![
+        ("AWS_APP_HOOK_EVENT".into(), event.to_str().into()),
+        ("AWS_APP_RESOURCE_ID".into(), resource_id.into()),
+        ("AWS_APP_HOOK_CONTEXT".into(), context.to_string().into()),
+    ];
+    if let Some(fields) = context.as_object() {
+        for (key, value) in fields {
+            let value: StackString = match value {
+                Value::String(s) => s.into(),
+                Value::Number(n) => format_sstr!("{n}"),
+                Value::Bool(b) => format_sstr!("{b}"),
+                _ => continue,
+            };
+            let key: String = key
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_uppercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            env.push((format_sstr!("AWS_APP_{key}"), value));
+        }
+    }
+    env
+}
+
+fn truncate_output(output: &str) -> StackString {
+    let output = output.trim();
+    match output.char_indices().nth(MAX_OUTPUT_LEN) {
+        Some((idx, _)) => format_sstr!("{}...", &output[..idx]),
+        None => output.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use serde_json::json;
+    use std::time::Duration;
+
+    use crate::{
+        config::{Config, ConfigInner},
+        hooks::{hook_env, Hook, HookEvent, HookTarget, Hooks},
+    };
+
+    fn config(hooks: &[&str], disabled_hooks: &[&str]) -> Config {
+        Config::from_inner(ConfigInner {
+            hooks: hooks.iter().map(|&h| h.into()).collect(),
+            disabled_hooks: disabled_hooks.iter().map(|&h| h.into()).collect(),
+            hook_timeout: 5,
+            ..ConfigInner::default()
+        })
+    }
+
+    #[test]
+    fn test_hooks_from_config() -> Result<(), Error> {
+        let hooks = Hooks::from_config(&config(
+            &[
+                "pre_terminate=/usr/local/bin/drain.sh",
+                "spot_fulfilled = https://example.com/hook",
+                "snapshot_completed=echo done",
+            ],
+            &["snapshot_completed"],
+        ))?;
+        let pre_terminate: Vec<_> = hooks.for_event(HookEvent::PreTerminate).collect();
+        assert_eq!(
+            pre_terminate,
+            vec![&Hook {
+                event: HookEvent::PreTerminate,
+                target: HookTarget::Command("/usr/local/bin/drain.sh".into()),
+            }]
+        );
+        assert_eq!(
+            hooks
+                .for_event(HookEvent::SpotFulfilled)
+                .map(|h| h.target.clone())
+                .collect::<Vec<_>>(),
+            vec![HookTarget::Http("https://example.com/hook".into())]
+        );
+        assert!(!hooks.has_event(HookEvent::SnapshotCompleted));
+        assert!(!hooks.has_event(HookEvent::PreLaunch));
+
+        assert!(Hooks::from_config(&config(&["post_terminate=echo"], &[])).is_err());
+        assert!(Hooks::from_config(&config(&["pre_launch"], &[])).is_err());
+        assert!(Hooks::from_config(&config(&["pre_launch="], &[])).is_err());
+        assert!(Hooks::from_config(&config(&[], &["pre_launch", "reboot"])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hook_env() {
+        let context = json!({
+            "instance_id": "i-1234",
+            "instance-type": "t3.micro",
+            "price": 0.0104,
+            "tags": {"Name": "test"},
+        });
+        let env = hook_env(HookEvent::PreTerminate, "i-1234", &context);
+        let get = |key: &str| env.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        assert_eq!(get("AWS_APP_HOOK_EVENT"), Some("pre_terminate"));
+        assert_eq!(get("AWS_APP_RESOURCE_ID"), Some("i-1234"));
+        assert_eq!(get("AWS_APP_INSTANCE_ID"), Some("i-1234"));
+        assert_eq!(get("AWS_APP_INSTANCE_TYPE"), Some("t3.micro"));
+        assert_eq!(get("AWS_APP_PRICE"), Some("0.0104"));
+        assert_eq!(get("AWS_APP_TAGS"), None);
+        assert_eq!(
+            get("AWS_APP_HOOK_CONTEXT")
+                .map(serde_json::from_str::<serde_json::Value>)
+                .transpose()
+                .unwrap(),
+            Some(context)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_hook() -> Result<(), Error> {
+        let hook = Hook {
+            event: HookEvent::SnapshotCompleted,
+            target: r#"test "$AWS_APP_VOLUME_ID" = vol-1 && echo "$AWS_APP_RESOURCE_ID""#.into(),
+        };
+        let context = json!({"volume_id": "vol-1"});
+        let output = hook.run("snap-1", &context, Duration::from_secs(5)).await?;
+        assert_eq!(output, "snap-1");
+
+        let context = json!({"volume_id": "vol-2"});
+        assert!(hook
+            .run("snap-1", &context, Duration::from_secs(5))
+            .await
+            .is_err());
+
+        let hook = Hook {
+            event: HookEvent::PreTerminate,
+            target: "sleep 5".into(),
+        };
+        let e = hook
+            .run("i-1", &context, Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "timed out after 100ms");
+        Ok(())
+    }
+}
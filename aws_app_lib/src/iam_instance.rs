@@ -1,8 +1,8 @@
-use anyhow::Error;
+use anyhow::{format_err, Error};
 use aws_config::SdkConfig;
 pub use aws_sdk_iam::types::AccessKeyMetadata;
 use aws_sdk_iam::{
-    types::{AccessKey, Group, User},
+    types::{AccessKey, Group, InstanceProfile, ReportStateType, User},
     Client as IamClient,
 };
 use aws_types::region::Region;
@@ -10,9 +10,13 @@ use serde::{Deserialize, Serialize};
 use stack_string::StackString;
 use std::collections::HashMap;
 use time::OffsetDateTime;
+use tokio::time::{sleep, Duration};
 
 use crate::date_time_wrapper::DateTimeWrapper;
 
+/// Times `generate_credential_report` is polled, two seconds apart
+const CREDENTIAL_REPORT_POLLS: usize = 15;
+
 #[derive(Clone)]
 pub struct IamInstance {
     iam_client: IamClient,
@@ -31,8 +35,8 @@ impl IamInstance {
     pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
         let region: String = region.as_ref().into();
         let region = Region::new(region);
-        let sdk_config = aws_config::from_env().region(region).load().await;
-        self.iam_client = IamClient::from_conf((&sdk_config).into());
+        let conf = self.iam_client.config().to_builder().region(region).build();
+        self.iam_client = IamClient::from_conf(conf);
         Ok(())
     }
 
@@ -65,6 +69,22 @@ impl IamInstance {
             .map_err(Into::into)
     }
 
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_instance_profiles(
+        &self,
+    ) -> Result<impl Iterator<Item = IamInstanceProfile>, Error> {
+        let profiles = self
+            .iam_client
+            .list_instance_profiles()
+            .send()
+            .await?
+            .instance_profiles
+            .into_iter()
+            .map(IamInstanceProfile::from_instance_profile);
+        Ok(profiles)
+    }
+
     /// # Errors
     /// Returns error if aws api call fails
     pub async fn list_groups(&self) -> Result<impl Iterator<Item = IamGroup>, Error> {
@@ -204,6 +224,86 @@ impl IamInstance {
             .map(|_| ())
             .map_err(Into::into)
     }
+
+    /// Mfa devices (virtual, hardware and security keys) of `user_name`,
+    /// `is_virtual` isn't reported by this call and is left false
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_mfa_devices(
+        &self,
+        user_name: impl Into<String>,
+    ) -> Result<impl Iterator<Item = MfaDevice>, Error> {
+        let devices = self
+            .iam_client
+            .list_mfa_devices()
+            .user_name(user_name)
+            .send()
+            .await?
+            .mfa_devices
+            .into_iter()
+            .map(|d| MfaDevice {
+                user_name: Some(d.user_name.into()),
+                serial_number: d.serial_number.into(),
+                enable_date:
+                    OffsetDateTime::from_unix_timestamp(d.enable_date.as_secs_f64() as i64)
+                        .ok()
+                        .map(Into::into),
+                is_virtual: false,
+            });
+        Ok(devices)
+    }
+
+    /// Virtual mfa devices of the account, unassigned devices have no
+    /// `user_name`
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_virtual_mfa_devices(&self) -> Result<impl Iterator<Item = MfaDevice>, Error> {
+        let devices = self
+            .iam_client
+            .list_virtual_mfa_devices()
+            .send()
+            .await?
+            .virtual_mfa_devices
+            .into_iter()
+            .map(|d| MfaDevice {
+                user_name: d.user.map(|u| u.user_name.into()),
+                serial_number: d.serial_number.into(),
+                enable_date: d.enable_date.and_then(|t| {
+                    OffsetDateTime::from_unix_timestamp(t.as_secs_f64() as i64)
+                        .ok()
+                        .map(Into::into)
+                }),
+                is_virtual: true,
+            });
+        Ok(devices)
+    }
+
+    /// Generate a fresh credential report, polling until aws has finished
+    /// it, and return the csv
+    /// # Errors
+    /// Returns error if aws api call fails or the report isn't ready in time
+    pub async fn get_credential_report(&self) -> Result<StackString, Error> {
+        for _ in 0..CREDENTIAL_REPORT_POLLS {
+            let state = self
+                .iam_client
+                .generate_credential_report()
+                .send()
+                .await?
+                .state;
+            if state == Some(ReportStateType::Complete) {
+                let content = self
+                    .iam_client
+                    .get_credential_report()
+                    .send()
+                    .await?
+                    .content
+                    .ok_or_else(|| format_err!("empty credential report"))?;
+                return StackString::from_utf8_vec(content.into_inner()).map_err(Into::into);
+            }
+            sleep(Duration::from_secs(2)).await;
+        }
+        Err(format_err!("credential report not ready"))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -213,6 +313,9 @@ pub struct IamUser {
     pub user_id: StackString,
     pub user_name: StackString,
     pub tags: HashMap<String, StackString>,
+    /// Only filled in by `AwsAppInterface::list_users_with_mfa`
+    #[serde(default)]
+    pub mfa_devices: Vec<MfaDevice>,
 }
 
 impl IamUser {
@@ -233,11 +336,21 @@ impl IamUser {
             user_id: user.user_id.into(),
             user_name: user.user_name.into(),
             tags,
+            mfa_devices: Vec::new(),
         })
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct MfaDevice {
+    pub user_name: Option<StackString>,
+    pub serial_number: StackString,
+    pub enable_date: Option<DateTimeWrapper>,
+    /// Authenticator app rather than a hardware token or security key
+    pub is_virtual: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct IamGroup {
     pub arn: StackString,
     pub create_date: OffsetDateTime,
@@ -258,6 +371,28 @@ impl IamGroup {
     }
 }
 
+/// An instance profile and the roles it passes to instances launched with it
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct IamInstanceProfile {
+    pub arn: StackString,
+    pub instance_profile_name: StackString,
+    pub roles: Vec<StackString>,
+}
+
+impl IamInstanceProfile {
+    fn from_instance_profile(profile: InstanceProfile) -> Self {
+        Self {
+            arn: profile.arn.into(),
+            instance_profile_name: profile.instance_profile_name.into(),
+            roles: profile
+                .roles
+                .into_iter()
+                .map(|r| r.role_name.into())
+                .collect(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct IamAccessKey {
     pub access_key_id: StackString,
@@ -288,31 +423,45 @@ mod tests {
     use aws_sdk_sts::Client as StsClient;
     use std::collections::HashMap;
 
-    use crate::iam_instance::IamInstance;
-
-    #[tokio::test]
-    async fn test_list_users() -> Result<(), Error> {
-        let sdk_config = aws_config::load_from_env().await;
-        let sts = StsClient::from_conf((&sdk_config).into());
-        let current_user_id = sts.get_caller_identity().send().await?.user_id.unwrap();
-        println!("{current_user_id}");
+    use crate::{
+        aws_api::{IamApi, MockAws},
+        iam_instance::IamInstance,
+    };
 
-        let iam = IamInstance::new((&sdk_config).into());
+    async fn check_list_users(iam: &dyn IamApi, current_user_id: &str) -> Result<(), Error> {
         let users_map: HashMap<_, _> = iam
             .list_users()
             .await?
+            .into_iter()
             .map(|user| (user.user_id.clone(), user))
             .collect();
         println!("{:?}", users_map);
-        assert!(users_map.contains_key(current_user_id.as_str()));
+        assert!(users_map.contains_key(current_user_id));
 
-        let user_name: Option<&str> = None;
-        let user = iam.get_user(user_name).await?.unwrap();
+        let user = iam.get_user(None).await?.unwrap();
         assert_eq!(user.user_id, current_user_id);
 
-        let groups: Vec<_> = iam.list_groups().await?.collect();
+        let groups = iam.list_groups().await?;
         println!("{:?}", groups);
         assert!(groups.len() > 0);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_list_users() -> Result<(), Error> {
+        let mock = MockAws::from_fixtures()?;
+        check_list_users(&mock, "AIDAEXAMPLE0000000001").await
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_list_users_live() -> Result<(), Error> {
+        let sdk_config = aws_config::load_from_env().await;
+        let sts = StsClient::from_conf((&sdk_config).into());
+        let current_user_id = sts.get_caller_identity().send().await?.user_id.unwrap();
+        println!("{current_user_id}");
+
+        let iam = IamInstance::new((&sdk_config).into());
+        check_list_users(&iam, &current_user_id).await
+    }
 }
@@ -1,7 +1,8 @@
 use anyhow::{format_err, Error};
 use flate2::read::GzDecoder;
-use futures::TryStreamExt;
+use log::{debug, error};
 use mail_parser::{Message, MessageParser, MessagePart};
+use serde_json::json;
 use stack_string::StackString;
 use std::{
     collections::{HashMap, HashSet},
@@ -18,9 +19,15 @@ use zip::ZipArchive;
 
 use crate::{
     config::Config,
+    email_forward::forward_email,
+    email_thread::{assign_thread, ThreadHeaders},
     models::{DmarcRecords, InboundEmailDB},
-    pgpool::PgPool,
     s3_instance::S3Instance,
+    ses_admin::INBOUND_EMAIL_PREFIX,
+    ses_client::SesInstance,
+    spam_filter::classify_email,
+    storage::{DmarcRecordsRepo, EmailAttachmentRepo, InboundEmailRepo, Storage},
+    webhook::{enqueue_webhooks, WebhookEvent, WebhookPayload},
 };
 
 #[derive(Debug)]
@@ -32,6 +39,7 @@ pub struct InboundEmail {
     pub text_content: StackString,
     pub html_content: StackString,
     pub raw_email: StackString,
+    pub thread: ThreadHeaders,
 }
 
 impl From<InboundEmailDB> for InboundEmail {
@@ -44,6 +52,11 @@ impl From<InboundEmailDB> for InboundEmail {
             text_content: value.text_content,
             html_content: value.html_content,
             raw_email: value.raw_email,
+            thread: ThreadHeaders {
+                message_id: value.message_id,
+                in_reply_to: value.in_reply_to,
+                references: Vec::new(),
+            },
         }
     }
 }
@@ -89,6 +102,7 @@ impl TryFrom<Message<'_>> for InboundEmail {
                 s
             });
         let raw_email = StackString::from_utf8(message.raw_message())?;
+        let thread = ThreadHeaders::from_message(&message);
         Ok(Self {
             from_address,
             to_address,
@@ -97,6 +111,7 @@ impl TryFrom<Message<'_>> for InboundEmail {
             text_content,
             html_content,
             raw_email,
+            thread,
         })
     }
 }
@@ -104,8 +119,9 @@ impl TryFrom<Message<'_>> for InboundEmail {
 impl InboundEmail {
     #[must_use]
     pub fn into_db(self, s3_bucket: &str, s3_key: &str) -> InboundEmailDB {
+        let id = Uuid::new_v4();
         InboundEmailDB {
-            id: Uuid::new_v4(),
+            id,
             s3_bucket: s3_bucket.into(),
             s3_key: s3_key.into(),
             from_address: self.from_address,
@@ -115,6 +131,12 @@ impl InboundEmail {
             text_content: self.text_content,
             html_content: self.html_content,
             raw_email: self.raw_email,
+            spam_score: 0.0,
+            verdicts: None,
+            spam_feedback: None,
+            message_id: self.thread.message_id,
+            in_reply_to: self.thread.in_reply_to,
+            thread_id: id,
         }
     }
 
@@ -123,20 +145,22 @@ impl InboundEmail {
     pub async fn sync_db(
         config: &Config,
         s3: &S3Instance,
-        pool: &PgPool,
+        ses: &SesInstance,
+        storage: &dyn Storage,
     ) -> Result<(Vec<StackString>, Vec<StackString>), Error> {
         let parser = MessageParser::default();
         let bucket = config
             .inbound_email_bucket
             .as_ref()
             .ok_or_else(|| format_err!("No Inbound Email Bucket"))?;
-        let key_dict: HashMap<StackString, _> = InboundEmailDB::get_keys(pool)
+        let key_dict: HashMap<StackString, _> = storage
+            .get_email_keys()
             .await?
-            .map_ok(|ibk| (ibk.s3_key.clone(), ibk))
-            .try_collect()
-            .await?;
+            .into_iter()
+            .map(|ibk| (ibk.s3_key.clone(), ibk))
+            .collect();
         let remote_keys: HashSet<StackString> = s3
-            .get_list_of_keys(bucket, Some("inbound-email/"))
+            .get_list_of_keys(bucket, Some(INBOUND_EMAIL_PREFIX))
             .await?
             .into_iter()
             .filter_map(|object| object.key.map(Into::into))
@@ -146,34 +170,149 @@ impl InboundEmail {
         let mut new_attachments = Vec::new();
         for (key, entry) in &key_dict {
             if !remote_keys.contains(key.as_str()) {
-                InboundEmailDB::delete_entry_by_id(entry.id, pool).await?;
-            } else if let Some(email) = InboundEmailDB::get_by_id(pool, entry.id).await? {
-                new_attachments.extend(email.extract_attachments(config, s3).await?);
+                storage.delete_email(entry.id).await?;
+            } else if let Some(mut email) = storage.get_email(entry.id).await? {
+                let mut modified = false;
+                if email.verdicts.is_none() {
+                    classify_email(&mut email, storage).await?;
+                    modified = true;
+                }
+                if email.message_id.is_none() {
+                    if let Some(message) = parser.parse(email.raw_email.as_bytes()) {
+                        let headers = ThreadHeaders::from_message(&message);
+                        if headers.message_id.is_some() {
+                            email.message_id.clone_from(&headers.message_id);
+                            email.in_reply_to.clone_from(&headers.in_reply_to);
+                            assign_thread(&mut email, &headers, storage).await?;
+                            modified = true;
+                        }
+                    }
+                }
+                if modified {
+                    storage.upsert_email(&email).await?;
+                }
+                new_attachments.extend(email.extract_attachments(config, s3, storage).await?);
             }
         }
         for key in &remote_keys {
-            let key = key.as_str();
-            if !key_dict.contains_key(key) {
-                let raw_email = s3.download_to_string(bucket, key).await?;
-                if let Some(message) = parser.parse(raw_email.as_bytes()) {
-                    let email: InboundEmail = message.try_into()?;
-                    let email = email.into_db(bucket, key);
-                    email.upsert_entry(pool).await?;
-                    email.extract_attachments(config, s3).await?;
-                    new_keys.push(key.into());
-                }
+            if !key_dict.contains_key(key)
+                && Self::ingest(config, s3, ses, storage, bucket, key)
+                    .await?
+                    .is_some()
+            {
+                new_keys.push(key.clone());
             }
         }
 
         Ok((new_keys, new_attachments))
     }
 
+    /// Parse and store the email at `key`, classify it, extract its
+    /// attachments, forward it and queue its webhooks. Returns `None` if the
+    /// object isn't an email.
+    /// # Errors
+    /// Returns error if the s3 download or db query fails
+    pub async fn ingest(
+        config: &Config,
+        s3: &S3Instance,
+        ses: &SesInstance,
+        storage: &dyn Storage,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<InboundEmailDB>, Error> {
+        let raw_email = s3.download_to_string(bucket, key).await?;
+        let message = match MessageParser::default().parse(raw_email.as_bytes()) {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        let email: InboundEmail = message.try_into()?;
+        let headers = email.thread.clone();
+        let mut email = email.into_db(bucket, key);
+        assign_thread(&mut email, &headers, storage).await?;
+        classify_email(&mut email, storage).await?;
+        storage.upsert_email(&email).await?;
+        email.extract_attachments(config, s3, storage).await?;
+        match forward_email(config, ses, storage, &email).await {
+            Ok(forwarded) if !forwarded.is_empty() => {
+                debug!("forwarded {key} to {}", forwarded.join(", "));
+            }
+            Ok(_) => (),
+            Err(e) => error!("failed to forward {key}: {e}"),
+        }
+        let payload = WebhookPayload::new(
+            WebhookEvent::InboundEmailReceived,
+            email.id.to_string(),
+            json!({
+                "from_address": email.from_address,
+                "to_address": email.to_address,
+                "subject": email.subject,
+                "is_spam": email.is_spam(),
+            }),
+        );
+        if let Err(e) = enqueue_webhooks(storage, &payload).await {
+            error!("failed to queue webhooks for {key}: {e}");
+        }
+        Ok(Some(email))
+    }
+
+    /// Ingest an object reported by an s3 event notification, objects outside
+    /// the inbound email prefix and already stored emails are skipped
+    /// # Errors
+    /// Returns error if the s3 download or db query fails
+    pub async fn ingest_notification(
+        config: &Config,
+        s3: &S3Instance,
+        ses: &SesInstance,
+        storage: &dyn Storage,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<InboundEmailDB>, Error> {
+        if config.inbound_email_bucket.as_deref() != Some(bucket)
+            || !key.starts_with(INBOUND_EMAIL_PREFIX)
+        {
+            debug!("ignoring s3://{bucket}/{key}");
+            return Ok(None);
+        }
+        if storage
+            .get_email_by_bucket_key(bucket, key)
+            .await?
+            .is_some()
+        {
+            return Ok(None);
+        }
+        Self::ingest(config, s3, ses, storage, bucket, key).await
+    }
+
+    /// Remove attachments of deleted emails, the s3 object is kept while
+    /// another email still references it. Returns the deleted s3 keys.
+    /// # Errors
+    /// Returns error if db query or s3 api call fails
+    pub async fn delete_orphaned_attachments(
+        s3: &S3Instance,
+        storage: &dyn Storage,
+    ) -> Result<Vec<StackString>, Error> {
+        let mut deleted = Vec::new();
+        for attachment in storage.get_orphaned_attachments().await? {
+            storage.delete_attachment(attachment.id).await?;
+            if storage
+                .count_attachments_by_s3_key(&attachment.s3_key)
+                .await?
+                == 0
+            {
+                s3.delete_key(&attachment.s3_bucket, &attachment.s3_key)
+                    .await?;
+                deleted.push(attachment.s3_key);
+            }
+        }
+        Ok(deleted)
+    }
+
     /// # Errors
     /// Returns error if db query fails
     pub async fn parse_dmarc_records(
         config: &Config,
         s3: &S3Instance,
-        pool: &PgPool,
+        storage: &dyn Storage,
     ) -> Result<Vec<DmarcRecords>, Error> {
         let mut new_records = Vec::new();
         let bucket = config
@@ -181,10 +320,7 @@ impl InboundEmail {
             .as_ref()
             .ok_or_else(|| format_err!("No Inbound Email Bucket"))?;
 
-        let parsed_attachments: HashSet<StackString> = DmarcRecords::get_parsed_s3_keys(pool)
-            .await?
-            .into_iter()
-            .collect();
+        let parsed_attachments: HashSet<StackString> = storage.get_parsed_s3_keys().await?;
 
         for attachment in s3.get_list_of_keys(bucket, Some("attachments/")).await? {
             if let Some(key) = &attachment.key {
@@ -216,7 +352,7 @@ impl InboundEmail {
                         }
                         for buffer in buffers {
                             for record in DmarcRecords::parse_xml(&buffer, Some(key.as_str()))? {
-                                record.insert_entry(pool).await?;
+                                storage.insert_dmarc_record(&record).await?;
                                 new_records.push(record);
                             }
                         }
@@ -262,6 +398,7 @@ mod tests {
         models::{DmarcRecords, InboundEmailDB},
         pgpool::PgPool,
         s3_instance::S3Instance,
+        ses_client::SesInstance,
     };
 
     #[test]
@@ -350,6 +487,7 @@ mod tests {
         let pool = PgPool::new(&config.database_url)?;
         let sdk_config = aws_config::load_from_env().await;
         let s3 = S3Instance::new(&sdk_config);
+        let ses = SesInstance::new(&sdk_config);
 
         let existing = if let Some(key) = Box::pin(InboundEmailDB::get_keys(&pool).await?)
             .try_next()
@@ -362,7 +500,7 @@ mod tests {
             None
         };
 
-        let (new_keys, _) = InboundEmail::sync_db(&config, &s3, &pool).await?;
+        let (new_keys, _) = InboundEmail::sync_db(&config, &s3, &ses, &pool).await?;
         if let Some(existing) = &existing {
             assert!(new_keys.len() > 0);
             assert!(new_keys.contains(existing));
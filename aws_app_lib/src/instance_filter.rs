@@ -0,0 +1,214 @@
+use anyhow::{format_err, Error};
+use aws_sdk_ec2::types::Filter;
+use stack_string::StackString;
+use std::{fmt, str::FromStr};
+
+use crate::ec2_instance::Ec2InstanceInfo;
+
+/// Column the instance list is ordered by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum InstanceSortKey {
+    /// Running instances first, then by launch time
+    #[default]
+    State,
+    LaunchTime,
+    Name,
+    InstanceType,
+    AvailabilityZone,
+}
+
+pub const INSTANCE_SORT_KEYS: [InstanceSortKey; 5] = [
+    InstanceSortKey::State,
+    InstanceSortKey::LaunchTime,
+    InstanceSortKey::Name,
+    InstanceSortKey::InstanceType,
+    InstanceSortKey::AvailabilityZone,
+];
+
+impl InstanceSortKey {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::State => "state",
+            Self::LaunchTime => "launch_time",
+            Self::Name => "name",
+            Self::InstanceType => "instance_type",
+            Self::AvailabilityZone => "availability_zone",
+        }
+    }
+}
+
+impl fmt::Display for InstanceSortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for InstanceSortKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        INSTANCE_SORT_KEYS
+            .iter()
+            .find(|k| k.to_str() == s)
+            .copied()
+            .ok_or_else(|| format_err!("{s} is not a valid sort key"))
+    }
+}
+
+/// Restricts and orders the instance list, empty fields match everything
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InstanceFilter {
+    pub state: Option<StackString>,
+    /// Case insensitive substring of the `Name` tag
+    pub name: Option<StackString>,
+    pub instance_type: Option<StackString>,
+    pub availability_zone: Option<StackString>,
+    pub sort: InstanceSortKey,
+    pub descending: bool,
+}
+
+fn non_empty(value: &Option<StackString>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+impl InstanceFilter {
+    /// True if every instance matches
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        non_empty(&self.state).is_none()
+            && non_empty(&self.name).is_none()
+            && non_empty(&self.instance_type).is_none()
+            && non_empty(&self.availability_zone).is_none()
+    }
+
+    /// Filters ec2 applies before paging `describe_instances`, the name is
+    /// left out as tag filters are case sensitive
+    #[must_use]
+    pub fn ec2_filters(&self) -> Vec<Filter> {
+        [
+            ("instance-state-name", non_empty(&self.state)),
+            ("instance-type", non_empty(&self.instance_type)),
+            ("availability-zone", non_empty(&self.availability_zone)),
+        ]
+        .iter()
+        .filter_map(|(name, value)| {
+            value.map(|value| Filter::builder().name(*name).values(value).build())
+        })
+        .collect()
+    }
+
+    #[must_use]
+    pub fn matches(&self, instance: &Ec2InstanceInfo) -> bool {
+        if let Some(state) = non_empty(&self.state) {
+            if instance.state != state {
+                return false;
+            }
+        }
+        if let Some(instance_type) = non_empty(&self.instance_type) {
+            if instance.instance_type != instance_type {
+                return false;
+            }
+        }
+        if let Some(availability_zone) = non_empty(&self.availability_zone) {
+            if instance.availability_zone != availability_zone {
+                return false;
+            }
+        }
+        if let Some(name) = non_empty(&self.name) {
+            let name = name.to_lowercase();
+            let instance_name = instance.tags.get("Name").map_or("", StackString::as_str);
+            if !instance_name.to_lowercase().contains(&name) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Stable sort of `instances` by `sort`
+    pub fn sort(&self, instances: &mut [Ec2InstanceInfo]) {
+        fn name(instance: &Ec2InstanceInfo) -> &str {
+            instance.tags.get("Name").map_or("", StackString::as_str)
+        }
+        instances.sort_by(|x, y| {
+            let ordering = match self.sort {
+                InstanceSortKey::State => (x.state != "running", x.launch_time)
+                    .cmp(&(y.state != "running", y.launch_time)),
+                InstanceSortKey::LaunchTime => x.launch_time.cmp(&y.launch_time),
+                InstanceSortKey::Name => name(x).cmp(name(y)),
+                InstanceSortKey::InstanceType => x.instance_type.cmp(&y.instance_type),
+                InstanceSortKey::AvailabilityZone => x.availability_zone.cmp(&y.availability_zone),
+            };
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::hashmap;
+    use time::{macros::datetime, Duration};
+
+    use crate::{
+        date_time_wrapper::DateTimeWrapper,
+        ec2_instance::Ec2InstanceInfo,
+        instance_filter::{InstanceFilter, InstanceSortKey},
+    };
+
+    fn instance(id: &str, state: &str, name: &str, hours: i64) -> Ec2InstanceInfo {
+        let launch_time = datetime!(2024-06-01 00:00:00 UTC) + Duration::hours(hours);
+        Ec2InstanceInfo {
+            id: id.into(),
+            state: state.into(),
+            launch_time: DateTimeWrapper::from_offsetdatetime(launch_time),
+            tags: hashmap! {"Name".into() => name.into()},
+            ..Ec2InstanceInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_instance_filter() -> Result<(), Error> {
+        let mut instances = vec![
+            instance("i-0001", "stopped", "Build", 0),
+            instance("i-0002", "running", "web-2", 2),
+            instance("i-0003", "running", "Web-1", 1),
+        ];
+
+        let filter = InstanceFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.ec2_filters().is_empty());
+        filter.sort(&mut instances);
+        let ids: Vec<_> = instances.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, vec!["i-0003", "i-0002", "i-0001"]);
+
+        let filter = InstanceFilter {
+            state: Some("running".into()),
+            name: Some("WEB".into()),
+            availability_zone: Some("".into()),
+            sort: "name".parse()?,
+            descending: true,
+            ..InstanceFilter::default()
+        };
+        assert!(!filter.is_empty());
+        assert_eq!(filter.ec2_filters().len(), 1);
+        let mut matching: Vec<_> = instances
+            .into_iter()
+            .filter(|i| filter.matches(i))
+            .collect();
+        filter.sort(&mut matching);
+        let ids: Vec<_> = matching.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, vec!["i-0002", "i-0003"]);
+
+        assert_eq!(
+            "availability_zone".parse::<InstanceSortKey>()?,
+            InstanceSortKey::AvailabilityZone
+        );
+        assert!("size".parse::<InstanceSortKey>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::Error;
+use log::debug;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::{env, time::Duration};
+
+/// Link-local address of the EC2 instance metadata service
+const IMDS_URL: &str = "http://169.254.169.254";
+
+/// How long an IMDSv2 session token stays valid
+const TOKEN_TTL_SECONDS: &str = "300";
+
+/// The metadata service answers within milliseconds on EC2, anywhere else
+/// the request just hangs
+const IMDS_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// `/latest/dynamic/instance-identity/document`
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct IdentityDocument {
+    account_id: StackString,
+    region: StackString,
+    availability_zone: StackString,
+    instance_id: StackString,
+    instance_type: StackString,
+    image_id: StackString,
+    private_ip: Option<StackString>,
+}
+
+/// `/latest/meta-data/spot/instance-action`, only present once the instance
+/// has been scheduled for interruption
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SpotInstanceAction {
+    pub action: StackString,
+    pub time: StackString,
+}
+
+/// `/latest/meta-data/iam/info`
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+struct IamInfo {
+    instance_profile_arn: StackString,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct InstanceMetadata {
+    pub account_id: StackString,
+    pub region: StackString,
+    pub availability_zone: StackString,
+    pub instance_id: StackString,
+    pub instance_type: StackString,
+    pub image_id: StackString,
+    pub private_ip: Option<StackString>,
+    pub public_ip: Option<StackString>,
+    /// `spot` or `on-demand`
+    pub lifecycle: Option<StackString>,
+    pub instance_profile_arn: Option<StackString>,
+    pub iam_role: Option<StackString>,
+    pub spot_action: Option<SpotInstanceAction>,
+    pub rebalance_recommended: bool,
+}
+
+impl InstanceMetadata {
+    fn from_parts(document: IdentityDocument) -> Self {
+        Self {
+            account_id: document.account_id,
+            region: document.region,
+            availability_zone: document.availability_zone,
+            instance_id: document.instance_id,
+            instance_type: document.instance_type,
+            image_id: document.image_id,
+            private_ip: document.private_ip,
+            public_ip: None,
+            lifecycle: None,
+            instance_profile_arn: None,
+            iam_role: None,
+            spot_action: None,
+            rebalance_recommended: false,
+        }
+    }
+
+    #[must_use]
+    pub fn is_spot(&self) -> bool {
+        self.lifecycle.as_deref() == Some("spot")
+    }
+
+    /// Human readable spot interruption status
+    #[must_use]
+    pub fn interruption_status(&self) -> StackString {
+        if let Some(action) = &self.spot_action {
+            format_sstr!("{} scheduled at {}", action.action, action.time)
+        } else if self.rebalance_recommended {
+            "rebalance recommended".into()
+        } else if self.is_spot() {
+            "none".into()
+        } else {
+            "not a spot instance".into()
+        }
+    }
+}
+
+/// Client for the instance metadata service, only IMDSv2 (session token)
+/// requests are made so it also works on instances with IMDSv1 disabled
+#[derive(Clone, Debug)]
+pub struct MetadataClient {
+    client: Client,
+}
+
+impl MetadataClient {
+    /// # Errors
+    /// Returns error if the http client can't be built
+    pub fn new() -> Result<Self, Error> {
+        let client = Client::builder().timeout(IMDS_TIMEOUT).build()?;
+        Ok(Self { client })
+    }
+
+    async fn get_token(&self) -> Result<StackString, Error> {
+        let token = self
+            .client
+            .put(format_sstr!("{IMDS_URL}/latest/api/token").as_str())
+            .header("X-aws-ec2-metadata-token-ttl-seconds", TOKEN_TTL_SECONDS)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(token.into())
+    }
+
+    /// `None` if the path doesn't exist (e.g. no iam role is attached)
+    async fn get_path(&self, token: &str, path: &str) -> Result<Option<StackString>, Error> {
+        let response = self
+            .client
+            .get(format_sstr!("{IMDS_URL}/latest/{path}").as_str())
+            .header("X-aws-ec2-metadata-token", token)
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let text = response.error_for_status()?.text().await?;
+        Ok(Some(text.trim().into()))
+    }
+
+    /// Metadata of the host this is running on, `None` when not running on
+    /// EC2 (or `AWS_EC2_METADATA_DISABLED` is set)
+    /// # Errors
+    /// Returns error if the metadata service returns invalid data
+    pub async fn get_metadata(&self) -> Result<Option<InstanceMetadata>, Error> {
+        if env::var("AWS_EC2_METADATA_DISABLED").map_or(false, |v| v == "true") {
+            return Ok(None);
+        }
+        let token = match self.get_token().await {
+            Ok(token) => token,
+            Err(e) => {
+                debug!("instance metadata unavailable: {e}");
+                return Ok(None);
+            }
+        };
+        let document = match self
+            .get_path(&token, "dynamic/instance-identity/document")
+            .await?
+        {
+            Some(document) => document,
+            None => return Ok(None),
+        };
+        let mut metadata = InstanceMetadata::from_parts(serde_json::from_str(&document)?);
+        metadata.public_ip = self.get_path(&token, "meta-data/public-ipv4").await?;
+        metadata.lifecycle = self
+            .get_path(&token, "meta-data/instance-life-cycle")
+            .await?;
+        if let Some(info) = self.get_path(&token, "meta-data/iam/info").await? {
+            let info: IamInfo = serde_json::from_str(&info)?;
+            metadata.instance_profile_arn = Some(info.instance_profile_arn);
+        }
+        metadata.iam_role = self
+            .get_path(&token, "meta-data/iam/security-credentials/")
+            .await?
+            .and_then(|roles| roles.lines().next().map(Into::into));
+        if let Some(action) = self
+            .get_path(&token, "meta-data/spot/instance-action")
+            .await?
+        {
+            metadata.spot_action = Some(serde_json::from_str(&action)?);
+        }
+        metadata.rebalance_recommended = self
+            .get_path(&token, "meta-data/events/recommendations/rebalance")
+            .await?
+            .is_some();
+        Ok(Some(metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::instance_metadata::{IdentityDocument, InstanceMetadata, SpotInstanceAction};
+
+    const IDENTITY_DOCUMENT: &str = r#"{
+        "accountId" : "123456789012",
+        "architecture" : "x86_64",
+        "availabilityZone" : "us-east-1b",
+        "imageId" : "ami-0123456789abcdef0",
+        "instanceId" : "i-0123456789abcdef0",
+        "instanceType" : "t3.micro",
+        "pendingTime" : "2024-01-01T00:00:00Z",
+        "privateIp" : "10.0.0.12",
+        "region" : "us-east-1",
+        "version" : "2017-09-30"
+    }"#;
+
+    #[test]
+    fn test_instance_metadata() -> Result<(), Error> {
+        let document: IdentityDocument = serde_json::from_str(IDENTITY_DOCUMENT)?;
+        let mut metadata = InstanceMetadata::from_parts(document);
+        assert_eq!(metadata.account_id, "123456789012");
+        assert_eq!(metadata.availability_zone, "us-east-1b");
+        assert_eq!(metadata.private_ip.as_deref(), Some("10.0.0.12"));
+        assert_eq!(metadata.interruption_status(), "not a spot instance");
+
+        metadata.lifecycle = Some("spot".into());
+        assert_eq!(metadata.interruption_status(), "none");
+        let action: SpotInstanceAction =
+            serde_json::from_str(r#"{"action": "terminate", "time": "2024-01-01T08:22:00Z"}"#)?;
+        metadata.spot_action = Some(action);
+        assert_eq!(
+            metadata.interruption_status(),
+            "terminate scheduled at 2024-01-01T08:22:00Z"
+        );
+        Ok(())
+    }
+}
@@ -3,7 +3,11 @@ use clap::Parser;
 use stack_string::StackString;
 use std::path::PathBuf;
 
-use crate::{config::Config, ec2_instance::InstanceRequest, spot_request_opt::get_tags};
+use crate::{
+    config::Config,
+    ec2_instance::{DataVolume, InstanceRequest, InstanceTenancy, ShutdownBehavior, VolumeSpec},
+    spot_request_opt::get_tags,
+};
 
 #[derive(Parser, Debug, Clone)]
 pub struct InstanceOpt {
@@ -19,6 +23,39 @@ pub struct InstanceOpt {
     tags: Vec<StackString>,
     #[clap(short, long)]
     key_name: Option<StackString>,
+    #[clap(long)]
+    /// Availability zone to launch in
+    availability_zone: Option<StackString>,
+    #[clap(long, default_value = "default")]
+    /// Instance tenancy, default or dedicated
+    tenancy: InstanceTenancy,
+    #[clap(long)]
+    /// Launch an ebs-optimized instance
+    ebs_optimized: bool,
+    #[clap(long)]
+    /// Stop or terminate the instance when it shuts itself down
+    shutdown_behavior: Option<ShutdownBehavior>,
+    #[clap(long)]
+    /// Root volume size in GB, defaults to the ami's
+    root_volume_size: Option<i32>,
+    #[clap(long)]
+    /// Root volume type, e.g. gp3
+    root_volume_type: Option<StackString>,
+    #[clap(long)]
+    /// Provisioned iops of the root volume (gp3/io1/io2)
+    root_volume_iops: Option<i32>,
+    #[clap(long = "data-volume")]
+    /// Additional volume as DEVICE:SIZE[:TYPE][:keep], e.g. /dev/sdf:100:gp3
+    data_volumes: Vec<DataVolume>,
+    #[clap(long)]
+    /// Name or arn of the instance profile to attach
+    instance_profile: Option<StackString>,
+    #[clap(long)]
+    /// Only allow metadata requests with a session token (IMDSv2)
+    require_imdsv2: bool,
+    #[clap(long)]
+    /// Route53 host name to point at the instance once it is running
+    dns_name: Option<StackString>,
 }
 
 impl InstanceOpt {
@@ -40,6 +77,19 @@ impl InstanceOpt {
             script: self.script.unwrap_or_else(|| "setup_aws.sh".into()),
             key_name,
             tags: get_tags(&self.tags),
+            availability_zone: self.availability_zone,
+            tenancy: self.tenancy,
+            ebs_optimized: self.ebs_optimized,
+            shutdown_behavior: self.shutdown_behavior,
+            root_volume: VolumeSpec::from_options(
+                self.root_volume_size,
+                self.root_volume_type,
+                self.root_volume_iops,
+            ),
+            data_volumes: self.data_volumes,
+            instance_profile: self.instance_profile,
+            require_imdsv2: self.require_imdsv2,
+            dns_name: self.dns_name,
         })
     }
 }
@@ -0,0 +1,532 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use stack_string::{format_sstr, StackString};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, Duration,
+    OffsetDateTime,
+};
+use uuid::Uuid;
+
+use crate::{
+    ec2_instance::{Ec2InstanceInfo, SnapshotInfo, VolumeInfo},
+    iam_instance::IamUser,
+    pgpool::PgPool,
+    route53_instance::DnsRecord,
+    terraform_drift::{dns_record_id, normalize_dns_name, sorted_values},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum InventoryResourceType {
+    Instance,
+    Volume,
+    Snapshot,
+    DnsRecord,
+    IamUser,
+}
+
+impl InventoryResourceType {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Instance => "instance",
+            Self::Volume => "volume",
+            Self::Snapshot => "snapshot",
+            Self::DnsRecord => "dns_record",
+            Self::IamUser => "iam_user",
+        }
+    }
+}
+
+impl fmt::Display for InventoryResourceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+/// A resource reduced to the attributes compared between inventory
+/// snapshots, tags are flattened to `tags.{key}`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InventoryItem {
+    pub resource_type: InventoryResourceType,
+    /// Resource id, `{zone_id}/{name}/{type}` for dns records and the user
+    /// name for iam users
+    pub id: StackString,
+    pub name: Option<StackString>,
+    pub attributes: BTreeMap<StackString, StackString>,
+}
+
+impl InventoryItem {
+    fn new(resource_type: InventoryResourceType, id: impl Into<StackString>) -> Self {
+        Self {
+            resource_type,
+            id: id.into(),
+            name: None,
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    fn attribute(mut self, key: &str, value: impl Into<StackString>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    fn tags<'a>(mut self, tags: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        for (key, value) in tags {
+            if key == "Name" {
+                self.name = Some(value.into());
+            }
+            if !key.starts_with("aws:") {
+                self.attributes
+                    .insert(format_sstr!("tags.{key}"), value.into());
+            }
+        }
+        self
+    }
+
+    #[must_use]
+    pub fn from_instance(instance: &Ec2InstanceInfo) -> Self {
+        let mut item = Self::new(InventoryResourceType::Instance, instance.id.clone())
+            .attribute("state", instance.state.clone())
+            .attribute("instance_type", instance.instance_type.clone())
+            .attribute("availability_zone", instance.availability_zone.clone())
+            .tags(instance.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        if let Some(public_ip) = &instance.public_ip {
+            item = item.attribute("public_ip", public_ip.clone());
+        }
+        item
+    }
+
+    #[must_use]
+    pub fn from_volume(volume: &VolumeInfo) -> Self {
+        let mut item = Self::new(InventoryResourceType::Volume, volume.id.clone())
+            .attribute("size", format_sstr!("{}", volume.size))
+            .attribute("type", volume.volume_type.clone())
+            .attribute("state", volume.state.clone())
+            .attribute("availability_zone", volume.availability_zone.clone())
+            .attribute("encrypted", format_sstr!("{}", volume.encrypted))
+            .tags(volume.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        if let Some((instance_id, _)) = &volume.attachment {
+            item = item.attribute("attached_to", instance_id.clone());
+        }
+        item
+    }
+
+    #[must_use]
+    pub fn from_snapshot(snapshot: &SnapshotInfo) -> Self {
+        let mut item = Self::new(InventoryResourceType::Snapshot, snapshot.id.clone())
+            .attribute("size", format_sstr!("{}", snapshot.volume_size))
+            .attribute("state", snapshot.state.clone())
+            .attribute("encrypted", format_sstr!("{}", snapshot.encrypted))
+            .tags(snapshot.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        if let Some(volume_id) = &snapshot.volume_id {
+            item = item.attribute("volume_id", volume_id.clone());
+        }
+        item
+    }
+
+    #[must_use]
+    pub fn from_dns_record(zone_id: &str, record: &DnsRecord) -> Self {
+        let id = dns_record_id(
+            zone_id,
+            &record.dnsname,
+            &record.record_type,
+            record.set_identifier.as_deref(),
+        );
+        let mut item = Self::new(InventoryResourceType::DnsRecord, id);
+        item.name = Some(normalize_dns_name(&record.dnsname));
+        if let Some(ttl) = record.ttl {
+            item = item.attribute("ttl", format_sstr!("{ttl}"));
+        }
+        match &record.alias_target {
+            Some(alias) => item.attribute("alias", normalize_dns_name(alias)),
+            None => item.attribute("records", sorted_values(&record.values)),
+        }
+    }
+
+    #[must_use]
+    pub fn from_iam_user(user: &IamUser) -> Self {
+        Self::new(InventoryResourceType::IamUser, user.user_name.clone())
+            .attribute("arn", user.arn.clone())
+            .attribute("user_id", user.user_id.clone())
+            .tags(user.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+    }
+}
+
+/// Every inventoried resource of a region at one point in time
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Serialize)]
+pub struct InventorySnapshot {
+    pub id: Uuid,
+    pub region: StackString,
+    /// Json array of `InventoryItem`
+    pub resources: Value,
+    pub created_at: OffsetDateTime,
+}
+
+impl InventorySnapshot {
+    /// # Errors
+    /// Returns error if serialization fails
+    pub fn new(region: impl Into<StackString>, items: &[InventoryItem]) -> Result<Self, Error> {
+        Ok(Self {
+            id: Uuid::new_v4(),
+            region: region.into(),
+            resources: serde_json::to_value(items)?,
+            created_at: OffsetDateTime::now_utc(),
+        })
+    }
+
+    /// # Errors
+    /// Returns error if deserialization fails
+    pub fn items(&self) -> Result<Vec<InventoryItem>, Error> {
+        serde_json::from_value(self.resources.clone()).map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_latest(pool: &PgPool, region: &str) -> Result<Option<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM inventory_snapshot
+                WHERE region = $region
+                ORDER BY created_at DESC
+                LIMIT 1
+            ",
+            region = region,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// Latest snapshot taken at or before `at`, the earliest one after it if
+    /// there is none
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_baseline(
+        pool: &PgPool,
+        region: &str,
+        at: OffsetDateTime,
+    ) -> Result<Option<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM inventory_snapshot
+                WHERE region = $region
+                ORDER BY created_at > $at, abs(extract(epoch FROM created_at - $at))
+                LIMIT 1
+            ",
+            region = region,
+            at = at,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO inventory_snapshot (id, region, resources, created_at)
+                VALUES ($id, $region, $resources, $created_at)
+            ",
+            id = self.id,
+            region = self.region,
+            resources = self.resources,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn delete_before(pool: &PgPool, cutoff: OffsetDateTime) -> Result<u64, Error> {
+        let query = query!(
+            "DELETE FROM inventory_snapshot WHERE created_at < $cutoff",
+            cutoff = cutoff,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Deleted,
+    Modified,
+}
+
+impl ChangeKind {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Deleted => "deleted",
+            Self::Modified => "modified",
+        }
+    }
+}
+
+/// An attribute whose value changed between snapshots, `None` where it's
+/// set in only one of them
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AttributeChange {
+    pub attribute: StackString,
+    pub before: Option<StackString>,
+    pub after: Option<StackString>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct InventoryChange {
+    pub kind: ChangeKind,
+    pub resource_type: InventoryResourceType,
+    pub id: StackString,
+    pub name: Option<StackString>,
+    /// Only set for modified resources
+    pub changes: Vec<AttributeChange>,
+}
+
+/// Changes between two inventory snapshots of a region
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct InventoryDiff {
+    pub region: StackString,
+    pub before: OffsetDateTime,
+    pub after: OffsetDateTime,
+    pub changes: Vec<InventoryChange>,
+}
+
+impl InventoryDiff {
+    /// # Errors
+    /// Returns error if the resources of either snapshot can't be read
+    pub fn new(before: &InventorySnapshot, after: &InventorySnapshot) -> Result<Self, Error> {
+        Ok(Self {
+            region: after.region.clone(),
+            before: before.created_at,
+            after: after.created_at,
+            changes: diff_inventories(&before.items()?, &after.items()?),
+        })
+    }
+
+    #[must_use]
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|c| c.kind == kind).count()
+    }
+}
+
+/// Resources created, deleted or modified between `before` and `after`,
+/// ordered by kind, resource type and id
+#[must_use]
+pub fn diff_inventories(before: &[InventoryItem], after: &[InventoryItem]) -> Vec<InventoryChange> {
+    let before: HashMap<_, _> = before
+        .iter()
+        .map(|item| ((item.resource_type, item.id.as_str()), item))
+        .collect();
+    let after: HashMap<_, _> = after
+        .iter()
+        .map(|item| ((item.resource_type, item.id.as_str()), item))
+        .collect();
+    let mut changes = Vec::new();
+    for (key, item) in &after {
+        let (kind, attribute_changes) = match before.get(key) {
+            None => (ChangeKind::Created, Vec::new()),
+            Some(previous) => {
+                let attribute_changes = attribute_changes(previous, item);
+                if attribute_changes.is_empty() {
+                    continue;
+                }
+                (ChangeKind::Modified, attribute_changes)
+            }
+        };
+        changes.push(InventoryChange {
+            kind,
+            resource_type: item.resource_type,
+            id: item.id.clone(),
+            name: item.name.clone(),
+            changes: attribute_changes,
+        });
+    }
+    for (key, item) in &before {
+        if !after.contains_key(key) {
+            changes.push(InventoryChange {
+                kind: ChangeKind::Deleted,
+                resource_type: item.resource_type,
+                id: item.id.clone(),
+                name: item.name.clone(),
+                changes: Vec::new(),
+            });
+        }
+    }
+    changes.sort_by(|a, b| (a.kind, a.resource_type, &a.id).cmp(&(b.kind, b.resource_type, &b.id)));
+    changes
+}
+
+fn attribute_changes(before: &InventoryItem, after: &InventoryItem) -> Vec<AttributeChange> {
+    let keys: BTreeSet<_> = before
+        .attributes
+        .keys()
+        .chain(after.attributes.keys())
+        .collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let old = before.attributes.get(key);
+            let new = after.attributes.get(key);
+            if old == new {
+                return None;
+            }
+            Some(AttributeChange {
+                attribute: key.clone(),
+                before: old.cloned(),
+                after: new.cloned(),
+            })
+        })
+        .collect()
+}
+
+/// Parse the `since` of `/aws/changes`: a duration before `now` such as
+/// `30m`, `24h` or `7d`, a date (`2024-06-01`) or an rfc3339 timestamp
+/// # Errors
+/// Returns error if `since` is none of these
+pub fn parse_since(since: &str, now: OffsetDateTime) -> Result<OffsetDateTime, Error> {
+    let since = since.trim();
+    let invalid = || format_err!("invalid since {since}, expected e.g. 24h, 7d or 2024-06-01");
+    if let Some(unit) = since.chars().last().filter(char::is_ascii_alphabetic) {
+        if let Ok(amount) = since[..since.len() - 1].parse::<i64>() {
+            let duration = match unit {
+                'm' => Duration::minutes(amount),
+                'h' => Duration::hours(amount),
+                'd' => Duration::days(amount),
+                'w' => Duration::weeks(amount),
+                _ => return Err(invalid()),
+            };
+            return Ok(now - duration);
+        }
+    }
+    if let Ok(date) = Date::parse(since, format_description!("[year]-[month]-[day]")) {
+        return Ok(date.midnight().assume_utc());
+    }
+    OffsetDateTime::parse(since, &Rfc3339).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::hashmap;
+    use time::macros::datetime;
+
+    use crate::{
+        ec2_instance::{Ec2InstanceInfo, VolumeInfo},
+        inventory::{
+            diff_inventories, parse_since, AttributeChange, ChangeKind, InventoryItem,
+            InventoryResourceType, InventorySnapshot,
+        },
+    };
+
+    fn instance(id: &str, state: &str, name: &str) -> InventoryItem {
+        InventoryItem::from_instance(&Ec2InstanceInfo {
+            id: id.into(),
+            state: state.into(),
+            instance_type: "t3.micro".into(),
+            availability_zone: "us-east-1a".into(),
+            tags: hashmap! {"Name".into() => name.into(), "aws:cloudformation".into() => "x".into()},
+            ..Ec2InstanceInfo::default()
+        })
+    }
+
+    fn volume(id: &str, size: i64) -> InventoryItem {
+        InventoryItem::from_volume(&VolumeInfo {
+            id: id.into(),
+            availability_zone: "us-east-1a".into(),
+            size,
+            iops: 3000,
+            state: "in-use".into(),
+            tags: hashmap! {},
+            volume_type: "gp3".into(),
+            encrypted: true,
+            attachment: Some(("i-1".into(), "/dev/sda1".into())),
+        })
+    }
+
+    #[test]
+    fn test_inventory_item() {
+        let item = instance("i-1", "running", "web");
+        assert_eq!(item.resource_type, InventoryResourceType::Instance);
+        assert_eq!(item.name.as_deref(), Some("web"));
+        assert_eq!(
+            item.attributes.get("tags.Name").map(|s| s.as_str()),
+            Some("web")
+        );
+        assert!(!item.attributes.contains_key("tags.aws:cloudformation"));
+        let item = volume("vol-1", 8);
+        assert_eq!(
+            item.attributes.get("attached_to").map(|s| s.as_str()),
+            Some("i-1")
+        );
+    }
+
+    #[test]
+    fn test_diff_inventories() {
+        let before = vec![
+            instance("i-1", "running", "web"),
+            instance("i-2", "running", "old"),
+            volume("vol-1", 8),
+        ];
+        let after = vec![
+            instance("i-1", "stopped", "web"),
+            instance("i-3", "running", "forgotten"),
+            volume("vol-1", 8),
+        ];
+        let changes = diff_inventories(&before, &after);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].kind, ChangeKind::Created);
+        assert_eq!(changes[0].id, "i-3");
+        assert_eq!(changes[0].name.as_deref(), Some("forgotten"));
+        assert_eq!(changes[1].kind, ChangeKind::Deleted);
+        assert_eq!(changes[1].id, "i-2");
+        assert_eq!(changes[2].kind, ChangeKind::Modified);
+        assert_eq!(changes[2].id, "i-1");
+        assert_eq!(
+            changes[2].changes,
+            vec![AttributeChange {
+                attribute: "state".into(),
+                before: Some("running".into()),
+                after: Some("stopped".into()),
+            }]
+        );
+        assert!(diff_inventories(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn test_inventory_snapshot_items() -> Result<(), Error> {
+        let items = vec![instance("i-1", "running", "web"), volume("vol-1", 8)];
+        let snapshot = InventorySnapshot::new("us-east-1", &items)?;
+        assert_eq!(snapshot.items()?, items);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_since() -> Result<(), Error> {
+        let now = datetime!(2024-06-08 12:00:00 UTC);
+        assert_eq!(parse_since("24h", now)?, datetime!(2024-06-07 12:00:00 UTC));
+        assert_eq!(parse_since("7d", now)?, datetime!(2024-06-01 12:00:00 UTC));
+        assert_eq!(parse_since("30m", now)?, datetime!(2024-06-08 11:30:00 UTC));
+        assert_eq!(
+            parse_since("2024-06-01", now)?,
+            datetime!(2024-06-01 00:00:00 UTC)
+        );
+        assert_eq!(
+            parse_since("2024-06-01T08:00:00Z", now)?,
+            datetime!(2024-06-01 08:00:00 UTC)
+        );
+        assert!(parse_since("yesterday", now).is_err());
+        assert!(parse_since("3y", now).is_err());
+        Ok(())
+    }
+}
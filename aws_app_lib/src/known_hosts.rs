@@ -0,0 +1,178 @@
+use anyhow::Error;
+use once_cell::sync::Lazy;
+use stack_string::{format_sstr, StackString};
+use std::path::{Path, PathBuf};
+use tokio::{fs, sync::Mutex};
+
+/// Serializes rewrites of the known_hosts file
+static KNOWN_HOSTS_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+const HOST_KEYS_BEGIN: &str = "-----BEGIN SSH HOST KEY KEYS-----";
+const HOST_KEYS_END: &str = "-----END SSH HOST KEY KEYS-----";
+
+/// Known hosts file managed by aws-app-rust, entries are keyed by instance id
+/// (passed to ssh as `HostKeyAlias`) rather than hostname, so that a
+/// recreated instance which reuses an address does not trip verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownHosts {
+    path: PathBuf,
+}
+
+impl KnownHosts {
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Options that make ssh verify `alias` against this file
+    #[must_use]
+    pub fn ssh_options(&self, alias: &str, strict: bool) -> [StackString; 6] {
+        let strict = if strict { "yes" } else { "accept-new" };
+        [
+            "-o".into(),
+            format_sstr!("UserKnownHostsFile={}", self.path.to_string_lossy()),
+            "-o".into(),
+            format_sstr!("HostKeyAlias={alias}"),
+            "-o".into(),
+            format_sstr!("StrictHostKeyChecking={strict}"),
+        ]
+    }
+
+    async fn read_lines(&self) -> Result<Vec<StackString>, Error> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let text = fs::read_to_string(&self.path).await?;
+        Ok(text.lines().map(Into::into).collect())
+    }
+
+    /// # Errors
+    /// Returns error if reading the file fails
+    pub async fn contains(&self, alias: &str) -> Result<bool, Error> {
+        let lines = self.read_lines().await?;
+        Ok(lines.iter().any(|l| entry_alias(l) == Some(alias)))
+    }
+
+    /// Replace any keys recorded for `alias` with `keys`, each of the form
+    /// `<key-type> <base64>`
+    /// # Errors
+    /// Returns error if writing the file fails
+    pub async fn set_host_keys(&self, alias: &str, keys: &[StackString]) -> Result<(), Error> {
+        let _lock = KNOWN_HOSTS_LOCK.lock().await;
+        let mut lines = self.read_lines().await?;
+        lines.retain(|l| entry_alias(l) != Some(alias));
+        lines.extend(keys.iter().map(|key| format_sstr!("{alias} {key}")));
+        self.write_lines(&lines).await
+    }
+
+    /// Forget the keys for `alias`, returns whether there were any
+    /// # Errors
+    /// Returns error if writing the file fails
+    pub async fn remove(&self, alias: &str) -> Result<bool, Error> {
+        let _lock = KNOWN_HOSTS_LOCK.lock().await;
+        let mut lines = self.read_lines().await?;
+        let before = lines.len();
+        lines.retain(|l| entry_alias(l) != Some(alias));
+        if lines.len() == before {
+            return Ok(false);
+        }
+        self.write_lines(&lines).await?;
+        Ok(true)
+    }
+
+    async fn write_lines(&self, lines: &[StackString]) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let mut text = lines.join("\n");
+        text.push('\n');
+        fs::write(&self.path, text).await?;
+        Ok(())
+    }
+}
+
+fn entry_alias(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return None;
+    }
+    line.split_whitespace().next()
+}
+
+/// Extract host keys cloud-init prints to the console between the
+/// `BEGIN SSH HOST KEY KEYS` markers, the trailing comment is dropped
+#[must_use]
+pub fn parse_console_host_keys(console_output: &str) -> Vec<StackString> {
+    console_output
+        .lines()
+        .skip_while(|l| !l.contains(HOST_KEYS_BEGIN))
+        .skip(1)
+        .take_while(|l| !l.contains(HOST_KEYS_END))
+        .filter_map(|l| {
+            let mut fields = l.split_whitespace();
+            let key_type = fields.next()?;
+            let key = fields.next()?;
+            if key_type.starts_with("ssh-") || key_type.starts_with("ecdsa-") {
+                Some(format_sstr!("{key_type} {key}"))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use tempfile::TempDir;
+
+    use crate::known_hosts::{parse_console_host_keys, KnownHosts};
+
+    const CONSOLE_OUTPUT: &str = "\
+[   12.345678] cloud-init[1234]: Cloud-init v. 23.1 running 'modules:final'
+-----BEGIN SSH HOST KEY KEYS-----
+ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTI= root@ip-172-31-1-2
+ssh-ed25519 AAAAC3NzaC1lZDI1NTE5 root@ip-172-31-1-2
+-----END SSH HOST KEY KEYS-----
+[   13.000000] cloud-init[1234]: Cloud-init v. 23.1 finished
+";
+
+    #[test]
+    fn test_parse_console_host_keys() {
+        let keys = parse_console_host_keys(CONSOLE_OUTPUT);
+        assert_eq!(
+            keys,
+            vec![
+                "ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTI=",
+                "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5"
+            ]
+        );
+        assert!(parse_console_host_keys("no keys yet").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_known_hosts() -> Result<(), Error> {
+        let td = TempDir::new()?;
+        let known_hosts = KnownHosts::new(td.path().join("known_hosts"));
+        assert!(!known_hosts.contains("i-0123").await?);
+
+        let keys = parse_console_host_keys(CONSOLE_OUTPUT);
+        known_hosts.set_host_keys("i-0123", &keys).await?;
+        known_hosts.set_host_keys("i-4567", &keys[..1]).await?;
+        known_hosts.set_host_keys("i-0123", &keys[1..]).await?;
+        assert!(known_hosts.contains("i-0123").await?);
+
+        let text = tokio::fs::read_to_string(known_hosts.path()).await?;
+        assert_eq!(text.lines().count(), 2);
+
+        assert!(known_hosts.remove("i-0123").await?);
+        assert!(!known_hosts.remove("i-0123").await?);
+        assert!(known_hosts.contains("i-4567").await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{format_err, Error};
+use aws_config::SdkConfig;
+use aws_sdk_lambda::{primitives::Blob, types::LogType, Client as LambdaClient};
+use aws_types::region::Region;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+
+use crate::config::Config;
+
+#[derive(Clone)]
+pub struct LambdaInstance {
+    lambda_client: LambdaClient,
+    region: StackString,
+}
+
+impl fmt::Debug for LambdaInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("LambdaInstance")
+    }
+}
+
+impl LambdaInstance {
+    #[must_use]
+    pub fn new(config: &Config, sdk_config: &SdkConfig) -> Self {
+        let region = sdk_config
+            .region()
+            .map_or_else(|| config.aws_region_name.clone(), |r| r.as_ref().into());
+        Self {
+            lambda_client: LambdaClient::from_conf(sdk_config.into()),
+            region,
+        }
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
+        let region: String = region.as_ref().into();
+        self.region = region.as_str().into();
+        let region = Region::new(region);
+        let conf = self
+            .lambda_client
+            .config()
+            .to_builder()
+            .region(region)
+            .build();
+        self.lambda_client = LambdaClient::from_conf(conf);
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_functions(&self) -> Result<impl Iterator<Item = LambdaFunctionInfo>, Error> {
+        let region = self.region.clone();
+        self.lambda_client
+            .list_functions()
+            .send()
+            .await
+            .map_err(Into::into)
+            .map(move |r| {
+                r.functions
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(move |f| {
+                        let function_name: StackString = f.function_name?.into();
+                        let log_url = cloudwatch_log_url(&region, &function_name);
+                        Some(LambdaFunctionInfo {
+                            function_name,
+                            function_arn: f.function_arn.unwrap_or_default().into(),
+                            runtime: f
+                                .runtime
+                                .map_or_else(|| "container".into(), |r| r.as_str().into()),
+                            handler: f.handler.unwrap_or_default().into(),
+                            memory_size: f.memory_size.unwrap_or(0),
+                            timeout: f.timeout.unwrap_or(0),
+                            code_size: f.code_size,
+                            last_modified: f.last_modified.unwrap_or_default().into(),
+                            log_url,
+                        })
+                    })
+            })
+    }
+
+    /// Invoke function synchronously with a json payload
+    /// # Errors
+    /// Returns error if aws api call fails or payload is not valid json
+    pub async fn invoke(
+        &self,
+        function_name: impl Into<String>,
+        payload: impl AsRef<str>,
+    ) -> Result<LambdaInvokeResult, Error> {
+        let payload = payload.as_ref();
+        let payload = if payload.trim().is_empty() {
+            "{}"
+        } else {
+            serde_json::from_str::<serde_json::Value>(payload)
+                .map_err(|e| format_err!("Invalid json payload {e}"))?;
+            payload
+        };
+        let output = self
+            .lambda_client
+            .invoke()
+            .function_name(function_name)
+            .log_type(LogType::Tail)
+            .payload(Blob::new(payload.as_bytes()))
+            .send()
+            .await?;
+        let response = output.payload.map_or_else(StackString::new, |p| {
+            String::from_utf8_lossy(p.as_ref()).as_ref().into()
+        });
+        let log_tail = output
+            .log_result
+            .and_then(|l| STANDARD.decode(l).ok())
+            .map_or_else(StackString::new, |l| {
+                String::from_utf8_lossy(&l).as_ref().into()
+            });
+        Ok(LambdaInvokeResult {
+            status_code: output.status_code,
+            function_error: output.function_error.map(Into::into),
+            response,
+            log_tail,
+        })
+    }
+}
+
+fn cloudwatch_log_url(region: &str, function_name: &str) -> StackString {
+    log_group_url(region, &format_sstr!("/aws/lambda/{function_name}"))
+}
+
+/// Cloudwatch console url of `log_group`, the console expects the slashes of
+/// the group name double url encoded
+pub(crate) fn log_group_url(region: &str, log_group: &str) -> StackString {
+    let log_group = log_group.replace('/', "$252F");
+    format_sstr!(
+        "https://{region}.console.aws.amazon.com/cloudwatch/home?region={region}#logsV2:log-groups/\
+         log-group/{log_group}"
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LambdaFunctionInfo {
+    pub function_name: StackString,
+    pub function_arn: StackString,
+    pub runtime: StackString,
+    pub handler: StackString,
+    pub memory_size: i32,
+    pub timeout: i32,
+    pub code_size: i64,
+    pub last_modified: StackString,
+    pub log_url: StackString,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LambdaInvokeResult {
+    pub status_code: i32,
+    pub function_error: Option<StackString>,
+    pub response: StackString,
+    pub log_tail: StackString,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lambda_instance::cloudwatch_log_url;
+
+    #[test]
+    fn test_cloudwatch_log_url() {
+        let url = cloudwatch_log_url("us-east-1", "my-function");
+        assert_eq!(
+            url,
+            "https://us-east-1.console.aws.amazon.com/cloudwatch/home?region=us-east-1#logsV2:\
+             log-groups/log-group/$252Faws$252Flambda$252Fmy-function"
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use serde::Serialize;
+use stack_string::{format_sstr, StackString};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    models::{LaunchHistory, SPOT_PENDING_STATUSES},
+    setup_check::{SETUP_FAILED, SETUP_TIMEOUT},
+};
+
+/// Steps a launch goes through, in order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum LaunchPhase {
+    Submitted,
+    Fulfilled,
+    Running,
+    CloudInit,
+    Healthy,
+}
+
+impl LaunchPhase {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Submitted => "request submitted",
+            Self::Fulfilled => "fulfilled",
+            Self::Running => "instance running",
+            Self::CloudInit => "cloud-init finished",
+            Self::Healthy => "services healthy",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum PhaseState {
+    Done(OffsetDateTime),
+    /// The phase the launch is waiting on
+    Active,
+    Waiting,
+    Failed(StackString),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PhaseProgress {
+    pub phase: LaunchPhase,
+    pub state: PhaseState,
+}
+
+/// Phases of one launch as recorded by the launch tracking
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LaunchProgress {
+    pub id: Uuid,
+    pub instance_id: Option<StackString>,
+    pub instance_type: StackString,
+    pub is_spot: bool,
+    pub launched_at: OffsetDateTime,
+    pub terminated_at: Option<OffsetDateTime>,
+    pub phases: Vec<PhaseProgress>,
+}
+
+impl LaunchProgress {
+    #[must_use]
+    pub fn new(launch: &LaunchHistory) -> Self {
+        let mut failure = failure(launch);
+        let mut active = launch.terminated_at.is_none();
+        let phases = [
+            (LaunchPhase::Submitted, Some(launch.launched_at)),
+            (LaunchPhase::Fulfilled, launch.fulfilled_at),
+            (LaunchPhase::Running, launch.running_at),
+            (LaunchPhase::CloudInit, launch.cloud_init_at),
+            (LaunchPhase::Healthy, launch.healthy_at),
+        ]
+        .iter()
+        .map(|(phase, at)| {
+            let state = match at {
+                Some(at) => PhaseState::Done(*at),
+                None => match failure.take() {
+                    Some(message) => {
+                        active = false;
+                        PhaseState::Failed(message)
+                    }
+                    None if active => {
+                        active = false;
+                        PhaseState::Active
+                    }
+                    None => PhaseState::Waiting,
+                },
+            };
+            PhaseProgress {
+                phase: *phase,
+                state,
+            }
+        })
+        .collect();
+        Self {
+            id: launch.id,
+            instance_id: launch.instance_id.clone(),
+            instance_type: launch.instance_type.clone(),
+            is_spot: launch.is_spot,
+            launched_at: launch.launched_at,
+            terminated_at: launch.terminated_at,
+            phases,
+        }
+    }
+
+    /// Nothing left to wait for: healthy, failed or terminated
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        !self.phases.iter().any(|p| p.state == PhaseState::Active)
+    }
+}
+
+fn failure(launch: &LaunchHistory) -> Option<StackString> {
+    if launch.is_spot
+        && launch.instance_id.is_none()
+        && !SPOT_PENDING_STATUSES.contains(&launch.status.as_str())
+    {
+        return Some(format_sstr!("spot request {}", launch.status));
+    }
+    match launch.setup_status.as_ref().map(StackString::as_str) {
+        Some(SETUP_FAILED) => Some(
+            launch
+                .setup_message
+                .clone()
+                .unwrap_or_else(|| "setup failed".into()),
+        ),
+        Some(SETUP_TIMEOUT) => Some("setup timed out".into()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::datetime, Duration};
+
+    use crate::{
+        launch_progress::{LaunchPhase, LaunchProgress, PhaseState},
+        models::LaunchHistory,
+        setup_check::SETUP_TIMEOUT,
+    };
+
+    #[test]
+    fn test_launch_progress() {
+        let start = datetime!(2024-06-01 00:00 UTC);
+        let mut launch = LaunchHistory::new("m5.large", "ami-0001", true);
+        launch.launched_at = start;
+
+        let progress = LaunchProgress::new(&launch);
+        assert_eq!(progress.phases.len(), 5);
+        assert_eq!(progress.phases[0].state, PhaseState::Done(start));
+        assert_eq!(progress.phases[1].phase, LaunchPhase::Fulfilled);
+        assert_eq!(progress.phases[1].state, PhaseState::Active);
+        assert_eq!(progress.phases[2].state, PhaseState::Waiting);
+        assert!(!progress.is_finished());
+
+        launch.instance_id = Some("i-0001".into());
+        launch.status = "fulfilled".into();
+        launch.fulfilled_at = Some(start + Duration::minutes(1));
+        launch.running_at = Some(start + Duration::minutes(2));
+        let progress = LaunchProgress::new(&launch);
+        assert_eq!(progress.phases[3].state, PhaseState::Active);
+
+        launch.setup_status = Some(SETUP_TIMEOUT.into());
+        let progress = LaunchProgress::new(&launch);
+        assert_eq!(
+            progress.phases[3].state,
+            PhaseState::Failed("setup timed out".into())
+        );
+        assert_eq!(progress.phases[4].state, PhaseState::Waiting);
+        assert!(progress.is_finished());
+
+        let mut failed = LaunchHistory::new("m5.large", "ami-0001", true);
+        failed.status = "price-too-low".into();
+        let progress = LaunchProgress::new(&failed);
+        assert_eq!(
+            progress.phases[1].state,
+            PhaseState::Failed("spot request price-too-low".into())
+        );
+
+        let ondemand = LaunchHistory::new("t3.micro", "ami-0001", false);
+        let progress = LaunchProgress::new(&ondemand);
+        assert!(matches!(progress.phases[1].state, PhaseState::Done(_)));
+        assert_eq!(progress.phases[2].state, PhaseState::Active);
+
+        let mut terminated = ondemand;
+        terminated.terminated_at = Some(start);
+        assert!(LaunchProgress::new(&terminated).is_finished());
+    }
+}
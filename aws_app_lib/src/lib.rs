@@ -9,58 +9,102 @@
 #![allow(clippy::default_trait_access)]
 #![allow(clippy::cast_possible_wrap)]
 
+pub mod account_profile;
+pub mod acm_instance;
+pub mod ami_catalog;
+pub mod aws_api;
 pub mod aws_app_interface;
 pub mod aws_app_opts;
+pub mod aws_error;
+pub mod backup_instance;
+pub mod bucket_summary;
 pub mod config;
+pub mod cost_attribution;
+pub mod credential_report;
+pub mod cron_schedule;
+pub mod crontab;
+pub mod csv_export;
 pub mod date_time_wrapper;
+pub mod db_backup;
+pub mod ddns;
+pub mod decommission;
+pub mod docker_instance;
+pub mod dr_policy;
 pub mod ec2_instance;
+pub mod ecr_history;
 pub mod ecr_instance;
+pub mod ecs_instance;
+pub mod email_forward;
+pub mod email_reply;
+pub mod email_thread;
+pub mod hooks;
 pub mod iam_instance;
 pub mod inbound_email;
 pub mod instance_family;
+pub mod instance_filter;
+pub mod instance_metadata;
 pub mod instance_opt;
+pub mod inventory;
+pub mod known_hosts;
+pub mod lambda_instance;
+pub mod launch_progress;
 pub mod models;
+pub mod naming_policy;
+pub mod notification;
 pub mod novnc_instance;
+pub mod output_format;
 pub mod pgpool;
+pub mod price_alert;
+pub mod price_forecast;
 pub mod pricing_instance;
+pub mod quota_instance;
+pub mod reencrypt;
+pub mod remote_client;
+pub mod replacement;
+pub mod resource_cache;
+pub mod resource_graph;
 pub mod resource_type;
+pub mod retry;
 pub mod route53_instance;
 pub mod s3_instance;
+pub mod schema;
 pub mod scrape_instance_info;
 pub mod scrape_pricing_info;
+pub mod secrets_instance;
+pub mod ses_admin;
 pub mod ses_client;
+pub mod setup_check;
+pub mod sns_event;
+pub mod spam_filter;
 pub mod spot_request_opt;
+pub mod sqlite_store;
+pub mod sqs_instance;
 pub mod ssh_instance;
+pub mod storage;
+pub mod sts_instance;
 pub mod sysinfo_instance;
 pub mod systemd_instance;
+pub mod tag_search;
+pub mod telemetry;
+pub mod terraform_drift;
+pub mod tui;
+pub mod user_preferences;
+pub mod waste;
+pub mod watch;
+pub mod webhook;
 
 use anyhow::Error;
-use rand::{
-    distributions::{Distribution, Uniform},
-    thread_rng,
-};
 use std::future::Future;
-use tokio::time::{sleep, Duration};
 
+use crate::retry::{retry_with_policy, RetryPolicy};
+
+/// `retry_with_policy` with the default policy
 /// # Errors
-/// Returns error if timeout is reached
+/// Returns error if the error is not retryable or the policy is exhausted
 pub async fn exponential_retry<T, U, F>(f: T) -> Result<U, Error>
 where
     T: Fn() -> F,
     F: Future<Output = Result<U, Error>>,
 {
-    let mut timeout: f64 = 1.0;
-    let range = Uniform::from(0..1000);
-    loop {
-        match f().await {
-            Ok(resp) => return Ok(resp),
-            Err(err) => {
-                sleep(Duration::from_millis((timeout * 1000.0) as u64)).await;
-                timeout *= 4.0 * f64::from(range.sample(&mut thread_rng())) / 1000.0;
-                if timeout >= 64.0 {
-                    return Err(err);
-                }
-            }
-        }
-    }
+    retry_with_policy(&RetryPolicy::default(), "aws", f).await
 }
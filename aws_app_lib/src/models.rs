@@ -3,10 +3,16 @@ use futures::Stream;
 use mail_parser::{MessageParser, MimeHeaders, PartType};
 use postgres_query::{client::GenericClient, query, query_dyn, Error as PqError, FromSqlRow};
 use roxmltree::{Document, NodeType};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use stack_string::{format_sstr, StackString};
-use std::{collections::HashSet, fmt};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
+};
 use tempfile::TempDir;
-use time::OffsetDateTime;
+use time::{Duration, OffsetDateTime};
 use tokio::fs;
 use uuid::Uuid;
 
@@ -14,9 +20,11 @@ use crate::{
     config::Config,
     pgpool::{PgPool, PgTransaction},
     s3_instance::S3Instance,
+    spam_filter::SPAM_THRESHOLD,
+    storage::{EmailAttachmentRepo, Storage},
 };
 
-#[derive(FromSqlRow, Clone, Debug, PartialEq, Eq)]
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct InstanceFamily {
     pub family_name: StackString,
     pub family_type: StackString,
@@ -122,13 +130,16 @@ impl InstanceFamily {
     }
 }
 
-#[derive(FromSqlRow, Clone, Debug, PartialEq)]
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct InstanceList {
     pub instance_type: StackString,
     pub family_name: StackString,
     pub n_cpu: i32,
     pub memory_gib: f64,
     pub generation: StackString,
+    pub gpu_count: i32,
+    pub gpu_type: Option<StackString>,
+    pub network_performance: Option<StackString>,
 }
 
 impl InstanceList {
@@ -188,9 +199,11 @@ impl InstanceList {
         let query = query!(
             r#"
                 INSERT INTO instance_list (
-                    instance_type, family_name, n_cpu, memory_gib, generation
+                    instance_type, family_name, n_cpu, memory_gib, generation,
+                    gpu_count, gpu_type, network_performance
                 ) VALUES (
-                    $instance_type, $family_name, $n_cpu, $memory_gib, $generation
+                    $instance_type, $family_name, $n_cpu, $memory_gib, $generation,
+                    $gpu_count, $gpu_type, $network_performance
                 )
             "#,
             instance_type = self.instance_type,
@@ -198,6 +211,9 @@ impl InstanceList {
             n_cpu = self.n_cpu,
             memory_gib = self.memory_gib,
             generation = self.generation,
+            gpu_count = self.gpu_count,
+            gpu_type = self.gpu_type,
+            network_performance = self.network_performance,
         );
         query.execute(conn).await?;
         Ok(())
@@ -210,7 +226,9 @@ impl InstanceList {
         let query = query!(
             r#"
                 UPDATE instance_list
-                SET family_name=$family_name, n_cpu=$n_cpu, memory_gib=$memory_gib, generation=$generation
+                SET family_name=$family_name, n_cpu=$n_cpu, memory_gib=$memory_gib, generation=$generation,
+                    gpu_count=$gpu_count, gpu_type=$gpu_type,
+                    network_performance=$network_performance
                 WHERE instance_type = $instance_type
             "#,
             instance_type = self.instance_type,
@@ -218,6 +236,9 @@ impl InstanceList {
             n_cpu = self.n_cpu,
             memory_gib = self.memory_gib,
             generation = self.generation,
+            gpu_count = self.gpu_count,
+            gpu_type = self.gpu_type,
+            network_performance = self.network_performance,
         );
         query.execute(conn).await?;
         Ok(())
@@ -367,11 +388,136 @@ impl InstancePricing {
         } else {
             self.update_entry(conn).await?;
         }
+        PriceHistory::from(self).insert_entry_impl(conn).await?;
         tran.commit().await?;
         Ok(existing_entry)
     }
 }
 
+/// Every price written to `instance_pricing`, `instance_pricing` itself only
+/// keeps the latest
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Serialize)]
+pub struct PriceHistory {
+    pub id: Uuid,
+    pub instance_type: StackString,
+    pub price_type: StackString,
+    pub price: f64,
+    pub price_timestamp: OffsetDateTime,
+    pub recorded_at: OffsetDateTime,
+}
+
+impl From<&InstancePricing> for PriceHistory {
+    fn from(item: &InstancePricing) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            instance_type: item.instance_type.clone(),
+            price_type: item.price_type.clone(),
+            price: item.price,
+            price_timestamp: item.price_timestamp,
+            recorded_at: OffsetDateTime::now_utc(),
+        }
+    }
+}
+
+impl PriceHistory {
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_instance_type(
+        instance_type: &str,
+        pool: &PgPool,
+    ) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r#"
+                SELECT * FROM price_history
+                WHERE instance_type = $instance_type
+                ORDER BY recorded_at
+            "#,
+            instance_type = instance_type,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Every price of `price_type` recorded since `since`, oldest first
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_price_type(
+        price_type: &str,
+        since: OffsetDateTime,
+        pool: &PgPool,
+    ) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r#"
+                SELECT * FROM price_history
+                WHERE price_type = $price_type
+                  AND recorded_at >= $since
+                ORDER BY recorded_at
+            "#,
+            price_type = price_type,
+            since = since,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// The most recent price of each (`instance_type`, `price_type`) recorded
+    /// at or before `timestamp`
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_prices_as_of(
+        timestamp: OffsetDateTime,
+        pool: &PgPool,
+    ) -> Result<impl Stream<Item = Result<Self, PqError>>, Error> {
+        let query = query!(
+            r#"
+                SELECT DISTINCT ON (instance_type, price_type) *
+                FROM price_history
+                WHERE recorded_at <= $timestamp
+                ORDER BY instance_type, price_type, recorded_at DESC
+            "#,
+            timestamp = timestamp,
+        );
+        let conn = pool.get().await?;
+        query.fetch_streaming(&conn).await.map_err(Into::into)
+    }
+
+    async fn insert_entry_impl<C>(&self, conn: &C) -> Result<(), Error>
+    where
+        C: GenericClient + Sync,
+    {
+        let query = query!(
+            r#"
+                INSERT INTO price_history (
+                    id, instance_type, price_type, price, price_timestamp, recorded_at
+                ) VALUES (
+                    $id, $instance_type, $price_type, $price, $price_timestamp, $recorded_at
+                )
+            "#,
+            id = self.id,
+            instance_type = self.instance_type,
+            price_type = self.price_type,
+            price = self.price,
+            price_timestamp = self.price_timestamp,
+            recorded_at = self.recorded_at,
+        );
+        query.execute(conn).await?;
+        Ok(())
+    }
+
+    /// Remove entries recorded more than `days` days ago
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn prune(days: i64, pool: &PgPool) -> Result<u64, Error> {
+        let cutoff = OffsetDateTime::now_utc() - Duration::days(days);
+        let query = query!(
+            "DELETE FROM price_history WHERE recorded_at < $cutoff",
+            cutoff = cutoff,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+}
+
 #[derive(Clone, Copy)]
 pub enum AwsGeneration {
     HVM,
@@ -468,6 +614,36 @@ impl AuthorizedUsers {
             None => Ok(None),
         }
     }
+
+    /// Theme chosen with the index page toggle, `None` until one is chosen
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_theme(pool: &PgPool, email: &str) -> Result<Option<StackString>, Error> {
+        #[derive(FromSqlRow)]
+        struct Theme {
+            theme: Option<StackString>,
+        }
+
+        let query = query!(
+            "SELECT theme FROM authorized_users WHERE email = $email AND deleted_at IS NULL",
+            email = email,
+        );
+        let conn = pool.get().await?;
+        let result: Option<Theme> = query.fetch_opt(&conn).await?;
+        Ok(result.and_then(|r| r.theme))
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn set_theme(pool: &PgPool, email: &str, theme: &str) -> Result<u64, Error> {
+        let query = query!(
+            "UPDATE authorized_users SET theme = $theme WHERE email = $email AND deleted_at IS NULL",
+            email = email,
+            theme = theme,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
 }
 
 #[derive(FromSqlRow, Clone, Debug, PartialEq)]
@@ -482,6 +658,14 @@ pub struct InboundEmailDB {
     pub text_content: StackString,
     pub html_content: StackString,
     pub raw_email: StackString,
+    pub spam_score: f64,
+    pub verdicts: Option<StackString>,
+    pub spam_feedback: Option<bool>,
+    pub message_id: Option<StackString>,
+    pub in_reply_to: Option<StackString>,
+    /// Shared by the emails of a conversation, the id of the first email
+    /// seen unless it replies to a known one
+    pub thread_id: Uuid,
 }
 
 #[derive(FromSqlRow, Clone, Debug)]
@@ -492,6 +676,86 @@ pub struct InboundEmailBucketKey {
 }
 
 impl InboundEmailDB {
+    /// Whether the email is spam, a user's verdict takes precedence over the
+    /// computed score
+    #[must_use]
+    pub fn is_spam(&self) -> bool {
+        self.spam_feedback
+            .unwrap_or(self.spam_score >= SPAM_THRESHOLD)
+    }
+
+    /// Whether any email from `from_address` has been marked as spam
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn is_known_spam_sender(pool: &PgPool, from_address: &str) -> Result<bool, Error> {
+        let query = query!(
+            r"
+                SELECT count(*) FROM inbound_email
+                WHERE from_address = $from_address
+                  AND spam_feedback
+            ",
+            from_address = from_address,
+        );
+        let conn = pool.get().await?;
+        let (count,): (i64,) = query.fetch_one(&conn).await?;
+        Ok(count > 0)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn set_spam_feedback(id: Uuid, spam: bool, pool: &PgPool) -> Result<u64, Error> {
+        let query = query!(
+            "UPDATE inbound_email SET spam_feedback = $spam WHERE id = $id",
+            id = id,
+            spam = spam,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+
+    /// Thread of the email with Message-ID `message_id`
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_thread_id(pool: &PgPool, message_id: &str) -> Result<Option<Uuid>, Error> {
+        let query = query!(
+            r"
+                SELECT thread_id FROM inbound_email
+                WHERE message_id = $message_id
+                ORDER BY date
+                LIMIT 1
+            ",
+            message_id = message_id,
+        );
+        let conn = pool.get().await?;
+        let thread_id: Option<(Uuid,)> = query.fetch_opt(&conn).await?;
+        Ok(thread_id.map(|(t,)| t))
+    }
+
+    /// Move the threads of replies to `message_id` that arrived before it
+    /// into `thread_id`
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn merge_replies(
+        pool: &PgPool,
+        message_id: &str,
+        thread_id: Uuid,
+    ) -> Result<u64, Error> {
+        let query = query!(
+            r"
+                UPDATE inbound_email SET thread_id = $thread_id
+                WHERE thread_id != $thread_id
+                  AND thread_id IN (
+                    SELECT thread_id FROM inbound_email
+                    WHERE in_reply_to = $message_id
+                  )
+            ",
+            message_id = message_id,
+            thread_id = thread_id,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+
     /// # Errors
     /// Returns error if db query fails
     pub async fn get_keys(
@@ -519,7 +783,7 @@ impl InboundEmailDB {
             query.push_str(&format_sstr!(" OFFSET {offset}"));
         }
         if let Some(limit) = limit {
-            query.push_str(&format_sstr!(" LMIIT {limit}"));
+            query.push_str(&format_sstr!(" LIMIT {limit}"));
         }
         let query = query_dyn!(&query)?;
         let conn = pool.get().await?;
@@ -551,8 +815,8 @@ impl InboundEmailDB {
         let query = query!(
             r"
                 SELECT * FROM inbound_email
-                WHERE bucket = $bucket
-                  AND key = $key
+                WHERE s3_bucket = $bucket
+                  AND s3_key = $key
             ",
             bucket = bucket,
             key = key,
@@ -569,10 +833,14 @@ impl InboundEmailDB {
             r"
                 INSERT INTO inbound_email (
                     id, s3_bucket, s3_key, from_address, to_address,
-                    subject, date, text_content, html_content, raw_email
+                    subject, date, text_content, html_content, raw_email,
+                    spam_score, verdicts, spam_feedback, message_id, in_reply_to,
+                    thread_id
                 ) VALUES (
                     $id, $s3_bucket, $s3_key, $from_address, $to_address,
-                    $subject, $date, $text_content, $html_content, $raw_email
+                    $subject, $date, $text_content, $html_content, $raw_email,
+                    $spam_score, $verdicts, $spam_feedback, $message_id, $in_reply_to,
+                    $thread_id
                 )
             ",
             id = self.id,
@@ -585,6 +853,12 @@ impl InboundEmailDB {
             text_content = self.text_content,
             html_content = self.html_content,
             raw_email = self.raw_email,
+            spam_score = self.spam_score,
+            verdicts = self.verdicts,
+            spam_feedback = self.spam_feedback,
+            message_id = self.message_id,
+            in_reply_to = self.in_reply_to,
+            thread_id = self.thread_id,
         );
         query.execute(conn).await?;
         Ok(())
@@ -605,7 +879,13 @@ impl InboundEmailDB {
                     date=$date,
                     text_content=$text_content,
                     html_content=$html_content,
-                    raw_email=$raw_email
+                    raw_email=$raw_email,
+                    spam_score=$spam_score,
+                    verdicts=$verdicts,
+                    spam_feedback=$spam_feedback,
+                    message_id=$message_id,
+                    in_reply_to=$in_reply_to,
+                    thread_id=$thread_id
                 WHERE id = $id
             ",
             id = self.id,
@@ -618,6 +898,12 @@ impl InboundEmailDB {
             text_content = self.text_content,
             html_content = self.html_content,
             raw_email = self.raw_email,
+            spam_score = self.spam_score,
+            verdicts = self.verdicts,
+            spam_feedback = self.spam_feedback,
+            message_id = self.message_id,
+            in_reply_to = self.in_reply_to,
+            thread_id = self.thread_id,
         );
         query.execute(conn).await?;
         Ok(())
@@ -650,12 +936,15 @@ impl InboundEmailDB {
         Ok(())
     }
 
+    /// Upload attachments missing from s3 and record each one in the
+    /// `email_attachment` table, returns the newly uploaded keys
     /// # Errors
     /// Returns error if db query fails
     pub async fn extract_attachments(
         &self,
         config: &Config,
         s3: &S3Instance,
+        storage: &dyn Storage,
     ) -> Result<Vec<StackString>, Error> {
         let mut extracted_attachments = Vec::new();
         let parser = MessageParser::default();
@@ -680,6 +969,22 @@ impl InboundEmailDB {
                         .and_then(|c| c.attribute("filename").or_else(|| c.attribute("name")))
                     {
                         let s3key = format_sstr!("attachments/{filename}");
+                        let content_type = attachment.content_type().map(|c| {
+                            c.subtype().map_or_else(
+                                || c.ctype().into(),
+                                |s| format_sstr!("{}/{s}", c.ctype()),
+                            )
+                        });
+                        let size = i64::try_from(body.len())?;
+                        let entry = EmailAttachment::new(
+                            self.id,
+                            filename,
+                            content_type,
+                            size,
+                            bucket.as_str(),
+                            s3key.as_str(),
+                        );
+                        storage.upsert_attachment(&entry).await?;
                         if attachments.contains(&s3key) {
                             continue;
                         }
@@ -687,11 +992,6 @@ impl InboundEmailDB {
                         fs::write(&filepath, &body).await?;
                         s3.upload(&filepath, bucket, &s3key).await?;
                         extracted_attachments.push(s3key);
-                        if let Some(content_type) = attachment.content_disposition() {
-                            for a in content_type.attributes().unwrap_or(&[]) {
-                                println!("{a:?}");
-                            }
-                        }
                     }
                 }
             }
@@ -894,25 +1194,1096 @@ impl DmarcRecords {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use anyhow::Error;
-    use flate2::read::GzDecoder;
-    use std::{fs, io::Read};
-    use tempfile::TempDir;
+/// Spot request status codes which may still be fulfilled
+pub const SPOT_PENDING_STATUSES: [&str; 3] =
+    ["requested", "pending-evaluation", "pending-fulfillment"];
 
-    use crate::models::DmarcRecords;
+/// `recovery_status` of a launch whose replacement was requested
+pub const RECOVERY_RECOVERED: &str = "recovered";
+/// `recovery_status` of a launch that ran out of recovery attempts
+pub const RECOVERY_EXHAUSTED: &str = "exhausted";
+/// `recovery_status` of a launch whose instance was terminated by its owner
+pub const RECOVERY_SKIPPED: &str = "skipped";
 
-    #[tokio::test]
-    async fn test_parse_xml() -> Result<(), Error> {
-        let td = TempDir::new()?;
-        let data = include_bytes!("../../tests/data/temp.xml.gz");
-        let p = td.path().join("temp.xml.gz");
-        fs::write(&p, data)?;
-        let mut body = String::new();
-        GzDecoder::new(fs::File::open(&p)?).read_to_string(&mut body)?;
-        let records = DmarcRecords::parse_xml(&body, Some("test_key"))?;
-        assert_eq!(records.len(), 21);
+#[derive(FromSqlRow, Clone, Debug, PartialEq)]
+pub struct LaunchHistory {
+    pub id: Uuid,
+    pub instance_id: Option<StackString>,
+    pub spot_request_id: Option<StackString>,
+    pub instance_type: StackString,
+    pub ami: StackString,
+    pub is_spot: bool,
+    pub status: StackString,
+    pub launched_at: OffsetDateTime,
+    pub terminated_at: Option<OffsetDateTime>,
+    /// The `SpotRequest` the launch was made with, as json
+    pub launch_params: Option<Value>,
+    /// Re-request the spot instance when aws terminates it
+    pub auto_recover: bool,
+    /// Failed attempts at recovering this launch
+    pub recovery_attempts: i32,
+    pub recovery_status: Option<StackString>,
+    /// One of `SETUP_DONE`, `SETUP_FAILED` or `SETUP_TIMEOUT` once the
+    /// post-launch setup check is finished
+    pub setup_status: Option<StackString>,
+    /// Seconds from instance launch until cloud-init finished
+    pub setup_seconds: Option<i32>,
+    /// Cloud-init error detail of a failed setup
+    pub setup_message: Option<StackString>,
+    /// Host name to point at the instance once it is running
+    pub dns_name: Option<StackString>,
+    pub dns_registered_at: Option<OffsetDateTime>,
+    /// When the spot request was fulfilled, the launch time of ondemand
+    /// instances
+    pub fulfilled_at: Option<OffsetDateTime>,
+    /// When the instance was first seen running
+    pub running_at: Option<OffsetDateTime>,
+    /// When cloud-init was first seen done
+    pub cloud_init_at: Option<OffsetDateTime>,
+    /// When the setup check passed
+    pub healthy_at: Option<OffsetDateTime>,
+}
+
+impl LaunchHistory {
+    #[must_use]
+    pub fn new(
+        instance_type: impl Into<StackString>,
+        ami: impl Into<StackString>,
+        is_spot: bool,
+    ) -> Self {
+        let launched_at = OffsetDateTime::now_utc();
+        Self {
+            id: Uuid::new_v4(),
+            instance_id: None,
+            spot_request_id: None,
+            instance_type: instance_type.into(),
+            ami: ami.into(),
+            is_spot,
+            status: if is_spot { "requested" } else { "running" }.into(),
+            launched_at,
+            terminated_at: None,
+            launch_params: None,
+            auto_recover: false,
+            recovery_attempts: 0,
+            recovery_status: None,
+            setup_status: None,
+            setup_seconds: None,
+            setup_message: None,
+            dns_name: None,
+            dns_registered_at: None,
+            fulfilled_at: if is_spot { None } else { Some(launched_at) },
+            running_at: None,
+            cloud_init_at: None,
+            healthy_at: None,
+        }
+    }
+
+    #[must_use]
+    pub fn is_pending_spot(&self) -> bool {
+        self.is_spot
+            && self.instance_id.is_none()
+            && SPOT_PENDING_STATUSES.contains(&self.status.as_str())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO launch_history (
+                    id, instance_id, spot_request_id, instance_type, ami, is_spot,
+                    status, launched_at, terminated_at, launch_params, auto_recover,
+                    recovery_attempts, recovery_status, setup_status, setup_seconds,
+                    setup_message, dns_name, dns_registered_at, fulfilled_at, running_at,
+                    cloud_init_at, healthy_at
+                ) VALUES (
+                    $id, $instance_id, $spot_request_id, $instance_type, $ami, $is_spot,
+                    $status, $launched_at, $terminated_at, $launch_params, $auto_recover,
+                    $recovery_attempts, $recovery_status, $setup_status, $setup_seconds,
+                    $setup_message, $dns_name, $dns_registered_at, $fulfilled_at, $running_at,
+                    $cloud_init_at, $healthy_at
+                )
+            ",
+            id = self.id,
+            instance_id = self.instance_id,
+            spot_request_id = self.spot_request_id,
+            instance_type = self.instance_type,
+            ami = self.ami,
+            is_spot = self.is_spot,
+            status = self.status,
+            launched_at = self.launched_at,
+            terminated_at = self.terminated_at,
+            launch_params = self.launch_params,
+            auto_recover = self.auto_recover,
+            recovery_attempts = self.recovery_attempts,
+            recovery_status = self.recovery_status,
+            setup_status = self.setup_status,
+            setup_seconds = self.setup_seconds,
+            setup_message = self.setup_message,
+            dns_name = self.dns_name,
+            dns_registered_at = self.dns_registered_at,
+            fulfilled_at = self.fulfilled_at,
+            running_at = self.running_at,
+            cloud_init_at = self.cloud_init_at,
+            healthy_at = self.healthy_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn update_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                UPDATE launch_history
+                SET instance_id=$instance_id,status=$status,terminated_at=$terminated_at,
+                    auto_recover=$auto_recover,recovery_attempts=$recovery_attempts,
+                    recovery_status=$recovery_status,setup_status=$setup_status,
+                    setup_seconds=$setup_seconds,setup_message=$setup_message,
+                    dns_registered_at=$dns_registered_at,fulfilled_at=$fulfilled_at,
+                    running_at=$running_at,cloud_init_at=$cloud_init_at,healthy_at=$healthy_at
+                WHERE id=$id
+            ",
+            id = self.id,
+            instance_id = self.instance_id,
+            status = self.status,
+            terminated_at = self.terminated_at,
+            auto_recover = self.auto_recover,
+            recovery_attempts = self.recovery_attempts,
+            recovery_status = self.recovery_status,
+            setup_status = self.setup_status,
+            setup_seconds = self.setup_seconds,
+            setup_message = self.setup_message,
+            dns_registered_at = self.dns_registered_at,
+            fulfilled_at = self.fulfilled_at,
+            running_at = self.running_at,
+            cloud_init_at = self.cloud_init_at,
+            healthy_at = self.healthy_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
         Ok(())
     }
+
+    /// Launches made since `since`, most recent first
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_recent(pool: &PgPool, since: OffsetDateTime) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM launch_history WHERE launched_at >= $since ORDER BY launched_at DESC",
+            since = since,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Launches which are still waiting on a spot request or whose instance
+    /// hasn't been seen terminated yet
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_open(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM launch_history
+                WHERE terminated_at IS NULL
+                  AND (instance_id IS NOT NULL OR status IN ($s0, $s1, $s2))
+            ",
+            s0 = SPOT_PENDING_STATUSES[0],
+            s1 = SPOT_PENDING_STATUSES[1],
+            s2 = SPOT_PENDING_STATUSES[2],
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Terminated spot launches flagged for recovery which haven't been
+    /// recovered, given up on or skipped yet
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_recoverable(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM launch_history
+                WHERE is_spot
+                  AND auto_recover
+                  AND terminated_at IS NOT NULL
+                  AND recovery_status IS NULL
+                  AND launch_params IS NOT NULL
+                ORDER BY terminated_at
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Running launches whose setup hasn't been checked to completion yet
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_pending_setup(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM launch_history
+                WHERE instance_id IS NOT NULL
+                  AND terminated_at IS NULL
+                  AND setup_status IS NULL
+                ORDER BY launched_at
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Running launches whose dns name hasn't been registered yet
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_pending_dns(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM launch_history
+                WHERE instance_id IS NOT NULL
+                  AND terminated_at IS NULL
+                  AND dns_name IS NOT NULL
+                  AND dns_registered_at IS NULL
+                ORDER BY launched_at
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Set the auto-recover flag of the running launch of `instance_id`,
+    /// returns false if there is no such launch
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn set_auto_recover(
+        pool: &PgPool,
+        instance_id: &str,
+        auto_recover: bool,
+    ) -> Result<bool, Error> {
+        let query = query!(
+            r"
+                UPDATE launch_history
+                SET auto_recover=$auto_recover
+                WHERE instance_id=$instance_id AND is_spot AND terminated_at IS NULL
+            ",
+            auto_recover = auto_recover,
+            instance_id = instance_id,
+        );
+        let conn = pool.get().await?;
+        let updated = query.execute(&conn).await?;
+        Ok(updated > 0)
+    }
+
+    /// Record an observed termination of `instance_id`, unlike
+    /// `set_terminated` the launch stays eligible for spot recovery. Returns
+    /// the number of launches updated
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn record_termination(
+        pool: &PgPool,
+        instance_id: &str,
+        terminated_at: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        let query = query!(
+            r"
+                UPDATE launch_history
+                SET terminated_at=$terminated_at
+                WHERE instance_id=$instance_id AND terminated_at IS NULL
+            ",
+            terminated_at = terminated_at,
+            instance_id = instance_id,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+
+    /// Most recent launch of each ami
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_last_launch_by_ami(
+        pool: &PgPool,
+    ) -> Result<HashMap<StackString, OffsetDateTime>, Error> {
+        #[derive(FromSqlRow)]
+        struct AmiLastLaunch {
+            ami: StackString,
+            last_launch: OffsetDateTime,
+        }
+
+        let query =
+            query!("SELECT ami, max(launched_at) AS last_launch FROM launch_history GROUP BY ami");
+        let conn = pool.get().await?;
+        let launches: Vec<AmiLastLaunch> = query.fetch(&conn).await?;
+        Ok(launches
+            .into_iter()
+            .map(|l| (l.ami, l.last_launch))
+            .collect())
+    }
+
+    /// Most recent launch of `instance_id`
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_instance_id(
+        pool: &PgPool,
+        instance_id: &str,
+    ) -> Result<Option<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM launch_history
+                WHERE instance_id = $instance_id
+                ORDER BY launched_at DESC
+                LIMIT 1
+            ",
+            instance_id = instance_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_spot_request_id(
+        pool: &PgPool,
+        spot_request_id: &str,
+    ) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM launch_history WHERE spot_request_id = $spot_request_id",
+            spot_request_id = spot_request_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// Instances terminated through the app are never auto-recovered
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn set_terminated(
+        pool: &PgPool,
+        instance_ids: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<(), Error> {
+        let terminated_at = OffsetDateTime::now_utc();
+        let conn = pool.get().await?;
+        for instance_id in instance_ids {
+            let instance_id = instance_id.as_ref();
+            let query = query!(
+                r"
+                    UPDATE launch_history
+                    SET terminated_at=$terminated_at,auto_recover=false
+                    WHERE instance_id=$instance_id AND terminated_at IS NULL
+                ",
+                terminated_at = terminated_at,
+                instance_id = instance_id,
+            );
+            query.execute(&conn).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Build record of an AMI created by the app
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AmiCatalogEntry {
+    pub ami_id: StackString,
+    pub name: StackString,
+    /// Ubuntu release the lineage started from, e.g. `noble-24.04`
+    pub base_release: Option<StackString>,
+    /// File name of the user-data script the instance was launched with
+    pub build_script: Option<StackString>,
+    pub build_date: OffsetDateTime,
+    /// AMI the instance the image was created from was launched with
+    pub parent_ami: Option<StackString>,
+    pub instance_id: Option<StackString>,
+}
+
+impl AmiCatalogEntry {
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM ami_catalog ORDER BY build_date");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_ami(pool: &PgPool, ami_id: &str) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM ami_catalog WHERE ami_id = $ami_id",
+            ami_id = ami_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn upsert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO ami_catalog (
+                    ami_id, name, base_release, build_script, build_date, parent_ami,
+                    instance_id
+                ) VALUES (
+                    $ami_id, $name, $base_release, $build_script, $build_date, $parent_ami,
+                    $instance_id
+                ) ON CONFLICT (ami_id) DO UPDATE
+                SET name=$name,base_release=$base_release,build_script=$build_script,
+                    build_date=$build_date,parent_ami=$parent_ami,instance_id=$instance_id
+            ",
+            ami_id = self.ami_id,
+            name = self.name,
+            base_release = self.base_release,
+            build_script = self.build_script,
+            build_date = self.build_date,
+            parent_ami = self.parent_ami,
+            instance_id = self.instance_id,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+#[derive(FromSqlRow, Clone, Debug, PartialEq)]
+pub struct WeeklyLaunches {
+    pub week: OffsetDateTime,
+    pub launches: i64,
+    pub spot_launches: i64,
+}
+
+#[derive(FromSqlRow, Clone, Debug, PartialEq)]
+pub struct LaunchCount {
+    pub key: StackString,
+    pub count: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct LaunchAnalytics {
+    pub weekly_launches: Vec<WeeklyLaunches>,
+    pub total_launches: i64,
+    pub spot_launches: i64,
+    pub failed_spot_requests: i64,
+    pub average_lifetime_hours: Option<f64>,
+    pub top_amis: Vec<LaunchCount>,
+    pub top_instance_types: Vec<LaunchCount>,
+}
+
+impl LaunchAnalytics {
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_analytics(pool: &PgPool, weeks: i64, limit: i64) -> Result<Self, Error> {
+        #[derive(FromSqlRow)]
+        struct LaunchTotals {
+            total_launches: i64,
+            spot_launches: i64,
+            failed_spot_requests: i64,
+            average_lifetime_hours: Option<f64>,
+        }
+
+        let conn = pool.get().await?;
+
+        let query = query!(
+            r"
+                SELECT date_trunc('week', launched_at) as week,
+                       count(*) as launches,
+                       count(*) FILTER (WHERE is_spot) as spot_launches
+                FROM launch_history
+                GROUP BY 1
+                ORDER BY 1 DESC
+                LIMIT $weeks
+            ",
+            weeks = weeks,
+        );
+        let weekly_launches: Vec<WeeklyLaunches> = query.fetch(&conn).await?;
+
+        let query = query!(
+            r"
+                SELECT count(*) as total_launches,
+                       count(*) FILTER (WHERE is_spot) as spot_launches,
+                       count(*) FILTER (
+                           WHERE is_spot
+                             AND instance_id IS NULL
+                             AND status NOT IN ($s0, $s1, $s2)
+                       ) as failed_spot_requests,
+                       (avg(extract(epoch FROM terminated_at - launched_at)) / 3600.0)::float8
+                           as average_lifetime_hours
+                FROM launch_history
+            ",
+            s0 = SPOT_PENDING_STATUSES[0],
+            s1 = SPOT_PENDING_STATUSES[1],
+            s2 = SPOT_PENDING_STATUSES[2],
+        );
+        let totals: LaunchTotals = query.fetch_one(&conn).await?;
+
+        let query = query!(
+            r"
+                SELECT ami as key, count(*) as count
+                FROM launch_history
+                GROUP BY 1
+                ORDER BY 2 DESC
+                LIMIT $limit
+            ",
+            limit = limit,
+        );
+        let top_amis: Vec<LaunchCount> = query.fetch(&conn).await?;
+
+        let query = query!(
+            r"
+                SELECT instance_type as key, count(*) as count
+                FROM launch_history
+                GROUP BY 1
+                ORDER BY 2 DESC
+                LIMIT $limit
+            ",
+            limit = limit,
+        );
+        let top_instance_types: Vec<LaunchCount> = query.fetch(&conn).await?;
+
+        Ok(Self {
+            weekly_launches,
+            total_launches: totals.total_launches,
+            spot_launches: totals.spot_launches,
+            failed_spot_requests: totals.failed_spot_requests,
+            average_lifetime_hours: totals.average_lifetime_hours,
+            top_amis,
+            top_instance_types,
+        })
+    }
+
+    /// Fraction of all launches that were spot requests
+    #[must_use]
+    pub fn spot_fraction(&self) -> Option<f64> {
+        if self.total_launches > 0 {
+            Some(self.spot_launches as f64 / self.total_launches as f64)
+        } else {
+            None
+        }
+    }
+
+    /// Fraction of spot requests that were never fulfilled
+    #[must_use]
+    pub fn spot_failure_rate(&self) -> Option<f64> {
+        if self.spot_launches > 0 {
+            Some(self.failed_spot_requests as f64 / self.spot_launches as f64)
+        } else {
+            None
+        }
+    }
+}
+
+/// Instance or volume ids and Name tags which may not be terminated or
+/// deleted without an admin override, in addition to
+/// `Config::protected_resources`
+#[derive(FromSqlRow, Clone, Debug, PartialEq)]
+pub struct ProtectedResource {
+    pub resource_id: StackString,
+    pub reason: Option<StackString>,
+    pub created_at: OffsetDateTime,
+}
+
+impl ProtectedResource {
+    #[must_use]
+    pub fn new(resource_id: impl Into<StackString>, reason: Option<StackString>) -> Self {
+        Self {
+            resource_id: resource_id.into(),
+            reason,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM protected_resource ORDER BY resource_id");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn upsert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO protected_resource (resource_id, reason, created_at)
+                VALUES ($resource_id, $reason, $created_at)
+                ON CONFLICT (resource_id) DO UPDATE SET reason=EXCLUDED.reason
+            ",
+            resource_id = self.resource_id,
+            reason = self.reason,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn delete_entry(resource_id: &str, pool: &PgPool) -> Result<u64, Error> {
+        let query = query!(
+            "DELETE FROM protected_resource WHERE resource_id = $resource_id",
+            resource_id = resource_id,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+}
+
+/// Attachment extracted from an inbound email, `email_id` is not a foreign
+/// key so that attachments outlive a deleted email until they are cleaned up
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EmailAttachment {
+    pub id: Uuid,
+    pub email_id: Uuid,
+    pub filename: StackString,
+    pub content_type: Option<StackString>,
+    pub size: i64,
+    pub s3_bucket: StackString,
+    pub s3_key: StackString,
+    pub created_at: OffsetDateTime,
+}
+
+impl EmailAttachment {
+    #[must_use]
+    pub fn new(
+        email_id: Uuid,
+        filename: impl Into<StackString>,
+        content_type: Option<StackString>,
+        size: i64,
+        s3_bucket: impl Into<StackString>,
+        s3_key: impl Into<StackString>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            email_id,
+            filename: filename.into(),
+            content_type,
+            size,
+            s3_bucket: s3_bucket.into(),
+            s3_key: s3_key.into(),
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!("SELECT * FROM email_attachment WHERE id = $id", id = id);
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_email_id(pool: &PgPool, email_id: Uuid) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM email_attachment WHERE email_id = $email_id ORDER BY filename",
+            email_id = email_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Attachments whose email has been deleted
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_orphaned(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM email_attachment
+                WHERE email_id NOT IN (SELECT id FROM inbound_email)
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Number of attachment entries that point at `s3_key`
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn count_by_s3_key(pool: &PgPool, s3_key: &str) -> Result<i64, Error> {
+        let query = query!(
+            "SELECT count(*) FROM email_attachment WHERE s3_key = $s3_key",
+            s3_key = s3_key,
+        );
+        let conn = pool.get().await?;
+        let (count,): (i64,) = query.fetch_one(&conn).await?;
+        Ok(count)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn upsert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO email_attachment (
+                    id, email_id, filename, content_type, size, s3_bucket, s3_key, created_at
+                ) VALUES (
+                    $id, $email_id, $filename, $content_type, $size, $s3_bucket, $s3_key,
+                    $created_at
+                )
+                ON CONFLICT (email_id, s3_key) DO UPDATE
+                SET filename=EXCLUDED.filename,
+                    content_type=EXCLUDED.content_type,
+                    size=EXCLUDED.size
+            ",
+            id = self.id,
+            email_id = self.email_id,
+            filename = self.filename,
+            content_type = self.content_type,
+            size = self.size,
+            s3_bucket = self.s3_bucket,
+            s3_key = self.s3_key,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn delete_entry_by_id(id: Uuid, pool: &PgPool) -> Result<(), Error> {
+        let query = query!("DELETE FROM email_attachment WHERE id = $id", id = id);
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// Forward inbound email whose recipient and subject match the (case
+/// insensitive regex) patterns to `forward_to`, unset patterns match anything
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailForwardRule {
+    pub id: Uuid,
+    pub name: StackString,
+    pub recipient_pattern: Option<StackString>,
+    pub subject_pattern: Option<StackString>,
+    pub forward_to: StackString,
+    pub enabled: bool,
+    pub created_at: OffsetDateTime,
+}
+
+impl EmailForwardRule {
+    #[must_use]
+    pub fn new(
+        name: impl Into<StackString>,
+        recipient_pattern: Option<StackString>,
+        subject_pattern: Option<StackString>,
+        forward_to: impl Into<StackString>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            recipient_pattern,
+            subject_pattern,
+            forward_to: forward_to.into(),
+            enabled: true,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM email_forward_rule ORDER BY name");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!("SELECT * FROM email_forward_rule WHERE id = $id", id = id);
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn upsert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO email_forward_rule (
+                    id, name, recipient_pattern, subject_pattern, forward_to, enabled, created_at
+                ) VALUES (
+                    $id, $name, $recipient_pattern, $subject_pattern, $forward_to, $enabled,
+                    $created_at
+                )
+                ON CONFLICT (id) DO UPDATE
+                SET name=EXCLUDED.name,
+                    recipient_pattern=EXCLUDED.recipient_pattern,
+                    subject_pattern=EXCLUDED.subject_pattern,
+                    forward_to=EXCLUDED.forward_to,
+                    enabled=EXCLUDED.enabled
+            ",
+            id = self.id,
+            name = self.name,
+            recipient_pattern = self.recipient_pattern,
+            subject_pattern = self.subject_pattern,
+            forward_to = self.forward_to,
+            enabled = self.enabled,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn delete_entry(id: Uuid, pool: &PgPool) -> Result<u64, Error> {
+        let query = query!("DELETE FROM email_forward_rule WHERE id = $id", id = id);
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+}
+
+/// Reply sent from the dashboard to an inbound email, shown in the email's
+/// conversation
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SentEmail {
+    pub id: Uuid,
+    /// Inbound email replied to
+    pub email_id: Uuid,
+    pub thread_id: Uuid,
+    pub from_address: StackString,
+    pub to_address: StackString,
+    pub subject: StackString,
+    pub body: StackString,
+    /// Message-ID header assigned by SES, without angle brackets
+    pub message_id: Option<StackString>,
+    pub sent_at: OffsetDateTime,
+}
+
+impl SentEmail {
+    #[must_use]
+    pub fn new(
+        email: &InboundEmailDB,
+        from_address: impl Into<StackString>,
+        to_address: impl Into<StackString>,
+        subject: impl Into<StackString>,
+        body: impl Into<StackString>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            email_id: email.id,
+            thread_id: email.thread_id,
+            from_address: from_address.into(),
+            to_address: to_address.into(),
+            subject: subject.into(),
+            body: body.into(),
+            message_id: None,
+            sent_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM sent_email ORDER BY sent_at");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_thread_id(pool: &PgPool, thread_id: Uuid) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM sent_email WHERE thread_id = $thread_id ORDER BY sent_at",
+            thread_id = thread_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Thread of the reply with Message-ID `message_id`
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_thread_id(pool: &PgPool, message_id: &str) -> Result<Option<Uuid>, Error> {
+        let query = query!(
+            "SELECT thread_id FROM sent_email WHERE message_id = $message_id LIMIT 1",
+            message_id = message_id,
+        );
+        let conn = pool.get().await?;
+        let thread_id: Option<(Uuid,)> = query.fetch_opt(&conn).await?;
+        Ok(thread_id.map(|(t,)| t))
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO sent_email (
+                    id, email_id, thread_id, from_address, to_address, subject, body,
+                    message_id, sent_at
+                ) VALUES (
+                    $id, $email_id, $thread_id, $from_address, $to_address, $subject, $body,
+                    $message_id, $sent_at
+                )
+            ",
+            id = self.id,
+            email_id = self.email_id,
+            thread_id = self.thread_id,
+            from_address = self.from_address,
+            to_address = self.to_address,
+            subject = self.subject,
+            body = self.body,
+            message_id = self.message_id,
+            sent_at = self.sent_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// Changes made automatically (without a user request), e.g. dns updates by
+/// the ddns daemon
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AuditLog {
+    pub id: Uuid,
+    pub action: StackString,
+    pub resource: StackString,
+    pub details: Option<StackString>,
+    pub created_at: OffsetDateTime,
+}
+
+impl AuditLog {
+    #[must_use]
+    pub fn new(
+        action: impl Into<StackString>,
+        resource: impl Into<StackString>,
+        details: Option<StackString>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            action: action.into(),
+            resource: resource.into(),
+            details,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_recent(pool: &PgPool, limit: i64) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM audit_log ORDER BY created_at DESC LIMIT $limit",
+            limit = limit,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO audit_log (id, action, resource, details, created_at)
+                VALUES ($id, $action, $resource, $details, $created_at)
+            ",
+            id = self.id,
+            action = self.action,
+            resource = self.resource,
+            details = self.details,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// Outcome of the most recent run of each background update task
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Serialize)]
+pub struct UpdateStatus {
+    pub task: StackString,
+    pub last_attempt: OffsetDateTime,
+    pub last_success: Option<OffsetDateTime>,
+    pub last_error: Option<StackString>,
+}
+
+impl UpdateStatus {
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM update_status ORDER BY task");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Record a run of `task`, `last_success` is only moved forward when
+    /// `error` is `None`
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn record(task: &str, error: Option<&str>, pool: &PgPool) -> Result<(), Error> {
+        let now = OffsetDateTime::now_utc();
+        let last_success = if error.is_none() { Some(now) } else { None };
+        let query = query!(
+            r"
+                INSERT INTO update_status (task, last_attempt, last_success, last_error)
+                VALUES ($task, $last_attempt, $last_success, $last_error)
+                ON CONFLICT (task) DO UPDATE
+                SET last_attempt=EXCLUDED.last_attempt,
+                    last_success=COALESCE(EXCLUDED.last_success, update_status.last_success),
+                    last_error=EXCLUDED.last_error
+            ",
+            task = task,
+            last_attempt = now,
+            last_success = last_success,
+            last_error = error,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use flate2::read::GzDecoder;
+    use std::{fs, io::Read};
+    use tempfile::TempDir;
+
+    use crate::models::{DmarcRecords, LaunchAnalytics, LaunchHistory};
+
+    #[tokio::test]
+    async fn test_parse_xml() -> Result<(), Error> {
+        let td = TempDir::new()?;
+        let data = include_bytes!("../../tests/data/temp.xml.gz");
+        let p = td.path().join("temp.xml.gz");
+        fs::write(&p, data)?;
+        let mut body = String::new();
+        GzDecoder::new(fs::File::open(&p)?).read_to_string(&mut body)?;
+        let records = DmarcRecords::parse_xml(&body, Some("test_key"))?;
+        assert_eq!(records.len(), 21);
+        Ok(())
+    }
+
+    #[test]
+    fn test_launch_history_pending_spot() {
+        let mut launch = LaunchHistory::new("t3.micro", "ami-1234", true);
+        assert!(launch.is_pending_spot());
+        launch.status = "capacity-not-available".into();
+        assert!(!launch.is_pending_spot());
+        let launch = LaunchHistory::new("t3.micro", "ami-1234", false);
+        assert!(!launch.is_pending_spot());
+    }
+
+    #[test]
+    fn test_launch_analytics_rates() {
+        let mut analytics = LaunchAnalytics::default();
+        assert_eq!(analytics.spot_fraction(), None);
+        assert_eq!(analytics.spot_failure_rate(), None);
+        analytics.total_launches = 8;
+        analytics.spot_launches = 4;
+        analytics.failed_spot_requests = 1;
+        assert_eq!(analytics.spot_fraction(), Some(0.5));
+        assert_eq!(analytics.spot_failure_rate(), Some(0.25));
+    }
 }
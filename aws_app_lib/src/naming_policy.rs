@@ -0,0 +1,129 @@
+use anyhow::{format_err, Error};
+use regex::Regex;
+use stack_string::StackString;
+use std::collections::HashMap;
+
+use crate::{config::Config, resource_type::ResourceType};
+
+#[derive(Debug, Clone, Default)]
+pub struct NamingPolicy {
+    policies: HashMap<ResourceType, Regex>,
+    auto_fix: bool,
+}
+
+impl NamingPolicy {
+    /// Parse `naming_policies` entries of the form `resource=regex`,
+    /// e.g. `ami=^[a-z][a-z0-9-]+$`
+    /// # Errors
+    /// Returns error if an entry is malformed or the regex is invalid
+    pub fn from_config(config: &Config) -> Result<Self, Error> {
+        let policies = config
+            .naming_policies
+            .iter()
+            .map(|entry| {
+                let (resource, pattern) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format_err!("Invalid naming policy {entry}"))?;
+                let resource: ResourceType = resource.trim().parse()?;
+                let regex = Regex::new(pattern.trim())?;
+                Ok((resource, regex))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Self {
+            policies,
+            auto_fix: config.naming_policy_auto_fix,
+        })
+    }
+
+    /// Returns the name to use for a new resource, if `auto_fix` is set a
+    /// non-conforming name is replaced with the suggested one
+    /// # Errors
+    /// Returns error if name does not match the naming convention
+    pub fn check(&self, resource: ResourceType, name: &str) -> Result<StackString, Error> {
+        let regex = match self.policies.get(&resource) {
+            Some(regex) => regex,
+            None => return Ok(name.into()),
+        };
+        if regex.is_match(name) {
+            return Ok(name.into());
+        }
+        match self.suggest(resource, name) {
+            Some(suggestion) if self.auto_fix => Ok(suggestion),
+            Some(suggestion) => Err(format_err!(
+                "{resource} name {name} does not match naming convention {regex}, suggested name \
+                 {suggestion}"
+            )),
+            None => Err(format_err!(
+                "{resource} name {name} does not match naming convention {regex}"
+            )),
+        }
+    }
+
+    /// Lowercase the name and collapse anything that isn't alphanumeric into
+    /// a single dash, returns the result only if it satisfies the convention
+    #[must_use]
+    pub fn suggest(&self, resource: ResourceType, name: &str) -> Option<StackString> {
+        let regex = self.policies.get(&resource)?;
+        let suggestion = normalize_name(name);
+        if regex.is_match(&suggestion) {
+            Some(suggestion)
+        } else {
+            None
+        }
+    }
+}
+
+fn normalize_name(name: &str) -> StackString {
+    let mut output = String::with_capacity(name.len());
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            output.push(c.to_ascii_lowercase());
+        } else if !output.is_empty() && !output.ends_with('-') {
+            output.push('-');
+        }
+    }
+    output.trim_end_matches('-').into()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::hashmap;
+    use regex::Regex;
+
+    use crate::{
+        naming_policy::{normalize_name, NamingPolicy},
+        resource_type::ResourceType,
+    };
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name(" My Spot_Worker!! "), "my-spot-worker");
+        assert_eq!(normalize_name("build--2024.01"), "build-2024-01");
+    }
+
+    #[test]
+    fn test_naming_policy_check() -> Result<(), Error> {
+        let mut policy = NamingPolicy {
+            policies: hashmap! {
+                ResourceType::Ami => Regex::new("^[a-z][a-z0-9-]+$")?,
+            },
+            auto_fix: false,
+        };
+        assert_eq!(
+            policy.check(ResourceType::Ami, "ubuntu-base")?,
+            "ubuntu-base"
+        );
+        assert_eq!(policy.check(ResourceType::User, "Any Name")?, "Any Name");
+        let err = policy.check(ResourceType::Ami, "Ubuntu Base").unwrap_err();
+        assert!(err.to_string().contains("suggested name ubuntu-base"));
+        assert!(policy.check(ResourceType::Ami, "2024").is_err());
+
+        policy.auto_fix = true;
+        assert_eq!(
+            policy.check(ResourceType::Ami, "Ubuntu Base")?,
+            "ubuntu-base"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::Error;
+use log::debug;
+use reqwest::Client;
+use serde::Serialize;
+use stack_string::format_sstr;
+
+use crate::{config::Config, ses_client::SesInstance};
+
+#[derive(Serialize)]
+struct TelegramMessage<'a> {
+    chat_id: i64,
+    text: &'a str,
+}
+
+/// # Errors
+/// Returns error if the telegram api call fails
+pub async fn send_telegram(token: &str, chat_id: i64, text: &str) -> Result<(), Error> {
+    let url = format_sstr!("https://api.telegram.org/bot{token}/sendMessage");
+    Client::new()
+        .post(url.as_str())
+        .json(&TelegramMessage { chat_id, text })
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Send `body` to every configured channel: telegram when
+/// `telegram_bot_token` and `telegram_chat_id` are set, email when
+/// `notification_email` is set
+/// # Errors
+/// Returns error if the telegram or ses api call fails
+pub async fn send_notification(
+    config: &Config,
+    ses: &SesInstance,
+    subject: &str,
+    body: &str,
+) -> Result<(), Error> {
+    if let (Some(token), Some(chat_id)) = (&config.telegram_bot_token, config.telegram_chat_id) {
+        let text = format_sstr!("{subject}\n{body}");
+        send_telegram(token, chat_id, &text).await?;
+    }
+    if let Some(email) = &config.notification_email {
+        ses.send_email(email.as_str(), email.as_str(), subject, body)
+            .await?;
+    }
+    if config.telegram_bot_token.is_none() && config.notification_email.is_none() {
+        debug!("no notification channel configured: {subject}");
+    }
+    Ok(())
+}
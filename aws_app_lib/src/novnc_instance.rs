@@ -2,6 +2,7 @@ use anyhow::{format_err, Error};
 use log::debug;
 use stack_string::StackString;
 use std::{path::Path, process::Stdio, sync::Arc};
+use time::OffsetDateTime;
 use tokio::{
     process::{Child, Command},
     sync::RwLock,
@@ -10,6 +11,7 @@ use tokio::{
 #[derive(Default, Clone)]
 pub struct NoVncInstance {
     children: Arc<RwLock<Vec<Child>>>,
+    started_at: Arc<RwLock<Option<OffsetDateTime>>>,
 }
 
 impl NoVncInstance {
@@ -17,6 +19,7 @@ impl NoVncInstance {
     pub fn new() -> Self {
         Self {
             children: Arc::new(RwLock::new(Vec::new())),
+            started_at: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -92,6 +95,10 @@ impl NoVncInstance {
         let mut children = self.children.write().await;
         children.push(x11vnc_command);
         children.push(websockify_command);
+        self.started_at
+            .write()
+            .await
+            .get_or_insert_with(OffsetDateTime::now_utc);
         Ok(())
     }
 
@@ -130,6 +137,7 @@ impl NoVncInstance {
             output.push(StackString::from_utf8_vec(result.stderr)?);
         }
         children.clear();
+        self.started_at.write().await.take();
         Ok(output)
     }
 
@@ -161,4 +169,9 @@ impl NoVncInstance {
     pub async fn get_novnc_status(&self) -> usize {
         self.children.read().await.len()
     }
+
+    /// When the running session was started, `None` if there is none
+    pub async fn get_started_at(&self) -> Option<OffsetDateTime> {
+        *self.started_at.read().await
+    }
 }
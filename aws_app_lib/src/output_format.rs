@@ -0,0 +1,53 @@
+use anyhow::{format_err, Error};
+use std::{fmt, str::FromStr};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format_err!("{} is not an OutputFormat", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::output_format::OutputFormat;
+
+    #[test]
+    fn test_output_format() -> Result<(), Error> {
+        for format in [OutputFormat::Text, OutputFormat::Json] {
+            assert_eq!(format.to_str().parse::<OutputFormat>()?, format);
+        }
+        assert!("xml".parse::<OutputFormat>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::Serialize;
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::pgpool::PgPool;
+
+/// Alert rule firing once the spot price of `instance_type` in `region`
+/// drops below `threshold`. It fires again only after the price has gone
+/// back up to the threshold.
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Serialize)]
+pub struct PriceAlert {
+    pub id: Uuid,
+    pub instance_type: StackString,
+    pub region: StackString,
+    pub threshold: f64,
+    pub enabled: bool,
+    pub last_price: Option<f64>,
+    /// Set while the price is below the threshold
+    pub triggered_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+impl fmt::Display for PriceAlert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} in {} below ${:.4}/hr",
+            self.instance_type, self.region, self.threshold
+        )
+    }
+}
+
+impl PriceAlert {
+    /// # Errors
+    /// Returns error if `instance_type` isn't `family.size` or `threshold`
+    /// isn't positive
+    pub fn new(instance_type: &str, region: &str, threshold: f64) -> Result<Self, Error> {
+        let instance_type = instance_type.trim();
+        if instance_type
+            .split_once('.')
+            .map_or(true, |(family, size)| family.is_empty() || size.is_empty())
+        {
+            return Err(format_err!("{instance_type} is not an instance type"));
+        }
+        if region.trim().is_empty() {
+            return Err(format_err!("price alert needs a region"));
+        }
+        if !threshold.is_finite() || threshold <= 0.0 {
+            return Err(format_err!("threshold must be greater than zero"));
+        }
+        Ok(Self {
+            id: Uuid::new_v4(),
+            instance_type: instance_type.into(),
+            region: region.trim().into(),
+            threshold,
+            enabled: true,
+            last_price: None,
+            triggered_at: None,
+            created_at: OffsetDateTime::now_utc(),
+        })
+    }
+
+    /// Record `price`, returns true when it has just dropped below the
+    /// threshold and a notification should be sent
+    pub fn evaluate(&mut self, price: f64, now: OffsetDateTime) -> bool {
+        self.last_price = Some(price);
+        if price >= self.threshold {
+            self.triggered_at = None;
+            return false;
+        }
+        if self.triggered_at.is_some() {
+            return false;
+        }
+        self.triggered_at = Some(now);
+        true
+    }
+
+    /// Notification line for a triggered alert
+    #[must_use]
+    pub fn alert_line(&self) -> StackString {
+        format_sstr!(
+            "{} spot price in {} is ${:.4}/hr, below ${:.4}/hr",
+            self.instance_type,
+            self.region,
+            self.last_price.unwrap_or(0.0),
+            self.threshold,
+        )
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM price_alerts ORDER BY region, instance_type");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_enabled(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query =
+            query!("SELECT * FROM price_alerts WHERE enabled ORDER BY region, instance_type");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO price_alerts (
+                    id, instance_type, region, threshold, enabled, last_price, triggered_at,
+                    created_at
+                ) VALUES (
+                    $id, $instance_type, $region, $threshold, $enabled, $last_price,
+                    $triggered_at, $created_at
+                )
+            ",
+            id = self.id,
+            instance_type = self.instance_type,
+            region = self.region,
+            threshold = self.threshold,
+            enabled = self.enabled,
+            last_price = self.last_price,
+            triggered_at = self.triggered_at,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Store the result of `evaluate`
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn update_state(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                UPDATE price_alerts
+                SET last_price = $last_price, triggered_at = $triggered_at
+                WHERE id = $id
+            ",
+            id = self.id,
+            last_price = self.last_price,
+            triggered_at = self.triggered_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Disabling an alert also clears its triggered state
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn set_enabled(pool: &PgPool, id: Uuid, enabled: bool) -> Result<u64, Error> {
+        let query = query!(
+            r"
+                UPDATE price_alerts
+                SET enabled = $enabled, triggered_at = NULL
+                WHERE id = $id
+            ",
+            id = id,
+            enabled = enabled,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn delete_entry(pool: &PgPool, id: Uuid) -> Result<u64, Error> {
+        let query = query!("DELETE FROM price_alerts WHERE id = $id", id = id);
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use time::macros::datetime;
+
+    use crate::price_alert::PriceAlert;
+
+    #[test]
+    fn test_new_price_alert() -> Result<(), Error> {
+        let alert = PriceAlert::new(" m5.large ", "us-east-1", 0.03)?;
+        assert_eq!(alert.instance_type, "m5.large");
+        assert!(alert.enabled);
+        assert_eq!(alert.to_string(), "m5.large in us-east-1 below $0.0300/hr");
+
+        assert!(PriceAlert::new("m5", "us-east-1", 0.03).is_err());
+        assert!(PriceAlert::new("m5.", "us-east-1", 0.03).is_err());
+        assert!(PriceAlert::new("m5.large", "", 0.03).is_err());
+        assert!(PriceAlert::new("m5.large", "us-east-1", 0.0).is_err());
+        assert!(PriceAlert::new("m5.large", "us-east-1", f64::NAN).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate() -> Result<(), Error> {
+        let now = datetime!(2024-06-01 00:00 UTC);
+        let mut alert = PriceAlert::new("m5.large", "us-east-1", 0.03)?;
+
+        assert!(!alert.evaluate(0.035, now));
+        assert_eq!(alert.last_price, Some(0.035));
+
+        assert!(alert.evaluate(0.025, now));
+        assert_eq!(alert.triggered_at, Some(now));
+        assert_eq!(
+            alert.alert_line(),
+            "m5.large spot price in us-east-1 is $0.0250/hr, below $0.0300/hr"
+        );
+        // still below, already notified
+        assert!(!alert.evaluate(0.02, now));
+
+        assert!(!alert.evaluate(0.03, now));
+        assert_eq!(alert.triggered_at, None);
+        assert!(alert.evaluate(0.029, now));
+        Ok(())
+    }
+}
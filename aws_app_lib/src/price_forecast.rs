@@ -0,0 +1,261 @@
+use serde::Serialize;
+use stack_string::StackString;
+use std::collections::{BTreeMap, HashMap};
+use time::{Duration, OffsetDateTime};
+
+use crate::models::PriceHistory;
+
+/// Days of spot price history a forecast is computed from
+pub const FORECAST_WINDOW_DAYS: i64 = 7;
+/// Hours covered by the rolling mean
+const ROLLING_WINDOW_HOURS: i64 = 24;
+/// Fewer samples than this and the risk is unknown
+const MIN_SAMPLES: usize = 6;
+
+/// Likelihood of a spot instance being interrupted, judged from how much the
+/// spot price moves and how close it gets to the ondemand price
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum InterruptionRisk {
+    Low,
+    Medium,
+    High,
+    Unknown,
+}
+
+impl InterruptionRisk {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Headroom added to the 90th percentile price for the suggested bid
+    fn bid_margin(self) -> f64 {
+        match self {
+            Self::Low => 0.1,
+            Self::Medium => 0.2,
+            Self::High | Self::Unknown => 0.3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SpotForecast {
+    pub instance_type: StackString,
+    pub samples: usize,
+    /// Most recently recorded spot price
+    pub current: f64,
+    /// Mean over the whole window
+    pub mean: f64,
+    /// Mean over the last day of the window
+    pub rolling_mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub max: f64,
+    /// Standard deviation relative to the mean
+    pub volatility: f64,
+    pub ondemand_price: Option<f64>,
+    pub risk: InterruptionRisk,
+    /// 90th percentile price plus a margin growing with the risk, never more
+    /// than the ondemand price
+    pub suggested_bid: f64,
+}
+
+impl SpotForecast {
+    /// Forecast from the spot prices of one instance type, `None` without any
+    /// history
+    #[must_use]
+    pub fn new(
+        instance_type: &str,
+        history: &[&PriceHistory],
+        ondemand_price: Option<f64>,
+    ) -> Option<Self> {
+        let latest = history.iter().max_by_key(|h| h.recorded_at)?;
+        let rolling_start = latest.recorded_at - Duration::hours(ROLLING_WINDOW_HOURS);
+        let mut prices: Vec<f64> = history.iter().map(|h| h.price).collect();
+        prices.sort_by(f64::total_cmp);
+        let recent: Vec<f64> = history
+            .iter()
+            .filter(|h| h.recorded_at >= rolling_start)
+            .map(|h| h.price)
+            .collect();
+
+        let mean = mean(&prices);
+        let variance = prices.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / prices.len() as f64;
+        let volatility = if mean > 0.0 {
+            variance.sqrt() / mean
+        } else {
+            0.0
+        };
+        let p50 = percentile(&prices, 50.0);
+        let p90 = percentile(&prices, 90.0);
+        let max = prices[prices.len() - 1];
+        let risk = risk(prices.len(), volatility, p90, ondemand_price);
+        let mut suggested_bid = p90 * (1.0 + risk.bid_margin());
+        if let Some(ondemand_price) = ondemand_price {
+            suggested_bid = suggested_bid.min(ondemand_price);
+        }
+        Some(Self {
+            instance_type: instance_type.into(),
+            samples: prices.len(),
+            current: latest.price,
+            mean,
+            rolling_mean: mean_or(&recent, latest.price),
+            p50,
+            p90,
+            max,
+            volatility,
+            ondemand_price,
+            risk,
+            suggested_bid,
+        })
+    }
+}
+
+/// Forecasts of every instance type in `history` recorded within
+/// `FORECAST_WINDOW_DAYS` of `now`, keyed by instance type
+#[must_use]
+pub fn spot_forecasts(
+    history: &[PriceHistory],
+    ondemand_prices: &HashMap<StackString, f64>,
+    now: OffsetDateTime,
+) -> BTreeMap<StackString, SpotForecast> {
+    let start = now - Duration::days(FORECAST_WINDOW_DAYS);
+    let mut by_type: BTreeMap<&str, Vec<&PriceHistory>> = BTreeMap::new();
+    for entry in history {
+        if entry.price_type == "spot" && entry.recorded_at >= start {
+            by_type
+                .entry(entry.instance_type.as_str())
+                .or_default()
+                .push(entry);
+        }
+    }
+    by_type
+        .into_iter()
+        .filter_map(|(instance_type, entries)| {
+            let ondemand_price = ondemand_prices.get(instance_type).copied();
+            let forecast = SpotForecast::new(instance_type, &entries, ondemand_price)?;
+            Some((instance_type.into(), forecast))
+        })
+        .collect()
+}
+
+fn risk(
+    samples: usize,
+    volatility: f64,
+    p90: f64,
+    ondemand_price: Option<f64>,
+) -> InterruptionRisk {
+    if samples < MIN_SAMPLES {
+        return InterruptionRisk::Unknown;
+    }
+    let ondemand_ratio = ondemand_price
+        .filter(|p| *p > 0.0)
+        .map_or(0.0, |ondemand| p90 / ondemand);
+    if volatility > 0.2 || ondemand_ratio >= 0.8 {
+        InterruptionRisk::High
+    } else if volatility > 0.05 || ondemand_ratio >= 0.5 {
+        InterruptionRisk::Medium
+    } else {
+        InterruptionRisk::Low
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    mean_or(values, 0.0)
+}
+
+fn mean_or(values: &[f64], default: f64) -> f64 {
+    if values.is_empty() {
+        default
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+/// Nearest rank percentile of `sorted`, which must not be empty
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    let rank = (pct / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+    use stack_string::StackString;
+    use time::{macros::datetime, Duration};
+    use uuid::Uuid;
+
+    use crate::{
+        models::PriceHistory,
+        price_forecast::{spot_forecasts, InterruptionRisk, FORECAST_WINDOW_DAYS},
+    };
+
+    fn history(instance_type: &str, prices: &[f64]) -> Vec<PriceHistory> {
+        let start = datetime!(2024-06-01 00:00 UTC);
+        prices
+            .iter()
+            .enumerate()
+            .map(|(idx, price)| {
+                let recorded_at = start + Duration::hours(6 * idx as i64);
+                PriceHistory {
+                    id: Uuid::new_v4(),
+                    instance_type: instance_type.into(),
+                    price_type: "spot".into(),
+                    price: *price,
+                    price_timestamp: recorded_at,
+                    recorded_at,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_spot_forecasts() {
+        let mut entries = history(
+            "m5.large",
+            &[0.03, 0.03, 0.031, 0.03, 0.03, 0.031, 0.03, 0.03],
+        );
+        entries.extend(history(
+            "c5.large",
+            &[0.02, 0.05, 0.03, 0.07, 0.04, 0.08, 0.03, 0.06],
+        ));
+        entries.extend(history("t3.micro", &[0.004, 0.004]));
+        let mut ondemand = history("m5.large", &[0.096]);
+        ondemand[0].price_type = "ondemand".into();
+        entries.extend(ondemand);
+
+        let ondemand_prices = hashmap! {
+            StackString::from("m5.large") => 0.096,
+            StackString::from("c5.large") => 0.085,
+        };
+        let now = datetime!(2024-06-03 00:00 UTC);
+        let forecasts = spot_forecasts(&entries, &ondemand_prices, now);
+        assert_eq!(forecasts.len(), 3);
+
+        let stable = &forecasts["m5.large"];
+        assert_eq!(stable.samples, 8);
+        assert_eq!(stable.risk, InterruptionRisk::Low);
+        assert!((stable.p50 - 0.03).abs() < 1e-9);
+        assert!((stable.p90 - 0.031).abs() < 1e-9);
+        assert!((stable.suggested_bid - 0.0341).abs() < 1e-9);
+        assert!((stable.current - 0.03).abs() < 1e-9);
+
+        let volatile = &forecasts["c5.large"];
+        assert_eq!(volatile.risk, InterruptionRisk::High);
+        assert!((volatile.max - 0.08).abs() < 1e-9);
+        assert!((volatile.suggested_bid - 0.085).abs() < 1e-9);
+        assert!((volatile.rolling_mean - 0.056).abs() < 1e-9);
+
+        let sparse = &forecasts["t3.micro"];
+        assert_eq!(sparse.risk, InterruptionRisk::Unknown);
+        assert_eq!(sparse.ondemand_price, None);
+
+        let later = now + Duration::days(FORECAST_WINDOW_DAYS + 2);
+        assert!(spot_forecasts(&entries, &ondemand_prices, later).is_empty());
+    }
+}
@@ -1,20 +1,21 @@
-use anyhow::Error;
+use anyhow::{format_err, Error};
 use aws_config::SdkConfig;
 use aws_sdk_pricing::{
+    operation::get_products::builders::GetProductsFluentBuilder,
     types::{Filter, FilterType},
     Client as PricingClient,
 };
-use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
-use stack_string::StackString;
-use std::{collections::HashMap, fmt};
+use stack_string::{format_sstr, StackString};
+use std::{collections::HashMap, fmt, str::FromStr};
 use stdout_channel::rate_limiter::RateLimiter;
 use time::OffsetDateTime;
 
 use crate::{
     date_time_wrapper::DateTimeWrapper,
-    models::{InstanceList, InstancePricing, PricingType},
-    pgpool::PgPool,
+    models::{AwsGeneration, InstanceFamily, InstanceList, InstancePricing, PricingType},
+    scrape_instance_info::{accelerator_model, insert_result},
+    storage::{InstanceListRepo, InstancePricingRepo, Storage},
 };
 
 #[derive(Clone)]
@@ -116,55 +117,48 @@ impl PricingInstance {
         Ok(results)
     }
 
+    fn products_request(&self, region: &str) -> Result<GetProductsFluentBuilder, Error> {
+        let builder = self
+            .pricing_client
+            .get_products()
+            .format_version("aws_v1")
+            .service_code("AmazonEC2");
+        let filters = [
+            ("operatingSystem", "Linux"),
+            ("regionCode", region),
+            ("tenancy", "Shared"),
+            ("preInstalledSw", "NA"),
+            ("capacitystatus", "Used"),
+            ("OfferingClass", "standard"),
+            ("locationType", "AWS Region"),
+        ];
+        filters.iter().try_fold(builder, |builder, (field, value)| {
+            let filter = Filter::builder()
+                .field(*field)
+                .r#type(FilterType::TermMatch)
+                .value(*value)
+                .build()?;
+            Ok(builder.filters(filter))
+        })
+    }
+
     /// # Errors
     /// Returns error if aws api fails
     pub async fn get_prices(
         &self,
         instance_type: &str,
+        region: &str,
     ) -> Result<HashMap<(StackString, PricingType), InstancePricing>, Error> {
         let mut next_token = None;
         let mut entries: HashMap<(StackString, PricingType), InstancePricing> = HashMap::new();
         loop {
-            let mut builder = self
-                .pricing_client
-                .get_products()
-                .format_version("aws_v1")
-                .service_code("AmazonEC2")
-                .filters(
-                    Filter::builder()
-                        .field("operatingSystem")
-                        .r#type(FilterType::TermMatch)
-                        .value("Linux")
-                        .build()?,
-                )
-                .filters(
-                    Filter::builder()
-                        .field("instanceType")
-                        .r#type(FilterType::TermMatch)
-                        .value(instance_type)
-                        .build()?,
-                )
-                .filters(
-                    Filter::builder()
-                        .field("location")
-                        .r#type(FilterType::TermMatch)
-                        .value("US East (N. Virginia)")
-                        .build()?,
-                )
-                .filters(
-                    Filter::builder()
-                        .field("OfferingClass")
-                        .r#type(FilterType::TermMatch)
-                        .value("standard")
-                        .build()?,
-                )
-                .filters(
-                    Filter::builder()
-                        .field("locationType")
-                        .r#type(FilterType::TermMatch)
-                        .value("AWS Region")
-                        .build()?,
-                );
+            let mut builder = self.products_request(region)?.filters(
+                Filter::builder()
+                    .field("instanceType")
+                    .r#type(FilterType::TermMatch)
+                    .value(instance_type)
+                    .build()?,
+            );
             if let Some(next_token) = &next_token {
                 builder = builder.next_token(next_token);
             }
@@ -172,108 +166,57 @@ impl PricingInstance {
             let mut response = builder.send().await?;
             if let Some(price_list) = response.price_list.take() {
                 for price in price_list {
-                    #[derive(Deserialize, Debug)]
-                    struct PricePerUnit<'a> {
-                        #[serde(rename = "USD")]
-                        usd: &'a str,
-                    }
-                    #[derive(Deserialize, Debug)]
-                    struct PriceDimension<'a> {
-                        unit: &'a str,
-                        #[serde(rename = "pricePerUnit")]
-                        price_per_unit: PricePerUnit<'a>,
-                    }
-                    #[derive(Deserialize, Debug)]
-                    struct TermAttributes<'a> {
-                        #[serde(rename = "LeaseContractLength")]
-                        lease_contract_length: Option<&'a str>,
-                        #[serde(rename = "PurchaseOption")]
-                        purchase_option: Option<&'a str>,
-                    }
-                    #[derive(Deserialize, Debug)]
-                    struct PriceDimensions<'a> {
-                        #[serde(rename = "priceDimensions", borrow)]
-                        dimensions: HashMap<&'a str, PriceDimension<'a>>,
-                        #[serde(rename = "effectiveDate")]
-                        effective_date: DateTimeWrapper,
-                        #[serde(rename = "termAttributes")]
-                        term_attributes: TermAttributes<'a>,
-                    }
-                    #[derive(Deserialize, Debug)]
-                    struct PriceList<'a> {
-                        #[serde(borrow)]
-                        terms: HashMap<&'a str, HashMap<&'a str, PriceDimensions<'a>>>,
-                    }
+                    let value: PriceList = serde_json::from_str(&price)?;
+                    insert_prices(instance_type, &value.terms, &mut entries);
+                }
+            }
+            if let Some(token) = response.next_token.take() {
+                next_token.replace(token);
+            } else {
+                break;
+            }
+        }
+        Ok(entries)
+    }
 
+    /// Instance types and their prices in `region` straight from the pricing
+    /// api, restricted to `families` (e.g. `t3`, `m6i`) when non-empty
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn get_products(
+        &self,
+        region: &str,
+        families: &[StackString],
+    ) -> Result<ApiProducts, Error> {
+        let mut next_token = None;
+        let mut products = ApiProducts::default();
+        let mut instance_families: HashMap<StackString, InstanceFamily> = HashMap::new();
+        let mut instance_types: HashMap<StackString, InstanceList> = HashMap::new();
+        let mut entries: HashMap<(StackString, PricingType), InstancePricing> = HashMap::new();
+        loop {
+            let mut builder = self.products_request(region)?;
+            if let Some(next_token) = &next_token {
+                builder = builder.next_token(next_token);
+            }
+            self.limit.acquire().await;
+            let mut response = builder.send().await?;
+            if let Some(price_list) = response.price_list.take() {
+                for price in price_list {
                     let value: PriceList = serde_json::from_str(&price)?;
-                    if let Some(ondemand) = value.terms.get("OnDemand") {
-                        for dimensions in ondemand.values() {
-                            for dimension in dimensions.dimensions.values() {
-                                if dimension.unit != "Hrs" {
-                                    continue;
-                                }
-                                if let Ok(price) = dimension.price_per_unit.usd.parse::<f64>() {
-                                    let price_type = PricingType::OnDemand;
-                                    let price_timestamp: OffsetDateTime =
-                                        dimensions.effective_date.into();
-                                    let instance_type: StackString = instance_type.into();
-                                    if let Some(i) =
-                                        entries.get(&(instance_type.clone(), price_type))
-                                    {
-                                        if i.price_timestamp > price_timestamp {
-                                            continue;
-                                        }
-                                    }
-                                    let i = InstancePricing::new(
-                                        instance_type.as_str(),
-                                        price,
-                                        price_type.to_str(),
-                                        dimensions.effective_date.into(),
-                                    );
-                                    entries.insert((instance_type, price_type), i);
-                                }
-                            }
-                        }
-                    }
-                    if let Some(reserved) = value.terms.get("Reserved") {
-                        for dimensions in reserved.values() {
-                            if dimensions.term_attributes.lease_contract_length != Some("1yr") {
-                                continue;
-                            }
-                            if dimensions.term_attributes.purchase_option != Some("All Upfront") {
-                                continue;
-                            }
-                            for dimension in dimensions.dimensions.values() {
-                                if dimension.unit != "Quantity" {
-                                    continue;
-                                }
-                                if let Ok(price) = dimension.price_per_unit.usd.parse::<f64>() {
-                                    if price == 0.0 {
-                                        continue;
-                                    }
-                                    let price = price / (365.0 * 24.0);
-
-                                    let price_type = PricingType::Reserved;
-                                    let price_timestamp = dimensions.effective_date.into();
-                                    let instance_type: StackString = instance_type.into();
-                                    if let Some(i) =
-                                        entries.get(&(instance_type.clone(), price_type))
-                                    {
-                                        if i.price_timestamp > price_timestamp {
-                                            continue;
-                                        }
-                                    }
-                                    let i = InstancePricing::new(
-                                        instance_type.as_str(),
-                                        price,
-                                        price_type.to_str(),
-                                        price_timestamp,
-                                    );
-                                    entries.insert((instance_type, price_type), i);
-                                }
-                            }
-                        }
+                    let attributes = match &value.product {
+                        Some(product) => &product.attributes,
+                        None => continue,
+                    };
+                    let (family, instance) = match parse_product(attributes) {
+                        Some(x) => x,
+                        None => continue,
+                    };
+                    if !families.is_empty() && !families.contains(&family.family_name) {
+                        continue;
                     }
+                    insert_prices(&instance.instance_type, &value.terms, &mut entries);
+                    instance_families.insert(family.family_name.clone(), family);
+                    instance_types.insert(instance.instance_type.clone(), instance);
                 }
             }
             if let Some(token) = response.next_token.take() {
@@ -282,20 +225,47 @@ impl PricingInstance {
                 break;
             }
         }
-        Ok(entries)
+        products.families.extend(instance_families.into_values());
+        products.instances.extend(instance_types.into_values());
+        products.prices.extend(entries.into_values());
+        Ok(products)
+    }
+
+    /// Populate `instance_family`, `instance_list` and `instance_pricing`
+    /// from the pricing api rather than the public instance type pages
+    /// # Errors
+    /// Returns error if aws api or db query fails
+    pub async fn update_from_api(
+        &self,
+        region: &str,
+        families: &[StackString],
+        storage: &dyn Storage,
+    ) -> Result<Vec<StackString>, Error> {
+        let ApiProducts {
+            families,
+            instances,
+            prices,
+        } = self.get_products(region, families).await?;
+        let mut output = insert_result(families, instances, storage).await?;
+        let number_of_prices = prices.len();
+        for price in prices {
+            storage.upsert_price(&price).await?;
+        }
+        output.push(format_sstr!("{number_of_prices} prices for {region}"));
+        Ok(output)
     }
 
     /// # Errors
     /// Returns error if aws api fails
-    pub async fn update_all_prices(&self, pool: &PgPool) -> Result<u32, Error> {
+    pub async fn update_all_prices(
+        &self,
+        region: &str,
+        storage: &dyn Storage,
+    ) -> Result<u32, Error> {
         let mut number_of_updates = 0;
-        let instances: Vec<_> = InstanceList::get_all_instances(pool)
-            .await?
-            .try_collect()
-            .await?;
-        for i in instances {
-            for (_, price) in self.get_prices(&i.instance_type).await? {
-                price.upsert_entry(pool).await?;
+        for i in storage.get_instances().await? {
+            for (_, price) in self.get_prices(&i.instance_type, region).await? {
+                storage.upsert_price(&price).await?;
                 number_of_updates += 1;
             }
         }
@@ -303,6 +273,234 @@ impl PricingInstance {
     }
 }
 
+#[derive(Default, Debug)]
+pub struct ApiProducts {
+    pub families: Vec<InstanceFamily>,
+    pub instances: Vec<InstanceList>,
+    pub prices: Vec<InstancePricing>,
+}
+
+/// Where `update` gets instance types from: the public instance type pages
+/// or the pricing api
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UpdateSource {
+    #[default]
+    Scrape,
+    Api,
+}
+
+impl UpdateSource {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Scrape => "scrape",
+            Self::Api => "api",
+        }
+    }
+}
+
+impl fmt::Display for UpdateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for UpdateSource {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scrape" => Ok(Self::Scrape),
+            "api" => Ok(Self::Api),
+            _ => Err(format_err!("{} is not an UpdateSource", s)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct PricePerUnit<'a> {
+    #[serde(rename = "USD")]
+    usd: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct PriceDimension<'a> {
+    unit: &'a str,
+    #[serde(rename = "pricePerUnit")]
+    price_per_unit: PricePerUnit<'a>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TermAttributes<'a> {
+    #[serde(rename = "LeaseContractLength")]
+    lease_contract_length: Option<&'a str>,
+    #[serde(rename = "PurchaseOption")]
+    purchase_option: Option<&'a str>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PriceDimensions<'a> {
+    #[serde(rename = "priceDimensions", borrow)]
+    dimensions: HashMap<&'a str, PriceDimension<'a>>,
+    #[serde(rename = "effectiveDate")]
+    effective_date: DateTimeWrapper,
+    #[serde(rename = "termAttributes")]
+    term_attributes: TermAttributes<'a>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ProductAttributes<'a> {
+    #[serde(rename = "instanceType")]
+    instance_type: Option<&'a str>,
+    #[serde(rename = "instanceFamily")]
+    instance_family: Option<&'a str>,
+    vcpu: Option<&'a str>,
+    memory: Option<&'a str>,
+    #[serde(rename = "currentGeneration")]
+    current_generation: Option<&'a str>,
+    gpu: Option<&'a str>,
+    #[serde(rename = "networkPerformance")]
+    network_performance: Option<&'a str>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Product<'a> {
+    #[serde(borrow)]
+    attributes: ProductAttributes<'a>,
+}
+
+type Terms<'a> = HashMap<&'a str, HashMap<&'a str, PriceDimensions<'a>>>;
+
+#[derive(Deserialize, Debug)]
+struct PriceList<'a> {
+    #[serde(borrow)]
+    product: Option<Product<'a>>,
+    #[serde(borrow)]
+    terms: Terms<'a>,
+}
+
+fn insert_price(
+    instance_type: &str,
+    price: f64,
+    price_type: PricingType,
+    price_timestamp: OffsetDateTime,
+    entries: &mut HashMap<(StackString, PricingType), InstancePricing>,
+) {
+    let instance_type: StackString = instance_type.into();
+    if let Some(i) = entries.get(&(instance_type.clone(), price_type)) {
+        if i.price_timestamp > price_timestamp {
+            return;
+        }
+    }
+    let i = InstancePricing::new(
+        instance_type.as_str(),
+        price,
+        price_type.to_str(),
+        price_timestamp,
+    );
+    entries.insert((instance_type, price_type), i);
+}
+
+/// Hourly `OnDemand` price and the hourly equivalent of the 1yr all upfront
+/// `Reserved` price
+fn insert_prices(
+    instance_type: &str,
+    terms: &Terms<'_>,
+    entries: &mut HashMap<(StackString, PricingType), InstancePricing>,
+) {
+    if let Some(ondemand) = terms.get("OnDemand") {
+        for dimensions in ondemand.values() {
+            for dimension in dimensions.dimensions.values() {
+                if dimension.unit != "Hrs" {
+                    continue;
+                }
+                if let Ok(price) = dimension.price_per_unit.usd.parse::<f64>() {
+                    let price_timestamp: OffsetDateTime = dimensions.effective_date.into();
+                    insert_price(
+                        instance_type,
+                        price,
+                        PricingType::OnDemand,
+                        price_timestamp,
+                        entries,
+                    );
+                }
+            }
+        }
+    }
+    if let Some(reserved) = terms.get("Reserved") {
+        for dimensions in reserved.values() {
+            if dimensions.term_attributes.lease_contract_length != Some("1yr") {
+                continue;
+            }
+            if dimensions.term_attributes.purchase_option != Some("All Upfront") {
+                continue;
+            }
+            for dimension in dimensions.dimensions.values() {
+                if dimension.unit != "Quantity" {
+                    continue;
+                }
+                if let Ok(price) = dimension.price_per_unit.usd.parse::<f64>() {
+                    if price == 0.0 {
+                        continue;
+                    }
+                    let price = price / (365.0 * 24.0);
+                    let price_timestamp: OffsetDateTime = dimensions.effective_date.into();
+                    insert_price(
+                        instance_type,
+                        price,
+                        PricingType::Reserved,
+                        price_timestamp,
+                        entries,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Instance family and type from the product attributes, `memory` is given
+/// as e.g. `"1,952 GiB"`
+fn parse_product(attributes: &ProductAttributes<'_>) -> Option<(InstanceFamily, InstanceList)> {
+    let instance_type = attributes.instance_type?;
+    let (family_name, _) = instance_type.split_once('.')?;
+    let n_cpu: i32 = attributes.vcpu?.parse().ok()?;
+    let memory_gib: f64 = attributes
+        .memory?
+        .trim_end_matches("GiB")
+        .replace(',', "")
+        .trim()
+        .parse()
+        .ok()?;
+    let gpu_count: i32 = attributes.gpu.and_then(|g| g.parse().ok()).unwrap_or(0);
+    let generation = if attributes.current_generation == Some("Yes") {
+        AwsGeneration::HVM
+    } else {
+        AwsGeneration::PV
+    };
+    let gpu_type = if gpu_count > 0 {
+        Some(accelerator_model(family_name).unwrap_or("GPU").into())
+    } else {
+        None
+    };
+    let family = InstanceFamily {
+        family_name: family_name.into(),
+        family_type: attributes.instance_family.unwrap_or("").into(),
+        data_url: None,
+        use_for_spot: false,
+    };
+    let instance = InstanceList {
+        instance_type: instance_type.into(),
+        family_name: family_name.into(),
+        n_cpu,
+        memory_gib,
+        generation: generation.into(),
+        gpu_count,
+        gpu_type,
+        network_performance: attributes.network_performance.map(Into::into),
+    };
+    Some((family, instance))
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AwsService {
     pub service_code: StackString,
@@ -312,8 +510,98 @@ pub struct AwsService {
 #[cfg(test)]
 mod tests {
     use anyhow::Error;
+    use std::collections::HashMap;
+
+    use crate::{
+        models::PricingType,
+        pricing_instance::{
+            insert_prices, parse_product, PriceList, PricingInstance, UpdateSource,
+        },
+    };
+
+    const PRODUCT: &str = r#"{
+        "product": {
+            "productFamily": "Compute Instance",
+            "attributes": {
+                "instanceType": "g4dn.xlarge",
+                "instanceFamily": "GPU instance",
+                "vcpu": "4",
+                "memory": "16 GiB",
+                "currentGeneration": "Yes",
+                "gpu": "1",
+                "networkPerformance": "Up to 25 Gigabit",
+                "regionCode": "eu-west-1"
+            },
+            "sku": "ABCDEF"
+        },
+        "terms": {
+            "OnDemand": {
+                "ABCDEF.JRTCKXETXF": {
+                    "priceDimensions": {
+                        "ABCDEF.JRTCKXETXF.6YS6EN2CT7": {
+                            "unit": "Hrs",
+                            "pricePerUnit": {"USD": "0.5870000000"}
+                        }
+                    },
+                    "effectiveDate": "2024-01-01T00:00:00Z",
+                    "termAttributes": {}
+                }
+            },
+            "Reserved": {
+                "ABCDEF.6QCMYABX3D": {
+                    "priceDimensions": {
+                        "ABCDEF.6QCMYABX3D.2TG2D8R56U": {
+                            "unit": "Quantity",
+                            "pricePerUnit": {"USD": "3066"}
+                        },
+                        "ABCDEF.6QCMYABX3D.6YS6EN2CT7": {
+                            "unit": "Hrs",
+                            "pricePerUnit": {"USD": "0.0000000000"}
+                        }
+                    },
+                    "effectiveDate": "2024-01-01T00:00:00Z",
+                    "termAttributes": {
+                        "LeaseContractLength": "1yr",
+                        "PurchaseOption": "All Upfront",
+                        "OfferingClass": "standard"
+                    }
+                }
+            }
+        }
+    }"#;
 
-    use crate::pricing_instance::PricingInstance;
+    #[test]
+    fn test_parse_product() -> Result<(), Error> {
+        let value: PriceList = serde_json::from_str(PRODUCT)?;
+        let attributes = &value.product.as_ref().unwrap().attributes;
+        let (family, instance) = parse_product(attributes).unwrap();
+        assert_eq!(family.family_name, "g4dn");
+        assert_eq!(family.family_type, "GPU instance");
+        assert_eq!(instance.instance_type, "g4dn.xlarge");
+        assert_eq!(instance.n_cpu, 4);
+        assert!((instance.memory_gib - 16.0).abs() < 1e-9);
+        assert_eq!(instance.generation, "hvm");
+        assert_eq!(instance.gpu_count, 1);
+        assert_eq!(instance.gpu_type.as_ref().unwrap(), "NVIDIA T4");
+
+        let mut entries = HashMap::new();
+        insert_prices(&instance.instance_type, &value.terms, &mut entries);
+        assert_eq!(entries.len(), 2);
+        let ondemand = &entries[&("g4dn.xlarge".into(), PricingType::OnDemand)];
+        assert!((ondemand.price - 0.587).abs() < 1e-9);
+        let reserved = &entries[&("g4dn.xlarge".into(), PricingType::Reserved)];
+        assert!((reserved.price - 3066.0 / (365.0 * 24.0)).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_source() -> Result<(), Error> {
+        for source in [UpdateSource::Scrape, UpdateSource::Api] {
+            assert_eq!(source.to_str().parse::<UpdateSource>()?, source);
+        }
+        assert!("html".parse::<UpdateSource>().is_err());
+        Ok(())
+    }
 
     #[tokio::test]
     async fn test_describe_services() -> Result<(), Error> {
@@ -350,7 +638,7 @@ mod tests {
     async fn test_get_prices() -> Result<(), Error> {
         let config = aws_config::load_from_env().await;
         let pricing = PricingInstance::new(&config);
-        let prices = pricing.get_prices("t3.micro").await?;
+        let prices = pricing.get_prices("t3.micro", "us-east-1").await?;
         assert_eq!(prices.len(), 2);
         Ok(())
     }
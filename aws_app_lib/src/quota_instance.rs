@@ -0,0 +1,498 @@
+use anyhow::Error;
+use aws_config::SdkConfig;
+use aws_sdk_servicequotas::Client as ServiceQuotasClient;
+use aws_types::region::Region;
+use serde::Serialize;
+use stack_string::StackString;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use crate::ec2_instance::VolumeInfo;
+
+/// Usage at or above this share of a quota is flagged
+pub const QUOTA_WARNING_THRESHOLD: f64 = 0.8;
+
+const EC2_SERVICE_CODE: &str = "ec2";
+const EBS_SERVICE_CODE: &str = "ebs";
+const ELASTIC_IP_QUOTA_CODE: &str = "L-0263D0A3";
+const SNAPSHOT_QUOTA_CODE: &str = "L-309BACF6";
+
+/// Instance families sharing a vCPU quota
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum VcpuClass {
+    Standard,
+    G,
+    P,
+    X,
+    F,
+    Inf,
+    Dl,
+    Trn,
+    HighMemory,
+}
+
+impl VcpuClass {
+    /// `None` for families without a vCPU quota, e.g. mac instances which
+    /// run on dedicated hosts
+    #[must_use]
+    pub fn from_instance_type(instance_type: &str) -> Option<Self> {
+        let prefix: String = instance_type
+            .trim()
+            .to_lowercase()
+            .chars()
+            .take_while(char::is_ascii_alphabetic)
+            .collect();
+        match prefix.as_str() {
+            "inf" => Some(Self::Inf),
+            "dl" => Some(Self::Dl),
+            "trn" => Some(Self::Trn),
+            "g" | "gr" | "vt" => Some(Self::G),
+            "p" => Some(Self::P),
+            "x" => Some(Self::X),
+            "f" => Some(Self::F),
+            "u" => Some(Self::HighMemory),
+            "a" | "c" | "d" | "h" | "i" | "im" | "is" | "m" | "r" | "t" | "z" => {
+                Some(Self::Standard)
+            }
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Standard => "Standard (A, C, D, H, I, M, R, T, Z)",
+            Self::G => "G and VT",
+            Self::P => "P",
+            Self::X => "X",
+            Self::F => "F",
+            Self::Inf => "Inf",
+            Self::Dl => "DL",
+            Self::Trn => "Trn",
+            Self::HighMemory => "High Memory",
+        }
+    }
+
+    #[must_use]
+    pub fn on_demand_quota_code(self) -> &'static str {
+        match self {
+            Self::Standard => "L-1216C47A",
+            Self::G => "L-DB2E81BA",
+            Self::P => "L-417A185B",
+            Self::X => "L-7295265B",
+            Self::F => "L-74FC7D96",
+            Self::Inf => "L-1945791B",
+            Self::Dl => "L-6E869C2A",
+            Self::Trn => "L-2C3B7624",
+            Self::HighMemory => "L-43DA4232",
+        }
+    }
+
+    /// High memory instances can't be requested as spot instances
+    #[must_use]
+    pub fn spot_quota_code(self) -> Option<&'static str> {
+        match self {
+            Self::Standard => Some("L-34B43A08"),
+            Self::G => Some("L-3819A6DF"),
+            Self::P => Some("L-7212CCBC"),
+            Self::X => Some("L-E3A00192"),
+            Self::F => Some("L-88CF9481"),
+            Self::Inf => Some("L-B5D1601B"),
+            Self::Dl => Some("L-85EED4F7"),
+            Self::Trn => Some("L-6B0D517C"),
+            Self::HighMemory => None,
+        }
+    }
+}
+
+impl fmt::Display for VcpuClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+/// Storage quota code of an EBS volume type, sizes are in TiB
+#[must_use]
+pub fn ebs_storage_quota_code(volume_type: &str) -> Option<&'static str> {
+    match volume_type {
+        "gp2" => Some("L-D18FCD1D"),
+        "gp3" => Some("L-7A658B76"),
+        "io1" => Some("L-FD252861"),
+        "io2" => Some("L-09BD8365"),
+        "st1" => Some("L-82ACEF56"),
+        "sc1" => Some("L-17AF77E8"),
+        "standard" => Some("L-9CF3C2EB"),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum QuotaCategory {
+    OnDemandVcpu,
+    SpotVcpu,
+    EbsStorage,
+    ElasticIp,
+    Snapshot,
+}
+
+impl QuotaCategory {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::OnDemandVcpu => "On-Demand vCPUs",
+            Self::SpotVcpu => "Spot vCPUs",
+            Self::EbsStorage => "EBS Storage",
+            Self::ElasticIp => "Elastic IPs",
+            Self::Snapshot => "Snapshots",
+        }
+    }
+}
+
+impl fmt::Display for QuotaCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+/// Current usage of a quota, `quota` is `None` when Service Quotas couldn't
+/// be queried for it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct QuotaUsage {
+    pub category: QuotaCategory,
+    pub name: StackString,
+    pub service_code: &'static str,
+    pub quota_code: &'static str,
+    pub usage: f64,
+    pub quota: Option<f64>,
+    pub unit: &'static str,
+}
+
+impl QuotaUsage {
+    #[must_use]
+    pub fn new(
+        category: QuotaCategory,
+        name: impl Into<StackString>,
+        service_code: &'static str,
+        quota_code: &'static str,
+        usage: f64,
+        unit: &'static str,
+    ) -> Self {
+        Self {
+            category,
+            name: name.into(),
+            service_code,
+            quota_code,
+            usage,
+            quota: None,
+            unit,
+        }
+    }
+
+    #[must_use]
+    pub fn vcpus(class: VcpuClass, spot: bool, usage: f64) -> Option<Self> {
+        let (category, quota_code) = if spot {
+            (QuotaCategory::SpotVcpu, class.spot_quota_code()?)
+        } else {
+            (QuotaCategory::OnDemandVcpu, class.on_demand_quota_code())
+        };
+        Some(Self::new(
+            category,
+            class.to_str(),
+            EC2_SERVICE_CODE,
+            quota_code,
+            usage,
+            "vCPUs",
+        ))
+    }
+
+    /// Share of the quota in use, `None` if the quota is unknown or zero
+    #[must_use]
+    pub fn utilization(&self) -> Option<f64> {
+        self.quota
+            .filter(|quota| *quota > 0.0)
+            .map(|quota| self.usage / quota)
+    }
+
+    #[must_use]
+    pub fn is_warning(&self) -> bool {
+        self.utilization()
+            .map_or(false, |utilization| utilization >= QUOTA_WARNING_THRESHOLD)
+    }
+
+    /// Whether `additional` more would go over the quota
+    #[must_use]
+    pub fn exceeded_by(&self, additional: f64) -> bool {
+        self.quota
+            .map_or(false, |quota| self.usage + additional > quota)
+    }
+}
+
+/// vCPUs of pending and running instances by class and whether they're spot
+/// instances. `instances` are `(instance_type, is_spot)` pairs, instance types
+/// missing from `vcpus` aren't counted.
+#[must_use]
+pub fn vcpu_usage<'a>(
+    instances: impl IntoIterator<Item = (&'a str, bool)>,
+    vcpus: &HashMap<StackString, i32>,
+) -> BTreeMap<(VcpuClass, bool), f64> {
+    let mut usage = BTreeMap::new();
+    for (instance_type, spot) in instances {
+        let class = match VcpuClass::from_instance_type(instance_type) {
+            Some(class) => class,
+            None => continue,
+        };
+        if let Some(n) = vcpus.get(instance_type) {
+            *usage.entry((class, spot)).or_insert(0.0) += f64::from(*n);
+        }
+    }
+    usage
+}
+
+/// Provisioned storage in TiB by volume type
+#[must_use]
+pub fn ebs_usage<'a>(
+    volumes: impl IntoIterator<Item = &'a VolumeInfo>,
+) -> BTreeMap<StackString, f64> {
+    let mut usage = BTreeMap::new();
+    for volume in volumes {
+        *usage.entry(volume.volume_type.clone()).or_insert(0.0) += volume.size as f64 / 1024.0;
+    }
+    usage
+}
+
+/// Usage of the vCPU, EBS storage, elastic ip and snapshot quotas. Standard
+/// vCPU quotas are always included, other classes and volume types only
+/// when in use.
+#[must_use]
+pub fn quota_usages(
+    vcpus: &BTreeMap<(VcpuClass, bool), f64>,
+    storage: &BTreeMap<StackString, f64>,
+    elastic_ips: usize,
+    snapshots: usize,
+) -> Vec<QuotaUsage> {
+    let mut usages = Vec::new();
+    for spot in [false, true] {
+        let mut classes: Vec<_> = vcpus
+            .keys()
+            .filter(|(_, s)| *s == spot)
+            .map(|(class, _)| *class)
+            .collect();
+        if !classes.contains(&VcpuClass::Standard) {
+            classes.insert(0, VcpuClass::Standard);
+        }
+        for class in classes {
+            let usage = vcpus.get(&(class, spot)).copied().unwrap_or(0.0);
+            usages.extend(QuotaUsage::vcpus(class, spot, usage));
+        }
+    }
+    for (volume_type, size) in storage {
+        if let Some(quota_code) = ebs_storage_quota_code(volume_type) {
+            usages.push(QuotaUsage::new(
+                QuotaCategory::EbsStorage,
+                volume_type.clone(),
+                EBS_SERVICE_CODE,
+                quota_code,
+                *size,
+                "TiB",
+            ));
+        }
+    }
+    usages.push(QuotaUsage::new(
+        QuotaCategory::ElasticIp,
+        "EC2-VPC Elastic IPs",
+        EC2_SERVICE_CODE,
+        ELASTIC_IP_QUOTA_CODE,
+        elastic_ips as f64,
+        "addresses",
+    ));
+    usages.push(QuotaUsage::new(
+        QuotaCategory::Snapshot,
+        "Snapshots per Region",
+        EBS_SERVICE_CODE,
+        SNAPSHOT_QUOTA_CODE,
+        snapshots as f64,
+        "snapshots",
+    ));
+    usages
+}
+
+#[derive(Clone)]
+pub struct QuotaInstance {
+    quotas_client: ServiceQuotasClient,
+}
+
+impl fmt::Debug for QuotaInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("QuotaInstance")
+    }
+}
+
+impl QuotaInstance {
+    #[must_use]
+    pub fn new(sdk_config: &SdkConfig) -> Self {
+        Self {
+            quotas_client: ServiceQuotasClient::from_conf(sdk_config.into()),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
+        let region: String = region.as_ref().into();
+        let conf = self
+            .quotas_client
+            .config()
+            .to_builder()
+            .region(Region::new(region))
+            .build();
+        self.quotas_client = ServiceQuotasClient::from_conf(conf);
+        Ok(())
+    }
+
+    /// Applied value of a quota, the aws default if it was never raised
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn get_quota(
+        &self,
+        service_code: &str,
+        quota_code: &str,
+    ) -> Result<Option<f64>, Error> {
+        match self
+            .quotas_client
+            .get_service_quota()
+            .service_code(service_code)
+            .quota_code(quota_code)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(output.quota.and_then(|q| q.value)),
+            Err(_) => {
+                let output = self
+                    .quotas_client
+                    .get_aws_default_service_quota()
+                    .service_code(service_code)
+                    .quota_code(quota_code)
+                    .send()
+                    .await?;
+                Ok(output.quota.and_then(|q| q.value))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stack_string::StackString;
+    use std::collections::HashMap;
+
+    use crate::{
+        ec2_instance::VolumeInfo,
+        quota_instance::{
+            ebs_usage, quota_usages, vcpu_usage, QuotaCategory, QuotaUsage, VcpuClass,
+        },
+    };
+
+    #[test]
+    fn test_vcpu_class() {
+        assert_eq!(
+            VcpuClass::from_instance_type("m5.large"),
+            Some(VcpuClass::Standard)
+        );
+        assert_eq!(
+            VcpuClass::from_instance_type("is4gen.xlarge"),
+            Some(VcpuClass::Standard)
+        );
+        assert_eq!(
+            VcpuClass::from_instance_type("g4dn.xlarge"),
+            Some(VcpuClass::G)
+        );
+        assert_eq!(
+            VcpuClass::from_instance_type("vt1.3xlarge"),
+            Some(VcpuClass::G)
+        );
+        assert_eq!(
+            VcpuClass::from_instance_type("inf2.xlarge"),
+            Some(VcpuClass::Inf)
+        );
+        assert_eq!(
+            VcpuClass::from_instance_type("x2iedn.xlarge"),
+            Some(VcpuClass::X)
+        );
+        assert_eq!(
+            VcpuClass::from_instance_type("u-6tb1.metal"),
+            Some(VcpuClass::HighMemory)
+        );
+        assert_eq!(VcpuClass::from_instance_type("mac1.metal"), None);
+        assert!(VcpuClass::HighMemory.spot_quota_code().is_none());
+    }
+
+    #[test]
+    fn test_quota_usages() {
+        let vcpus: HashMap<StackString, i32> =
+            [("m5.large", 2), ("c5.xlarge", 4), ("p3.2xlarge", 8)]
+                .iter()
+                .map(|(t, n)| ((*t).into(), *n))
+                .collect();
+        let instances = vec![
+            ("m5.large", false),
+            ("c5.xlarge", true),
+            ("c5.xlarge", true),
+            ("p3.2xlarge", true),
+            ("t3.nano", false),
+        ];
+        let usage = vcpu_usage(instances, &vcpus);
+        assert_eq!(usage.get(&(VcpuClass::Standard, false)), Some(&2.0));
+        assert_eq!(usage.get(&(VcpuClass::Standard, true)), Some(&8.0));
+        assert_eq!(usage.get(&(VcpuClass::P, true)), Some(&8.0));
+
+        let volumes = vec![
+            VolumeInfo {
+                size: 512,
+                volume_type: "gp3".into(),
+                ..VolumeInfo::default()
+            },
+            VolumeInfo {
+                size: 1536,
+                volume_type: "gp3".into(),
+                ..VolumeInfo::default()
+            },
+        ];
+        let storage = ebs_usage(&volumes);
+        assert_eq!(storage.get("gp3"), Some(&2.0));
+
+        let usages = quota_usages(&usage, &storage, 3, 40);
+        let names: Vec<_> = usages
+            .iter()
+            .map(|u| (u.category, u.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (QuotaCategory::OnDemandVcpu, VcpuClass::Standard.to_str()),
+                (QuotaCategory::SpotVcpu, VcpuClass::Standard.to_str()),
+                (QuotaCategory::SpotVcpu, VcpuClass::P.to_str()),
+                (QuotaCategory::EbsStorage, "gp3"),
+                (QuotaCategory::ElasticIp, "EC2-VPC Elastic IPs"),
+                (QuotaCategory::Snapshot, "Snapshots per Region"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quota_warning() {
+        let mut usage = QuotaUsage::vcpus(VcpuClass::Standard, true, 28.0).unwrap();
+        assert_eq!(usage.utilization(), None);
+        assert!(!usage.is_warning());
+        assert!(!usage.exceeded_by(100.0));
+        usage.quota = Some(32.0);
+        assert!(usage.is_warning());
+        assert!(!usage.exceeded_by(4.0));
+        assert!(usage.exceeded_by(8.0));
+        usage.usage = 16.0;
+        assert!(!usage.is_warning());
+        usage.quota = Some(0.0);
+        assert_eq!(usage.utilization(), None);
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{format_err, Error};
+use maplit::hashmap;
+use serde::Serialize;
+use stack_string::{format_sstr, StackString};
+use std::collections::HashMap;
+
+use crate::ec2_instance::VolumeInfo;
+
+/// Tag on the encrypted volume naming the volume it replaces
+pub const REENCRYPTED_FROM_TAG: &str = "reencrypted-from";
+
+/// Re-creating an unencrypted volume as an encrypted one: snapshot it, copy
+/// the snapshot with encryption, create a volume from the copy and swap it in
+/// for the original. The original volume is kept until it is deleted by hand.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ReencryptPlan {
+    pub volume_id: StackString,
+    pub name: Option<StackString>,
+    pub availability_zone: StackString,
+    pub volume_type: StackString,
+    /// Instance and device the replacement is attached to
+    pub attachment: Option<(StackString, StackString)>,
+    /// Key the copy is encrypted with, the account's default ebs key if unset
+    pub kms_key_id: Option<StackString>,
+}
+
+impl ReencryptPlan {
+    /// `instance_state` is the state of the instance `volume` is attached to
+    /// # Errors
+    /// Returns error if `volume` is already encrypted, busy, or attached to an
+    /// instance which isn't stopped
+    pub fn new(
+        volume: &VolumeInfo,
+        instance_state: Option<&str>,
+        kms_key_id: Option<StackString>,
+    ) -> Result<Self, Error> {
+        if volume.encrypted {
+            return Err(format_err!("{} is already encrypted", volume.id));
+        }
+        if volume.state != "available" && volume.state != "in-use" {
+            return Err(format_err!("{} is {}", volume.id, volume.state));
+        }
+        if let Some((instance_id, _)) = &volume.attachment {
+            let state = instance_state.unwrap_or("unknown");
+            if state != "stopped" {
+                return Err(format_err!(
+                    "stop {instance_id} ({state}) before re-encrypting {}",
+                    volume.id
+                ));
+            }
+        }
+        Ok(Self {
+            volume_id: volume.id.clone(),
+            name: volume.tags.get("Name").cloned(),
+            availability_zone: volume.availability_zone.clone(),
+            volume_type: volume.volume_type.clone(),
+            attachment: volume.attachment.clone(),
+            kms_key_id: kms_key_id.filter(|k| !k.is_empty()),
+        })
+    }
+
+    /// Tags of the snapshots and the encrypted volume
+    #[must_use]
+    pub fn tags(&self) -> HashMap<StackString, StackString> {
+        let mut tags = hashmap! {
+            REENCRYPTED_FROM_TAG.into() => self.volume_id.clone(),
+        };
+        if let Some(name) = &self.name {
+            tags.insert("Name".into(), name.clone());
+        }
+        tags
+    }
+
+    /// One line per change, in the order they are made
+    #[must_use]
+    pub fn steps(&self) -> Vec<StackString> {
+        let key = self
+            .kms_key_id
+            .as_ref()
+            .map_or("default ebs key", StackString::as_str);
+        let mut steps = vec![
+            format_sstr!("snapshot {}", self.volume_id),
+            format_sstr!("copy snapshot encrypted with {key}"),
+            format_sstr!(
+                "create {} volume in {} from the copy",
+                self.volume_type,
+                self.availability_zone
+            ),
+        ];
+        if let Some((instance_id, device)) = &self.attachment {
+            steps.push(format_sstr!("detach {} from {instance_id}", self.volume_id));
+            steps.push(format_sstr!(
+                "attach encrypted volume to {instance_id} at {device}"
+            ));
+        }
+        steps.push("delete the unencrypted snapshot".into());
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::hashmap;
+
+    use crate::{
+        ec2_instance::VolumeInfo,
+        reencrypt::{ReencryptPlan, REENCRYPTED_FROM_TAG},
+    };
+
+    fn volume(attachment: Option<(&str, &str)>) -> VolumeInfo {
+        VolumeInfo {
+            id: "vol-0001".into(),
+            availability_zone: "us-east-1a".into(),
+            size: 8,
+            state: if attachment.is_some() {
+                "in-use"
+            } else {
+                "available"
+            }
+            .into(),
+            tags: hashmap! {"Name".into() => "data".into()},
+            volume_type: "gp2".into(),
+            attachment: attachment.map(|(i, d)| (i.into(), d.into())),
+            ..VolumeInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_reencrypt_plan() -> Result<(), Error> {
+        let attached = volume(Some(("i-0001", "/dev/sdf")));
+        assert!(ReencryptPlan::new(&attached, Some("running"), None).is_err());
+        assert!(ReencryptPlan::new(&attached, None, None).is_err());
+
+        let plan = ReencryptPlan::new(&attached, Some("stopped"), Some("".into()))?;
+        assert_eq!(plan.kms_key_id, None);
+        assert_eq!(plan.tags()["Name"], "data");
+        assert_eq!(plan.tags()[REENCRYPTED_FROM_TAG], "vol-0001");
+        assert_eq!(
+            plan.steps(),
+            vec![
+                "snapshot vol-0001",
+                "copy snapshot encrypted with default ebs key",
+                "create gp2 volume in us-east-1a from the copy",
+                "detach vol-0001 from i-0001",
+                "attach encrypted volume to i-0001 at /dev/sdf",
+                "delete the unencrypted snapshot",
+            ]
+        );
+
+        let plan = ReencryptPlan::new(&volume(None), None, Some("alias/ebs".into()))?;
+        assert_eq!(plan.steps().len(), 4);
+        assert_eq!(plan.steps()[1], "copy snapshot encrypted with alias/ebs");
+
+        let encrypted = VolumeInfo {
+            encrypted: true,
+            ..volume(None)
+        };
+        assert!(ReencryptPlan::new(&encrypted, None, None).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::{format_err, Error};
+use aws_app_client::AwsAppClient;
+use serde_json::Value;
+use std::fmt;
+
+pub use aws_app_client::{DeviceAuthorization, RemoteSession};
+
+use crate::{config::Config, resource_type::ResourceType};
+
+/// Run the OAuth2 device authorization flow against the auth service,
+/// `prompt` is called with the url and code the user must enter
+/// # Errors
+/// Returns error if `auth_url` is not set, the request is denied or expires
+pub async fn device_login(
+    config: &Config,
+    prompt: impl Fn(&DeviceAuthorization),
+) -> Result<RemoteSession, Error> {
+    let auth_url = config
+        .auth_url
+        .as_ref()
+        .ok_or_else(|| format_err!("auth_url not set"))?;
+    aws_app_client::device_login(auth_url, &config.session_path, prompt).await
+}
+
+/// Client for the aws_app_http api, used by the cli in `--remote` mode so
+/// that operators do not need local aws credentials
+#[derive(Clone)]
+pub struct RemoteClient(AwsAppClient);
+
+impl fmt::Debug for RemoteClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RemoteClient({:?})", self.0)
+    }
+}
+
+impl RemoteClient {
+    /// # Errors
+    /// Returns error if `remote_url` is not set or there is no saved session
+    pub async fn from_config(config: &Config) -> Result<Self, Error> {
+        let base_url = config
+            .remote_url
+            .as_ref()
+            .ok_or_else(|| format_err!("remote_url not set"))?;
+        AwsAppClient::from_session_file(base_url, &config.session_path)
+            .await
+            .map(Self)
+    }
+
+    /// Returns `{"resource": ..., "items": [...]}`
+    /// # Errors
+    /// Returns error if api call fails
+    pub async fn list(&self, resource: ResourceType) -> Result<Value, Error> {
+        self.0.list(resource.to_str()).await
+    }
+
+    /// # Errors
+    /// Returns error if api call fails
+    pub async fn terminate(&self, instance_id: &str) -> Result<(), Error> {
+        self.0.terminate(instance_id).await
+    }
+}
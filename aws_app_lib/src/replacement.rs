@@ -0,0 +1,441 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::Serialize;
+use stack_string::StackString;
+use std::{fmt, str::FromStr};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    pgpool::PgPool,
+    storage::{InstanceReplacementRepo, Storage},
+};
+
+/// Steps of a blue/green replacement, a replacement moves through them in
+/// order until it's completed, fails or is cancelled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplacementState {
+    /// Spot request for the new instance not made yet
+    Pending,
+    /// Waiting for the spot request to be fulfilled
+    Launching,
+    /// Waiting for the new instance to pass its setup check
+    HealthCheck,
+    /// Pointing the dns name at the new instance
+    SwappingDns,
+    /// Both instances are kept until the soak period is over
+    Soaking,
+    TerminatingOld,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl ReplacementState {
+    pub const ALL: [Self; 9] = [
+        Self::Pending,
+        Self::Launching,
+        Self::HealthCheck,
+        Self::SwappingDns,
+        Self::Soaking,
+        Self::TerminatingOld,
+        Self::Completed,
+        Self::Failed,
+        Self::Cancelled,
+    ];
+
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Launching => "launching",
+            Self::HealthCheck => "health_check",
+            Self::SwappingDns => "swapping_dns",
+            Self::Soaking => "soaking",
+            Self::TerminatingOld => "terminating_old",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    #[must_use]
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+
+    /// Replacements can be cancelled until the old instance is terminated
+    #[must_use]
+    pub fn can_cancel(self) -> bool {
+        !self.is_finished() && self != Self::TerminatingOld
+    }
+}
+
+impl fmt::Display for ReplacementState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for ReplacementState {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|state| state.to_str() == s)
+            .copied()
+            .ok_or_else(|| format_err!("{s} is not a ReplacementState"))
+    }
+}
+
+/// Replacement of an instance by a new one launched with the same
+/// parameters from a newer ami, persisted so it continues after a restart
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Serialize)]
+pub struct InstanceReplacement {
+    pub id: Uuid,
+    pub old_instance_id: StackString,
+    pub new_instance_id: Option<StackString>,
+    pub spot_request_id: Option<StackString>,
+    pub ami: StackString,
+    /// Host name moved from the old to the new instance
+    pub dns_name: Option<StackString>,
+    pub terminate_old: bool,
+    /// Minutes both instances are kept after the dns swap
+    pub soak_minutes: i32,
+    pub state: StackString,
+    /// Why the replacement failed or was cancelled
+    pub message: Option<StackString>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+    /// When traffic moved to the new instance, the end of its health check
+    /// if there is no dns name
+    pub dns_swapped_at: Option<OffsetDateTime>,
+}
+
+impl InstanceReplacement {
+    #[must_use]
+    pub fn new(
+        old_instance_id: impl Into<StackString>,
+        ami: impl Into<StackString>,
+        dns_name: Option<StackString>,
+        terminate_old: bool,
+        soak_minutes: i32,
+    ) -> Self {
+        let now = OffsetDateTime::now_utc();
+        Self {
+            id: Uuid::new_v4(),
+            old_instance_id: old_instance_id.into(),
+            new_instance_id: None,
+            spot_request_id: None,
+            ami: ami.into(),
+            dns_name,
+            terminate_old,
+            soak_minutes,
+            state: ReplacementState::Pending.to_str().into(),
+            message: None,
+            created_at: now,
+            updated_at: now,
+            dns_swapped_at: None,
+        }
+    }
+
+    /// # Errors
+    /// Returns error if the stored state is unknown
+    pub fn state(&self) -> Result<ReplacementState, Error> {
+        self.state.parse()
+    }
+
+    pub fn set_state(&mut self, state: ReplacementState) {
+        self.state = state.to_str().into();
+        self.updated_at = OffsetDateTime::now_utc();
+    }
+
+    /// Whether the soak period after the dns swap is over at `now`
+    #[must_use]
+    pub fn soak_finished(&self, now: OffsetDateTime) -> bool {
+        self.dns_swapped_at.map_or(false, |swapped_at| {
+            now >= swapped_at + Duration::minutes(self.soak_minutes.into())
+        })
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!("SELECT * FROM instance_replacement WHERE id = $id", id = id);
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// Replacements that aren't completed, failed or cancelled, oldest first
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_active(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM instance_replacement
+                WHERE state NOT IN ('completed', 'failed', 'cancelled')
+                ORDER BY created_at
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_active_by_instance(
+        pool: &PgPool,
+        old_instance_id: &str,
+    ) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM instance_replacement
+                WHERE old_instance_id = $old_instance_id
+                  AND state NOT IN ('completed', 'failed', 'cancelled')
+            ",
+            old_instance_id = old_instance_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_recent(pool: &PgPool, limit: i64) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM instance_replacement ORDER BY created_at DESC LIMIT $limit",
+            limit = limit,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO instance_replacement (
+                    id, old_instance_id, new_instance_id, spot_request_id, ami, dns_name,
+                    terminate_old, soak_minutes, state, message, created_at, updated_at,
+                    dns_swapped_at
+                ) VALUES (
+                    $id, $old_instance_id, $new_instance_id, $spot_request_id, $ami, $dns_name,
+                    $terminate_old, $soak_minutes, $state, $message, $created_at, $updated_at,
+                    $dns_swapped_at
+                )
+            ",
+            id = self.id,
+            old_instance_id = self.old_instance_id,
+            new_instance_id = self.new_instance_id,
+            spot_request_id = self.spot_request_id,
+            ami = self.ami,
+            dns_name = self.dns_name,
+            terminate_old = self.terminate_old,
+            soak_minutes = self.soak_minutes,
+            state = self.state,
+            message = self.message,
+            created_at = self.created_at,
+            updated_at = self.updated_at,
+            dns_swapped_at = self.dns_swapped_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Move to `state` unless the stored state changed since this copy was
+    /// loaded (a cancel, or another pass advancing it). Every transition is
+    /// claimed this way before the side effects of the next step run, so a
+    /// cancelled replacement never requests, swaps or terminates anything.
+    /// Returns whether the transition was claimed, if not the state is left
+    /// as it was
+    /// # Errors
+    /// Returns error if the state is unknown or the db query fails
+    pub async fn claim(
+        &mut self,
+        storage: &dyn Storage,
+        state: ReplacementState,
+    ) -> Result<bool, Error> {
+        let current_state = self.state()?;
+        let previous = self.clone();
+        self.set_state(state);
+        if !storage.update_replacement_from(self, current_state).await? {
+            self.state = previous.state;
+            self.updated_at = previous.updated_at;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Store this replacement only if the stored state is still
+    /// `current_state`, returns whether it was stored
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn update_from(
+        &self,
+        pool: &PgPool,
+        current_state: ReplacementState,
+    ) -> Result<bool, Error> {
+        let query = query!(
+            r"
+                UPDATE instance_replacement
+                SET new_instance_id=$new_instance_id,spot_request_id=$spot_request_id,
+                    state=$state,message=$message,updated_at=$updated_at,
+                    dns_swapped_at=$dns_swapped_at
+                WHERE id=$id AND state=$current_state
+            ",
+            id = self.id,
+            new_instance_id = self.new_instance_id,
+            spot_request_id = self.spot_request_id,
+            state = self.state,
+            message = self.message,
+            updated_at = self.updated_at,
+            dns_swapped_at = self.dns_swapped_at,
+            current_state = current_state.to_str(),
+        );
+        let conn = pool.get().await?;
+        Ok(query.execute(&conn).await? > 0)
+    }
+
+    /// Record the spot request made for the new instance whatever the state,
+    /// so an instance launched just before a cancel is still shown
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn update_spot_request_id(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "UPDATE instance_replacement SET spot_request_id=$spot_request_id WHERE id=$id",
+            id = self.id,
+            spot_request_id = self.spot_request_id,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{format_err, Error};
+    use postgres_query::query;
+    use time::{Duration, OffsetDateTime};
+
+    use crate::{
+        config::Config,
+        pgpool::PgPool,
+        replacement::{InstanceReplacement, ReplacementState},
+    };
+
+    #[test]
+    fn test_replacement_state() -> Result<(), Error> {
+        for state in ReplacementState::ALL {
+            assert_eq!(state.to_str().parse::<ReplacementState>()?, state);
+        }
+        assert!("rolled_back".parse::<ReplacementState>().is_err());
+        assert!(ReplacementState::Soaking.can_cancel());
+        assert!(!ReplacementState::TerminatingOld.can_cancel());
+        assert!(!ReplacementState::Completed.can_cancel());
+        assert!(ReplacementState::Cancelled.is_finished());
+        assert!(!ReplacementState::HealthCheck.is_finished());
+        Ok(())
+    }
+
+    #[test]
+    fn test_soak_finished() -> Result<(), Error> {
+        let mut replacement =
+            InstanceReplacement::new("i-old", "ami-new", Some("www.example.com".into()), true, 30);
+        assert_eq!(replacement.state()?, ReplacementState::Pending);
+        let now = OffsetDateTime::now_utc();
+        assert!(!replacement.soak_finished(now));
+        replacement.dns_swapped_at = Some(now - Duration::minutes(10));
+        assert!(!replacement.soak_finished(now));
+        replacement.dns_swapped_at = Some(now - Duration::minutes(30));
+        assert!(replacement.soak_finished(now));
+        replacement.set_state(ReplacementState::TerminatingOld);
+        assert_eq!(replacement.state, "terminating_old");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancel_during_soak() -> Result<(), Error> {
+        let config = Config::init_config()?;
+        let pool = PgPool::new(&config.database_url)?;
+
+        let mut replacement = InstanceReplacement::new("i-test-soak", "ami-new", None, true, 30);
+        replacement.dns_swapped_at = Some(OffsetDateTime::now_utc() - Duration::minutes(60));
+        replacement.set_state(ReplacementState::Soaking);
+        replacement.insert_entry(&pool).await?;
+
+        // the background pass holds a copy loaded before the cancel
+        let mut advancing = replacement.clone();
+        assert!(
+            replacement
+                .claim(&pool, ReplacementState::Cancelled)
+                .await?
+        );
+
+        assert!(advancing.soak_finished(OffsetDateTime::now_utc()));
+        assert!(
+            !advancing
+                .claim(&pool, ReplacementState::TerminatingOld)
+                .await?
+        );
+        assert_eq!(advancing.state()?, ReplacementState::Soaking);
+
+        let stored = InstanceReplacement::get_by_id(&pool, replacement.id)
+            .await?
+            .ok_or_else(|| format_err!("replacement not stored"))?;
+        assert_eq!(stored.state()?, ReplacementState::Cancelled);
+
+        delete_replacement(&pool, &replacement).await
+    }
+
+    #[tokio::test]
+    async fn test_cancel_before_launch() -> Result<(), Error> {
+        let config = Config::init_config()?;
+        let pool = PgPool::new(&config.database_url)?;
+
+        let replacement = InstanceReplacement::new("i-test-pending", "ami-new", None, true, 30);
+        replacement.insert_entry(&pool).await?;
+
+        // loaded by the background pass, then cancelled before its first step
+        let mut advancing = InstanceReplacement::get_by_id(&pool, replacement.id)
+            .await?
+            .ok_or_else(|| format_err!("replacement not stored"))?;
+        let mut cancelling = advancing.clone();
+        assert!(cancelling.claim(&pool, ReplacementState::Cancelled).await?);
+
+        // the spot request is only made once launching is claimed
+        assert!(!advancing.claim(&pool, ReplacementState::Launching).await?);
+        assert_eq!(advancing.state()?, ReplacementState::Pending);
+
+        // a cancel racing a claimed step doesn't overwrite it either
+        let mut advancing = InstanceReplacement::new("i-test-soaked", "ami-new", None, true, 0);
+        advancing.set_state(ReplacementState::Soaking);
+        advancing.insert_entry(&pool).await?;
+        let mut cancelling = advancing.clone();
+        assert!(
+            advancing
+                .claim(&pool, ReplacementState::TerminatingOld)
+                .await?
+        );
+        assert!(!cancelling.claim(&pool, ReplacementState::Cancelled).await?);
+
+        delete_replacement(&pool, &replacement).await?;
+        delete_replacement(&pool, &advancing).await
+    }
+
+    async fn delete_replacement(
+        pool: &PgPool,
+        replacement: &InstanceReplacement,
+    ) -> Result<(), Error> {
+        let query = query!(
+            "DELETE FROM instance_replacement WHERE id = $id",
+            id = replacement.id
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
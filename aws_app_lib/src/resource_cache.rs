@@ -0,0 +1,101 @@
+use parking_lot::RwLock;
+use std::{collections::HashMap, fmt, sync::Arc};
+use time::{Duration, OffsetDateTime};
+
+use crate::resource_type::ResourceType;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheEntry<T> {
+    pub value: T,
+    pub updated: OffsetDateTime,
+}
+
+impl<T> CacheEntry<T> {
+    #[must_use]
+    pub fn age(&self) -> Duration {
+        OffsetDateTime::now_utc() - self.updated
+    }
+}
+
+/// Per resource type cache whose entries expire after `ttl`, mutating
+/// operations are expected to call `invalidate` for the types they touch
+#[derive(Clone)]
+pub struct ResourceCache<T> {
+    ttl: Duration,
+    entries: Arc<RwLock<HashMap<ResourceType, CacheEntry<T>>>>,
+}
+
+impl<T> fmt::Debug for ResourceCache<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ResourceCache(ttl={})", self.ttl)
+    }
+}
+
+impl<T: Clone> ResourceCache<T> {
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    #[must_use]
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Returns the cached entry if it is younger than the ttl
+    #[must_use]
+    pub fn get(&self, resource: ResourceType) -> Option<CacheEntry<T>> {
+        self.entries
+            .read()
+            .get(&resource)
+            .filter(|entry| entry.age() < self.ttl)
+            .cloned()
+    }
+
+    pub fn insert(&self, resource: ResourceType, value: T) {
+        let entry = CacheEntry {
+            value,
+            updated: OffsetDateTime::now_utc(),
+        };
+        self.entries.write().insert(resource, entry);
+    }
+
+    pub fn invalidate(&self, resources: impl IntoIterator<Item = ResourceType>) {
+        let mut entries = self.entries.write();
+        for resource in resources {
+            entries.remove(&resource);
+        }
+    }
+
+    pub fn invalidate_all(&self) {
+        self.entries.write().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use crate::{resource_cache::ResourceCache, resource_type::ResourceType};
+
+    #[test]
+    fn test_resource_cache() {
+        let cache = ResourceCache::new(Duration::seconds(30));
+        assert_eq!(cache.get(ResourceType::Volume), None);
+        cache.insert(ResourceType::Volume, 1);
+        cache.insert(ResourceType::Snapshot, 2);
+        assert_eq!(cache.get(ResourceType::Volume).map(|e| e.value), Some(1));
+        cache.invalidate([ResourceType::Volume]);
+        assert_eq!(cache.get(ResourceType::Volume), None);
+        assert_eq!(cache.get(ResourceType::Snapshot).map(|e| e.value), Some(2));
+        cache.invalidate_all();
+        assert_eq!(cache.get(ResourceType::Snapshot), None);
+
+        let cache = ResourceCache::new(Duration::ZERO);
+        cache.insert(ResourceType::Volume, 1);
+        assert_eq!(cache.get(ResourceType::Volume), None);
+    }
+}
@@ -0,0 +1,370 @@
+use serde::Serialize;
+use stack_string::{format_sstr, StackString};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use crate::{
+    ec2_instance::{AmiInfo, Ec2InstanceInfo, SnapshotInfo, SpotInstanceRequestInfo, VolumeInfo},
+    route53_instance::DnsRecord,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    DnsRecord,
+    Ip,
+    SpotRequest,
+    Instance,
+    Volume,
+    Snapshot,
+    Ami,
+}
+
+impl NodeKind {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::DnsRecord => "dns_record",
+            Self::Ip => "ip",
+            Self::SpotRequest => "spot_request",
+            Self::Instance => "instance",
+            Self::Volume => "volume",
+            Self::Snapshot => "snapshot",
+            Self::Ami => "ami",
+        }
+    }
+
+    fn shape(self) -> &'static str {
+        match self {
+            Self::DnsRecord => "note",
+            Self::Ip => "ellipse",
+            Self::SpotRequest => "diamond",
+            Self::Instance => "box",
+            Self::Volume => "cylinder",
+            Self::Snapshot => "folder",
+            Self::Ami => "component",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GraphNode {
+    /// Resource id, the name for dns records and the address for ips
+    pub id: StackString,
+    pub kind: NodeKind,
+    /// Name tag or image name where there is one
+    pub label: StackString,
+}
+
+/// `from` refers to `to`. When `dependency` is set `from` stops working if
+/// `to` is deleted, otherwise the edge only records where `from` came from.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GraphEdge {
+    pub from: StackString,
+    pub to: StackString,
+    pub relation: &'static str,
+    pub dependency: bool,
+}
+
+/// Instances with their volumes, the snapshots of those volumes and the amis
+/// backed by the snapshots, along with the spot requests and dns records
+/// pointing at them
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ResourceGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl ResourceGraph {
+    #[must_use]
+    pub fn new(
+        instances: &[Ec2InstanceInfo],
+        volumes: &[VolumeInfo],
+        snapshots: &[SnapshotInfo],
+        amis: &[AmiInfo],
+        spot_requests: &[SpotInstanceRequestInfo],
+        dns_records: &[(String, DnsRecord)],
+    ) -> Self {
+        let mut graph = Self::default();
+        let mut ip_owners: BTreeMap<&str, &str> = BTreeMap::new();
+        for instance in instances {
+            graph.add_node(&instance.id, NodeKind::Instance, name_tag(&instance.tags));
+            for ip in instance.public_ip.iter().chain(instance.private_ip.iter()) {
+                ip_owners.insert(ip.as_str(), instance.id.as_str());
+            }
+            for volume in &instance.volumes {
+                graph.add_edge(&instance.id, volume, "attaches", true);
+            }
+        }
+        for volume in volumes {
+            graph.add_node(&volume.id, NodeKind::Volume, name_tag(&volume.tags));
+            if let Some((instance_id, _)) = &volume.attachment {
+                graph.add_edge(instance_id, &volume.id, "attaches", true);
+            }
+        }
+        for snapshot in snapshots {
+            graph.add_node(&snapshot.id, NodeKind::Snapshot, name_tag(&snapshot.tags));
+            if let Some(volume_id) = &snapshot.volume_id {
+                graph.add_edge(&snapshot.id, volume_id, "snapshot of", false);
+            }
+        }
+        for ami in amis {
+            graph.add_node(&ami.id, NodeKind::Ami, Some(ami.name.as_str()));
+            for snapshot_id in &ami.snapshot_ids {
+                graph.add_edge(&ami.id, snapshot_id, "backed by", true);
+            }
+        }
+        for spot in spot_requests {
+            graph.add_node(&spot.id, NodeKind::SpotRequest, None);
+            if !spot.imageid.is_empty() {
+                graph.add_edge(&spot.id, &spot.imageid, "launches from", true);
+            }
+            if let Some(instance_id) = &spot.instance_id {
+                graph.add_edge(&spot.id, instance_id, "launched", false);
+            }
+        }
+        for (_, record) in dns_records {
+            if record.record_type != "A" && record.record_type != "AAAA" {
+                continue;
+            }
+            let name = format_sstr!("{} {}", record.dnsname, record.record_type);
+            graph.add_node(&name, NodeKind::DnsRecord, Some(record.dnsname.as_str()));
+            for value in &record.values {
+                graph.add_node(value, NodeKind::Ip, None);
+                graph.add_edge(&name, value, "resolves to", true);
+                if let Some(instance_id) = ip_owners.get(value.as_str()) {
+                    graph.add_edge(value, instance_id, "assigned to", true);
+                }
+            }
+        }
+        graph.prune_dangling();
+        graph
+    }
+
+    fn add_node(&mut self, id: &str, kind: NodeKind, label: Option<&str>) {
+        if self.nodes.iter().any(|n| n.id.as_str() == id) {
+            return;
+        }
+        self.nodes.push(GraphNode {
+            id: id.into(),
+            kind,
+            label: label.unwrap_or(id).into(),
+        });
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str, relation: &'static str, dependency: bool) {
+        if self
+            .edges
+            .iter()
+            .any(|e| e.from.as_str() == from && e.to.as_str() == to)
+        {
+            return;
+        }
+        self.edges.push(GraphEdge {
+            from: from.into(),
+            to: to.into(),
+            relation,
+            dependency,
+        });
+    }
+
+    /// Drop edges to resources which weren't listed, e.g. the volume of a
+    /// snapshot which has since been deleted
+    fn prune_dangling(&mut self) {
+        let ids: BTreeSet<StackString> = self.nodes.iter().map(|n| n.id.clone()).collect();
+        self.edges
+            .retain(|e| ids.contains(&e.from) && ids.contains(&e.to));
+    }
+
+    /// Resources which stop working if `id` is deleted, nearest first
+    #[must_use]
+    pub fn impacted(&self, id: &str) -> Vec<&GraphNode> {
+        let mut dependents: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for edge in self.edges.iter().filter(|e| e.dependency) {
+            dependents
+                .entry(edge.to.as_str())
+                .or_default()
+                .push(edge.from.as_str());
+        }
+        let mut seen: BTreeSet<&str> = BTreeSet::new();
+        seen.insert(id);
+        let mut queue: VecDeque<&str> = VecDeque::from([id]);
+        let mut impacted = Vec::new();
+        while let Some(current) = queue.pop_front() {
+            for &dependent in dependents.get(current).into_iter().flatten() {
+                if seen.insert(dependent) {
+                    queue.push_back(dependent);
+                    if let Some(node) = self.nodes.iter().find(|n| n.id.as_str() == dependent) {
+                        impacted.push(node);
+                    }
+                }
+            }
+        }
+        impacted
+    }
+
+    /// Graphviz dot source of the graph, dashed edges aren't dependencies
+    #[must_use]
+    pub fn to_dot(&self) -> StackString {
+        let mut dot = String::from("digraph resources {\n    rankdir=LR;\n");
+        for node in &self.nodes {
+            let label = if node.label == node.id {
+                node.id.clone()
+            } else {
+                format_sstr!("{}\\n{}", node.label, node.id)
+            };
+            dot.push_str(&format_sstr!(
+                "    \"{}\" [shape={}, label=\"{}\"];\n",
+                escape(&node.id),
+                node.kind.shape(),
+                escape(&label)
+            ));
+        }
+        for edge in &self.edges {
+            let style = if edge.dependency { "solid" } else { "dashed" };
+            dot.push_str(&format_sstr!(
+                "    \"{}\" -> \"{}\" [label=\"{}\", style={style}];\n",
+                escape(&edge.from),
+                escape(&edge.to),
+                edge.relation
+            ));
+        }
+        dot.push_str("}\n");
+        dot.into()
+    }
+}
+
+fn name_tag(tags: &HashMap<StackString, StackString>) -> Option<&str> {
+    tags.get("Name").map(StackString::as_str)
+}
+
+fn escape(s: &str) -> StackString {
+    s.replace('"', "\\\"").into()
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+
+    use crate::{
+        ec2_instance::{
+            AmiInfo, Ec2InstanceInfo, SnapshotInfo, SpotInstanceRequestInfo, VolumeInfo,
+        },
+        resource_graph::{NodeKind, ResourceGraph},
+        route53_instance::DnsRecord,
+    };
+
+    fn graph() -> ResourceGraph {
+        let instance = Ec2InstanceInfo {
+            id: "i-0001".into(),
+            state: "running".into(),
+            tags: hashmap! {"Name".into() => "web".into()},
+            volumes: vec!["vol-0001".into()],
+            public_ip: Some("1.2.3.4".into()),
+            private_ip: Some("10.0.0.5".into()),
+            ..Ec2InstanceInfo::default()
+        };
+        let volume = VolumeInfo {
+            id: "vol-0001".into(),
+            attachment: Some(("i-0001".into(), "/dev/xvda".into())),
+            ..VolumeInfo::default()
+        };
+        let snapshots = [
+            SnapshotInfo {
+                id: "snap-0001".into(),
+                volume_id: Some("vol-0001".into()),
+                ..SnapshotInfo::default()
+            },
+            SnapshotInfo {
+                id: "snap-0002".into(),
+                volume_id: Some("vol-deleted".into()),
+                ..SnapshotInfo::default()
+            },
+        ];
+        let ami = AmiInfo {
+            id: "ami-0001".into(),
+            name: "web-image".into(),
+            state: "available".into(),
+            snapshot_ids: vec!["snap-0001".into()],
+            creation_date: None,
+            ..AmiInfo::default()
+        };
+        let spot = SpotInstanceRequestInfo {
+            id: "sir-0001".into(),
+            imageid: "ami-0001".into(),
+            instance_id: Some("i-0001".into()),
+            ..SpotInstanceRequestInfo::default()
+        };
+        let record = DnsRecord {
+            dnsname: "web.example.com".into(),
+            ip: "1.2.3.4".into(),
+            set_identifier: None,
+            routing: None,
+            health_check_id: None,
+            record_type: "A".into(),
+            ttl: Some(300),
+            values: vec!["1.2.3.4".into()],
+            alias_target: None,
+        };
+        ResourceGraph::new(
+            &[instance],
+            &[volume],
+            &snapshots,
+            &[ami],
+            &[spot],
+            &[("Z0123".into(), record)],
+        )
+    }
+
+    #[test]
+    fn test_resource_graph() {
+        let graph = graph();
+        assert_eq!(graph.nodes.len(), 8);
+        assert_eq!(graph.nodes[0].label, "web");
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| (e.from.as_str(), e.to.as_str(), e.relation))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("i-0001", "vol-0001", "attaches"),
+                ("snap-0001", "vol-0001", "snapshot of"),
+                ("ami-0001", "snap-0001", "backed by"),
+                ("sir-0001", "ami-0001", "launches from"),
+                ("sir-0001", "i-0001", "launched"),
+                ("web.example.com A", "1.2.3.4", "resolves to"),
+                ("1.2.3.4", "i-0001", "assigned to"),
+            ]
+        );
+
+        let impacted: Vec<_> = graph
+            .impacted("snap-0001")
+            .into_iter()
+            .map(|n| (n.kind, n.id.as_str()))
+            .collect();
+        assert_eq!(
+            impacted,
+            vec![
+                (NodeKind::Ami, "ami-0001"),
+                (NodeKind::SpotRequest, "sir-0001")
+            ]
+        );
+        let impacted: Vec<_> = graph
+            .impacted("vol-0001")
+            .into_iter()
+            .map(|n| n.id.as_str())
+            .collect();
+        assert_eq!(impacted, vec!["i-0001", "1.2.3.4", "web.example.com A"]);
+        assert!(graph.impacted("web.example.com A").is_empty());
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = graph().to_dot();
+        assert!(dot.starts_with("digraph resources {"));
+        assert!(dot.contains("\"i-0001\" [shape=box, label=\"web\\ni-0001\"];"));
+        assert!(dot.contains("\"sir-0001\" -> \"i-0001\" [label=\"launched\", style=dashed];"));
+        assert!(dot.contains("\"ami-0001\" -> \"snap-0001\" [label=\"backed by\", style=solid];"));
+    }
+}
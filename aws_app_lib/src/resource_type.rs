@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 use stack_string::StackString;
 use std::{convert::TryFrom, fmt, str::FromStr};
 
-pub static ALL_RESOURCES: [ResourceType; 15] = [
+pub static ALL_RESOURCES: [ResourceType; 21] = [
     ResourceType::Instances,
     ResourceType::Reserved,
     ResourceType::Spot,
@@ -19,6 +19,12 @@ pub static ALL_RESOURCES: [ResourceType; 15] = [
     ResourceType::Route53,
     ResourceType::SystemD,
     ResourceType::InboundEmail,
+    ResourceType::Sqs,
+    ResourceType::Backup,
+    ResourceType::Lambda,
+    ResourceType::Acm,
+    ResourceType::Docker,
+    ResourceType::Ecs,
 ];
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
@@ -54,6 +60,18 @@ pub enum ResourceType {
     SystemD,
     #[serde(rename = "inbound-email")]
     InboundEmail,
+    #[serde(rename = "sqs")]
+    Sqs,
+    #[serde(rename = "backup")]
+    Backup,
+    #[serde(rename = "lambda")]
+    Lambda,
+    #[serde(rename = "acm")]
+    Acm,
+    #[serde(rename = "docker")]
+    Docker,
+    #[serde(rename = "ecs")]
+    Ecs,
     #[serde(rename = "all")]
     All,
 }
@@ -77,6 +95,12 @@ impl ResourceType {
             Self::Route53 => "route53",
             Self::SystemD => "systemd",
             Self::InboundEmail => "inbound-email",
+            Self::Sqs => "sqs",
+            Self::Backup => "backup",
+            Self::Lambda => "lambda",
+            Self::Acm => "acm",
+            Self::Docker => "docker",
+            Self::Ecs => "ecs",
             Self::All => "all",
         }
     }
@@ -108,6 +132,12 @@ impl FromStr for ResourceType {
             "route53" | "dns" => Ok(Self::Route53),
             "systemd" => Ok(Self::SystemD),
             "inbound-email" => Ok(Self::InboundEmail),
+            "sqs" => Ok(Self::Sqs),
+            "backup" => Ok(Self::Backup),
+            "lambda" => Ok(Self::Lambda),
+            "acm" | "certificates" => Ok(Self::Acm),
+            "docker" => Ok(Self::Docker),
+            "ecs" | "fargate" => Ok(Self::Ecs),
             "all" => Ok(Self::All),
             _ => Err(format_err!("{} is not a ResourceType", s)),
         }
@@ -0,0 +1,384 @@
+//! Retry loop used around aws calls: errors are classified so that auth and
+//! validation failures fail fast, and the backoff is capped both per attempt
+//! and over the whole call.
+use anyhow::Error;
+use aws_sdk_s3::{
+    config::http::HttpResponse,
+    error::{ProvideErrorMetadata, SdkError},
+};
+use log::{debug, warn};
+use rand::{thread_rng, Rng};
+use std::{
+    error::Error as StdError,
+    fmt,
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::time::{sleep, Duration, Instant};
+use tracing::instrument;
+
+use crate::{aws_error::AwsErrorInfo, config::Config};
+
+/// Error codes returned by aws when a caller is being rate limited
+const THROTTLING_CODES: &[&str] = &[
+    "Throttling",
+    "ThrottlingException",
+    "ThrottledException",
+    "RequestThrottled",
+    "RequestThrottledException",
+    "TooManyRequestsException",
+    "RequestLimitExceeded",
+    "SlowDown",
+    "PriorRequestNotComplete",
+    "ProvisionedThroughputExceededException",
+];
+
+/// Error codes that are worth retrying even though they come back as 4xx
+const TRANSIENT_CODES: &[&str] = &[
+    "RequestTimeout",
+    "RequestTimeoutException",
+    "InternalError",
+    "ServiceUnavailable",
+    "IDPCommunicationError",
+];
+
+static RETRY_ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+static RETRY_THROTTLED: AtomicU64 = AtomicU64::new(0);
+static RETRY_EXHAUSTED: AtomicU64 = AtomicU64::new(0);
+static RETRY_PERMANENT: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Retryability {
+    /// Network, timeout or 5xx failure
+    Transient,
+    /// Rate limited by aws
+    Throttled,
+    /// Auth, validation or other client errors that will fail again
+    Permanent,
+}
+
+impl Retryability {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Transient => "transient",
+            Self::Throttled => "throttled",
+            Self::Permanent => "permanent",
+        }
+    }
+
+    #[must_use]
+    pub fn is_retryable(self) -> bool {
+        self != Self::Permanent
+    }
+
+    fn from_code_and_status(code: Option<&str>, status: Option<u16>) -> Self {
+        if let Some(code) = code {
+            if THROTTLING_CODES.contains(&code) {
+                return Self::Throttled;
+            }
+            if TRANSIENT_CODES.contains(&code) {
+                return Self::Transient;
+            }
+        }
+        match status {
+            Some(429) => Self::Throttled,
+            Some(s) if s >= 500 => Self::Transient,
+            _ => Self::Permanent,
+        }
+    }
+}
+
+impl fmt::Display for Retryability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+/// An sdk error tagged with whether retrying it can succeed
+#[derive(Debug)]
+pub struct ClassifiedError {
+    pub retryability: Retryability,
+    /// Set for service errors, see `AwsErrorInfo::from_error`
+    pub info: Option<AwsErrorInfo>,
+    inner: Box<dyn StdError + Send + Sync>,
+}
+
+impl fmt::Display for ClassifiedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+impl StdError for ClassifiedError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.inner.source()
+    }
+}
+
+/// Dispatch and timeout failures, and any variant added to the
+/// `non_exhaustive` `SdkError` later, are treated as transient
+#[must_use]
+pub fn classify_sdk_error<E>(err: &SdkError<E, HttpResponse>) -> Retryability
+where
+    E: ProvideErrorMetadata,
+{
+    match err {
+        SdkError::ConstructionFailure(_) => Retryability::Permanent,
+        SdkError::ServiceError(ctx) => {
+            Retryability::from_code_and_status(ctx.err().code(), Some(ctx.raw().status().as_u16()))
+        }
+        SdkError::ResponseError(ctx) => {
+            Retryability::from_code_and_status(None, Some(ctx.raw().status().as_u16()))
+        }
+        _ => Retryability::Transient,
+    }
+}
+
+/// Convert an sdk error into an `anyhow::Error` carrying its retryability,
+/// for use as `.map_err(sdk_error)`
+pub fn sdk_error<E>(err: SdkError<E, HttpResponse>) -> Error
+where
+    E: ProvideErrorMetadata + StdError + Send + Sync + 'static,
+{
+    let retryability = classify_sdk_error(&err);
+    let info = err.as_service_error().map(AwsErrorInfo::from_provider);
+    ClassifiedError {
+        retryability,
+        info,
+        inner: Box::new(err),
+    }
+    .into()
+}
+
+/// Errors that were not converted with `sdk_error` (io errors reading a
+/// response body, etc) keep the old behaviour of being retried
+#[must_use]
+pub fn classify_error(err: &Error) -> Retryability {
+    err.downcast_ref::<ClassifiedError>()
+        .map_or(Retryability::Transient, |e| e.retryability)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of calls, including the first
+    pub max_attempts: u32,
+    /// Give up rather than sleep past this much time since the first call
+    pub max_elapsed: Duration,
+    /// Delay before the first retry, doubled on every subsequent one
+    pub backoff_base: Duration,
+    /// Cap on a single delay
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            max_elapsed: Duration::from_secs(60),
+            backoff_base: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(20),
+        }
+    }
+}
+
+impl From<&Config> for RetryPolicy {
+    fn from(config: &Config) -> Self {
+        Self {
+            max_attempts: config.retry_max_attempts.max(1),
+            max_elapsed: Duration::from_secs(config.retry_max_elapsed_secs),
+            backoff_base: Duration::from_millis(config.retry_backoff_base_ms),
+            max_backoff: Duration::from_millis(config.retry_max_backoff_ms),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay after the `attempt`th failed call (starting at 1), `jitter` in
+    /// `[0, 1)` picks a point between half and all of the capped delay
+    #[must_use]
+    pub fn backoff_delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let delay = self
+            .backoff_base
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        delay.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetryMetrics {
+    /// Number of retries, i.e. calls after the first
+    pub attempts: u64,
+    /// Retries caused by throttling
+    pub throttled: u64,
+    /// Calls that failed after exhausting the policy
+    pub exhausted: u64,
+    /// Calls that failed immediately with a non-retryable error
+    pub permanent: u64,
+}
+
+/// Counters accumulated by every `retry_with_policy` call in this process
+#[must_use]
+pub fn retry_metrics() -> RetryMetrics {
+    RetryMetrics {
+        attempts: RETRY_ATTEMPTS.load(Ordering::Relaxed),
+        throttled: RETRY_THROTTLED.load(Ordering::Relaxed),
+        exhausted: RETRY_EXHAUSTED.load(Ordering::Relaxed),
+        permanent: RETRY_PERMANENT.load(Ordering::Relaxed),
+    }
+}
+
+/// Runs inside an `aws_call` span so that failures can be traced back to the
+/// request that issued them
+/// # Errors
+/// Returns the last error once it is not retryable or the policy is exhausted
+#[instrument(name = "aws_call", skip_all, fields(operation = operation))]
+pub async fn retry_with_policy<T, U, F>(
+    policy: &RetryPolicy,
+    operation: &str,
+    f: T,
+) -> Result<U, Error>
+where
+    T: Fn() -> F,
+    F: Future<Output = Result<U, Error>>,
+{
+    let start = Instant::now();
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let err = match f().await {
+            Ok(resp) => {
+                if attempt > 1 {
+                    debug!("retry operation={operation} attempt={attempt} result=success");
+                }
+                return Ok(resp);
+            }
+            Err(err) => err,
+        };
+        let retryability = classify_error(&err);
+        if !retryability.is_retryable() {
+            RETRY_PERMANENT.fetch_add(1, Ordering::Relaxed);
+            debug!("retry operation={operation} attempt={attempt} result=permanent error={err}");
+            return Err(err);
+        }
+        let delay = policy.backoff_delay(attempt, thread_rng().gen());
+        if attempt >= policy.max_attempts || start.elapsed() + delay > policy.max_elapsed {
+            RETRY_EXHAUSTED.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "retry operation={operation} attempt={attempt} result=exhausted \
+                 elapsed_ms={} error={err}",
+                start.elapsed().as_millis()
+            );
+            return Err(err);
+        }
+        RETRY_ATTEMPTS.fetch_add(1, Ordering::Relaxed);
+        if retryability == Retryability::Throttled {
+            RETRY_THROTTLED.fetch_add(1, Ordering::Relaxed);
+        }
+        warn!(
+            "retry operation={operation} attempt={attempt} result={retryability} delay_ms={} \
+             error={err}",
+            delay.as_millis()
+        );
+        sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{format_err, Error};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::time::Duration;
+
+    use crate::retry::{retry_with_policy, ClassifiedError, RetryPolicy, Retryability};
+
+    fn permanent_error() -> Error {
+        ClassifiedError {
+            retryability: Retryability::Permanent,
+            info: None,
+            inner: format_err!("AccessDenied").into(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_retryability_from_code_and_status() {
+        assert_eq!(
+            Retryability::from_code_and_status(Some("ThrottlingException"), Some(400)),
+            Retryability::Throttled
+        );
+        assert_eq!(
+            Retryability::from_code_and_status(Some("AccessDenied"), Some(403)),
+            Retryability::Permanent
+        );
+        assert_eq!(
+            Retryability::from_code_and_status(Some("ValidationException"), Some(400)),
+            Retryability::Permanent
+        );
+        assert_eq!(
+            Retryability::from_code_and_status(Some("RequestTimeout"), Some(400)),
+            Retryability::Transient
+        );
+        assert_eq!(
+            Retryability::from_code_and_status(None, Some(503)),
+            Retryability::Transient
+        );
+        assert_eq!(
+            Retryability::from_code_and_status(None, Some(429)),
+            Retryability::Throttled
+        );
+    }
+
+    #[test]
+    fn test_backoff_delay_is_capped() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff_delay(1, 0.0), Duration::from_millis(250));
+        assert_eq!(policy.backoff_delay(3, 0.0), Duration::from_secs(1));
+        for attempt in 1..64 {
+            assert!(policy.backoff_delay(attempt, 1.0) <= policy.max_backoff);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_policy() -> Result<(), Error> {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            max_elapsed: Duration::from_secs(5),
+            backoff_base: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        };
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), Error> = retry_with_policy(&policy, "test", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(permanent_error())
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), Error> = retry_with_policy(&policy, "test", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(format_err!("connection reset"))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = AtomicU32::new(0);
+        let result = retry_with_policy(&policy, "test", || async {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(format_err!("connection reset"))
+            } else {
+                Ok(42)
+            }
+        })
+        .await?;
+        assert_eq!(result, 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+}
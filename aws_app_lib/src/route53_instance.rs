@@ -1,12 +1,18 @@
 use anyhow::{format_err, Error};
 use aws_config::SdkConfig;
 use aws_sdk_route53::{
-    types::{Change, ChangeAction, ChangeBatch, HostedZone, ResourceRecordSet, RrType},
+    types::{
+        Change, ChangeAction, ChangeBatch, HealthCheck, HealthCheckConfig, HealthCheckType,
+        HostedZone, ResourceRecord, ResourceRecordSet, ResourceRecordSetFailover, RrType,
+    },
     Client as Route53Client,
 };
 use aws_types::region::Region;
-use futures::{stream::FuturesUnordered, TryStreamExt};
-use std::{fmt, net::Ipv4Addr};
+use futures::{future::try_join_all, stream::FuturesUnordered, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::{fmt, fmt::Write, net::Ipv4Addr};
+use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct Route53Instance {
@@ -19,10 +25,301 @@ impl fmt::Debug for Route53Instance {
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+/// Check that `name` is a fully qualified host name such as
+/// `build.example.com`
+/// # Errors
+/// Returns error if `name` isn't a valid host name
+pub fn validate_host_name(name: &str) -> Result<(), Error> {
+    let name = name.trim_end_matches('.');
+    if name.len() > 253 || !name.contains('.') {
+        return Err(format_err!("{name} is not a fully qualified host name"));
+    }
+    for label in name.split('.') {
+        if label.is_empty()
+            || label.len() > 63
+            || label.starts_with('-')
+            || label.ends_with('-')
+            || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err(format_err!("{name} is not a valid host name"));
+        }
+    }
+    Ok(())
+}
+
+/// Id of the hosted zone `name` belongs in, the zone with the longest
+/// matching suffix wins. `zones` are `(id, name)` pairs.
+#[must_use]
+pub fn hosted_zone_for<'a>(
+    name: &str,
+    zones: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Option<&'a str> {
+    let name = name.trim_end_matches('.').to_lowercase();
+    zones
+        .into_iter()
+        .filter(|(_, zone)| {
+            let zone = zone.trim_end_matches('.').to_lowercase();
+            name == zone || name.ends_with(&format!(".{zone}"))
+        })
+        .max_by_key(|(_, zone)| zone.trim_end_matches('.').len())
+        .map(|(id, _)| id)
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, Serialize, Deserialize)]
 pub struct DnsRecord {
     pub dnsname: String,
+    /// First of `values`, or the alias target of an alias record
     pub ip: String,
+    /// Distinguishes the records of a failover or weighted set
+    pub set_identifier: Option<String>,
+    pub routing: Option<RecordRouting>,
+    pub health_check_id: Option<String>,
+    /// `A`, `CNAME`, `MX`, ...
+    #[serde(default = "default_record_type")]
+    pub record_type: String,
+    /// Unset for alias records, which use the ttl of their target
+    pub ttl: Option<i64>,
+    #[serde(default)]
+    pub values: Vec<String>,
+    /// Dns name of the resource an alias record points at
+    pub alias_target: Option<String>,
+}
+
+fn default_record_type() -> String {
+    "A".into()
+}
+
+impl DnsRecord {
+    fn from_record_set(record: ResourceRecordSet) -> Self {
+        let routing = RecordRouting::from_record_set(&record);
+        let values: Vec<String> = record
+            .resource_records
+            .unwrap_or_default()
+            .into_iter()
+            .map(|r| r.value)
+            .collect();
+        let alias_target = record.alias_target.map(|a| a.dns_name);
+        let ip = values
+            .first()
+            .or(alias_target.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        Self {
+            dnsname: record.name.trim_end_matches('.').into(),
+            ip,
+            set_identifier: record.set_identifier,
+            routing,
+            health_check_id: record.health_check_id,
+            record_type: record.r#type.as_str().into(),
+            ttl: record.ttl,
+            values,
+            alias_target,
+        }
+    }
+
+    #[must_use]
+    pub fn is_a_record(&self) -> bool {
+        self.record_type == "A"
+    }
+}
+
+/// `records` of the zone `origin` as a BIND zone file, alias records have no
+/// BIND equivalent and are written as comments
+#[must_use]
+pub fn zone_file(zone_id: &str, origin: &str, records: &[DnsRecord]) -> StackString {
+    let origin = origin.trim_end_matches('.');
+    let mut buf = format_sstr!("; route53 hosted zone {zone_id}\n$ORIGIN {origin}.\n");
+    for record in records {
+        // route53 escapes the wildcard label
+        let name = record.dnsname.replace("\\052", "*");
+        let name = if name == origin {
+            "@".into()
+        } else {
+            format_sstr!("{name}.")
+        };
+        let record_type = &record.record_type;
+        if let Some(routing) = &record.routing {
+            let set_id = record.set_identifier.as_deref().unwrap_or("");
+            writeln!(buf, "; {routing} {set_id}").ok();
+        }
+        match (&record.alias_target, record.ttl) {
+            (Some(target), _) => {
+                writeln!(buf, "; {name} ALIAS {record_type} {target}").ok();
+            }
+            (None, ttl) => {
+                let ttl = ttl.unwrap_or(300);
+                for value in &record.values {
+                    writeln!(buf, "{name} {ttl} IN {record_type} {value}").ok();
+                }
+            }
+        }
+    }
+    buf
+}
+
+/// Routing policy of a record set, plain (simple) records have none
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum RecordRouting {
+    FailoverPrimary,
+    FailoverSecondary,
+    Weighted(i64),
+}
+
+impl fmt::Display for RecordRouting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::FailoverPrimary => f.write_str("failover primary"),
+            Self::FailoverSecondary => f.write_str("failover secondary"),
+            Self::Weighted(weight) => write!(f, "weight {weight}"),
+        }
+    }
+}
+
+impl RecordRouting {
+    fn from_record_set(record: &ResourceRecordSet) -> Option<Self> {
+        match (&record.failover, record.weight) {
+            (Some(ResourceRecordSetFailover::Primary), _) => Some(Self::FailoverPrimary),
+            (Some(ResourceRecordSetFailover::Secondary), _) => Some(Self::FailoverSecondary),
+            (_, Some(weight)) => Some(Self::Weighted(weight)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Serialize)]
+pub struct HealthCheckInfo {
+    pub id: StackString,
+    pub check_type: StackString,
+    pub ip_address: Option<StackString>,
+    pub fqdn: Option<StackString>,
+    pub port: Option<i32>,
+    pub resource_path: Option<StackString>,
+    /// Checkers reporting success out of all checkers, `None` until the
+    /// first observations come in
+    pub healthy_checkers: Option<(usize, usize)>,
+}
+
+impl HealthCheckInfo {
+    fn from_health_check(check: HealthCheck) -> Self {
+        let config = check.health_check_config;
+        Self {
+            id: check.id.into(),
+            check_type: config.as_ref().map_or("", |c| c.r#type.as_str()).into(),
+            ip_address: config
+                .as_ref()
+                .and_then(|c| c.ip_address.as_deref())
+                .map(Into::into),
+            fqdn: config
+                .as_ref()
+                .and_then(|c| c.fully_qualified_domain_name.as_deref())
+                .map(Into::into),
+            port: config.as_ref().and_then(|c| c.port),
+            resource_path: config
+                .as_ref()
+                .and_then(|c| c.resource_path.as_deref())
+                .map(Into::into),
+            healthy_checkers: None,
+        }
+    }
+
+    /// Route53 considers an endpoint healthy when more than 18% of the
+    /// checkers report success
+    #[must_use]
+    pub fn is_healthy(&self) -> Option<bool> {
+        self.healthy_checkers
+            .filter(|(_, total)| *total > 0)
+            .map(|(healthy, total)| healthy * 100 > total * 18)
+    }
+
+    #[must_use]
+    pub fn status(&self) -> &'static str {
+        match self.is_healthy() {
+            Some(true) => "healthy",
+            Some(false) => "unhealthy",
+            None => "unknown",
+        }
+    }
+
+    #[must_use]
+    pub fn target(&self) -> StackString {
+        let host = self
+            .fqdn
+            .as_ref()
+            .or(self.ip_address.as_ref())
+            .map_or("", StackString::as_str);
+        let mut target: StackString = host.into();
+        if let Some(port) = self.port {
+            target.push_str(&format!(":{port}"));
+        }
+        if let Some(path) = &self.resource_path {
+            target.push_str(path);
+        }
+        target
+    }
+}
+
+/// An A record which is part of a failover or weighted set, records sharing
+/// `name` are told apart by `set_identifier`
+#[derive(Clone, Debug)]
+pub struct RoutingRecord {
+    pub name: StackString,
+    pub ip: Ipv4Addr,
+    pub set_identifier: StackString,
+    pub routing: RecordRouting,
+    pub health_check_id: Option<StackString>,
+    pub ttl: i64,
+}
+
+/// What a new health check probes, either `ip_address` or `fqdn` is required
+#[derive(Clone, Debug)]
+pub struct HealthCheckTarget {
+    pub check_type: HealthCheckType,
+    pub ip_address: Option<StackString>,
+    pub fqdn: Option<StackString>,
+    pub port: Option<i32>,
+    pub resource_path: Option<StackString>,
+}
+
+impl HealthCheckTarget {
+    /// `host` is either an ipv4 address or a domain name
+    /// # Errors
+    /// Returns error if `check_type` is not HTTP, HTTPS or TCP or `host` is
+    /// empty
+    pub fn new(
+        check_type: &str,
+        host: &str,
+        port: Option<i32>,
+        resource_path: Option<&str>,
+    ) -> Result<Self, Error> {
+        let check_type = match check_type.to_uppercase().as_str() {
+            "HTTP" => HealthCheckType::Http,
+            "HTTPS" => HealthCheckType::Https,
+            "TCP" => HealthCheckType::Tcp,
+            _ => return Err(format_err!("Unsupported check type {check_type}")),
+        };
+        let host = host.trim();
+        if host.is_empty() {
+            return Err(format_err!(
+                "Health check needs an ip address or domain name"
+            ));
+        }
+        let (ip_address, fqdn) = if host.parse::<Ipv4Addr>().is_ok() {
+            (Some(host.into()), None)
+        } else {
+            (None, Some(host.into()))
+        };
+        let resource_path = resource_path
+            .map(str::trim)
+            .filter(|p| !p.is_empty() && check_type != HealthCheckType::Tcp)
+            .map(Into::into);
+        Ok(Self {
+            check_type,
+            ip_address,
+            fqdn,
+            port,
+            resource_path,
+        })
+    }
 }
 
 impl Route53Instance {
@@ -38,8 +335,13 @@ impl Route53Instance {
     pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
         let region: String = region.as_ref().into();
         let region = Region::new(region);
-        let sdk_config = aws_config::from_env().region(region).load().await;
-        self.route53_client = Route53Client::from_conf((&sdk_config).into());
+        let conf = self
+            .route53_client
+            .config()
+            .to_builder()
+            .region(region)
+            .build();
+        self.route53_client = Route53Client::from_conf(conf);
         Ok(())
     }
 
@@ -60,32 +362,56 @@ impl Route53Instance {
         &self,
         id: impl Into<String>,
     ) -> Result<Vec<ResourceRecordSet>, Error> {
-        self.route53_client
-            .list_resource_record_sets()
-            .hosted_zone_id(id)
-            .send()
-            .await
-            .map_err(Into::into)
-            .map(|r| r.resource_record_sets)
+        let id = id.into();
+        let mut record_sets = Vec::new();
+        let mut start = (None, None, None);
+        loop {
+            let (name, record_type, identifier) = start;
+            let result = self
+                .route53_client
+                .list_resource_record_sets()
+                .hosted_zone_id(&id)
+                .set_start_record_name(name)
+                .set_start_record_type(record_type)
+                .set_start_record_identifier(identifier)
+                .send()
+                .await?;
+            record_sets.extend(result.resource_record_sets);
+            if !result.is_truncated {
+                break;
+            }
+            start = (
+                result.next_record_name,
+                result.next_record_type,
+                result.next_record_identifier,
+            );
+        }
+        Ok(record_sets)
     }
 
+    /// Every record set of the zone, not only `A` records
     /// # Errors
     /// Returns error if aws api fails
     pub async fn list_dns_records(&self, id: impl Into<String>) -> Result<Vec<DnsRecord>, Error> {
-        self.list_record_sets(id).await.map(|result| {
-            result
-                .into_iter()
-                .filter_map(|record| {
-                    if record.r#type == RrType::A {
-                        let dnsname = record.name.trim_end_matches('.').into();
-                        let ip = record.resource_records?.pop()?.value().into();
-                        Some(DnsRecord { dnsname, ip })
-                    } else {
-                        None
-                    }
-                })
-                .collect()
-        })
+        self.list_record_sets(id)
+            .await
+            .map(|result| result.into_iter().map(DnsRecord::from_record_set).collect())
+    }
+
+    /// The hosted zone `zone_id` as a BIND zone file
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn export_zone(&self, zone_id: &str) -> Result<StackString, Error> {
+        let zone = self
+            .route53_client
+            .get_hosted_zone()
+            .id(zone_id)
+            .send()
+            .await?
+            .hosted_zone
+            .ok_or_else(|| format_err!("No hosted zone {zone_id}"))?;
+        let records = self.list_dns_records(zone_id).await?;
+        Ok(zone_file(zone_id, &zone.name, &records))
     }
 
     /// # Errors
@@ -122,12 +448,24 @@ impl Route53Instance {
         }
         let old_ip = old_ip.to_string();
         let new_ip = new_ip.to_string();
-        let mut record = self
+        let mut records: Vec<_> = self
             .list_record_sets(zone_id)
             .await?
             .into_iter()
-            .find(|r| r.r#type == RrType::A && r.name == name)
-            .ok_or_else(|| format_err!("No record found"))?;
+            .filter(|r| r.r#type == RrType::A && r.name == name)
+            .collect();
+        // a failover or weighted set has several records with the same name
+        let has_old_ip = |r: &ResourceRecordSet| {
+            r.resource_records
+                .as_ref()
+                .and_then(|v| v.first())
+                .map_or(false, |v| v.value == old_ip)
+        };
+        if records.is_empty() {
+            return Err(format_err!("No record found"));
+        }
+        let idx = records.iter().position(has_old_ip).unwrap_or(0);
+        let mut record = records.swap_remove(idx);
 
         let value = record
             .resource_records
@@ -164,6 +502,224 @@ impl Route53Instance {
         Ok(())
     }
 
+    /// Create `name` as an `A` record pointing at `ip`, or repoint it if it
+    /// exists
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn upsert_a_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        ip: Ipv4Addr,
+        ttl: i64,
+    ) -> Result<(), Error> {
+        let record_set = ResourceRecordSet::builder()
+            .name(name)
+            .r#type(RrType::A)
+            .ttl(ttl)
+            .resource_records(ResourceRecord::builder().value(ip.to_string()).build()?)
+            .build()?;
+        let change_batch = ChangeBatch::builder()
+            .comment(format!("point {name} at {ip}"))
+            .changes(
+                Change::builder()
+                    .action(ChangeAction::Upsert)
+                    .resource_record_set(record_set)
+                    .build()?,
+            )
+            .build()?;
+        self.route53_client
+            .change_resource_record_sets()
+            .hosted_zone_id(zone_id)
+            .change_batch(change_batch)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Delete the record set `record` was read from, matched on name, type
+    /// and set identifier
+    /// # Errors
+    /// Returns error if aws api fails or the record no longer exists
+    pub async fn delete_dns_record(&self, zone_id: &str, record: &DnsRecord) -> Result<(), Error> {
+        let record_set = self
+            .list_record_sets(zone_id)
+            .await?
+            .into_iter()
+            .find(|r| {
+                r.name.trim_end_matches('.') == record.dnsname
+                    && r.r#type.as_str() == record.record_type
+                    && r.set_identifier == record.set_identifier
+            })
+            .ok_or_else(|| {
+                format_err!("No {} record {} found", record.record_type, record.dnsname)
+            })?;
+        let change_batch = ChangeBatch::builder()
+            .comment(format!(
+                "delete {} record {}",
+                record.record_type, record.dnsname
+            ))
+            .changes(
+                Change::builder()
+                    .action(ChangeAction::Delete)
+                    .resource_record_set(record_set)
+                    .build()?,
+            )
+            .build()?;
+        self.route53_client
+            .change_resource_record_sets()
+            .hosted_zone_id(zone_id)
+            .change_batch(change_batch)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn create_routing_record(
+        &self,
+        zone_id: &str,
+        record: RoutingRecord,
+    ) -> Result<(), Error> {
+        let RoutingRecord {
+            name,
+            ip,
+            set_identifier,
+            routing,
+            health_check_id,
+            ttl,
+        } = record;
+        let mut builder = ResourceRecordSet::builder()
+            .name(name.as_str())
+            .r#type(RrType::A)
+            .ttl(ttl)
+            .set_identifier(set_identifier.as_str())
+            .resource_records(ResourceRecord::builder().value(ip.to_string()).build()?)
+            .set_health_check_id(health_check_id.map(Into::into));
+        builder = match routing {
+            RecordRouting::FailoverPrimary => builder.failover(ResourceRecordSetFailover::Primary),
+            RecordRouting::FailoverSecondary => {
+                builder.failover(ResourceRecordSetFailover::Secondary)
+            }
+            RecordRouting::Weighted(weight) => builder.weight(weight),
+        };
+        let change_batch = ChangeBatch::builder()
+            .comment(format!("{routing} record {set_identifier} for {name}"))
+            .changes(
+                Change::builder()
+                    .action(ChangeAction::Upsert)
+                    .resource_record_set(builder.build()?)
+                    .build()?,
+            )
+            .build()?;
+        self.route53_client
+            .change_resource_record_sets()
+            .hosted_zone_id(zone_id)
+            .change_batch(change_batch)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Health checks along with their current status
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn list_health_checks(&self) -> Result<Vec<HealthCheckInfo>, Error> {
+        let mut checks = Vec::new();
+        let mut marker = None;
+        loop {
+            let result = self
+                .route53_client
+                .list_health_checks()
+                .set_marker(marker)
+                .send()
+                .await?;
+            checks.extend(
+                result
+                    .health_checks
+                    .into_iter()
+                    .map(HealthCheckInfo::from_health_check),
+            );
+            marker = result.next_marker;
+            if !result.is_truncated || marker.is_none() {
+                break;
+            }
+        }
+        let futures = checks.into_iter().map(|mut check| async move {
+            check.healthy_checkers = self.get_health_check_status(&check.id).await?;
+            Ok::<_, Error>(check)
+        });
+        try_join_all(futures).await
+    }
+
+    /// Number of checkers reporting success and the total number of checkers
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn get_health_check_status(&self, id: &str) -> Result<Option<(usize, usize)>, Error> {
+        let observations = self
+            .route53_client
+            .get_health_check_status()
+            .health_check_id(id)
+            .send()
+            .await?
+            .health_check_observations;
+        if observations.is_empty() {
+            return Ok(None);
+        }
+        let healthy = observations
+            .iter()
+            .filter(|o| {
+                o.status_report
+                    .as_ref()
+                    .and_then(|r| r.status.as_deref())
+                    .map_or(false, |s| s.starts_with("Success"))
+            })
+            .count();
+        Ok(Some((healthy, observations.len())))
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn create_health_check(
+        &self,
+        target: HealthCheckTarget,
+    ) -> Result<StackString, Error> {
+        if target.ip_address.is_none() && target.fqdn.is_none() {
+            return Err(format_err!(
+                "Health check needs an ip address or domain name"
+            ));
+        }
+        let config = HealthCheckConfig::builder()
+            .r#type(target.check_type)
+            .set_ip_address(target.ip_address.map(Into::into))
+            .set_fully_qualified_domain_name(target.fqdn.map(Into::into))
+            .set_port(target.port)
+            .set_resource_path(target.resource_path.map(Into::into))
+            .build()?;
+        let check = self
+            .route53_client
+            .create_health_check()
+            .caller_reference(Uuid::new_v4().to_string())
+            .health_check_config(config)
+            .send()
+            .await?
+            .health_check
+            .ok_or_else(|| format_err!("No health check returned"))?;
+        Ok(check.id.into())
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn delete_health_check(&self, id: &str) -> Result<(), Error> {
+        self.route53_client
+            .delete_health_check()
+            .health_check_id(id)
+            .send()
+            .await?;
+        Ok(())
+    }
+
     /// # Errors
     /// Returns error if aws api fails
     pub async fn get_ip_address(&self) -> Result<Ipv4Addr, Error> {
@@ -183,10 +739,130 @@ mod tests {
     use std::collections::HashMap;
 
     use crate::{
+        aws_api::{MockAws, Route53Api},
         config::Config,
-        route53_instance::{DnsRecord, Route53Instance},
+        route53_instance::{
+            hosted_zone_for, validate_host_name, zone_file, DnsRecord, HealthCheckInfo,
+            HealthCheckTarget, RecordRouting, Route53Instance,
+        },
     };
 
+    fn record(dnsname: &str, record_type: &str, values: &[&str]) -> DnsRecord {
+        DnsRecord {
+            dnsname: dnsname.into(),
+            ip: values.first().map_or_else(String::new, |v| (*v).into()),
+            set_identifier: None,
+            routing: None,
+            health_check_id: None,
+            record_type: record_type.into(),
+            ttl: Some(300),
+            values: values.iter().map(|v| (*v).into()).collect(),
+            alias_target: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_host_name() {
+        assert!(validate_host_name("build.example.com").is_ok());
+        assert!(validate_host_name("build-2.example.com.").is_ok());
+        assert!(validate_host_name("build").is_err());
+        assert!(validate_host_name("build..example.com").is_err());
+        assert!(validate_host_name("-build.example.com").is_err());
+        assert!(validate_host_name("build_2.example.com").is_err());
+    }
+
+    #[test]
+    fn test_hosted_zone_for() {
+        let zones = [
+            ("/hostedzone/Z1", "example.com."),
+            ("/hostedzone/Z2", "dev.example.com."),
+            ("/hostedzone/Z3", "ample.com."),
+        ];
+        assert_eq!(
+            hosted_zone_for("build.example.com", zones.iter().copied()),
+            Some("/hostedzone/Z1")
+        );
+        assert_eq!(
+            hosted_zone_for("Build.Dev.example.com.", zones.iter().copied()),
+            Some("/hostedzone/Z2")
+        );
+        assert_eq!(
+            hosted_zone_for("example.com", zones.iter().copied()),
+            Some("/hostedzone/Z1")
+        );
+        assert_eq!(hosted_zone_for("example.org", zones.iter().copied()), None);
+    }
+
+    #[test]
+    fn test_zone_file() {
+        let mut alias = record("cdn.example.com", "A", &[]);
+        alias.ttl = None;
+        alias.alias_target = Some("d111111abcdef8.cloudfront.net.".into());
+        let records = [
+            record(
+                "example.com",
+                "MX",
+                &["10 mail.example.com.", "20 mx2.example.com."],
+            ),
+            record("\\052.example.com", "CNAME", &["example.com."]),
+            record("www.example.com", "A", &["192.0.2.10"]),
+            alias,
+        ];
+        let zone = zone_file("Z0EXAMPLE", "example.com.", &records);
+        let lines: Vec<_> = zone.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "; route53 hosted zone Z0EXAMPLE",
+                "$ORIGIN example.com.",
+                "@ 300 IN MX 10 mail.example.com.",
+                "@ 300 IN MX 20 mx2.example.com.",
+                "*.example.com. 300 IN CNAME example.com.",
+                "www.example.com. 300 IN A 192.0.2.10",
+                "; cdn.example.com. ALIAS A d111111abcdef8.cloudfront.net.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_health_check_target() -> Result<(), Error> {
+        let target = HealthCheckTarget::new("https", "192.0.2.1", Some(443), Some("/health"))?;
+        assert_eq!(target.ip_address.as_deref(), Some("192.0.2.1"));
+        assert_eq!(target.fqdn, None);
+        assert_eq!(target.resource_path.as_deref(), Some("/health"));
+        let target = HealthCheckTarget::new("TCP", "www.example.com", Some(22), Some("/"))?;
+        assert_eq!(target.fqdn.as_deref(), Some("www.example.com"));
+        assert_eq!(target.resource_path, None);
+        assert!(HealthCheckTarget::new("icmp", "www.example.com", None, None).is_err());
+        assert!(HealthCheckTarget::new("HTTP", " ", None, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_health_check_status() {
+        let mut check = HealthCheckInfo {
+            id: "abc".into(),
+            check_type: "HTTPS".into(),
+            ip_address: None,
+            fqdn: Some("www.example.com".into()),
+            port: Some(443),
+            resource_path: Some("/health".into()),
+            healthy_checkers: None,
+        };
+        assert_eq!(check.status(), "unknown");
+        assert_eq!(check.target(), "www.example.com:443/health");
+        check.healthy_checkers = Some((1, 16));
+        assert_eq!(check.status(), "unhealthy");
+        check.healthy_checkers = Some((15, 16));
+        assert_eq!(check.status(), "healthy");
+
+        assert_eq!(
+            RecordRouting::FailoverPrimary.to_string(),
+            "failover primary"
+        );
+        assert_eq!(RecordRouting::Weighted(10).to_string(), "weight 10");
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_route53_instance() -> Result<(), Error> {
@@ -209,34 +885,57 @@ mod tests {
         Ok(())
     }
 
-    #[tokio::test]
-    #[ignore]
-    async fn test_list_all_dns_records() -> Result<(), Error> {
-        let config = aws_config::load_from_env().await;
-        let r53 = Route53Instance::new(&config);
+    async fn check_list_all_dns_records(r53: &dyn Route53Api) -> Result<(), Error> {
         let result = r53.list_all_dns_records().await?;
         assert!(result.len() > 0);
         println!("{:?}", result);
         Ok(())
     }
 
-    #[tokio::test]
-    #[ignore]
-    async fn test_get_ip_address() -> Result<(), Error> {
-        let config = aws_config::load_from_env().await;
-        let r53 = Route53Instance::new(&config);
+    /// The record for `domain`, when there is one, points at this host
+    async fn check_get_ip_address(r53: &dyn Route53Api, domain: &str) -> Result<(), Error> {
         let ip = r53.get_ip_address().await?;
         let name_map: HashMap<_, _> = r53
             .list_all_dns_records()
             .await?
             .into_iter()
-            .map(|(_, DnsRecord { dnsname, ip })| (dnsname, ip))
+            .filter(|(_, record)| record.is_a_record())
+            .map(|(_, DnsRecord { dnsname, ip, .. })| (dnsname, ip))
             .collect();
+        if let Some(home_ip) = name_map.get(domain) {
+            assert_eq!(&ip.to_string(), home_ip);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_all_dns_records() -> Result<(), Error> {
+        let mock = MockAws::from_fixtures()?;
+        check_list_all_dns_records(&mock).await
+    }
+
+    #[tokio::test]
+    async fn test_get_ip_address() -> Result<(), Error> {
+        let mock = MockAws::from_fixtures()?;
+        check_get_ip_address(&mock, "www.example.com").await
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_list_all_dns_records_live() -> Result<(), Error> {
+        let config = aws_config::load_from_env().await;
+        let r53 = Route53Instance::new(&config);
+        check_list_all_dns_records(&r53).await
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_get_ip_address_live() -> Result<(), Error> {
+        let config = aws_config::load_from_env().await;
+        let r53 = Route53Instance::new(&config);
         let config = Config::init_config()?;
         if config.domain == "www.ddboline.net" || config.domain == "cloud.ddboline.net" {
-            if let Some(home_ip) = name_map.get(config.domain.as_str()) {
-                assert_eq!(&ip.to_string(), home_ip);
-            }
+            check_get_ip_address(&r53, &config.domain).await?;
         }
         Ok(())
     }
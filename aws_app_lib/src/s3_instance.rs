@@ -1,27 +1,80 @@
 use anyhow::{format_err, Error};
 use aws_config::SdkConfig;
 use aws_sdk_s3::{
+    error::ProvideErrorMetadata,
     operation::list_objects::ListObjectsOutput,
     primitives::ByteStream,
-    types::{Bucket, Object},
+    types::{Bucket, CompletedMultipartUpload, CompletedPart, Object},
     Client as S3Client,
 };
+use bytes::{Bytes, BytesMut};
+use futures::{pin_mut, stream, Stream, TryStreamExt};
+use log::warn;
 use once_cell::sync::Lazy;
 use parking_lot::{Mutex, MutexGuard};
 use std::{fmt, path::Path};
-use tokio::io::AsyncReadExt;
+use tokio::{fs::File, io::AsyncReadExt};
 use url::Url;
 
 static S3INSTANCE_TEST_MUTEX: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 
 use stack_string::StackString;
 
-use crate::exponential_retry;
+use crate::{
+    bucket_summary::{BucketUsage, LifecycleRuleInfo},
+    retry::{retry_with_policy, sdk_error, RetryPolicy},
+};
+
+/// Part size used when the length of an upload isn't known up front, which
+/// limits such uploads to about 156 GiB
+pub const MULTIPART_DEFAULT_PART_SIZE: u64 = 16 * 1024 * 1024;
+/// Most parts s3 accepts for a single upload
+pub const MULTIPART_MAX_PARTS: u64 = 10_000;
+/// Size of the reads when streaming a local file
+const FILE_READ_SIZE: usize = 1024 * 1024;
+
+/// Part size keeping an upload of `content_length` bytes within
+/// `MULTIPART_MAX_PARTS`, rounded up to a whole MiB
+#[must_use]
+pub fn multipart_part_size(content_length: Option<u64>) -> u64 {
+    const MIB: u64 = 1024 * 1024;
+    let needed = content_length.map_or(0, |len| {
+        let size = (len + MULTIPART_MAX_PARTS - 1) / MULTIPART_MAX_PARTS;
+        (size + MIB - 1) / MIB * MIB
+    });
+    needed.max(MULTIPART_DEFAULT_PART_SIZE)
+}
+
+/// State of a multipart upload, passed to the progress callback after every
+/// part
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UploadProgress {
+    pub bytes_uploaded: u64,
+    pub content_length: Option<u64>,
+    pub parts: i32,
+}
+
+impl fmt::Display for UploadProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let uploaded = self.bytes_uploaded as f64 / 1e6;
+        match self.content_length {
+            Some(total) if total > 0 => write!(
+                f,
+                "{uploaded:.1} of {:.1} MB ({:.0}%) in {} parts",
+                total as f64 / 1e6,
+                self.bytes_uploaded as f64 * 100.0 / total as f64,
+                self.parts
+            ),
+            _ => write!(f, "{uploaded:.1} MB in {} parts", self.parts),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct S3Instance {
     s3_client: S3Client,
     max_keys: Option<i32>,
+    retry_policy: RetryPolicy,
 }
 
 impl fmt::Debug for S3Instance {
@@ -36,6 +89,7 @@ impl S3Instance {
         Self {
             s3_client: S3Client::from_conf(sdk_config.into()),
             max_keys: None,
+            retry_policy: RetryPolicy::default(),
         }
     }
 
@@ -49,30 +103,41 @@ impl S3Instance {
         self
     }
 
+    #[must_use]
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
     /// # Errors
     /// Return error if db query fails
     pub async fn get_list_of_buckets(&self) -> Result<Vec<Bucket>, Error> {
-        exponential_retry(|| async move {
-            self.s3_client
-                .list_buckets()
-                .send()
-                .await
-                .map(|l| l.buckets.unwrap_or_default())
-                .map_err(Into::into)
-        })
+        retry_with_policy(
+            &self.retry_policy,
+            "s3.get_list_of_buckets",
+            || async move {
+                self.s3_client
+                    .list_buckets()
+                    .send()
+                    .await
+                    .map(|l| l.buckets.unwrap_or_default())
+                    .map_err(sdk_error)
+            },
+        )
         .await
     }
 
     /// # Errors
     /// Return error if db query fails
     pub async fn create_bucket(&self, bucket_name: &str) -> Result<String, Error> {
-        exponential_retry(|| async move {
+        retry_with_policy(&self.retry_policy, "s3.create_bucket", || async move {
             let location = self
                 .s3_client
                 .create_bucket()
                 .bucket(bucket_name)
                 .send()
-                .await?
+                .await
+                .map_err(sdk_error)?
                 .location
                 .ok_or_else(|| format_err!("Failed to create bucket"))?;
             Ok(location)
@@ -83,14 +148,14 @@ impl S3Instance {
     /// # Errors
     /// Return error if db query fails
     pub async fn delete_bucket(&self, bucket_name: &str) -> Result<(), Error> {
-        exponential_retry(|| async move {
+        retry_with_policy(&self.retry_policy, "s3.delete_bucket", || async move {
             self.s3_client
                 .delete_bucket()
                 .bucket(bucket_name)
                 .send()
                 .await
                 .map(|_| ())
-                .map_err(Into::into)
+                .map_err(sdk_error)
         })
         .await
     }
@@ -98,7 +163,7 @@ impl S3Instance {
     /// # Errors
     /// Return error if db query fails
     pub async fn delete_key(&self, bucket_name: &str, key_name: &str) -> Result<(), Error> {
-        exponential_retry(|| async move {
+        retry_with_policy(&self.retry_policy, "s3.delete_key", || async move {
             self.s3_client
                 .delete_object()
                 .bucket(bucket_name)
@@ -106,7 +171,7 @@ impl S3Instance {
                 .send()
                 .await
                 .map(|_| ())
-                .map_err(Into::into)
+                .map_err(sdk_error)
         })
         .await
     }
@@ -119,7 +184,7 @@ impl S3Instance {
         bucket_to: &str,
         key_to: &str,
     ) -> Result<Option<String>, Error> {
-        exponential_retry(|| {
+        retry_with_policy(&self.retry_policy, "s3.copy_key", || {
             let copy_source = source.to_string();
             async move {
                 self.s3_client
@@ -129,7 +194,7 @@ impl S3Instance {
                     .key(key_to)
                     .send()
                     .await
-                    .map_err(Into::into)
+                    .map_err(sdk_error)
             }
         })
         .await
@@ -147,7 +212,7 @@ impl S3Instance {
         if !fname.exists() {
             return Err(format_err!("File doesn't exist {fname:?}"));
         }
-        exponential_retry(|| async move {
+        retry_with_policy(&self.retry_policy, "s3.upload", || async move {
             let body = ByteStream::read_from().path(fname).build().await?;
             self.s3_client
                 .put_object()
@@ -157,7 +222,7 @@ impl S3Instance {
                 .send()
                 .await
                 .map(|_| ())
-                .map_err(Into::into)
+                .map_err(sdk_error)
         })
         .await
     }
@@ -170,14 +235,15 @@ impl S3Instance {
         key_name: &str,
         fname: &Path,
     ) -> Result<StackString, Error> {
-        exponential_retry(|| async move {
+        retry_with_policy(&self.retry_policy, "s3.download", || async move {
             let resp = self
                 .s3_client
                 .get_object()
                 .bucket(bucket_name)
                 .key(key_name)
                 .send()
-                .await?;
+                .await
+                .map_err(sdk_error)?;
             let etag = resp
                 .e_tag
                 .ok_or_else(|| format_err!("No etag"))?
@@ -210,7 +276,7 @@ impl S3Instance {
         if let Some(max_keys) = max_keys {
             builder = builder.max_keys(max_keys);
         }
-        builder.send().await.map_err(Into::into)
+        builder.send().await.map_err(sdk_error)
     }
 
     /// # Errors
@@ -220,7 +286,7 @@ impl S3Instance {
         bucket: &str,
         prefix: Option<&str>,
     ) -> Result<Vec<Object>, Error> {
-        exponential_retry(|| async move {
+        retry_with_policy(&self.retry_policy, "s3.get_list_of_keys", || async move {
             let mut marker: Option<String> = None;
             let mut list_of_keys = Vec::new();
             let mut max_keys = self.max_keys;
@@ -289,6 +355,371 @@ impl S3Instance {
         Ok(())
     }
 
+    /// Rules of the bucket lifecycle configuration, empty if the bucket has
+    /// none
+    /// # Errors
+    /// Return error if s3 api fails
+    pub async fn get_bucket_lifecycle(
+        &self,
+        bucket: &str,
+    ) -> Result<Vec<LifecycleRuleInfo>, Error> {
+        retry_with_policy(
+            &self.retry_policy,
+            "s3.get_bucket_lifecycle",
+            || async move {
+                let output = match self
+                    .s3_client
+                    .get_bucket_lifecycle_configuration()
+                    .bucket(bucket)
+                    .send()
+                    .await
+                {
+                    Ok(output) => output,
+                    Err(e) if e.code() == Some("NoSuchLifecycleConfiguration") => {
+                        return Ok(Vec::new())
+                    }
+                    Err(e) => return Err(sdk_error(e)),
+                };
+                let rules = output
+                    .rules
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|rule| LifecycleRuleInfo {
+                        id: rule.id.unwrap_or_default().into(),
+                        enabled: rule.status.as_str() == "Enabled",
+                        expiration_days: rule.expiration.and_then(|e| e.days),
+                        transitions: rule
+                            .transitions
+                            .unwrap_or_default()
+                            .into_iter()
+                            .filter_map(|t| {
+                                let storage_class = t.storage_class?;
+                                Some((storage_class.as_str().into(), t.days.unwrap_or(0)))
+                            })
+                            .collect(),
+                    })
+                    .collect();
+                Ok(rules)
+            },
+        )
+        .await
+    }
+
+    /// Default server side encryption algorithm of the bucket, `None` if
+    /// default encryption isn't configured
+    /// # Errors
+    /// Return error if s3 api fails
+    pub async fn get_bucket_encryption(&self, bucket: &str) -> Result<Option<StackString>, Error> {
+        retry_with_policy(
+            &self.retry_policy,
+            "s3.get_bucket_encryption",
+            || async move {
+                let output = match self
+                    .s3_client
+                    .get_bucket_encryption()
+                    .bucket(bucket)
+                    .send()
+                    .await
+                {
+                    Ok(output) => output,
+                    Err(e)
+                        if e.code() == Some("ServerSideEncryptionConfigurationNotFoundError") =>
+                    {
+                        return Ok(None)
+                    }
+                    Err(e) => return Err(sdk_error(e)),
+                };
+                let algorithm = output
+                    .server_side_encryption_configuration
+                    .and_then(|c| {
+                        c.rules
+                            .into_iter()
+                            .find_map(|r| r.apply_server_side_encryption_by_default)
+                    })
+                    .map(|d| d.sse_algorithm.as_str().into());
+                Ok(algorithm)
+            },
+        )
+        .await
+    }
+
+    /// Count and size of every object in the bucket, this lists the whole
+    /// bucket so can be slow for large buckets
+    /// # Errors
+    /// Return error if s3 api fails
+    pub async fn get_bucket_usage(&self, bucket: &str) -> Result<BucketUsage, Error> {
+        let mut marker: Option<String> = None;
+        let mut usage = BucketUsage::default();
+        loop {
+            let mut output = retry_with_policy(&self.retry_policy, "s3.get_bucket_usage", || {
+                self.list_keys(bucket, None, marker.as_ref(), None)
+            })
+            .await?;
+            if let Some(contents) = output.contents.take() {
+                if let Some(key) = contents.last().and_then(|last| last.key.as_ref()) {
+                    marker.replace(key.into());
+                }
+                for object in &contents {
+                    usage.object_count += 1;
+                    usage.size_bytes += object.size.unwrap_or(0).max(0) as u64;
+                }
+            }
+            if output.is_truncated != Some(true) || marker.is_none() {
+                break;
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Stream `body` to `key_name` with the multipart upload api, buffering
+    /// one part at a time. The upload is aborted if any part fails so no
+    /// orphaned parts are left behind.
+    /// # Errors
+    /// Return error if reading `body` or the s3 api fails
+    pub async fn multipart_upload<S, F>(
+        &self,
+        bucket_name: &str,
+        key_name: &str,
+        content_length: Option<u64>,
+        body: S,
+        progress: F,
+    ) -> Result<UploadProgress, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>>,
+        F: Fn(&UploadProgress),
+    {
+        let upload_id = retry_with_policy(
+            &self.retry_policy,
+            "s3.create_multipart_upload",
+            || async move {
+                self.s3_client
+                    .create_multipart_upload()
+                    .bucket(bucket_name)
+                    .key(key_name)
+                    .send()
+                    .await
+                    .map_err(sdk_error)?
+                    .upload_id
+                    .ok_or_else(|| format_err!("No upload id"))
+            },
+        )
+        .await?;
+        let result = self
+            .upload_parts(
+                bucket_name,
+                key_name,
+                &upload_id,
+                content_length,
+                body,
+                progress,
+            )
+            .await;
+        if result.is_err() {
+            if let Err(e) = self
+                .s3_client
+                .abort_multipart_upload()
+                .bucket(bucket_name)
+                .key(key_name)
+                .upload_id(&upload_id)
+                .send()
+                .await
+            {
+                warn!("failed to abort upload {upload_id} of {key_name}: {e}");
+            }
+        }
+        result
+    }
+
+    async fn upload_parts<S, F>(
+        &self,
+        bucket_name: &str,
+        key_name: &str,
+        upload_id: &str,
+        content_length: Option<u64>,
+        body: S,
+        progress: F,
+    ) -> Result<UploadProgress, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>>,
+        F: Fn(&UploadProgress),
+    {
+        pin_mut!(body);
+        let part_size = multipart_part_size(content_length) as usize;
+        let mut buffer = BytesMut::with_capacity(part_size);
+        let mut parts = Vec::new();
+        let mut status = UploadProgress {
+            content_length,
+            ..UploadProgress::default()
+        };
+        let mut finished = false;
+        while !finished {
+            match body.try_next().await? {
+                Some(chunk) => buffer.extend_from_slice(&chunk),
+                None => finished = true,
+            }
+            // an empty body is still uploaded as a single empty part
+            while buffer.len() >= part_size
+                || (finished && (!buffer.is_empty() || parts.is_empty()))
+            {
+                let part_number = status.parts + 1;
+                if part_number as u64 > MULTIPART_MAX_PARTS {
+                    return Err(format_err!(
+                        "upload exceeds {MULTIPART_MAX_PARTS} parts of {part_size} bytes"
+                    ));
+                }
+                let data = buffer.split_to(buffer.len().min(part_size)).freeze();
+                let length = data.len();
+                let e_tag = retry_with_policy(&self.retry_policy, "s3.upload_part", || {
+                    let data = data.clone();
+                    async move {
+                        self.s3_client
+                            .upload_part()
+                            .bucket(bucket_name)
+                            .key(key_name)
+                            .upload_id(upload_id)
+                            .part_number(part_number)
+                            .content_length(length as i64)
+                            .body(ByteStream::from(data))
+                            .send()
+                            .await
+                            .map_err(sdk_error)?
+                            .e_tag
+                            .ok_or_else(|| format_err!("No etag for part {part_number}"))
+                    }
+                })
+                .await?;
+                parts.push(
+                    CompletedPart::builder()
+                        .e_tag(e_tag)
+                        .part_number(part_number)
+                        .build(),
+                );
+                status.parts = part_number;
+                status.bytes_uploaded += length as u64;
+                progress(&status);
+            }
+        }
+        let upload = CompletedMultipartUpload::builder()
+            .set_parts(Some(parts))
+            .build();
+        retry_with_policy(&self.retry_policy, "s3.complete_multipart_upload", || {
+            let upload = upload.clone();
+            async move {
+                self.s3_client
+                    .complete_multipart_upload()
+                    .bucket(bucket_name)
+                    .key(key_name)
+                    .upload_id(upload_id)
+                    .multipart_upload(upload)
+                    .send()
+                    .await
+                    .map(|_| ())
+                    .map_err(sdk_error)
+            }
+        })
+        .await?;
+        Ok(status)
+    }
+
+    /// Multipart upload of a local file, for files too large for `upload`
+    /// (over 5 GB)
+    /// # Errors
+    /// Return error if the file can't be read or the s3 api fails
+    pub async fn upload_file_multipart<F>(
+        &self,
+        fname: &Path,
+        bucket_name: &str,
+        key_name: &str,
+        progress: F,
+    ) -> Result<UploadProgress, Error>
+    where
+        F: Fn(&UploadProgress),
+    {
+        let file = File::open(fname)
+            .await
+            .map_err(|e| format_err!("{}: {e}", fname.display()))?;
+        let content_length = file.metadata().await?.len();
+        let body = stream::try_unfold(file, |mut file| async move {
+            let mut buf = BytesMut::with_capacity(FILE_READ_SIZE);
+            if file.read_buf(&mut buf).await? == 0 {
+                Ok::<_, Error>(None)
+            } else {
+                Ok(Some((buf.freeze(), file)))
+            }
+        });
+        self.multipart_upload(bucket_name, key_name, Some(content_length), body, progress)
+            .await
+    }
+
+    /// # Errors
+    /// Return error if s3 api fails
+    pub async fn upload_bytes(
+        &self,
+        data: Bytes,
+        bucket_name: &str,
+        key_name: &str,
+    ) -> Result<(), Error> {
+        retry_with_policy(&self.retry_policy, "s3.upload_bytes", || {
+            let data = data.clone();
+            async move {
+                self.s3_client
+                    .put_object()
+                    .bucket(bucket_name)
+                    .key(key_name)
+                    .body(ByteStream::from(data))
+                    .send()
+                    .await
+                    .map(|_| ())
+                    .map_err(sdk_error)
+            }
+        })
+        .await
+    }
+
+    /// # Errors
+    /// Return error if s3 api fails
+    pub async fn download_to_bytes(
+        &self,
+        bucket_name: &str,
+        key_name: &str,
+    ) -> Result<Bytes, Error> {
+        retry_with_policy(&self.retry_policy, "s3.download_to_bytes", || async move {
+            let resp = self
+                .s3_client
+                .get_object()
+                .bucket(bucket_name)
+                .key(key_name)
+                .send()
+                .await
+                .map_err(sdk_error)?;
+            let data = resp.body.collect().await?;
+            Ok(data.into_bytes())
+        })
+        .await
+    }
+
+    /// Body of `key_name` as a stream, along with its length if known
+    /// # Errors
+    /// Return error if s3 api fails
+    pub async fn download_stream(
+        &self,
+        bucket_name: &str,
+        key_name: &str,
+    ) -> Result<(Option<i64>, ByteStream), Error> {
+        retry_with_policy(&self.retry_policy, "s3.download_stream", || async move {
+            let resp = self
+                .s3_client
+                .get_object()
+                .bucket(bucket_name)
+                .key(key_name)
+                .send()
+                .await
+                .map_err(sdk_error)?;
+            Ok((resp.content_length, resp.body))
+        })
+        .await
+    }
+
     /// # Errors
     /// Return error if s3 api fails
     pub async fn download_to_string(
@@ -296,14 +727,15 @@ impl S3Instance {
         bucket_name: &str,
         key_name: &str,
     ) -> Result<String, Error> {
-        exponential_retry(|| async move {
+        retry_with_policy(&self.retry_policy, "s3.download_to_string", || async move {
             let resp = self
                 .s3_client
                 .get_object()
                 .bucket(bucket_name)
                 .key(key_name)
                 .send()
-                .await?;
+                .await
+                .map_err(sdk_error)?;
             let mut buf = String::new();
             resp.body.into_async_read().read_to_string(&mut buf).await?;
             Ok(buf)
@@ -311,3 +743,40 @@ impl S3Instance {
         .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::s3_instance::{
+        multipart_part_size, UploadProgress, MULTIPART_DEFAULT_PART_SIZE, MULTIPART_MAX_PARTS,
+    };
+
+    #[test]
+    fn test_multipart_part_size() {
+        assert_eq!(multipart_part_size(None), MULTIPART_DEFAULT_PART_SIZE);
+        assert_eq!(multipart_part_size(Some(0)), MULTIPART_DEFAULT_PART_SIZE);
+        assert_eq!(
+            multipart_part_size(Some(1_000_000_000)),
+            MULTIPART_DEFAULT_PART_SIZE
+        );
+        let five_tb = 5 * 1024 * 1024 * 1024 * 1024;
+        let part_size = multipart_part_size(Some(five_tb));
+        assert_eq!(part_size % (1024 * 1024), 0);
+        assert!(part_size * MULTIPART_MAX_PARTS >= five_tb);
+        assert!((part_size - 1024 * 1024) * MULTIPART_MAX_PARTS < five_tb);
+    }
+
+    #[test]
+    fn test_upload_progress() {
+        let progress = UploadProgress {
+            bytes_uploaded: 25_000_000,
+            content_length: Some(100_000_000),
+            parts: 2,
+        };
+        assert_eq!(progress.to_string(), "25.0 of 100.0 MB (25%) in 2 parts");
+        let progress = UploadProgress {
+            content_length: None,
+            ..progress
+        };
+        assert_eq!(progress.to_string(), "25.0 MB in 2 parts");
+    }
+}
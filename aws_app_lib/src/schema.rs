@@ -0,0 +1,177 @@
+use anyhow::{format_err, Error};
+use refinery::{embed_migrations, Migration};
+use serde::Serialize;
+use stack_string::{format_sstr, StackString};
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use crate::{date_time_wrapper::DateTimeWrapper, pgpool::PgPool};
+
+embed_migrations!("../migrations");
+
+/// Table refinery records applied migrations in
+pub const SCHEMA_HISTORY_TABLE: &str = "refinery_schema_history";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MigrateAction {
+    #[default]
+    Run,
+    Pending,
+    Info,
+}
+
+impl MigrateAction {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Run => "run",
+            Self::Pending => "pending",
+            Self::Info => "info",
+        }
+    }
+}
+
+impl fmt::Display for MigrateAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for MigrateAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "run" => Ok(Self::Run),
+            "pending" => Ok(Self::Pending),
+            "info" => Ok(Self::Info),
+            _ => Err(format_err!("{} is not a MigrateAction", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationInfo {
+    pub version: i64,
+    pub name: StackString,
+    pub applied_on: Option<DateTimeWrapper>,
+}
+
+impl From<&Migration> for MigrationInfo {
+    fn from(migration: &Migration) -> Self {
+        Self {
+            version: i64::from(migration.version()),
+            name: migration.name().into(),
+            applied_on: migration.applied_on().copied().map(Into::into),
+        }
+    }
+}
+
+impl fmt::Display for MigrationInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "V{:02}__{}", self.version, self.name)?;
+        if let Some(applied_on) = &self.applied_on {
+            write!(f, " applied {applied_on}")?;
+        }
+        Ok(())
+    }
+}
+
+impl MigrationInfo {
+    /// Refinery migrations are forward only, this describes how to undo one
+    /// by hand
+    #[must_use]
+    pub fn rollback_hint(&self) -> StackString {
+        format_sstr!(
+            "to roll back {self} revert the changes in migrations/V{:02}__{}.sql by hand, then \
+             run `DELETE FROM {SCHEMA_HISTORY_TABLE} WHERE version = {}`",
+            self.version,
+            self.name,
+            self.version,
+        )
+    }
+}
+
+/// Apply all pending migrations, returns the ones that were applied
+/// # Errors
+/// Returns error if a migration fails
+pub async fn run_migrations(pool: &PgPool) -> Result<Vec<MigrationInfo>, Error> {
+    let mut client = pool.get().await?;
+    let report = migrations::runner().run_async(&mut **client).await?;
+    Ok(report.applied_migrations().iter().map(Into::into).collect())
+}
+
+/// # Errors
+/// Returns error if db query fails
+pub async fn applied_migrations(pool: &PgPool) -> Result<Vec<MigrationInfo>, Error> {
+    let mut client = pool.get().await?;
+    let applied = migrations::runner()
+        .get_applied_migrations_async(&mut **client)
+        .await?;
+    Ok(applied.iter().map(Into::into).collect())
+}
+
+/// Embedded migrations which have not been applied to the database yet
+/// # Errors
+/// Returns error if db query fails
+pub async fn pending_migrations(pool: &PgPool) -> Result<Vec<MigrationInfo>, Error> {
+    let applied: HashSet<_> = applied_migrations(pool)
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect();
+    Ok(migrations::runner()
+        .get_migrations()
+        .iter()
+        .map(MigrationInfo::from)
+        .filter(|m| !applied.contains(&m.version))
+        .collect())
+}
+
+/// Most recently applied migration, `None` for an empty database
+/// # Errors
+/// Returns error if db query fails
+pub async fn schema_version(pool: &PgPool) -> Result<Option<MigrationInfo>, Error> {
+    let mut client = pool.get().await?;
+    let last = migrations::runner()
+        .get_last_applied_migration_async(&mut **client)
+        .await?;
+    Ok(last.as_ref().map(Into::into))
+}
+
+/// Latest migration embedded in this binary
+#[must_use]
+pub fn latest_migration() -> Option<MigrationInfo> {
+    migrations::runner()
+        .get_migrations()
+        .iter()
+        .map(MigrationInfo::from)
+        .max_by_key(|m| m.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::schema::{latest_migration, MigrateAction};
+
+    #[test]
+    fn test_migrate_action() -> Result<(), Error> {
+        for action in [
+            MigrateAction::Run,
+            MigrateAction::Pending,
+            MigrateAction::Info,
+        ] {
+            assert_eq!(action.to_str().parse::<MigrateAction>()?, action);
+        }
+        assert!("rollback".parse::<MigrateAction>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_latest_migration() {
+        let latest = latest_migration().expect("no embedded migrations");
+        assert!(latest.version >= 13);
+        let hint = latest.rollback_hint();
+        assert!(hint.contains(&format!("WHERE version = {}", latest.version)));
+    }
+}
@@ -12,7 +12,7 @@ use std::collections::HashMap;
 
 use crate::{
     models::{AwsGeneration, InstanceFamily, InstanceList},
-    pgpool::PgPool,
+    storage::{InstanceFamilyRepo, InstanceListRepo, Storage},
 };
 
 /// # Errors
@@ -30,12 +30,12 @@ pub fn get_url(generation: AwsGeneration) -> Result<Url, Error> {
 /// Returns error if api call fails
 pub async fn scrape_instance_info(
     generation: AwsGeneration,
-    pool: &PgPool,
+    storage: &dyn Storage,
 ) -> Result<Vec<StackString>, Error> {
     let url = get_url(generation)?;
     let body = reqwest::get(url).await?.text().await?;
     let (families, types) = parse_result(&body, generation)?;
-    insert_result(families, types, pool).await
+    insert_result(families, types, storage).await
 }
 
 fn parse_result(
@@ -115,15 +115,15 @@ fn parse_result(
     Ok((instance_families, instance_types))
 }
 
-async fn insert_result(
+pub(crate) async fn insert_result(
     instance_families: Vec<InstanceFamily>,
     instance_types: Vec<InstanceList>,
-    pool: &PgPool,
+    storage: &dyn Storage,
 ) -> Result<Vec<StackString>, Error> {
     let futures: FuturesUnordered<_> = instance_families
         .into_iter()
         .map(|t| async move {
-            if t.upsert_entry(pool).await?.is_some() {
+            if storage.upsert_instance_family(&t).await?.is_some() {
                 Ok(Some(format_sstr!("{t:?}")))
             } else {
                 Ok(None)
@@ -134,7 +134,7 @@ async fn insert_result(
     let futures: FuturesUnordered<_> = instance_types
         .into_iter()
         .map(|t| async move {
-            if t.upsert_entry(pool).await?.is_some() {
+            if storage.upsert_instance(&t).await?.is_some() {
                 Ok(Some(format_sstr!("{t:?}")))
             } else {
                 Ok(None)
@@ -146,24 +146,125 @@ async fn insert_result(
     Ok(output)
 }
 
+/// Accelerator column headers and the kind of accelerator they count
+const ACCELERATOR_COLUMNS: [(&str, &str); 3] = [("GPUs", "GPU"), ("GPU", "GPU"), ("FPGAs", "FPGA")];
+
+/// Accelerator models by instance family, the scraped tables only give counts
+const ACCELERATOR_MODELS: [(&str, &str); 16] = [
+    ("dl1", "Habana Gaudi"),
+    ("f1", "Xilinx UltraScale+ VU9P"),
+    ("g3", "NVIDIA M60"),
+    ("g3s", "NVIDIA M60"),
+    ("g4ad", "AMD Radeon Pro V520"),
+    ("g4dn", "NVIDIA T4"),
+    ("g5", "NVIDIA A10G"),
+    ("g5g", "NVIDIA T4G"),
+    ("g6", "NVIDIA L4"),
+    ("inf1", "AWS Inferentia"),
+    ("inf2", "AWS Inferentia2"),
+    ("p2", "NVIDIA K80"),
+    ("p3", "NVIDIA V100"),
+    ("p3dn", "NVIDIA V100"),
+    ("p4d", "NVIDIA A100"),
+    ("p5", "NVIDIA H100"),
+];
+
+/// Accelerator model for `family_name`, if it's one we know about
+pub(crate) fn accelerator_model(family_name: &str) -> Option<&'static str> {
+    ACCELERATOR_MODELS
+        .iter()
+        .find(|(fam, _)| *fam == family_name)
+        .map(|(_, model)| *model)
+}
+
 #[derive(Debug, Clone, Copy)]
 struct ColumnIndicies {
     instance_family: usize,
     instance_type: usize,
     n_cpu: usize,
     memory: usize,
+    gpu_count: Option<usize>,
+    accelerator: Option<&'static str>,
+    network_performance: Option<usize>,
+    network_in_gbps: bool,
+}
+
+impl ColumnIndicies {
+    fn new(instance_family: usize, instance_type: usize, n_cpu: usize, memory: usize) -> Self {
+        Self {
+            instance_family,
+            instance_type,
+            n_cpu,
+            memory,
+            gpu_count: None,
+            accelerator: None,
+            network_performance: None,
+            network_in_gbps: false,
+        }
+    }
+
+    /// Locate the optional accelerator and network columns in `header`
+    fn with_optional_columns(mut self, header: &[impl AsRef<str>]) -> Self {
+        for (idx, col) in header.iter().enumerate() {
+            let col = col.as_ref().trim();
+            if let Some((_, kind)) = ACCELERATOR_COLUMNS.iter().find(|(name, _)| *name == col) {
+                self.gpu_count = Some(idx);
+                self.accelerator = Some(kind);
+            } else if col.to_lowercase().starts_with("network") {
+                self.network_performance = Some(idx);
+                self.network_in_gbps = col.contains("(Gbps)");
+            }
+        }
+        self
+    }
+
+    fn gpu_count(&self, row: &[impl AsRef<str>], offset: usize) -> Result<i32, Error> {
+        match self.gpu_count.and_then(|i| row.get(i.checked_sub(offset)?)) {
+            Some(count) => count
+                .as_ref()
+                .replace('*', "")
+                .trim()
+                .parse()
+                .map_err(Into::into),
+            None => Ok(0),
+        }
+    }
+
+    fn gpu_type(&self, family_name: &str, gpu_count: i32) -> Option<StackString> {
+        if gpu_count == 0 {
+            return None;
+        }
+        accelerator_model(family_name)
+            .or(self.accelerator)
+            .map(Into::into)
+    }
+
+    fn network_performance(&self, row: &[impl AsRef<str>], offset: usize) -> Option<StackString> {
+        let value = self
+            .network_performance
+            .and_then(|i| row.get(i.checked_sub(offset)?))?
+            .as_ref()
+            .trim();
+        if value.is_empty() {
+            None
+        } else if self.network_in_gbps && !value.contains("Gb") {
+            Some(format_sstr!("{value} Gbps"))
+        } else {
+            Some(value.into())
+        }
+    }
 }
 
 fn extract_instance_types_pv(
     table: &Node,
 ) -> Result<(Vec<InstanceFamily>, Vec<InstanceList>), Error> {
     fn indicies_to_struct(indicies: &[Option<usize>; 4]) -> Option<ColumnIndicies> {
-        Some(ColumnIndicies {
-            instance_family: indicies[0]?,
-            instance_type: indicies[1]?,
-            n_cpu: indicies[2]?,
-            memory: indicies[3]?,
-        })
+        Some(ColumnIndicies::new(
+            indicies[0]?,
+            indicies[1]?,
+            indicies[2]?,
+            indicies[3]?,
+        ))
     }
 
     let allowed_columns = ["Instance Family", "Instance Type", "vCPU", "Memory (GiB)"];
@@ -203,6 +304,7 @@ fn extract_instance_types_pv(
             }
         }
         if let Some(final_indicies) = indicies_to_struct(&final_indicies) {
+            let final_indicies = final_indicies.with_optional_columns(&rows[0]);
             let instance_families = rows[1..]
                 .iter()
                 .map(|row| extract_instance_family_object_pv(row, final_indicies))
@@ -238,12 +340,12 @@ fn extract_instance_family_object_pv(
 
 fn extract_instance_types_hvm(table: &Node) -> Result<Vec<InstanceList>, Error> {
     fn indicies_to_struct(indicies: &[Option<usize>; 3]) -> Option<ColumnIndicies> {
-        Some(ColumnIndicies {
-            instance_family: 0,
-            instance_type: indicies[0]?,
-            n_cpu: indicies[1]?,
-            memory: indicies[2]?,
-        })
+        Some(ColumnIndicies::new(
+            0,
+            indicies[0]?,
+            indicies[1]?,
+            indicies[2]?,
+        ))
     }
 
     let allowed_columns = [
@@ -302,7 +404,7 @@ fn extract_instance_types_hvm(table: &Node) -> Result<Vec<InstanceList>, Error>
             }
         }
         if let Some(indicies) = indicies_to_struct(&indicies) {
-            final_indicies = Some(indicies);
+            final_indicies = Some(indicies.with_optional_columns(&rows[0]));
             break;
         }
     }
@@ -351,7 +453,7 @@ fn extract_instance_type_object_hvm(
         .as_ref()
         .replace('*', "")
         .into();
-    let family_name = instance_type.split('.').next().unwrap_or("").into();
+    let family_name: StackString = instance_type.split('.').next().unwrap_or("").into();
     let n_cpu: i32 = row[indicies.n_cpu - idx]
         .as_ref()
         .replace('*', "")
@@ -360,6 +462,9 @@ fn extract_instance_type_object_hvm(
         .as_ref()
         .replace(',', "")
         .parse()?;
+    let gpu_count = indicies.gpu_count(row, idx)?;
+    let gpu_type = indicies.gpu_type(&family_name, gpu_count);
+    let network_performance = indicies.network_performance(row, idx);
 
     Ok(InstanceList {
         instance_type,
@@ -367,6 +472,9 @@ fn extract_instance_type_object_hvm(
         n_cpu,
         memory_gib,
         generation: AwsGeneration::HVM.into(),
+        gpu_count,
+        gpu_type,
+        network_performance,
     })
 }
 
@@ -380,7 +488,7 @@ fn extract_instance_type_object_pv(
         .as_ref()
         .replace('*', "")
         .into();
-    let family_name = instance_type.split('.').next().unwrap_or("").into();
+    let family_name: StackString = instance_type.split('.').next().unwrap_or("").into();
     let n_cpu: i32 = row[indicies.n_cpu - idx]
         .as_ref()
         .replace('*', "")
@@ -389,6 +497,9 @@ fn extract_instance_type_object_pv(
         .as_ref()
         .replace(',', "")
         .parse()?;
+    let gpu_count = indicies.gpu_count(row, idx)?;
+    let gpu_type = indicies.gpu_type(&family_name, gpu_count);
+    let network_performance = indicies.network_performance(row, idx);
 
     Ok(InstanceList {
         instance_type,
@@ -396,6 +507,9 @@ fn extract_instance_type_object_pv(
         n_cpu,
         memory_gib,
         generation: AwsGeneration::PV.into(),
+        gpu_count,
+        gpu_type,
+        network_performance,
     })
 }
 
@@ -411,6 +525,20 @@ mod tests {
         let (families, types) = parse_result(&text_hvm, AwsGeneration::HVM)?;
         assert_eq!(families.len(), 32);
         assert_eq!(types.len(), 264);
+        let p3 = types
+            .iter()
+            .find(|t| t.instance_type == "p3.8xlarge")
+            .expect("missing p3.8xlarge");
+        assert_eq!(p3.gpu_count, 4);
+        assert_eq!(p3.gpu_type.as_deref(), Some("NVIDIA V100"));
+        assert_eq!(p3.network_performance.as_deref(), Some("10 Gigabit"));
+        let t3 = types
+            .iter()
+            .find(|t| t.instance_type == "t3.micro")
+            .expect("missing t3.micro");
+        assert_eq!(t3.gpu_count, 0);
+        assert_eq!(t3.gpu_type, None);
+        assert_eq!(t3.network_performance.as_deref(), Some("Up to 5 Gbps"));
         let text_pv = include_str!("../../tests/data/instance_types_pv.html");
         let (families, types) = parse_result(&text_pv, AwsGeneration::PV)?;
         assert_eq!(families.len(), 12);
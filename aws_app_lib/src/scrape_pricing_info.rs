@@ -9,14 +9,14 @@ use time::OffsetDateTime;
 
 use crate::{
     models::{InstancePricing, PricingType},
-    pgpool::PgPool,
+    storage::{InstancePricingRepo, Storage},
 };
 
 /// # Errors
 /// Returns error if api call fails
 pub async fn scrape_pricing_info(
     ptype: PricingType,
-    pool: &PgPool,
+    storage: &dyn Storage,
 ) -> Result<Vec<StackString>, Error> {
     let mut output = Vec::new();
     let url = extract_json_url(get_url(ptype)?).await?;
@@ -27,7 +27,7 @@ pub async fn scrape_pricing_info(
 
     let futures: FuturesUnordered<_> = results
         .into_iter()
-        .map(|r| async move { r.upsert_entry(pool).await.map(|_| ()) })
+        .map(|r| async move { storage.upsert_price(&r).await.map(|_| ()) })
         .collect();
     let _: Vec<_> = futures.try_collect().await?;
     Ok(output)
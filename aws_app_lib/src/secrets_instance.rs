@@ -0,0 +1,345 @@
+use anyhow::{format_err, Error};
+use aws_config::SdkConfig;
+use aws_sdk_secretsmanager::{
+    primitives::DateTime,
+    types::{Filter, FilterNameStringType},
+    Client as SecretsManagerClient,
+};
+use aws_sdk_ssm::{types::ParameterStringFilter, Client as SsmClient};
+use aws_types::region::Region;
+use serde::Serialize;
+use serde_json::Value;
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+use time::OffsetDateTime;
+
+use crate::config::ConfigInner;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum SecretSource {
+    #[serde(rename = "secretsmanager")]
+    SecretsManager,
+    #[serde(rename = "ssm")]
+    Ssm,
+}
+
+impl SecretSource {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::SecretsManager => "secretsmanager",
+            Self::Ssm => "ssm",
+        }
+    }
+}
+
+impl fmt::Display for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+/// A config value of the form `secretsmanager:name`, `ssm:name` or, to pick
+/// one key out of a json secret, `secretsmanager:name#key`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecretRef {
+    pub source: SecretSource,
+    pub name: StackString,
+    pub key: Option<StackString>,
+}
+
+impl SecretRef {
+    /// `None` when `value` is a plain value rather than a reference
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        let (source, rest) = value.split_once(':')?;
+        let source = match source {
+            "secretsmanager" => SecretSource::SecretsManager,
+            "ssm" => SecretSource::Ssm,
+            _ => return None,
+        };
+        let (name, key) = match rest.split_once('#') {
+            Some((name, key)) => (name, Some(key.into())),
+            None => (rest, None),
+        };
+        if name.is_empty() {
+            return None;
+        }
+        Some(Self {
+            source,
+            name: name.into(),
+            key,
+        })
+    }
+}
+
+/// Name and rotation dates of a secret or parameter, never its value
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SecretSummary {
+    pub source: SecretSource,
+    pub name: StackString,
+    pub description: Option<StackString>,
+    pub last_changed: Option<OffsetDateTime>,
+    pub last_rotated: Option<OffsetDateTime>,
+    pub rotation_enabled: bool,
+}
+
+#[derive(Clone)]
+pub struct SecretsInstance {
+    secretsmanager_client: SecretsManagerClient,
+    ssm_client: SsmClient,
+}
+
+impl fmt::Debug for SecretsInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SecretsInstance")
+    }
+}
+
+impl SecretsInstance {
+    #[must_use]
+    pub fn new(sdk_config: &SdkConfig) -> Self {
+        Self {
+            secretsmanager_client: SecretsManagerClient::from_conf(sdk_config.into()),
+            ssm_client: SsmClient::from_conf(sdk_config.into()),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
+        let region: String = region.as_ref().into();
+        let region = Region::new(region);
+        let conf = self
+            .secretsmanager_client
+            .config()
+            .to_builder()
+            .region(region.clone())
+            .build();
+        self.secretsmanager_client = SecretsManagerClient::from_conf(conf);
+        let conf = self.ssm_client.config().to_builder().region(region).build();
+        self.ssm_client = SsmClient::from_conf(conf);
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails, the secret has no string value or
+    /// `key` is missing from it
+    pub async fn get_value(&self, secret: &SecretRef) -> Result<StackString, Error> {
+        let value = match secret.source {
+            SecretSource::SecretsManager => {
+                self.secretsmanager_client
+                    .get_secret_value()
+                    .secret_id(secret.name.as_str())
+                    .send()
+                    .await?
+                    .secret_string
+            }
+            SecretSource::Ssm => self
+                .ssm_client
+                .get_parameter()
+                .name(secret.name.as_str())
+                .with_decryption(true)
+                .send()
+                .await?
+                .parameter
+                .and_then(|p| p.value),
+        }
+        .ok_or_else(|| format_err!("{}:{} has no string value", secret.source, secret.name))?;
+        match &secret.key {
+            Some(key) => json_key(&value, key)
+                .ok_or_else(|| format_err!("{}:{} has no key {key}", secret.source, secret.name)),
+            None => Ok(value.into()),
+        }
+    }
+
+    /// Replace secret references in `database_url`, `database_password` and
+    /// `telegram_bot_token` with their values
+    /// # Errors
+    /// Returns error naming the field whose secret can't be read
+    pub async fn resolve_config(&self, conf: &mut ConfigInner) -> Result<(), Error> {
+        if let Some(secret) = SecretRef::parse(&conf.database_url) {
+            conf.database_url = self
+                .get_value(&secret)
+                .await
+                .map_err(|e| format_err!("invalid config field database_url: {e}"))?;
+        }
+        for (field, value) in [
+            ("database_password", &mut conf.database_password),
+            ("telegram_bot_token", &mut conf.telegram_bot_token),
+        ] {
+            if let Some(secret) = value.as_deref().and_then(SecretRef::parse) {
+                *value = Some(
+                    self.get_value(&secret)
+                        .await
+                        .map_err(|e| format_err!("invalid config field {field}: {e}"))?,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Summaries of the secrets or parameters under `prefix`, itself of the
+    /// form `secretsmanager:prefix` or `ssm:prefix`
+    /// # Errors
+    /// Returns error if `prefix` has no source or aws api call fails
+    pub async fn list_prefix(&self, prefix: &str) -> Result<Vec<SecretSummary>, Error> {
+        let secret = SecretRef::parse(prefix)
+            .ok_or_else(|| format_err!("{prefix} is not a secret prefix"))?;
+        match secret.source {
+            SecretSource::SecretsManager => self.list_secrets(&secret.name).await,
+            SecretSource::Ssm => self.list_parameters(&secret.name).await,
+        }
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_secrets(&self, prefix: &str) -> Result<Vec<SecretSummary>, Error> {
+        let filter = Filter::builder()
+            .key(FilterNameStringType::Name)
+            .values(prefix)
+            .build();
+        let mut secrets = Vec::new();
+        let mut next_token = None;
+        loop {
+            let result = self
+                .secretsmanager_client
+                .list_secrets()
+                .filters(filter.clone())
+                .set_next_token(next_token)
+                .send()
+                .await?;
+            secrets.extend(
+                result
+                    .secret_list
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|s| {
+                        Some(SecretSummary {
+                            source: SecretSource::SecretsManager,
+                            name: s.name?.into(),
+                            description: s.description.map(Into::into),
+                            last_changed: s.last_changed_date.as_ref().and_then(from_datetime),
+                            last_rotated: s.last_rotated_date.as_ref().and_then(from_datetime),
+                            rotation_enabled: s.rotation_enabled.unwrap_or(false),
+                        })
+                    }),
+            );
+            next_token = result.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        Ok(secrets)
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_parameters(&self, prefix: &str) -> Result<Vec<SecretSummary>, Error> {
+        let filter = ParameterStringFilter::builder()
+            .key("Name")
+            .option("BeginsWith")
+            .values(prefix)
+            .build()?;
+        let mut parameters = Vec::new();
+        let mut next_token = None;
+        loop {
+            let result = self
+                .ssm_client
+                .describe_parameters()
+                .parameter_filters(filter.clone())
+                .set_next_token(next_token)
+                .send()
+                .await?;
+            parameters.extend(
+                result
+                    .parameters
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|p| {
+                        let description = match (p.description, p.r#type) {
+                            (Some(d), _) => Some(d.into()),
+                            (None, Some(t)) => Some(format_sstr!("{}", t.as_str())),
+                            (None, None) => None,
+                        };
+                        Some(SecretSummary {
+                            source: SecretSource::Ssm,
+                            name: p.name?.into(),
+                            description,
+                            last_changed: p.last_modified_date.as_ref().and_then(from_datetime),
+                            last_rotated: None,
+                            rotation_enabled: false,
+                        })
+                    }),
+            );
+            next_token = result.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        Ok(parameters)
+    }
+}
+
+fn from_datetime(d: &DateTime) -> Option<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp(d.secs()).ok()
+}
+
+/// `key` of a json object secret, numbers and booleans are stringified
+fn json_key(value: &str, key: &str) -> Option<StackString> {
+    match serde_json::from_str::<Value>(value).ok()?.get(key)? {
+        Value::String(s) => Some(s.into()),
+        Value::Number(n) => Some(format_sstr!("{n}")),
+        Value::Bool(b) => Some(format_sstr!("{b}")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::secrets_instance::{json_key, SecretRef, SecretSource, SecretsInstance};
+
+    #[test]
+    fn test_secret_ref_parse() {
+        let secret = SecretRef::parse("secretsmanager:prod/db#password").unwrap();
+        assert_eq!(secret.source, SecretSource::SecretsManager);
+        assert_eq!(secret.name, "prod/db");
+        assert_eq!(secret.key.as_deref(), Some("password"));
+
+        let secret = SecretRef::parse("ssm:/aws_app/telegram_bot_token").unwrap();
+        assert_eq!(secret.source, SecretSource::Ssm);
+        assert_eq!(secret.name, "/aws_app/telegram_bot_token");
+        assert_eq!(secret.key, None);
+
+        let arn = "secretsmanager:arn:aws:secretsmanager:us-east-1:123456789012:secret:db-AbCdEf";
+        assert_eq!(
+            SecretRef::parse(arn).unwrap().name,
+            "arn:aws:secretsmanager:us-east-1:123456789012:secret:db-AbCdEf"
+        );
+
+        assert_eq!(SecretRef::parse("postgresql://user:pw@host:5432/db"), None);
+        assert_eq!(SecretRef::parse("12345:abcdef"), None);
+        assert_eq!(SecretRef::parse("ssm:"), None);
+    }
+
+    #[test]
+    fn test_json_key() {
+        let value = r#"{"username": "aws", "password": "hunter2", "port": 5432}"#;
+        assert_eq!(json_key(value, "password").as_deref(), Some("hunter2"));
+        assert_eq!(json_key(value, "port").as_deref(), Some("5432"));
+        assert_eq!(json_key(value, "host"), None);
+        assert_eq!(json_key("hunter2", "password"), None);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_list_prefix() -> Result<(), Error> {
+        let sdk_config = aws_config::load_from_env().await;
+        let secrets = SecretsInstance::new(&sdk_config);
+        let parameters = secrets.list_prefix("ssm:/").await?;
+        assert!(parameters.iter().all(|p| p.name.starts_with('/')));
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+use anyhow::Error;
+use aws_config::SdkConfig;
+use aws_sdk_ses::{
+    types::{ReceiptAction, ReceiptRule, S3Action},
+    Client as SesClient,
+};
+use aws_sdk_sesv2::{types::IdentityType, Client as SesV2Client};
+use aws_types::region::Region;
+use futures::future::try_join_all;
+use serde::Serialize;
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+
+/// Prefix `InboundEmail::sync_db` reads new messages from
+pub const INBOUND_EMAIL_PREFIX: &str = "inbound-email/";
+
+/// Domain and email identities (SES v2) and the receipt rule sets (SES v1,
+/// there is no v2 api for receiving) which write inbound email to s3
+#[derive(Clone)]
+pub struct SesAdminInstance {
+    ses_client: SesClient,
+    sesv2_client: SesV2Client,
+}
+
+impl fmt::Debug for SesAdminInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SesAdminInstance")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SesIdentity {
+    pub name: StackString,
+    pub identity_type: StackString,
+    pub verification_status: StackString,
+    pub sending_enabled: bool,
+    pub dkim_signing_enabled: bool,
+    pub dkim_status: StackString,
+    pub dkim_tokens: Vec<StackString>,
+}
+
+impl SesIdentity {
+    #[must_use]
+    pub fn is_domain(&self) -> bool {
+        self.identity_type == IdentityType::Domain.as_str()
+    }
+
+    /// CNAME records (name, value) that have to exist for easy DKIM
+    #[must_use]
+    pub fn dkim_records(&self) -> Vec<(StackString, StackString)> {
+        if !self.is_domain() {
+            return Vec::new();
+        }
+        self.dkim_tokens
+            .iter()
+            .map(|token| {
+                (
+                    format_sstr!("{token}._domainkey.{}", self.name),
+                    format_sstr!("{token}.dkim.amazonses.com"),
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ReceiptRuleInfo {
+    pub name: StackString,
+    pub enabled: bool,
+    pub recipients: Vec<StackString>,
+    pub s3_bucket: Option<StackString>,
+    pub s3_prefix: Option<StackString>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ReceiptRuleSetInfo {
+    pub name: StackString,
+    pub active: bool,
+    pub rules: Vec<ReceiptRuleInfo>,
+}
+
+impl From<ReceiptRule> for ReceiptRuleInfo {
+    fn from(rule: ReceiptRule) -> Self {
+        let s3_action = rule
+            .actions
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|action| action.s3_action);
+        Self {
+            name: rule.name.into(),
+            enabled: rule.enabled,
+            recipients: rule
+                .recipients
+                .unwrap_or_default()
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            s3_bucket: s3_action.as_ref().map(|a| a.bucket_name.as_str().into()),
+            s3_prefix: s3_action.and_then(|a| a.object_key_prefix.map(Into::into)),
+        }
+    }
+}
+
+impl SesAdminInstance {
+    #[must_use]
+    pub fn new(sdk_config: &SdkConfig) -> Self {
+        Self {
+            ses_client: SesClient::new(sdk_config),
+            sesv2_client: SesV2Client::new(sdk_config),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
+        let region: String = region.as_ref().into();
+        let region = Region::new(region);
+        let conf = self
+            .ses_client
+            .config()
+            .to_builder()
+            .region(region.clone())
+            .build();
+        self.ses_client = SesClient::from_conf(conf);
+        let conf = self
+            .sesv2_client
+            .config()
+            .to_builder()
+            .region(region)
+            .build();
+        self.sesv2_client = SesV2Client::from_conf(conf);
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn list_identities(&self) -> Result<Vec<SesIdentity>, Error> {
+        let mut names = Vec::new();
+        let mut next_token = None;
+        loop {
+            let result = self
+                .sesv2_client
+                .list_email_identities()
+                .set_next_token(next_token)
+                .send()
+                .await?;
+            names.extend(
+                result
+                    .email_identities
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|i| i.identity_name),
+            );
+            next_token = result.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        let futures = names.iter().map(|name| self.get_identity(name));
+        try_join_all(futures).await
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn get_identity(&self, name: &str) -> Result<SesIdentity, Error> {
+        let identity = self
+            .sesv2_client
+            .get_email_identity()
+            .email_identity(name)
+            .send()
+            .await?;
+        let dkim = identity.dkim_attributes;
+        Ok(SesIdentity {
+            name: name.into(),
+            identity_type: identity
+                .identity_type
+                .as_ref()
+                .map_or("", IdentityType::as_str)
+                .into(),
+            verification_status: identity
+                .verification_status
+                .as_ref()
+                .map_or("", |s| s.as_str())
+                .into(),
+            sending_enabled: identity.verified_for_sending_status,
+            dkim_signing_enabled: dkim.as_ref().map_or(false, |d| d.signing_enabled),
+            dkim_status: dkim
+                .as_ref()
+                .and_then(|d| d.status.as_ref())
+                .map_or("", |s| s.as_str())
+                .into(),
+            dkim_tokens: dkim
+                .and_then(|d| d.tokens)
+                .unwrap_or_default()
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        })
+    }
+
+    /// Start verification of a domain (or email address), for a domain the
+    /// returned identity lists the DKIM records to publish
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn verify_identity(&self, name: &str) -> Result<SesIdentity, Error> {
+        self.sesv2_client
+            .create_email_identity()
+            .email_identity(name)
+            .send()
+            .await?;
+        self.get_identity(name).await
+    }
+
+    /// Rule sets along with their rules, the active set first
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn list_receipt_rule_sets(&self) -> Result<Vec<ReceiptRuleSetInfo>, Error> {
+        let active = self
+            .ses_client
+            .describe_active_receipt_rule_set()
+            .send()
+            .await?
+            .metadata
+            .and_then(|m| m.name);
+        let names: Vec<String> = self
+            .ses_client
+            .list_receipt_rule_sets()
+            .send()
+            .await?
+            .rule_sets
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|m| m.name)
+            .collect();
+        let futures = names.into_iter().map(|name| {
+            let is_active = active.as_ref() == Some(&name);
+            async move {
+                let rules = self
+                    .ses_client
+                    .describe_receipt_rule_set()
+                    .rule_set_name(&name)
+                    .send()
+                    .await?
+                    .rules
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Into::into)
+                    .collect();
+                Ok::<_, Error>(ReceiptRuleSetInfo {
+                    name: name.into(),
+                    active: is_active,
+                    rules,
+                })
+            }
+        });
+        let mut rule_sets = try_join_all(futures).await?;
+        rule_sets.sort_by(|a, b| b.active.cmp(&a.active).then_with(|| a.name.cmp(&b.name)));
+        Ok(rule_sets)
+    }
+
+    /// Add a rule to `rule_set` (created if missing) which stores mail for
+    /// `recipients` under `inbound-email/` in `bucket`
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn create_inbound_rule(
+        &self,
+        rule_set: &str,
+        rule_name: &str,
+        recipients: &[impl AsRef<str>],
+        bucket: &str,
+    ) -> Result<(), Error> {
+        let existing = self
+            .ses_client
+            .list_receipt_rule_sets()
+            .send()
+            .await?
+            .rule_sets
+            .unwrap_or_default();
+        if !existing.iter().any(|m| m.name.as_deref() == Some(rule_set)) {
+            self.ses_client
+                .create_receipt_rule_set()
+                .rule_set_name(rule_set)
+                .send()
+                .await?;
+        }
+        let action = S3Action::builder()
+            .bucket_name(bucket)
+            .object_key_prefix(INBOUND_EMAIL_PREFIX)
+            .build()?;
+        let rule = ReceiptRule::builder()
+            .name(rule_name)
+            .enabled(true)
+            .scan_enabled(true)
+            .set_recipients(Some(recipients.iter().map(|r| r.as_ref().into()).collect()))
+            .actions(ReceiptAction::builder().s3_action(action).build())
+            .build()?;
+        self.ses_client
+            .create_receipt_rule()
+            .rule_set_name(rule_set)
+            .rule(rule)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn delete_receipt_rule(&self, rule_set: &str, rule_name: &str) -> Result<(), Error> {
+        self.ses_client
+            .delete_receipt_rule()
+            .rule_set_name(rule_set)
+            .rule_name(rule_name)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn activate_receipt_rule_set(&self, rule_set: &str) -> Result<(), Error> {
+        self.ses_client
+            .set_active_receipt_rule_set()
+            .rule_set_name(rule_set)
+            .send()
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ses_admin::SesIdentity;
+
+    #[test]
+    fn test_dkim_records() {
+        let mut identity = SesIdentity {
+            name: "example.com".into(),
+            identity_type: "DOMAIN".into(),
+            verification_status: "PENDING".into(),
+            sending_enabled: false,
+            dkim_signing_enabled: true,
+            dkim_status: "PENDING".into(),
+            dkim_tokens: vec!["abc".into(), "def".into()],
+        };
+        let records = identity.dkim_records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].0, "abc._domainkey.example.com");
+        assert_eq!(records[0].1, "abc.dkim.amazonses.com");
+
+        identity.identity_type = "EMAIL_ADDRESS".into();
+        assert!(identity.dkim_records().is_empty());
+    }
+}
@@ -3,11 +3,13 @@
 use anyhow::Error;
 use aws_config::SdkConfig;
 use aws_sdk_ses::{
-    types::{Body, Content, Destination, Message},
+    primitives::Blob,
+    types::{Body, Content, Destination, Message, RawMessage},
     Client as SesClient,
 };
+use aws_types::region::Region;
 use serde::Serialize;
-use stack_string::format_sstr;
+use stack_string::{format_sstr, StackString};
 use std::fmt;
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
@@ -66,6 +68,29 @@ impl SesInstance {
         Ok(())
     }
 
+    /// Send a complete MIME message, `raw` must contain its own headers.
+    /// Returns the Message-ID header SES gave the message.
+    /// # Errors
+    /// Returns error if send email fails
+    pub async fn send_raw_email(
+        &self,
+        src: impl Into<String>,
+        dest: impl Into<String>,
+        raw: &[u8],
+    ) -> Result<StackString, Error> {
+        let message = RawMessage::builder().data(Blob::new(raw)).build()?;
+        let output = self
+            .ses_client
+            .send_raw_email()
+            .source(src)
+            .destinations(dest)
+            .raw_message(message)
+            .send()
+            .await?;
+        let region = self.ses_client.config().region().map(Region::as_ref);
+        Ok(message_id_header(output.message_id(), region))
+    }
+
     /// # Errors
     /// Returns error if
     ///     * `get_send_quota` api call fails
@@ -115,6 +140,15 @@ impl SesInstance {
     }
 }
 
+/// SES replaces the Message-ID of sent mail with `<id>@email.amazonses.com`
+/// in us-east-1 and `<id>@{region}.amazonses.com` elsewhere
+fn message_id_header(ses_message_id: &str, region: Option<&str>) -> StackString {
+    match region {
+        None | Some("us-east-1") => format_sstr!("{ses_message_id}@email.amazonses.com"),
+        Some(region) => format_sstr!("{ses_message_id}@{region}.amazonses.com"),
+    }
+}
+
 #[derive(Default, Debug, Serialize)]
 pub struct SesQuotas {
     pub max_24_hour_send: f64,
@@ -168,7 +202,7 @@ pub struct Statistics {
 
 #[cfg(test)]
 mod tests {
-    use crate::ses_client::SesInstance;
+    use crate::ses_client::{message_id_header, SesInstance};
 
     #[tokio::test]
     async fn test_debug() {
@@ -176,4 +210,16 @@ mod tests {
         let ses = SesInstance::new(&sdk_config);
         assert_eq!(&format!("{:?}", ses), "SesInstance");
     }
+
+    #[test]
+    fn test_message_id_header() {
+        assert_eq!(
+            message_id_header("0100abc", Some("us-east-1")),
+            "0100abc@email.amazonses.com"
+        );
+        assert_eq!(
+            message_id_header("0100abc", Some("eu-west-1")),
+            "0100abc@eu-west-1.amazonses.com"
+        );
+    }
 }
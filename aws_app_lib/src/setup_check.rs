@@ -0,0 +1,168 @@
+use stack_string::StackString;
+use time::Duration;
+
+/// `setup_status` of a launch whose cloud-init finished and whose
+/// `setup_check_command` (if any) succeeded
+pub const SETUP_DONE: &str = "done";
+/// `setup_status` of a launch whose cloud-init reported an error
+pub const SETUP_FAILED: &str = "failed";
+/// `setup_status` of a launch which didn't finish within `setup_timeout`
+pub const SETUP_TIMEOUT: &str = "timeout";
+/// `setup_status` of launches recorded before setup was checked
+pub const SETUP_UNCHECKED: &str = "unchecked";
+/// Printed after `setup_check_command` exits successfully, ssh doesn't pass
+/// the exit status back
+pub const SETUP_CHECK_OK: &str = "setup-check-ok";
+
+/// State reported by `cloud-init status --long`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CloudInitStatus {
+    Running,
+    Done,
+    Error(StackString),
+    /// No status line, typically because ssh isn't up yet
+    Unknown,
+}
+
+impl CloudInitStatus {
+    /// Newer cloud-init reports `degraded done` when it finished with
+    /// warnings, which counts as done. The `detail` of an error is kept as
+    /// its message.
+    #[must_use]
+    pub fn parse(lines: &[StackString]) -> Self {
+        let status = lines
+            .iter()
+            .find_map(|l| l.trim().strip_prefix("status:"))
+            .map(str::trim);
+        match status {
+            Some(s) if s.starts_with("done") || s == "disabled" => Self::Done,
+            Some("error") => {
+                let detail: Vec<_> = lines
+                    .iter()
+                    .map(|l| l.trim())
+                    .skip_while(|l| *l != "detail:")
+                    .skip(1)
+                    .take_while(|l| !l.is_empty() && !l.starts_with("errors:"))
+                    .collect();
+                Self::Error(detail.join("; ").into())
+            }
+            Some(_) => Self::Running,
+            None => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SetupOutcome {
+    Pending,
+    Done,
+    Failed(StackString),
+    TimedOut,
+}
+
+impl SetupOutcome {
+    /// Decide the outcome of a launch from its cloud-init status and, once
+    /// cloud-init is done, whether `setup_check_command` succeeded (`None`
+    /// if no command is configured)
+    #[must_use]
+    pub fn evaluate(
+        status: &CloudInitStatus,
+        check_ok: Option<bool>,
+        elapsed: Duration,
+        timeout: Duration,
+    ) -> Self {
+        match (status, check_ok) {
+            (CloudInitStatus::Error(detail), _) => {
+                if detail.is_empty() {
+                    Self::Failed("cloud-init reported an error".into())
+                } else {
+                    Self::Failed(detail.clone())
+                }
+            }
+            (CloudInitStatus::Done, None | Some(true)) => Self::Done,
+            (CloudInitStatus::Done, Some(false)) if elapsed > timeout => {
+                Self::Failed("setup check command did not succeed".into())
+            }
+            _ if elapsed > timeout => Self::TimedOut,
+            _ => Self::Pending,
+        }
+    }
+
+    #[must_use]
+    pub fn to_status(&self) -> Option<&'static str> {
+        match self {
+            Self::Pending => None,
+            Self::Done => Some(SETUP_DONE),
+            Self::Failed(_) => Some(SETUP_FAILED),
+            Self::TimedOut => Some(SETUP_TIMEOUT),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stack_string::StackString;
+    use time::Duration;
+
+    use crate::setup_check::{CloudInitStatus, SetupOutcome};
+
+    fn lines(output: &str) -> Vec<StackString> {
+        output.split('\n').map(Into::into).collect()
+    }
+
+    #[test]
+    fn test_parse_cloud_init_status() {
+        let done =
+            lines("status: done\nextended_status: degraded done\ndetail:\nDataSourceEc2Local\n");
+        assert_eq!(CloudInitStatus::parse(&done), CloudInitStatus::Done);
+        let running = lines("status: running\ntime: Sat, 01 Jun 2024 00:00:00 +0000\n");
+        assert_eq!(CloudInitStatus::parse(&running), CloudInitStatus::Running);
+        let error = lines(
+            "status: error\ntime: Sat, 01 Jun 2024 00:00:00 +0000\ndetail:\n('scripts-user', \
+             RuntimeError('Runparts: 1 failures'))\n\nerrors:\n",
+        );
+        assert_eq!(
+            CloudInitStatus::parse(&error),
+            CloudInitStatus::Error("('scripts-user', RuntimeError('Runparts: 1 failures'))".into())
+        );
+        assert_eq!(CloudInitStatus::parse(&lines("")), CloudInitStatus::Unknown);
+    }
+
+    #[test]
+    fn test_setup_outcome() {
+        let timeout = Duration::minutes(30);
+        let early = Duration::minutes(5);
+        let late = Duration::minutes(31);
+        let evaluate = |status: &CloudInitStatus, check_ok, elapsed| {
+            SetupOutcome::evaluate(status, check_ok, elapsed, timeout)
+        };
+        assert_eq!(
+            evaluate(&CloudInitStatus::Done, None, early),
+            SetupOutcome::Done
+        );
+        assert_eq!(
+            evaluate(&CloudInitStatus::Done, Some(true), late),
+            SetupOutcome::Done
+        );
+        assert_eq!(
+            evaluate(&CloudInitStatus::Done, Some(false), early),
+            SetupOutcome::Pending
+        );
+        assert!(matches!(
+            evaluate(&CloudInitStatus::Done, Some(false), late),
+            SetupOutcome::Failed(_)
+        ));
+        assert_eq!(
+            evaluate(&CloudInitStatus::Error("".into()), None, early).to_status(),
+            Some("failed")
+        );
+        assert_eq!(
+            evaluate(&CloudInitStatus::Running, None, early),
+            SetupOutcome::Pending
+        );
+        assert_eq!(
+            evaluate(&CloudInitStatus::Unknown, None, late),
+            SetupOutcome::TimedOut
+        );
+    }
+}
@@ -0,0 +1,488 @@
+use anyhow::{format_err, Error};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use regex::Regex;
+use reqwest::Client;
+use ring::signature::{
+    UnparsedPublicKey, VerificationAlgorithm, RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY,
+    RSA_PKCS1_2048_8192_SHA256,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use stack_string::{format_sstr, StackString};
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+use url::{form_urlencoded, Url};
+use x509_parser::pem::parse_x509_pem;
+
+pub const SNS_NOTIFICATION: &str = "Notification";
+pub const SNS_SUBSCRIPTION_CONFIRMATION: &str = "SubscriptionConfirmation";
+pub const SNS_UNSUBSCRIBE_CONFIRMATION: &str = "UnsubscribeConfirmation";
+
+const STATE_CHANGE_DETAIL_TYPE: &str = "EC2 Instance State-change Notification";
+const SPOT_INTERRUPTION_DETAIL_TYPE: &str = "EC2 Spot Instance Interruption Warning";
+const ECR_IMAGE_ACTION_DETAIL_TYPE: &str = "ECR Image Action";
+
+/// Hosts sns signing certificates and subscribe urls are served from
+static SNS_HOST: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^sns\.[a-z0-9-]+\.amazonaws\.com(\.cn)?$").expect("invalid sns host regex")
+});
+
+static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+/// Public keys of signing certificates already fetched, keyed by url
+static SIGNING_KEYS: Lazy<RwLock<HashMap<StackString, Arc<Vec<u8>>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Message POSTed by sns to an http(s) subscription
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnsMessage {
+    #[serde(rename = "Type")]
+    pub message_type: StackString,
+    pub message_id: StackString,
+    pub topic_arn: StackString,
+    pub subject: Option<StackString>,
+    pub message: StackString,
+    pub timestamp: StackString,
+    pub signature_version: StackString,
+    pub signature: StackString,
+    #[serde(rename = "SigningCertURL")]
+    pub signing_cert_url: StackString,
+    pub token: Option<StackString>,
+    #[serde(rename = "SubscribeURL")]
+    pub subscribe_url: Option<StackString>,
+}
+
+impl SnsMessage {
+    /// The canonical string sns signs, fields in byte order each followed by
+    /// its value on the next line
+    /// # Errors
+    /// Returns error if a field required for the message type is missing
+    pub fn string_to_sign(&self) -> Result<StackString, Error> {
+        let mut fields: Vec<(&str, &str)> =
+            vec![("Message", &self.message), ("MessageId", &self.message_id)];
+        match self.message_type.as_str() {
+            SNS_NOTIFICATION => {
+                if let Some(subject) = &self.subject {
+                    fields.push(("Subject", subject));
+                }
+                fields.push(("Timestamp", &self.timestamp));
+            }
+            SNS_SUBSCRIPTION_CONFIRMATION | SNS_UNSUBSCRIBE_CONFIRMATION => {
+                let subscribe_url = self
+                    .subscribe_url
+                    .as_ref()
+                    .ok_or_else(|| format_err!("missing SubscribeURL"))?;
+                let token = self
+                    .token
+                    .as_ref()
+                    .ok_or_else(|| format_err!("missing Token"))?;
+                fields.push(("SubscribeURL", subscribe_url));
+                fields.push(("Timestamp", &self.timestamp));
+                fields.push(("Token", token));
+            }
+            t => return Err(format_err!("{t} is not an sns message type")),
+        }
+        fields.push(("TopicArn", &self.topic_arn));
+        fields.push(("Type", &self.message_type));
+        let mut buf = StackString::new();
+        for (key, value) in fields {
+            writeln!(buf, "{key}\n{value}")?;
+        }
+        Ok(buf)
+    }
+
+    fn algorithm(&self) -> Result<&'static dyn VerificationAlgorithm, Error> {
+        match self.signature_version.as_str() {
+            "1" => Ok(&RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY),
+            "2" => Ok(&RSA_PKCS1_2048_8192_SHA256),
+            v => Err(format_err!("unsupported sns signature version {v}")),
+        }
+    }
+
+    /// Check the signature against the certificate at `SigningCertURL`,
+    /// which has to be served over https from an sns host
+    /// # Errors
+    /// Returns error if the certificate can't be fetched or the signature
+    /// doesn't match
+    pub async fn verify(&self) -> Result<(), Error> {
+        let algorithm = self.algorithm()?;
+        let public_key = signing_key(&self.signing_cert_url).await?;
+        let signature = STANDARD.decode(self.signature.as_bytes())?;
+        let string_to_sign = self.string_to_sign()?;
+        UnparsedPublicKey::new(algorithm, public_key.as_slice())
+            .verify(string_to_sign.as_bytes(), &signature)
+            .map_err(|_| format_err!("invalid sns signature for {}", self.message_id))
+    }
+
+    /// Visit `SubscribeURL` to confirm a subscription
+    /// # Errors
+    /// Returns error if the url isn't an sns url or the request fails
+    pub async fn confirm_subscription(&self) -> Result<(), Error> {
+        let subscribe_url = self
+            .subscribe_url
+            .as_ref()
+            .ok_or_else(|| format_err!("missing SubscribeURL"))?;
+        let url = validate_sns_url(subscribe_url)?;
+        CLIENT.get(url).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// # Errors
+/// Returns error unless `url` is an https url on an sns host
+pub fn validate_sns_url(url: &str) -> Result<Url, Error> {
+    let url: Url = url.parse()?;
+    let host = url.host_str().unwrap_or("");
+    if url.scheme() != "https" || !SNS_HOST.is_match(host) {
+        return Err(format_err!("{url} is not an sns url"));
+    }
+    Ok(url)
+}
+
+async fn signing_key(cert_url: &str) -> Result<Arc<Vec<u8>>, Error> {
+    if let Some(key) = SIGNING_KEYS.read().get(cert_url) {
+        return Ok(key.clone());
+    }
+    let url = validate_sns_url(cert_url)?;
+    if !url.path().ends_with(".pem") {
+        return Err(format_err!("{url} is not a certificate"));
+    }
+    let pem = CLIENT
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let (_, pem) = parse_x509_pem(&pem).map_err(|e| format_err!("{e}"))?;
+    let cert = pem.parse_x509().map_err(|e| format_err!("{e}"))?;
+    if !cert.validity().is_valid() {
+        return Err(format_err!(
+            "sns signing certificate {cert_url} has expired"
+        ));
+    }
+    let key = Arc::new(cert.public_key().subject_public_key.data.to_vec());
+    SIGNING_KEYS.write().insert(cert_url.into(), key.clone());
+    Ok(key)
+}
+
+/// Instance change carried by an EventBridge event
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Ec2Event {
+    StateChange {
+        instance_id: StackString,
+        state: StackString,
+    },
+    SpotInterruption {
+        instance_id: StackString,
+        action: StackString,
+    },
+}
+
+#[derive(Deserialize)]
+struct EventBridgeEvent {
+    #[serde(rename = "detail-type")]
+    detail_type: StackString,
+    detail: Value,
+}
+
+impl EventBridgeEvent {
+    fn detail_str(&self, key: &str) -> Option<StackString> {
+        self.detail.get(key).and_then(Value::as_str).map(Into::into)
+    }
+
+    fn detail(&self, key: &str) -> Result<StackString, Error> {
+        self.detail_str(key)
+            .ok_or_else(|| format_err!("{} event has no {key}", self.detail_type))
+    }
+}
+
+impl Ec2Event {
+    /// Parse the EventBridge event in the `Message` of an sns notification,
+    /// `None` for events other than instance state changes and spot
+    /// interruption warnings
+    /// # Errors
+    /// Returns error if `message` isn't an EventBridge event
+    pub fn from_message(message: &str) -> Result<Option<Self>, Error> {
+        let event: EventBridgeEvent = serde_json::from_str(message)?;
+        match event.detail_type.as_str() {
+            STATE_CHANGE_DETAIL_TYPE => Ok(Some(Self::StateChange {
+                instance_id: event.detail("instance-id")?,
+                state: event.detail("state")?,
+            })),
+            SPOT_INTERRUPTION_DETAIL_TYPE => Ok(Some(Self::SpotInterruption {
+                instance_id: event.detail("instance-id")?,
+                action: event.detail("instance-action")?,
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    #[must_use]
+    pub fn instance_id(&self) -> &str {
+        match self {
+            Self::StateChange { instance_id, .. } | Self::SpotInterruption { instance_id, .. } => {
+                instance_id
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn description(&self) -> StackString {
+        match self {
+            Self::StateChange { instance_id, state } => format_sstr!("{instance_id} is {state}"),
+            Self::SpotInterruption {
+                instance_id,
+                action,
+            } => format_sstr!("spot instance {instance_id} will {action} in 2 minutes"),
+        }
+    }
+}
+
+/// Object created in a bucket, from an s3 event notification
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct S3ObjectCreated {
+    pub bucket: StackString,
+    pub key: StackString,
+}
+
+#[derive(Deserialize)]
+struct S3Event {
+    #[serde(rename = "Records", default)]
+    records: Vec<S3EventRecord>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct S3EventRecord {
+    event_name: StackString,
+    s3: S3EventEntity,
+}
+
+#[derive(Deserialize)]
+struct S3EventEntity {
+    bucket: S3EventName,
+    object: S3EventKey,
+}
+
+#[derive(Deserialize)]
+struct S3EventName {
+    name: StackString,
+}
+
+#[derive(Deserialize)]
+struct S3EventKey {
+    key: StackString,
+}
+
+impl S3ObjectCreated {
+    /// Parse the s3 event notification in the `Message` of an sns
+    /// notification, empty for other events such as the `s3:TestEvent` sent
+    /// when the notification is set up
+    /// # Errors
+    /// Returns error if `message` isn't an s3 event notification
+    pub fn from_message(message: &str) -> Result<Vec<Self>, Error> {
+        let event: S3Event = serde_json::from_str(message)?;
+        Ok(event
+            .records
+            .into_iter()
+            .filter(|r| r.event_name.starts_with("ObjectCreated:"))
+            .map(|r| Self {
+                bucket: r.s3.bucket.name,
+                key: decode_s3_key(&r.s3.object.key),
+            })
+            .collect())
+    }
+}
+
+/// Object keys in s3 events are form encoded, spaces become `+` and `&`
+/// and `=` are escaped
+fn decode_s3_key(key: &str) -> StackString {
+    form_urlencoded::parse(key.as_bytes())
+        .next()
+        .map_or_else(StackString::new, |(key, _)| key.as_ref().into())
+}
+
+/// Successful ecr image push carried by an EventBridge event
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct EcrPushEvent {
+    pub repository: StackString,
+    pub digest: StackString,
+    pub tag: Option<StackString>,
+}
+
+impl EcrPushEvent {
+    /// Parse the EventBridge event in the `Message` of an sns notification,
+    /// `None` for events other than successful pushes
+    /// # Errors
+    /// Returns error if `message` isn't an EventBridge event
+    pub fn from_message(message: &str) -> Result<Option<Self>, Error> {
+        let event: EventBridgeEvent = serde_json::from_str(message)?;
+        if event.detail_type != ECR_IMAGE_ACTION_DETAIL_TYPE
+            || event.detail_str("action-type").as_deref() != Some("PUSH")
+            || event.detail_str("result").as_deref() != Some("SUCCESS")
+        {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            repository: event.detail("repository-name")?,
+            digest: event.detail("image-digest")?,
+            tag: event.detail_str("image-tag"),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::sns_event::{validate_sns_url, Ec2Event, EcrPushEvent, S3ObjectCreated, SnsMessage};
+
+    fn notification(message: &str) -> SnsMessage {
+        SnsMessage {
+            message_type: "Notification".into(),
+            message_id: "22b80b92-fdea-4c2c-8f9d-bdfb0c7bf324".into(),
+            topic_arn: "arn:aws:sns:us-east-1:123456789012:ec2-events".into(),
+            subject: None,
+            message: message.into(),
+            timestamp: "2024-01-01T00:00:00.000Z".into(),
+            signature_version: "1".into(),
+            signature: "".into(),
+            signing_cert_url: "https://sns.us-east-1.amazonaws.com/SimpleNotificationService.pem"
+                .into(),
+            token: None,
+            subscribe_url: None,
+        }
+    }
+
+    #[test]
+    fn test_string_to_sign() -> Result<(), Error> {
+        let mut msg = notification("hello");
+        assert_eq!(
+            msg.string_to_sign()?,
+            "Message\nhello\nMessageId\n22b80b92-fdea-4c2c-8f9d-bdfb0c7bf324\nTimestamp\n\
+             2024-01-01T00:00:00.000Z\nTopicArn\narn:aws:sns:us-east-1:123456789012:ec2-events\n\
+             Type\nNotification\n"
+        );
+        msg.subject = Some("subject".into());
+        assert!(msg.string_to_sign()?.contains(
+            "MessageId\n22b80b92-fdea-4c2c-8f9d-bdfb0c7bf324\nSubject\nsubject\nTimestamp\n"
+        ));
+
+        msg.message_type = "SubscriptionConfirmation".into();
+        assert!(msg.string_to_sign().is_err());
+        msg.subscribe_url =
+            Some("https://sns.us-east-1.amazonaws.com/?Action=ConfirmSubscription".into());
+        msg.token = Some("token".into());
+        let s = msg.string_to_sign()?;
+        assert!(s.contains("SubscribeURL\n"));
+        assert!(s.contains("Token\ntoken\nTopicArn\n"));
+        assert!(!s.contains("Subject"));
+
+        msg.message_type = "Other".into();
+        assert!(msg.string_to_sign().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_sns_url() {
+        assert!(validate_sns_url("https://sns.us-east-1.amazonaws.com/cert.pem").is_ok());
+        assert!(validate_sns_url("https://sns.cn-north-1.amazonaws.com.cn/cert.pem").is_ok());
+        assert!(validate_sns_url("http://sns.us-east-1.amazonaws.com/cert.pem").is_err());
+        assert!(validate_sns_url("https://sns.us-east-1.amazonaws.com.evil.com/cert.pem").is_err());
+        assert!(validate_sns_url("https://example.com/cert.pem").is_err());
+    }
+
+    #[test]
+    fn test_ec2_event_from_message() -> Result<(), Error> {
+        let state_change = r#"{
+            "version": "0",
+            "detail-type": "EC2 Instance State-change Notification",
+            "source": "aws.ec2",
+            "region": "us-east-1",
+            "detail": {"instance-id": "i-0123456789abcdef0", "state": "stopping"}
+        }"#;
+        let event = Ec2Event::from_message(state_change)?.unwrap();
+        assert_eq!(event.instance_id(), "i-0123456789abcdef0");
+        assert_eq!(event.description(), "i-0123456789abcdef0 is stopping");
+
+        let interruption = r#"{
+            "detail-type": "EC2 Spot Instance Interruption Warning",
+            "detail": {"instance-id": "i-0123456789abcdef0", "instance-action": "terminate"}
+        }"#;
+        assert_eq!(
+            Ec2Event::from_message(interruption)?,
+            Some(Ec2Event::SpotInterruption {
+                instance_id: "i-0123456789abcdef0".into(),
+                action: "terminate".into()
+            })
+        );
+
+        let other = r#"{"detail-type": "AWS API Call via CloudTrail", "detail": {}}"#;
+        assert_eq!(Ec2Event::from_message(other)?, None);
+        let missing = r#"{"detail-type": "EC2 Instance State-change Notification", "detail": {}}"#;
+        assert!(Ec2Event::from_message(missing).is_err());
+        assert!(Ec2Event::from_message("not json").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ecr_push_event_from_message() -> Result<(), Error> {
+        let push = r#"{
+            "detail-type": "ECR Image Action",
+            "source": "aws.ecr",
+            "detail": {
+                "result": "SUCCESS",
+                "repository-name": "app",
+                "image-digest": "sha256:aaaa",
+                "action-type": "PUSH",
+                "image-tag": "latest"
+            }
+        }"#;
+        assert_eq!(
+            EcrPushEvent::from_message(push)?,
+            Some(EcrPushEvent {
+                repository: "app".into(),
+                digest: "sha256:aaaa".into(),
+                tag: Some("latest".into()),
+            })
+        );
+        let delete = push.replace("PUSH", "DELETE");
+        assert_eq!(EcrPushEvent::from_message(&delete)?, None);
+        let failed = push.replace("SUCCESS", "FAILURE");
+        assert_eq!(EcrPushEvent::from_message(&failed)?, None);
+        let state_change =
+            r#"{"detail-type": "EC2 Instance State-change Notification", "detail": {}}"#;
+        assert_eq!(EcrPushEvent::from_message(state_change)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_s3_object_created_from_message() -> Result<(), Error> {
+        let created = r#"{"Records": [{
+            "eventVersion": "2.1",
+            "eventSource": "aws:s3",
+            "eventName": "ObjectCreated:Put",
+            "s3": {
+                "bucket": {"name": "inbound-email"},
+                "object": {"key": "inbound-email/abc+def%3D1", "size": 1024}
+            }
+        }]}"#;
+        assert_eq!(
+            S3ObjectCreated::from_message(created)?,
+            vec![S3ObjectCreated {
+                bucket: "inbound-email".into(),
+                key: "inbound-email/abc def=1".into(),
+            }]
+        );
+        let removed = created.replace("ObjectCreated:Put", "ObjectRemoved:Delete");
+        assert!(S3ObjectCreated::from_message(&removed)?.is_empty());
+        let test_event = r#"{"Service": "Amazon S3", "Event": "s3:TestEvent", "Bucket": "b"}"#;
+        assert!(S3ObjectCreated::from_message(test_event)?.is_empty());
+        assert!(S3ObjectCreated::from_message("not json").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::Error;
+use serde::Serialize;
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+
+use crate::{
+    models::InboundEmailDB,
+    storage::{InboundEmailRepo, Storage},
+};
+
+/// Emails scoring at least this much are treated as spam unless a user has
+/// marked them otherwise
+pub const SPAM_THRESHOLD: f64 = 5.0;
+
+/// Cap on what keyword matches alone can contribute
+const MAX_KEYWORD_SCORE: f64 = 4.0;
+
+/// Score added when a previous email from the same sender was marked as spam
+const KNOWN_SPAM_SENDER_SCORE: f64 = 5.0;
+
+const SPAM_KEYWORDS: [(&str, f64); 14] = [
+    ("act now", 1.0),
+    ("bitcoin", 1.0),
+    ("click here", 0.5),
+    ("congratulations", 0.5),
+    ("crypto", 0.5),
+    ("free money", 2.0),
+    ("gift card", 1.5),
+    ("limited time", 1.0),
+    ("lottery", 2.0),
+    ("urgent", 0.5),
+    ("verify your account", 1.5),
+    ("viagra", 3.0),
+    ("winner", 1.0),
+    ("wire transfer", 1.5),
+];
+
+/// Verdicts SES records in the `X-SES-*-Verdict` and `Authentication-Results`
+/// headers of received mail
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Verdicts {
+    pub spf: Option<StackString>,
+    pub dkim: Option<StackString>,
+    pub dmarc: Option<StackString>,
+    pub spam: Option<StackString>,
+    pub virus: Option<StackString>,
+}
+
+impl fmt::Display for Verdicts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let verdicts = [
+            ("spf", &self.spf),
+            ("dkim", &self.dkim),
+            ("dmarc", &self.dmarc),
+            ("spam", &self.spam),
+            ("virus", &self.virus),
+        ];
+        let mut first = true;
+        for (name, verdict) in verdicts {
+            if let Some(verdict) = verdict {
+                if !first {
+                    f.write_str(" ")?;
+                }
+                write!(f, "{name}={verdict}")?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Verdicts {
+    #[must_use]
+    pub fn from_raw_email(raw_email: &str) -> Self {
+        let headers = parse_headers(raw_email);
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        };
+        let normalize = |v: &str| -> StackString { v.trim().to_lowercase().into() };
+        let mut verdicts = Self {
+            spam: header("X-SES-Spam-Verdict").map(normalize),
+            virus: header("X-SES-Virus-Verdict").map(normalize),
+            ..Self::default()
+        };
+        if let Some(results) = header("Authentication-Results") {
+            for result in results.split(';') {
+                let mut fields = result.split_whitespace();
+                let verdict = fields.next().and_then(|f| f.split_once('='));
+                if let Some((method, value)) = verdict {
+                    let value = Some(normalize(value));
+                    match method.to_lowercase().as_str() {
+                        "spf" => verdicts.spf = value,
+                        "dkim" => verdicts.dkim = value,
+                        "dmarc" => verdicts.dmarc = value,
+                        _ => (),
+                    }
+                }
+            }
+        }
+        if verdicts.spf.is_none() {
+            verdicts.spf = header("Received-SPF")
+                .and_then(|v| v.split_whitespace().next())
+                .map(normalize);
+        }
+        verdicts
+    }
+
+    fn score(&self) -> f64 {
+        let is = |v: &Option<StackString>, values: &[&str]| {
+            v.as_ref().map_or(false, |v| values.contains(&v.as_str()))
+        };
+        let mut score = 0.0;
+        if is(&self.spam, &["fail"]) {
+            score += 5.0;
+        }
+        if is(&self.virus, &["fail"]) {
+            score += 10.0;
+        }
+        if is(&self.spf, &["fail"]) {
+            score += 2.0;
+        } else if is(&self.spf, &["softfail", "none"]) {
+            score += 1.0;
+        }
+        if is(&self.dkim, &["fail"]) {
+            score += 2.0;
+        }
+        if is(&self.dmarc, &["fail"]) {
+            score += 3.0;
+        }
+        score
+    }
+}
+
+/// Unfolded `(name, value)` pairs from the header block of a raw message
+fn parse_headers(raw_email: &str) -> Vec<(StackString, String)> {
+    let mut headers: Vec<(StackString, String)> = Vec::new();
+    for line in raw_email.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().into(), value.trim().into()));
+        }
+    }
+    headers
+}
+
+fn keyword_score(subject: &str, text: &str) -> f64 {
+    let subject = subject.to_lowercase();
+    let text = text.to_lowercase();
+    let score: f64 = SPAM_KEYWORDS
+        .iter()
+        .map(|(keyword, weight)| {
+            let mut score = 0.0;
+            if subject.contains(keyword) {
+                score += 2.0 * weight;
+            }
+            if text.contains(keyword) {
+                score += weight;
+            }
+            score
+        })
+        .sum();
+    score.min(MAX_KEYWORD_SCORE)
+}
+
+/// Spam score of a message, higher is more likely spam
+#[must_use]
+pub fn spam_score(verdicts: &Verdicts, subject: &str, text: &str, known_spam_sender: bool) -> f64 {
+    let mut score = verdicts.score() + keyword_score(subject, text);
+    if known_spam_sender {
+        score += KNOWN_SPAM_SENDER_SCORE;
+    }
+    score
+}
+
+/// Set `spam_score` and `verdicts` of `email` from its headers and content
+/// # Errors
+/// Returns error if db query fails
+pub async fn classify_email(
+    email: &mut InboundEmailDB,
+    storage: &dyn Storage,
+) -> Result<(), Error> {
+    let verdicts = Verdicts::from_raw_email(&email.raw_email);
+    let known_spam_sender = storage.is_known_spam_sender(&email.from_address).await?;
+    email.spam_score = spam_score(
+        &verdicts,
+        &email.subject,
+        &email.text_content,
+        known_spam_sender,
+    );
+    email.verdicts = Some(format_sstr!("{verdicts}"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spam_filter::{spam_score, Verdicts, SPAM_THRESHOLD};
+
+    const SES_HEADERS: &str = "\
+Return-Path: <sender@example.com>
+Received: from mail.example.com by inbound-smtp.us-east-1.amazonaws.com
+Authentication-Results: amazonses.com;
+ spf=pass (spf: domain of example.com designates 192.0.2.1 as permitted sender) \
+smtp.mailfrom=example.com;
+ dkim=fail header.i=@example.com;
+ dmarc=fail header.from=example.com;
+X-SES-Spam-Verdict: PASS
+X-SES-Virus-Verdict: PASS
+Subject: You are a WINNER
+
+Claim your lottery prize, click here
+";
+
+    #[test]
+    fn test_verdicts_from_raw_email() {
+        let verdicts = Verdicts::from_raw_email(SES_HEADERS);
+        assert_eq!(verdicts.spf.as_deref(), Some("pass"));
+        assert_eq!(verdicts.dkim.as_deref(), Some("fail"));
+        assert_eq!(verdicts.dmarc.as_deref(), Some("fail"));
+        assert_eq!(verdicts.spam.as_deref(), Some("pass"));
+        assert_eq!(verdicts.virus.as_deref(), Some("pass"));
+        assert_eq!(
+            verdicts.to_string(),
+            "spf=pass dkim=fail dmarc=fail spam=pass virus=pass"
+        );
+        assert_eq!(
+            Verdicts::from_raw_email("Subject: hi\n\nbody"),
+            Verdicts::default()
+        );
+    }
+
+    #[test]
+    fn test_spam_score() {
+        let verdicts = Verdicts::from_raw_email(SES_HEADERS);
+        let score = spam_score(
+            &verdicts,
+            "You are a WINNER",
+            "Claim your lottery prize, click here",
+            false,
+        );
+        assert!(score >= SPAM_THRESHOLD);
+
+        let clean = Verdicts::from_raw_email("Authentication-Results: amazonses.com; spf=pass;\n");
+        let score = spam_score(&clean, "Meeting notes", "See attached", false);
+        assert!(score < SPAM_THRESHOLD);
+        let score = spam_score(&clean, "Meeting notes", "See attached", true);
+        assert!(score >= SPAM_THRESHOLD);
+    }
+}
@@ -3,7 +3,10 @@ use clap::Parser;
 use stack_string::StackString;
 use std::{collections::HashMap, path::PathBuf};
 
-use crate::{config::Config, ec2_instance::SpotRequest};
+use crate::{
+    config::Config,
+    ec2_instance::{DataVolume, InstanceTenancy, SpotRequest, VolumeSpec},
+};
 
 #[derive(Debug, Clone, Parser)]
 pub struct SpotRequestOpt {
@@ -21,6 +24,48 @@ pub struct SpotRequestOpt {
     tags: Vec<StackString>,
     #[clap(short, long)]
     key_name: Option<StackString>,
+    #[clap(short, long, default_value = "1")]
+    /// Number of instances to request
+    count: usize,
+    #[clap(long, use_value_delimiter = true, value_delimiter = ',')]
+    /// Further instance types to spread the request across
+    extra_instance_types: Vec<StackString>,
+    #[clap(long, use_value_delimiter = true, value_delimiter = ',')]
+    /// Availability zones to spread the request across
+    availability_zones: Vec<StackString>,
+    #[clap(long)]
+    /// Maximum combined hourly price of all instances
+    max_total_price: Option<f32>,
+    #[clap(long)]
+    /// Re-request instances terminated by aws
+    auto_recover: bool,
+    #[clap(long, default_value = "default")]
+    /// Instance tenancy, default or dedicated
+    tenancy: InstanceTenancy,
+    #[clap(long)]
+    /// Launch ebs-optimized instances
+    ebs_optimized: bool,
+    #[clap(long)]
+    /// Root volume size in GB, defaults to the ami's
+    root_volume_size: Option<i32>,
+    #[clap(long)]
+    /// Root volume type, e.g. gp3
+    root_volume_type: Option<StackString>,
+    #[clap(long)]
+    /// Provisioned iops of the root volume (gp3/io1/io2)
+    root_volume_iops: Option<i32>,
+    #[clap(long = "data-volume")]
+    /// Additional volume as DEVICE:SIZE[:TYPE][:keep], e.g. /dev/sdf:100:gp3
+    data_volumes: Vec<DataVolume>,
+    #[clap(long)]
+    /// Name or arn of the instance profile to attach
+    instance_profile: Option<StackString>,
+    #[clap(long)]
+    /// Only allow metadata requests with a session token (IMDSv2)
+    require_imdsv2: bool,
+    #[clap(long)]
+    /// Route53 host name to point at the instance once it is running
+    dns_name: Option<StackString>,
 }
 
 impl SpotRequestOpt {
@@ -43,6 +88,22 @@ impl SpotRequestOpt {
             key_name,
             price: self.price,
             tags: get_tags(&self.tags),
+            count: self.count,
+            extra_instance_types: self.extra_instance_types,
+            availability_zones: self.availability_zones,
+            max_total_price: self.max_total_price,
+            auto_recover: self.auto_recover,
+            tenancy: self.tenancy,
+            ebs_optimized: self.ebs_optimized,
+            root_volume: VolumeSpec::from_options(
+                self.root_volume_size,
+                self.root_volume_type,
+                self.root_volume_iops,
+            ),
+            data_volumes: self.data_volumes,
+            instance_profile: self.instance_profile,
+            require_imdsv2: self.require_imdsv2,
+            dns_name: self.dns_name,
         })
     }
 }
@@ -0,0 +1,2965 @@
+use anyhow::Error;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use rusqlite::{params, types::Type, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use stack_string::StackString;
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use time::{Duration, OffsetDateTime};
+use tokio::task::spawn_blocking;
+use uuid::Uuid;
+
+use crate::{
+    dr_policy::DrPolicy,
+    ecr_history::EcrImageHistory,
+    inventory::InventorySnapshot,
+    models::{
+        AmiCatalogEntry, AuditLog, AuthorizedUsers, DmarcRecords, EmailAttachment,
+        EmailForwardRule, InboundEmailBucketKey, InboundEmailDB, InstanceFamily, InstanceList,
+        InstancePricing, LaunchAnalytics, LaunchCount, LaunchHistory, PriceHistory,
+        ProtectedResource, SentEmail, UpdateStatus, WeeklyLaunches, SPOT_PENDING_STATUSES,
+    },
+    price_alert::PriceAlert,
+    replacement::{InstanceReplacement, ReplacementState},
+    schema::latest_migration,
+    storage::{
+        AmiCatalogRepo, AuditLogRepo, AuthorizedUsersRepo, DmarcRecordsRepo, DrPolicyRepo,
+        EcrHistoryRepo, EmailAttachmentRepo, EmailForwardRuleRepo, InboundEmailRepo,
+        InstanceFamilyRepo, InstanceListRepo, InstancePricingRepo, InstanceReplacementRepo,
+        InventorySnapshotRepo, LaunchHistoryRepo, PriceAlertRepo, ProtectedResourceRepo,
+        SchemaRepo, SentEmailRepo, UpdateStatusRepo, UserPreferencesRepo, WebhookRepo,
+    },
+    user_preferences::UserPreferences,
+    webhook::{Webhook, WebhookDelivery, DELIVERY_PENDING},
+};
+
+/// Every table of the migrations at their latest version, timestamps are unix
+/// seconds, uuids text and json columns serialized json
+const SQLITE_SCHEMA: &str = r"
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS instance_family (
+        family_name TEXT PRIMARY KEY NOT NULL,
+        family_type TEXT NOT NULL,
+        data_url TEXT,
+        use_for_spot INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS instance_list (
+        instance_type TEXT PRIMARY KEY NOT NULL,
+        family_name TEXT NOT NULL,
+        n_cpu INTEGER NOT NULL,
+        memory_gib REAL NOT NULL,
+        generation TEXT NOT NULL,
+        gpu_count INTEGER NOT NULL DEFAULT 0,
+        gpu_type TEXT,
+        network_performance TEXT
+    );
+    CREATE TABLE IF NOT EXISTS instance_pricing (
+        id TEXT PRIMARY KEY NOT NULL,
+        instance_type TEXT NOT NULL,
+        price REAL NOT NULL,
+        price_type TEXT NOT NULL,
+        price_timestamp INTEGER NOT NULL,
+        UNIQUE (instance_type, price_type)
+    );
+    CREATE TABLE IF NOT EXISTS price_history (
+        id TEXT PRIMARY KEY NOT NULL,
+        instance_type TEXT NOT NULL,
+        price_type TEXT NOT NULL,
+        price REAL NOT NULL,
+        price_timestamp INTEGER NOT NULL,
+        recorded_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS price_history_instance_type_idx
+        ON price_history (instance_type, recorded_at);
+    CREATE TABLE IF NOT EXISTS authorized_users (
+        email TEXT PRIMARY KEY NOT NULL,
+        telegram_userid INTEGER,
+        created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+        deleted_at INTEGER,
+        theme TEXT
+    );
+    CREATE TABLE IF NOT EXISTS inbound_email (
+        id TEXT PRIMARY KEY NOT NULL,
+        s3_bucket TEXT NOT NULL,
+        s3_key TEXT NOT NULL,
+        from_address TEXT NOT NULL,
+        to_address TEXT NOT NULL,
+        subject TEXT NOT NULL,
+        date INTEGER NOT NULL,
+        text_content TEXT NOT NULL,
+        html_content TEXT NOT NULL,
+        raw_email TEXT NOT NULL,
+        spam_score REAL NOT NULL DEFAULT 0,
+        verdicts TEXT,
+        spam_feedback INTEGER,
+        message_id TEXT,
+        in_reply_to TEXT,
+        thread_id TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS inbound_email_message_id_idx ON inbound_email (message_id);
+    CREATE INDEX IF NOT EXISTS inbound_email_thread_id_idx ON inbound_email (thread_id);
+    CREATE TABLE IF NOT EXISTS dmarc_records (
+        id TEXT PRIMARY KEY NOT NULL,
+        s3_key TEXT,
+        org_name TEXT,
+        email TEXT,
+        report_id TEXT,
+        date_range_begin INTEGER,
+        date_range_end INTEGER,
+        policy_domain TEXT,
+        source_ip TEXT,
+        count INTEGER,
+        auth_result_type TEXT,
+        auth_result_domain TEXT,
+        auth_result_result TEXT,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS launch_history (
+        id TEXT PRIMARY KEY NOT NULL,
+        instance_id TEXT,
+        spot_request_id TEXT,
+        instance_type TEXT NOT NULL,
+        ami TEXT NOT NULL,
+        is_spot INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        launched_at INTEGER NOT NULL,
+        terminated_at INTEGER,
+        launch_params TEXT,
+        auto_recover INTEGER NOT NULL DEFAULT 0,
+        recovery_attempts INTEGER NOT NULL DEFAULT 0,
+        recovery_status TEXT,
+        setup_status TEXT,
+        setup_seconds INTEGER,
+        setup_message TEXT,
+        dns_name TEXT,
+        dns_registered_at INTEGER,
+        fulfilled_at INTEGER,
+        running_at INTEGER,
+        cloud_init_at INTEGER,
+        healthy_at INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS launch_history_instance_id_idx ON launch_history (instance_id);
+    CREATE INDEX IF NOT EXISTS launch_history_launched_at_idx ON launch_history (launched_at);
+    CREATE TABLE IF NOT EXISTS ami_catalog (
+        ami_id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        base_release TEXT,
+        build_script TEXT,
+        build_date INTEGER NOT NULL,
+        parent_ami TEXT,
+        instance_id TEXT
+    );
+    CREATE TABLE IF NOT EXISTS protected_resource (
+        resource_id TEXT PRIMARY KEY NOT NULL,
+        reason TEXT,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS email_attachment (
+        id TEXT PRIMARY KEY NOT NULL,
+        email_id TEXT NOT NULL,
+        filename TEXT NOT NULL,
+        content_type TEXT,
+        size INTEGER NOT NULL,
+        s3_bucket TEXT NOT NULL,
+        s3_key TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        UNIQUE (email_id, s3_key)
+    );
+    CREATE TABLE IF NOT EXISTS email_forward_rule (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        recipient_pattern TEXT,
+        subject_pattern TEXT,
+        forward_to TEXT NOT NULL,
+        enabled INTEGER NOT NULL DEFAULT 1,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sent_email (
+        id TEXT PRIMARY KEY NOT NULL,
+        email_id TEXT NOT NULL,
+        thread_id TEXT NOT NULL,
+        from_address TEXT NOT NULL,
+        to_address TEXT NOT NULL,
+        subject TEXT NOT NULL,
+        body TEXT NOT NULL,
+        message_id TEXT,
+        sent_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS sent_email_thread_id_idx ON sent_email (thread_id);
+    CREATE TABLE IF NOT EXISTS audit_log (
+        id TEXT PRIMARY KEY NOT NULL,
+        action TEXT NOT NULL,
+        resource TEXT NOT NULL,
+        details TEXT,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS audit_log_created_at_idx ON audit_log (created_at);
+    CREATE TABLE IF NOT EXISTS update_status (
+        task TEXT PRIMARY KEY NOT NULL,
+        last_attempt INTEGER NOT NULL,
+        last_success INTEGER,
+        last_error TEXT
+    );
+    CREATE TABLE IF NOT EXISTS webhooks (
+        id TEXT PRIMARY KEY NOT NULL,
+        url TEXT NOT NULL,
+        secret TEXT NOT NULL,
+        events TEXT NOT NULL,
+        enabled INTEGER NOT NULL DEFAULT 1,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS webhook_deliveries (
+        id TEXT PRIMARY KEY NOT NULL,
+        webhook_id TEXT NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+        event TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        status TEXT NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        response_code INTEGER,
+        last_error TEXT,
+        next_attempt_at INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        delivered_at INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS webhook_deliveries_pending_idx
+        ON webhook_deliveries (status, next_attempt_at);
+    CREATE TABLE IF NOT EXISTS price_alerts (
+        id TEXT PRIMARY KEY NOT NULL,
+        instance_type TEXT NOT NULL,
+        region TEXT NOT NULL,
+        threshold REAL NOT NULL,
+        enabled INTEGER NOT NULL DEFAULT 1,
+        last_price REAL,
+        triggered_at INTEGER,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS dr_policies (
+        id TEXT PRIMARY KEY NOT NULL,
+        volume_name TEXT NOT NULL,
+        target_region TEXT NOT NULL,
+        encrypted INTEGER NOT NULL DEFAULT 0,
+        kms_key_id TEXT,
+        max_age_hours INTEGER NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS ecr_image_history (
+        id TEXT PRIMARY KEY NOT NULL,
+        repo TEXT NOT NULL,
+        digest TEXT NOT NULL,
+        tags TEXT NOT NULL,
+        image_size REAL NOT NULL,
+        pushed_at INTEGER NOT NULL,
+        recorded_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS ecr_image_history_repo_idx
+        ON ecr_image_history (repo, digest, recorded_at);
+    CREATE TABLE IF NOT EXISTS user_preferences (
+        email TEXT PRIMARY KEY NOT NULL,
+        hidden_columns TEXT NOT NULL,
+        default_sort TEXT,
+        sort_descending INTEGER NOT NULL DEFAULT 0,
+        rows_per_page INTEGER,
+        default_region TEXT,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS inventory_snapshot (
+        id TEXT PRIMARY KEY NOT NULL,
+        region TEXT NOT NULL,
+        resources TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS inventory_snapshot_region_created_at_idx
+        ON inventory_snapshot (region, created_at);
+    CREATE TABLE IF NOT EXISTS instance_replacement (
+        id TEXT PRIMARY KEY NOT NULL,
+        old_instance_id TEXT NOT NULL,
+        new_instance_id TEXT,
+        spot_request_id TEXT,
+        ami TEXT NOT NULL,
+        dns_name TEXT,
+        terminate_old INTEGER NOT NULL,
+        soak_minutes INTEGER NOT NULL,
+        state TEXT NOT NULL,
+        message TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        dns_swapped_at INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS instance_replacement_state_idx ON instance_replacement (state);
+";
+
+/// Single file storage for running without a Postgres server, timestamps are
+/// stored as unix seconds
+#[derive(Clone)]
+pub struct SqliteStore {
+    path: Arc<PathBuf>,
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl fmt::Debug for SqliteStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SqliteStore {}", self.path.display())
+    }
+}
+
+impl SqliteStore {
+    /// Open (creating if needed) the database at `path`
+    /// # Errors
+    /// Returns error if the database can't be opened or created
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::from_connection(Connection::open(path)?, path)
+    }
+
+    /// # Errors
+    /// Returns error if the schema can't be created
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::from_connection(Connection::open_in_memory()?, Path::new(":memory:"))
+    }
+
+    fn from_connection(conn: Connection, path: &Path) -> Result<Self, Error> {
+        conn.execute_batch(SQLITE_SCHEMA)?;
+        Ok(Self {
+            path: Arc::new(path.to_path_buf()),
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    async fn with_conn<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        spawn_blocking(move || f(&mut conn.lock())).await?
+    }
+}
+
+fn from_timestamp(timestamp: i64) -> rusqlite::Result<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp(timestamp)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Integer, e.into()))
+}
+
+fn parse_uuid(id: &str) -> rusqlite::Result<Uuid> {
+    id.parse().map_err(|e: uuid::Error| {
+        rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.into())
+    })
+}
+
+fn get_str(row: &Row, column: &str) -> rusqlite::Result<StackString> {
+    row.get::<_, String>(column).map(Into::into)
+}
+
+fn get_opt_str(row: &Row, column: &str) -> rusqlite::Result<Option<StackString>> {
+    Ok(row.get::<_, Option<String>>(column)?.map(Into::into))
+}
+
+fn get_uuid(row: &Row, column: &str) -> rusqlite::Result<Uuid> {
+    parse_uuid(&row.get::<_, String>(column)?)
+}
+
+fn get_timestamp(row: &Row, column: &str) -> rusqlite::Result<OffsetDateTime> {
+    from_timestamp(row.get(column)?)
+}
+
+fn get_opt_timestamp(row: &Row, column: &str) -> rusqlite::Result<Option<OffsetDateTime>> {
+    row.get::<_, Option<i64>>(column)?
+        .map(from_timestamp)
+        .transpose()
+}
+
+fn parse_json<T: DeserializeOwned>(json: &str) -> rusqlite::Result<T> {
+    serde_json::from_str(json)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.into()))
+}
+
+fn get_json<T: DeserializeOwned>(row: &Row, column: &str) -> rusqlite::Result<T> {
+    parse_json(&row.get::<_, String>(column)?)
+}
+
+fn opt_timestamp(timestamp: Option<OffsetDateTime>) -> Option<i64> {
+    timestamp.map(OffsetDateTime::unix_timestamp)
+}
+
+fn family_from_row(row: &Row) -> rusqlite::Result<InstanceFamily> {
+    Ok(InstanceFamily {
+        family_name: get_str(row, "family_name")?,
+        family_type: get_str(row, "family_type")?,
+        data_url: get_opt_str(row, "data_url")?,
+        use_for_spot: row.get("use_for_spot")?,
+    })
+}
+
+fn instance_from_row(row: &Row) -> rusqlite::Result<InstanceList> {
+    Ok(InstanceList {
+        instance_type: get_str(row, "instance_type")?,
+        family_name: get_str(row, "family_name")?,
+        n_cpu: row.get("n_cpu")?,
+        memory_gib: row.get("memory_gib")?,
+        generation: get_str(row, "generation")?,
+        gpu_count: row.get("gpu_count")?,
+        gpu_type: get_opt_str(row, "gpu_type")?,
+        network_performance: get_opt_str(row, "network_performance")?,
+    })
+}
+
+fn pricing_from_row(row: &Row) -> rusqlite::Result<InstancePricing> {
+    Ok(InstancePricing {
+        id: get_uuid(row, "id")?,
+        instance_type: get_str(row, "instance_type")?,
+        price: row.get("price")?,
+        price_type: get_str(row, "price_type")?,
+        price_timestamp: get_timestamp(row, "price_timestamp")?,
+    })
+}
+
+fn history_from_row(row: &Row) -> rusqlite::Result<PriceHistory> {
+    Ok(PriceHistory {
+        id: get_uuid(row, "id")?,
+        instance_type: get_str(row, "instance_type")?,
+        price_type: get_str(row, "price_type")?,
+        price: row.get("price")?,
+        price_timestamp: get_timestamp(row, "price_timestamp")?,
+        recorded_at: get_timestamp(row, "recorded_at")?,
+    })
+}
+
+fn user_from_row(row: &Row) -> rusqlite::Result<AuthorizedUsers> {
+    Ok(AuthorizedUsers {
+        email: get_str(row, "email")?,
+        telegram_userid: row.get("telegram_userid")?,
+        created_at: get_timestamp(row, "created_at")?,
+    })
+}
+
+fn email_from_row(row: &Row) -> rusqlite::Result<InboundEmailDB> {
+    Ok(InboundEmailDB {
+        id: get_uuid(row, "id")?,
+        s3_bucket: get_str(row, "s3_bucket")?,
+        s3_key: get_str(row, "s3_key")?,
+        from_address: get_str(row, "from_address")?,
+        to_address: get_str(row, "to_address")?,
+        subject: get_str(row, "subject")?,
+        date: get_timestamp(row, "date")?,
+        text_content: get_str(row, "text_content")?,
+        html_content: get_str(row, "html_content")?,
+        raw_email: get_str(row, "raw_email")?,
+        spam_score: row.get("spam_score")?,
+        verdicts: get_opt_str(row, "verdicts")?,
+        spam_feedback: row.get("spam_feedback")?,
+        message_id: get_opt_str(row, "message_id")?,
+        in_reply_to: get_opt_str(row, "in_reply_to")?,
+        thread_id: get_uuid(row, "thread_id")?,
+    })
+}
+
+fn launch_from_row(row: &Row) -> rusqlite::Result<LaunchHistory> {
+    Ok(LaunchHistory {
+        id: get_uuid(row, "id")?,
+        instance_id: get_opt_str(row, "instance_id")?,
+        spot_request_id: get_opt_str(row, "spot_request_id")?,
+        instance_type: get_str(row, "instance_type")?,
+        ami: get_str(row, "ami")?,
+        is_spot: row.get("is_spot")?,
+        status: get_str(row, "status")?,
+        launched_at: get_timestamp(row, "launched_at")?,
+        terminated_at: get_opt_timestamp(row, "terminated_at")?,
+        launch_params: row
+            .get::<_, Option<String>>("launch_params")?
+            .map(|p| parse_json(&p))
+            .transpose()?,
+        auto_recover: row.get("auto_recover")?,
+        recovery_attempts: row.get("recovery_attempts")?,
+        recovery_status: get_opt_str(row, "recovery_status")?,
+        setup_status: get_opt_str(row, "setup_status")?,
+        setup_seconds: row.get("setup_seconds")?,
+        setup_message: get_opt_str(row, "setup_message")?,
+        dns_name: get_opt_str(row, "dns_name")?,
+        dns_registered_at: get_opt_timestamp(row, "dns_registered_at")?,
+        fulfilled_at: get_opt_timestamp(row, "fulfilled_at")?,
+        running_at: get_opt_timestamp(row, "running_at")?,
+        cloud_init_at: get_opt_timestamp(row, "cloud_init_at")?,
+        healthy_at: get_opt_timestamp(row, "healthy_at")?,
+    })
+}
+
+fn launch_count_from_row(row: &Row) -> rusqlite::Result<LaunchCount> {
+    Ok(LaunchCount {
+        key: get_str(row, "key")?,
+        count: row.get("count")?,
+    })
+}
+
+fn ami_from_row(row: &Row) -> rusqlite::Result<AmiCatalogEntry> {
+    Ok(AmiCatalogEntry {
+        ami_id: get_str(row, "ami_id")?,
+        name: get_str(row, "name")?,
+        base_release: get_opt_str(row, "base_release")?,
+        build_script: get_opt_str(row, "build_script")?,
+        build_date: get_timestamp(row, "build_date")?,
+        parent_ami: get_opt_str(row, "parent_ami")?,
+        instance_id: get_opt_str(row, "instance_id")?,
+    })
+}
+
+fn protected_from_row(row: &Row) -> rusqlite::Result<ProtectedResource> {
+    Ok(ProtectedResource {
+        resource_id: get_str(row, "resource_id")?,
+        reason: get_opt_str(row, "reason")?,
+        created_at: get_timestamp(row, "created_at")?,
+    })
+}
+
+fn attachment_from_row(row: &Row) -> rusqlite::Result<EmailAttachment> {
+    Ok(EmailAttachment {
+        id: get_uuid(row, "id")?,
+        email_id: get_uuid(row, "email_id")?,
+        filename: get_str(row, "filename")?,
+        content_type: get_opt_str(row, "content_type")?,
+        size: row.get("size")?,
+        s3_bucket: get_str(row, "s3_bucket")?,
+        s3_key: get_str(row, "s3_key")?,
+        created_at: get_timestamp(row, "created_at")?,
+    })
+}
+
+fn forward_rule_from_row(row: &Row) -> rusqlite::Result<EmailForwardRule> {
+    Ok(EmailForwardRule {
+        id: get_uuid(row, "id")?,
+        name: get_str(row, "name")?,
+        recipient_pattern: get_opt_str(row, "recipient_pattern")?,
+        subject_pattern: get_opt_str(row, "subject_pattern")?,
+        forward_to: get_str(row, "forward_to")?,
+        enabled: row.get("enabled")?,
+        created_at: get_timestamp(row, "created_at")?,
+    })
+}
+
+fn sent_email_from_row(row: &Row) -> rusqlite::Result<SentEmail> {
+    Ok(SentEmail {
+        id: get_uuid(row, "id")?,
+        email_id: get_uuid(row, "email_id")?,
+        thread_id: get_uuid(row, "thread_id")?,
+        from_address: get_str(row, "from_address")?,
+        to_address: get_str(row, "to_address")?,
+        subject: get_str(row, "subject")?,
+        body: get_str(row, "body")?,
+        message_id: get_opt_str(row, "message_id")?,
+        sent_at: get_timestamp(row, "sent_at")?,
+    })
+}
+
+fn audit_from_row(row: &Row) -> rusqlite::Result<AuditLog> {
+    Ok(AuditLog {
+        id: get_uuid(row, "id")?,
+        action: get_str(row, "action")?,
+        resource: get_str(row, "resource")?,
+        details: get_opt_str(row, "details")?,
+        created_at: get_timestamp(row, "created_at")?,
+    })
+}
+
+fn update_status_from_row(row: &Row) -> rusqlite::Result<UpdateStatus> {
+    Ok(UpdateStatus {
+        task: get_str(row, "task")?,
+        last_attempt: get_timestamp(row, "last_attempt")?,
+        last_success: get_opt_timestamp(row, "last_success")?,
+        last_error: get_opt_str(row, "last_error")?,
+    })
+}
+
+fn webhook_from_row(row: &Row) -> rusqlite::Result<Webhook> {
+    Ok(Webhook {
+        id: get_uuid(row, "id")?,
+        url: get_str(row, "url")?,
+        secret: get_str(row, "secret")?,
+        events: get_json(row, "events")?,
+        enabled: row.get("enabled")?,
+        created_at: get_timestamp(row, "created_at")?,
+    })
+}
+
+fn delivery_from_row(row: &Row) -> rusqlite::Result<WebhookDelivery> {
+    Ok(WebhookDelivery {
+        id: get_uuid(row, "id")?,
+        webhook_id: get_uuid(row, "webhook_id")?,
+        event: get_str(row, "event")?,
+        payload: get_json(row, "payload")?,
+        status: get_str(row, "status")?,
+        attempts: row.get("attempts")?,
+        response_code: row.get("response_code")?,
+        last_error: get_opt_str(row, "last_error")?,
+        next_attempt_at: get_timestamp(row, "next_attempt_at")?,
+        created_at: get_timestamp(row, "created_at")?,
+        delivered_at: get_opt_timestamp(row, "delivered_at")?,
+    })
+}
+
+fn price_alert_from_row(row: &Row) -> rusqlite::Result<PriceAlert> {
+    Ok(PriceAlert {
+        id: get_uuid(row, "id")?,
+        instance_type: get_str(row, "instance_type")?,
+        region: get_str(row, "region")?,
+        threshold: row.get("threshold")?,
+        enabled: row.get("enabled")?,
+        last_price: row.get("last_price")?,
+        triggered_at: get_opt_timestamp(row, "triggered_at")?,
+        created_at: get_timestamp(row, "created_at")?,
+    })
+}
+
+fn replacement_from_row(row: &Row) -> rusqlite::Result<InstanceReplacement> {
+    Ok(InstanceReplacement {
+        id: get_uuid(row, "id")?,
+        old_instance_id: get_str(row, "old_instance_id")?,
+        new_instance_id: get_opt_str(row, "new_instance_id")?,
+        spot_request_id: get_opt_str(row, "spot_request_id")?,
+        ami: get_str(row, "ami")?,
+        dns_name: get_opt_str(row, "dns_name")?,
+        terminate_old: row.get("terminate_old")?,
+        soak_minutes: row.get("soak_minutes")?,
+        state: get_str(row, "state")?,
+        message: get_opt_str(row, "message")?,
+        created_at: get_timestamp(row, "created_at")?,
+        updated_at: get_timestamp(row, "updated_at")?,
+        dns_swapped_at: get_opt_timestamp(row, "dns_swapped_at")?,
+    })
+}
+
+fn dr_policy_from_row(row: &Row) -> rusqlite::Result<DrPolicy> {
+    Ok(DrPolicy {
+        id: get_uuid(row, "id")?,
+        volume_name: get_str(row, "volume_name")?,
+        target_region: get_str(row, "target_region")?,
+        encrypted: row.get("encrypted")?,
+        kms_key_id: get_opt_str(row, "kms_key_id")?,
+        max_age_hours: row.get("max_age_hours")?,
+        created_at: get_timestamp(row, "created_at")?,
+    })
+}
+
+fn ecr_from_row(row: &Row) -> rusqlite::Result<EcrImageHistory> {
+    Ok(EcrImageHistory {
+        id: get_uuid(row, "id")?,
+        repo: get_str(row, "repo")?,
+        digest: get_str(row, "digest")?,
+        tags: get_json(row, "tags")?,
+        image_size: row.get("image_size")?,
+        pushed_at: get_timestamp(row, "pushed_at")?,
+        recorded_at: get_timestamp(row, "recorded_at")?,
+    })
+}
+
+fn preferences_from_row(row: &Row) -> rusqlite::Result<UserPreferences> {
+    Ok(UserPreferences {
+        email: get_str(row, "email")?,
+        hidden_columns: get_json(row, "hidden_columns")?,
+        default_sort: get_opt_str(row, "default_sort")?,
+        sort_descending: row.get("sort_descending")?,
+        rows_per_page: row.get("rows_per_page")?,
+        default_region: get_opt_str(row, "default_region")?,
+        updated_at: get_timestamp(row, "updated_at")?,
+    })
+}
+
+fn inventory_from_row(row: &Row) -> rusqlite::Result<InventorySnapshot> {
+    Ok(InventorySnapshot {
+        id: get_uuid(row, "id")?,
+        region: get_str(row, "region")?,
+        resources: get_json(row, "resources")?,
+        created_at: get_timestamp(row, "created_at")?,
+    })
+}
+
+#[async_trait]
+impl SchemaRepo for SqliteStore {
+    async fn schema_version(&self) -> Result<Option<i64>, Error> {
+        self.with_conn(|conn| {
+            conn.query_row("SELECT 1", [], |_| Ok(()))?;
+            Ok(latest_migration().map(|m| m.version))
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl InstanceFamilyRepo for SqliteStore {
+    async fn get_instance_families(
+        &self,
+        for_spot_instance: Option<bool>,
+    ) -> Result<Vec<InstanceFamily>, Error> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT * FROM instance_family
+                    WHERE ?1 IS NULL OR use_for_spot = ?1
+                    ORDER BY family_type, family_name
+                ",
+            )?;
+            let rows = stmt.query_map(params![for_spot_instance], family_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn upsert_instance_family(
+        &self,
+        family: &InstanceFamily,
+    ) -> Result<Option<InstanceFamily>, Error> {
+        let family = family.clone();
+        self.with_conn(move |conn| {
+            let tran = conn.transaction()?;
+            let existing = tran
+                .query_row(
+                    "SELECT * FROM instance_family WHERE family_name = ?1",
+                    params![family.family_name.as_str()],
+                    family_from_row,
+                )
+                .optional()?;
+            // use_for_spot is only ever changed by the user
+            tran.execute(
+                r"
+                    INSERT INTO instance_family (family_name, family_type, data_url)
+                    VALUES (?1, ?2, ?3)
+                    ON CONFLICT (family_name) DO UPDATE
+                    SET family_type = excluded.family_type, data_url = excluded.data_url
+                ",
+                params![
+                    family.family_name.as_str(),
+                    family.family_type.as_str(),
+                    family.data_url.as_deref(),
+                ],
+            )?;
+            tran.commit()?;
+            Ok(existing)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl InstanceListRepo for SqliteStore {
+    async fn get_instances(&self) -> Result<Vec<InstanceList>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM instance_list")?;
+            let rows = stmt.query_map([], instance_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_instance(&self, instance_type: &str) -> Result<Option<InstanceList>, Error> {
+        let instance_type: StackString = instance_type.into();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM instance_list WHERE instance_type = ?1",
+                params![instance_type.as_str()],
+                instance_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn upsert_instance(
+        &self,
+        instance: &InstanceList,
+    ) -> Result<Option<InstanceList>, Error> {
+        let instance = instance.clone();
+        self.with_conn(move |conn| {
+            let tran = conn.transaction()?;
+            let existing = tran
+                .query_row(
+                    "SELECT * FROM instance_list WHERE instance_type = ?1",
+                    params![instance.instance_type.as_str()],
+                    instance_from_row,
+                )
+                .optional()?;
+            tran.execute(
+                r"
+                    INSERT OR REPLACE INTO instance_list (
+                        instance_type, family_name, n_cpu, memory_gib, generation,
+                        gpu_count, gpu_type, network_performance
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                ",
+                params![
+                    instance.instance_type.as_str(),
+                    instance.family_name.as_str(),
+                    instance.n_cpu,
+                    instance.memory_gib,
+                    instance.generation.as_str(),
+                    instance.gpu_count,
+                    instance.gpu_type.as_deref(),
+                    instance.network_performance.as_deref(),
+                ],
+            )?;
+            tran.commit()?;
+            Ok(existing)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl InstancePricingRepo for SqliteStore {
+    async fn get_prices(&self) -> Result<Vec<InstancePricing>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM instance_pricing")?;
+            let rows = stmt.query_map([], pricing_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn upsert_price(
+        &self,
+        price: &InstancePricing,
+    ) -> Result<Option<InstancePricing>, Error> {
+        let price = price.clone();
+        let history = PriceHistory::from(&price);
+        self.with_conn(move |conn| {
+            let tran = conn.transaction()?;
+            let existing = tran
+                .query_row(
+                    r"
+                        SELECT * FROM instance_pricing
+                        WHERE instance_type = ?1 AND price_type = ?2
+                    ",
+                    params![price.instance_type.as_str(), price.price_type.as_str()],
+                    pricing_from_row,
+                )
+                .optional()?;
+            tran.execute(
+                r"
+                    INSERT INTO instance_pricing (
+                        id, instance_type, price, price_type, price_timestamp
+                    ) VALUES (?1, ?2, ?3, ?4, ?5)
+                    ON CONFLICT (instance_type, price_type) DO UPDATE
+                    SET price = excluded.price, price_timestamp = excluded.price_timestamp
+                ",
+                params![
+                    price.id.to_string(),
+                    price.instance_type.as_str(),
+                    price.price,
+                    price.price_type.as_str(),
+                    price.price_timestamp.unix_timestamp(),
+                ],
+            )?;
+            tran.execute(
+                r"
+                    INSERT INTO price_history (
+                        id, instance_type, price_type, price, price_timestamp, recorded_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ",
+                params![
+                    history.id.to_string(),
+                    history.instance_type.as_str(),
+                    history.price_type.as_str(),
+                    history.price,
+                    history.price_timestamp.unix_timestamp(),
+                    history.recorded_at.unix_timestamp(),
+                ],
+            )?;
+            tran.commit()?;
+            Ok(existing)
+        })
+        .await
+    }
+
+    async fn get_price_history(&self, instance_type: &str) -> Result<Vec<PriceHistory>, Error> {
+        let instance_type: StackString = instance_type.into();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT * FROM price_history WHERE instance_type = ?1 ORDER BY recorded_at",
+            )?;
+            let rows = stmt.query_map(params![instance_type.as_str()], history_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_price_type_history(
+        &self,
+        price_type: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<PriceHistory>, Error> {
+        let price_type: StackString = price_type.into();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT * FROM price_history
+                    WHERE price_type = ?1 AND recorded_at >= ?2
+                    ORDER BY recorded_at
+                ",
+            )?;
+            let rows = stmt.query_map(
+                params![price_type.as_str(), since.unix_timestamp()],
+                history_from_row,
+            )?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_prices_as_of(
+        &self,
+        timestamp: OffsetDateTime,
+    ) -> Result<Vec<PriceHistory>, Error> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT h.* FROM price_history h
+                    WHERE h.recorded_at = (
+                        SELECT max(recorded_at) FROM price_history
+                        WHERE instance_type = h.instance_type
+                          AND price_type = h.price_type
+                          AND recorded_at <= ?1
+                    )
+                    GROUP BY h.instance_type, h.price_type
+                ",
+            )?;
+            let rows = stmt.query_map(params![timestamp.unix_timestamp()], history_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn prune_price_history(&self, days: i64) -> Result<u64, Error> {
+        let cutoff = OffsetDateTime::now_utc() - Duration::days(days);
+        self.with_conn(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM price_history WHERE recorded_at < ?1",
+                params![cutoff.unix_timestamp()],
+            )?;
+            Ok(deleted as u64)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl AuthorizedUsersRepo for SqliteStore {
+    async fn get_authorized_users(&self) -> Result<Vec<AuthorizedUsers>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare("SELECT * FROM authorized_users WHERE deleted_at IS NULL")?;
+            let rows = stmt.query_map([], user_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_most_recent_user_change(&self) -> Result<Option<OffsetDateTime>, Error> {
+        self.with_conn(|conn| {
+            let (created_at, deleted_at): (Option<i64>, Option<i64>) = conn.query_row(
+                "SELECT max(created_at), max(deleted_at) FROM authorized_users",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            created_at
+                .max(deleted_at)
+                .map(from_timestamp)
+                .transpose()
+                .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_theme(&self, email: &str) -> Result<Option<StackString>, Error> {
+        let email: StackString = email.into();
+        self.with_conn(move |conn| {
+            let theme: Option<Option<String>> = conn
+                .query_row(
+                    "SELECT theme FROM authorized_users WHERE email = ?1 AND deleted_at IS NULL",
+                    params![email.as_str()],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(theme.flatten().map(Into::into))
+        })
+        .await
+    }
+
+    async fn set_theme(&self, email: &str, theme: &str) -> Result<u64, Error> {
+        let email: StackString = email.into();
+        let theme: StackString = theme.into();
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                "UPDATE authorized_users SET theme = ?2 WHERE email = ?1 AND deleted_at IS NULL",
+                params![email.as_str(), theme.as_str()],
+            )?;
+            Ok(updated as u64)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl InboundEmailRepo for SqliteStore {
+    async fn is_known_spam_sender(&self, from_address: &str) -> Result<bool, Error> {
+        let from_address: StackString = from_address.into();
+        self.with_conn(move |conn| {
+            let count: i64 = conn.query_row(
+                "SELECT count(*) FROM inbound_email WHERE from_address = ?1 AND spam_feedback",
+                params![from_address.as_str()],
+                |row| row.get(0),
+            )?;
+            Ok(count > 0)
+        })
+        .await
+    }
+
+    async fn set_spam_feedback(&self, id: Uuid, spam: bool) -> Result<u64, Error> {
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                "UPDATE inbound_email SET spam_feedback = ?2 WHERE id = ?1",
+                params![id.to_string(), spam],
+            )?;
+            Ok(updated as u64)
+        })
+        .await
+    }
+
+    async fn get_email_thread_id(&self, message_id: &str) -> Result<Option<Uuid>, Error> {
+        let message_id: StackString = message_id.into();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                r"
+                    SELECT thread_id FROM inbound_email
+                    WHERE message_id = ?1
+                    ORDER BY date
+                    LIMIT 1
+                ",
+                params![message_id.as_str()],
+                |row| get_uuid(row, "thread_id"),
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn merge_replies(&self, message_id: &str, thread_id: Uuid) -> Result<u64, Error> {
+        let message_id: StackString = message_id.into();
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                r"
+                    UPDATE inbound_email SET thread_id = ?2
+                    WHERE thread_id != ?2
+                      AND thread_id IN (
+                        SELECT thread_id FROM inbound_email
+                        WHERE in_reply_to = ?1
+                      )
+                ",
+                params![message_id.as_str(), thread_id.to_string()],
+            )?;
+            Ok(updated as u64)
+        })
+        .await
+    }
+
+    async fn get_email_keys(&self) -> Result<Vec<InboundEmailBucketKey>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT id, s3_bucket, s3_key FROM inbound_email")?;
+            let rows = stmt.query_map([], |row| {
+                Ok(InboundEmailBucketKey {
+                    id: get_uuid(row, "id")?,
+                    s3_bucket: get_str(row, "s3_bucket")?,
+                    s3_key: get_str(row, "s3_key")?,
+                })
+            })?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_emails(
+        &self,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<Vec<InboundEmailDB>, Error> {
+        // a negative limit is no limit in sqlite
+        let limit = limit.map_or(Ok(-1), i64::try_from)?;
+        let offset = offset.map_or(Ok(0), i64::try_from)?;
+        self.with_conn(move |conn| {
+            let mut stmt =
+                conn.prepare("SELECT * FROM inbound_email ORDER BY date LIMIT ?1 OFFSET ?2")?;
+            let rows = stmt.query_map(params![limit, offset], email_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_email(&self, id: Uuid) -> Result<Option<InboundEmailDB>, Error> {
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM inbound_email WHERE id = ?1",
+                params![id.to_string()],
+                email_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_email_by_bucket_key(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<InboundEmailDB>, Error> {
+        let bucket: StackString = bucket.into();
+        let key: StackString = key.into();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM inbound_email WHERE s3_bucket = ?1 AND s3_key = ?2",
+                params![bucket.as_str(), key.as_str()],
+                email_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn upsert_email(&self, email: &InboundEmailDB) -> Result<Option<InboundEmailDB>, Error> {
+        let email = email.clone();
+        self.with_conn(move |conn| {
+            let tran = conn.transaction()?;
+            let existing = tran
+                .query_row(
+                    "SELECT * FROM inbound_email WHERE id = ?1",
+                    params![email.id.to_string()],
+                    email_from_row,
+                )
+                .optional()?;
+            tran.execute(
+                r"
+                    INSERT OR REPLACE INTO inbound_email (
+                        id, s3_bucket, s3_key, from_address, to_address,
+                        subject, date, text_content, html_content, raw_email,
+                        spam_score, verdicts, spam_feedback, message_id, in_reply_to,
+                        thread_id
+                    ) VALUES (
+                        ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16
+                    )
+                ",
+                params![
+                    email.id.to_string(),
+                    email.s3_bucket.as_str(),
+                    email.s3_key.as_str(),
+                    email.from_address.as_str(),
+                    email.to_address.as_str(),
+                    email.subject.as_str(),
+                    email.date.unix_timestamp(),
+                    email.text_content.as_str(),
+                    email.html_content.as_str(),
+                    email.raw_email.as_str(),
+                    email.spam_score,
+                    email.verdicts.as_deref(),
+                    email.spam_feedback,
+                    email.message_id.as_deref(),
+                    email.in_reply_to.as_deref(),
+                    email.thread_id.to_string(),
+                ],
+            )?;
+            tran.commit()?;
+            Ok(existing)
+        })
+        .await
+    }
+
+    async fn delete_email(&self, id: Uuid) -> Result<(), Error> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "DELETE FROM inbound_email WHERE id = ?1",
+                params![id.to_string()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl DmarcRecordsRepo for SqliteStore {
+    async fn get_parsed_s3_keys(&self) -> Result<HashSet<StackString>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare("SELECT DISTINCT s3_key FROM dmarc_records WHERE s3_key IS NOT NULL")?;
+            let rows = stmt.query_map([], |row| get_str(row, "s3_key"))?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn delete_dmarc_records(&self, s3_key: &str) -> Result<u64, Error> {
+        let s3_key: StackString = s3_key.into();
+        self.with_conn(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM dmarc_records WHERE s3_key = ?1",
+                params![s3_key.as_str()],
+            )?;
+            Ok(deleted as u64)
+        })
+        .await
+    }
+
+    async fn insert_dmarc_record(&self, record: &DmarcRecords) -> Result<(), Error> {
+        let record = record.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO dmarc_records (
+                        id, s3_key, org_name, email, report_id, date_range_begin,
+                        date_range_end, policy_domain, source_ip, count,
+                        auth_result_type, auth_result_domain, auth_result_result,
+                        created_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+                ",
+                params![
+                    record.id.to_string(),
+                    record.s3_key.as_deref(),
+                    record.org_name.as_deref(),
+                    record.email.as_deref(),
+                    record.report_id.as_deref(),
+                    record.date_range_begin,
+                    record.date_range_end,
+                    record.policy_domain.as_deref(),
+                    record.source_ip.as_deref(),
+                    record.count,
+                    record.auth_result_type.as_deref(),
+                    record.auth_result_domain.as_deref(),
+                    record.auth_result_result.as_deref(),
+                    record.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+impl SqliteStore {
+    async fn get_launches(
+        &self,
+        query: &'static str,
+        since: Option<OffsetDateTime>,
+    ) -> Result<Vec<LaunchHistory>, Error> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(query)?;
+            let rows = match since {
+                Some(since) => stmt.query_map(params![since.unix_timestamp()], launch_from_row)?,
+                None => stmt.query_map([], launch_from_row)?,
+            };
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl LaunchHistoryRepo for SqliteStore {
+    async fn insert_launch(&self, launch: &LaunchHistory) -> Result<(), Error> {
+        let launch = launch.clone();
+        let launch_params = launch
+            .launch_params
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO launch_history (
+                        id, instance_id, spot_request_id, instance_type, ami, is_spot,
+                        status, launched_at, terminated_at, launch_params, auto_recover,
+                        recovery_attempts, recovery_status, setup_status, setup_seconds,
+                        setup_message, dns_name, dns_registered_at, fulfilled_at, running_at,
+                        cloud_init_at, healthy_at
+                    ) VALUES (
+                        ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11,
+                        ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22
+                    )
+                ",
+                params![
+                    launch.id.to_string(),
+                    launch.instance_id.as_deref(),
+                    launch.spot_request_id.as_deref(),
+                    launch.instance_type.as_str(),
+                    launch.ami.as_str(),
+                    launch.is_spot,
+                    launch.status.as_str(),
+                    launch.launched_at.unix_timestamp(),
+                    opt_timestamp(launch.terminated_at),
+                    launch_params,
+                    launch.auto_recover,
+                    launch.recovery_attempts,
+                    launch.recovery_status.as_deref(),
+                    launch.setup_status.as_deref(),
+                    launch.setup_seconds,
+                    launch.setup_message.as_deref(),
+                    launch.dns_name.as_deref(),
+                    opt_timestamp(launch.dns_registered_at),
+                    opt_timestamp(launch.fulfilled_at),
+                    opt_timestamp(launch.running_at),
+                    opt_timestamp(launch.cloud_init_at),
+                    opt_timestamp(launch.healthy_at),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_launch(&self, launch: &LaunchHistory) -> Result<(), Error> {
+        let launch = launch.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    UPDATE launch_history
+                    SET instance_id=?2,status=?3,terminated_at=?4,auto_recover=?5,
+                        recovery_attempts=?6,recovery_status=?7,setup_status=?8,
+                        setup_seconds=?9,setup_message=?10,dns_registered_at=?11,
+                        fulfilled_at=?12,running_at=?13,cloud_init_at=?14,healthy_at=?15
+                    WHERE id=?1
+                ",
+                params![
+                    launch.id.to_string(),
+                    launch.instance_id.as_deref(),
+                    launch.status.as_str(),
+                    opt_timestamp(launch.terminated_at),
+                    launch.auto_recover,
+                    launch.recovery_attempts,
+                    launch.recovery_status.as_deref(),
+                    launch.setup_status.as_deref(),
+                    launch.setup_seconds,
+                    launch.setup_message.as_deref(),
+                    opt_timestamp(launch.dns_registered_at),
+                    opt_timestamp(launch.fulfilled_at),
+                    opt_timestamp(launch.running_at),
+                    opt_timestamp(launch.cloud_init_at),
+                    opt_timestamp(launch.healthy_at),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_recent_launches(
+        &self,
+        since: OffsetDateTime,
+    ) -> Result<Vec<LaunchHistory>, Error> {
+        self.get_launches(
+            "SELECT * FROM launch_history WHERE launched_at >= ?1 ORDER BY launched_at DESC",
+            Some(since),
+        )
+        .await
+    }
+
+    async fn get_open_launches(&self) -> Result<Vec<LaunchHistory>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT * FROM launch_history
+                    WHERE terminated_at IS NULL
+                      AND (instance_id IS NOT NULL OR status IN (?1, ?2, ?3))
+                ",
+            )?;
+            let rows = stmt.query_map(
+                params![
+                    SPOT_PENDING_STATUSES[0],
+                    SPOT_PENDING_STATUSES[1],
+                    SPOT_PENDING_STATUSES[2],
+                ],
+                launch_from_row,
+            )?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_recoverable_launches(&self) -> Result<Vec<LaunchHistory>, Error> {
+        self.get_launches(
+            r"
+                SELECT * FROM launch_history
+                WHERE is_spot
+                  AND auto_recover
+                  AND terminated_at IS NOT NULL
+                  AND recovery_status IS NULL
+                  AND launch_params IS NOT NULL
+                ORDER BY terminated_at
+            ",
+            None,
+        )
+        .await
+    }
+
+    async fn get_pending_setup_launches(&self) -> Result<Vec<LaunchHistory>, Error> {
+        self.get_launches(
+            r"
+                SELECT * FROM launch_history
+                WHERE instance_id IS NOT NULL
+                  AND terminated_at IS NULL
+                  AND setup_status IS NULL
+                ORDER BY launched_at
+            ",
+            None,
+        )
+        .await
+    }
+
+    async fn get_pending_dns_launches(&self) -> Result<Vec<LaunchHistory>, Error> {
+        self.get_launches(
+            r"
+                SELECT * FROM launch_history
+                WHERE instance_id IS NOT NULL
+                  AND terminated_at IS NULL
+                  AND dns_name IS NOT NULL
+                  AND dns_registered_at IS NULL
+                ORDER BY launched_at
+            ",
+            None,
+        )
+        .await
+    }
+
+    async fn set_auto_recover(&self, instance_id: &str, auto_recover: bool) -> Result<bool, Error> {
+        let instance_id: StackString = instance_id.into();
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                r"
+                    UPDATE launch_history
+                    SET auto_recover=?2
+                    WHERE instance_id=?1 AND is_spot AND terminated_at IS NULL
+                ",
+                params![instance_id.as_str(), auto_recover],
+            )?;
+            Ok(updated > 0)
+        })
+        .await
+    }
+
+    async fn record_termination(
+        &self,
+        instance_id: &str,
+        terminated_at: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        let instance_id: StackString = instance_id.into();
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                r"
+                    UPDATE launch_history
+                    SET terminated_at=?2
+                    WHERE instance_id=?1 AND terminated_at IS NULL
+                ",
+                params![instance_id.as_str(), terminated_at.unix_timestamp()],
+            )?;
+            Ok(updated as u64)
+        })
+        .await
+    }
+
+    async fn set_terminated(&self, instance_ids: &[StackString]) -> Result<(), Error> {
+        let instance_ids = instance_ids.to_vec();
+        let terminated_at = OffsetDateTime::now_utc().unix_timestamp();
+        self.with_conn(move |conn| {
+            let tran = conn.transaction()?;
+            for instance_id in &instance_ids {
+                tran.execute(
+                    r"
+                        UPDATE launch_history
+                        SET terminated_at=?2,auto_recover=false
+                        WHERE instance_id=?1 AND terminated_at IS NULL
+                    ",
+                    params![instance_id.as_str(), terminated_at],
+                )?;
+            }
+            tran.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_last_launch_by_ami(&self) -> Result<HashMap<StackString, OffsetDateTime>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT ami, max(launched_at) AS last_launch FROM launch_history GROUP BY ami",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok((get_str(row, "ami")?, get_timestamp(row, "last_launch")?))
+            })?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_launch_by_instance_id(
+        &self,
+        instance_id: &str,
+    ) -> Result<Option<LaunchHistory>, Error> {
+        let instance_id: StackString = instance_id.into();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                r"
+                    SELECT * FROM launch_history
+                    WHERE instance_id = ?1
+                    ORDER BY launched_at DESC
+                    LIMIT 1
+                ",
+                params![instance_id.as_str()],
+                launch_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_launch_by_spot_request_id(
+        &self,
+        spot_request_id: &str,
+    ) -> Result<Option<LaunchHistory>, Error> {
+        let spot_request_id: StackString = spot_request_id.into();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM launch_history WHERE spot_request_id = ?1",
+                params![spot_request_id.as_str()],
+                launch_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_launch_analytics(&self, weeks: i64, limit: i64) -> Result<LaunchAnalytics, Error> {
+        self.with_conn(move |conn| {
+            // weeks start on monday like date_trunc('week', ...), the epoch
+            // was a thursday so mondays are 4 days (345600s) off
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT launched_at - (launched_at - 345600) % 604800 as week,
+                           count(*) as launches,
+                           count(*) FILTER (WHERE is_spot) as spot_launches
+                    FROM launch_history
+                    GROUP BY 1
+                    ORDER BY 1 DESC
+                    LIMIT ?1
+                ",
+            )?;
+            let weekly_launches = stmt
+                .query_map(params![weeks], |row| {
+                    Ok(WeeklyLaunches {
+                        week: get_timestamp(row, "week")?,
+                        launches: row.get("launches")?,
+                        spot_launches: row.get("spot_launches")?,
+                    })
+                })?
+                .collect::<Result<_, _>>()?;
+
+            let mut analytics = conn.query_row(
+                r"
+                    SELECT count(*) as total_launches,
+                           count(*) FILTER (WHERE is_spot) as spot_launches,
+                           count(*) FILTER (
+                               WHERE is_spot
+                                 AND instance_id IS NULL
+                                 AND status NOT IN (?1, ?2, ?3)
+                           ) as failed_spot_requests,
+                           avg(terminated_at - launched_at) / 3600.0 as average_lifetime_hours
+                    FROM launch_history
+                ",
+                params![
+                    SPOT_PENDING_STATUSES[0],
+                    SPOT_PENDING_STATUSES[1],
+                    SPOT_PENDING_STATUSES[2],
+                ],
+                |row| {
+                    Ok(LaunchAnalytics {
+                        total_launches: row.get("total_launches")?,
+                        spot_launches: row.get("spot_launches")?,
+                        failed_spot_requests: row.get("failed_spot_requests")?,
+                        average_lifetime_hours: row.get("average_lifetime_hours")?,
+                        ..LaunchAnalytics::default()
+                    })
+                },
+            )?;
+            analytics.weekly_launches = weekly_launches;
+
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT ami as key, count(*) as count
+                    FROM launch_history
+                    GROUP BY 1
+                    ORDER BY 2 DESC
+                    LIMIT ?1
+                ",
+            )?;
+            analytics.top_amis = stmt
+                .query_map(params![limit], launch_count_from_row)?
+                .collect::<Result<_, _>>()?;
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT instance_type as key, count(*) as count
+                    FROM launch_history
+                    GROUP BY 1
+                    ORDER BY 2 DESC
+                    LIMIT ?1
+                ",
+            )?;
+            analytics.top_instance_types = stmt
+                .query_map(params![limit], launch_count_from_row)?
+                .collect::<Result<_, _>>()?;
+            Ok(analytics)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl AmiCatalogRepo for SqliteStore {
+    async fn get_ami_catalog(&self) -> Result<Vec<AmiCatalogEntry>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM ami_catalog ORDER BY build_date")?;
+            let rows = stmt.query_map([], ami_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_ami_catalog_entry(&self, ami_id: &str) -> Result<Option<AmiCatalogEntry>, Error> {
+        let ami_id: StackString = ami_id.into();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM ami_catalog WHERE ami_id = ?1",
+                params![ami_id.as_str()],
+                ami_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn upsert_ami_catalog_entry(&self, entry: &AmiCatalogEntry) -> Result<(), Error> {
+        let entry = entry.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO ami_catalog (
+                        ami_id, name, base_release, build_script, build_date, parent_ami,
+                        instance_id
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                    ON CONFLICT (ami_id) DO UPDATE
+                    SET name=excluded.name,base_release=excluded.base_release,
+                        build_script=excluded.build_script,build_date=excluded.build_date,
+                        parent_ami=excluded.parent_ami,instance_id=excluded.instance_id
+                ",
+                params![
+                    entry.ami_id.as_str(),
+                    entry.name.as_str(),
+                    entry.base_release.as_deref(),
+                    entry.build_script.as_deref(),
+                    entry.build_date.unix_timestamp(),
+                    entry.parent_ami.as_deref(),
+                    entry.instance_id.as_deref(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl ProtectedResourceRepo for SqliteStore {
+    async fn get_protected_resources(&self) -> Result<Vec<ProtectedResource>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM protected_resource ORDER BY resource_id")?;
+            let rows = stmt.query_map([], protected_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn upsert_protected_resource(&self, resource: &ProtectedResource) -> Result<(), Error> {
+        let resource = resource.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO protected_resource (resource_id, reason, created_at)
+                    VALUES (?1, ?2, ?3)
+                    ON CONFLICT (resource_id) DO UPDATE SET reason=excluded.reason
+                ",
+                params![
+                    resource.resource_id.as_str(),
+                    resource.reason.as_deref(),
+                    resource.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_protected_resource(&self, resource_id: &str) -> Result<u64, Error> {
+        let resource_id: StackString = resource_id.into();
+        self.with_conn(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM protected_resource WHERE resource_id = ?1",
+                params![resource_id.as_str()],
+            )?;
+            Ok(deleted as u64)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl EmailAttachmentRepo for SqliteStore {
+    async fn get_attachment(&self, id: Uuid) -> Result<Option<EmailAttachment>, Error> {
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM email_attachment WHERE id = ?1",
+                params![id.to_string()],
+                attachment_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_attachments_by_email(
+        &self,
+        email_id: Uuid,
+    ) -> Result<Vec<EmailAttachment>, Error> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn
+                .prepare("SELECT * FROM email_attachment WHERE email_id = ?1 ORDER BY filename")?;
+            let rows = stmt.query_map(params![email_id.to_string()], attachment_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_orphaned_attachments(&self) -> Result<Vec<EmailAttachment>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT * FROM email_attachment
+                    WHERE email_id NOT IN (SELECT id FROM inbound_email)
+                ",
+            )?;
+            let rows = stmt.query_map([], attachment_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn count_attachments_by_s3_key(&self, s3_key: &str) -> Result<i64, Error> {
+        let s3_key: StackString = s3_key.into();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT count(*) FROM email_attachment WHERE s3_key = ?1",
+                params![s3_key.as_str()],
+                |row| row.get(0),
+            )
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn upsert_attachment(&self, attachment: &EmailAttachment) -> Result<(), Error> {
+        let attachment = attachment.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO email_attachment (
+                        id, email_id, filename, content_type, size, s3_bucket, s3_key,
+                        created_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                    ON CONFLICT (email_id, s3_key) DO UPDATE
+                    SET filename=excluded.filename,
+                        content_type=excluded.content_type,
+                        size=excluded.size
+                ",
+                params![
+                    attachment.id.to_string(),
+                    attachment.email_id.to_string(),
+                    attachment.filename.as_str(),
+                    attachment.content_type.as_deref(),
+                    attachment.size,
+                    attachment.s3_bucket.as_str(),
+                    attachment.s3_key.as_str(),
+                    attachment.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_attachment(&self, id: Uuid) -> Result<(), Error> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "DELETE FROM email_attachment WHERE id = ?1",
+                params![id.to_string()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl EmailForwardRuleRepo for SqliteStore {
+    async fn get_forward_rules(&self) -> Result<Vec<EmailForwardRule>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM email_forward_rule ORDER BY name")?;
+            let rows = stmt.query_map([], forward_rule_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_forward_rule(&self, id: Uuid) -> Result<Option<EmailForwardRule>, Error> {
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM email_forward_rule WHERE id = ?1",
+                params![id.to_string()],
+                forward_rule_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn upsert_forward_rule(&self, rule: &EmailForwardRule) -> Result<(), Error> {
+        let rule = rule.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO email_forward_rule (
+                        id, name, recipient_pattern, subject_pattern, forward_to, enabled,
+                        created_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                    ON CONFLICT (id) DO UPDATE
+                    SET name=excluded.name,
+                        recipient_pattern=excluded.recipient_pattern,
+                        subject_pattern=excluded.subject_pattern,
+                        forward_to=excluded.forward_to,
+                        enabled=excluded.enabled
+                ",
+                params![
+                    rule.id.to_string(),
+                    rule.name.as_str(),
+                    rule.recipient_pattern.as_deref(),
+                    rule.subject_pattern.as_deref(),
+                    rule.forward_to.as_str(),
+                    rule.enabled,
+                    rule.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_forward_rule(&self, id: Uuid) -> Result<u64, Error> {
+        self.with_conn(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM email_forward_rule WHERE id = ?1",
+                params![id.to_string()],
+            )?;
+            Ok(deleted as u64)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl SentEmailRepo for SqliteStore {
+    async fn get_sent_emails(&self) -> Result<Vec<SentEmail>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM sent_email ORDER BY sent_at")?;
+            let rows = stmt.query_map([], sent_email_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_sent_emails_by_thread(&self, thread_id: Uuid) -> Result<Vec<SentEmail>, Error> {
+        self.with_conn(move |conn| {
+            let mut stmt =
+                conn.prepare("SELECT * FROM sent_email WHERE thread_id = ?1 ORDER BY sent_at")?;
+            let rows = stmt.query_map(params![thread_id.to_string()], sent_email_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_sent_thread_id(&self, message_id: &str) -> Result<Option<Uuid>, Error> {
+        let message_id: StackString = message_id.into();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT thread_id FROM sent_email WHERE message_id = ?1 LIMIT 1",
+                params![message_id.as_str()],
+                |row| get_uuid(row, "thread_id"),
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn insert_sent_email(&self, sent: &SentEmail) -> Result<(), Error> {
+        let sent = sent.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO sent_email (
+                        id, email_id, thread_id, from_address, to_address, subject, body,
+                        message_id, sent_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                ",
+                params![
+                    sent.id.to_string(),
+                    sent.email_id.to_string(),
+                    sent.thread_id.to_string(),
+                    sent.from_address.as_str(),
+                    sent.to_address.as_str(),
+                    sent.subject.as_str(),
+                    sent.body.as_str(),
+                    sent.message_id.as_deref(),
+                    sent.sent_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl AuditLogRepo for SqliteStore {
+    async fn get_recent_audit_log(&self, limit: i64) -> Result<Vec<AuditLog>, Error> {
+        self.with_conn(move |conn| {
+            let mut stmt =
+                conn.prepare("SELECT * FROM audit_log ORDER BY created_at DESC LIMIT ?1")?;
+            let rows = stmt.query_map(params![limit], audit_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn insert_audit_log(&self, entry: &AuditLog) -> Result<(), Error> {
+        let entry = entry.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO audit_log (id, action, resource, details, created_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5)
+                ",
+                params![
+                    entry.id.to_string(),
+                    entry.action.as_str(),
+                    entry.resource.as_str(),
+                    entry.details.as_deref(),
+                    entry.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl UpdateStatusRepo for SqliteStore {
+    async fn get_update_status(&self) -> Result<Vec<UpdateStatus>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM update_status ORDER BY task")?;
+            let rows = stmt.query_map([], update_status_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn record_update_status(&self, task: &str, error: Option<&str>) -> Result<(), Error> {
+        let task: StackString = task.into();
+        let error: Option<StackString> = error.map(Into::into);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let last_success = if error.is_none() { Some(now) } else { None };
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO update_status (task, last_attempt, last_success, last_error)
+                    VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT (task) DO UPDATE
+                    SET last_attempt=excluded.last_attempt,
+                        last_success=COALESCE(excluded.last_success, update_status.last_success),
+                        last_error=excluded.last_error
+                ",
+                params![task.as_str(), now, last_success, error.as_deref()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl WebhookRepo for SqliteStore {
+    async fn get_webhooks(&self) -> Result<Vec<Webhook>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM webhooks ORDER BY created_at")?;
+            let rows = stmt.query_map([], webhook_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_webhook(&self, id: Uuid) -> Result<Option<Webhook>, Error> {
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM webhooks WHERE id = ?1",
+                params![id.to_string()],
+                webhook_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn insert_webhook(&self, webhook: &Webhook) -> Result<(), Error> {
+        let webhook = webhook.clone();
+        let events = serde_json::to_string(&webhook.events)?;
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO webhooks (id, url, secret, events, enabled, created_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ",
+                params![
+                    webhook.id.to_string(),
+                    webhook.url.as_str(),
+                    webhook.secret.as_str(),
+                    events,
+                    webhook.enabled,
+                    webhook.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_webhook(&self, id: Uuid) -> Result<u64, Error> {
+        self.with_conn(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM webhooks WHERE id = ?1",
+                params![id.to_string()],
+            )?;
+            Ok(deleted as u64)
+        })
+        .await
+    }
+
+    async fn get_recent_deliveries(&self, limit: i64) -> Result<Vec<WebhookDelivery>, Error> {
+        self.with_conn(move |conn| {
+            let mut stmt =
+                conn.prepare("SELECT * FROM webhook_deliveries ORDER BY created_at DESC LIMIT ?1")?;
+            let rows = stmt.query_map(params![limit], delivery_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_due_deliveries(&self) -> Result<Vec<WebhookDelivery>, Error> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT * FROM webhook_deliveries
+                    WHERE status = ?1 AND next_attempt_at <= ?2
+                    ORDER BY next_attempt_at
+                ",
+            )?;
+            let rows = stmt.query_map(params![DELIVERY_PENDING, now], delivery_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn insert_delivery(&self, delivery: &WebhookDelivery) -> Result<(), Error> {
+        let delivery = delivery.clone();
+        let payload = serde_json::to_string(&delivery.payload)?;
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO webhook_deliveries (
+                        id, webhook_id, event, payload, status, attempts, next_attempt_at,
+                        created_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                ",
+                params![
+                    delivery.id.to_string(),
+                    delivery.webhook_id.to_string(),
+                    delivery.event.as_str(),
+                    payload,
+                    delivery.status.as_str(),
+                    delivery.attempts,
+                    delivery.next_attempt_at.unix_timestamp(),
+                    delivery.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_delivery(&self, delivery: &WebhookDelivery) -> Result<(), Error> {
+        let delivery = delivery.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    UPDATE webhook_deliveries
+                    SET status=?2,attempts=?3,response_code=?4,last_error=?5,
+                        next_attempt_at=?6,delivered_at=?7
+                    WHERE id=?1
+                ",
+                params![
+                    delivery.id.to_string(),
+                    delivery.status.as_str(),
+                    delivery.attempts,
+                    delivery.response_code,
+                    delivery.last_error.as_deref(),
+                    delivery.next_attempt_at.unix_timestamp(),
+                    opt_timestamp(delivery.delivered_at),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl PriceAlertRepo for SqliteStore {
+    async fn get_price_alerts(&self) -> Result<Vec<PriceAlert>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare("SELECT * FROM price_alerts ORDER BY region, instance_type")?;
+            let rows = stmt.query_map([], price_alert_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_enabled_price_alerts(&self) -> Result<Vec<PriceAlert>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT * FROM price_alerts WHERE enabled ORDER BY region, instance_type",
+            )?;
+            let rows = stmt.query_map([], price_alert_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn insert_price_alert(&self, alert: &PriceAlert) -> Result<(), Error> {
+        let alert = alert.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO price_alerts (
+                        id, instance_type, region, threshold, enabled, last_price,
+                        triggered_at, created_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                ",
+                params![
+                    alert.id.to_string(),
+                    alert.instance_type.as_str(),
+                    alert.region.as_str(),
+                    alert.threshold,
+                    alert.enabled,
+                    alert.last_price,
+                    opt_timestamp(alert.triggered_at),
+                    alert.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_price_alert_state(&self, alert: &PriceAlert) -> Result<(), Error> {
+        let alert = alert.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "UPDATE price_alerts SET last_price = ?2, triggered_at = ?3 WHERE id = ?1",
+                params![
+                    alert.id.to_string(),
+                    alert.last_price,
+                    opt_timestamp(alert.triggered_at),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn set_price_alert_enabled(&self, id: Uuid, enabled: bool) -> Result<u64, Error> {
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                "UPDATE price_alerts SET enabled = ?2, triggered_at = NULL WHERE id = ?1",
+                params![id.to_string(), enabled],
+            )?;
+            Ok(updated as u64)
+        })
+        .await
+    }
+
+    async fn delete_price_alert(&self, id: Uuid) -> Result<u64, Error> {
+        self.with_conn(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM price_alerts WHERE id = ?1",
+                params![id.to_string()],
+            )?;
+            Ok(deleted as u64)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl InstanceReplacementRepo for SqliteStore {
+    async fn get_replacement(&self, id: Uuid) -> Result<Option<InstanceReplacement>, Error> {
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM instance_replacement WHERE id = ?1",
+                params![id.to_string()],
+                replacement_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_active_replacements(&self) -> Result<Vec<InstanceReplacement>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT * FROM instance_replacement
+                    WHERE state NOT IN ('completed', 'failed', 'cancelled')
+                    ORDER BY created_at
+                ",
+            )?;
+            let rows = stmt.query_map([], replacement_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_active_replacements_by_instance(
+        &self,
+        old_instance_id: &str,
+    ) -> Result<Vec<InstanceReplacement>, Error> {
+        let old_instance_id: StackString = old_instance_id.into();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT * FROM instance_replacement
+                    WHERE old_instance_id = ?1
+                      AND state NOT IN ('completed', 'failed', 'cancelled')
+                ",
+            )?;
+            let rows = stmt.query_map(params![old_instance_id.as_str()], replacement_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_recent_replacements(&self, limit: i64) -> Result<Vec<InstanceReplacement>, Error> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn
+                .prepare("SELECT * FROM instance_replacement ORDER BY created_at DESC LIMIT ?1")?;
+            let rows = stmt.query_map(params![limit], replacement_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn insert_replacement(&self, replacement: &InstanceReplacement) -> Result<(), Error> {
+        let replacement = replacement.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO instance_replacement (
+                        id, old_instance_id, new_instance_id, spot_request_id, ami, dns_name,
+                        terminate_old, soak_minutes, state, message, created_at, updated_at,
+                        dns_swapped_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+                ",
+                params![
+                    replacement.id.to_string(),
+                    replacement.old_instance_id.as_str(),
+                    replacement.new_instance_id.as_deref(),
+                    replacement.spot_request_id.as_deref(),
+                    replacement.ami.as_str(),
+                    replacement.dns_name.as_deref(),
+                    replacement.terminate_old,
+                    replacement.soak_minutes,
+                    replacement.state.as_str(),
+                    replacement.message.as_deref(),
+                    replacement.created_at.unix_timestamp(),
+                    replacement.updated_at.unix_timestamp(),
+                    opt_timestamp(replacement.dns_swapped_at),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_replacement_from(
+        &self,
+        replacement: &InstanceReplacement,
+        current_state: ReplacementState,
+    ) -> Result<bool, Error> {
+        let replacement = replacement.clone();
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                r"
+                    UPDATE instance_replacement
+                    SET new_instance_id=?2,spot_request_id=?3,state=?4,message=?5,
+                        updated_at=?6,dns_swapped_at=?7
+                    WHERE id=?1 AND state=?8
+                ",
+                params![
+                    replacement.id.to_string(),
+                    replacement.new_instance_id.as_deref(),
+                    replacement.spot_request_id.as_deref(),
+                    replacement.state.as_str(),
+                    replacement.message.as_deref(),
+                    replacement.updated_at.unix_timestamp(),
+                    opt_timestamp(replacement.dns_swapped_at),
+                    current_state.to_str(),
+                ],
+            )?;
+            Ok(updated > 0)
+        })
+        .await
+    }
+
+    async fn update_replacement_spot_request_id(
+        &self,
+        replacement: &InstanceReplacement,
+    ) -> Result<(), Error> {
+        let id = replacement.id;
+        let spot_request_id = replacement.spot_request_id.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "UPDATE instance_replacement SET spot_request_id=?2 WHERE id=?1",
+                params![id.to_string(), spot_request_id.as_deref()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl DrPolicyRepo for SqliteStore {
+    async fn get_dr_policies(&self) -> Result<Vec<DrPolicy>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare("SELECT * FROM dr_policies ORDER BY volume_name, target_region")?;
+            let rows = stmt.query_map([], dr_policy_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn insert_dr_policy(&self, policy: &DrPolicy) -> Result<(), Error> {
+        let policy = policy.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO dr_policies (
+                        id, volume_name, target_region, encrypted, kms_key_id, max_age_hours,
+                        created_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ",
+                params![
+                    policy.id.to_string(),
+                    policy.volume_name.as_str(),
+                    policy.target_region.as_str(),
+                    policy.encrypted,
+                    policy.kms_key_id.as_deref(),
+                    policy.max_age_hours,
+                    policy.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_dr_policy(&self, id: Uuid) -> Result<u64, Error> {
+        self.with_conn(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM dr_policies WHERE id = ?1",
+                params![id.to_string()],
+            )?;
+            Ok(deleted as u64)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl EcrHistoryRepo for SqliteStore {
+    async fn get_latest_ecr_history(&self) -> Result<Vec<EcrImageHistory>, Error> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                r"
+                    SELECT * FROM (
+                        SELECT *, row_number() OVER (
+                            PARTITION BY repo, digest ORDER BY recorded_at DESC
+                        ) as latest
+                        FROM ecr_image_history
+                    )
+                    WHERE latest = 1
+                    ORDER BY repo, digest
+                ",
+            )?;
+            let rows = stmt.query_map([], ecr_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_ecr_history(&self, repo: &str) -> Result<Vec<EcrImageHistory>, Error> {
+        let repo: StackString = repo.into();
+        self.with_conn(move |conn| {
+            let mut stmt = conn
+                .prepare("SELECT * FROM ecr_image_history WHERE repo = ?1 ORDER BY recorded_at")?;
+            let rows = stmt.query_map(params![repo.as_str()], ecr_from_row)?;
+            rows.collect::<Result<_, _>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn insert_ecr_history(&self, entry: &EcrImageHistory) -> Result<(), Error> {
+        let entry = entry.clone();
+        let tags = serde_json::to_string(&entry.tags)?;
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO ecr_image_history (
+                        id, repo, digest, tags, image_size, pushed_at, recorded_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ",
+                params![
+                    entry.id.to_string(),
+                    entry.repo.as_str(),
+                    entry.digest.as_str(),
+                    tags,
+                    entry.image_size,
+                    entry.pushed_at.unix_timestamp(),
+                    entry.recorded_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl UserPreferencesRepo for SqliteStore {
+    async fn get_user_preferences(&self, email: &str) -> Result<UserPreferences, Error> {
+        let email: StackString = email.into();
+        self.with_conn(move |conn| {
+            let preferences = conn
+                .query_row(
+                    "SELECT * FROM user_preferences WHERE email = ?1",
+                    params![email.as_str()],
+                    preferences_from_row,
+                )
+                .optional()?;
+            Ok(preferences.unwrap_or_else(|| UserPreferences::new(email)))
+        })
+        .await
+    }
+
+    async fn upsert_user_preferences(&self, preferences: &UserPreferences) -> Result<(), Error> {
+        let preferences = preferences.clone();
+        let hidden_columns = serde_json::to_string(&preferences.hidden_columns)?;
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO user_preferences (
+                        email, hidden_columns, default_sort, sort_descending, rows_per_page,
+                        default_region, updated_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                    ON CONFLICT (email) DO UPDATE
+                    SET hidden_columns=excluded.hidden_columns,
+                        default_sort=excluded.default_sort,
+                        sort_descending=excluded.sort_descending,
+                        rows_per_page=excluded.rows_per_page,
+                        default_region=excluded.default_region,
+                        updated_at=excluded.updated_at
+                ",
+                params![
+                    preferences.email.as_str(),
+                    hidden_columns,
+                    preferences.default_sort.as_deref(),
+                    preferences.sort_descending,
+                    preferences.rows_per_page,
+                    preferences.default_region.as_deref(),
+                    now,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl InventorySnapshotRepo for SqliteStore {
+    async fn get_latest_inventory(&self, region: &str) -> Result<Option<InventorySnapshot>, Error> {
+        let region: StackString = region.into();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                r"
+                    SELECT * FROM inventory_snapshot
+                    WHERE region = ?1
+                    ORDER BY created_at DESC
+                    LIMIT 1
+                ",
+                params![region.as_str()],
+                inventory_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn get_inventory_baseline(
+        &self,
+        region: &str,
+        at: OffsetDateTime,
+    ) -> Result<Option<InventorySnapshot>, Error> {
+        let region: StackString = region.into();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                r"
+                    SELECT * FROM inventory_snapshot
+                    WHERE region = ?1
+                    ORDER BY created_at > ?2, abs(created_at - ?2)
+                    LIMIT 1
+                ",
+                params![region.as_str(), at.unix_timestamp()],
+                inventory_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    async fn insert_inventory(&self, snapshot: &InventorySnapshot) -> Result<(), Error> {
+        let snapshot = snapshot.clone();
+        let resources = serde_json::to_string(&snapshot.resources)?;
+        self.with_conn(move |conn| {
+            conn.execute(
+                r"
+                    INSERT INTO inventory_snapshot (id, region, resources, created_at)
+                    VALUES (?1, ?2, ?3, ?4)
+                ",
+                params![
+                    snapshot.id.to_string(),
+                    snapshot.region.as_str(),
+                    resources,
+                    snapshot.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_inventory_before(&self, cutoff: OffsetDateTime) -> Result<u64, Error> {
+        self.with_conn(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM inventory_snapshot WHERE created_at < ?1",
+                params![cutoff.unix_timestamp()],
+            )?;
+            Ok(deleted as u64)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{format_err, Error};
+    use rusqlite::params;
+    use serde_json::json;
+    use time::{Duration, OffsetDateTime, Weekday};
+    use uuid::Uuid;
+
+    use crate::{
+        inventory::InventorySnapshot,
+        models::{
+            EmailAttachment, InboundEmailDB, InstanceFamily, InstanceList, InstancePricing,
+            LaunchHistory,
+        },
+        replacement::{InstanceReplacement, ReplacementState},
+        sqlite_store::SqliteStore,
+        storage::{
+            AuthorizedUsersRepo, EmailAttachmentRepo, InboundEmailRepo, InstanceFamilyRepo,
+            InstanceListRepo, InstancePricingRepo, InstanceReplacementRepo, InventorySnapshotRepo,
+            LaunchHistoryRepo, UpdateStatusRepo, UserPreferencesRepo, WebhookRepo,
+        },
+        webhook::{Webhook, WebhookDelivery, WebhookEvent, WebhookPayload},
+    };
+
+    fn whole_seconds(timestamp: OffsetDateTime) -> Result<OffsetDateTime, Error> {
+        OffsetDateTime::from_unix_timestamp(timestamp.unix_timestamp()).map_err(Into::into)
+    }
+
+    fn inbound_email(
+        message_id: &str,
+        in_reply_to: Option<&str>,
+        date: OffsetDateTime,
+    ) -> InboundEmailDB {
+        let id = Uuid::new_v4();
+        InboundEmailDB {
+            id,
+            s3_bucket: "test-bucket".into(),
+            s3_key: message_id.into(),
+            from_address: "sender@example.com".into(),
+            to_address: "user@example.com".into(),
+            subject: "test".into(),
+            date,
+            text_content: "text".into(),
+            html_content: "<p>text</p>".into(),
+            raw_email: "raw".into(),
+            spam_score: 0.0,
+            verdicts: None,
+            spam_feedback: None,
+            message_id: Some(message_id.into()),
+            in_reply_to: in_reply_to.map(Into::into),
+            thread_id: id,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store() -> Result<(), Error> {
+        let store = SqliteStore::open_in_memory()?;
+
+        let family = InstanceFamily {
+            family_name: "m5".into(),
+            family_type: "General Purpose".into(),
+            data_url: None,
+            use_for_spot: false,
+        };
+        assert!(store.upsert_instance_family(&family).await?.is_none());
+        assert!(store.upsert_instance_family(&family).await?.is_some());
+        assert_eq!(store.get_instance_families(None).await?, vec![family]);
+        assert!(store.get_instance_families(Some(true)).await?.is_empty());
+
+        let instance = InstanceList {
+            instance_type: "m5.large".into(),
+            family_name: "m5".into(),
+            n_cpu: 2,
+            memory_gib: 8.0,
+            generation: "hvm".into(),
+            gpu_count: 0,
+            gpu_type: None,
+            network_performance: Some("Up to 10 Gigabit".into()),
+        };
+        assert!(store.upsert_instance(&instance).await?.is_none());
+        assert_eq!(store.get_instance("m5.large").await?, Some(instance));
+        assert_eq!(store.get_instances().await?.len(), 1);
+
+        let timestamp = OffsetDateTime::from_unix_timestamp(1_700_000_000)?;
+        let price = InstancePricing::new("m5.large", 0.096, "ondemand", timestamp);
+        assert!(store.upsert_price(&price).await?.is_none());
+        let price = InstancePricing::new("m5.large", 0.1, "ondemand", timestamp);
+        let existing = store.upsert_price(&price).await?.unwrap();
+        assert!((existing.price - 0.096).abs() < 1e-9);
+
+        let prices = store.get_prices().await?;
+        assert_eq!(prices.len(), 1);
+        assert!((prices[0].price - 0.1).abs() < 1e-9);
+        assert_eq!(store.get_price_history("m5.large").await?.len(), 2);
+
+        let now = OffsetDateTime::now_utc() + Duration::minutes(1);
+        let latest = store.get_prices_as_of(now).await?;
+        assert_eq!(latest.len(), 1);
+        let week_ago = OffsetDateTime::now_utc() - Duration::days(7);
+        assert!(store.get_prices_as_of(week_ago).await?.is_empty());
+
+        let spot = InstancePricing::new("m5.large", 0.035, "spot", timestamp);
+        assert!(store.upsert_price(&spot).await?.is_none());
+        let spot_history = store
+            .get_price_type_history("spot", timestamp - Duration::days(1))
+            .await?;
+        assert_eq!(spot_history.len(), 1);
+        assert_eq!(spot_history[0].price_type, "spot");
+        assert_eq!(store.prune_price_history(1).await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_launch_history() -> Result<(), Error> {
+        let store = SqliteStore::open_in_memory()?;
+
+        let mut spot = LaunchHistory::new("m5.large", "ami-spot", true);
+        spot.launched_at = whole_seconds(spot.launched_at)?;
+        spot.launch_params = Some(json!({"price": "0.05"}));
+        store.insert_launch(&spot).await?;
+        let mut ondemand = LaunchHistory::new("t3.micro", "ami-ondemand", false);
+        ondemand.launched_at = OffsetDateTime::from_unix_timestamp(1_700_000_000)?;
+        ondemand.instance_id = Some("i-ondemand".into());
+        store.insert_launch(&ondemand).await?;
+
+        // the pending spot request and the running instance are both open
+        assert_eq!(store.get_open_launches().await?.len(), 2);
+
+        spot.instance_id = Some("i-spot".into());
+        spot.status = "fulfilled".into();
+        store.update_launch(&spot).await?;
+        assert_eq!(
+            store.get_launch_by_instance_id("i-spot").await?,
+            Some(spot.clone())
+        );
+        assert_eq!(store.get_pending_setup_launches().await?.len(), 2);
+
+        assert!(store.set_auto_recover("i-spot", true).await?);
+        assert!(!store.set_auto_recover("i-ondemand", true).await?);
+        let terminated_at = OffsetDateTime::now_utc();
+        assert_eq!(store.record_termination("i-spot", terminated_at).await?, 1);
+        let recoverable = store.get_recoverable_launches().await?;
+        assert_eq!(recoverable.len(), 1);
+        assert_eq!(recoverable[0].id, spot.id);
+
+        store.set_terminated(&["i-ondemand".into()]).await?;
+        assert!(store.get_open_launches().await?.is_empty());
+
+        let last_launch = store.get_last_launch_by_ami().await?;
+        assert_eq!(last_launch.get("ami-spot"), Some(&spot.launched_at));
+
+        let analytics = store.get_launch_analytics(52, 5).await?;
+        assert_eq!(analytics.total_launches, 2);
+        assert_eq!(analytics.spot_launches, 1);
+        assert_eq!(analytics.failed_spot_requests, 0);
+        assert_eq!(analytics.weekly_launches.len(), 2);
+        for week in &analytics.weekly_launches {
+            assert_eq!(week.week.weekday(), Weekday::Monday);
+            assert_eq!(week.week.time(), time::Time::MIDNIGHT);
+        }
+        assert!(analytics.average_lifetime_hours.is_some());
+        assert_eq!(analytics.top_amis.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_replacement_claim() -> Result<(), Error> {
+        let store = SqliteStore::open_in_memory()?;
+
+        let mut replacement = InstanceReplacement::new("i-test-soak", "ami-new", None, true, 30);
+        replacement.dns_swapped_at = Some(OffsetDateTime::now_utc() - Duration::minutes(60));
+        replacement.set_state(ReplacementState::Soaking);
+        store.insert_replacement(&replacement).await?;
+        assert_eq!(store.get_active_replacements().await?.len(), 1);
+
+        // the background pass holds a copy loaded before the cancel
+        let mut advancing = replacement.clone();
+        assert!(
+            replacement
+                .claim(&store, ReplacementState::Cancelled)
+                .await?
+        );
+        assert!(
+            !advancing
+                .claim(&store, ReplacementState::TerminatingOld)
+                .await?
+        );
+        assert_eq!(advancing.state()?, ReplacementState::Soaking);
+
+        let stored = store
+            .get_replacement(replacement.id)
+            .await?
+            .ok_or_else(|| format_err!("replacement not stored"))?;
+        assert_eq!(stored.state()?, ReplacementState::Cancelled);
+        assert!(store.get_active_replacements().await?.is_empty());
+        assert_eq!(store.get_recent_replacements(10).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_email_threads() -> Result<(), Error> {
+        let store = SqliteStore::open_in_memory()?;
+
+        let date = OffsetDateTime::from_unix_timestamp(1_700_000_000)?;
+        // the reply arrives before the email it replies to
+        let reply = inbound_email("<reply@example.com>", Some("<first@example.com>"), date);
+        let first = inbound_email("<first@example.com>", None, date - Duration::minutes(5));
+        assert!(store.upsert_email(&reply).await?.is_none());
+        assert!(store.upsert_email(&first).await?.is_none());
+        assert_eq!(store.upsert_email(&first).await?.as_ref(), Some(&first));
+
+        assert_eq!(
+            store.get_email_thread_id("<first@example.com>").await?,
+            Some(first.thread_id)
+        );
+        assert_eq!(
+            store
+                .merge_replies("<first@example.com>", first.thread_id)
+                .await?,
+            1
+        );
+        let reply = store
+            .get_email(reply.id)
+            .await?
+            .ok_or_else(|| format_err!("reply not stored"))?;
+        assert_eq!(reply.thread_id, first.thread_id);
+
+        let emails = store.get_emails(None, None).await?;
+        assert_eq!(emails.len(), 2);
+        assert_eq!(emails[0].id, first.id);
+        let emails = store.get_emails(Some(1), Some(1)).await?;
+        assert_eq!(emails.len(), 1);
+        assert_eq!(emails[0].id, reply.id);
+        assert_eq!(store.get_email_keys().await?.len(), 2);
+
+        assert!(!store.is_known_spam_sender("sender@example.com").await?);
+        assert_eq!(store.set_spam_feedback(reply.id, true).await?, 1);
+        assert!(store.is_known_spam_sender("sender@example.com").await?);
+
+        let attachment = EmailAttachment::new(
+            reply.id,
+            "report.pdf",
+            Some("application/pdf".into()),
+            1024,
+            "test-bucket",
+            "attachments/report.pdf",
+        );
+        store.upsert_attachment(&attachment).await?;
+        store.upsert_attachment(&attachment).await?;
+        assert_eq!(
+            store
+                .count_attachments_by_s3_key("attachments/report.pdf")
+                .await?,
+            1
+        );
+        assert!(store.get_orphaned_attachments().await?.is_empty());
+        store.delete_email(reply.id).await?;
+        assert_eq!(store.get_orphaned_attachments().await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_app_state() -> Result<(), Error> {
+        let store = SqliteStore::open_in_memory()?;
+
+        store.record_update_status("pricing", None).await?;
+        store
+            .record_update_status("pricing", Some("timeout"))
+            .await?;
+        let status = store.get_update_status().await?;
+        assert_eq!(status.len(), 1);
+        assert!(status[0].last_success.is_some());
+        assert_eq!(status[0].last_error.as_deref(), Some("timeout"));
+
+        let webhook = Webhook::new(
+            "https://example.com/hook",
+            &[WebhookEvent::InstanceStateChanged],
+        )?;
+        store.insert_webhook(&webhook).await?;
+        let payload = WebhookPayload::new(
+            WebhookEvent::InstanceStateChanged,
+            "i-test",
+            json!({"state": "running"}),
+        );
+        let delivery = WebhookDelivery::new(webhook.id, &payload)?;
+        store.insert_delivery(&delivery).await?;
+        assert_eq!(store.get_webhooks().await?[0].events, webhook.events);
+        assert_eq!(store.get_due_deliveries().await?.len(), 1);
+        assert_eq!(store.delete_webhook(webhook.id).await?, 1);
+        assert!(store.get_recent_deliveries(10).await?.is_empty());
+
+        let mut preferences = store.get_user_preferences("user@example.com").await?;
+        assert!(!preferences.sort_descending);
+        preferences.sort_descending = true;
+        preferences.hidden_columns = json!({"instances": ["ami"]});
+        store.upsert_user_preferences(&preferences).await?;
+        let stored = store.get_user_preferences("user@example.com").await?;
+        assert!(stored.sort_descending);
+        assert_eq!(stored.hidden_columns, preferences.hidden_columns);
+
+        let now = OffsetDateTime::now_utc();
+        let mut old = InventorySnapshot::new("us-east-1", &[])?;
+        old.created_at = now - Duration::days(2);
+        store.insert_inventory(&old).await?;
+        let new = InventorySnapshot::new("us-east-1", &[])?;
+        store.insert_inventory(&new).await?;
+        let baseline = store
+            .get_inventory_baseline("us-east-1", now - Duration::days(1))
+            .await?
+            .ok_or_else(|| format_err!("no baseline"))?;
+        assert_eq!(baseline.id, old.id);
+        let latest = store
+            .get_latest_inventory("us-east-1")
+            .await?
+            .ok_or_else(|| format_err!("no inventory"))?;
+        assert_eq!(latest.id, new.id);
+        assert_eq!(
+            store
+                .delete_inventory_before(now - Duration::days(1))
+                .await?,
+            1
+        );
+
+        store
+            .with_conn(|conn| {
+                conn.execute(
+                    "INSERT INTO authorized_users (email) VALUES (?1)",
+                    params!["user@example.com"],
+                )?;
+                Ok(())
+            })
+            .await?;
+        assert_eq!(store.get_authorized_users().await?.len(), 1);
+        assert!(store.get_most_recent_user_change().await?.is_some());
+        assert_eq!(store.get_theme("user@example.com").await?, None);
+        assert_eq!(store.set_theme("user@example.com", "dark").await?, 1);
+        assert_eq!(
+            store.get_theme("user@example.com").await?.as_deref(),
+            Some("dark")
+        );
+        Ok(())
+    }
+}
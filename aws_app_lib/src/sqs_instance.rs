@@ -0,0 +1,216 @@
+use anyhow::Error;
+use aws_config::SdkConfig;
+use aws_sdk_sqs::{
+    types::{MessageSystemAttributeName, QueueAttributeName},
+    Client as SqsClient,
+};
+use aws_types::region::Region;
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::fmt;
+use time::OffsetDateTime;
+
+#[derive(Clone)]
+pub struct SqsInstance {
+    sqs_client: SqsClient,
+}
+
+impl fmt::Debug for SqsInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SqsInstance")
+    }
+}
+
+impl SqsInstance {
+    #[must_use]
+    pub fn new(sdk_config: &SdkConfig) -> Self {
+        Self {
+            sqs_client: SqsClient::from_conf(sdk_config.into()),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if aws api fails
+    pub async fn set_region(&mut self, region: impl AsRef<str>) -> Result<(), Error> {
+        let region: String = region.as_ref().into();
+        let region = Region::new(region);
+        let conf = self.sqs_client.config().to_builder().region(region).build();
+        self.sqs_client = SqsClient::from_conf(conf);
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_queues(&self) -> Result<impl Iterator<Item = StackString>, Error> {
+        self.sqs_client
+            .list_queues()
+            .send()
+            .await
+            .map_err(Into::into)
+            .map(|q| q.queue_urls.unwrap_or_default().into_iter().map(Into::into))
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_queue_info(&self, queue_url: impl AsRef<str>) -> Result<QueueInfo, Error> {
+        let queue_url = queue_url.as_ref();
+        let attributes = self
+            .sqs_client
+            .get_queue_attributes()
+            .queue_url(queue_url)
+            .attribute_names(QueueAttributeName::All)
+            .send()
+            .await?
+            .attributes
+            .unwrap_or_default();
+        let get_attr = |name: &QueueAttributeName| -> Option<i64> {
+            attributes.get(name).and_then(|v| v.parse().ok())
+        };
+        Ok(QueueInfo {
+            queue_url: queue_url.into(),
+            queue_name: queue_name(queue_url).into(),
+            approximate_messages: get_attr(&QueueAttributeName::ApproximateNumberOfMessages)
+                .unwrap_or(0),
+            approximate_messages_in_flight: get_attr(
+                &QueueAttributeName::ApproximateNumberOfMessagesNotVisible,
+            )
+            .unwrap_or(0),
+            approximate_messages_delayed: get_attr(
+                &QueueAttributeName::ApproximateNumberOfMessagesDelayed,
+            )
+            .unwrap_or(0),
+            visibility_timeout: get_attr(&QueueAttributeName::VisibilityTimeout).unwrap_or(0),
+            created_timestamp: get_attr(&QueueAttributeName::CreatedTimestamp)
+                .and_then(|t| OffsetDateTime::from_unix_timestamp(t).ok()),
+            redrive_policy: attributes
+                .get(&QueueAttributeName::RedrivePolicy)
+                .map(|p| p.as_str().into()),
+        })
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn list_queue_info(&self) -> Result<Vec<QueueInfo>, Error> {
+        let futures = self
+            .list_queues()
+            .await?
+            .map(|queue_url| async move { self.get_queue_info(queue_url).await });
+        try_join_all(futures).await
+    }
+
+    /// Receive up to `max_messages` without hiding them from other consumers.
+    /// This is not read only: each receive increments the message's
+    /// `ApproximateReceiveCount`, so on a queue with a redrive policy repeated
+    /// peeks move messages to the dead letter queue once `maxReceiveCount` is
+    /// reached
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn peek_messages(
+        &self,
+        queue_url: impl Into<String>,
+        max_messages: i32,
+    ) -> Result<impl Iterator<Item = SqsMessage>, Error> {
+        self.sqs_client
+            .receive_message()
+            .queue_url(queue_url)
+            .max_number_of_messages(max_messages.clamp(1, 10))
+            .visibility_timeout(0)
+            .message_system_attribute_names(MessageSystemAttributeName::All)
+            .send()
+            .await
+            .map_err(Into::into)
+            .map(|r| {
+                r.messages.unwrap_or_default().into_iter().filter_map(|m| {
+                    let attributes = m.attributes.unwrap_or_default();
+                    let sent_timestamp = attributes
+                        .get(&MessageSystemAttributeName::SentTimestamp)
+                        .and_then(|t| t.parse::<i64>().ok())
+                        .and_then(|t| OffsetDateTime::from_unix_timestamp(t / 1000).ok());
+                    let receive_count = attributes
+                        .get(&MessageSystemAttributeName::ApproximateReceiveCount)
+                        .and_then(|c| c.parse().ok())
+                        .unwrap_or(0);
+                    Some(SqsMessage {
+                        message_id: m.message_id?.into(),
+                        body: m.body.unwrap_or_default().into(),
+                        sent_timestamp,
+                        receive_count,
+                    })
+                })
+            })
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn purge_queue(&self, queue_url: impl Into<String>) -> Result<(), Error> {
+        self.sqs_client
+            .purge_queue()
+            .queue_url(queue_url)
+            .send()
+            .await
+            .map_err(Into::into)
+            .map(|_| ())
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn delete_queue(&self, queue_url: impl Into<String>) -> Result<(), Error> {
+        self.sqs_client
+            .delete_queue()
+            .queue_url(queue_url)
+            .send()
+            .await
+            .map_err(Into::into)
+            .map(|_| ())
+    }
+}
+
+fn queue_name(queue_url: &str) -> &str {
+    queue_url.rsplit('/').next().unwrap_or(queue_url)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueInfo {
+    pub queue_url: StackString,
+    pub queue_name: StackString,
+    pub approximate_messages: i64,
+    pub approximate_messages_in_flight: i64,
+    pub approximate_messages_delayed: i64,
+    pub visibility_timeout: i64,
+    pub created_timestamp: Option<OffsetDateTime>,
+    pub redrive_policy: Option<StackString>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SqsMessage {
+    pub message_id: StackString,
+    pub body: StackString,
+    pub sent_timestamp: Option<OffsetDateTime>,
+    pub receive_count: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::sqs_instance::{queue_name, SqsInstance};
+
+    #[test]
+    fn test_queue_name() {
+        assert_eq!(
+            queue_name("https://sqs.us-east-1.amazonaws.com/123456789012/spot-workers"),
+            "spot-workers"
+        );
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_list_queue_info() -> Result<(), Error> {
+        let sdk_config = aws_config::load_from_env().await;
+        let sqs = SqsInstance::new(&sdk_config);
+        let queues = sqs.list_queue_info().await?;
+        assert!(queues.iter().all(|q| !q.queue_name.is_empty()));
+        Ok(())
+    }
+}
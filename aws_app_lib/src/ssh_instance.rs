@@ -2,20 +2,87 @@ use anyhow::{format_err, Error};
 use log::debug;
 use once_cell::sync::Lazy;
 use stack_string::{format_sstr, StackString};
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Output,
+};
+use thiserror::Error as ThisError;
 use tokio::{
+    fs,
     process::Command,
     sync::{Mutex, RwLock},
 };
 
+use crate::known_hosts::KnownHosts;
+
 static LOCK_CACHE: Lazy<RwLock<HashMap<StackString, Mutex<()>>>> =
     Lazy::new(|| RwLock::new(HashMap::new()));
 
+/// ssh refused to connect because the host key no longer matches the one
+/// recorded for the instance, typically after it was recreated
+#[derive(ThisError, Debug, Clone, PartialEq, Eq)]
+#[error("Host key verification failed for {alias}")]
+pub struct HostKeyMismatch {
+    pub alias: StackString,
+}
+
 #[derive(Debug, Clone)]
 pub struct SSHInstance {
     pub user: StackString,
     pub host: StackString,
     pub port: u16,
+    pub host_key: Option<HostKeyCheck>,
+    pub multiplex: Option<Multiplex>,
+}
+
+/// Managed known_hosts file and the alias (instance id) to verify against,
+/// with `strict` unset an unknown key is accepted and recorded
+#[derive(Debug, Clone)]
+pub struct HostKeyCheck {
+    pub known_hosts: KnownHosts,
+    pub alias: StackString,
+    pub strict: bool,
+}
+
+/// Reuse of one master connection per host by every ssh and scp call, the
+/// master exits after `idle_timeout` seconds without a client
+#[derive(Debug, Clone)]
+pub struct Multiplex {
+    pub control_dir: PathBuf,
+    pub idle_timeout: u64,
+}
+
+impl Multiplex {
+    /// `None` when `idle_timeout` is 0
+    #[must_use]
+    pub fn new(control_dir: impl Into<PathBuf>, idle_timeout: u64) -> Option<Self> {
+        if idle_timeout == 0 {
+            None
+        } else {
+            Some(Self {
+                control_dir: control_dir.into(),
+                idle_timeout,
+            })
+        }
+    }
+
+    /// `%C` is a hash of the local host, remote host, port and user which
+    /// keeps the socket path short
+    fn control_path(&self) -> StackString {
+        format_sstr!("ControlPath={}/%C", self.control_dir.to_string_lossy())
+    }
+
+    fn ssh_options(&self) -> Vec<StackString> {
+        vec![
+            "-o".into(),
+            "ControlMaster=auto".into(),
+            "-o".into(),
+            self.control_path(),
+            "-o".into(),
+            format_sstr!("ControlPersist={}s", self.idle_timeout),
+        ]
+    }
 }
 
 impl SSHInstance {
@@ -33,9 +100,23 @@ impl SSHInstance {
             user: user.into(),
             host,
             port,
+            host_key: None,
+            multiplex: None,
         }
     }
 
+    #[must_use]
+    pub fn with_host_key_check(mut self, host_key: HostKeyCheck) -> Self {
+        self.host_key = Some(host_key);
+        self
+    }
+
+    #[must_use]
+    pub fn with_multiplex(mut self, multiplex: Option<Multiplex>) -> Self {
+        self.multiplex = multiplex;
+        self
+    }
+
     #[must_use]
     pub fn get_ssh_username_host(&self) -> StackString {
         if self.port == 22 {
@@ -58,11 +139,15 @@ impl SSHInstance {
             let user_host = self.get_ssh_username_host();
 
             let output = Command::new("ssh")
+                .args(self.ssh_options().await?.iter().map(StackString::as_str))
                 .args([&user_host, "--"])
                 .args(cmd.split_whitespace())
                 .kill_on_drop(true)
                 .output()
                 .await?;
+            if !output.status.success() {
+                self.check_host_key(&output)?;
+            }
             let output = StackString::from_utf8_vec(output.stdout)?;
             let output: Vec<_> = output.split('\n').map(Into::into).collect();
             Ok(output)
@@ -70,4 +155,150 @@ impl SSHInstance {
             Err(format_err!("Failed to acquire lock"))
         }
     }
+
+    /// Copy `local` to `remote_path` on the instance with scp
+    /// # Errors
+    /// Returns error if `remote_path` is invalid or scp fails
+    pub async fn upload_file(&self, local: &Path, remote_path: &str) -> Result<(), Error> {
+        let remote = self.scp_target(remote_path)?;
+        self.scp(local.to_string_lossy().as_ref(), &remote).await
+    }
+
+    /// Copy `remote_path` on the instance to `local` with scp
+    /// # Errors
+    /// Returns error if `remote_path` is invalid or scp fails
+    pub async fn download_file(&self, remote_path: &str, local: &Path) -> Result<(), Error> {
+        let remote = self.scp_target(remote_path)?;
+        self.scp(&remote, local.to_string_lossy().as_ref()).await
+    }
+
+    fn scp_target(&self, remote_path: &str) -> Result<StackString, Error> {
+        validate_remote_path(remote_path)?;
+        Ok(format_sstr!("{}@{}:{remote_path}", self.user, self.host))
+    }
+
+    async fn scp(&self, from: &str, to: &str) -> Result<(), Error> {
+        if let Some(host_lock) = LOCK_CACHE.read().await.get(&self.host) {
+            let _lock = host_lock.lock().await;
+            debug!("scp {} {}", from, to);
+            let port = StackString::from_display(self.port);
+            let output = Command::new("scp")
+                .args(self.ssh_options().await?.iter().map(StackString::as_str))
+                .args(["-q", "-P", &port, "--", from, to])
+                .kill_on_drop(true)
+                .output()
+                .await?;
+            if output.status.success() {
+                Ok(())
+            } else {
+                self.check_host_key(&output)?;
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(format_err!("scp failed: {}", stderr.trim()))
+            }
+        } else {
+            Err(format_err!("Failed to acquire lock"))
+        }
+    }
+
+    /// Ask the master connection to the host to exit, so the next command
+    /// connects afresh. Nothing to do without multiplexing or a master.
+    /// # Errors
+    /// Returns error if ssh can't be run
+    pub async fn close_master(&self) -> Result<(), Error> {
+        let multiplex = match &self.multiplex {
+            Some(multiplex) => multiplex,
+            None => return Ok(()),
+        };
+        let user_host = format_sstr!("{}@{}", self.user, self.host);
+        let port = StackString::from_display(self.port);
+        let output = Command::new("ssh")
+            .args(["-o", &multiplex.control_path(), "-p", &port, "-O", "exit"])
+            .arg(&user_host)
+            .kill_on_drop(true)
+            .output()
+            .await?;
+        if !output.status.success() {
+            debug!(
+                "no master connection to {user_host}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    async fn ssh_options(&self) -> Result<Vec<StackString>, Error> {
+        let mut options = self.host_key_options();
+        if let Some(multiplex) = &self.multiplex {
+            fs::create_dir_all(&multiplex.control_dir).await?;
+            options.extend(multiplex.ssh_options());
+        }
+        Ok(options)
+    }
+
+    fn host_key_options(&self) -> Vec<StackString> {
+        let mut options = Vec::new();
+        if let Some(check) = &self.host_key {
+            options.extend(check.known_hosts.ssh_options(&check.alias, check.strict));
+            options.extend(["-o".into(), "BatchMode=yes".into()]);
+        }
+        options
+    }
+
+    fn check_host_key(&self, output: &Output) -> Result<(), HostKeyMismatch> {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("Host key verification failed")
+            || stderr.contains("REMOTE HOST IDENTIFICATION HAS CHANGED")
+        {
+            let alias = self
+                .host_key
+                .as_ref()
+                .map_or_else(|| self.host.clone(), |check| check.alias.clone());
+            return Err(HostKeyMismatch { alias });
+        }
+        Ok(())
+    }
+}
+
+/// Remote paths are interpreted by the remote shell, so only allow plain
+/// absolute or home relative paths
+/// # Errors
+/// Returns error if `path` is empty or contains anything but path characters
+pub fn validate_remote_path(path: &str) -> Result<(), Error> {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || "/._-~".contains(c);
+    if path.is_empty() || path.starts_with('-') || !path.chars().all(valid_char) {
+        return Err(format_err!("Invalid remote path {path}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ssh_instance::{validate_remote_path, Multiplex};
+
+    #[test]
+    fn test_multiplex_options() {
+        assert!(Multiplex::new("/tmp/ssh", 0).is_none());
+        let multiplex = Multiplex::new("/tmp/ssh", 300).expect("multiplex");
+        assert_eq!(
+            multiplex.ssh_options(),
+            vec![
+                "-o",
+                "ControlMaster=auto",
+                "-o",
+                "ControlPath=/tmp/ssh/%C",
+                "-o",
+                "ControlPersist=300s",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_remote_path() {
+        assert!(validate_remote_path("/var/log/cloud-init-output.log").is_ok());
+        assert!(validate_remote_path("~/.config/app.toml").is_ok());
+        assert!(validate_remote_path("").is_err());
+        assert!(validate_remote_path("-oProxyCommand=id").is_err());
+        assert!(validate_remote_path("/tmp/$(id)").is_err());
+        assert!(validate_remote_path("/tmp/a b").is_err());
+    }
 }
@@ -0,0 +1,1265 @@
+//! Repository traits for every table with a Postgres implementation on
+//! `PgPool`, delegating to the queries of the models, and a SQLite one on
+//! `SqliteStore`, so the app can run from a single local file without a
+//! Postgres server. Only the migrations and database backups need Postgres.
+use anyhow::{format_err, Error};
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+    sync::Arc,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    dr_policy::DrPolicy,
+    ecr_history::EcrImageHistory,
+    inventory::InventorySnapshot,
+    models::{
+        AmiCatalogEntry, AuditLog, AuthorizedUsers, DmarcRecords, EmailAttachment,
+        EmailForwardRule, InboundEmailBucketKey, InboundEmailDB, InstanceFamily, InstanceList,
+        InstancePricing, LaunchAnalytics, LaunchHistory, PriceHistory, ProtectedResource,
+        SentEmail, UpdateStatus,
+    },
+    pgpool::PgPool,
+    price_alert::PriceAlert,
+    replacement::{InstanceReplacement, ReplacementState},
+    schema::schema_version,
+    sqlite_store::SqliteStore,
+    user_preferences::UserPreferences,
+    webhook::{Webhook, WebhookDelivery},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Postgres,
+    Sqlite,
+}
+
+impl StorageBackend {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Postgres => "postgres",
+            Self::Sqlite => "sqlite",
+        }
+    }
+}
+
+impl fmt::Display for StorageBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for StorageBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "postgres" => Ok(Self::Postgres),
+            "sqlite" => Ok(Self::Sqlite),
+            _ => Err(format_err!("{} is not a StorageBackend", s)),
+        }
+    }
+}
+
+#[async_trait]
+pub trait SchemaRepo: Send + Sync {
+    /// Version of the most recent migration applied, the sqlite schema is
+    /// always created at the latest version
+    /// # Errors
+    /// Returns error if the database can't be reached
+    async fn schema_version(&self) -> Result<Option<i64>, Error>;
+}
+
+#[async_trait]
+pub trait InstanceFamilyRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_instance_families(
+        &self,
+        for_spot_instance: Option<bool>,
+    ) -> Result<Vec<InstanceFamily>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn upsert_instance_family(
+        &self,
+        family: &InstanceFamily,
+    ) -> Result<Option<InstanceFamily>, Error>;
+}
+
+#[async_trait]
+pub trait InstanceListRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_instances(&self) -> Result<Vec<InstanceList>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_instance(&self, instance_type: &str) -> Result<Option<InstanceList>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn upsert_instance(&self, instance: &InstanceList)
+        -> Result<Option<InstanceList>, Error>;
+}
+
+#[async_trait]
+pub trait InstancePricingRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_prices(&self) -> Result<Vec<InstancePricing>, Error>;
+
+    /// Update the current price and append it to the price history
+    /// # Errors
+    /// Returns error if the query fails
+    async fn upsert_price(&self, price: &InstancePricing)
+        -> Result<Option<InstancePricing>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_price_history(&self, instance_type: &str) -> Result<Vec<PriceHistory>, Error>;
+
+    /// Prices of `price_type` recorded since `since`, oldest first
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_price_type_history(
+        &self,
+        price_type: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<PriceHistory>, Error>;
+
+    /// The most recent price of each (`instance_type`, `price_type`)
+    /// recorded at or before `timestamp`
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_prices_as_of(&self, timestamp: OffsetDateTime)
+        -> Result<Vec<PriceHistory>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn prune_price_history(&self, days: i64) -> Result<u64, Error>;
+}
+
+#[async_trait]
+pub trait AuthorizedUsersRepo: Send + Sync {
+    /// Users which haven't been deleted
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_authorized_users(&self) -> Result<Vec<AuthorizedUsers>, Error>;
+
+    /// Most recent time a user was added or deleted
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_most_recent_user_change(&self) -> Result<Option<OffsetDateTime>, Error>;
+
+    /// Theme chosen with the index page toggle, `None` until one is chosen
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_theme(&self, email: &str) -> Result<Option<StackString>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn set_theme(&self, email: &str, theme: &str) -> Result<u64, Error>;
+}
+
+#[async_trait]
+pub trait InboundEmailRepo: Send + Sync {
+    /// Whether any email from `from_address` has been marked as spam
+    /// # Errors
+    /// Returns error if the query fails
+    async fn is_known_spam_sender(&self, from_address: &str) -> Result<bool, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn set_spam_feedback(&self, id: Uuid, spam: bool) -> Result<u64, Error>;
+
+    /// Thread of the email with Message-ID `message_id`
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_email_thread_id(&self, message_id: &str) -> Result<Option<Uuid>, Error>;
+
+    /// Move the threads of replies to `message_id` that arrived before it
+    /// into `thread_id`
+    /// # Errors
+    /// Returns error if the query fails
+    async fn merge_replies(&self, message_id: &str, thread_id: Uuid) -> Result<u64, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_email_keys(&self) -> Result<Vec<InboundEmailBucketKey>, Error>;
+
+    /// Emails ordered by date
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_emails(
+        &self,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<Vec<InboundEmailDB>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_email(&self, id: Uuid) -> Result<Option<InboundEmailDB>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_email_by_bucket_key(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<InboundEmailDB>, Error>;
+
+    /// Returns the email as it was before the update
+    /// # Errors
+    /// Returns error if the query fails
+    async fn upsert_email(&self, email: &InboundEmailDB) -> Result<Option<InboundEmailDB>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn delete_email(&self, id: Uuid) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait DmarcRecordsRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_parsed_s3_keys(&self) -> Result<HashSet<StackString>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn delete_dmarc_records(&self, s3_key: &str) -> Result<u64, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_dmarc_record(&self, record: &DmarcRecords) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait LaunchHistoryRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_launch(&self, launch: &LaunchHistory) -> Result<(), Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn update_launch(&self, launch: &LaunchHistory) -> Result<(), Error>;
+
+    /// Launches made since `since`, most recent first
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_recent_launches(&self, since: OffsetDateTime)
+        -> Result<Vec<LaunchHistory>, Error>;
+
+    /// Launches which are still waiting on a spot request or whose instance
+    /// hasn't been seen terminated yet
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_open_launches(&self) -> Result<Vec<LaunchHistory>, Error>;
+
+    /// Terminated spot launches flagged for recovery which haven't been
+    /// recovered, given up on or skipped yet
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_recoverable_launches(&self) -> Result<Vec<LaunchHistory>, Error>;
+
+    /// Running launches whose setup hasn't been checked to completion yet
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_pending_setup_launches(&self) -> Result<Vec<LaunchHistory>, Error>;
+
+    /// Running launches whose dns name hasn't been registered yet
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_pending_dns_launches(&self) -> Result<Vec<LaunchHistory>, Error>;
+
+    /// Set the auto-recover flag of the running launch of `instance_id`,
+    /// returns false if there is no such launch
+    /// # Errors
+    /// Returns error if the query fails
+    async fn set_auto_recover(&self, instance_id: &str, auto_recover: bool) -> Result<bool, Error>;
+
+    /// Record an observed termination of `instance_id`, the launch stays
+    /// eligible for spot recovery
+    /// # Errors
+    /// Returns error if the query fails
+    async fn record_termination(
+        &self,
+        instance_id: &str,
+        terminated_at: OffsetDateTime,
+    ) -> Result<u64, Error>;
+
+    /// Instances terminated through the app are never auto-recovered
+    /// # Errors
+    /// Returns error if the query fails
+    async fn set_terminated(&self, instance_ids: &[StackString]) -> Result<(), Error>;
+
+    /// Most recent launch of each ami
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_last_launch_by_ami(&self) -> Result<HashMap<StackString, OffsetDateTime>, Error>;
+
+    /// Most recent launch of `instance_id`
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_launch_by_instance_id(
+        &self,
+        instance_id: &str,
+    ) -> Result<Option<LaunchHistory>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_launch_by_spot_request_id(
+        &self,
+        spot_request_id: &str,
+    ) -> Result<Option<LaunchHistory>, Error>;
+
+    /// Launches of the last `weeks` weeks and the `limit` most launched amis
+    /// and instance types
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_launch_analytics(&self, weeks: i64, limit: i64) -> Result<LaunchAnalytics, Error>;
+}
+
+#[async_trait]
+pub trait AmiCatalogRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_ami_catalog(&self) -> Result<Vec<AmiCatalogEntry>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_ami_catalog_entry(&self, ami_id: &str) -> Result<Option<AmiCatalogEntry>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn upsert_ami_catalog_entry(&self, entry: &AmiCatalogEntry) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait ProtectedResourceRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_protected_resources(&self) -> Result<Vec<ProtectedResource>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn upsert_protected_resource(&self, resource: &ProtectedResource) -> Result<(), Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn delete_protected_resource(&self, resource_id: &str) -> Result<u64, Error>;
+}
+
+#[async_trait]
+pub trait EmailAttachmentRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_attachment(&self, id: Uuid) -> Result<Option<EmailAttachment>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_attachments_by_email(&self, email_id: Uuid)
+        -> Result<Vec<EmailAttachment>, Error>;
+
+    /// Attachments whose email has been deleted
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_orphaned_attachments(&self) -> Result<Vec<EmailAttachment>, Error>;
+
+    /// Number of attachment entries that point at `s3_key`
+    /// # Errors
+    /// Returns error if the query fails
+    async fn count_attachments_by_s3_key(&self, s3_key: &str) -> Result<i64, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn upsert_attachment(&self, attachment: &EmailAttachment) -> Result<(), Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn delete_attachment(&self, id: Uuid) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait EmailForwardRuleRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_forward_rules(&self) -> Result<Vec<EmailForwardRule>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_forward_rule(&self, id: Uuid) -> Result<Option<EmailForwardRule>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn upsert_forward_rule(&self, rule: &EmailForwardRule) -> Result<(), Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn delete_forward_rule(&self, id: Uuid) -> Result<u64, Error>;
+}
+
+#[async_trait]
+pub trait SentEmailRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_sent_emails(&self) -> Result<Vec<SentEmail>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_sent_emails_by_thread(&self, thread_id: Uuid) -> Result<Vec<SentEmail>, Error>;
+
+    /// Thread of the reply with Message-ID `message_id`
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_sent_thread_id(&self, message_id: &str) -> Result<Option<Uuid>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_sent_email(&self, sent: &SentEmail) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait AuditLogRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_recent_audit_log(&self, limit: i64) -> Result<Vec<AuditLog>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_audit_log(&self, entry: &AuditLog) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait UpdateStatusRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_update_status(&self) -> Result<Vec<UpdateStatus>, Error>;
+
+    /// Record a run of `task`, `last_success` is only moved forward when
+    /// `error` is `None`
+    /// # Errors
+    /// Returns error if the query fails
+    async fn record_update_status(&self, task: &str, error: Option<&str>) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait WebhookRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_webhooks(&self) -> Result<Vec<Webhook>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_webhook(&self, id: Uuid) -> Result<Option<Webhook>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_webhook(&self, webhook: &Webhook) -> Result<(), Error>;
+
+    /// Deliveries of the webhook are deleted with it
+    /// # Errors
+    /// Returns error if the query fails
+    async fn delete_webhook(&self, id: Uuid) -> Result<u64, Error>;
+
+    /// Most recent deliveries for the delivery log
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_recent_deliveries(&self, limit: i64) -> Result<Vec<WebhookDelivery>, Error>;
+
+    /// Pending deliveries whose next attempt is due
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_due_deliveries(&self) -> Result<Vec<WebhookDelivery>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_delivery(&self, delivery: &WebhookDelivery) -> Result<(), Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn update_delivery(&self, delivery: &WebhookDelivery) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait PriceAlertRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_price_alerts(&self) -> Result<Vec<PriceAlert>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_enabled_price_alerts(&self) -> Result<Vec<PriceAlert>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_price_alert(&self, alert: &PriceAlert) -> Result<(), Error>;
+
+    /// Store the result of `PriceAlert::evaluate`
+    /// # Errors
+    /// Returns error if the query fails
+    async fn update_price_alert_state(&self, alert: &PriceAlert) -> Result<(), Error>;
+
+    /// Disabling an alert also clears its triggered state
+    /// # Errors
+    /// Returns error if the query fails
+    async fn set_price_alert_enabled(&self, id: Uuid, enabled: bool) -> Result<u64, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn delete_price_alert(&self, id: Uuid) -> Result<u64, Error>;
+}
+
+#[async_trait]
+pub trait InstanceReplacementRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_replacement(&self, id: Uuid) -> Result<Option<InstanceReplacement>, Error>;
+
+    /// Replacements that aren't completed, failed or cancelled, oldest first
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_active_replacements(&self) -> Result<Vec<InstanceReplacement>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_active_replacements_by_instance(
+        &self,
+        old_instance_id: &str,
+    ) -> Result<Vec<InstanceReplacement>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_recent_replacements(&self, limit: i64) -> Result<Vec<InstanceReplacement>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_replacement(&self, replacement: &InstanceReplacement) -> Result<(), Error>;
+
+    /// Store `replacement` only if its stored state is still
+    /// `current_state`, returns whether it was stored
+    /// # Errors
+    /// Returns error if the query fails
+    async fn update_replacement_from(
+        &self,
+        replacement: &InstanceReplacement,
+        current_state: ReplacementState,
+    ) -> Result<bool, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn update_replacement_spot_request_id(
+        &self,
+        replacement: &InstanceReplacement,
+    ) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait DrPolicyRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_dr_policies(&self) -> Result<Vec<DrPolicy>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_dr_policy(&self, policy: &DrPolicy) -> Result<(), Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn delete_dr_policy(&self, id: Uuid) -> Result<u64, Error>;
+}
+
+#[async_trait]
+pub trait EcrHistoryRepo: Send + Sync {
+    /// Most recent row of every digest
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_latest_ecr_history(&self) -> Result<Vec<EcrImageHistory>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_ecr_history(&self, repo: &str) -> Result<Vec<EcrImageHistory>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_ecr_history(&self, entry: &EcrImageHistory) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait UserPreferencesRepo: Send + Sync {
+    /// Saved preferences of `email`, the defaults if none were saved
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_user_preferences(&self, email: &str) -> Result<UserPreferences, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn upsert_user_preferences(&self, preferences: &UserPreferences) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait InventorySnapshotRepo: Send + Sync {
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_latest_inventory(&self, region: &str) -> Result<Option<InventorySnapshot>, Error>;
+
+    /// Latest snapshot taken at or before `at`, the earliest one after it if
+    /// there is none
+    /// # Errors
+    /// Returns error if the query fails
+    async fn get_inventory_baseline(
+        &self,
+        region: &str,
+        at: OffsetDateTime,
+    ) -> Result<Option<InventorySnapshot>, Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn insert_inventory(&self, snapshot: &InventorySnapshot) -> Result<(), Error>;
+
+    /// # Errors
+    /// Returns error if the query fails
+    async fn delete_inventory_before(&self, cutoff: OffsetDateTime) -> Result<u64, Error>;
+}
+
+pub trait Storage:
+    SchemaRepo
+    + InstanceFamilyRepo
+    + InstanceListRepo
+    + InstancePricingRepo
+    + AuthorizedUsersRepo
+    + InboundEmailRepo
+    + DmarcRecordsRepo
+    + LaunchHistoryRepo
+    + AmiCatalogRepo
+    + ProtectedResourceRepo
+    + EmailAttachmentRepo
+    + EmailForwardRuleRepo
+    + SentEmailRepo
+    + AuditLogRepo
+    + UpdateStatusRepo
+    + WebhookRepo
+    + PriceAlertRepo
+    + InstanceReplacementRepo
+    + DrPolicyRepo
+    + EcrHistoryRepo
+    + UserPreferencesRepo
+    + InventorySnapshotRepo
+    + fmt::Debug
+{
+}
+
+impl<T> Storage for T where
+    T: SchemaRepo
+        + InstanceFamilyRepo
+        + InstanceListRepo
+        + InstancePricingRepo
+        + AuthorizedUsersRepo
+        + InboundEmailRepo
+        + DmarcRecordsRepo
+        + LaunchHistoryRepo
+        + AmiCatalogRepo
+        + ProtectedResourceRepo
+        + EmailAttachmentRepo
+        + EmailForwardRuleRepo
+        + SentEmailRepo
+        + AuditLogRepo
+        + UpdateStatusRepo
+        + WebhookRepo
+        + PriceAlertRepo
+        + InstanceReplacementRepo
+        + DrPolicyRepo
+        + EcrHistoryRepo
+        + UserPreferencesRepo
+        + InventorySnapshotRepo
+        + fmt::Debug
+{
+}
+
+/// Storage selected by `Config::storage_backend`
+/// # Errors
+/// Returns error if the sqlite database can't be opened
+pub fn open_storage(config: &Config, pool: &PgPool) -> Result<Arc<dyn Storage>, Error> {
+    match config.storage_backend {
+        StorageBackend::Postgres => Ok(Arc::new(pool.clone())),
+        StorageBackend::Sqlite => Ok(Arc::new(SqliteStore::open(&config.sqlite_path)?)),
+    }
+}
+
+#[async_trait]
+impl SchemaRepo for PgPool {
+    async fn schema_version(&self) -> Result<Option<i64>, Error> {
+        Ok(schema_version(self).await?.map(|m| m.version))
+    }
+}
+
+#[async_trait]
+impl InstanceFamilyRepo for PgPool {
+    async fn get_instance_families(
+        &self,
+        for_spot_instance: Option<bool>,
+    ) -> Result<Vec<InstanceFamily>, Error> {
+        InstanceFamily::get_all(self, for_spot_instance)
+            .await?
+            .try_collect()
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn upsert_instance_family(
+        &self,
+        family: &InstanceFamily,
+    ) -> Result<Option<InstanceFamily>, Error> {
+        family.upsert_entry(self).await
+    }
+}
+
+#[async_trait]
+impl InstanceListRepo for PgPool {
+    async fn get_instances(&self) -> Result<Vec<InstanceList>, Error> {
+        InstanceList::get_all_instances(self)
+            .await?
+            .try_collect()
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_instance(&self, instance_type: &str) -> Result<Option<InstanceList>, Error> {
+        InstanceList::get_by_instance_type(instance_type, self).await
+    }
+
+    async fn upsert_instance(
+        &self,
+        instance: &InstanceList,
+    ) -> Result<Option<InstanceList>, Error> {
+        instance.upsert_entry(self).await
+    }
+}
+
+#[async_trait]
+impl InstancePricingRepo for PgPool {
+    async fn get_prices(&self) -> Result<Vec<InstancePricing>, Error> {
+        InstancePricing::get_all(self)
+            .await?
+            .try_collect()
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn upsert_price(
+        &self,
+        price: &InstancePricing,
+    ) -> Result<Option<InstancePricing>, Error> {
+        price.upsert_entry(self).await
+    }
+
+    async fn get_price_history(&self, instance_type: &str) -> Result<Vec<PriceHistory>, Error> {
+        PriceHistory::get_by_instance_type(instance_type, self).await
+    }
+
+    async fn get_price_type_history(
+        &self,
+        price_type: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<PriceHistory>, Error> {
+        PriceHistory::get_by_price_type(price_type, since, self).await
+    }
+
+    async fn get_prices_as_of(
+        &self,
+        timestamp: OffsetDateTime,
+    ) -> Result<Vec<PriceHistory>, Error> {
+        PriceHistory::get_prices_as_of(timestamp, self)
+            .await?
+            .try_collect()
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn prune_price_history(&self, days: i64) -> Result<u64, Error> {
+        PriceHistory::prune(days, self).await
+    }
+}
+
+#[async_trait]
+impl AuthorizedUsersRepo for PgPool {
+    async fn get_authorized_users(&self) -> Result<Vec<AuthorizedUsers>, Error> {
+        AuthorizedUsers::get_authorized_users(self)
+            .await?
+            .try_collect()
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_most_recent_user_change(&self) -> Result<Option<OffsetDateTime>, Error> {
+        AuthorizedUsers::get_most_recent(self).await
+    }
+
+    async fn get_theme(&self, email: &str) -> Result<Option<StackString>, Error> {
+        AuthorizedUsers::get_theme(self, email).await
+    }
+
+    async fn set_theme(&self, email: &str, theme: &str) -> Result<u64, Error> {
+        AuthorizedUsers::set_theme(self, email, theme).await
+    }
+}
+
+#[async_trait]
+impl InboundEmailRepo for PgPool {
+    async fn is_known_spam_sender(&self, from_address: &str) -> Result<bool, Error> {
+        InboundEmailDB::is_known_spam_sender(self, from_address).await
+    }
+
+    async fn set_spam_feedback(&self, id: Uuid, spam: bool) -> Result<u64, Error> {
+        InboundEmailDB::set_spam_feedback(id, spam, self).await
+    }
+
+    async fn get_email_thread_id(&self, message_id: &str) -> Result<Option<Uuid>, Error> {
+        InboundEmailDB::get_thread_id(self, message_id).await
+    }
+
+    async fn merge_replies(&self, message_id: &str, thread_id: Uuid) -> Result<u64, Error> {
+        InboundEmailDB::merge_replies(self, message_id, thread_id).await
+    }
+
+    async fn get_email_keys(&self) -> Result<Vec<InboundEmailBucketKey>, Error> {
+        InboundEmailDB::get_keys(self)
+            .await?
+            .try_collect()
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_emails(
+        &self,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<Vec<InboundEmailDB>, Error> {
+        InboundEmailDB::get_all(self, offset, limit)
+            .await?
+            .try_collect()
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_email(&self, id: Uuid) -> Result<Option<InboundEmailDB>, Error> {
+        InboundEmailDB::get_by_id(self, id).await
+    }
+
+    async fn get_email_by_bucket_key(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<InboundEmailDB>, Error> {
+        InboundEmailDB::get_by_bucket_key(self, bucket, key).await
+    }
+
+    async fn upsert_email(&self, email: &InboundEmailDB) -> Result<Option<InboundEmailDB>, Error> {
+        email.upsert_entry(self).await
+    }
+
+    async fn delete_email(&self, id: Uuid) -> Result<(), Error> {
+        InboundEmailDB::delete_entry_by_id(id, self).await
+    }
+}
+
+#[async_trait]
+impl DmarcRecordsRepo for PgPool {
+    async fn get_parsed_s3_keys(&self) -> Result<HashSet<StackString>, Error> {
+        DmarcRecords::get_parsed_s3_keys(self).await
+    }
+
+    async fn delete_dmarc_records(&self, s3_key: &str) -> Result<u64, Error> {
+        DmarcRecords::delete_by_s3_key(s3_key, self).await
+    }
+
+    async fn insert_dmarc_record(&self, record: &DmarcRecords) -> Result<(), Error> {
+        record.insert_entry(self).await
+    }
+}
+
+#[async_trait]
+impl LaunchHistoryRepo for PgPool {
+    async fn insert_launch(&self, launch: &LaunchHistory) -> Result<(), Error> {
+        launch.insert_entry(self).await
+    }
+
+    async fn update_launch(&self, launch: &LaunchHistory) -> Result<(), Error> {
+        launch.update_entry(self).await
+    }
+
+    async fn get_recent_launches(
+        &self,
+        since: OffsetDateTime,
+    ) -> Result<Vec<LaunchHistory>, Error> {
+        LaunchHistory::get_recent(self, since).await
+    }
+
+    async fn get_open_launches(&self) -> Result<Vec<LaunchHistory>, Error> {
+        LaunchHistory::get_open(self).await
+    }
+
+    async fn get_recoverable_launches(&self) -> Result<Vec<LaunchHistory>, Error> {
+        LaunchHistory::get_recoverable(self).await
+    }
+
+    async fn get_pending_setup_launches(&self) -> Result<Vec<LaunchHistory>, Error> {
+        LaunchHistory::get_pending_setup(self).await
+    }
+
+    async fn get_pending_dns_launches(&self) -> Result<Vec<LaunchHistory>, Error> {
+        LaunchHistory::get_pending_dns(self).await
+    }
+
+    async fn set_auto_recover(&self, instance_id: &str, auto_recover: bool) -> Result<bool, Error> {
+        LaunchHistory::set_auto_recover(self, instance_id, auto_recover).await
+    }
+
+    async fn record_termination(
+        &self,
+        instance_id: &str,
+        terminated_at: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        LaunchHistory::record_termination(self, instance_id, terminated_at).await
+    }
+
+    async fn set_terminated(&self, instance_ids: &[StackString]) -> Result<(), Error> {
+        LaunchHistory::set_terminated(self, instance_ids).await
+    }
+
+    async fn get_last_launch_by_ami(&self) -> Result<HashMap<StackString, OffsetDateTime>, Error> {
+        LaunchHistory::get_last_launch_by_ami(self).await
+    }
+
+    async fn get_launch_by_instance_id(
+        &self,
+        instance_id: &str,
+    ) -> Result<Option<LaunchHistory>, Error> {
+        LaunchHistory::get_by_instance_id(self, instance_id).await
+    }
+
+    async fn get_launch_by_spot_request_id(
+        &self,
+        spot_request_id: &str,
+    ) -> Result<Option<LaunchHistory>, Error> {
+        LaunchHistory::get_by_spot_request_id(self, spot_request_id).await
+    }
+
+    async fn get_launch_analytics(&self, weeks: i64, limit: i64) -> Result<LaunchAnalytics, Error> {
+        LaunchAnalytics::get_analytics(self, weeks, limit).await
+    }
+}
+
+#[async_trait]
+impl AmiCatalogRepo for PgPool {
+    async fn get_ami_catalog(&self) -> Result<Vec<AmiCatalogEntry>, Error> {
+        AmiCatalogEntry::get_all(self).await
+    }
+
+    async fn get_ami_catalog_entry(&self, ami_id: &str) -> Result<Option<AmiCatalogEntry>, Error> {
+        AmiCatalogEntry::get_by_ami(self, ami_id).await
+    }
+
+    async fn upsert_ami_catalog_entry(&self, entry: &AmiCatalogEntry) -> Result<(), Error> {
+        entry.upsert_entry(self).await
+    }
+}
+
+#[async_trait]
+impl ProtectedResourceRepo for PgPool {
+    async fn get_protected_resources(&self) -> Result<Vec<ProtectedResource>, Error> {
+        ProtectedResource::get_all(self).await
+    }
+
+    async fn upsert_protected_resource(&self, resource: &ProtectedResource) -> Result<(), Error> {
+        resource.upsert_entry(self).await
+    }
+
+    async fn delete_protected_resource(&self, resource_id: &str) -> Result<u64, Error> {
+        ProtectedResource::delete_entry(resource_id, self).await
+    }
+}
+
+#[async_trait]
+impl EmailAttachmentRepo for PgPool {
+    async fn get_attachment(&self, id: Uuid) -> Result<Option<EmailAttachment>, Error> {
+        EmailAttachment::get_by_id(self, id).await
+    }
+
+    async fn get_attachments_by_email(
+        &self,
+        email_id: Uuid,
+    ) -> Result<Vec<EmailAttachment>, Error> {
+        EmailAttachment::get_by_email_id(self, email_id).await
+    }
+
+    async fn get_orphaned_attachments(&self) -> Result<Vec<EmailAttachment>, Error> {
+        EmailAttachment::get_orphaned(self).await
+    }
+
+    async fn count_attachments_by_s3_key(&self, s3_key: &str) -> Result<i64, Error> {
+        EmailAttachment::count_by_s3_key(self, s3_key).await
+    }
+
+    async fn upsert_attachment(&self, attachment: &EmailAttachment) -> Result<(), Error> {
+        attachment.upsert_entry(self).await
+    }
+
+    async fn delete_attachment(&self, id: Uuid) -> Result<(), Error> {
+        EmailAttachment::delete_entry_by_id(id, self).await
+    }
+}
+
+#[async_trait]
+impl EmailForwardRuleRepo for PgPool {
+    async fn get_forward_rules(&self) -> Result<Vec<EmailForwardRule>, Error> {
+        EmailForwardRule::get_all(self).await
+    }
+
+    async fn get_forward_rule(&self, id: Uuid) -> Result<Option<EmailForwardRule>, Error> {
+        EmailForwardRule::get_by_id(self, id).await
+    }
+
+    async fn upsert_forward_rule(&self, rule: &EmailForwardRule) -> Result<(), Error> {
+        rule.upsert_entry(self).await
+    }
+
+    async fn delete_forward_rule(&self, id: Uuid) -> Result<u64, Error> {
+        EmailForwardRule::delete_entry(id, self).await
+    }
+}
+
+#[async_trait]
+impl SentEmailRepo for PgPool {
+    async fn get_sent_emails(&self) -> Result<Vec<SentEmail>, Error> {
+        SentEmail::get_all(self).await
+    }
+
+    async fn get_sent_emails_by_thread(&self, thread_id: Uuid) -> Result<Vec<SentEmail>, Error> {
+        SentEmail::get_by_thread_id(self, thread_id).await
+    }
+
+    async fn get_sent_thread_id(&self, message_id: &str) -> Result<Option<Uuid>, Error> {
+        SentEmail::get_thread_id(self, message_id).await
+    }
+
+    async fn insert_sent_email(&self, sent: &SentEmail) -> Result<(), Error> {
+        sent.insert_entry(self).await
+    }
+}
+
+#[async_trait]
+impl AuditLogRepo for PgPool {
+    async fn get_recent_audit_log(&self, limit: i64) -> Result<Vec<AuditLog>, Error> {
+        AuditLog::get_recent(self, limit).await
+    }
+
+    async fn insert_audit_log(&self, entry: &AuditLog) -> Result<(), Error> {
+        entry.insert_entry(self).await
+    }
+}
+
+#[async_trait]
+impl UpdateStatusRepo for PgPool {
+    async fn get_update_status(&self) -> Result<Vec<UpdateStatus>, Error> {
+        UpdateStatus::get_all(self).await
+    }
+
+    async fn record_update_status(&self, task: &str, error: Option<&str>) -> Result<(), Error> {
+        UpdateStatus::record(task, error, self).await
+    }
+}
+
+#[async_trait]
+impl WebhookRepo for PgPool {
+    async fn get_webhooks(&self) -> Result<Vec<Webhook>, Error> {
+        Webhook::get_all(self).await
+    }
+
+    async fn get_webhook(&self, id: Uuid) -> Result<Option<Webhook>, Error> {
+        Webhook::get_by_id(self, id).await
+    }
+
+    async fn insert_webhook(&self, webhook: &Webhook) -> Result<(), Error> {
+        webhook.insert_entry(self).await
+    }
+
+    async fn delete_webhook(&self, id: Uuid) -> Result<u64, Error> {
+        Webhook::delete_entry(self, id).await
+    }
+
+    async fn get_recent_deliveries(&self, limit: i64) -> Result<Vec<WebhookDelivery>, Error> {
+        WebhookDelivery::get_recent(self, limit).await
+    }
+
+    async fn get_due_deliveries(&self) -> Result<Vec<WebhookDelivery>, Error> {
+        WebhookDelivery::get_due(self).await
+    }
+
+    async fn insert_delivery(&self, delivery: &WebhookDelivery) -> Result<(), Error> {
+        delivery.insert_entry(self).await
+    }
+
+    async fn update_delivery(&self, delivery: &WebhookDelivery) -> Result<(), Error> {
+        delivery.update_entry(self).await
+    }
+}
+
+#[async_trait]
+impl PriceAlertRepo for PgPool {
+    async fn get_price_alerts(&self) -> Result<Vec<PriceAlert>, Error> {
+        PriceAlert::get_all(self).await
+    }
+
+    async fn get_enabled_price_alerts(&self) -> Result<Vec<PriceAlert>, Error> {
+        PriceAlert::get_enabled(self).await
+    }
+
+    async fn insert_price_alert(&self, alert: &PriceAlert) -> Result<(), Error> {
+        alert.insert_entry(self).await
+    }
+
+    async fn update_price_alert_state(&self, alert: &PriceAlert) -> Result<(), Error> {
+        alert.update_state(self).await
+    }
+
+    async fn set_price_alert_enabled(&self, id: Uuid, enabled: bool) -> Result<u64, Error> {
+        PriceAlert::set_enabled(self, id, enabled).await
+    }
+
+    async fn delete_price_alert(&self, id: Uuid) -> Result<u64, Error> {
+        PriceAlert::delete_entry(self, id).await
+    }
+}
+
+#[async_trait]
+impl InstanceReplacementRepo for PgPool {
+    async fn get_replacement(&self, id: Uuid) -> Result<Option<InstanceReplacement>, Error> {
+        InstanceReplacement::get_by_id(self, id).await
+    }
+
+    async fn get_active_replacements(&self) -> Result<Vec<InstanceReplacement>, Error> {
+        InstanceReplacement::get_active(self).await
+    }
+
+    async fn get_active_replacements_by_instance(
+        &self,
+        old_instance_id: &str,
+    ) -> Result<Vec<InstanceReplacement>, Error> {
+        InstanceReplacement::get_active_by_instance(self, old_instance_id).await
+    }
+
+    async fn get_recent_replacements(&self, limit: i64) -> Result<Vec<InstanceReplacement>, Error> {
+        InstanceReplacement::get_recent(self, limit).await
+    }
+
+    async fn insert_replacement(&self, replacement: &InstanceReplacement) -> Result<(), Error> {
+        replacement.insert_entry(self).await
+    }
+
+    async fn update_replacement_from(
+        &self,
+        replacement: &InstanceReplacement,
+        current_state: ReplacementState,
+    ) -> Result<bool, Error> {
+        replacement.update_from(self, current_state).await
+    }
+
+    async fn update_replacement_spot_request_id(
+        &self,
+        replacement: &InstanceReplacement,
+    ) -> Result<(), Error> {
+        replacement.update_spot_request_id(self).await
+    }
+}
+
+#[async_trait]
+impl DrPolicyRepo for PgPool {
+    async fn get_dr_policies(&self) -> Result<Vec<DrPolicy>, Error> {
+        DrPolicy::get_all(self).await
+    }
+
+    async fn insert_dr_policy(&self, policy: &DrPolicy) -> Result<(), Error> {
+        policy.insert_entry(self).await
+    }
+
+    async fn delete_dr_policy(&self, id: Uuid) -> Result<u64, Error> {
+        DrPolicy::delete_entry(self, id).await
+    }
+}
+
+#[async_trait]
+impl EcrHistoryRepo for PgPool {
+    async fn get_latest_ecr_history(&self) -> Result<Vec<EcrImageHistory>, Error> {
+        EcrImageHistory::get_latest(self).await
+    }
+
+    async fn get_ecr_history(&self, repo: &str) -> Result<Vec<EcrImageHistory>, Error> {
+        EcrImageHistory::get_by_repo(self, repo).await
+    }
+
+    async fn insert_ecr_history(&self, entry: &EcrImageHistory) -> Result<(), Error> {
+        entry.insert_entry(self).await
+    }
+}
+
+#[async_trait]
+impl UserPreferencesRepo for PgPool {
+    async fn get_user_preferences(&self, email: &str) -> Result<UserPreferences, Error> {
+        UserPreferences::get(self, email).await
+    }
+
+    async fn upsert_user_preferences(&self, preferences: &UserPreferences) -> Result<(), Error> {
+        preferences.upsert_entry(self).await
+    }
+}
+
+#[async_trait]
+impl InventorySnapshotRepo for PgPool {
+    async fn get_latest_inventory(&self, region: &str) -> Result<Option<InventorySnapshot>, Error> {
+        InventorySnapshot::get_latest(self, region).await
+    }
+
+    async fn get_inventory_baseline(
+        &self,
+        region: &str,
+        at: OffsetDateTime,
+    ) -> Result<Option<InventorySnapshot>, Error> {
+        InventorySnapshot::get_baseline(self, region, at).await
+    }
+
+    async fn insert_inventory(&self, snapshot: &InventorySnapshot) -> Result<(), Error> {
+        snapshot.insert_entry(self).await
+    }
+
+    async fn delete_inventory_before(&self, cutoff: OffsetDateTime) -> Result<u64, Error> {
+        InventorySnapshot::delete_before(self, cutoff).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::storage::StorageBackend;
+
+    #[test]
+    fn test_storage_backend() -> Result<(), Error> {
+        for backend in [StorageBackend::Postgres, StorageBackend::Sqlite] {
+            assert_eq!(backend.to_str().parse::<StorageBackend>()?, backend);
+        }
+        assert!("mysql".parse::<StorageBackend>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+use anyhow::{format_err, Error};
+use aws_config::{sts::AssumeRoleProvider, SdkConfig};
+use aws_credential_types::provider::{ProvideCredentials, SharedCredentialsProvider};
+use aws_sdk_sts::{types::Credentials, Client as StsClient};
+use aws_types::region::Region;
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+use time::{Duration, OffsetDateTime};
+
+use crate::account_profile::AccountProfile;
+
+#[derive(Clone)]
+pub struct StsInstance {
+    sts_client: StsClient,
+    credentials_provider: Option<SharedCredentialsProvider>,
+    region: Option<StackString>,
+}
+
+impl fmt::Debug for StsInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("StsInstance")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallerIdentity {
+    pub account: StackString,
+    pub arn: StackString,
+    pub user_id: StackString,
+}
+
+/// Who requests are made as, shown in the header of the index page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityBanner {
+    pub identity: CallerIdentity,
+    pub region: StackString,
+    /// `None` for long lived access keys
+    pub credential_expiry: Option<OffsetDateTime>,
+}
+
+impl IdentityBanner {
+    /// Warning once the credentials expire within `warning`
+    #[must_use]
+    pub fn expiry_warning(&self, now: OffsetDateTime, warning: Duration) -> Option<StackString> {
+        let remaining = self.credential_expiry? - now;
+        if remaining <= Duration::ZERO {
+            Some("credentials have expired".into())
+        } else if remaining < warning {
+            Some(format_sstr!(
+                "credentials expire in {} minutes",
+                remaining.whole_minutes()
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Short lived credentials from `GetFederationToken` or `AssumeRole`
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemporaryCredentials {
+    pub access_key_id: StackString,
+    pub secret_access_key: StackString,
+    pub session_token: StackString,
+    pub expiration: OffsetDateTime,
+}
+
+impl fmt::Debug for TemporaryCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "TemporaryCredentials {{ access_key_id: {}, expiration: {} }}",
+            self.access_key_id, self.expiration
+        )
+    }
+}
+
+impl TemporaryCredentials {
+    fn from_credentials(credentials: Credentials) -> Result<Self, Error> {
+        Ok(Self {
+            access_key_id: credentials.access_key_id.into(),
+            secret_access_key: credentials.secret_access_key.into(),
+            session_token: credentials.session_token.into(),
+            expiration: OffsetDateTime::from_unix_timestamp(credentials.expiration.secs())?,
+        })
+    }
+
+    /// Shell `export` lines setting the standard aws environment variables,
+    /// to be placed at the top of a launch script
+    #[must_use]
+    pub fn to_env_exports(&self) -> StackString {
+        format_sstr!(
+            "export AWS_ACCESS_KEY_ID='{}'\nexport AWS_SECRET_ACCESS_KEY='{}'\nexport \
+             AWS_SESSION_TOKEN='{}'\n",
+            self.access_key_id,
+            self.secret_access_key,
+            self.session_token
+        )
+    }
+
+    /// Insert `to_env_exports` into `script` after its shebang line
+    #[must_use]
+    pub fn inject_into_script(&self, script: &str) -> StackString {
+        let exports = self.to_env_exports();
+        match script.strip_prefix("#!") {
+            Some(rest) => {
+                let (shebang, body) = rest.split_once('\n').unwrap_or((rest, ""));
+                format_sstr!("#!{shebang}\n{exports}{body}")
+            }
+            None => format_sstr!("{exports}{script}"),
+        }
+    }
+}
+
+impl StsInstance {
+    #[must_use]
+    pub fn new(sdk_config: &SdkConfig) -> Self {
+        Self {
+            sts_client: StsClient::from_conf(sdk_config.into()),
+            credentials_provider: sdk_config.credentials_provider(),
+            region: sdk_config.region().map(|r| r.as_ref().into()),
+        }
+    }
+
+    #[must_use]
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    /// Expiry of the credentials requests are signed with, `None` if they
+    /// don't expire
+    /// # Errors
+    /// Returns error if no credentials can be loaded
+    pub async fn get_credential_expiry(&self) -> Result<Option<OffsetDateTime>, Error> {
+        let provider = match &self.credentials_provider {
+            Some(provider) => provider,
+            None => return Ok(None),
+        };
+        let credentials = provider.provide_credentials().await?;
+        Ok(credentials.expiry().map(Into::into))
+    }
+
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_caller_identity(&self) -> Result<CallerIdentity, Error> {
+        let identity = self.sts_client.get_caller_identity().send().await?;
+        Ok(CallerIdentity {
+            account: identity
+                .account
+                .ok_or_else(|| format_err!("No account"))?
+                .into(),
+            arn: identity.arn.ok_or_else(|| format_err!("No arn"))?.into(),
+            user_id: identity
+                .user_id
+                .ok_or_else(|| format_err!("No user id"))?
+                .into(),
+        })
+    }
+
+    /// Credentials of the calling iam user restricted to `policy`, a json
+    /// policy document. Valid for `duration` (15 minutes to 36 hours).
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn get_federation_token(
+        &self,
+        name: &str,
+        policy: &str,
+        duration: Duration,
+    ) -> Result<TemporaryCredentials, Error> {
+        let credentials = self
+            .sts_client
+            .get_federation_token()
+            .name(name)
+            .policy(policy)
+            .duration_seconds(duration.whole_seconds() as i32)
+            .send()
+            .await?
+            .credentials
+            .ok_or_else(|| format_err!("No credentials"))?;
+        TemporaryCredentials::from_credentials(credentials)
+    }
+
+    /// Credentials of `role_arn`, further restricted to `policy` if given.
+    /// Valid for `duration`, up to the maximum session length of the role.
+    /// # Errors
+    /// Returns error if aws api call fails
+    pub async fn assume_role(
+        &self,
+        role_arn: &str,
+        session_name: &str,
+        policy: Option<&str>,
+        duration: Duration,
+    ) -> Result<TemporaryCredentials, Error> {
+        let credentials = self
+            .sts_client
+            .assume_role()
+            .role_arn(role_arn)
+            .role_session_name(session_name)
+            .set_policy(policy.map(Into::into))
+            .duration_seconds(duration.whole_seconds() as i32)
+            .send()
+            .await?
+            .credentials
+            .ok_or_else(|| format_err!("No credentials"))?;
+        TemporaryCredentials::from_credentials(credentials)
+    }
+
+    /// Build a config whose credentials come from assuming the profile's
+    /// role, the provider refreshes the credentials before they expire
+    pub async fn assume_role_config(sdk_config: &SdkConfig, profile: &AccountProfile) -> SdkConfig {
+        let region = profile
+            .region
+            .as_ref()
+            .map(|r| Region::new(r.to_string()))
+            .or_else(|| sdk_config.region().cloned());
+        let mut builder = AssumeRoleProvider::builder(profile.role_arn.as_str())
+            .session_name("aws-app-rust")
+            .configure(sdk_config);
+        if let Some(external_id) = &profile.external_id {
+            builder = builder.external_id(external_id.as_str());
+        }
+        if let Some(region) = region.clone() {
+            builder = builder.region(region);
+        }
+        let provider = builder.build().await;
+        let mut loader = aws_config::from_env().credentials_provider(provider);
+        if let Some(region) = region {
+            loader = loader.region(region);
+        }
+        loader.load().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::datetime, Duration};
+
+    use crate::sts_instance::{CallerIdentity, IdentityBanner, TemporaryCredentials};
+
+    #[test]
+    fn test_identity_banner_expiry_warning() {
+        let now = datetime!(2024-06-01 12:00:00 UTC);
+        let warning = Duration::minutes(30);
+        let mut banner = IdentityBanner {
+            identity: CallerIdentity {
+                account: "123456789012".into(),
+                arn: "arn:aws:sts::123456789012:assumed-role/admin/aws-app-rust".into(),
+                user_id: "AROAEXAMPLE:aws-app-rust".into(),
+            },
+            region: "us-east-1".into(),
+            credential_expiry: None,
+        };
+        assert_eq!(banner.expiry_warning(now, warning), None);
+        banner.credential_expiry = Some(now + Duration::hours(1));
+        assert_eq!(banner.expiry_warning(now, warning), None);
+        banner.credential_expiry = Some(now + Duration::minutes(10));
+        assert_eq!(
+            banner.expiry_warning(now, warning).as_deref(),
+            Some("credentials expire in 10 minutes")
+        );
+        banner.credential_expiry = Some(now - Duration::minutes(1));
+        assert_eq!(
+            banner.expiry_warning(now, warning).as_deref(),
+            Some("credentials have expired")
+        );
+    }
+
+    #[test]
+    fn test_inject_into_script() {
+        let credentials = TemporaryCredentials {
+            access_key_id: "ASIAEXAMPLE".into(),
+            secret_access_key: "secret".into(),
+            session_token: "token".into(),
+            expiration: datetime!(2024-06-01 00:00:00 UTC),
+        };
+        let exports = credentials.to_env_exports();
+        assert_eq!(
+            exports,
+            "export AWS_ACCESS_KEY_ID='ASIAEXAMPLE'\nexport AWS_SECRET_ACCESS_KEY='secret'\nexport \
+             AWS_SESSION_TOKEN='token'\n"
+        );
+        assert_eq!(
+            credentials.inject_into_script("#!/bin/bash\necho hi\n"),
+            format!("#!/bin/bash\n{exports}echo hi\n")
+        );
+        assert_eq!(
+            credentials.inject_into_script("echo hi"),
+            format!("{exports}echo hi")
+        );
+        assert!(!format!("{credentials:?}").contains("secret"));
+    }
+}
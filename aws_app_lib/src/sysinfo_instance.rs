@@ -1,7 +1,8 @@
 use parking_lot::Mutex;
+use serde::Serialize;
 use stack_string::StackString;
 use std::{collections::BTreeSet, ffi::OsStr, fmt, sync::Arc};
-use sysinfo::{Process, ProcessesToUpdate, System};
+use sysinfo::{Disk, Disks, Networks, Process, ProcessesToUpdate, System};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ProcessInfo {
@@ -47,6 +48,140 @@ impl From<&Process> for ProcessInfo {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct MemoryUsage {
+    pub total: u64,
+    pub used: u64,
+    pub available: u64,
+    pub swap_total: u64,
+    pub swap_used: u64,
+}
+
+impl MemoryUsage {
+    #[must_use]
+    pub fn used_percent(&self) -> f64 {
+        percent(self.used, self.total)
+    }
+
+    #[must_use]
+    pub fn swap_used_percent(&self) -> f64 {
+        percent(self.swap_used, self.swap_total)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum DiskAlert {
+    #[serde(rename = "ok")]
+    Ok,
+    #[serde(rename = "warning")]
+    Warning,
+    #[serde(rename = "critical")]
+    Critical,
+}
+
+impl DiskAlert {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for DiskAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiskUsage {
+    pub mount_point: StackString,
+    pub device: StackString,
+    pub file_system: StackString,
+    pub total_space: u64,
+    pub available_space: u64,
+}
+
+impl From<&Disk> for DiskUsage {
+    fn from(disk: &Disk) -> Self {
+        Self {
+            mount_point: disk.mount_point().to_string_lossy().into(),
+            device: disk.name().to_string_lossy().into(),
+            file_system: disk.file_system().to_string_lossy().into(),
+            total_space: disk.total_space(),
+            available_space: disk.available_space(),
+        }
+    }
+}
+
+impl DiskUsage {
+    #[must_use]
+    pub fn used_space(&self) -> u64 {
+        self.total_space.saturating_sub(self.available_space)
+    }
+
+    #[must_use]
+    pub fn used_percent(&self) -> f64 {
+        percent(self.used_space(), self.total_space)
+    }
+
+    /// Compare used space against the `warning` and `critical` percentages
+    #[must_use]
+    pub fn alert(&self, warning: f64, critical: f64) -> DiskAlert {
+        let used = self.used_percent();
+        if used >= critical {
+            DiskAlert::Critical
+        } else if used >= warning {
+            DiskAlert::Warning
+        } else {
+            DiskAlert::Ok
+        }
+    }
+}
+
+/// Counters since boot for one network interface
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NetworkUsage {
+    pub interface: StackString,
+    pub received_bytes: u64,
+    pub transmitted_bytes: u64,
+    pub received_packets: u64,
+    pub transmitted_packets: u64,
+    pub receive_errors: u64,
+    pub transmit_errors: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HostMetrics {
+    pub host_name: Option<StackString>,
+    pub uptime: u64,
+    pub cpu_count: usize,
+    /// Percent of all cpus used since the previous call
+    pub cpu_usage: f32,
+    pub load_average: LoadAverage,
+    pub memory: MemoryUsage,
+    pub disks: Vec<DiskUsage>,
+    pub networks: Vec<NetworkUsage>,
+}
+
+fn percent(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        used as f64 * 100.0 / total as f64
+    }
+}
+
 #[derive(Clone)]
 pub struct SysinfoInstance {
     system: Arc<Mutex<System>>,
@@ -87,13 +222,100 @@ impl SysinfoInstance {
         sys.refresh_processes(ProcessesToUpdate::All, true);
         sys.processes_by_name(name).map(Into::into).collect()
     }
+
+    /// Load, memory, disk and network usage of this host, disks with a file
+    /// system in `ignored_file_systems` (e.g. `tmpfs`) are left out
+    #[must_use]
+    pub fn get_host_metrics(&self, ignored_file_systems: &[impl AsRef<str>]) -> HostMetrics {
+        let (cpu_count, cpu_usage, memory) = {
+            let mut sys = self.system.lock();
+            sys.refresh_cpu_usage();
+            sys.refresh_memory();
+            let memory = MemoryUsage {
+                total: sys.total_memory(),
+                used: sys.used_memory(),
+                available: sys.available_memory(),
+                swap_total: sys.total_swap(),
+                swap_used: sys.used_swap(),
+            };
+            (sys.cpus().len(), sys.global_cpu_usage(), memory)
+        };
+        let load = System::load_average();
+        let mut disks: Vec<DiskUsage> = Disks::new_with_refreshed_list()
+            .iter()
+            .map(Into::into)
+            .filter(|d: &DiskUsage| {
+                !ignored_file_systems
+                    .iter()
+                    .any(|fs| fs.as_ref() == d.file_system.as_str())
+            })
+            .collect();
+        disks.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+        disks.dedup_by(|a, b| a.mount_point == b.mount_point);
+        let mut networks: Vec<NetworkUsage> = Networks::new_with_refreshed_list()
+            .iter()
+            .map(|(interface, data)| NetworkUsage {
+                interface: interface.into(),
+                received_bytes: data.total_received(),
+                transmitted_bytes: data.total_transmitted(),
+                received_packets: data.total_packets_received(),
+                transmitted_packets: data.total_packets_transmitted(),
+                receive_errors: data.total_errors_on_received(),
+                transmit_errors: data.total_errors_on_transmitted(),
+            })
+            .collect();
+        networks.sort_by(|a, b| a.interface.cmp(&b.interface));
+        HostMetrics {
+            host_name: System::host_name().map(Into::into),
+            uptime: System::uptime(),
+            cpu_count,
+            cpu_usage,
+            load_average: LoadAverage {
+                one: load.one,
+                five: load.five,
+                fifteen: load.fifteen,
+            },
+            memory,
+            disks,
+            networks,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::sysinfo_instance::SysinfoInstance;
+    use crate::sysinfo_instance::{DiskAlert, DiskUsage, SysinfoInstance};
     use anyhow::Error;
 
+    #[test]
+    fn test_disk_alert() {
+        let mut disk = DiskUsage {
+            mount_point: "/".into(),
+            device: "/dev/nvme0n1p1".into(),
+            file_system: "ext4".into(),
+            total_space: 100,
+            available_space: 50,
+        };
+        assert_eq!(disk.used_space(), 50);
+        assert_eq!(disk.alert(80.0, 90.0), DiskAlert::Ok);
+        disk.available_space = 15;
+        assert_eq!(disk.alert(80.0, 90.0), DiskAlert::Warning);
+        disk.available_space = 5;
+        assert_eq!(disk.alert(80.0, 90.0), DiskAlert::Critical);
+        disk.total_space = 0;
+        disk.available_space = 0;
+        assert_eq!(disk.alert(80.0, 90.0), DiskAlert::Ok);
+    }
+
+    #[test]
+    fn test_get_host_metrics() {
+        let sys_instance = SysinfoInstance::new(Vec::<String>::new());
+        let metrics = sys_instance.get_host_metrics(&["tmpfs"]);
+        assert!(metrics.cpu_count >= 1);
+        assert!(metrics.memory.total >= metrics.memory.used);
+        assert!(metrics.disks.iter().all(|d| d.file_system != "tmpfs"));
+    }
+
     #[test]
     fn test_sysinfo_instance() -> Result<(), Error> {
         let names = vec!["cargo"];
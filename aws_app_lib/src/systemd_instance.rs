@@ -1,25 +1,132 @@
-use anyhow::Error;
+use anyhow::{format_err, Error};
+use futures::{stream::FuturesUnordered, StreamExt, TryStreamExt};
+use log::warn;
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use stack_string::StackString;
+use stack_string::{format_sstr, StackString};
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
     convert::{TryFrom, TryInto},
     fmt,
+    str::FromStr,
+    time::Duration as StdDuration,
 };
 use time::{Duration, OffsetDateTime, UtcOffset};
-use tokio::process::Command;
+use tokio::{
+    net::TcpStream,
+    process::Command,
+    time::{sleep, timeout},
+};
+
+use crate::{config::Config, date_time_wrapper::DateTimeWrapper};
+
+const HEALTH_CHECK_ATTEMPTS: usize = 10;
+const HEALTH_PROBE_TIMEOUT: StdDuration = StdDuration::from_secs(5);
 
-use crate::date_time_wrapper::DateTimeWrapper;
+const TIMER_PROPERTIES: &str =
+    "Id,ActiveState,SubState,UnitFileState,Unit,NextElapseUSecRealtime,LastTriggerUSec";
+const SOCKET_PROPERTIES: &str =
+    "Id,ActiveState,SubState,UnitFileState,Triggers,Listen,NConnections,NAccepted";
 
 #[derive(Default, Clone)]
 pub struct SystemdInstance {
     services: BTreeSet<StackString>,
+    timers: BTreeSet<StackString>,
+    sockets: BTreeSet<StackString>,
 }
 
 impl SystemdInstance {
     pub fn new(services: &[impl AsRef<str>]) -> Self {
         let services = services.iter().map(AsRef::as_ref).map(Into::into).collect();
-        Self { services }
+        Self {
+            services,
+            ..Self::default()
+        }
+    }
+
+    /// Timer units listed by `list_timers`, the `.timer` suffix is optional
+    #[must_use]
+    pub fn timers(mut self, timers: &[impl AsRef<str>]) -> Self {
+        self.timers = timers
+            .iter()
+            .map(|t| unit_name(t.as_ref(), UnitKind::Timer))
+            .collect();
+        self
+    }
+
+    /// Socket units listed by `list_sockets`, the `.socket` suffix is
+    /// optional
+    #[must_use]
+    pub fn sockets(mut self, sockets: &[impl AsRef<str>]) -> Self {
+        self.sockets = sockets
+            .iter()
+            .map(|s| unit_name(s.as_ref(), UnitKind::Socket))
+            .collect();
+        self
+    }
+
+    /// Whether `unit` is one of the configured services, timers or sockets
+    #[must_use]
+    pub fn is_managed(&self, unit: &str) -> bool {
+        self.services.contains(unit) || self.timers.contains(unit) || self.sockets.contains(unit)
+    }
+
+    /// # Errors
+    /// Returns error if spawn of systemctl fails
+    pub async fn list_timers(&self) -> Result<Vec<TimerStatus>, Error> {
+        let futures: FuturesUnordered<_> = self
+            .timers
+            .iter()
+            .map(|timer| async move {
+                let output = show_unit(timer, TIMER_PROPERTIES).await?;
+                Ok::<_, Error>(TimerStatus::parse(timer, &output))
+            })
+            .collect();
+        let mut timers: Vec<_> = futures.try_collect().await?;
+        timers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(timers)
+    }
+
+    /// # Errors
+    /// Returns error if spawn of systemctl fails
+    pub async fn list_sockets(&self) -> Result<Vec<SocketStatus>, Error> {
+        let futures: FuturesUnordered<_> = self
+            .sockets
+            .iter()
+            .map(|socket| async move {
+                let output = show_unit(socket, SOCKET_PROPERTIES).await?;
+                Ok::<_, Error>(SocketStatus::parse(socket, &output))
+            })
+            .collect();
+        let mut sockets: Vec<_> = futures.try_collect().await?;
+        sockets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(sockets)
+    }
+
+    /// Enable or disable `unit` and start or stop it along with it, so a
+    /// timer is scheduled (or unscheduled) without waiting for a reboot
+    /// # Errors
+    /// Returns error if spawn of systemctl fails or systemctl exits
+    /// unsuccessfully
+    pub async fn unit_file_action(
+        &self,
+        enable: bool,
+        unit: impl AsRef<str>,
+    ) -> Result<StackString, Error> {
+        let action = if enable { "enable" } else { "disable" };
+        let command = Command::new("sudo")
+            .args(["systemctl", action, "--now", unit.as_ref()])
+            .output()
+            .await?;
+        if !command.status.success() {
+            return Err(format_err!(
+                "systemctl {action} {} failed: {}",
+                unit.as_ref(),
+                String::from_utf8_lossy(&command.stderr).trim()
+            ));
+        }
+        let output = String::from_utf8_lossy(&command.stderr);
+        Ok(output.as_ref().into())
     }
 
     /// # Errors
@@ -130,6 +237,678 @@ impl SystemdInstance {
         let stdout = String::from_utf8_lossy(&command.stdout);
         Ok(stdout.as_ref().into())
     }
+
+    /// Wait for the unit to become active, then retry `probe` until it
+    /// succeeds
+    /// # Errors
+    /// Returns error if spawn of systemctl fails
+    pub async fn check_health(
+        &self,
+        service: impl AsRef<str>,
+        probe: Option<&HealthProbe>,
+    ) -> Result<RestartOutcome, Error> {
+        let service = service.as_ref();
+        let mut status = self.get_service_status(service).await?;
+        for _ in 0..HEALTH_CHECK_ATTEMPTS {
+            if status.is_active() {
+                break;
+            }
+            sleep(StdDuration::from_secs(1)).await;
+            status = self.get_service_status(service).await?;
+        }
+        if !status.is_active() {
+            return Ok(RestartOutcome::Degraded(format_sstr!(
+                "unit state {} {}",
+                status.active_state,
+                status.sub_state
+            )));
+        }
+        if let Some(probe) = probe {
+            let client = Client::new();
+            let mut last_error = StackString::new();
+            for _ in 0..HEALTH_CHECK_ATTEMPTS {
+                match probe.probe(&client).await {
+                    Ok(()) => return Ok(RestartOutcome::Healthy),
+                    Err(e) => last_error = e,
+                }
+                sleep(StdDuration::from_secs(1)).await;
+            }
+            return Ok(RestartOutcome::Degraded(last_error));
+        }
+        Ok(RestartOutcome::Healthy)
+    }
+
+    /// Probe each running service that has a configured probe once
+    pub async fn probe_services(
+        &self,
+        config: &Config,
+        services: &BTreeMap<StackString, RunStatus>,
+    ) -> BTreeMap<StackString, ProbeStatus> {
+        let probes = health_probes(&config.systemd_health_checks);
+        let client = &Client::new();
+        let futures: FuturesUnordered<_> = probes
+            .into_iter()
+            .filter(|(service, _)| services.get(service) == Some(&RunStatus::Running))
+            .map(|(service, probe)| async move {
+                let result = probe.probe(client).await;
+                (
+                    service,
+                    ProbeStatus {
+                        probe,
+                        error: result.err(),
+                    },
+                )
+            })
+            .collect();
+        futures.collect().await
+    }
+
+    /// `sudo systemctl restart service`, returns what systemctl printed when
+    /// the restart itself failed
+    async fn restart_unit(&self, service: &str) -> Result<Option<StackString>, Error> {
+        let command = Command::new("sudo")
+            .args(["systemctl", "restart", service])
+            .output()
+            .await?;
+        if command.status.success() {
+            return Ok(None);
+        }
+        let stderr = String::from_utf8_lossy(&command.stderr);
+        let stderr = stderr.trim();
+        Ok(Some(if stderr.is_empty() {
+            format_sstr!("systemctl exited with {}", command.status)
+        } else {
+            stderr.into()
+        }))
+    }
+
+    /// Order the configured services are restarted in: every service after
+    /// the services it depends on, both those declared in
+    /// `systemd_dependencies` and the `Requires`/`After` relations between
+    /// managed units that systemd reports
+    /// # Errors
+    /// Returns error if
+    ///     * dependencies contain a cycle
+    ///     * spawn of systemctl fails
+    pub async fn restart_plan(&self, config: &Config) -> Result<Vec<StackString>, Error> {
+        let graph = self.get_dependency_graph().await?;
+        let dependencies = merge_dependencies(&config.systemd_dependencies, &graph);
+        restart_order(&config.systemd_services, &dependencies)
+    }
+
+    /// Restart the configured services in dependency order, verifying each
+    /// one before moving on, stops at the first service that fails to come
+    /// back healthy.
+    /// # Errors
+    /// Returns error if
+    ///     * dependencies contain a cycle
+    ///     * spawn of systemctl fails
+    pub async fn restart_all(
+        &self,
+        config: &Config,
+        skip: &[&str],
+    ) -> Result<Vec<RestartResult>, Error> {
+        let order = self.restart_plan(config).await?;
+        self.restart_services(config, order, skip).await
+    }
+
+    /// Restart `services` in the order given, verifying each one and stopping
+    /// at the first failure
+    /// # Errors
+    /// Returns error if spawn of systemctl fails
+    pub async fn restart_services(
+        &self,
+        config: &Config,
+        services: Vec<StackString>,
+        skip: &[&str],
+    ) -> Result<Vec<RestartResult>, Error> {
+        let probes = health_probes(&config.systemd_health_checks);
+        let mut results = Vec::with_capacity(services.len());
+        let mut failed = false;
+        for service in services {
+            if skip.contains(&service.as_str())
+                || config.systemd_restart_blacklist.contains(&service)
+            {
+                results.push(RestartResult::new(service, RestartOutcome::Skipped));
+                continue;
+            }
+            if failed {
+                results.push(RestartResult::new(service, RestartOutcome::NotAttempted));
+                continue;
+            }
+            let started_at = OffsetDateTime::now_utc();
+            let outcome = match self.restart_unit(&service).await? {
+                Some(error) => RestartOutcome::Failed(error),
+                None => self.check_health(&service, probes.get(&service)).await?,
+            };
+            failed = !outcome.is_healthy();
+            results.push(RestartResult {
+                service,
+                outcome,
+                started_at: Some(started_at),
+                elapsed_seconds: Some((OffsetDateTime::now_utc() - started_at).as_seconds_f64()),
+            });
+        }
+        Ok(results)
+    }
+
+    /// # Errors
+    /// Returns error if spawn of systemctl fails
+    pub async fn get_unit_dependencies(
+        &self,
+        service: impl AsRef<str>,
+    ) -> Result<UnitDependencies, Error> {
+        let command = Command::new("systemctl")
+            .args([
+                "show",
+                "-p",
+                "Requires,BindsTo,After,RequiredBy,BoundBy",
+                service.as_ref(),
+            ])
+            .output()
+            .await?;
+        let stdout = String::from_utf8_lossy(&command.stdout);
+        Ok(UnitDependencies::parse(&stdout))
+    }
+
+    /// # Errors
+    /// Returns error if spawn of systemctl fails
+    pub async fn get_dependency_graph(
+        &self,
+    ) -> Result<BTreeMap<StackString, UnitDependencies>, Error> {
+        let futures: FuturesUnordered<_> = self
+            .services
+            .iter()
+            .map(|service| async move {
+                self.get_unit_dependencies(service)
+                    .await
+                    .map(|deps| (service.clone(), deps))
+            })
+            .collect();
+        futures.try_collect().await
+    }
+}
+
+async fn show_unit(unit: &str, properties: &str) -> Result<String, Error> {
+    let command = Command::new("systemctl")
+        .args(["show", "--timestamp=unix", "-p", properties, unit])
+        .output()
+        .await?;
+    Ok(String::from_utf8_lossy(&command.stdout).into_owned())
+}
+
+/// `name` with the suffix for `kind` appended unless it's already there
+fn unit_name(name: &str, kind: UnitKind) -> StackString {
+    let suffix = kind.suffix();
+    if name.ends_with(suffix) {
+        name.into()
+    } else {
+        format_sstr!("{name}{suffix}")
+    }
+}
+
+/// Timestamps as printed by `systemctl show --timestamp=unix`, e.g.
+/// `@1717642800`, unset timestamps are empty or `n/a`
+fn parse_unix_timestamp(value: &str) -> Option<OffsetDateTime> {
+    let seconds: i64 = value.trim().strip_prefix('@')?.parse().ok()?;
+    OffsetDateTime::from_unix_timestamp(seconds).ok()
+}
+
+fn non_empty(value: &str) -> Option<StackString> {
+    let value = value.trim();
+    if value.is_empty() || value == "n/a" {
+        None
+    } else {
+        Some(value.into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitKind {
+    Timer,
+    Socket,
+}
+
+impl UnitKind {
+    #[must_use]
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Timer => ".timer",
+            Self::Socket => ".socket",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TimerStatus {
+    pub name: StackString,
+    pub active_state: StackString,
+    pub sub_state: StackString,
+    pub unit_file_state: StackString,
+    /// Unit the timer activates
+    pub unit: Option<StackString>,
+    pub next_trigger: Option<OffsetDateTime>,
+    pub last_trigger: Option<OffsetDateTime>,
+}
+
+impl TimerStatus {
+    fn parse(name: &str, output: &str) -> Self {
+        let mut status = Self {
+            name: name.into(),
+            ..Self::default()
+        };
+        for (key, val) in output.split('\n').filter_map(|line| line.split_once('=')) {
+            match key {
+                "ActiveState" => status.active_state = val.into(),
+                "SubState" => status.sub_state = val.into(),
+                "UnitFileState" => status.unit_file_state = val.into(),
+                "Unit" => status.unit = non_empty(val),
+                "NextElapseUSecRealtime" => status.next_trigger = parse_unix_timestamp(val),
+                "LastTriggerUSec" => status.last_trigger = parse_unix_timestamp(val),
+                _ => (),
+            }
+        }
+        status
+    }
+
+    /// Active and with a next trigger time
+    #[must_use]
+    pub fn is_scheduled(&self) -> bool {
+        self.active_state == "active" && self.next_trigger.is_some()
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.unit_file_state == "enabled"
+    }
+}
+
+impl fmt::Display for TimerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let next = self
+            .next_trigger
+            .map_or_else(|| "never".into(), |t| format_sstr!("{t}"));
+        let last = self
+            .last_trigger
+            .map_or_else(|| "never".into(), |t| format_sstr!("{t}"));
+        write!(
+            f,
+            "{} {} {} {} next {next} last {last}",
+            self.name, self.active_state, self.sub_state, self.unit_file_state
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SocketStatus {
+    pub name: StackString,
+    pub active_state: StackString,
+    pub sub_state: StackString,
+    pub unit_file_state: StackString,
+    /// Units activated by connections to the socket
+    pub triggers: Vec<StackString>,
+    pub listen: Vec<StackString>,
+    pub connections: Option<u64>,
+    pub accepted: Option<u64>,
+}
+
+impl SocketStatus {
+    fn parse(name: &str, output: &str) -> Self {
+        let mut status = Self {
+            name: name.into(),
+            ..Self::default()
+        };
+        for (key, val) in output.split('\n').filter_map(|line| line.split_once('=')) {
+            match key {
+                "ActiveState" => status.active_state = val.into(),
+                "SubState" => status.sub_state = val.into(),
+                "UnitFileState" => status.unit_file_state = val.into(),
+                "Triggers" => status
+                    .triggers
+                    .extend(val.split_whitespace().map(Into::into)),
+                "Listen" => status.listen.extend(non_empty(val)),
+                "NConnections" => status.connections = val.parse().ok(),
+                "NAccepted" => status.accepted = val.parse().ok(),
+                _ => (),
+            }
+        }
+        status
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.unit_file_state == "enabled"
+    }
+}
+
+impl fmt::Display for SocketStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} connections {} accepted {}",
+            self.name,
+            self.active_state,
+            self.sub_state,
+            self.unit_file_state,
+            self.listen.join(","),
+            self.connections.unwrap_or(0),
+            self.accepted.unwrap_or(0),
+        )
+    }
+}
+
+/// Services in `graph` affected by a restart of `service`, i.e. everything
+/// which transitively requires it, in the order they should be restarted
+/// (`service` itself comes first).
+/// # Errors
+/// Returns error if the dependencies contain a cycle
+pub fn restart_impact(
+    service: &str,
+    graph: &BTreeMap<StackString, UnitDependencies>,
+) -> Result<Vec<StackString>, Error> {
+    let mut affected: BTreeSet<&str> = BTreeSet::new();
+    affected.insert(service);
+    let mut stack = vec![service];
+    while let Some(current) = stack.pop() {
+        for (name, deps) in graph {
+            let depends = deps.requires.contains(current)
+                || graph
+                    .get(current)
+                    .map_or(false, |d| d.required_by.contains(name));
+            if depends && affected.insert(name.as_str()) {
+                stack.push(name.as_str());
+            }
+        }
+    }
+    let services: Vec<&str> = std::iter::once(service)
+        .chain(
+            graph
+                .keys()
+                .map(StackString::as_str)
+                .filter(|s| *s != service && affected.contains(s)),
+        )
+        .collect();
+    let dependencies: Vec<StackString> = graph
+        .iter()
+        .filter(|(name, _)| affected.contains(name.as_str()))
+        .flat_map(|(name, deps)| {
+            deps.requires
+                .iter()
+                .chain(deps.after.iter())
+                .filter(|d| affected.contains(d.as_str()))
+                .map(move |d| format_sstr!("{name}:{d}"))
+        })
+        .collect();
+    restart_order(&services, &dependencies)
+}
+
+/// `dependencies` (`service:dependency` entries) extended with the units each
+/// service of `graph` requires or is ordered after, restricted to the
+/// services in `graph`
+#[must_use]
+pub fn merge_dependencies(
+    dependencies: &[impl AsRef<str>],
+    graph: &BTreeMap<StackString, UnitDependencies>,
+) -> Vec<StackString> {
+    let mut merged: Vec<StackString> = dependencies.iter().map(|d| d.as_ref().into()).collect();
+    for (name, deps) in graph {
+        for dependency in deps.requires.iter().chain(deps.after.iter()) {
+            if dependency == name || !graph.contains_key(dependency) {
+                continue;
+            }
+            let entry = format_sstr!("{name}:{dependency}");
+            if !merged.contains(&entry) {
+                merged.push(entry);
+            }
+        }
+    }
+    merged
+}
+
+/// Order `services` so that each one comes after its dependencies, where
+/// `dependencies` entries have the form `service:dependency`.  Services
+/// otherwise keep their configured order.
+/// # Errors
+/// Returns error if the dependencies contain a cycle
+pub fn restart_order(
+    services: &[impl AsRef<str>],
+    dependencies: &[impl AsRef<str>],
+) -> Result<Vec<StackString>, Error> {
+    let services: Vec<StackString> = services.iter().map(|s| s.as_ref().into()).collect();
+    let mut deps: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for entry in dependencies {
+        if let Some((service, dependency)) = entry.as_ref().split_once(':') {
+            let service = services.iter().find(|s| s.as_str() == service.trim());
+            let dependency = services.iter().find(|s| s.as_str() == dependency.trim());
+            if let (Some(service), Some(dependency)) = (service, dependency) {
+                deps.entry(service.as_str())
+                    .or_default()
+                    .insert(dependency.as_str());
+            }
+        }
+    }
+    let mut ordered: Vec<StackString> = Vec::with_capacity(services.len());
+    while ordered.len() < services.len() {
+        let next = services
+            .iter()
+            .find(|s| {
+                !ordered.contains(s)
+                    && deps.get(s.as_str()).map_or(true, |d| {
+                        d.iter().all(|d| ordered.iter().any(|o| o.as_str() == *d))
+                    })
+            })
+            .ok_or_else(|| {
+                let remaining: Vec<_> = services
+                    .iter()
+                    .filter(|s| !ordered.contains(s))
+                    .map(StackString::as_str)
+                    .collect();
+                format_err!(
+                    "Cycle in systemd dependencies between {}",
+                    remaining.join(", ")
+                )
+            })?;
+        ordered.push(next.clone());
+    }
+    Ok(ordered)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnitDependencies {
+    pub requires: BTreeSet<StackString>,
+    pub after: BTreeSet<StackString>,
+    pub required_by: BTreeSet<StackString>,
+}
+
+impl UnitDependencies {
+    fn parse(output: &str) -> Self {
+        let mut deps = Self::default();
+        for (key, val) in output.split('\n').filter_map(|line| line.split_once('=')) {
+            let units = val.split_whitespace().map(|unit| {
+                let unit = unit.strip_suffix(".service").unwrap_or(unit);
+                StackString::from(unit)
+            });
+            match key {
+                "Requires" | "BindsTo" => deps.requires.extend(units),
+                "After" => deps.after.extend(units),
+                "RequiredBy" | "BoundBy" => deps.required_by.extend(units),
+                _ => (),
+            }
+        }
+        deps
+    }
+}
+
+/// How a service is verified after a restart, configured in
+/// `systemd_health_checks` as `service=http://host/path` or `service=tcp:port`
+/// (`tcp:host:port` for other hosts)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthProbe {
+    /// Expect a success status from a GET request
+    Http(StackString),
+    /// Expect the address to accept a connection
+    Tcp(StackString),
+}
+
+impl HealthProbe {
+    /// Probe once, `client` is used for http probes, returns why the probe
+    /// failed
+    /// # Errors
+    /// Returns error if the request or connection fails
+    pub async fn probe(&self, client: &Client) -> Result<(), StackString> {
+        match self {
+            Self::Http(url) => match client
+                .get(url.as_str())
+                .timeout(HEALTH_PROBE_TIMEOUT)
+                .send()
+                .await
+            {
+                Ok(resp) if resp.status().is_success() => Ok(()),
+                Ok(resp) => Err(format_sstr!("{url} returned {}", resp.status())),
+                Err(e) => Err(format_sstr!("{url} failed {e}")),
+            },
+            Self::Tcp(addr) => {
+                match timeout(HEALTH_PROBE_TIMEOUT, TcpStream::connect(addr.as_str())).await {
+                    Ok(Ok(_)) => Ok(()),
+                    Ok(Err(e)) => Err(format_sstr!("{addr} connect failed {e}")),
+                    Err(_) => Err(format_sstr!("{addr} connect timed out")),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for HealthProbe {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(Self::Http(s.into()));
+        }
+        let addr = s
+            .strip_prefix("tcp:")
+            .ok_or_else(|| format_err!("unknown health probe {s}"))?;
+        let addr = addr.trim_start_matches("//");
+        let addr = if addr.parse::<u16>().is_ok() {
+            format_sstr!("127.0.0.1:{addr}")
+        } else {
+            addr.into()
+        };
+        match addr.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(Self::Tcp(addr))
+            }
+            _ => Err(format_err!("invalid tcp health probe {s}")),
+        }
+    }
+}
+
+impl fmt::Display for HealthProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(url) => write!(f, "{url}"),
+            Self::Tcp(addr) => write!(f, "tcp:{addr}"),
+        }
+    }
+}
+
+/// Parse `service=probe` entries of `systemd_health_checks`, invalid entries
+/// are logged and ignored
+#[must_use]
+pub fn health_probes(entries: &[impl AsRef<str>]) -> HashMap<StackString, HealthProbe> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let entry = entry.as_ref();
+            let (service, probe) = entry.split_once('=')?;
+            match probe.parse() {
+                Ok(probe) => Some((service.trim().into(), probe)),
+                Err(e) => {
+                    warn!("ignoring health check {entry}: {e}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Result of probing a running service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeStatus {
+    pub probe: HealthProbe,
+    /// Why the probe failed, `None` if the service is healthy
+    pub error: Option<StackString>,
+}
+
+impl ProbeStatus {
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl fmt::Display for ProbeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error {
+            None => f.write_str("healthy"),
+            Some(error) => write!(f, "unhealthy: {error}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "message", rename_all = "snake_case")]
+pub enum RestartOutcome {
+    Healthy,
+    /// The unit restarted but isn't active or its health check fails
+    Degraded(StackString),
+    /// `systemctl restart` itself failed
+    Failed(StackString),
+    Skipped,
+    NotAttempted,
+}
+
+impl RestartOutcome {
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, Self::Healthy | Self::Skipped)
+    }
+}
+
+impl fmt::Display for RestartOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Healthy => f.write_str("healthy"),
+            Self::Degraded(reason) => write!(f, "degraded: {reason}"),
+            Self::Failed(reason) => write!(f, "failed: {reason}"),
+            Self::Skipped => f.write_str("skipped"),
+            Self::NotAttempted => f.write_str("not attempted"),
+        }
+    }
+}
+
+/// Outcome of restarting one service of a restart chain
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RestartResult {
+    pub service: StackString,
+    pub outcome: RestartOutcome,
+    /// Unset for services that weren't restarted
+    pub started_at: Option<OffsetDateTime>,
+    /// Seconds from the restart until the service was found healthy (or
+    /// gave up)
+    pub elapsed_seconds: Option<f64>,
+}
+
+impl RestartResult {
+    #[must_use]
+    pub fn new(service: impl Into<StackString>, outcome: RestartOutcome) -> Self {
+        Self {
+            service: service.into(),
+            outcome,
+            started_at: None,
+            elapsed_seconds: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -164,6 +943,13 @@ pub struct ServiceStatus {
     memory: Option<u64>,
 }
 
+impl ServiceStatus {
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.active_state == "active"
+    }
+}
+
 impl fmt::Display for ServiceStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -226,8 +1012,227 @@ impl fmt::Display for ServiceLogEntry {
 #[cfg(test)]
 mod tests {
     use anyhow::Error;
+    use reqwest::Client;
+    use stack_string::{format_sstr, StackString};
+
+    use std::collections::BTreeMap;
+
+    use crate::systemd_instance::{
+        health_probes, merge_dependencies, restart_impact, restart_order, unit_name, HealthProbe,
+        RestartOutcome, RestartResult, SocketStatus, SystemdInstance, TimerStatus,
+        UnitDependencies, UnitKind,
+    };
+
+    #[test]
+    fn test_timer_status_parse() {
+        let output = "Unit=db-backup.service\nNextElapseUSecRealtime=@1717642800\n\
+                      LastTriggerUSec=@1717556400\nId=db-backup.timer\nActiveState=active\n\
+                      SubState=waiting\nUnitFileState=enabled\n";
+        let timer = TimerStatus::parse("db-backup.timer", output);
+        assert_eq!(timer.unit.as_deref(), Some("db-backup.service"));
+        assert_eq!(
+            timer.next_trigger.map(|t| t.unix_timestamp()),
+            Some(1_717_642_800)
+        );
+        assert_eq!(
+            timer.last_trigger.map(|t| t.unix_timestamp()),
+            Some(1_717_556_400)
+        );
+        assert!(timer.is_scheduled());
+        assert!(timer.is_enabled());
+
+        let output = "Unit=db-backup.service\nNextElapseUSecRealtime=\nLastTriggerUSec=n/a\n\
+                      ActiveState=inactive\nSubState=dead\nUnitFileState=disabled\n";
+        let timer = TimerStatus::parse("db-backup.timer", output);
+        assert_eq!(timer.next_trigger, None);
+        assert_eq!(timer.last_trigger, None);
+        assert!(!timer.is_scheduled());
+        assert!(!timer.is_enabled());
+    }
+
+    #[test]
+    fn test_socket_status_parse() {
+        let output = "Triggers=docker.service\nListen=/run/docker.sock (Stream)\n\
+                      NConnections=2\nNAccepted=17\nActiveState=active\nSubState=running\n\
+                      UnitFileState=enabled\n";
+        let socket = SocketStatus::parse("docker.socket", output);
+        assert_eq!(socket.triggers, vec![StackString::from("docker.service")]);
+        assert_eq!(
+            socket.listen,
+            vec![StackString::from("/run/docker.sock (Stream)")]
+        );
+        assert_eq!(socket.connections, Some(2));
+        assert_eq!(socket.accepted, Some(17));
+        assert!(socket.is_enabled());
+    }
 
-    use crate::systemd_instance::SystemdInstance;
+    #[test]
+    fn test_unit_name() {
+        assert_eq!(unit_name("db-backup", UnitKind::Timer), "db-backup.timer");
+        assert_eq!(
+            unit_name("db-backup.timer", UnitKind::Timer),
+            "db-backup.timer"
+        );
+        assert_eq!(unit_name("docker", UnitKind::Socket), "docker.socket");
+        let systemd = SystemdInstance::new(&["nginx"]).timers(&["db-backup"]);
+        assert!(systemd.is_managed("nginx"));
+        assert!(systemd.is_managed("db-backup.timer"));
+        assert!(!systemd.is_managed("sshd"));
+    }
+
+    #[test]
+    fn test_restart_order() -> Result<(), Error> {
+        let services = ["nginx", "aws-app-http", "auth-server-rust", "postgresql"];
+        let deps = [
+            "aws-app-http:auth-server-rust",
+            "auth-server-rust:postgresql",
+        ];
+        let order = restart_order(&services, &deps)?;
+        let order: Vec<_> = order.iter().map(StackString::as_str).collect();
+        assert_eq!(
+            order,
+            vec!["nginx", "postgresql", "auth-server-rust", "aws-app-http"]
+        );
+
+        let deps = ["aws-app-http:nginx", "nginx:aws-app-http"];
+        let error = restart_order(&services, &deps).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cycle in systemd dependencies between nginx, aws-app-http"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_dependencies() -> Result<(), Error> {
+        let mut graph = BTreeMap::new();
+        graph.insert(
+            "auth-server-rust".into(),
+            UnitDependencies::parse("Requires=system.slice\nAfter=postgresql.service\n"),
+        );
+        graph.insert("postgresql".into(), UnitDependencies::default());
+        graph.insert(
+            "aws-app-http".into(),
+            UnitDependencies::parse("After=network.target\n"),
+        );
+        let merged = merge_dependencies(&["aws-app-http:auth-server-rust"], &graph);
+        assert_eq!(
+            merged,
+            vec![
+                "aws-app-http:auth-server-rust",
+                "auth-server-rust:postgresql"
+            ]
+        );
+
+        let services = ["aws-app-http", "auth-server-rust", "postgresql"];
+        let order = restart_order(&services, &merged)?;
+        let order: Vec<_> = order.iter().map(StackString::as_str).collect();
+        assert_eq!(
+            order,
+            vec!["postgresql", "auth-server-rust", "aws-app-http"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_restart_result_json() -> Result<(), Error> {
+        let result = RestartResult::new(
+            "nginx",
+            RestartOutcome::Failed("Job for nginx.service failed".into()),
+        );
+        assert_eq!(
+            serde_json::to_value(&result)?,
+            serde_json::json!({
+                "service": "nginx",
+                "outcome": {"status": "failed", "message": "Job for nginx.service failed"},
+                "started_at": null,
+                "elapsed_seconds": null,
+            })
+        );
+        let skipped = serde_json::to_value(RestartOutcome::Skipped)?;
+        assert_eq!(skipped, serde_json::json!({"status": "skipped"}));
+        assert!(!RestartOutcome::Failed("x".into()).is_healthy());
+        Ok(())
+    }
+
+    #[test]
+    fn test_health_probes() -> Result<(), Error> {
+        assert_eq!(
+            "http://localhost:3096/health".parse::<HealthProbe>()?,
+            HealthProbe::Http("http://localhost:3096/health".into())
+        );
+        assert_eq!(
+            "tcp:5432".parse::<HealthProbe>()?,
+            HealthProbe::Tcp("127.0.0.1:5432".into())
+        );
+        assert_eq!(
+            "tcp://db.local:5432".parse::<HealthProbe>()?,
+            HealthProbe::Tcp("db.local:5432".into())
+        );
+        assert!("tcp:db.local".parse::<HealthProbe>().is_err());
+        assert!("tcp:70000".parse::<HealthProbe>().is_err());
+        assert!("ftp://localhost".parse::<HealthProbe>().is_err());
+
+        let probes = health_probes(&[
+            "aws-app-http=http://localhost:3096/aws/index.html",
+            "postgresql = tcp:5432",
+            "nginx=port 80",
+            "no-probe",
+        ]);
+        assert_eq!(probes.len(), 2);
+        assert_eq!(
+            probes.get("postgresql"),
+            Some(&HealthProbe::Tcp("127.0.0.1:5432".into()))
+        );
+        assert_eq!(
+            probes["aws-app-http"].to_string(),
+            "http://localhost:3096/aws/index.html"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tcp_probe() -> Result<(), Error> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let probe: HealthProbe = format_sstr!("tcp:{port}").parse()?;
+        let client = Client::new();
+        assert_eq!(probe.probe(&client).await, Ok(()));
+        drop(listener);
+        assert!(probe.probe(&client).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_restart_impact() -> Result<(), Error> {
+        let postgresql = UnitDependencies::parse(
+            "Requires=system.slice sysinit.target\nAfter=network.target\nRequiredBy=\
+             auth-server-rust.service\nBoundBy=\n",
+        );
+        assert!(postgresql.required_by.contains("auth-server-rust"));
+        let auth = UnitDependencies::parse(
+            "Requires=system.slice\nAfter=postgresql.service network.target\n",
+        );
+        let aws = UnitDependencies::parse(
+            "Requires=auth-server-rust.service\nAfter=auth-server-rust.service\n",
+        );
+        let mut graph = BTreeMap::new();
+        graph.insert("postgresql".into(), postgresql);
+        graph.insert("auth-server-rust".into(), auth);
+        graph.insert("aws-app-http".into(), aws);
+        graph.insert("nginx".into(), UnitDependencies::default());
+
+        let order = restart_impact("postgresql", &graph)?;
+        let order: Vec<_> = order.iter().map(StackString::as_str).collect();
+        assert_eq!(
+            order,
+            vec!["postgresql", "auth-server-rust", "aws-app-http"]
+        );
+
+        let order = restart_impact("nginx", &graph)?;
+        assert_eq!(order.len(), 1);
+        Ok(())
+    }
 
     #[tokio::test]
     #[ignore]
@@ -0,0 +1,190 @@
+use anyhow::{format_err, Error};
+use serde::Serialize;
+use stack_string::StackString;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    ec2_instance::{AmiInfo, Ec2InstanceInfo, SnapshotInfo, VolumeInfo},
+    resource_type::ResourceType,
+};
+
+/// Tag key and optional value to search for, parsed from `key=value` or
+/// `key:value`. With `=` the key may itself contain `:`, as in
+/// `aws:cloudformation:stack-name=blog`. A bare key matches any value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TagQuery {
+    pub key: StackString,
+    pub value: Option<StackString>,
+}
+
+impl TagQuery {
+    /// # Errors
+    /// Returns error if the key is empty
+    pub fn parse(query: &str) -> Result<Self, Error> {
+        let query = query.trim();
+        let (key, value) = match query.split_once('=').or_else(|| query.split_once(':')) {
+            Some((key, value)) => (key.trim(), Some(value.trim())),
+            None => (query, None),
+        };
+        if key.is_empty() {
+            return Err(format_err!("No tag key in {query}"));
+        }
+        Ok(Self {
+            key: key.into(),
+            value: value.filter(|v| !v.is_empty()).map(Into::into),
+        })
+    }
+
+    #[must_use]
+    pub fn matches(&self, tags: &HashMap<StackString, StackString>) -> bool {
+        match (tags.get(&self.key), &self.value) {
+            (Some(value), Some(expected)) => value == expected,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+/// An instance, volume, snapshot or ami matched by a tag search
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TaggedResource {
+    pub resource_type: ResourceType,
+    pub id: StackString,
+    pub name: Option<StackString>,
+    pub state: StackString,
+    pub tags: BTreeMap<StackString, StackString>,
+}
+
+impl TaggedResource {
+    fn new(
+        resource_type: ResourceType,
+        id: &StackString,
+        state: &StackString,
+        tags: &HashMap<StackString, StackString>,
+    ) -> Self {
+        Self {
+            resource_type,
+            id: id.clone(),
+            name: tags.get("Name").cloned(),
+            state: state.clone(),
+            tags: tags.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        }
+    }
+}
+
+/// Resources whose tags match `query`, instances first then volumes,
+/// snapshots and amis
+#[must_use]
+pub fn search_tags(
+    query: &TagQuery,
+    instances: &[Ec2InstanceInfo],
+    volumes: &[VolumeInfo],
+    snapshots: &[SnapshotInfo],
+    amis: &[AmiInfo],
+) -> Vec<TaggedResource> {
+    let instances = instances
+        .iter()
+        .filter(|i| i.state != "terminated" && query.matches(&i.tags))
+        .map(|i| TaggedResource::new(ResourceType::Instances, &i.id, &i.state, &i.tags));
+    let volumes = volumes
+        .iter()
+        .filter(|v| query.matches(&v.tags))
+        .map(|v| TaggedResource::new(ResourceType::Volume, &v.id, &v.state, &v.tags));
+    let snapshots = snapshots
+        .iter()
+        .filter(|s| query.matches(&s.tags))
+        .map(|s| TaggedResource::new(ResourceType::Snapshot, &s.id, &s.state, &s.tags));
+    let amis = amis.iter().filter(|a| query.matches(&a.tags)).map(|a| {
+        let mut resource = TaggedResource::new(ResourceType::Ami, &a.id, &a.state, &a.tags);
+        resource.name.get_or_insert_with(|| a.name.clone());
+        resource
+    });
+    instances
+        .chain(volumes)
+        .chain(snapshots)
+        .chain(amis)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::hashmap;
+
+    use crate::{
+        ec2_instance::{AmiInfo, SnapshotInfo, VolumeInfo},
+        resource_type::ResourceType,
+        tag_search::{search_tags, TagQuery},
+    };
+
+    #[test]
+    fn test_tag_query_parse() -> Result<(), Error> {
+        let query = TagQuery::parse("project:blog")?;
+        assert_eq!(query.key, "project");
+        assert_eq!(query.value.as_deref(), Some("blog"));
+
+        let query = TagQuery::parse("aws:cloudformation:stack-name=blog")?;
+        assert_eq!(query.key, "aws:cloudformation:stack-name");
+        assert_eq!(query.value.as_deref(), Some("blog"));
+
+        let query = TagQuery::parse(" project ")?;
+        assert_eq!(query.key, "project");
+        assert_eq!(query.value, None);
+        assert_eq!(TagQuery::parse("project:")?.value, None);
+
+        assert!(TagQuery::parse(":blog").is_err());
+        assert!(TagQuery::parse("").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_tags() -> Result<(), Error> {
+        let volumes = [
+            VolumeInfo {
+                id: "vol-0001".into(),
+                state: "in-use".into(),
+                tags: hashmap! {"project".into() => "blog".into(), "Name".into() => "data".into()},
+                ..VolumeInfo::default()
+            },
+            VolumeInfo {
+                id: "vol-0002".into(),
+                tags: hashmap! {"project".into() => "shop".into()},
+                ..VolumeInfo::default()
+            },
+        ];
+        let snapshots = [SnapshotInfo {
+            id: "snap-0001".into(),
+            tags: hashmap! {"owner".into() => "ops".into()},
+            ..SnapshotInfo::default()
+        }];
+        let amis = [AmiInfo {
+            id: "ami-0001".into(),
+            name: "blog-image".into(),
+            tags: hashmap! {"project".into() => "blog".into()},
+            ..AmiInfo::default()
+        }];
+
+        let found = search_tags(
+            &TagQuery::parse("project:blog")?,
+            &[],
+            &volumes,
+            &snapshots,
+            &amis,
+        );
+        let ids: Vec<_> = found.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["vol-0001", "ami-0001"]);
+        assert_eq!(found[0].resource_type, ResourceType::Volume);
+        assert_eq!(found[0].name.as_deref(), Some("data"));
+        assert_eq!(found[1].name.as_deref(), Some("blog-image"));
+
+        let found = search_tags(
+            &TagQuery::parse("project")?,
+            &[],
+            &volumes,
+            &snapshots,
+            &amis,
+        );
+        assert_eq!(found.len(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+//! Tracing setup shared by the cli and aws-app-http: `RUST_LOG` filtered
+//! output on stderr, `log` records bridged into the enclosing spans, and spans
+//! exported over otlp when `otlp_endpoint` is configured.
+use anyhow::Error;
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{runtime::Tokio, trace::TracerProvider, Resource};
+use tracing_subscriber::{
+    filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
+};
+
+use crate::config::Config;
+
+/// Flushes spans still buffered for the otlp exporter when dropped
+#[must_use]
+pub struct TracingGuard(Option<TracerProvider>);
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.0.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("failed to flush spans: {e}");
+            }
+        }
+    }
+}
+
+/// Install the global subscriber, output defaults to errors only as it did
+/// with `env_logger`, exported spans are always kept down to `info`
+/// # Errors
+/// Returns error if the otlp exporter can't be built or a subscriber is
+/// already installed
+pub fn init_tracing(config: &Config, service_name: &'static str) -> Result<TracingGuard, Error> {
+    let output = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_filter(EnvFilter::from_default_env());
+    let provider = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let exporter = SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint.as_str())
+                .build()?;
+            let provider = TracerProvider::builder()
+                .with_batch_exporter(exporter, Tokio)
+                .with_resource(Resource::new([KeyValue::new("service.name", service_name)]))
+                .build();
+            Some(provider)
+        }
+        None => None,
+    };
+    let export = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(service_name))
+            .with_filter(LevelFilter::INFO)
+    });
+    tracing_subscriber::registry()
+        .with(output)
+        .with(export)
+        .try_init()?;
+    Ok(TracingGuard(provider))
+}
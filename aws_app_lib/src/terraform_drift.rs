@@ -0,0 +1,622 @@
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use stack_string::{format_sstr, StackString};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    ec2_instance::{Ec2InstanceInfo, VolumeInfo},
+    route53_instance::DnsRecord,
+};
+
+/// The parts of a terraform state file (format version 4) drift is checked
+/// against
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TerraformState {
+    pub version: i64,
+    #[serde(default)]
+    pub resources: Vec<StateResource>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StateResource {
+    pub mode: StackString,
+    #[serde(rename = "type")]
+    pub resource_type: StackString,
+    pub name: StackString,
+    pub module: Option<StackString>,
+    #[serde(default)]
+    pub instances: Vec<StateInstance>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StateInstance {
+    pub index_key: Option<Value>,
+    #[serde(default)]
+    pub attributes: Value,
+}
+
+impl TerraformState {
+    /// # Errors
+    /// Returns error if `state` isn't a version 4 state file
+    pub fn from_json(state: &str) -> Result<Self, Error> {
+        let state: Self = serde_json::from_str(state)?;
+        if state.version != 4 {
+            return Err(format_err!(
+                "unsupported terraform state version {}",
+                state.version
+            ));
+        }
+        Ok(state)
+    }
+
+    /// Instances, volumes and dns records declared in the state
+    #[must_use]
+    pub fn declared(&self) -> Vec<ComparedResource> {
+        self.resources
+            .iter()
+            .filter(|r| r.mode == "managed")
+            .flat_map(|resource| {
+                resource.instances.iter().filter_map(move |instance| {
+                    let address = resource.address(instance.index_key.as_ref());
+                    let attributes = &instance.attributes;
+                    let mut declared = match resource.resource_type.as_str() {
+                        "aws_instance" => declared_instance(attributes),
+                        "aws_ebs_volume" => declared_volume(attributes),
+                        "aws_route53_record" => declared_dns_record(attributes),
+                        _ => None,
+                    }?;
+                    declared.address = Some(address);
+                    Some(declared)
+                })
+            })
+            .collect()
+    }
+}
+
+impl StateResource {
+    fn address(&self, index_key: Option<&Value>) -> StackString {
+        let address = match &self.module {
+            Some(module) => format_sstr!("{module}.{}.{}", self.resource_type, self.name),
+            None => format_sstr!("{}.{}", self.resource_type, self.name),
+        };
+        match index_key {
+            Some(Value::String(key)) => format_sstr!("{address}[\"{key}\"]"),
+            Some(key) => format_sstr!("{address}[{key}]"),
+            None => address,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum DriftResourceType {
+    Instance,
+    Volume,
+    DnsRecord,
+}
+
+impl DriftResourceType {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Instance => "instance",
+            Self::Volume => "volume",
+            Self::DnsRecord => "dns record",
+        }
+    }
+}
+
+/// A resource reduced to the attributes compared between state and aws,
+/// tags are flattened to `tags.{key}`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ComparedResource {
+    pub resource_type: DriftResourceType,
+    /// Instance or volume id, `{zone_id}/{name}/{type}` for dns records
+    pub id: StackString,
+    /// Terraform address, unset for live resources
+    pub address: Option<StackString>,
+    pub attributes: BTreeMap<StackString, StackString>,
+}
+
+impl ComparedResource {
+    fn new(resource_type: DriftResourceType, id: impl Into<StackString>) -> Self {
+        Self {
+            resource_type,
+            id: id.into(),
+            address: None,
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    fn attribute(mut self, key: &str, value: impl Into<StackString>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    fn tags<'a>(mut self, tags: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        for (key, value) in tags {
+            if !key.starts_with("aws:") {
+                self.attributes
+                    .insert(format_sstr!("tags.{key}"), value.into());
+            }
+        }
+        self
+    }
+
+    #[must_use]
+    pub fn from_instance(instance: &Ec2InstanceInfo) -> Self {
+        Self::new(DriftResourceType::Instance, instance.id.clone())
+            .attribute("instance_type", instance.instance_type.clone())
+            .attribute("availability_zone", instance.availability_zone.clone())
+            .tags(instance.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+    }
+
+    #[must_use]
+    pub fn from_volume(volume: &VolumeInfo) -> Self {
+        Self::new(DriftResourceType::Volume, volume.id.clone())
+            .attribute("size", format_sstr!("{}", volume.size))
+            .attribute("type", volume.volume_type.clone())
+            .attribute("availability_zone", volume.availability_zone.clone())
+            .attribute("encrypted", format_sstr!("{}", volume.encrypted))
+            .tags(volume.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+    }
+
+    #[must_use]
+    pub fn from_dns_record(zone_id: &str, record: &DnsRecord) -> Self {
+        let id = dns_record_id(
+            zone_id,
+            &record.dnsname,
+            &record.record_type,
+            record.set_identifier.as_deref(),
+        );
+        let mut compared = Self::new(DriftResourceType::DnsRecord, id);
+        if let Some(ttl) = record.ttl {
+            compared = compared.attribute("ttl", format_sstr!("{ttl}"));
+        }
+        match &record.alias_target {
+            Some(alias) => compared.attribute("alias", normalize_dns_name(alias)),
+            None => compared.attribute("records", sorted_values(&record.values)),
+        }
+    }
+}
+
+/// An attribute whose declared and live values differ, `None` where it's set
+/// on only one side
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AttributeDiff {
+    pub attribute: StackString,
+    pub declared: Option<StackString>,
+    pub live: Option<StackString>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum DriftStatus {
+    /// Declared in the state but gone from aws
+    Missing,
+    /// Changed out of band since terraform last applied
+    Modified,
+    /// Exists in aws but not in the state
+    Unmanaged,
+}
+
+impl DriftStatus {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Modified => "modified",
+            Self::Unmanaged => "unmanaged",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DriftItem {
+    pub resource_type: DriftResourceType,
+    pub id: StackString,
+    pub address: Option<StackString>,
+    pub status: DriftStatus,
+    pub differences: Vec<AttributeDiff>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DriftReport {
+    /// Declared resources matching aws
+    pub in_sync: usize,
+    pub items: Vec<DriftItem>,
+}
+
+impl DriftReport {
+    /// Compare the `declared` resources against the `live` ones. Live dns
+    /// records are only reported as unmanaged in zones the state declares
+    /// records in, and `NS` / `SOA` records never are.
+    #[must_use]
+    pub fn new(declared: &[ComparedResource], live: &[ComparedResource]) -> Self {
+        let live_by_id: BTreeMap<_, _> = live
+            .iter()
+            .map(|r| ((r.resource_type, r.id.as_str()), r))
+            .collect();
+        let declared_ids: BTreeSet<_> = declared
+            .iter()
+            .map(|r| (r.resource_type, r.id.as_str()))
+            .collect();
+        let managed_zones: BTreeSet<&str> = declared
+            .iter()
+            .filter(|r| r.resource_type == DriftResourceType::DnsRecord)
+            .filter_map(|r| r.id.split('/').next())
+            .collect();
+
+        let mut report = Self::default();
+        for resource in declared {
+            match live_by_id.get(&(resource.resource_type, resource.id.as_str())) {
+                None => report.items.push(DriftItem {
+                    resource_type: resource.resource_type,
+                    id: resource.id.clone(),
+                    address: resource.address.clone(),
+                    status: DriftStatus::Missing,
+                    differences: Vec::new(),
+                }),
+                Some(live) => {
+                    let differences = attribute_diffs(resource, live);
+                    if differences.is_empty() {
+                        report.in_sync += 1;
+                    } else {
+                        report.items.push(DriftItem {
+                            resource_type: resource.resource_type,
+                            id: resource.id.clone(),
+                            address: resource.address.clone(),
+                            status: DriftStatus::Modified,
+                            differences,
+                        });
+                    }
+                }
+            }
+        }
+        for resource in live {
+            if declared_ids.contains(&(resource.resource_type, resource.id.as_str())) {
+                continue;
+            }
+            if resource.resource_type == DriftResourceType::DnsRecord {
+                let mut parts = resource.id.split('/');
+                let zone = parts.next().unwrap_or("");
+                let record_type = parts.nth(1).unwrap_or("");
+                if !managed_zones.contains(zone) || record_type == "NS" || record_type == "SOA" {
+                    continue;
+                }
+            }
+            report.items.push(DriftItem {
+                resource_type: resource.resource_type,
+                id: resource.id.clone(),
+                address: None,
+                status: DriftStatus::Unmanaged,
+                differences: Vec::new(),
+            });
+        }
+        report.items.sort_by(|a, b| {
+            (a.status, a.resource_type, &a.id).cmp(&(b.status, b.resource_type, &b.id))
+        });
+        report
+    }
+}
+
+fn attribute_diffs(declared: &ComparedResource, live: &ComparedResource) -> Vec<AttributeDiff> {
+    let keys: BTreeSet<_> = declared
+        .attributes
+        .keys()
+        .chain(live.attributes.keys())
+        .collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let declared = declared.attributes.get(key);
+            let live = live.attributes.get(key);
+            if declared == live {
+                None
+            } else {
+                Some(AttributeDiff {
+                    attribute: key.clone(),
+                    declared: declared.cloned(),
+                    live: live.cloned(),
+                })
+            }
+        })
+        .collect()
+}
+
+fn declared_instance(attributes: &Value) -> Option<ComparedResource> {
+    let mut compared = ComparedResource::new(DriftResourceType::Instance, attr(attributes, "id")?);
+    for key in ["instance_type", "availability_zone"] {
+        if let Some(value) = attr(attributes, key) {
+            compared = compared.attribute(key, value);
+        }
+    }
+    Some(compared.tags(state_tags(attributes)))
+}
+
+fn declared_volume(attributes: &Value) -> Option<ComparedResource> {
+    let mut compared = ComparedResource::new(DriftResourceType::Volume, attr(attributes, "id")?);
+    for key in ["size", "type", "availability_zone", "encrypted"] {
+        if let Some(value) = attr(attributes, key) {
+            compared = compared.attribute(key, value);
+        }
+    }
+    Some(compared.tags(state_tags(attributes)))
+}
+
+fn declared_dns_record(attributes: &Value) -> Option<ComparedResource> {
+    let id = dns_record_id(
+        &attr(attributes, "zone_id")?,
+        &attr(attributes, "name")?,
+        &attr(attributes, "type")?,
+        attr(attributes, "set_identifier").as_deref(),
+    );
+    let mut compared = ComparedResource::new(DriftResourceType::DnsRecord, id);
+    let alias = attributes
+        .get("alias")
+        .and_then(Value::as_array)
+        .and_then(|a| a.first())
+        .and_then(|a| attr(a, "name"));
+    if let Some(alias) = alias {
+        return Some(compared.attribute("alias", normalize_dns_name(&alias)));
+    }
+    if let Some(ttl) = attr(attributes, "ttl") {
+        compared = compared.attribute("ttl", ttl);
+    }
+    let records: Vec<_> = attributes
+        .get("records")
+        .and_then(Value::as_array)
+        .map(|records| records.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    Some(compared.attribute("records", sorted_values(&records)))
+}
+
+/// `tags_all` includes the provider's default tags, older states only have
+/// `tags`
+fn state_tags(attributes: &Value) -> impl Iterator<Item = (&str, &str)> {
+    attributes
+        .get("tags_all")
+        .filter(|t| t.is_object())
+        .or_else(|| attributes.get("tags"))
+        .and_then(Value::as_object)
+        .into_iter()
+        .flat_map(|tags| {
+            tags.iter()
+                .filter_map(|(k, v)| Some((k.as_str(), v.as_str()?)))
+        })
+}
+
+fn attr(attributes: &Value, key: &str) -> Option<StackString> {
+    match attributes.get(key)? {
+        Value::String(s) if !s.is_empty() => Some(s.into()),
+        Value::Number(n) => Some(format_sstr!("{n}")),
+        Value::Bool(b) => Some(format_sstr!("{b}")),
+        _ => None,
+    }
+}
+
+pub(crate) fn dns_record_id(
+    zone_id: &str,
+    name: &str,
+    record_type: &str,
+    set_identifier: Option<&str>,
+) -> StackString {
+    let zone_id = zone_id.trim_start_matches("/hostedzone/");
+    let name = normalize_dns_name(name);
+    match set_identifier {
+        Some(set_identifier) => format_sstr!("{zone_id}/{name}/{record_type}/{set_identifier}"),
+        None => format_sstr!("{zone_id}/{name}/{record_type}"),
+    }
+}
+
+pub(crate) fn normalize_dns_name(name: &str) -> StackString {
+    name.trim_end_matches('.').to_lowercase().into()
+}
+
+pub(crate) fn sorted_values<T: AsRef<str>>(values: &[T]) -> StackString {
+    let values: BTreeSet<_> = values.iter().map(T::as_ref).collect();
+    let values: Vec<_> = values.into_iter().collect();
+    values.join(",").into()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::hashmap;
+
+    use crate::{
+        ec2_instance::{Ec2InstanceInfo, VolumeInfo},
+        route53_instance::DnsRecord,
+        terraform_drift::{
+            ComparedResource, DriftReport, DriftResourceType, DriftStatus, TerraformState,
+        },
+    };
+
+    const STATE: &str = r#"{
+        "version": 4,
+        "terraform_version": "1.6.0",
+        "resources": [
+            {
+                "mode": "managed",
+                "type": "aws_instance",
+                "name": "web",
+                "instances": [
+                    {
+                        "index_key": 0,
+                        "attributes": {
+                            "id": "i-0001",
+                            "instance_type": "t3.micro",
+                            "availability_zone": "us-east-1a",
+                            "tags": {"Name": "web"},
+                            "tags_all": {"Name": "web", "env": "prod"}
+                        }
+                    }
+                ]
+            },
+            {
+                "mode": "managed",
+                "type": "aws_ebs_volume",
+                "name": "data",
+                "module": "module.storage",
+                "instances": [
+                    {
+                        "attributes": {
+                            "id": "vol-0001",
+                            "size": 20,
+                            "type": "gp3",
+                            "availability_zone": "us-east-1a",
+                            "encrypted": true,
+                            "tags": null
+                        }
+                    },
+                    {
+                        "index_key": "old",
+                        "attributes": {"id": "vol-0002", "size": 8, "type": "gp2"}
+                    }
+                ]
+            },
+            {
+                "mode": "managed",
+                "type": "aws_route53_record",
+                "name": "www",
+                "instances": [
+                    {
+                        "attributes": {
+                            "zone_id": "Z0123",
+                            "name": "www.example.com",
+                            "type": "A",
+                            "ttl": 300,
+                            "records": ["1.2.3.4"],
+                            "set_identifier": ""
+                        }
+                    }
+                ]
+            },
+            {
+                "mode": "data",
+                "type": "aws_ami",
+                "name": "ubuntu",
+                "instances": [{"attributes": {"id": "ami-0001"}}]
+            }
+        ]
+    }"#;
+
+    fn record(dnsname: &str, record_type: &str, values: &[&str]) -> DnsRecord {
+        let values: Vec<String> = values.iter().map(|v| (*v).into()).collect();
+        DnsRecord {
+            dnsname: dnsname.into(),
+            ip: values.first().cloned().unwrap_or_default(),
+            set_identifier: None,
+            routing: None,
+            health_check_id: None,
+            record_type: record_type.into(),
+            ttl: Some(300),
+            values,
+            alias_target: None,
+        }
+    }
+
+    #[test]
+    fn test_terraform_state() -> Result<(), Error> {
+        let state = TerraformState::from_json(STATE)?;
+        let declared = state.declared();
+        let addresses: Vec<_> = declared
+            .iter()
+            .filter_map(|r| r.address.as_deref())
+            .collect();
+        assert_eq!(
+            addresses,
+            vec![
+                "aws_instance.web[0]",
+                "module.storage.aws_ebs_volume.data",
+                "module.storage.aws_ebs_volume.data[\"old\"]",
+                "aws_route53_record.www",
+            ]
+        );
+        assert_eq!(declared[0].attributes["tags.env"], "prod");
+        assert_eq!(declared[1].attributes["encrypted"], "true");
+        assert_eq!(declared[3].id, "Z0123/www.example.com/A");
+        assert_eq!(declared[3].attributes["records"], "1.2.3.4");
+
+        assert!(TerraformState::from_json(r#"{"version": 3}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_drift_report() -> Result<(), Error> {
+        let declared = TerraformState::from_json(STATE)?.declared();
+        let instance = |id: &str, instance_type: &str| Ec2InstanceInfo {
+            id: id.into(),
+            state: "running".into(),
+            instance_type: instance_type.into(),
+            availability_zone: "us-east-1a".into(),
+            tags: hashmap! {
+                "Name".into() => "web".into(),
+                "env".into() => "prod".into(),
+                "aws:autoscaling:groupName".into() => "asg".into(),
+            },
+            ..Ec2InstanceInfo::default()
+        };
+        let volume = VolumeInfo {
+            id: "vol-0001".into(),
+            availability_zone: "us-east-1a".into(),
+            size: 20,
+            volume_type: "gp3".into(),
+            encrypted: true,
+            ..VolumeInfo::default()
+        };
+        let live = vec![
+            ComparedResource::from_instance(&instance("i-0001", "t3.small")),
+            ComparedResource::from_instance(&instance("i-0002", "t3.micro")),
+            ComparedResource::from_volume(&volume),
+            ComparedResource::from_dns_record(
+                "/hostedzone/Z0123",
+                &record("www.example.com", "A", &["1.2.3.4"]),
+            ),
+            ComparedResource::from_dns_record(
+                "/hostedzone/Z0123",
+                &record("api.example.com", "A", &["5.6.7.8"]),
+            ),
+            ComparedResource::from_dns_record(
+                "/hostedzone/Z0123",
+                &record("example.com", "NS", &["ns-1.awsdns.com."]),
+            ),
+            ComparedResource::from_dns_record(
+                "/hostedzone/Z9999",
+                &record("other.org", "A", &["9.9.9.9"]),
+            ),
+        ];
+        let report = DriftReport::new(&declared, &live);
+        assert_eq!(report.in_sync, 2);
+
+        let summary: Vec<_> = report
+            .items
+            .iter()
+            .map(|i| (i.status, i.resource_type, i.id.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (DriftStatus::Missing, DriftResourceType::Volume, "vol-0002"),
+                (DriftStatus::Modified, DriftResourceType::Instance, "i-0001"),
+                (
+                    DriftStatus::Unmanaged,
+                    DriftResourceType::Instance,
+                    "i-0002"
+                ),
+                (
+                    DriftStatus::Unmanaged,
+                    DriftResourceType::DnsRecord,
+                    "Z0123/api.example.com/A"
+                ),
+            ]
+        );
+        let modified = &report.items[1];
+        assert_eq!(modified.differences.len(), 1);
+        assert_eq!(modified.differences[0].attribute, "instance_type");
+        assert_eq!(
+            modified.differences[0].declared.as_deref(),
+            Some("t3.micro")
+        );
+        assert_eq!(modified.differences[0].live.as_deref(), Some("t3.small"));
+        Ok(())
+    }
+}
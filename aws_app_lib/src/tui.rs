@@ -0,0 +1,444 @@
+use anyhow::Error;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph, Row, Table, TableState, Wrap},
+    DefaultTerminal, Frame,
+};
+use stack_string::{format_sstr, StackString};
+use std::time::{Duration, Instant};
+use tokio::{process::Command, task::spawn_blocking};
+
+use crate::{
+    aws_app_interface::{AwsAppInterface, AwsInstancePrice, INSTANCE_LIST},
+    ec2_instance::Ec2InstanceInfo,
+};
+
+/// How long to wait for a key before checking whether a refresh is due
+const TICK: Duration = Duration::from_millis(250);
+
+const INSTANCE_HELP: &str =
+    "q quit | j/k move | r refresh | enter status | c connect | s stop | t terminate | p prices";
+const PRICE_HELP: &str = "q quit | j/k move | / search | p instances";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Instances,
+    Prices,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Up,
+    Down,
+    Refresh,
+    Status,
+    Connect,
+    Stop,
+    Terminate,
+    TogglePane,
+    Search,
+}
+
+impl Action {
+    /// Action bound to `key` in `pane`
+    #[must_use]
+    pub fn from_key(pane: Pane, key: KeyCode) -> Option<Self> {
+        match (pane, key) {
+            (_, KeyCode::Char('q') | KeyCode::Esc) => Some(Self::Quit),
+            (_, KeyCode::Char('k') | KeyCode::Up) => Some(Self::Up),
+            (_, KeyCode::Char('j') | KeyCode::Down) => Some(Self::Down),
+            (_, KeyCode::Char('p') | KeyCode::Tab) => Some(Self::TogglePane),
+            (Pane::Instances, KeyCode::Char('r')) => Some(Self::Refresh),
+            (Pane::Instances, KeyCode::Enter) => Some(Self::Status),
+            (Pane::Instances, KeyCode::Char('c')) => Some(Self::Connect),
+            (Pane::Instances, KeyCode::Char('s')) => Some(Self::Stop),
+            (Pane::Instances, KeyCode::Char('t')) => Some(Self::Terminate),
+            (Pane::Prices, KeyCode::Char('/')) => Some(Self::Search),
+            _ => None,
+        }
+    }
+
+    /// Actions that change the instance ask for a `y` first
+    #[must_use]
+    pub fn needs_confirm(self) -> bool {
+        matches!(self, Self::Stop | Self::Terminate)
+    }
+}
+
+/// Next selected row moving by `delta` in a table of `len` rows, wrapping
+/// around at either end
+#[must_use]
+pub fn move_selection(selected: Option<usize>, len: usize, delta: isize) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    let len = len as isize;
+    let current = selected.map_or(if delta > 0 { -1 } else { 0 }, |s| s as isize);
+    Some((current + delta).rem_euclid(len) as usize)
+}
+
+/// Interactive instance list and price browser over `AwsAppInterface`
+pub struct TuiApp {
+    aws: AwsAppInterface,
+    refresh_interval: Duration,
+    pane: Pane,
+    instances: Vec<Ec2InstanceInfo>,
+    instance_state: TableState,
+    prices: Vec<AwsInstancePrice>,
+    price_state: TableState,
+    price_search: StackString,
+    editing_search: bool,
+    pending: Option<(Action, StackString)>,
+    output: Vec<StackString>,
+    message: StackString,
+    last_refresh: Option<Instant>,
+}
+
+impl TuiApp {
+    #[must_use]
+    pub fn new(aws: AwsAppInterface, refresh_interval: Duration) -> Self {
+        Self {
+            aws,
+            refresh_interval,
+            pane: Pane::Instances,
+            instances: Vec::new(),
+            instance_state: TableState::default(),
+            prices: Vec::new(),
+            price_state: TableState::default(),
+            price_search: StackString::new(),
+            editing_search: false,
+            pending: None,
+            output: Vec::new(),
+            message: StackString::new(),
+            last_refresh: None,
+        }
+    }
+
+    /// Take over the terminal until `q` is pressed
+    /// # Errors
+    /// Returns error if the terminal can't be drawn to
+    pub async fn run(mut self) -> Result<(), Error> {
+        let mut terminal = ratatui::init();
+        let result = self.event_loop(&mut terminal).await;
+        ratatui::restore();
+        result
+    }
+
+    async fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Error> {
+        loop {
+            if self
+                .last_refresh
+                .map_or(true, |t| t.elapsed() >= self.refresh_interval)
+            {
+                self.refresh().await;
+            }
+            terminal.draw(|frame| self.render(frame))?;
+            let event = spawn_blocking(|| -> Result<Option<Event>, std::io::Error> {
+                if event::poll(TICK)? {
+                    event::read().map(Some)
+                } else {
+                    Ok(None)
+                }
+            })
+            .await??;
+            let key = match event {
+                Some(Event::Key(key)) if key.kind == KeyEventKind::Press => key.code,
+                _ => continue,
+            };
+            if self.editing_search {
+                self.edit_search(key).await;
+                continue;
+            }
+            if let Some((action, instance_id)) = self.pending.take() {
+                if key == KeyCode::Char('y') {
+                    self.run_instance_action(action, &instance_id).await;
+                } else {
+                    self.message = "cancelled".into();
+                }
+                continue;
+            }
+            match Action::from_key(self.pane, key) {
+                Some(Action::Quit) => return Ok(()),
+                Some(Action::Connect) => {
+                    if let Some(instance_id) = self.selected_instance() {
+                        self.connect(terminal, &instance_id).await?;
+                    }
+                }
+                Some(action) => self.handle_action(action).await,
+                None => {}
+            }
+        }
+    }
+
+    async fn refresh(&mut self) {
+        self.last_refresh = Some(Instant::now());
+        match self.aws.fill_instance_list().await {
+            Ok(()) => {
+                self.instances = INSTANCE_LIST.read().await.to_vec();
+                let selected = self.instance_state.selected();
+                if selected.map_or(true, |s| s >= self.instances.len()) {
+                    self.instance_state
+                        .select(move_selection(None, self.instances.len(), 1));
+                }
+            }
+            Err(e) => self.message = format_sstr!("refresh failed: {e}"),
+        }
+    }
+
+    fn selected_instance(&self) -> Option<StackString> {
+        self.instance_state
+            .selected()
+            .and_then(|i| self.instances.get(i))
+            .map(|inst| inst.id.clone())
+    }
+
+    async fn handle_action(&mut self, action: Action) {
+        match action {
+            Action::Up | Action::Down => {
+                let delta = if action == Action::Up { -1 } else { 1 };
+                let (state, len) = match self.pane {
+                    Pane::Instances => (&mut self.instance_state, self.instances.len()),
+                    Pane::Prices => (&mut self.price_state, self.prices.len()),
+                };
+                state.select(move_selection(state.selected(), len, delta));
+            }
+            Action::Refresh => self.refresh().await,
+            Action::TogglePane => {
+                self.pane = match self.pane {
+                    Pane::Instances => Pane::Prices,
+                    Pane::Prices => Pane::Instances,
+                };
+            }
+            Action::Search => {
+                self.editing_search = true;
+                self.message = "instance type prefixes, comma separated".into();
+            }
+            Action::Status | Action::Stop | Action::Terminate => {
+                if let Some(instance_id) = self.selected_instance() {
+                    if action.needs_confirm() {
+                        self.message = format_sstr!("{action:?} {instance_id}? (y/n)");
+                        self.pending = Some((action, instance_id));
+                    } else {
+                        self.run_instance_action(action, &instance_id).await;
+                    }
+                }
+            }
+            Action::Quit | Action::Connect => {}
+        }
+    }
+
+    async fn run_instance_action(&mut self, action: Action, instance_id: &str) {
+        let result = match action {
+            Action::Status => match self.aws.get_status(instance_id).await {
+                Ok(lines) => {
+                    self.output = lines;
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            },
+            Action::Stop => self.aws.stop(&[instance_id]).await,
+            Action::Terminate => self.aws.terminate(&[instance_id]).await,
+            _ => Ok(()),
+        };
+        self.message = match result {
+            Ok(()) => format_sstr!("{action:?} {instance_id} done"),
+            Err(e) => format_sstr!("{action:?} {instance_id} failed: {e}"),
+        };
+        if action != Action::Status {
+            self.refresh().await;
+        }
+    }
+
+    /// Hand the terminal to `ssh` and take it back once the session ends
+    async fn connect(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        instance_id: &str,
+    ) -> Result<(), Error> {
+        let args = match self.aws.ssh_args(instance_id).await {
+            Ok(Some(args)) => args,
+            Ok(None) => {
+                self.message = format_sstr!("{instance_id} is not running");
+                return Ok(());
+            }
+            Err(e) => {
+                self.message = format_sstr!("connect failed: {e}");
+                return Ok(());
+            }
+        };
+        terminal.clear()?;
+        ratatui::restore();
+        let status = Command::new("ssh")
+            .args(args.iter().map(StackString::as_str))
+            .status()
+            .await;
+        self.message = match status {
+            Ok(status) => format_sstr!("ssh {instance_id} exited with {status}"),
+            Err(e) => format_sstr!("ssh {instance_id} failed: {e}"),
+        };
+        *terminal = ratatui::init();
+        Ok(())
+    }
+
+    async fn edit_search(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Enter => {
+                self.editing_search = false;
+                let search: Vec<_> = self
+                    .price_search
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .collect();
+                match self.aws.get_ec2_prices(search.as_slice()).await {
+                    Ok(prices) => {
+                        self.message = format_sstr!("{} instance types", prices.len());
+                        self.prices = prices;
+                        self.price_state
+                            .select(move_selection(None, self.prices.len(), 1));
+                    }
+                    Err(e) => self.message = format_sstr!("price search failed: {e}"),
+                }
+            }
+            KeyCode::Esc => self.editing_search = false,
+            KeyCode::Backspace => {
+                self.price_search.pop();
+            }
+            KeyCode::Char(c) => self.price_search.push(c),
+            _ => {}
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [header, body, output, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let selected = Style::default().add_modifier(Modifier::REVERSED);
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+
+        frame.render_widget(Line::from(self.message.as_str()), header);
+        match self.pane {
+            Pane::Instances => {
+                let rows = self.instances.iter().map(|inst| {
+                    let name = inst.tags.get("Name").map_or("", StackString::as_str);
+                    Row::new(vec![
+                        inst.id.as_str(),
+                        name,
+                        inst.state.as_str(),
+                        inst.instance_type.as_str(),
+                        inst.availability_zone.as_str(),
+                        inst.dns_name.as_str(),
+                    ])
+                });
+                let widths = [
+                    Constraint::Length(20),
+                    Constraint::Length(20),
+                    Constraint::Length(14),
+                    Constraint::Length(14),
+                    Constraint::Length(12),
+                    Constraint::Min(20),
+                ];
+                let title = format_sstr!("Instances ({})", self.instances.len());
+                let table = Table::new(rows, widths)
+                    .header(
+                        Row::new(vec!["Id", "Name", "State", "Type", "AZ", "Hostname"]).style(bold),
+                    )
+                    .block(Block::default().borders(Borders::ALL).title(title.as_str()))
+                    .row_highlight_style(selected);
+                frame.render_stateful_widget(table, body, &mut self.instance_state);
+            }
+            Pane::Prices => {
+                let price = |p: Option<f64>| p.map_or_else(String::new, |p| format!("{p:.4}"));
+                let rows = self.prices.iter().map(|p| {
+                    Row::new(vec![
+                        p.instance_type.to_string(),
+                        price(p.ondemand_price),
+                        price(p.spot_price),
+                        price(p.reserved_price),
+                        p.ncpu.to_string(),
+                        format!("{:.1}", p.memory),
+                    ])
+                });
+                let widths = [
+                    Constraint::Length(16),
+                    Constraint::Length(10),
+                    Constraint::Length(10),
+                    Constraint::Length(10),
+                    Constraint::Length(6),
+                    Constraint::Min(8),
+                ];
+                let title = if self.editing_search {
+                    format_sstr!("Prices, search: {}_", self.price_search)
+                } else {
+                    format_sstr!("Prices, search: {}", self.price_search)
+                };
+                let table = Table::new(rows, widths)
+                    .header(
+                        Row::new(vec![
+                            "Type", "OnDemand", "Spot", "Reserved", "CPU", "Memory",
+                        ])
+                        .style(bold),
+                    )
+                    .block(Block::default().borders(Borders::ALL).title(title.as_str()))
+                    .row_highlight_style(selected);
+                frame.render_stateful_widget(table, body, &mut self.price_state);
+            }
+        }
+        let text: Vec<_> = self.output.iter().map(|l| Line::from(l.as_str())).collect();
+        frame.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::default().borders(Borders::ALL).title("Output")),
+            output,
+        );
+        let help = match self.pane {
+            Pane::Instances => INSTANCE_HELP,
+            Pane::Prices => PRICE_HELP,
+        };
+        frame.render_widget(Line::from(help), footer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::crossterm::event::KeyCode;
+
+    use crate::tui::{move_selection, Action, Pane};
+
+    #[test]
+    fn test_action_from_key() {
+        assert_eq!(
+            Action::from_key(Pane::Instances, KeyCode::Char('t')),
+            Some(Action::Terminate)
+        );
+        assert_eq!(Action::from_key(Pane::Prices, KeyCode::Char('t')), None);
+        assert_eq!(
+            Action::from_key(Pane::Prices, KeyCode::Char('/')),
+            Some(Action::Search)
+        );
+        assert_eq!(
+            Action::from_key(Pane::Prices, KeyCode::Esc),
+            Some(Action::Quit)
+        );
+        assert!(Action::Stop.needs_confirm());
+        assert!(!Action::Status.needs_confirm());
+    }
+
+    #[test]
+    fn test_move_selection() {
+        assert_eq!(move_selection(None, 0, 1), None);
+        assert_eq!(move_selection(None, 3, 1), Some(0));
+        assert_eq!(move_selection(None, 3, -1), Some(2));
+        assert_eq!(move_selection(Some(2), 3, 1), Some(0));
+        assert_eq!(move_selection(Some(0), 3, -1), Some(2));
+        assert_eq!(move_selection(Some(1), 3, 1), Some(2));
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::Serialize;
+use serde_json::Value;
+use stack_string::StackString;
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+
+use crate::{
+    instance_filter::{InstanceFilter, InstanceSortKey},
+    pgpool::PgPool,
+};
+
+pub const MAX_ROWS_PER_PAGE: i32 = 1000;
+
+/// UI preferences of a logged in user, applied when resource tables are
+/// rendered
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Serialize)]
+pub struct UserPreferences {
+    pub email: StackString,
+    /// Resource name to the header names of the columns hidden in its table
+    pub hidden_columns: Value,
+    /// Sort key of the instance list
+    pub default_sort: Option<StackString>,
+    pub sort_descending: bool,
+    /// Rows shown per page of each table, all rows if `None`
+    pub rows_per_page: Option<i32>,
+    /// Region the app switches to when the user opens the index page
+    pub default_region: Option<StackString>,
+    pub updated_at: OffsetDateTime,
+}
+
+impl UserPreferences {
+    #[must_use]
+    pub fn new(email: impl Into<StackString>) -> Self {
+        Self {
+            email: email.into(),
+            hidden_columns: Value::Object(serde_json::Map::new()),
+            default_sort: None,
+            sort_descending: false,
+            rows_per_page: None,
+            default_region: None,
+            updated_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Saved preferences of `email`, the defaults if none were saved
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get(pool: &PgPool, email: &str) -> Result<Self, Error> {
+        let query = query!(
+            "SELECT * FROM user_preferences WHERE email = $email",
+            email = email,
+        );
+        let conn = pool.get().await?;
+        let preferences: Option<Self> = query.fetch_opt(&conn).await?;
+        Ok(preferences.unwrap_or_else(|| Self::new(email)))
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn upsert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO user_preferences (
+                    email, hidden_columns, default_sort, sort_descending, rows_per_page,
+                    default_region, updated_at
+                ) VALUES (
+                    $email, $hidden_columns, $default_sort, $sort_descending, $rows_per_page,
+                    $default_region, now()
+                ) ON CONFLICT (email) DO UPDATE
+                SET hidden_columns=$hidden_columns,default_sort=$default_sort,
+                    sort_descending=$sort_descending,rows_per_page=$rows_per_page,
+                    default_region=$default_region,updated_at=now()
+            ",
+            email = self.email,
+            hidden_columns = self.hidden_columns,
+            default_sort = self.default_sort,
+            sort_descending = self.sort_descending,
+            rows_per_page = self.rows_per_page,
+            default_region = self.default_region,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Header names of the columns hidden in the table of `resource`
+    #[must_use]
+    pub fn hidden_columns(&self, resource: &str) -> Vec<StackString> {
+        self.hidden_columns
+            .get(resource)
+            .and_then(Value::as_array)
+            .map(|columns| {
+                columns
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(Into::into)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// # Errors
+    /// Returns error if serialization fails
+    pub fn set_hidden_columns(
+        &mut self,
+        hidden_columns: impl IntoIterator<Item = (StackString, Vec<StackString>)>,
+    ) -> Result<(), Error> {
+        let hidden_columns: BTreeMap<_, _> = hidden_columns
+            .into_iter()
+            .filter(|(_, columns)| !columns.is_empty())
+            .collect();
+        self.hidden_columns = serde_json::to_value(hidden_columns)?;
+        Ok(())
+    }
+
+    /// Filter ordering the instance list by the preferred sort, `None` to
+    /// keep the default order
+    #[must_use]
+    pub fn instance_filter(&self) -> Option<InstanceFilter> {
+        let sort: InstanceSortKey = self.default_sort.as_ref()?.parse().ok()?;
+        if sort == InstanceSortKey::default() && !self.sort_descending {
+            return None;
+        }
+        Some(InstanceFilter {
+            sort,
+            descending: self.sort_descending,
+            ..InstanceFilter::default()
+        })
+    }
+
+    /// # Errors
+    /// Returns error if the sort key is unknown or rows per page is out of
+    /// range
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(sort) = &self.default_sort {
+            sort.parse::<InstanceSortKey>()?;
+        }
+        if let Some(rows) = self.rows_per_page {
+            if !(1..=MAX_ROWS_PER_PAGE).contains(&rows) {
+                return Err(format_err!(
+                    "rows per page must be between 1 and {MAX_ROWS_PER_PAGE}"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::btreemap;
+
+    use crate::{instance_filter::InstanceSortKey, user_preferences::UserPreferences};
+
+    #[test]
+    fn test_hidden_columns() -> Result<(), Error> {
+        let mut preferences = UserPreferences::new("user@example.com");
+        assert!(preferences.hidden_columns("ami").is_empty());
+        preferences.set_hidden_columns(btreemap! {
+            "ami".into() => vec!["Snapshot ID".into(), "State".into()],
+            "volume".into() => Vec::new(),
+        })?;
+        assert_eq!(
+            preferences.hidden_columns("ami"),
+            vec!["Snapshot ID", "State"]
+        );
+        assert!(preferences.hidden_columns("volume").is_empty());
+        assert!(preferences.hidden_columns.get("volume").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_instance_filter() {
+        let mut preferences = UserPreferences::new("user@example.com");
+        assert_eq!(preferences.instance_filter(), None);
+        preferences.default_sort = Some("state".into());
+        assert_eq!(preferences.instance_filter(), None);
+        preferences.default_sort = Some("name".into());
+        preferences.sort_descending = true;
+        let filter = preferences.instance_filter().unwrap();
+        assert_eq!(filter.sort, InstanceSortKey::Name);
+        assert!(filter.descending);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut preferences = UserPreferences::new("user@example.com");
+        assert!(preferences.validate().is_ok());
+        preferences.rows_per_page = Some(0);
+        assert!(preferences.validate().is_err());
+        preferences.rows_per_page = Some(50);
+        preferences.default_sort = Some("price".into());
+        assert!(preferences.validate().is_err());
+        preferences.default_sort = Some("launch_time".into());
+        assert!(preferences.validate().is_ok());
+    }
+}
@@ -0,0 +1,309 @@
+use stack_string::{format_sstr, StackString};
+use std::collections::HashMap;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+use crate::{
+    config::Config,
+    cost_attribution::HOURS_PER_MONTH,
+    ec2_instance::{AmiInfo, Ec2InstanceInfo, ElasticIpInfo, SnapshotInfo, VolumeInfo},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WasteKind {
+    UnattachedVolume,
+    UnassociatedElasticIp,
+    StoppedInstance,
+    UnusedAmi,
+    IdleNovnc,
+}
+
+impl WasteKind {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::UnattachedVolume => "unattached volume",
+            Self::UnassociatedElasticIp => "unassociated elastic ip",
+            Self::StoppedInstance => "stopped instance",
+            Self::UnusedAmi => "unused ami",
+            Self::IdleNovnc => "idle novnc session",
+        }
+    }
+
+    /// Method and url of the route cleaning up the resource `id`
+    #[must_use]
+    pub fn cleanup(self, id: &str) -> (&'static str, StackString) {
+        match self {
+            Self::UnattachedVolume => ("DELETE", format_sstr!("/aws/delete_volume?volid={id}")),
+            Self::UnassociatedElasticIp => (
+                "DELETE",
+                format_sstr!("/aws/release_address?allocation_id={id}"),
+            ),
+            Self::StoppedInstance => ("DELETE", format_sstr!("/aws/terminate?instance={id}")),
+            Self::UnusedAmi => ("DELETE", format_sstr!("/aws/delete_image?ami={id}")),
+            Self::IdleNovnc => ("POST", "/aws/novnc/stop".into()),
+        }
+    }
+}
+
+/// A resource paid for (or running) without being used
+#[derive(Clone, Debug, PartialEq)]
+pub struct WasteItem {
+    pub kind: WasteKind,
+    pub id: StackString,
+    pub name: StackString,
+    pub detail: StackString,
+    pub monthly_cost: f64,
+}
+
+/// When a resource counts as unused, and the prices its cost is estimated
+/// from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WastePolicy {
+    pub stopped_instance_days: i64,
+    pub unused_ami_months: i64,
+    pub idle_novnc_hours: i64,
+    pub volume_gb_month: f64,
+    pub snapshot_gb_month: f64,
+    pub elastic_ip_hourly: f64,
+}
+
+impl From<&Config> for WastePolicy {
+    fn from(config: &Config) -> Self {
+        Self {
+            stopped_instance_days: config.waste_stopped_instance_days,
+            unused_ami_months: config.waste_unused_ami_months,
+            idle_novnc_hours: config.waste_idle_novnc_hours,
+            volume_gb_month: config.volume_gb_month_price,
+            snapshot_gb_month: config.snapshot_gb_month_price,
+            elastic_ip_hourly: config.elastic_ip_hourly_price,
+        }
+    }
+}
+
+/// Everything the waste report is built from
+#[derive(Clone, Debug, Default)]
+pub struct WasteInventory {
+    pub instances: Vec<Ec2InstanceInfo>,
+    pub volumes: Vec<VolumeInfo>,
+    pub snapshots: Vec<SnapshotInfo>,
+    pub elastic_ips: Vec<ElasticIpInfo>,
+    pub amis: Vec<AmiInfo>,
+    /// Most recent recorded launch of each ami
+    pub ami_last_launch: HashMap<StackString, OffsetDateTime>,
+    pub novnc_started_at: Option<OffsetDateTime>,
+}
+
+fn name_tag(tags: &HashMap<StackString, StackString>) -> StackString {
+    tags.get("Name").cloned().unwrap_or_default()
+}
+
+impl WasteInventory {
+    /// Stopped instances are judged on their launch time, which aws resets
+    /// every time an instance is started, and are costed at the storage of
+    /// their volumes. Amis are costed at the full size of their snapshots.
+    #[must_use]
+    pub fn find_waste(&self, policy: &WastePolicy, now: OffsetDateTime) -> Vec<WasteItem> {
+        let volume_sizes: HashMap<&str, i64> = self
+            .volumes
+            .iter()
+            .map(|v| (v.id.as_str(), v.size))
+            .collect();
+        let snapshot_sizes: HashMap<&str, i64> = self
+            .snapshots
+            .iter()
+            .map(|s| (s.id.as_str(), s.volume_size))
+            .collect();
+        let mut waste = Vec::new();
+
+        for volume in self.volumes.iter().filter(|v| v.state == "available") {
+            waste.push(WasteItem {
+                kind: WasteKind::UnattachedVolume,
+                id: volume.id.clone(),
+                name: name_tag(&volume.tags),
+                detail: format_sstr!("{} GB in {}", volume.size, volume.availability_zone),
+                monthly_cost: volume.size as f64 * policy.volume_gb_month,
+            });
+        }
+
+        for address in self
+            .elastic_ips
+            .iter()
+            .filter(|a| a.association_id.is_none())
+        {
+            waste.push(WasteItem {
+                kind: WasteKind::UnassociatedElasticIp,
+                id: address.allocation_id.clone(),
+                name: name_tag(&address.tags),
+                detail: address.public_ip.clone(),
+                monthly_cost: policy.elastic_ip_hourly * HOURS_PER_MONTH,
+            });
+        }
+
+        let stopped_cutoff = now - Duration::days(policy.stopped_instance_days);
+        for instance in self
+            .instances
+            .iter()
+            .filter(|i| i.state == "stopped" && i.launch_time.to_offsetdatetime() < stopped_cutoff)
+        {
+            let size: i64 = instance
+                .volumes
+                .iter()
+                .filter_map(|v| volume_sizes.get(v.as_str()))
+                .sum();
+            let days = (now - instance.launch_time.to_offsetdatetime()).whole_days();
+            waste.push(WasteItem {
+                kind: WasteKind::StoppedInstance,
+                id: instance.id.clone(),
+                name: name_tag(&instance.tags),
+                detail: format_sstr!("{} last started {days} days ago", instance.instance_type),
+                monthly_cost: size as f64 * policy.volume_gb_month,
+            });
+        }
+
+        let ami_cutoff = now - Duration::days(policy.unused_ami_months * 30);
+        for ami in &self.amis {
+            let created = ami
+                .creation_date
+                .as_ref()
+                .and_then(|d| OffsetDateTime::parse(d, &Rfc3339).ok());
+            if created.map_or(true, |c| c > ami_cutoff) {
+                continue;
+            }
+            let last_launch = self.ami_last_launch.get(&ami.id);
+            if last_launch.map_or(false, |l| *l > ami_cutoff) {
+                continue;
+            }
+            let size: i64 = ami
+                .snapshot_ids
+                .iter()
+                .filter_map(|s| snapshot_sizes.get(s.as_str()))
+                .sum();
+            let detail = match last_launch {
+                Some(l) => format_sstr!("last launched {} days ago", (now - *l).whole_days()),
+                None => "never launched".into(),
+            };
+            waste.push(WasteItem {
+                kind: WasteKind::UnusedAmi,
+                id: ami.id.clone(),
+                name: ami.name.clone(),
+                detail,
+                monthly_cost: size as f64 * policy.snapshot_gb_month,
+            });
+        }
+
+        if let Some(started_at) = self.novnc_started_at {
+            let hours = (now - started_at).whole_hours();
+            if hours >= policy.idle_novnc_hours {
+                waste.push(WasteItem {
+                    kind: WasteKind::IdleNovnc,
+                    id: "novnc".into(),
+                    name: "".into(),
+                    detail: format_sstr!("running for {hours} hours"),
+                    monthly_cost: 0.0,
+                });
+            }
+        }
+        waste
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+    use time::{macros::datetime, Duration};
+
+    use crate::{
+        date_time_wrapper::DateTimeWrapper,
+        ec2_instance::{AmiInfo, Ec2InstanceInfo, ElasticIpInfo, SnapshotInfo, VolumeInfo},
+        waste::{WasteInventory, WasteKind, WastePolicy},
+    };
+
+    #[test]
+    fn test_find_waste() {
+        let now = datetime!(2024-06-01 00:00:00 UTC);
+        let policy = WastePolicy {
+            stopped_instance_days: 14,
+            unused_ami_months: 3,
+            idle_novnc_hours: 4,
+            volume_gb_month: 0.1,
+            snapshot_gb_month: 0.05,
+            elastic_ip_hourly: 0.005,
+        };
+        let instance = |id: &str, state: &str, days: i64| Ec2InstanceInfo {
+            id: id.into(),
+            state: state.into(),
+            launch_time: DateTimeWrapper::from_offsetdatetime(now - Duration::days(days)),
+            volumes: vec![format!("vol-{id}").into()],
+            ..Ec2InstanceInfo::default()
+        };
+        let ami = |id: &str, created: &str| AmiInfo {
+            id: id.into(),
+            name: id.into(),
+            state: "available".into(),
+            snapshot_ids: vec!["snap-0001".into()],
+            creation_date: Some(created.into()),
+            ..AmiInfo::default()
+        };
+        let inventory = WasteInventory {
+            instances: vec![
+                instance("old", "stopped", 30),
+                instance("recent", "stopped", 2),
+                instance("running", "running", 30),
+            ],
+            volumes: vec![
+                VolumeInfo {
+                    id: "vol-old".into(),
+                    size: 8,
+                    state: "in-use".into(),
+                    ..VolumeInfo::default()
+                },
+                VolumeInfo {
+                    id: "vol-spare".into(),
+                    size: 50,
+                    state: "available".into(),
+                    ..VolumeInfo::default()
+                },
+            ],
+            snapshots: vec![SnapshotInfo {
+                id: "snap-0001".into(),
+                volume_size: 20,
+                ..SnapshotInfo::default()
+            }],
+            elastic_ips: vec![
+                ElasticIpInfo {
+                    allocation_id: "eipalloc-free".into(),
+                    public_ip: "192.0.2.1".into(),
+                    ..ElasticIpInfo::default()
+                },
+                ElasticIpInfo {
+                    allocation_id: "eipalloc-used".into(),
+                    public_ip: "192.0.2.2".into(),
+                    association_id: Some("eipassoc-1".into()),
+                    ..ElasticIpInfo::default()
+                },
+            ],
+            amis: vec![
+                ami("ami-old", "2023-01-01T00:00:00.000Z"),
+                ami("ami-launched", "2023-01-01T00:00:00.000Z"),
+                ami("ami-new", "2024-05-01T00:00:00.000Z"),
+            ],
+            ami_last_launch: hashmap! {"ami-launched".into() => now - Duration::days(10)},
+            novnc_started_at: Some(now - Duration::hours(5)),
+        };
+        let waste = inventory.find_waste(&policy, now);
+        let found: Vec<_> = waste.iter().map(|w| (w.kind, w.id.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (WasteKind::UnattachedVolume, "vol-spare"),
+                (WasteKind::UnassociatedElasticIp, "eipalloc-free"),
+                (WasteKind::StoppedInstance, "old"),
+                (WasteKind::UnusedAmi, "ami-old"),
+                (WasteKind::IdleNovnc, "novnc"),
+            ]
+        );
+        assert!((waste[0].monthly_cost - 5.0).abs() < 1e-9);
+        assert!((waste[2].monthly_cost - 0.8).abs() < 1e-9);
+        assert!((waste[3].monthly_cost - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,128 @@
+use stack_string::{format_sstr, StackString};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::resource_type::ResourceType;
+
+/// Moves the cursor home and clears the terminal before each refresh
+pub const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const CHANGED: &str = "\x1b[1;33m";
+const ADDED: &str = "\x1b[1;32m";
+const REMOVED: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+/// Resources which have a state worth watching
+pub const WATCH_RESOURCES: [ResourceType; 4] = [
+    ResourceType::Instances,
+    ResourceType::Spot,
+    ResourceType::Volume,
+    ResourceType::Snapshot,
+];
+
+/// One line of a watched listing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchRow {
+    pub id: StackString,
+    pub state: StackString,
+    pub line: StackString,
+}
+
+/// Remembers the state of every row between refreshes so the next render can
+/// highlight what changed
+#[derive(Default, Debug)]
+pub struct StateWatcher {
+    previous: Option<HashMap<StackString, StackString>>,
+}
+
+impl StateWatcher {
+    /// Lines of `sections`, each a heading and its rows. Rows whose state
+    /// changed since the previous call are highlighted with the old state,
+    /// rows which are new are marked `+` and rows which disappeared `-`.
+    /// Nothing is highlighted on the first call.
+    pub fn render(&mut self, sections: &[(&str, Vec<WatchRow>)]) -> Vec<StackString> {
+        let mut current = HashMap::new();
+        let mut lines = Vec::new();
+        for (heading, rows) in sections {
+            lines.push(format_sstr!("{heading}:"));
+            for row in rows {
+                let line = match self.previous.as_ref().map(|p| p.get(&row.id)) {
+                    None => format_sstr!("  {}", row.line),
+                    Some(None) => format_sstr!("{ADDED}+ {}{RESET}", row.line),
+                    Some(Some(state)) if state != &row.state => {
+                        format_sstr!("{CHANGED}* {} (was {state}){RESET}", row.line)
+                    }
+                    Some(Some(_)) => format_sstr!("  {}", row.line),
+                };
+                lines.push(line);
+                current.insert(row.id.clone(), row.state.clone());
+            }
+        }
+        if let Some(previous) = &self.previous {
+            let removed: BTreeSet<_> = previous
+                .iter()
+                .filter(|(id, _)| !current.contains_key(*id))
+                .collect();
+            for (id, state) in removed {
+                lines.push(format_sstr!("{REMOVED}- {id} {state} (gone){RESET}"));
+            }
+        }
+        self.previous.replace(current);
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::watch::{StateWatcher, WatchRow};
+
+    fn row(id: &str, state: &str) -> WatchRow {
+        WatchRow {
+            id: id.into(),
+            state: state.into(),
+            line: format!("{id} {state}").into(),
+        }
+    }
+
+    #[test]
+    fn test_state_watcher() {
+        let mut watcher = StateWatcher::default();
+        let lines = watcher.render(&[
+            (
+                "instances",
+                vec![row("i-0001", "pending"), row("i-0002", "running")],
+            ),
+            ("spot", vec![row("sir-0001", "open")]),
+        ]);
+        assert_eq!(
+            lines,
+            vec![
+                "instances:",
+                "  i-0001 pending",
+                "  i-0002 running",
+                "spot:",
+                "  sir-0001 open",
+            ]
+        );
+
+        let lines = watcher.render(&[
+            (
+                "instances",
+                vec![row("i-0001", "running"), row("i-0003", "pending")],
+            ),
+            ("spot", vec![row("sir-0001", "open")]),
+        ]);
+        assert_eq!(
+            lines,
+            vec![
+                "instances:",
+                "\x1b[1;33m* i-0001 running (was pending)\x1b[0m",
+                "\x1b[1;32m+ i-0003 pending\x1b[0m",
+                "spot:",
+                "  sir-0001 open",
+                "\x1b[1;31m- i-0002 running (gone)\x1b[0m",
+            ]
+        );
+
+        let lines = watcher.render(&[("instances", vec![row("i-0001", "running")])]);
+        assert_eq!(lines[1], "  i-0001 running");
+    }
+}
@@ -0,0 +1,683 @@
+use anyhow::{format_err, Error};
+use hmac::{Hmac, Mac};
+use log::debug;
+use postgres_query::{query, FromSqlRow};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use stack_string::{format_sstr, StackString};
+use std::{collections::HashMap, fmt, fmt::Write, str::FromStr};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    date_time_wrapper::DateTimeWrapper,
+    ec2_instance::{Ec2InstanceInfo, SnapshotInfo, SpotInstanceRequestInfo},
+    pgpool::PgPool,
+    storage::{Storage, WebhookRepo},
+};
+
+pub const DELIVERY_PENDING: &str = "pending";
+pub const DELIVERY_DELIVERED: &str = "delivered";
+pub const DELIVERY_FAILED: &str = "failed";
+
+/// Header carrying `sha256=` followed by the hex hmac of the body keyed by
+/// the webhook secret
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+
+/// Status code of a fulfilled spot request
+const SPOT_FULFILLED: &str = "fulfilled";
+
+const SNAPSHOT_COMPLETED: &str = "completed";
+
+/// Upper bound on the delay between delivery attempts
+const MAX_RETRY_DELAY: Duration = Duration::hours(1);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WebhookEvent {
+    #[serde(rename = "instance.state_changed")]
+    InstanceStateChanged,
+    #[serde(rename = "spot.fulfilled")]
+    SpotRequestFulfilled,
+    #[serde(rename = "email.received")]
+    InboundEmailReceived,
+}
+
+impl WebhookEvent {
+    pub const ALL: [Self; 3] = [
+        Self::InstanceStateChanged,
+        Self::SpotRequestFulfilled,
+        Self::InboundEmailReceived,
+    ];
+
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::InstanceStateChanged => "instance.state_changed",
+            Self::SpotRequestFulfilled => "spot.fulfilled",
+            Self::InboundEmailReceived => "email.received",
+        }
+    }
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|e| e.to_str() == s)
+            .copied()
+            .ok_or_else(|| format_err!("{s} is not a WebhookEvent"))
+    }
+}
+
+/// Body POSTed to subscribers
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    pub resource_id: StackString,
+    pub detail: Value,
+    pub timestamp: DateTimeWrapper,
+}
+
+impl WebhookPayload {
+    #[must_use]
+    pub fn new(event: WebhookEvent, resource_id: impl Into<StackString>, detail: Value) -> Self {
+        Self {
+            event,
+            resource_id: resource_id.into(),
+            detail,
+            timestamp: DateTimeWrapper::now(),
+        }
+    }
+}
+
+/// `sha256=` followed by the hex encoded hmac-sha256 of `body`
+/// # Errors
+/// Returns error if the secret can't be used as an hmac key
+pub fn sign_payload(secret: &str, body: &[u8]) -> Result<StackString, Error> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| format_err!("invalid webhook secret: {e}"))?;
+    mac.update(body);
+    let mut signature = String::from("sha256=");
+    for byte in mac.finalize().into_bytes() {
+        write!(signature, "{byte:02x}")?;
+    }
+    Ok(signature.into())
+}
+
+/// Delay before the next attempt after `attempts` failed ones: 30 seconds
+/// doubling each time, capped at an hour
+#[must_use]
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.clamp(1, 16).unsigned_abs() - 1;
+    (Duration::seconds(30) * 2_i32.pow(exponent)).min(MAX_RETRY_DELAY)
+}
+
+/// Instance states and spot request statuses seen on the previous poll
+#[derive(Default, Debug)]
+pub struct ResourceStates {
+    instances: HashMap<StackString, StackString>,
+    spot_requests: HashMap<StackString, StackString>,
+    seeded: bool,
+    snapshots: HashMap<StackString, StackString>,
+    snapshots_seeded: bool,
+}
+
+impl ResourceStates {
+    /// Events since the previous call, the first call only records the
+    /// current state
+    pub fn update(
+        &mut self,
+        instances: impl IntoIterator<Item = Ec2InstanceInfo>,
+        spot_requests: impl IntoIterator<Item = SpotInstanceRequestInfo>,
+    ) -> Vec<WebhookPayload> {
+        let mut events = Vec::new();
+        let instances: HashMap<_, _> = instances
+            .into_iter()
+            .map(|inst| {
+                let name = inst.tags.get("Name").cloned();
+                (inst.id, (inst.state, name))
+            })
+            .collect();
+        for (id, (state, name)) in &instances {
+            let previous = self.instances.get(id);
+            if self.seeded && previous != Some(state) {
+                events.push(WebhookPayload::new(
+                    WebhookEvent::InstanceStateChanged,
+                    id.clone(),
+                    json!({"previous_state": previous, "state": state, "name": name}),
+                ));
+            }
+        }
+        let spot_requests: HashMap<_, _> = spot_requests
+            .into_iter()
+            .map(|req| (req.id.clone(), req))
+            .collect();
+        for (id, req) in &spot_requests {
+            let was_fulfilled = self
+                .spot_requests
+                .get(id)
+                .map_or(false, |s| s.as_str() == SPOT_FULFILLED);
+            if self.seeded && req.status.as_str() == SPOT_FULFILLED && !was_fulfilled {
+                events.push(WebhookPayload::new(
+                    WebhookEvent::SpotRequestFulfilled,
+                    id.clone(),
+                    json!({
+                        "instance_id": req.instance_id,
+                        "instance_type": req.instance_type,
+                        "price": req.price,
+                    }),
+                ));
+            }
+        }
+        self.instances = instances
+            .into_iter()
+            .map(|(id, (state, _))| (id, state))
+            .collect();
+        self.spot_requests = spot_requests
+            .into_iter()
+            .map(|(id, req)| (id, req.status))
+            .collect();
+        self.seeded = true;
+        events
+    }
+    /// Snapshots that completed since the previous call, including new ones
+    /// which completed in between, the first call only records the current
+    /// state
+    pub fn completed_snapshots(
+        &mut self,
+        snapshots: impl IntoIterator<Item = SnapshotInfo>,
+    ) -> Vec<SnapshotInfo> {
+        let mut completed = Vec::new();
+        let mut states = HashMap::new();
+        for snapshot in snapshots {
+            let was_completed = self
+                .snapshots
+                .get(&snapshot.id)
+                .map_or(false, |s| s.as_str() == SNAPSHOT_COMPLETED);
+            states.insert(snapshot.id.clone(), snapshot.state.clone());
+            if self.snapshots_seeded
+                && snapshot.state.as_str() == SNAPSHOT_COMPLETED
+                && !was_completed
+            {
+                completed.push(snapshot);
+            }
+        }
+        self.snapshots = states;
+        self.snapshots_seeded = true;
+        completed
+    }
+}
+
+/// A subscriber url and the events it receives
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: StackString,
+    #[serde(skip)]
+    pub secret: StackString,
+    pub events: Vec<StackString>,
+    pub enabled: bool,
+    pub created_at: OffsetDateTime,
+}
+
+impl Webhook {
+    /// A new enabled webhook with a random signing secret
+    /// # Errors
+    /// Returns error if `url` isn't an http(s) url
+    pub fn new(url: &str, events: &[WebhookEvent]) -> Result<Self, Error> {
+        let parsed: url::Url = url.parse()?;
+        if !["http", "https"].contains(&parsed.scheme()) {
+            return Err(format_err!("webhook url {url} must be http or https"));
+        }
+        if events.is_empty() {
+            return Err(format_err!("webhook needs at least one event"));
+        }
+        let secret: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        Ok(Self {
+            id: Uuid::new_v4(),
+            url: url.into(),
+            secret: secret.into(),
+            events: events.iter().map(|e| e.to_str().into()).collect(),
+            enabled: true,
+            created_at: OffsetDateTime::now_utc(),
+        })
+    }
+
+    #[must_use]
+    pub fn subscribes_to(&self, event: WebhookEvent) -> bool {
+        self.enabled && self.events.iter().any(|e| e == event.to_str())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM webhooks ORDER BY created_at");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!("SELECT * FROM webhooks WHERE id = $id", id = id);
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO webhooks (id, url, secret, events, enabled, created_at)
+                VALUES ($id, $url, $secret, $events, $enabled, $created_at)
+            ",
+            id = self.id,
+            url = self.url,
+            secret = self.secret,
+            events = self.events,
+            enabled = self.enabled,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Deliveries of the webhook are deleted with it
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn delete_entry(pool: &PgPool, id: Uuid) -> Result<u64, Error> {
+        let query = query!("DELETE FROM webhooks WHERE id = $id", id = id);
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+}
+
+/// One payload queued for one webhook, retried until delivered or
+/// `webhook_max_attempts` is reached
+#[derive(FromSqlRow, Clone, Debug, PartialEq, Serialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: StackString,
+    pub payload: Value,
+    pub status: StackString,
+    pub attempts: i32,
+    pub response_code: Option<i32>,
+    pub last_error: Option<StackString>,
+    pub next_attempt_at: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+    pub delivered_at: Option<OffsetDateTime>,
+}
+
+impl WebhookDelivery {
+    /// # Errors
+    /// Returns error if the payload can't be serialized
+    pub fn new(webhook_id: Uuid, payload: &WebhookPayload) -> Result<Self, Error> {
+        let now = OffsetDateTime::now_utc();
+        Ok(Self {
+            id: Uuid::new_v4(),
+            webhook_id,
+            event: payload.event.to_str().into(),
+            payload: serde_json::to_value(payload)?,
+            status: DELIVERY_PENDING.into(),
+            attempts: 0,
+            response_code: None,
+            last_error: None,
+            next_attempt_at: now,
+            created_at: now,
+            delivered_at: None,
+        })
+    }
+
+    /// Most recent deliveries for the delivery log
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_recent(pool: &PgPool, limit: i64) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM webhook_deliveries ORDER BY created_at DESC LIMIT $limit",
+            limit = limit,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_due(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            r"
+                SELECT * FROM webhook_deliveries
+                WHERE status = $status AND next_attempt_at <= now()
+                ORDER BY next_attempt_at
+            ",
+            status = DELIVERY_PENDING,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                INSERT INTO webhook_deliveries (
+                    id, webhook_id, event, payload, status, attempts, next_attempt_at, created_at
+                ) VALUES (
+                    $id, $webhook_id, $event, $payload, $status, $attempts, $next_attempt_at,
+                    $created_at
+                )
+            ",
+            id = self.id,
+            webhook_id = self.webhook_id,
+            event = self.event,
+            payload = self.payload,
+            status = self.status,
+            attempts = self.attempts,
+            next_attempt_at = self.next_attempt_at,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn update_entry(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            r"
+                UPDATE webhook_deliveries
+                SET status=$status,attempts=$attempts,response_code=$response_code,
+                    last_error=$last_error,next_attempt_at=$next_attempt_at,
+                    delivered_at=$delivered_at
+                WHERE id=$id
+            ",
+            id = self.id,
+            status = self.status,
+            attempts = self.attempts,
+            response_code = self.response_code,
+            last_error = self.last_error,
+            next_attempt_at = self.next_attempt_at,
+            delivered_at = self.delivered_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Record the outcome of an attempt: a 2xx response marks the delivery
+    /// delivered, anything else schedules a retry or, after `max_attempts`,
+    /// marks it failed
+    pub fn record_attempt(
+        &mut self,
+        result: Result<u16, StackString>,
+        max_attempts: i32,
+        now: OffsetDateTime,
+    ) {
+        self.attempts += 1;
+        let error = match result {
+            Ok(code) => {
+                self.response_code = Some(code.into());
+                if (200..300).contains(&code) {
+                    None
+                } else {
+                    Some(format_sstr!("subscriber returned {code}"))
+                }
+            }
+            Err(e) => Some(e),
+        };
+        match error {
+            None => {
+                self.status = DELIVERY_DELIVERED.into();
+                self.last_error = None;
+                self.delivered_at = Some(now);
+            }
+            Some(error) => {
+                self.last_error = Some(error);
+                if self.attempts >= max_attempts {
+                    self.status = DELIVERY_FAILED.into();
+                } else {
+                    self.next_attempt_at = now + retry_delay(self.attempts);
+                }
+            }
+        }
+    }
+}
+
+/// Queue `payload` for every enabled webhook subscribed to its event.
+/// Returns the number of deliveries queued.
+/// # Errors
+/// Returns error if db query fails
+pub async fn enqueue_webhooks(
+    storage: &dyn Storage,
+    payload: &WebhookPayload,
+) -> Result<usize, Error> {
+    let mut queued = 0;
+    for webhook in storage.get_webhooks().await? {
+        if webhook.subscribes_to(payload.event) {
+            let delivery = WebhookDelivery::new(webhook.id, payload)?;
+            storage.insert_delivery(&delivery).await?;
+            queued += 1;
+        }
+    }
+    Ok(queued)
+}
+
+/// Attempt every delivery that is due. Returns the number delivered.
+/// # Errors
+/// Returns error if db query fails, failed requests are recorded on the
+/// delivery instead
+pub async fn deliver_webhooks(
+    storage: &dyn Storage,
+    max_attempts: i32,
+    timeout: std::time::Duration,
+) -> Result<usize, Error> {
+    let due = storage.get_due_deliveries().await?;
+    if due.is_empty() {
+        return Ok(0);
+    }
+    let webhooks: HashMap<_, _> = storage
+        .get_webhooks()
+        .await?
+        .into_iter()
+        .map(|w| (w.id, w))
+        .collect();
+    let client = Client::builder().timeout(timeout).build()?;
+    let mut delivered = 0;
+    for mut delivery in due {
+        let webhook = match webhooks.get(&delivery.webhook_id) {
+            Some(webhook) if webhook.enabled => webhook,
+            _ => continue,
+        };
+        let body = serde_json::to_vec(&delivery.payload)?;
+        let signature = sign_payload(&webhook.secret, &body)?;
+        let result = client
+            .post(webhook.url.as_str())
+            .header("Content-Type", "application/json")
+            .header(EVENT_HEADER, delivery.event.as_str())
+            .header(SIGNATURE_HEADER, signature.as_str())
+            .body(body)
+            .send()
+            .await
+            .map(|r| r.status().as_u16())
+            .map_err(|e| format_sstr!("{e}"));
+        delivery.record_attempt(result, max_attempts, OffsetDateTime::now_utc());
+        if delivery.status == DELIVERY_DELIVERED {
+            delivered += 1;
+        } else {
+            debug!(
+                "webhook delivery {} to {} failed: {:?}",
+                delivery.id, webhook.url, delivery.last_error
+            );
+        }
+        storage.update_delivery(&delivery).await?;
+    }
+    Ok(delivered)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use maplit::hashmap;
+    use std::collections::HashMap;
+    use time::{macros::datetime, Duration};
+    use uuid::Uuid;
+
+    use crate::{
+        ec2_instance::{Ec2InstanceInfo, SnapshotInfo, SpotInstanceRequestInfo},
+        webhook::{
+            retry_delay, sign_payload, ResourceStates, Webhook, WebhookDelivery, WebhookEvent,
+            WebhookPayload, DELIVERY_DELIVERED, DELIVERY_FAILED, DELIVERY_PENDING,
+        },
+    };
+
+    fn instance(id: &str, state: &str) -> Ec2InstanceInfo {
+        Ec2InstanceInfo {
+            id: id.into(),
+            state: state.into(),
+            tags: hashmap! {"Name".into() => "test".into()},
+            ..Ec2InstanceInfo::default()
+        }
+    }
+
+    fn spot(id: &str, status: &str) -> SpotInstanceRequestInfo {
+        SpotInstanceRequestInfo {
+            id: id.into(),
+            status: status.into(),
+            ..SpotInstanceRequestInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_sign_payload() -> Result<(), Error> {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign_payload("Jefe", b"what do ya want for nothing?")?,
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_webhook_event() -> Result<(), Error> {
+        for event in WebhookEvent::ALL {
+            assert_eq!(event.to_str().parse::<WebhookEvent>()?, event);
+        }
+        assert!("instance.deleted".parse::<WebhookEvent>().is_err());
+        let webhook = Webhook::new(
+            "https://example.com/hook",
+            &[WebhookEvent::SpotRequestFulfilled],
+        )?;
+        assert_eq!(webhook.secret.len(), 32);
+        assert!(webhook.subscribes_to(WebhookEvent::SpotRequestFulfilled));
+        assert!(!webhook.subscribes_to(WebhookEvent::InboundEmailReceived));
+        assert!(Webhook::new("ftp://example.com", &[WebhookEvent::InboundEmailReceived]).is_err());
+        assert!(Webhook::new("https://example.com", &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_states() {
+        let mut states = ResourceStates::default();
+        let events = states.update(
+            vec![instance("i-1", "pending")],
+            vec![spot("sir-1", "pending-fulfillment")],
+        );
+        assert!(events.is_empty());
+
+        let events = states.update(
+            vec![instance("i-1", "running"), instance("i-2", "pending")],
+            vec![spot("sir-1", "fulfilled")],
+        );
+        assert_eq!(events.len(), 3);
+        assert!(events
+            .iter()
+            .any(|e| e.event == WebhookEvent::SpotRequestFulfilled && e.resource_id == "sir-1"));
+        let changed: Vec<_> = events
+            .iter()
+            .filter(|e| e.event == WebhookEvent::InstanceStateChanged)
+            .collect();
+        assert_eq!(changed.len(), 2);
+
+        let events = states.update(
+            vec![instance("i-1", "running"), instance("i-2", "pending")],
+            vec![spot("sir-1", "fulfilled")],
+        );
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_completed_snapshots() {
+        let snapshot = |id: &str, state: &str| SnapshotInfo {
+            id: id.into(),
+            volume_id: Some("vol-1".into()),
+            volume_size: 8,
+            state: state.into(),
+            progress: "".into(),
+            tags: HashMap::new(),
+            start_time: None,
+            encrypted: false,
+        };
+        let mut states = ResourceStates::default();
+        let completed = states.completed_snapshots(vec![
+            snapshot("snap-1", "pending"),
+            snapshot("snap-2", "completed"),
+        ]);
+        assert!(completed.is_empty());
+
+        let completed = states.completed_snapshots(vec![
+            snapshot("snap-1", "completed"),
+            snapshot("snap-2", "completed"),
+            snapshot("snap-3", "completed"),
+            snapshot("snap-4", "pending"),
+        ]);
+        let ids: Vec<_> = completed.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["snap-1", "snap-3"]);
+
+        let completed = states.completed_snapshots(vec![snapshot("snap-4", "completed")]);
+        assert_eq!(completed.len(), 1);
+    }
+
+    #[test]
+    fn test_record_attempt() -> Result<(), Error> {
+        let now = datetime!(2024-06-01 00:00:00 UTC);
+        let payload = WebhookPayload::new(
+            WebhookEvent::InboundEmailReceived,
+            "email-1",
+            serde_json::json!({}),
+        );
+        let mut delivery = WebhookDelivery::new(Uuid::new_v4(), &payload)?;
+        delivery.record_attempt(Ok(500), 2, now);
+        assert_eq!(delivery.status, DELIVERY_PENDING);
+        assert_eq!(delivery.next_attempt_at, now + Duration::seconds(30));
+        delivery.record_attempt(Err("connection refused".into()), 2, now);
+        assert_eq!(delivery.status, DELIVERY_FAILED);
+
+        let mut delivery = WebhookDelivery::new(Uuid::new_v4(), &payload)?;
+        delivery.record_attempt(Ok(204), 2, now);
+        assert_eq!(delivery.status, DELIVERY_DELIVERED);
+        assert_eq!(delivery.delivered_at, Some(now));
+
+        assert_eq!(retry_delay(3), Duration::minutes(2));
+        assert_eq!(retry_delay(12), Duration::hours(1));
+        Ok(())
+    }
+}
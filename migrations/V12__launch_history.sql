@@ -0,0 +1,14 @@
+CREATE TABLE launch_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    instance_id TEXT,
+    spot_request_id TEXT,
+    instance_type TEXT NOT NULL,
+    ami TEXT NOT NULL,
+    is_spot BOOLEAN NOT NULL DEFAULT false,
+    status TEXT NOT NULL,
+    launched_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    terminated_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX launch_history_instance_id_idx ON launch_history (instance_id);
+CREATE INDEX launch_history_launched_at_idx ON launch_history (launched_at);
@@ -0,0 +1,3 @@
+ALTER TABLE instance_list ADD COLUMN gpu_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE instance_list ADD COLUMN gpu_type TEXT;
+ALTER TABLE instance_list ADD COLUMN network_performance TEXT;
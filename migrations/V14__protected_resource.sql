@@ -0,0 +1,5 @@
+CREATE TABLE protected_resource (
+    resource_id TEXT PRIMARY KEY,
+    reason TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
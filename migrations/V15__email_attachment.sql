@@ -0,0 +1,13 @@
+CREATE TABLE email_attachment (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    email_id UUID NOT NULL,
+    filename TEXT NOT NULL,
+    content_type TEXT,
+    size BIGINT NOT NULL,
+    s3_bucket TEXT NOT NULL,
+    s3_key TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    UNIQUE (email_id, s3_key)
+);
+
+CREATE INDEX email_attachment_email_id_idx ON email_attachment (email_id);
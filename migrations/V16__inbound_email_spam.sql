@@ -0,0 +1,3 @@
+ALTER TABLE inbound_email ADD COLUMN spam_score DOUBLE PRECISION NOT NULL DEFAULT 0.0;
+ALTER TABLE inbound_email ADD COLUMN verdicts TEXT;
+ALTER TABLE inbound_email ADD COLUMN spam_feedback BOOLEAN;
@@ -0,0 +1,9 @@
+CREATE TABLE email_forward_rule (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    recipient_pattern TEXT,
+    subject_pattern TEXT,
+    forward_to TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
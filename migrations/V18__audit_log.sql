@@ -0,0 +1,9 @@
+CREATE TABLE audit_log (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    action TEXT NOT NULL,
+    resource TEXT NOT NULL,
+    details TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX audit_log_created_at_idx ON audit_log (created_at);
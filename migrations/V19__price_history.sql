@@ -0,0 +1,11 @@
+CREATE TABLE price_history (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    instance_type TEXT NOT NULL,
+    price_type TEXT NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    price_timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
+    recorded_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX price_history_instance_type_idx ON price_history (instance_type, recorded_at);
+CREATE INDEX price_history_recorded_at_idx ON price_history (recorded_at);
@@ -0,0 +1,6 @@
+CREATE TABLE update_status (
+    task TEXT PRIMARY KEY NOT NULL,
+    last_attempt TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_success TIMESTAMP WITH TIME ZONE,
+    last_error TEXT
+);
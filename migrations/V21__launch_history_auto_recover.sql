@@ -0,0 +1,4 @@
+ALTER TABLE launch_history ADD COLUMN launch_params JSONB;
+ALTER TABLE launch_history ADD COLUMN auto_recover BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE launch_history ADD COLUMN recovery_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE launch_history ADD COLUMN recovery_status TEXT;
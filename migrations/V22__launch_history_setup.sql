@@ -0,0 +1,4 @@
+ALTER TABLE launch_history ADD COLUMN setup_status TEXT;
+ALTER TABLE launch_history ADD COLUMN setup_seconds INTEGER;
+ALTER TABLE launch_history ADD COLUMN setup_message TEXT;
+UPDATE launch_history SET setup_status = 'unchecked';
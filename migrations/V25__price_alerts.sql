@@ -0,0 +1,10 @@
+CREATE TABLE price_alerts (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    instance_type TEXT NOT NULL,
+    region TEXT NOT NULL,
+    threshold DOUBLE PRECISION NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    last_price DOUBLE PRECISION,
+    triggered_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
@@ -0,0 +1,2 @@
+ALTER TABLE launch_history ADD COLUMN dns_name TEXT;
+ALTER TABLE launch_history ADD COLUMN dns_registered_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,9 @@
+CREATE TABLE dr_policies (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    volume_name TEXT NOT NULL,
+    target_region TEXT NOT NULL,
+    encrypted BOOLEAN NOT NULL DEFAULT true,
+    kms_key_id TEXT,
+    max_age_hours INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
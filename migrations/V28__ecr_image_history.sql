@@ -0,0 +1,11 @@
+CREATE TABLE ecr_image_history (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    repo TEXT NOT NULL,
+    digest TEXT NOT NULL,
+    tags TEXT[] NOT NULL,
+    image_size DOUBLE PRECISION NOT NULL,
+    pushed_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    recorded_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX ecr_image_history_repo_idx ON ecr_image_history (repo, digest, recorded_at);
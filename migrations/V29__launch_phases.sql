@@ -0,0 +1,4 @@
+ALTER TABLE launch_history ADD COLUMN fulfilled_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE launch_history ADD COLUMN running_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE launch_history ADD COLUMN cloud_init_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE launch_history ADD COLUMN healthy_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,7 @@
+ALTER TABLE inbound_email ADD COLUMN message_id TEXT;
+ALTER TABLE inbound_email ADD COLUMN in_reply_to TEXT;
+ALTER TABLE inbound_email ADD COLUMN thread_id UUID;
+UPDATE inbound_email SET thread_id = id;
+ALTER TABLE inbound_email ALTER COLUMN thread_id SET NOT NULL;
+CREATE INDEX IF NOT EXISTS inbound_email_message_id_idx ON inbound_email (message_id);
+CREATE INDEX IF NOT EXISTS inbound_email_thread_id_idx ON inbound_email (thread_id);
@@ -0,0 +1,14 @@
+CREATE TABLE sent_email (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    email_id UUID NOT NULL,
+    thread_id UUID NOT NULL,
+    from_address TEXT NOT NULL,
+    to_address TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    message_id TEXT,
+    sent_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX sent_email_thread_id_idx ON sent_email (thread_id);
+CREATE INDEX sent_email_message_id_idx ON sent_email (message_id);
@@ -0,0 +1,11 @@
+CREATE TABLE ami_catalog (
+    ami_id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    base_release TEXT,
+    build_script TEXT,
+    build_date TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    parent_ami TEXT,
+    instance_id TEXT
+);
+
+CREATE INDEX ami_catalog_parent_ami_idx ON ami_catalog (parent_ami);
@@ -0,0 +1,9 @@
+CREATE TABLE user_preferences (
+    email TEXT PRIMARY KEY NOT NULL,
+    hidden_columns JSONB NOT NULL DEFAULT '{}',
+    default_sort TEXT,
+    sort_descending BOOLEAN NOT NULL DEFAULT false,
+    rows_per_page INTEGER,
+    default_region TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
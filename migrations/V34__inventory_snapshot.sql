@@ -0,0 +1,8 @@
+CREATE TABLE inventory_snapshot (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    region TEXT NOT NULL,
+    resources JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX inventory_snapshot_region_created_at_idx ON inventory_snapshot (region, created_at);
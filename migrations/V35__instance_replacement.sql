@@ -0,0 +1,17 @@
+CREATE TABLE instance_replacement (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    old_instance_id TEXT NOT NULL,
+    new_instance_id TEXT,
+    spot_request_id TEXT,
+    ami TEXT NOT NULL,
+    dns_name TEXT,
+    terminate_old BOOLEAN NOT NULL DEFAULT false,
+    soak_minutes INTEGER NOT NULL DEFAULT 0,
+    state TEXT NOT NULL,
+    message TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    dns_swapped_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX instance_replacement_state_idx ON instance_replacement (state);
@@ -1,56 +1,170 @@
-function listResource( resource_type ) {
-    let url = "/aws/list?resource=" + resource_type;
+const csrfSafeMethods = ["GET", "HEAD", "OPTIONS"];
+const xmlHttpOpen = XMLHttpRequest.prototype.open;
+XMLHttpRequest.prototype.open = function open( method, url, ...rest ) {
+    xmlHttpOpen.call(this, method, url, ...rest);
+    let token = document.querySelector('meta[name="csrf-token"]');
+    if (token && !csrfSafeMethods.includes(method.toUpperCase())) {
+        this.setRequestHeader("X-CSRF-Token", token.content);
+    }
+};
+let idempotencyKeys = {};
+function idempotencyKey( ...parts ) {
+    let key = parts.join(":");
+    if (!(key in idempotencyKeys)) {
+        idempotencyKeys[key] = (window.crypto && crypto.randomUUID)
+            ? crypto.randomUUID()
+            : Date.now() + "-" + Math.random().toString(16).slice(2);
+    }
+    return idempotencyKeys[key];
+}
+function runAction( element ) {
+    let name = element.dataset.action;
+    let binding = routeBindings[name];
+    let output = document.getElementById("garminconnectoutput");
+    if (!binding) {
+        output.innerHTML = "no binding for " + name;
+        return;
+    }
+    let params = element.dataset.params ? JSON.parse(element.dataset.params) : {};
+    let values = Object.values(params);
+    if (binding.confirm && !confirm(binding.confirm + " " + values.join(" ") + "?")) {
+        return;
+    }
+    let query = new URLSearchParams(params).toString();
+    let url = query ? binding.path + "?" + query : binding.path;
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.onload = function f() {
-        document.getElementById("sub_article").innerHTML = "&nbsp;";
-        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
-        document.getElementById("garminconnectoutput").innerHTML = "done";
+        if (xmlhttp.status >= 400) {
+            output.innerHTML = xmlhttp.responseText;
+            return;
+        }
+        if (binding.target == "main") {
+            document.getElementById("sub_article").innerHTML = "&nbsp;";
+            document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        } else if (binding.target == "sub") {
+            document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+        }
+        output.innerHTML = "done";
+        if (binding.refresh) {
+            listResource(binding.refresh, true);
+        }
+    }
+    xmlhttp.open(binding.method, url, true);
+    if (binding.idempotent) {
+        xmlhttp.setRequestHeader("Idempotency-Key", idempotencyKey(name, ...values));
     }
-    xmlhttp.open("GET", url, true);
     xmlhttp.send(null);
-    document.getElementById("garminconnectoutput").innerHTML = "running";
+    output.innerHTML = "running";
+}
+document.addEventListener("click", function onAction( event ) {
+    let element = event.target.closest("[data-action]");
+    if (element) {
+        runAction(element);
+    }
+});
+if (window.EventSource) {
+    let instanceEvents = new EventSource("/aws/events/stream");
+    instanceEvents.addEventListener("instance", function onInstanceEvent( event ) {
+        let data = JSON.parse(event.data);
+        let output = document.getElementById("garminconnectoutput");
+        if (data.type == "spot_interruption") {
+            output.innerHTML = "spot " + data.instance_id + " will " + data.action;
+        } else {
+            output.innerHTML = data.instance_id + " " + data.state;
+        }
+        if (document.getElementById("instance_filter_state")) {
+            filterInstances();
+        }
+    });
+    instanceEvents.addEventListener("email", function onEmailEvent( event ) {
+        let data = JSON.parse(event.data);
+        if (!data.is_spam) {
+            document.getElementById("garminconnectoutput").textContent =
+                "new email from " + data.from_address + ": " + data.subject;
+        }
+        if (document.getElementById("inbound_email_table")) {
+            listResource('inbound-email');
+        }
+    });
 }
-function terminateInstance( instance_id ) {
-    let url = "/aws/terminate?instance=" + instance_id;
+function listResource( resource_type, refresh ) {
+    let url = "/aws/list?resource=" + resource_type;
+    if (refresh) {
+        url += "&refresh=true";
+    }
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.onload = function f() {
+        document.getElementById("sub_article").innerHTML = "&nbsp;";
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
         document.getElementById("garminconnectoutput").innerHTML = "done";
-        listResource('instances');
     }
-    xmlhttp.open("DELETE", url, true);
+    xmlhttp.open("GET", url, true);
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
-function createImage( inst_id, name ) {
-    let url = "/aws/create_image?inst_id=" + inst_id + "&name=" + name;
+function createKeyPair( key_name ) {
+    if (!key_name) {
+        return;
+    }
+    let url = "/aws/create_key_pair/" + encodeURIComponent(key_name);
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.onload = function f() {
+        if (xmlhttp.status == 200) {
+            let blob = new Blob([xmlhttp.responseText], {type: "application/x-pem-file"});
+            let link = document.createElement("a");
+            link.href = URL.createObjectURL(blob);
+            link.download = key_name + ".pem";
+            link.click();
+            URL.revokeObjectURL(link.href);
+            listResource('key', true);
+        } else {
+            document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+        }
         document.getElementById("garminconnectoutput").innerHTML = "done";
-        listResource('ami');
     }
     xmlhttp.open("POST", url, true);
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
-function deleteImage( ami ) {
-    let url = "/aws/delete_image?ami=" + ami;
+function importKeyPair( key_name, public_key ) {
+    let url = "/aws/import_key_pair";
+    let data = JSON.stringify({"key_name": key_name, "public_key": public_key});
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.onload = function f() {
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
         document.getElementById("garminconnectoutput").innerHTML = "done";
-        listResource('ami');
+        listResource('key', true);
     }
-    xmlhttp.open("DELETE", url, true);
-    xmlhttp.send(null);
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.send(data);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
-function deleteVolume( volid ) {
-    let url = "/aws/delete_volume?volid=" + volid;
+function filterInstances() {
+    let params = new URLSearchParams();
+    let fields = {
+        "state": "instance_filter_state",
+        "name": "instance_filter_name",
+        "instance_type": "instance_filter_type",
+        "availability_zone": "instance_filter_az",
+        "sort": "instance_filter_sort",
+    };
+    for (let key in fields) {
+        let value = document.getElementById(fields[key]).value;
+        if (value) {
+            params.append(key, value);
+        }
+    }
+    if (document.getElementById("instance_filter_desc").checked) {
+        params.append("descending", "true");
+    }
+    let url = "/aws/instance_list?" + params.toString();
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
         document.getElementById("garminconnectoutput").innerHTML = "done";
-        listResource('volume');
     }
-    xmlhttp.open("DELETE", url, true);
+    xmlhttp.open("GET", url, true);
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
@@ -65,6 +179,7 @@ function createSnapshot( volid, name ) {
         listResource('volume');
     }
     xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader("Idempotency-Key", idempotencyKey("create_snapshot", volid, name));
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
@@ -81,39 +196,6 @@ function modifyVolume( volid ) {
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
-function deleteSnapshot( snapid ) {
-    let url = "/aws/delete_snapshot?snapid=" + snapid;
-    let xmlhttp = new XMLHttpRequest();
-    xmlhttp.onload = function f() {
-        document.getElementById("garminconnectoutput").innerHTML = "done";
-        listResource('snapshot');
-    }
-    xmlhttp.open("DELETE", url, true);
-    xmlhttp.send(null);
-    document.getElementById("garminconnectoutput").innerHTML = "running";
-}
-function deleteEcrImage( repo, id ) {
-    let url = "/aws/delete_image?reponame=" + repo + "&imageid=" + id;
-    let xmlhttp = new XMLHttpRequest();
-    xmlhttp.onload = function f() {
-        document.getElementById("garminconnectoutput").innerHTML = "done";
-        listResource('ecr');
-    }
-    xmlhttp.open("DELETE", url, true);
-    xmlhttp.send(null);
-    document.getElementById("garminconnectoutput").innerHTML = "running";
-}
-function cleanupEcrImages() {
-    let url = "/aws/cleanup_ecr_images";
-    let xmlhttp = new XMLHttpRequest();
-    xmlhttp.onload = function f() {
-        document.getElementById("garminconnectoutput").innerHTML = "done";
-        listResource('ecr');
-    }
-    xmlhttp.open("DELETE", url, true);
-    xmlhttp.send(null);
-    document.getElementById("garminconnectoutput").innerHTML = "running";
-}
 function editScript( filename ) {
     let url = "/aws/edit_script?filename=" + filename;
     let xmlhttp = new XMLHttpRequest();
@@ -154,13 +236,19 @@ function deleteScript( filename ) {
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
 function buildSpotRequest( ami, inst, script ) {
-    let url = "/aws/build_spot_request";
+    let params = [];
     if (ami) {
-        url = url + "?ami=" + ami;
-    } else if (inst) {
-        url = url + "?inst=" + inst;
-    } else if (script) {
-        url = url + "?script=" + script;
+        params.push("ami=" + ami);
+    }
+    if (inst) {
+        params.push("inst=" + inst);
+    }
+    if (script) {
+        params.push("script=" + script);
+    }
+    let url = "/aws/build_spot_request";
+    if (params.length > 0) {
+        url = url + "?" + params.join("&");
     }
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.onload = function f() {
@@ -196,6 +284,24 @@ function requestSpotInstance() {
     let key = document.getElementById('key').value;
     let price = document.getElementById('price').value;
     let name = document.getElementById('name').value;
+    let count = parseInt(document.getElementById('count').value) || 1;
+    let extra_instance_types = Array.from(
+        document.getElementById('extra_instance_types').selectedOptions
+    ).map(o => o.value);
+    let availability_zones = document.getElementById('availability_zones').value
+        .split(',').map(z => z.trim()).filter(z => z.length > 0);
+    let max_total_price = document.getElementById('max_total_price').value;
+    let auto_recover = document.getElementById('auto_recover').checked;
+    let tenancy = document.getElementById('tenancy').value;
+    let ebs_optimized = document.getElementById('ebs_optimized').checked;
+    let root_volume_size = parseInt(document.getElementById('root_volume_size').value) || null;
+    let root_volume_type = document.getElementById('root_volume_type').value || null;
+    let root_volume_iops = parseInt(document.getElementById('root_volume_iops').value) || null;
+    let data_volumes = document.getElementById('data_volumes').value
+        .split(',').map(v => v.trim()).filter(v => v.length > 0);
+    let instance_profile = document.getElementById('instance_profile').value || null;
+    let require_imdsv2 = document.getElementById('require_imdsv2').checked;
+    let dns_name = document.getElementById('dns_name').value || null;
 
     let data = JSON.stringify({
         'ami': ami,
@@ -205,28 +311,36 @@ function requestSpotInstance() {
         'key_name': key,
         'price': price,
         'name': name,
+        'count': count,
+        'extra_instance_types': extra_instance_types,
+        'availability_zones': availability_zones,
+        'max_total_price': max_total_price || null,
+        'auto_recover': auto_recover,
+        'tenancy': tenancy,
+        'ebs_optimized': ebs_optimized,
+        'root_volume_size': root_volume_size,
+        'root_volume_type': root_volume_type,
+        'root_volume_iops': root_volume_iops,
+        'data_volumes': data_volumes,
+        'instance_profile': instance_profile,
+        'require_imdsv2': require_imdsv2,
+        'dns_name': dns_name,
     });
 
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.open('POST', url, true);
     xmlhttp.onload = function see_result() {
-        listResource('instances');
+        if (xmlhttp.status >= 400) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        launchStatus();
     }
     xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.setRequestHeader("Idempotency-Key", idempotencyKey("request_spot", data));
     xmlhttp.send(data);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
-function cancelSpotRequest(spot_id) {
-    let url = "/aws/cancel_spot?spot_id=" + spot_id;
-    let xmlhttp = new XMLHttpRequest();
-    xmlhttp.onload = function f() {
-        document.getElementById("garminconnectoutput").innerHTML = "done";
-        listResource('spot');
-    }
-    xmlhttp.open("DELETE", url, true);
-    xmlhttp.send(null);
-    document.getElementById("garminconnectoutput").innerHTML = "running";
-}
 function listAllPrices() {
     let url = "/aws/prices";
     let xmlhttp = new XMLHttpRequest();
@@ -278,6 +392,41 @@ function getStatus( instance ) {
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
+function resetHostKey( instance ) {
+    let url = "/aws/reset_host_key?instance=" + instance;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+        getStatus(instance);
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "resetting host key";
+}
+function uploadFile( instance ) {
+    let url = "/aws/upload/" + instance;
+    let file = document.getElementById( 'upload_file' ).files[0];
+    if (!file) {
+        return;
+    }
+    let data = new FormData();
+    data.append('file', file);
+    data.append('path', document.getElementById( 'upload_path' ).value);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "uploading " + file.name;
+}
+function downloadFile( instance ) {
+    let path = document.getElementById( 'download_path' ).value;
+    if (!path) {
+        return;
+    }
+    window.location = "/aws/download/" + instance + "?path=" + encodeURIComponent(path);
+}
 function runCommand( instance ) {
     let url = "/aws/command";
     let command = document.getElementById( 'command_text' ).value;
@@ -301,6 +450,37 @@ function instanceOptions() {
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.onload = function f() {
         document.getElementById("instance_type").innerHTML = xmlhttp.responseText;
+        let extra = document.getElementById("extra_instance_types");
+        if (extra) {
+            extra.innerHTML = xmlhttp.responseText;
+        }
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+}
+function ubuntuImages() {
+    let release = document.getElementById("ubuntu_release").value;
+    let arch = document.getElementById("ubuntu_arch").value;
+    let minimal = document.getElementById("ubuntu_minimal").checked;
+    let url = "/aws/ubuntu_images?release=" + release + "&arch=" + arch + "&minimal=" + minimal;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status != 200) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        let ami = document.getElementById("ami");
+        let group = document.getElementById("ubuntu_amis");
+        if (!group) {
+            group = document.createElement("optgroup");
+            group.id = "ubuntu_amis";
+            ami.insertBefore(group, ami.firstChild);
+        }
+        group.label = "ubuntu " + release + " " + arch + (minimal ? " minimal" : "");
+        group.innerHTML = xmlhttp.responseText;
+        if (group.firstElementChild) {
+            ami.value = group.firstElementChild.value;
+        }
     }
     xmlhttp.open("GET", url, true);
     xmlhttp.send(null);
@@ -350,6 +530,7 @@ function createUser( user_name ) {
         listResource('user');
     }
     xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader("Idempotency-Key", idempotencyKey("create_user", user_name));
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
@@ -427,7 +608,8 @@ function systemdAction(action, service) {
     let url = "/aws/systemd_action?action=" + action + "&service=" + service;
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.onload = function f() {
-        document.getElementById("garminconnectoutput").innerHTML = "done";
+        let output = xmlhttp.responseText.trim();
+        document.getElementById("garminconnectoutput").textContent = output ? output : "done";
         listResource('systemd');
     }
     xmlhttp.open("POST", url, true);
@@ -446,17 +628,49 @@ function systemdLogs(service) {
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
 function systemdRestartAll() {
-    const sleep = ms => new Promise(r => setTimeout(r, ms));
     let url = "/aws/systemd_restart_all";
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.onload = function f() {
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function systemdRestartPreview(service) {
+    let url = "/aws/systemd_restart_preview?service=" + service;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function systemdRestartDependents(service) {
+    let url = "/aws/systemd_restart_dependents?service=" + service;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
         document.getElementById("garminconnectoutput").innerHTML = "done";
-        sleep(2000).then(() => listResource('systemd'));
     }
     xmlhttp.open("POST", url, true);
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
+function systemdDependencies() {
+    let url = "/aws/systemd_dependencies";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
 function crontabLogs(crontab_type) {
     let url = "/aws/crontab_logs/" + crontab_type;
     let xmlhttp = new XMLHttpRequest();
@@ -468,6 +682,25 @@ function crontabLogs(crontab_type) {
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "running";
 }
+function crontabPreview() {
+    crontabSubmit('/aws/crontab/preview');
+}
+function crontabInstall() {
+    crontabSubmit('/aws/crontab');
+}
+function crontabSubmit(url) {
+    let text = document.getElementById( 'crontab_editor_form' ).value;
+    let data = JSON.stringify({'text': text});
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open('POST', url, true);
+    xmlhttp.onload = function see_result() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("crontab_diff").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
 function emailDetail( id ) {
     let url = `/aws/inbound-email/${id}`;
     let xmlhttp = new XMLHttpRequest();
@@ -478,6 +711,20 @@ function emailDetail( id ) {
     xmlhttp.open("GET", url, true);
     xmlhttp.send(null);
 }
+function replyEmail( id ) {
+    let body = document.getElementById("reply-body-form").value;
+    let url = `/aws/inbound-email/${id}/reply`;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+        if (xmlhttp.status < 300) {
+            emailDetail(id);
+        }
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(JSON.stringify({"body": body}));
+}
 function deleteEmail( id ) {
     let url = `/aws/inbound-email/${id}`;
     let xmlhttp = new XMLHttpRequest();
@@ -489,14 +736,832 @@ function deleteEmail( id ) {
     xmlhttp.open("DELETE", url, true);
     xmlhttp.send(null);
 }
-function syncEmail() {
-    let url = "/aws/inbound-email/sync";
+function filterEmails( filter ) {
+    let rows = document.querySelectorAll("#inbound_email_table tbody tr");
+    for (let row of rows) {
+        let spam = row.getAttribute("data-spam") == "true";
+        let collapsed = row.getAttribute("data-collapsed") == "true";
+        let show = !collapsed && (filter == "all" || (filter == "spam") == spam);
+        row.style.display = show ? "" : "none";
+    }
+}
+function toggleThread( thread_id ) {
+    let rows = document.querySelectorAll(`#inbound_email_table tbody tr[data-thread="${thread_id}"]`);
+    for (let row of rows) {
+        if (row.querySelector("input[name='thread']") == null) {
+            let collapsed = row.getAttribute("data-collapsed") == "true";
+            row.setAttribute("data-collapsed", collapsed ? "false" : "true");
+        }
+    }
+    filterEmails(document.getElementById("email_filter").value);
+}
+function emailSpamFeedback( id, spam ) {
+    let url = `/aws/inbound-email/spam?id=${id}&spam=${spam}`;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+        listResource('inbound-email');
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+}
+function deleteOrphanedAttachments() {
+    let url = "/aws/inbound-email/attachments";
     let xmlhttp = new XMLHttpRequest();
     xmlhttp.onload = function f() {
         document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
         document.getElementById("garminconnectoutput").innerHTML = "done";
-        listResource('inbound-email');
+    }
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function sesIdentities() {
+    let url = "/aws/ses/identities";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function sesVerifyIdentity() {
+    let identity = document.getElementById( 'ses_identity' ).value;
+    let url = "/aws/ses/identities?identity=" + encodeURIComponent(identity);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("sub_article").innerHTML = "<pre>" + xmlhttp.responseText + "</pre>";
+        sesIdentities();
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function sesCreateReceiptRule() {
+    let url = "/aws/ses/receipt_rule";
+    let data = JSON.stringify({
+        'rule_set': document.getElementById( 'ses_rule_set' ).value,
+        'rule_name': document.getElementById( 'ses_rule_name' ).value,
+        'recipients': document.getElementById( 'ses_recipients' ).value,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+        sesIdentities();
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+}
+function sesDeleteReceiptRule( rule_set, rule_name ) {
+    let url = "/aws/ses/receipt_rule?rule_set=" + encodeURIComponent(rule_set) + "&rule_name=" + encodeURIComponent(rule_name);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        sesIdentities();
+    }
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.send(null);
+}
+function sesActivateRuleSet( rule_set ) {
+    let url = "/aws/ses/receipt_rule_set/activate?rule_set=" + encodeURIComponent(rule_set);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        sesIdentities();
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+}
+function createHealthCheck() {
+    let url = "/aws/route53/health_check";
+    let port = document.getElementById( 'health_check_port' ).value;
+    let data = JSON.stringify({
+        'check_type': document.getElementById( 'health_check_type' ).value,
+        'host': document.getElementById( 'health_check_host' ).value,
+        'port': port ? parseInt(port) : null,
+        'resource_path': document.getElementById( 'health_check_path' ).value,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+        listResource('route53');
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+}
+function deleteHealthCheck( id ) {
+    let url = "/aws/route53/health_check?id=" + encodeURIComponent(id);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        listResource('route53');
+    }
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.send(null);
+}
+function createRoutingRecord() {
+    let url = "/aws/route53/record";
+    let weight = document.getElementById( 'routing_weight' ).value;
+    let data = JSON.stringify({
+        'zone': document.getElementById( 'routing_zone' ).value,
+        'dns_name': document.getElementById( 'routing_name' ).value,
+        'ip': document.getElementById( 'routing_ip' ).value,
+        'set_identifier': document.getElementById( 'routing_set_id' ).value,
+        'policy': document.getElementById( 'routing_policy' ).value,
+        'weight': weight ? parseInt(weight) : null,
+        'health_check_id': document.getElementById( 'routing_health_check' ).value,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+        listResource('route53');
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+}
+function emailRules() {
+    let url = "/aws/email_rules";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function saveEmailRule() {
+    let url = "/aws/email_rules";
+    let data = JSON.stringify({
+        'name': document.getElementById( 'rule_name' ).value,
+        'recipient_pattern': document.getElementById( 'rule_recipient' ).value,
+        'subject_pattern': document.getElementById( 'rule_subject' ).value,
+        'forward_to': document.getElementById( 'rule_forward_to' ).value,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+        emailRules();
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+}
+function toggleEmailRule( id, enabled ) {
+    let url = "/aws/email_rules";
+    let data = JSON.stringify({'id': id, 'enabled': enabled});
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+        emailRules();
     }
     xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+}
+function deleteEmailRule( id ) {
+    let url = "/aws/email_rules?id=" + id;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        emailRules();
+    }
+    xmlhttp.open("DELETE", url, true);
     xmlhttp.send(null);
-}
\ No newline at end of file
+}
+function testEmailRules() {
+    let url = "/aws/email_rules/test";
+    let data = JSON.stringify({
+        'to_address': document.getElementById( 'test_to_address' ).value,
+        'subject': document.getElementById( 'test_subject' ).value,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("sub_article").innerHTML = "<pre>" + xmlhttp.responseText + "</pre>";
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+}
+function webhooks() {
+    let url = "/aws/webhooks";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function createWebhook() {
+    let url = "/aws/webhooks";
+    let events = Array.from(document.getElementsByClassName("webhook-event"))
+        .filter(e => e.checked)
+        .map(e => e.value);
+    let data = JSON.stringify({
+        'url': document.getElementById( 'webhook_url' ).value,
+        'events': events,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status >= 400) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        let created = JSON.parse(xmlhttp.responseText);
+        webhooks();
+        document.getElementById("sub_article").innerHTML =
+            "<pre>Signing secret for " + created.url + " (shown once): " + created.secret + "</pre>";
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+}
+function deleteWebhook( id ) {
+    if (!confirm("Delete webhook and its delivery log?")) {
+        return;
+    }
+    let url = "/aws/webhooks?id=" + id;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        webhooks();
+    }
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.send(null);
+}
+function priceAlerts() {
+    let url = "/aws/price_alerts";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function createPriceAlert() {
+    let url = "/aws/price_alerts";
+    let data = JSON.stringify({
+        'instance_type': document.getElementById( 'price_alert_instance_type' ).value,
+        'region': document.getElementById( 'price_alert_region' ).value,
+        'threshold': parseFloat(document.getElementById( 'price_alert_threshold' ).value),
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status >= 400) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        priceAlerts();
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+}
+function updatePriceAlert( id, enabled ) {
+    let url = "/aws/price_alerts?id=" + id + "&enabled=" + enabled;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        priceAlerts();
+    }
+    xmlhttp.open("PATCH", url, true);
+    xmlhttp.send(null);
+}
+function deletePriceAlert( id ) {
+    if (!confirm("Delete price alert?")) {
+        return;
+    }
+    let url = "/aws/price_alerts?id=" + id;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        priceAlerts();
+    }
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.send(null);
+}
+function drPolicies() {
+    let url = "/aws/dr";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function terraformDrift() {
+    let path = document.getElementById( 'terraform_state_path' ).value;
+    let url = "/aws/terraform_drift?path=" + encodeURIComponent(path);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function inventoryChanges() {
+    let since = document.getElementById( 'inventory_since' ).value;
+    let url = "/aws/changes?since=" + encodeURIComponent(since);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function replacements() {
+    let url = "/aws/replacements";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function startReplacement() {
+    let url = "/aws/replacements";
+    let data = JSON.stringify({
+        'instance_id': document.getElementById( 'replacement_instance' ).value,
+        'ami': document.getElementById( 'replacement_ami' ).value,
+        'dns_name': document.getElementById( 'replacement_dns_name' ).value,
+        'soak_minutes': parseInt(document.getElementById( 'replacement_soak_minutes' ).value),
+        'terminate_old': document.getElementById( 'replacement_terminate_old' ).checked,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status >= 400) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function cancelReplacement( id ) {
+    if (!confirm("Cancel instance replacement?")) {
+        return;
+    }
+    let url = "/aws/replacements/cancel?id=" + id;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status >= 400) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        replacements();
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+}
+function tagSearch() {
+    let tag = document.getElementById( 'tag_query' ).value;
+    let url = "/aws/search?tag=" + encodeURIComponent(tag);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function createDrPolicy() {
+    let url = "/aws/dr";
+    let data = JSON.stringify({
+        'volume_name': document.getElementById( 'dr_volume_name' ).value,
+        'target_region': document.getElementById( 'dr_target_region' ).value,
+        'max_age_hours': parseInt(document.getElementById( 'dr_max_age_hours' ).value),
+        'kms_key_id': document.getElementById( 'dr_kms_key_id' ).value,
+        'encrypted': document.getElementById( 'dr_encrypted' ).checked,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status >= 400) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        drPolicies();
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+}
+function deleteDrPolicy( id ) {
+    if (!confirm("Delete disaster recovery policy?")) {
+        return;
+    }
+    let url = "/aws/dr?id=" + id;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        drPolicies();
+    }
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.send(null);
+}
+function copySnapshot(id) {
+    let region = document.getElementById(id + '_copy_region').value;
+    let url = "/aws/copy_snapshot?snapid=" + id + "&region=" + region;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status >= 400) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        listResource('snapshot');
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function decommissionInstance(instance) {
+    let checked = id => {
+        let box = document.getElementById(id);
+        return box !== null && box.checked;
+    };
+    let url = "/aws/decommission?instance=" + instance
+        + "&final_snapshot=" + checked("decommission_final_snapshot")
+        + "&cancel_spot_requests=" + checked("decommission_cancel_spot_requests")
+        + "&delete_dns_records=" + checked("decommission_delete_dns_records")
+        + "&delete_volumes=" + checked("decommission_delete_volumes");
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function syncEmail() {
+    let url = "/aws/inbound-email/sync";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        listResource('inbound-email');
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+}
+function peekSqsQueue( queue_url, has_redrive ) {
+    let url = "/aws/sqs_peek?queue_url=" + encodeURIComponent(queue_url);
+    if (has_redrive) {
+        if (!confirm("Peeking increments the receive count of each message, which can move them to the dead letter queue. Peek anyway?")) {
+            return;
+        }
+        url = url + "&force=true";
+    }
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function purgeSqsQueue( queue_url ) {
+    let url = "/aws/sqs_purge?queue_url=" + encodeURIComponent(queue_url);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        listResource('sqs');
+    }
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function deleteSqsQueue( queue_url ) {
+    let url = "/aws/sqs_delete?queue_url=" + encodeURIComponent(queue_url);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        listResource('sqs');
+    }
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function assignBackupVolumes( plan_id ) {
+    let volumes = document.getElementById("backup_volumes_" + plan_id).value;
+    let url = "/aws/backup_assign?plan_id=" + plan_id + "&volumes=" + volumes;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        listResource('backup');
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function invokeLambda( function_name ) {
+    let url = "/aws/lambda_invoke";
+    let payload = document.getElementById( 'lambda_payload_' + function_name ).value;
+    let data = JSON.stringify({
+        'function_name': function_name,
+        'payload': payload,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function costsByTag( tag ) {
+    let url = "/aws/costs_by_tag?tag=" + encodeURIComponent(tag);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function wasteReport() {
+    let url = "/aws/waste";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("sub_article").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function wasteCleanup( method, url ) {
+    if (!confirm("Clean up " + url + "?")) {
+        return;
+    }
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status >= 400) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        wasteReport();
+    }
+    xmlhttp.open(method, url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function switchAccount( account ) {
+    let url = "/aws/account";
+    let data = JSON.stringify({'account': account});
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        location.reload();
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function batchSelected( resource ) {
+    return Array.from(
+        document.querySelectorAll('input.batch-' + resource + ':checked')
+    ).map(e => e.value);
+}
+function batchToggle( resource, checked ) {
+    document.querySelectorAll('input.batch-' + resource).forEach(e => { e.checked = checked; });
+}
+function batchAction( resource, action ) {
+    let ids = batchSelected(resource);
+    if (ids.length == 0) {
+        document.getElementById("garminconnectoutput").innerHTML = "nothing selected";
+        return;
+    }
+    let data = {'ids': ids};
+    if (action == 'tag') {
+        data['tag'] = document.getElementById('batch_tag_' + resource).value;
+    } else if (!confirm(action + " " + ids.join(", ") + "?")) {
+        return;
+    }
+    let url = "/aws/batch/" + action;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status != 200) {
+            document.getElementById("garminconnectoutput").innerHTML = "failed";
+            document.getElementById("sub_article").textContent = xmlhttp.responseText;
+            return;
+        }
+        let results = JSON.parse(xmlhttp.responseText);
+        let failed = results.filter(r => !r.success);
+        document.getElementById("garminconnectoutput").innerHTML =
+            (results.length - failed.length) + " succeeded, " + failed.length + " failed";
+        if (failed.length == 0) {
+            listResource(resource);
+            return;
+        }
+        let sub_article = document.getElementById("sub_article");
+        sub_article.textContent = "";
+        failed.forEach(r => {
+            let line = document.createElement("div");
+            line.textContent = r.id + ": " + r.message;
+            sub_article.appendChild(line);
+        });
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(JSON.stringify(data));
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function toggleTheme() {
+    let body = document.body;
+    let theme = body.classList.contains("theme-dark") ? "light" : "dark";
+    let url = "/aws/theme";
+    let data = JSON.stringify({'theme': theme});
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status >= 400) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        body.classList.toggle("theme-dark", theme == "dark");
+        body.classList.toggle("theme-light", theme == "light");
+        document.getElementById("theme_toggle").value = theme == "dark" ? "Light" : "Dark";
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function savePreferences() {
+    let hidden = {};
+    document.querySelectorAll(".pref-column").forEach(function (input) {
+        let resource = input.dataset.resource;
+        if (!(resource in hidden)) {
+            hidden[resource] = [];
+        }
+        if (!input.checked) {
+            hidden[resource].push(input.dataset.column);
+        }
+    });
+    let rows = document.getElementById("pref_rows").value;
+    let data = JSON.stringify({
+        'hidden_columns': hidden,
+        'default_sort': document.getElementById("pref_sort").value,
+        'sort_descending': document.getElementById("pref_descending").checked,
+        'rows_per_page': rows ? parseInt(rows) : null,
+        'default_region': document.getElementById("pref_region").value,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.open("POST", "/aws/preferences", true);
+    xmlhttp.setRequestHeader('Content-Type', 'application/json');
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function showTablePage( container, page ) {
+    let rows = parseInt(container.dataset.rowsPerPage);
+    let body = container.querySelector("tbody");
+    if (!rows || !body) {
+        return;
+    }
+    let trs = Array.from(body.children);
+    let pages = Math.max(1, Math.ceil(trs.length / rows));
+    page = Math.min(Math.max(page, 0), pages - 1);
+    container.dataset.page = page;
+    trs.forEach(function (tr, idx) {
+        tr.style.display = Math.floor(idx / rows) == page ? "" : "none";
+    });
+    let pager = container.querySelector(".table-pager");
+    if (pages <= 1) {
+        if (pager) {
+            pager.remove();
+        }
+        return;
+    }
+    if (!pager) {
+        pager = document.createElement("div");
+        pager.className = "table-pager";
+        container.appendChild(pager);
+    }
+    pager.textContent = "";
+    let prev = document.createElement("input");
+    prev.type = "button";
+    prev.value = "Prev";
+    prev.disabled = page == 0;
+    prev.onclick = function () { showTablePage(container, page - 1); };
+    let next = document.createElement("input");
+    next.type = "button";
+    next.value = "Next";
+    next.disabled = page == pages - 1;
+    next.onclick = function () { showTablePage(container, page + 1); };
+    let label = document.createElement("span");
+    label.textContent = " page " + (page + 1) + " of " + pages + " ";
+    pager.append(prev, label, next);
+}
+function paginateTables() {
+    document.querySelectorAll(".table-preferences").forEach(function (container) {
+        if (container.dataset.rowsPerPage && container.dataset.page === undefined) {
+            showTablePage(container, 0);
+        }
+    });
+}
+new MutationObserver(paginateTables).observe(document.body, {childList: true, subtree: true});
+paginateTables();
+function toggleColumns() {
+    document.body.classList.toggle("show-all-columns");
+}
+const graphColumns = ["dns_record", "ip", "spot_request", "instance", "volume", "snapshot", "ami"];
+function resourceGraph( focus ) {
+    let url = focus ? "/aws/graph?focus=" + encodeURIComponent(focus) : "/aws/graph";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        if (xmlhttp.status >= 400) {
+            document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+            return;
+        }
+        let graph = JSON.parse(xmlhttp.responseText);
+        document.getElementById("main_article").innerHTML = renderGraph(graph, focus);
+        if (focus) {
+            let impacted = graph.impacted.map(n => n.kind + " " + n.label).join("<br>");
+            document.getElementById("sub_article").innerHTML = "<b>Deleting " + focus
+                + " breaks:</b><br>" + (impacted || "nothing");
+        } else {
+            document.getElementById("sub_article").innerHTML = "&nbsp;";
+        }
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}
+function renderGraph( graph, focus ) {
+    let width = 200;
+    let height = 40;
+    let rows = {};
+    let positions = {};
+    graph.nodes.forEach(function place( node ) {
+        let column = graphColumns.indexOf(node.kind);
+        let row = rows[column] || 0;
+        rows[column] = row + 1;
+        positions[node.id] = {x: 20 + column * width, y: 40 + row * height};
+    });
+    let maxRows = Math.max(1, ...Object.values(rows));
+    let impacted = new Set((graph.impacted || []).map(n => n.id));
+    let escape = s => s.replace(/&/g, "&amp;").replace(/</g, "&lt;").replace(/"/g, "&quot;");
+    let svg = '<a href="/aws/graph?format=dot" target="_blank">DOT</a><br>'
+        + '<svg width="' + (graphColumns.length * width + 40) + '" height="' + (maxRows * height + 60) + '">';
+    graphColumns.forEach(function header( kind, column ) {
+        svg += '<text x="' + (20 + column * width) + '" y="20" font-weight="bold">' + kind + '</text>';
+    });
+    graph.edges.forEach(function edge( e ) {
+        let from = positions[e.from];
+        let to = positions[e.to];
+        let dash = e.dependency ? "" : ' stroke-dasharray="4"';
+        svg += '<line x1="' + (from.x + 150) + '" y1="' + (from.y + 10) + '" x2="' + to.x + '" y2="'
+            + (to.y + 10) + '" stroke="gray"' + dash + '><title>' + e.relation + '</title></line>';
+    });
+    graph.nodes.forEach(function node( n ) {
+        let p = positions[n.id];
+        let color = n.id == focus ? "blue" : impacted.has(n.id) ? "red" : "black";
+        svg += '<g style="cursor: pointer;" onclick="resourceGraph(\'' + escape(n.id) + '\')">'
+            + '<rect x="' + p.x + '" y="' + p.y + '" width="150" height="20" fill="white" stroke="'
+            + color + '"></rect><text x="' + (p.x + 4) + '" y="' + (p.y + 15) + '" font-size="11" fill="'
+            + color + '"><title>' + escape(n.id) + '</title>' + escape(n.label.slice(0, 24)) + '</text></g>';
+    });
+    return svg + '</svg>';
+}
+function launchStatus() {
+    let url = "/aws/launch_status";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function f() {
+        document.getElementById("main_article").innerHTML = xmlhttp.responseText;
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        let status = document.getElementById("launch_status");
+        if (status && status.dataset.pending === "true") {
+            setTimeout(function refresh() {
+                if (document.getElementById("launch_status")) {
+                    launchStatus();
+                }
+            }, 15000);
+        }
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "running";
+}